@@ -3,7 +3,7 @@ use rdkafka::{
     producer::{FutureProducer, FutureRecord},
     util::Timeout,
 };
-use svix_bridge_types::{async_trait, BoxError, ForwardRequest, ReceiverOutput};
+use svix_bridge_types::{async_trait, BoxError, DeliveryMode, ForwardRequest, ReceiverOutput};
 
 use crate::config::KafkaOutputOpts;
 
@@ -46,4 +46,9 @@ impl ReceiverOutput for KafkaProducer {
 
         Ok(())
     }
+
+    fn delivery_mode(&self) -> DeliveryMode {
+        // Whether the record is replicated by the time it's acknowledged is up to the brokers
+        DeliveryMode::Asynchronous
+    }
 }
@@ -6,7 +6,7 @@ use rdkafka::{
 };
 use serde_json::json;
 use svix_bridge_plugin_kafka::{KafkaOutputOpts, KafkaProducer};
-use svix_bridge_types::{ForwardRequest, ReceiverOutput as _};
+use svix_bridge_types::{DeliveryMode, ForwardRequest, ReceiverOutput as _};
 
 use crate::{create_topic, delete_topic, kafka_admin_client, BROKER_HOST};
 
@@ -49,7 +49,15 @@ async fn test_produce_ok() {
         },
     )
     .unwrap();
-    producer.handle(ForwardRequest { payload }).await.unwrap();
+    producer
+        .handle(ForwardRequest {
+            payload,
+            method: None,
+            path: None,
+            signature_headers: None,
+        })
+        .await
+        .unwrap();
 
     // Assert that the message is received
     let msg = recv_join_hdl.await.unwrap();
@@ -62,3 +70,19 @@ async fn test_produce_ok() {
 
     delete_topic(&admin_client, topic).await;
 }
+
+#[test]
+fn test_delivery_mode() {
+    // Creating the producer doesn't connect to the broker
+    let producer = KafkaProducer::new(
+        "test".into(),
+        KafkaOutputOpts::Inner {
+            bootstrap_brokers: BROKER_HOST.to_owned(),
+            topic: "test".to_owned(),
+            security_protocol: svix_bridge_plugin_kafka::KafkaSecurityProtocol::Plaintext,
+            debug_contexts: None,
+        },
+    )
+    .unwrap();
+    assert_eq!(producer.delivery_mode(), DeliveryMode::Asynchronous);
+}
@@ -0,0 +1,13 @@
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("postgres error")]
+    Postgres(#[from] sqlx::Error),
+
+    #[error("invalid identifier `{0}`")]
+    InvalidIdentifier(String),
+
+    #[error("column mapping is empty")]
+    EmptyColumnMapping,
+}
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
@@ -0,0 +1,166 @@
+use std::{fmt, str::FromStr};
+
+use serde::Deserialize;
+use serde_json::Value;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Segment {
+    Key(String),
+    Index(usize),
+}
+
+/// A path into a JSON document, selecting a single value.
+///
+/// Only a small subset of JSONPath is supported: the path starts at the root (`$`), followed by
+/// any number of object keys (`.key` or `['key']`) and array indices (`[0]`).
+///
+/// Example: `$.data.customer['first name']`, `$.items[0].id`
+#[derive(Clone, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct JsonPath {
+    src: String,
+    segments: Vec<Segment>,
+}
+
+impl JsonPath {
+    /// Looks up the value at this path, if there is one.
+    pub fn evaluate<'a>(&self, value: &'a Value) -> Option<&'a Value> {
+        self.segments
+            .iter()
+            .try_fold(value, |value, segment| match segment {
+                Segment::Key(key) => value.as_object()?.get(key),
+                Segment::Index(idx) => value.as_array()?.get(*idx),
+            })
+    }
+}
+
+impl fmt::Debug for JsonPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("JsonPath").field(&self.src).finish()
+    }
+}
+
+impl fmt::Display for JsonPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.src)
+    }
+}
+
+impl FromStr for JsonPath {
+    type Err = String;
+
+    fn from_str(src: &str) -> Result<Self, Self::Err> {
+        let err = |reason: &str| format!("invalid JSON path `{src}`: {reason}");
+
+        let mut rest = src
+            .strip_prefix('$')
+            .ok_or_else(|| err("must start with `$`"))?;
+        let mut segments = Vec::new();
+        while !rest.is_empty() {
+            if let Some(after_dot) = rest.strip_prefix('.') {
+                let end = after_dot.find(['.', '[']).unwrap_or(after_dot.len());
+                let (key, after_key) = after_dot.split_at(end);
+                if key.is_empty() {
+                    return Err(err("empty key"));
+                }
+                segments.push(Segment::Key(key.to_owned()));
+                rest = after_key;
+            } else if let Some(after_bracket) = rest.strip_prefix('[') {
+                let end = after_bracket.find(']').ok_or_else(|| err("unclosed `[`"))?;
+                let (inner, after_inner) = after_bracket.split_at(end);
+                let quoted = inner
+                    .strip_prefix('\'')
+                    .and_then(|s| s.strip_suffix('\''))
+                    .or_else(|| inner.strip_prefix('"').and_then(|s| s.strip_suffix('"')));
+                let segment = match quoted {
+                    Some(key) => Segment::Key(key.to_owned()),
+                    None => Segment::Index(
+                        inner
+                            .parse()
+                            .map_err(|_| err("brackets must hold a quoted key or an index"))?,
+                    ),
+                };
+                segments.push(segment);
+                rest = &after_inner[1..];
+            } else {
+                return Err(err("expected `.` or `[`"));
+            }
+        }
+
+        Ok(Self {
+            src: src.to_owned(),
+            segments,
+        })
+    }
+}
+
+impl TryFrom<String> for JsonPath {
+    type Error = String;
+
+    fn try_from(src: String) -> Result<Self, Self::Error> {
+        src.parse()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::JsonPath;
+
+    fn path(src: &str) -> JsonPath {
+        src.parse().unwrap()
+    }
+
+    #[test]
+    fn test_evaluate() {
+        let value = json!({
+            "type": "order.created",
+            "data": {
+                "id": 123,
+                "first name": "Jane",
+                "items": [{"sku": "a"}, {"sku": "b"}],
+            },
+        });
+
+        assert_eq!(path("$").evaluate(&value), Some(&value));
+        assert_eq!(
+            path("$.type").evaluate(&value),
+            Some(&json!("order.created"))
+        );
+        assert_eq!(path("$.data.id").evaluate(&value), Some(&json!(123)));
+        assert_eq!(
+            path("$.data['first name']").evaluate(&value),
+            Some(&json!("Jane"))
+        );
+        assert_eq!(path("$[\"data\"].id").evaluate(&value), Some(&json!(123)));
+        assert_eq!(
+            path("$.data.items[1].sku").evaluate(&value),
+            Some(&json!("b"))
+        );
+        assert_eq!(
+            path("$.data.items").evaluate(&value),
+            Some(&json!([{"sku": "a"}, {"sku": "b"}]))
+        );
+
+        assert_eq!(path("$.missing").evaluate(&value), None);
+        assert_eq!(path("$.data.items[2]").evaluate(&value), None);
+        // Indexing into an object, or keying into an array, finds nothing
+        assert_eq!(path("$.data[0]").evaluate(&value), None);
+        assert_eq!(path("$.data.items.sku").evaluate(&value), None);
+    }
+
+    #[test]
+    fn test_parse_invalid() {
+        for src in ["", "data.id", "$.", "$..id", "$.data[", "$.data[x]", "$id"] {
+            assert!(src.parse::<JsonPath>().is_err(), "`{src}` should not parse");
+        }
+    }
+
+    #[test]
+    fn test_deserialize() {
+        let path: JsonPath = serde_json::from_value(json!("$.data.id")).unwrap();
+        assert_eq!(path.to_string(), "$.data.id");
+        assert!(serde_json::from_value::<JsonPath>(json!("data.id")).is_err());
+    }
+}
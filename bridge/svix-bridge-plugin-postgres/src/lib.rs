@@ -0,0 +1,11 @@
+mod config;
+mod error;
+mod json_path;
+mod output;
+
+pub use self::{
+    config::{into_receiver_output, PostgresOutputOpts},
+    error::{Error, Result},
+    json_path::JsonPath,
+    output::PostgresOutput,
+};
@@ -0,0 +1,148 @@
+use serde_json::{Map, Value};
+use sqlx::{postgres::PgPoolOptions, types::Json, PgPool};
+use svix_bridge_types::{async_trait, BoxError, ForwardRequest, ReceiverOutput};
+
+use crate::{config::PostgresOutputOpts, Error, JsonPath, Result};
+
+/// Writes webhook payloads as rows of a postgres table.
+pub struct PostgresOutput {
+    name: String,
+    /// Each column, with the path of the value written to it. Sorted by column.
+    columns: Vec<(String, JsonPath)>,
+    query: String,
+    pool: PgPool,
+}
+
+impl PostgresOutput {
+    pub fn new(name: String, opts: PostgresOutputOpts) -> Result<Self> {
+        let PostgresOutputOpts::Inner {
+            dsn,
+            table,
+            column_mapping,
+            max_connections,
+        } = opts;
+
+        let mut columns: Vec<_> = column_mapping.into_iter().collect();
+        columns.sort_by(|(a, _), (b, _)| a.cmp(b));
+        let query = insert_query(&table, columns.iter().map(|(column, _)| column.as_str()))?;
+
+        // Connections are only opened once there's something to insert
+        let pool = PgPoolOptions::new()
+            .max_connections(max_connections)
+            .connect_lazy(&dsn)?;
+
+        Ok(Self {
+            name,
+            columns,
+            query,
+            pool,
+        })
+    }
+
+    /// Picks the value for each column out of the payload, keyed by column.
+    fn row(&self, payload: &Value) -> Map<String, Value> {
+        self.columns
+            .iter()
+            .map(|(column, path)| {
+                let value = path.evaluate(payload).cloned().unwrap_or(Value::Null);
+                (column.clone(), value)
+            })
+            .collect()
+    }
+}
+
+#[async_trait]
+impl ReceiverOutput for PostgresOutput {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    async fn handle(&self, request: ForwardRequest) -> Result<(), BoxError> {
+        let row = self.row(&request.payload);
+        sqlx::query(&self.query)
+            .bind(Json(row))
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+}
+
+/// Builds the statement inserting a single row, given as a JSON object bound to `$1`.
+///
+/// Rather than binding each value separately, `jsonb_populate_record` converts every value to the
+/// type of its column, so that the same payload field can be written to a `text`, `integer` or
+/// `jsonb` column alike.
+fn insert_query<'a>(table: &str, columns: impl Iterator<Item = &'a str>) -> Result<String> {
+    let table = table
+        .split('.')
+        .map(quote_identifier)
+        .collect::<Result<Vec<_>>>()?;
+    if table.len() > 2 {
+        return Err(Error::InvalidIdentifier(table.join(".")));
+    }
+    let table = table.join(".");
+
+    let columns = columns
+        .map(quote_identifier)
+        .collect::<Result<Vec<_>>>()?
+        .join(", ");
+    if columns.is_empty() {
+        return Err(Error::EmptyColumnMapping);
+    }
+
+    Ok(format!(
+        "INSERT INTO {table} ({columns}) \
+         SELECT {columns} FROM jsonb_populate_record(NULL::{table}, $1)"
+    ))
+}
+
+/// Quotes an identifier so it can be used in a statement as-is, whatever characters it holds.
+fn quote_identifier(ident: &str) -> Result<String> {
+    if ident.is_empty() || ident.contains('\0') {
+        return Err(Error::InvalidIdentifier(ident.to_owned()));
+    }
+    Ok(format!("\"{}\"", ident.replace('"', "\"\"")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{insert_query, quote_identifier};
+    use crate::Error;
+
+    #[test]
+    fn test_quote_identifier() {
+        assert_eq!(quote_identifier("events").unwrap(), r#""events""#);
+        assert_eq!(quote_identifier("Event Type").unwrap(), r#""Event Type""#);
+        assert_eq!(
+            quote_identifier(r#"a"; DROP TABLE x; --"#).unwrap(),
+            r#""a""; DROP TABLE x; --""#
+        );
+        assert!(matches!(
+            quote_identifier(""),
+            Err(Error::InvalidIdentifier(_))
+        ));
+    }
+
+    #[test]
+    fn test_insert_query() {
+        assert_eq!(
+            insert_query("events", ["event_type", "id"].into_iter()).unwrap(),
+            r#"INSERT INTO "events" ("event_type", "id") SELECT "event_type", "id" FROM jsonb_populate_record(NULL::"events", $1)"#
+        );
+        assert_eq!(
+            insert_query("webhooks.events", ["id"].into_iter()).unwrap(),
+            r#"INSERT INTO "webhooks"."events" ("id") SELECT "id" FROM jsonb_populate_record(NULL::"webhooks"."events", $1)"#
+        );
+
+        assert!(matches!(
+            insert_query("events", [].into_iter()),
+            Err(Error::EmptyColumnMapping)
+        ));
+        for table in ["", "a..b", "a.b.c"] {
+            assert!(matches!(
+                insert_query(table, ["id"].into_iter()),
+                Err(Error::InvalidIdentifier(_))
+            ));
+        }
+    }
+}
@@ -0,0 +1,156 @@
+use std::collections::HashMap;
+
+use serde_json::{json, Value};
+use sqlx::{types::Json, PgPool, Row as _};
+use svix_bridge_plugin_postgres::{PostgresOutput, PostgresOutputOpts};
+use svix_bridge_types::{ForwardRequest, ReceiverOutput as _};
+
+use crate::{pg_pool, DSN};
+
+async fn create_table(pool: &PgPool, table: &str) {
+    sqlx::query(&format!(r#"DROP TABLE IF EXISTS "{table}""#))
+        .execute(pool)
+        .await
+        .unwrap();
+    sqlx::query(&format!(
+        r#"CREATE TABLE "{table}" (
+            id SERIAL PRIMARY KEY,
+            event_type TEXT NOT NULL,
+            "Amount" INTEGER,
+            data JSONB,
+            received_at TIMESTAMPTZ NOT NULL DEFAULT now()
+        )"#
+    ))
+    .execute(pool)
+    .await
+    .unwrap();
+}
+
+async fn drop_table(pool: &PgPool, table: &str) {
+    sqlx::query(&format!(r#"DROP TABLE "{table}""#))
+        .execute(pool)
+        .await
+        .unwrap();
+}
+
+fn output(table: &str, column_mapping: &[(&str, &str)]) -> PostgresOutput {
+    PostgresOutput::new(
+        "test".into(),
+        PostgresOutputOpts::Inner {
+            dsn: DSN.to_owned(),
+            table: table.to_owned(),
+            column_mapping: column_mapping
+                .iter()
+                .map(|(column, path)| (column.to_string(), path.parse().unwrap()))
+                .collect::<HashMap<_, _>>(),
+            max_connections: 1,
+        },
+    )
+    .unwrap()
+}
+
+fn request(payload: Value) -> ForwardRequest {
+    ForwardRequest {
+        payload,
+        method: None,
+        path: None,
+        signature_headers: None,
+    }
+}
+
+#[tokio::test]
+async fn test_insert_ok() {
+    let table = unique_table_name!();
+    let pool = pg_pool().await;
+    create_table(&pool, table).await;
+
+    let output = output(
+        table,
+        &[
+            ("event_type", "$.type"),
+            ("Amount", "$.data.amount"),
+            ("data", "$.data"),
+        ],
+    );
+    output
+        .handle(request(json!({
+            "type": "invoice.paid",
+            "data": { "amount": 1200, "currency": "EUR" },
+        })))
+        .await
+        .unwrap();
+
+    let rows = sqlx::query(&format!(
+        r#"SELECT event_type, "Amount", data FROM "{table}""#
+    ))
+    .fetch_all(&pool)
+    .await
+    .unwrap();
+    assert_eq!(rows.len(), 1);
+    assert_eq!(rows[0].get::<String, _>("event_type"), "invoice.paid");
+    assert_eq!(rows[0].get::<Option<i32>, _>("Amount"), Some(1200));
+    assert_eq!(
+        rows[0].get::<Option<Json<Value>>, _>("data").unwrap().0,
+        json!({ "amount": 1200, "currency": "EUR" })
+    );
+
+    drop_table(&pool, table).await;
+}
+
+#[tokio::test]
+async fn test_insert_missing_values_are_null() {
+    let table = unique_table_name!();
+    let pool = pg_pool().await;
+    create_table(&pool, table).await;
+
+    let output = output(
+        table,
+        &[("event_type", "$.type"), ("Amount", "$.data.amount")],
+    );
+    output
+        .handle(request(json!({ "type": "invoice.voided" })))
+        .await
+        .unwrap();
+
+    let row = sqlx::query(&format!(r#"SELECT event_type, "Amount" FROM "{table}""#))
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert_eq!(row.get::<String, _>("event_type"), "invoice.voided");
+    assert_eq!(row.get::<Option<i32>, _>("Amount"), None);
+
+    // Missing values for a `NOT NULL` column fail the request
+    output
+        .handle(request(json!({ "data": { "amount": 1 } })))
+        .await
+        .unwrap_err();
+
+    drop_table(&pool, table).await;
+}
+
+#[tokio::test]
+async fn test_insert_mismatched_type_is_err() {
+    let table = unique_table_name!();
+    let pool = pg_pool().await;
+    create_table(&pool, table).await;
+
+    let output = output(
+        table,
+        &[("event_type", "$.type"), ("Amount", "$.data.amount")],
+    );
+    output
+        .handle(request(json!({
+            "type": "invoice.paid",
+            "data": { "amount": "a lot" },
+        })))
+        .await
+        .unwrap_err();
+
+    let count: i64 = sqlx::query_scalar(&format!(r#"SELECT count(*) FROM "{table}""#))
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert_eq!(count, 0);
+
+    drop_table(&pool, table).await;
+}
@@ -82,13 +82,15 @@ pub enum QueueOutputOpts {
 #[cfg(test)]
 mod tests {
     use svix_bridge_types::{
-        SenderOutputOpts, SvixSenderOutputOpts, TransformationConfig, TransformerInputFormat,
+        DeliveryMode, SenderOutputOpts, SvixSenderOutputOpts, TransformationConfig,
+        TransformerInputFormat,
     };
 
     use super::{into_receiver_output, into_sender_input};
     use crate::{
         config::{QueueInputOpts, QueueOutputOpts},
         redis::{RedisInputOpts, RedisOutputOpts},
+        sqs::SqsOutputOpts,
     };
 
     // FIXME: can't support raw payload access for redis because it requires JSON internally.
@@ -153,4 +155,29 @@ mod tests {
             crate::error::Error::Generic(msg) if msg == "redis only supports json formatted transformations"
         ));
     }
+
+    #[tokio::test]
+    async fn test_receiver_delivery_mode() {
+        let redis_out = QueueOutputOpts::Redis(RedisOutputOpts {
+            dsn: "".to_string(),
+            max_connections: 0,
+            queue_key: "".to_string(),
+            delayed_queue_key: None,
+            ack_deadline_ms: 2_000,
+        });
+        let sqs_out = QueueOutputOpts::Sqs(SqsOutputOpts {
+            queue_dsn: "".to_string(),
+            override_endpoint: false,
+        });
+
+        // The outputs connect lazily, so nothing needs to be listening
+        let output = into_receiver_output("".to_string(), redis_out, None)
+            .await
+            .unwrap();
+        assert_eq!(output.delivery_mode(), DeliveryMode::Synchronous);
+        let output = into_receiver_output("".to_string(), sqs_out, None)
+            .await
+            .unwrap();
+        assert_eq!(output.delivery_mode(), DeliveryMode::Asynchronous);
+    }
 }
@@ -1,7 +1,7 @@
 use std::sync::Arc;
 
 use omniqueue::{DynProducer, QueueError};
-use svix_bridge_types::{async_trait, BoxError, ForwardRequest, ReceiverOutput};
+use svix_bridge_types::{async_trait, BoxError, DeliveryMode, ForwardRequest, ReceiverOutput};
 use tokio::sync::Mutex;
 
 use crate::{config::QueueOutputOpts, error::Result};
@@ -87,4 +87,14 @@ impl ReceiverOutput for QueueForwarder {
 
         Ok(())
     }
+
+    fn delivery_mode(&self) -> DeliveryMode {
+        match self.opts {
+            // SQS only acknowledges that it has received the message
+            QueueOutputOpts::Sqs(_) => DeliveryMode::Asynchronous,
+            QueueOutputOpts::GcpPubSub(_)
+            | QueueOutputOpts::RabbitMQ(_)
+            | QueueOutputOpts::Redis(_) => DeliveryMode::Synchronous,
+        }
+    }
 }
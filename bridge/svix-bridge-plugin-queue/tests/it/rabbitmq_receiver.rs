@@ -90,6 +90,9 @@ async fn test_connection_recovery() {
 
     let req = ForwardRequest {
         payload: json!({"test": true}),
+        method: None,
+        path: None,
+        signature_headers: None,
     };
 
     assert!(
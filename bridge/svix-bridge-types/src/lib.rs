@@ -1,10 +1,11 @@
-use std::time::Duration;
+use std::{collections::HashMap, time::Duration};
 
 pub use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
 pub use svix;
 use svix::api::{MessageIn, PostOptions as PostOptions_, SvixOptions as _SvixOptions};
 use tokio::sync::{mpsc, oneshot};
+pub use tokio_util::sync::CancellationToken;
 
 #[derive(Deserialize, Default, Eq, PartialEq, Copy, Clone)]
 #[serde(rename_all = "lowercase")]
@@ -85,6 +86,9 @@ impl From<String> for TransformerInput {
 
 /// Plain old JSON objects are what the transformations expect to receive and produce.
 pub type JsObject = serde_json::Map<String, serde_json::Value>;
+/// Values describing where a payload came from, such as the `integration_id` of the receiver it
+/// was sent to. Scripts can read them from the `svix_context` global.
+pub type TransformerContext = HashMap<String, serde_json::Value>;
 /// A channel for plugins to send payloads/scripts to for execution.
 pub type TransformerTx = mpsc::UnboundedSender<TransformerJob>;
 /// The receiver side for transformations. The JS executor reads from this.
@@ -102,7 +106,18 @@ pub type TransformerCallbackRx = oneshot::Receiver<Result<TransformerOutput, ()>
 pub struct TransformerJob {
     pub callback_tx: TransformerCallbackTx,
     pub input: TransformerInput,
+    /// Passed to the script's `handler` as its second argument, for example the values captured
+    /// from the path of an inbound request. Empty unless the caller has something to add.
+    pub metadata: JsObject,
+    /// Exposed to the script as `svix_context`. Empty unless the caller sets it.
+    pub context: TransformerContext,
     pub script: String,
+    /// Signals the JS executor to abort the script.
+    ///
+    /// The executor also cancels this itself when the caller stops waiting on the callback channel
+    /// (for example when the caller's timeout elapses), so the worker thread isn't left spinning on
+    /// a script nobody wants the output of.
+    pub cancellation_token: CancellationToken,
 }
 
 #[derive(Debug)]
@@ -117,12 +132,24 @@ pub enum TransformerOutput {
 
 impl TransformerJob {
     pub fn new(script: String, input: TransformerInput) -> (Self, TransformerCallbackRx) {
+        Self::with_metadata(script, input, JsObject::new())
+    }
+
+    /// Like [`TransformerJob::new`], but with `metadata` handed to the script alongside the input.
+    pub fn with_metadata(
+        script: String,
+        input: TransformerInput,
+        metadata: JsObject,
+    ) -> (Self, TransformerCallbackRx) {
         let (callback_tx, callback_rx) = oneshot::channel();
         (
             Self {
                 input,
+                metadata,
+                context: TransformerContext::new(),
                 script,
                 callback_tx,
+                cancellation_token: CancellationToken::new(),
             },
             callback_rx,
         )
@@ -151,6 +178,18 @@ pub trait PollerInput: Send {
 
 pub type BoxError = Box<dyn std::error::Error + Send + Sync>;
 
+/// Whether a [`ReceiverOutput`] has durably stored a payload by the time it's done handling it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DeliveryMode {
+    /// The payload has been delivered once it's handled, so the webhook is answered with a
+    /// `204 No Content`.
+    #[default]
+    Synchronous,
+    /// The payload has only been accepted for delivery once it's handled, so the webhook is
+    /// answered with a `202 Accepted`.
+    Asynchronous,
+}
+
 /// Represents something we can hand a webhook payload to.
 /// Aka a "forwarder."
 ///
@@ -159,6 +198,9 @@ pub type BoxError = Box<dyn std::error::Error + Send + Sync>;
 pub trait ReceiverOutput: Send + Sync {
     fn name(&self) -> &str;
     async fn handle(&self, request: ForwardRequest) -> Result<(), BoxError>;
+    fn delivery_mode(&self) -> DeliveryMode {
+        DeliveryMode::Synchronous
+    }
 }
 
 #[derive(Deserialize, Debug, Clone, Default)]
@@ -271,4 +313,16 @@ pub struct ForwardRequest {
     // be represented in json.
     // FIXME: can we leverage RawValue here?
     pub payload: serde_json::Value,
+    /// The HTTP method of the inbound webhook, when the receiver is configured with
+    /// `forward_method`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub method: Option<String>,
+    /// The path of the inbound webhook, when the receiver is configured with `forward_method`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    /// The inbound webhook's original signature headers (`svix-id`, `svix-timestamp` and
+    /// `svix-signature`, or their `webhook-` equivalents), when the receiver is configured with
+    /// `forward_signature`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signature_headers: Option<HashMap<String, String>>,
 }
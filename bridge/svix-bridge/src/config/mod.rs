@@ -6,13 +6,19 @@ use std::{
     io::{Error, ErrorKind},
     net::SocketAddr,
     num::NonZeroUsize,
+    path::PathBuf,
+    time::Duration,
 };
 
 use anyhow::anyhow;
+use chrono::{DateTime, Utc};
+use regex::Regex;
 use serde::{de, Deserialize, Deserializer};
 use shellexpand::LookupError;
 #[cfg(feature = "kafka")]
 use svix_bridge_plugin_kafka::{KafkaInputOpts, KafkaOutputOpts};
+#[cfg(feature = "postgres")]
+use svix_bridge_plugin_postgres::PostgresOutputOpts;
 use svix_bridge_plugin_queue::config::{QueueInputOpts, QueueOutputOpts};
 use svix_bridge_types::{
     svix::api::Svix, ReceiverInputOpts, ReceiverOutput, SenderInput, SenderOutputOpts, SvixOptions,
@@ -20,6 +26,11 @@ use svix_bridge_types::{
 };
 use tracing::Level;
 
+use crate::{
+    delayed_output::DelayedOutput, fanout_output::FanoutOutput,
+    unix_socket_output::UnixSocketOutput,
+};
+
 #[derive(Deserialize)]
 #[serde(untagged)]
 pub enum EitherReceiver {
@@ -49,6 +60,17 @@ pub struct Config {
     pub http_listen_address: SocketAddr,
     #[serde(default = "default_transformation_worker_count")]
     pub transformation_worker_count: NonZeroUsize,
+    /// The largest request body, in bytes, that webhook receivers will accept. Larger requests
+    /// are rejected with a 413 before they are read into memory.
+    #[serde(default = "default_receiver_max_payload_bytes")]
+    pub receiver_max_payload_bytes: usize,
+    /// The largest object, in bytes once serialized as JSON, that a transformation may produce.
+    /// Larger outputs are rejected, with a 413 for webhook receivers. Must be at least 1KiB.
+    #[serde(default = "default_transformation_max_output_bytes")]
+    pub transformation_max_output_bytes: usize,
+    /// Serve webhook receivers over HTTPS rather than plain HTTP.
+    #[serde(default)]
+    pub tls: Option<TlsConfig>,
 }
 
 impl Config {
@@ -74,6 +96,13 @@ impl Config {
         let cfg: Self = serde_yaml::from_str(&src)
             .map_err(|e| Error::new(ErrorKind::Other, format!("Failed to parse config: {e}")))?;
 
+        if cfg.transformation_max_output_bytes < 1024 {
+            return Err(Error::new(
+                ErrorKind::Other,
+                "transformation_max_output_bytes must be at least 1024",
+            ));
+        }
+
         for sc in &cfg.senders {
             if let Some(tc) = sc.transformation() {
                 crate::runtime::validate_script(tc.source().as_str()).map_err(|e| {
@@ -88,6 +117,72 @@ impl Config {
             }
         }
 
+        for receiver in cfg.receivers.iter().filter_map(|either| match either {
+            EitherReceiver::Webhook(receiver) => Some(receiver),
+            EitherReceiver::Poller(_) => None,
+        }) {
+            let name = &receiver.name;
+            match &receiver.mode {
+                ReceiverMode::VerifyAndForward if receiver.output.is_none() => {
+                    return Err(Error::new(
+                        ErrorKind::Other,
+                        format!("receiver `{name}` requires an output"),
+                    ));
+                }
+                ReceiverMode::VerifyOnly { .. } if receiver.transformation.is_some() => {
+                    return Err(Error::new(
+                        ErrorKind::Other,
+                        format!("receiver `{name}` can't use a transformation in verify-only mode"),
+                    ));
+                }
+                ReceiverMode::VerifyOnly { upstream_url, .. } => {
+                    upstream_url.parse::<http::Uri>().map_err(|e| {
+                        Error::new(
+                            ErrorKind::Other,
+                            format!("invalid upstream_url for receiver `{name}`: {e}"),
+                        )
+                    })?;
+                }
+                ReceiverMode::VerifyAndForward => {}
+            }
+        }
+
+        for receiver in cfg.receivers.iter().filter_map(|either| match either {
+            EitherReceiver::Webhook(_) => None,
+            EitherReceiver::Poller(receiver) => Some(receiver),
+        }) {
+            let PollerInputOpts::SvixEvents {
+                max_messages_per_batch,
+                initial_since,
+                app_ids,
+                ..
+            } = &receiver.input;
+            if max_messages_per_batch.is_some_and(|max| !(1..=250).contains(&max)) {
+                return Err(Error::new(
+                    ErrorKind::Other,
+                    format!(
+                        "max_messages_per_batch for receiver `{}` must be between 1 and 250",
+                        receiver.name
+                    ),
+                ));
+            }
+            if initial_since.is_some_and(|since| since > Utc::now()) {
+                return Err(Error::new(
+                    ErrorKind::Other,
+                    format!(
+                        "initial_since for receiver `{}` must not be in the future",
+                        receiver.name
+                    ),
+                ));
+            }
+            if app_ids.as_ref().is_some_and(Vec::is_empty) {
+                return Err(Error::new(
+                    ErrorKind::Other,
+                    format!("app_ids for receiver `{}` must not be empty", receiver.name),
+                ));
+            }
+        }
+
         for (name, tc) in cfg.receivers.iter().filter_map(|either| match either {
             EitherReceiver::Webhook(receiver) => receiver
                 .transformation
@@ -118,6 +213,26 @@ fn default_transformation_worker_count() -> NonZeroUsize {
     NonZeroUsize::new(4).expect("4 is greater than 0")
 }
 
+fn default_receiver_max_payload_bytes() -> usize {
+    1024 * 1024
+}
+
+pub(crate) fn default_transformation_max_output_bytes() -> usize {
+    1024 * 1024
+}
+
+/// The certificate and private key webhook receivers are served with.
+///
+/// Both files are PEM encoded. They're watched for changes, so that a renewed certificate is used
+/// without restarting Bridge.
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct TlsConfig {
+    /// The certificate chain, starting with the server's own certificate
+    pub cert_path: PathBuf,
+    pub key_path: PathBuf,
+}
+
 #[derive(Deserialize)]
 pub struct OtelExporterConfig {
     /// The OpenTelemetry service name to use
@@ -129,9 +244,11 @@ pub struct OtelExporterConfig {
     pub sample_ratio: Option<f64>,
 }
 
-#[derive(Clone, Debug, Default, Deserialize)]
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
 #[serde(rename_all = "lowercase")]
 pub enum LogLevel {
+    Error,
+    Warn,
     #[default]
     Info,
     Debug,
@@ -141,6 +258,8 @@ pub enum LogLevel {
 impl fmt::Display for LogLevel {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
+            Self::Error => Level::ERROR,
+            Self::Warn => Level::WARN,
             Self::Info => Level::INFO,
             Self::Debug => Level::DEBUG,
             Self::Trace => Level::TRACE,
@@ -221,7 +340,138 @@ pub struct WebhookReceiverConfig {
     pub input: ReceiverInputOpts,
     #[serde(default)]
     pub transformation: Option<TransformationConfig>,
-    pub output: ReceiverOutputOpts,
+    /// When set, requests to any path under `/webhook/` matching this pattern in full, for example
+    /// `/webhook/orders/(?P<order_id>\d+)`, are routed to this receiver as well as those to its
+    /// `path_id`. The values of the pattern's named capture groups are handed to the transformation
+    /// as the second argument of its `handler`.
+    #[serde(default, deserialize_with = "deserialize_path_pattern")]
+    pub path_pattern: Option<Regex>,
+    /// Required, unless the `mode` is `verify-only`.
+    #[serde(default)]
+    pub output: Option<ReceiverOutputOpts>,
+    /// When set, the HTTP method and path of the inbound request are added to the
+    /// `ForwardRequest` handed to the output.
+    #[serde(default)]
+    pub forward_method: bool,
+    /// When set, the inbound request's original signature headers are added to the
+    /// `ForwardRequest` handed to the output, so that they can be verified again further down the
+    /// line, for example by another bridge instance.
+    #[serde(default)]
+    pub forward_signature: bool,
+    #[serde(default)]
+    pub mode: ReceiverMode,
+    /// When set, every inbound request is recorded in this audit log before anything else is
+    /// done with it.
+    #[serde(default)]
+    pub audit_log: Option<AuditLogConfig>,
+    /// What to respond with when the output fails to handle a request.
+    #[serde(default)]
+    pub on_error: ErrorPolicy,
+}
+
+fn deserialize_path_pattern<'de, D>(deserializer: D) -> Result<Option<Regex>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Option::<String>::deserialize(deserializer)?
+        .map(|pattern| {
+            // Anchored, so that the pattern has to match the whole path rather than only part of it
+            Regex::new(&format!("^(?:{pattern})$"))
+                .map_err(|e| de::Error::custom(format!("invalid path_pattern: {e}")))
+        })
+        .transpose()
+}
+
+/// An append-only file recording the inbound requests of a webhook receiver.
+///
+/// Request bodies are never written to it, only their SHA-256 hash.
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
+pub struct AuditLogConfig {
+    pub file_path: PathBuf,
+    #[serde(default)]
+    pub format: AuditLogFormat,
+    /// Once writing a record would take the file past this size, it's renamed with a timestamp
+    /// suffix and a new file is started. Rotated files are never deleted.
+    #[serde(default = "default_audit_log_max_file_bytes")]
+    pub max_file_bytes: u64,
+}
+
+fn default_audit_log_max_file_bytes() -> u64 {
+    100 * 1024 * 1024
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum AuditLogFormat {
+    /// One JSON object per line.
+    #[default]
+    JsonLines,
+    /// A header row followed by one row per request, with the headers as a JSON object.
+    Csv,
+}
+
+/// How a webhook receiver responds when its output fails to handle a request.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum ErrorPolicy {
+    /// Respond with a `500`, so that the sender can retry.
+    #[default]
+    Propagate,
+    /// Respond with a `200` as if the request had been handled, so that the sender doesn't retry.
+    /// The error is only logged, at `log_level`.
+    Absorb {
+        #[serde(default = "default_absorb_log_level")]
+        log_level: LogLevel,
+    },
+}
+
+fn default_absorb_log_level() -> LogLevel {
+    LogLevel::Error
+}
+
+/// What a webhook receiver does with requests once they've been verified.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum ReceiverMode {
+    /// Parse the request body as JSON, optionally run it through the transformation, then hand it
+    /// to the `output`.
+    #[default]
+    VerifyAndForward,
+    /// Proxy the raw request, with its original method, headers and body, to `upstream_url`.
+    /// Neither a transformation nor an output are used in this mode.
+    VerifyOnly {
+        upstream_url: String,
+        /// Credentials to send to the upstream, replacing the request's own `Authorization` header.
+        #[serde(default)]
+        auth: Option<OutputAuth>,
+    },
+}
+
+/// How Bridge authenticates itself to an HTTP service it forwards requests to.
+#[derive(Clone, Deserialize, PartialEq, Eq)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum OutputAuth {
+    /// Sent as `Authorization: Bearer <token>`.
+    Bearer { token: String },
+    /// Sent as `Authorization: Basic <base64 of username:password>`.
+    Basic { username: String, password: String },
+}
+
+// Not derived, to keep the secrets out of logs.
+impl fmt::Debug for OutputAuth {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Bearer { .. } => f
+                .debug_struct("Bearer")
+                .field("token", &"<redacted>")
+                .finish(),
+            Self::Basic { username, .. } => f
+                .debug_struct("Basic")
+                .field("username", username)
+                .field("password", &"<redacted>")
+                .finish(),
+        }
+    }
 }
 
 #[derive(Deserialize)]
@@ -229,23 +479,146 @@ pub struct WebhookReceiverConfig {
 pub enum ReceiverOutputOpts {
     #[cfg(feature = "kafka")]
     Kafka(KafkaOutputOpts),
+    #[cfg(feature = "postgres")]
+    Postgres(PostgresOutputOpts),
     Queue(QueueOutputOpts),
+    Delayed(DelayedOutputOpts),
+    UnixSocket(UnixSocketOutputOpts),
+    Fanout(FanoutOutputOpts),
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type")]
+pub enum FanoutOutputOpts {
+    // Single-variant enum so we can require the "type": "fanout" field in deserialization
+    #[serde(rename = "fanout")]
+    Inner {
+        /// The outputs each request is handed to, concurrently.
+        targets: Vec<ReceiverOutputOpts>,
+        /// How the results of the `targets` decide whether the request as a whole succeeded.
+        #[serde(default)]
+        strategy: FanoutStrategy,
+    },
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum FanoutStrategy {
+    /// Succeed only if every target succeeds.
+    #[default]
+    All,
+    /// Succeed if at least one target succeeds.
+    Any,
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type")]
+pub enum DelayedOutputOpts {
+    // Single-variant enum so we can require the "type": "delayed" field in deserialization
+    #[serde(rename = "delayed")]
+    Inner {
+        /// The output requests are handed to once the delay is up.
+        inner: Box<ReceiverOutputOpts>,
+        /// How long to wait before handing each request to the `inner` output, in milliseconds.
+        delay_ms: u64,
+    },
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type")]
+pub enum UnixSocketOutputOpts {
+    // Single-variant enum so we can require the "type": "unix-socket" field in deserialization
+    #[serde(rename = "unix-socket")]
+    Inner {
+        /// The path of the socket a local process is listening on.
+        socket_path: PathBuf,
+        /// How long to wait for each request to be sent and the connection closed by the other
+        /// side, in milliseconds.
+        #[serde(default = "default_unix_socket_timeout_ms")]
+        timeout_ms: u64,
+        /// How many more times to try connecting to the socket after a failed attempt, before
+        /// giving up on the request.
+        #[serde(default = "default_unix_socket_max_retries")]
+        max_retries: u32,
+    },
+}
+
+fn default_unix_socket_timeout_ms() -> u64 {
+    5000
+}
+
+fn default_unix_socket_max_retries() -> u32 {
+    3
+}
+
+impl ReceiverOutputOpts {
+    async fn into_receiver_output(
+        mut self,
+        name: String,
+        transformation: Option<&TransformationConfig>,
+    ) -> anyhow::Result<Box<dyn ReceiverOutput>> {
+        // Delayed outputs can be nested, so unwrap them down to the actual output first
+        let mut delays = Vec::new();
+        let output: Box<dyn ReceiverOutput> = loop {
+            match self {
+                ReceiverOutputOpts::Kafka(opts) => {
+                    break svix_bridge_plugin_kafka::into_receiver_output(name, opts)?;
+                }
+                #[cfg(feature = "postgres")]
+                ReceiverOutputOpts::Postgres(opts) => {
+                    break svix_bridge_plugin_postgres::into_receiver_output(name, opts)?;
+                }
+                ReceiverOutputOpts::Queue(x) => {
+                    break svix_bridge_plugin_queue::into_receiver_output(name, x, transformation)
+                        .await?;
+                }
+                ReceiverOutputOpts::UnixSocket(UnixSocketOutputOpts::Inner {
+                    socket_path,
+                    timeout_ms,
+                    max_retries,
+                }) => {
+                    break Box::new(UnixSocketOutput::new(
+                        name,
+                        socket_path,
+                        Duration::from_millis(timeout_ms),
+                        max_retries,
+                    ));
+                }
+                ReceiverOutputOpts::Fanout(FanoutOutputOpts::Inner { targets, strategy }) => {
+                    if targets.is_empty() {
+                        anyhow::bail!("fanout output for receiver `{name}` has no targets");
+                    }
+                    let mut outputs = Vec::with_capacity(targets.len());
+                    for target in targets {
+                        // Targets can be any output, including other fanouts
+                        outputs.push(
+                            Box::pin(target.into_receiver_output(name.clone(), transformation))
+                                .await?,
+                        );
+                    }
+                    break Box::new(FanoutOutput::new(name, outputs, strategy));
+                }
+                ReceiverOutputOpts::Delayed(DelayedOutputOpts::Inner { inner, delay_ms }) => {
+                    delays.push(Duration::from_millis(delay_ms));
+                    self = *inner;
+                }
+            }
+        };
+
+        Ok(delays.into_iter().rev().fold(output, |inner, delay| {
+            Box::new(DelayedOutput::new(inner, delay))
+        }))
+    }
 }
 
 impl WebhookReceiverConfig {
     pub async fn into_receiver_output(self) -> anyhow::Result<Box<dyn ReceiverOutput>> {
-        match self.output {
-            ReceiverOutputOpts::Kafka(opts) => {
-                svix_bridge_plugin_kafka::into_receiver_output(self.name, opts).map_err(Into::into)
-            }
-            ReceiverOutputOpts::Queue(x) => svix_bridge_plugin_queue::into_receiver_output(
-                self.name.clone(),
-                x,
-                self.transformation.as_ref(),
-            )
+        let Some(output) = self.output else {
+            anyhow::bail!("receiver `{}` has no output configured", self.name);
+        };
+        output
+            .into_receiver_output(self.name, self.transformation.as_ref())
             .await
-            .map_err(Into::into),
-        }
     }
 }
 
@@ -280,6 +653,29 @@ pub enum PollerInputOpts {
         subscription_token: MessageStreamBridgeConfig,
         #[serde(default)]
         svix_options: Option<SvixOptions>,
+        /// The most messages to fetch (and handle) per request, between 1 and 250. Smaller batches
+        /// let the output apply backpressure sooner when working through a large backlog. When
+        /// unset, the server's default page size is used.
+        #[serde(default)]
+        max_messages_per_batch: Option<usize>,
+        /// Where to start reading from when the poller has no iterator yet, as an RFC 3339
+        /// timestamp. Events created after this time are fetched, so a newly deployed bridge can
+        /// work through a backlog rather than starting from "now". Must not be in the future.
+        ///
+        /// This only applies to the first request: once an iterator has been received it takes
+        /// precedence, and `initial_since` is not consulted again.
+        #[serde(default)]
+        initial_since: Option<DateTime<Utc>>,
+        /// Only fetch events sent to these channels. This lets several pollers, each subscribed
+        /// to different channels, share one app. When unset, events from all channels are
+        /// fetched.
+        #[serde(default)]
+        channels: Option<Vec<String>>,
+        /// The apps to poll, each with its own iterator, when there's more than the one the
+        /// subscription token is for. The token has to have access to all of them. Each app is
+        /// polled independently, so one which is failing doesn't hold up the others.
+        #[serde(default)]
+        app_ids: Option<Vec<String>>,
     },
 }
 
@@ -296,6 +692,19 @@ impl PollerInputOpts {
             )),
         }
     }
+
+    /// The apps to poll: those in `app_ids` if set, otherwise the subscription token's.
+    pub fn app_ids(&self) -> Vec<&str> {
+        match self {
+            PollerInputOpts::SvixEvents {
+                app_ids: Some(app_ids),
+                ..
+            } => app_ids.iter().map(String::as_str).collect(),
+            PollerInputOpts::SvixEvents {
+                subscription_token, ..
+            } => vec![subscription_token.app_id.as_str()],
+        }
+    }
 }
 
 /// Config for fetching from HTTP endpoints and forwarding them to plugins.
@@ -310,20 +719,10 @@ pub struct PollerReceiverConfig {
 }
 
 impl PollerReceiverConfig {
-    // FIXME: duplicate from WebhookReceiverConfig. Extract/refactor as TryFrom ReceiverOutputOpts?
     pub async fn into_receiver_output(self) -> anyhow::Result<Box<dyn ReceiverOutput>> {
-        match self.output {
-            ReceiverOutputOpts::Kafka(opts) => {
-                svix_bridge_plugin_kafka::into_receiver_output(self.name, opts).map_err(Into::into)
-            }
-            ReceiverOutputOpts::Queue(x) => svix_bridge_plugin_queue::into_receiver_output(
-                self.name.clone(),
-                x,
-                self.transformation.as_ref(),
-            )
+        self.output
+            .into_receiver_output(self.name, self.transformation.as_ref())
             .await
-            .map_err(Into::into),
-        }
     }
 }
 
@@ -0,0 +1,89 @@
+use std::time::Duration;
+
+use svix_bridge_types::{async_trait, BoxError, DeliveryMode, ForwardRequest, ReceiverOutput};
+
+/// Waits for a fixed delay before handing each request to the output it wraps.
+///
+/// Meant for simulating a slow downstream, eg. to exercise backpressure and timeouts in tests.
+pub struct DelayedOutput {
+    inner: Box<dyn ReceiverOutput>,
+    delay: Duration,
+}
+
+impl DelayedOutput {
+    pub fn new(inner: Box<dyn ReceiverOutput>, delay: Duration) -> Self {
+        Self { inner, delay }
+    }
+}
+
+#[async_trait]
+impl ReceiverOutput for DelayedOutput {
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+
+    async fn handle(&self, request: ForwardRequest) -> Result<(), BoxError> {
+        // If the caller gives up on the request (eg. on a timeout), the sleep is dropped along
+        // with this future and the request never reaches the inner output.
+        tokio::time::sleep(self.delay).await;
+        self.inner.handle(request).await
+    }
+
+    fn delivery_mode(&self) -> DeliveryMode {
+        self.inner.delivery_mode()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use serde_json::json;
+    use svix_bridge_types::{DeliveryMode, ForwardRequest, ReceiverOutput};
+    use tokio::sync::mpsc;
+
+    use super::DelayedOutput;
+    use crate::test_util::FakeReceiverOutput;
+
+    fn delayed_output(
+        delay: Duration,
+    ) -> (DelayedOutput, mpsc::UnboundedReceiver<serde_json::Value>) {
+        let (output, rx) = FakeReceiverOutput::with_delivery_mode(DeliveryMode::Asynchronous);
+        (DelayedOutput::new(Box::new(output), delay), rx)
+    }
+
+    fn request() -> ForwardRequest {
+        ForwardRequest {
+            payload: json!({"a": true}),
+            method: None,
+            path: None,
+            signature_headers: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_delays_handling() {
+        let (output, mut rx) = delayed_output(Duration::from_millis(200));
+        assert_eq!(output.name(), "fake output");
+
+        let start = tokio::time::Instant::now();
+        output.handle(request()).await.unwrap();
+
+        assert!(start.elapsed() >= Duration::from_millis(200));
+        assert_eq!(rx.try_recv().unwrap(), json!({"a": true}));
+        // Delivered as the output it wraps delivers
+        assert_eq!(output.delivery_mode(), DeliveryMode::Asynchronous);
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_while_delayed() {
+        let (output, mut rx) = delayed_output(Duration::from_millis(200));
+
+        let res = tokio::time::timeout(Duration::from_millis(50), output.handle(request())).await;
+        assert!(res.is_err());
+
+        // The request was dropped along with the sleep, rather than being handled late
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        assert!(rx.try_recv().is_err());
+    }
+}
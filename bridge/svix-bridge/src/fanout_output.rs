@@ -0,0 +1,193 @@
+use futures::future::join_all;
+use svix_bridge_types::{async_trait, BoxError, DeliveryMode, ForwardRequest, ReceiverOutput};
+
+use crate::config::FanoutStrategy;
+
+/// Hands each request to several outputs concurrently.
+///
+/// Whether the request as a whole succeeded is decided by the [`FanoutStrategy`]. When it's
+/// considered failed, the request may be retried by its sender, in which case it's handed to all
+/// outputs again, including those that succeeded the first time.
+pub struct FanoutOutput {
+    name: String,
+    outputs: Vec<Box<dyn ReceiverOutput>>,
+    strategy: FanoutStrategy,
+}
+
+impl FanoutOutput {
+    pub fn new(
+        name: String,
+        outputs: Vec<Box<dyn ReceiverOutput>>,
+        strategy: FanoutStrategy,
+    ) -> Self {
+        Self {
+            name,
+            outputs,
+            strategy,
+        }
+    }
+}
+
+#[async_trait]
+impl ReceiverOutput for FanoutOutput {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    async fn handle(&self, request: ForwardRequest) -> Result<(), BoxError> {
+        let results = join_all(
+            self.outputs
+                .iter()
+                .map(|output| output.handle(request.clone())),
+        )
+        .await;
+
+        let mut failures = Vec::new();
+        for (output, result) in self.outputs.iter().zip(results) {
+            if let Err(e) = result {
+                tracing::warn!(output = output.name(), error = %e, "fanout target failed");
+                failures.push(format!("{}: {e}", output.name()));
+            }
+        }
+
+        let succeeded = match self.strategy {
+            FanoutStrategy::All => failures.is_empty(),
+            FanoutStrategy::Any => failures.len() < self.outputs.len(),
+        };
+        if succeeded {
+            Ok(())
+        } else {
+            Err(format!(
+                "{} of {} fanout targets failed: {}",
+                failures.len(),
+                self.outputs.len(),
+                failures.join("; ")
+            )
+            .into())
+        }
+    }
+
+    /// Asynchronous when any of the outputs is, since the request can't be considered delivered
+    /// until all of them have delivered it.
+    fn delivery_mode(&self) -> DeliveryMode {
+        if self
+            .outputs
+            .iter()
+            .any(|output| output.delivery_mode() == DeliveryMode::Asynchronous)
+        {
+            DeliveryMode::Asynchronous
+        } else {
+            DeliveryMode::Synchronous
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use svix_bridge_types::{DeliveryMode, ForwardRequest, ReceiverOutput};
+    use tokio::sync::mpsc;
+
+    use super::FanoutOutput;
+    use crate::{config::FanoutStrategy, test_util::FakeReceiverOutput};
+
+    /// The channel of each fake output in a fanout, along with the output's name.
+    type NamedReceivers = Vec<(&'static str, mpsc::UnboundedReceiver<serde_json::Value>)>;
+
+    /// A fanout to one output per entry of `fail`, which fails when its entry is true.
+    fn fanout_output(strategy: FanoutStrategy, fail: &[bool]) -> (FanoutOutput, NamedReceivers) {
+        const NAMES: &[&str] = &["a", "b", "c"];
+
+        let (outputs, receivers) = fail
+            .iter()
+            .zip(NAMES)
+            .map(|(&fail, &name)| {
+                let (output, rx) = FakeReceiverOutput::new();
+                let output = output.named(name);
+                let output = if fail { output.failing() } else { output };
+                (Box::new(output) as Box<dyn ReceiverOutput>, (name, rx))
+            })
+            .unzip();
+        (
+            FanoutOutput::new("fanout".to_owned(), outputs, strategy),
+            receivers,
+        )
+    }
+
+    fn request() -> ForwardRequest {
+        ForwardRequest {
+            payload: json!({"a": true}),
+            method: None,
+            path: None,
+            signature_headers: None,
+        }
+    }
+
+    /// The names of the outputs which were handed a request.
+    fn handled(receivers: &mut NamedReceivers) -> Vec<&'static str> {
+        receivers
+            .iter_mut()
+            .filter(|(_, rx)| rx.try_recv().is_ok())
+            .map(|(name, _)| *name)
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_all_succeed() {
+        for strategy in [FanoutStrategy::All, FanoutStrategy::Any] {
+            let (output, mut rx) = fanout_output(strategy, &[false, false, false]);
+            output.handle(request()).await.unwrap();
+            assert_eq!(handled(&mut rx), ["a", "b", "c"]);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_one_fails_with_all_strategy() {
+        let (output, mut rx) = fanout_output(FanoutStrategy::All, &[false, true, false]);
+        let err = output.handle(request()).await.unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "1 of 3 fanout targets failed: b: fake failure"
+        );
+        // The other outputs are still handed the request
+        assert_eq!(handled(&mut rx), ["a", "b", "c"]);
+    }
+
+    #[tokio::test]
+    async fn test_one_succeeds_with_any_strategy() {
+        let (output, mut rx) = fanout_output(FanoutStrategy::Any, &[true, false, true]);
+        output.handle(request()).await.unwrap();
+        assert_eq!(handled(&mut rx), ["a", "b", "c"]);
+
+        let (output, _rx) = fanout_output(FanoutStrategy::Any, &[true, true]);
+        assert!(output.handle(request()).await.is_err());
+    }
+
+    #[test]
+    fn test_delivery_mode() {
+        let output = |delivery_mode| {
+            let (output, _rx) = FakeReceiverOutput::with_delivery_mode(delivery_mode);
+            Box::new(output) as Box<dyn ReceiverOutput>
+        };
+
+        let fanout = FanoutOutput::new(
+            "fanout".to_owned(),
+            vec![
+                output(DeliveryMode::Synchronous),
+                output(DeliveryMode::Synchronous),
+            ],
+            FanoutStrategy::All,
+        );
+        assert_eq!(fanout.delivery_mode(), DeliveryMode::Synchronous);
+
+        let fanout = FanoutOutput::new(
+            "fanout".to_owned(),
+            vec![
+                output(DeliveryMode::Synchronous),
+                output(DeliveryMode::Asynchronous),
+            ],
+            FanoutStrategy::All,
+        );
+        assert_eq!(fanout.delivery_mode(), DeliveryMode::Asynchronous);
+    }
+}
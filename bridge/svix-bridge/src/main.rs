@@ -23,8 +23,13 @@ use self::config::Config;
 
 mod allocator;
 mod config;
+mod delayed_output;
+mod fanout_output;
 mod metrics;
 mod runtime;
+#[cfg(test)]
+mod test_util;
+mod unix_socket_output;
 mod webhook_receiver;
 
 use crate::{
@@ -324,13 +329,28 @@ async fn main() -> Result<()> {
         let pooler = runtime::JsPooler::new(cfg.transformation_worker_count);
         while let Some(TransformerJob {
             input,
+            metadata,
+            context,
             script,
-            callback_tx,
+            mut callback_tx,
+            cancellation_token,
         }) = xform_rx.recv().await
         {
             let tp = pooler.clone();
             tokio::spawn(async move {
-                let out = tp.run_script(input, script).await;
+                let run =
+                    tp.run_script(input, metadata, context, script, cancellation_token.clone());
+                tokio::pin!(run);
+                let out = tokio::select! {
+                    out = &mut run => out,
+                    _ = callback_tx.closed() => {
+                        // Nobody is waiting on the output anymore (the caller timed out or went
+                        // away), so abort the script and free up the worker for the next job.
+                        cancellation_token.cancel();
+                        let _ = run.await;
+                        return;
+                    }
+                };
                 // FIXME: seeing this Err case come up during load testing.
                 //   Seems like we shouldn't be hitting this so easily while the process is not terminating.
                 //   Regularly there are group error log lines that show up right at the end of an
@@ -373,12 +393,22 @@ async fn main() -> Result<()> {
             EitherReceiver::Poller(y) => Either::Right(y),
         });
 
-    let webhook_receivers_fut =
-        webhook_receiver::run(cfg.http_listen_address, webhook_receivers, xform_tx.clone());
+    let webhook_receivers_fut = webhook_receiver::run(
+        cfg.http_listen_address,
+        cfg.receiver_max_payload_bytes,
+        cfg.transformation_max_output_bytes,
+        cfg.tls,
+        webhook_receivers,
+        xform_tx.clone(),
+    );
 
     let mut pollers: Vec<Box<dyn PollerInput>> = Vec::with_capacity(poller_receivers.len());
     for poller_cfg in poller_receivers {
-        pollers.push(poller_cfg.into_poller_input(xform_tx.clone()).await?);
+        pollers.push(
+            poller_cfg
+                .into_poller_input(xform_tx.clone(), cfg.transformation_max_output_bytes)
+                .await?,
+        );
     }
 
     let poller_receivers_fut = supervise_pollers(pollers);
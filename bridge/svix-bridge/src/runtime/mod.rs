@@ -8,27 +8,57 @@ use deno_core::{
     v8::{self},
     JsRuntime,
 };
-use svix_bridge_types::{JsObject, TransformerInput, TransformerOutput};
+use svix_bridge_types::{
+    CancellationToken, JsObject, TransformerContext, TransformerInput, TransformerOutput,
+};
 use tokio::sync::oneshot;
 
 struct Executor {
     tx: std::sync::mpsc::Sender<Job>,
+    /// Used to interrupt whatever script the executor thread is currently running.
+    isolate_handle: v8::IsolateHandle,
     _handle: std::thread::JoinHandle<()>,
 }
 
 impl Default for Executor {
     fn default() -> Self {
         let (tx, rx) = std::sync::mpsc::channel::<Job>();
+        let (isolate_handle_tx, isolate_handle_rx) = std::sync::mpsc::channel();
         let _handle = std::thread::spawn(move || {
             let mut runtime = JsRuntime::new(Default::default());
-            for Job { input, script, cb } in rx {
-                let ret = run_script_inner(&mut runtime, input, script);
+            isolate_handle_tx
+                .send(runtime.v8_isolate().thread_safe_handle())
+                .expect("executor construction is waiting on the isolate handle");
+            for Job {
+                input,
+                metadata,
+                context,
+                script,
+                cancellation_token,
+                cb,
+            } in rx
+            {
+                // A termination requested for a previous job may have landed after that job had
+                // already completed. Clear it so it doesn't abort this one.
+                runtime.v8_isolate().cancel_terminate_execution();
+                let ret = if cancellation_token.is_cancelled() {
+                    Err(anyhow::anyhow!("transformation cancelled"))
+                } else {
+                    run_script_inner(&mut runtime, input, metadata, context, script)
+                };
                 if cb.send(ret).is_err() {
                     tracing::error!("failed to send script output to caller");
                 }
             }
         });
-        Self { tx, _handle }
+        let isolate_handle = isolate_handle_rx
+            .recv()
+            .expect("executor thread failed to start");
+        Self {
+            tx,
+            isolate_handle,
+            _handle,
+        }
     }
 }
 
@@ -36,7 +66,10 @@ type Callback = oneshot::Sender<Result<TransformerOutput>>;
 
 struct Job {
     input: TransformerInput,
+    metadata: JsObject,
+    context: TransformerContext,
     script: String,
+    cancellation_token: CancellationToken,
     cb: Callback,
 }
 
@@ -44,15 +77,30 @@ impl Executor {
     async fn execute(
         &mut self,
         input: TransformerInput,
+        metadata: JsObject,
+        context: TransformerContext,
         script: String,
+        cancellation_token: CancellationToken,
     ) -> Result<TransformerOutput> {
-        let (tx, rx) = oneshot::channel();
+        let (tx, mut rx) = oneshot::channel();
         self.tx.send(Job {
             input,
+            metadata,
+            context,
             script,
+            cancellation_token: cancellation_token.clone(),
             cb: tx,
         })?;
-        rx.await?
+        tokio::select! {
+            res = &mut rx => res?,
+            _ = cancellation_token.cancelled() => {
+                self.isolate_handle.terminate_execution();
+                // Wait for the thread to wind down the aborted script before handing this
+                // executor back to the pool, otherwise the next job would queue up behind it.
+                let _ = rx.await;
+                Err(anyhow::anyhow!("transformation cancelled"))
+            }
+        }
     }
 }
 
@@ -76,7 +124,10 @@ impl JsPooler {
     pub async fn run_script(
         &self,
         input: TransformerInput,
+        metadata: JsObject,
+        context: TransformerContext,
         script: String,
+        cancellation_token: CancellationToken,
     ) -> Result<TransformerOutput> {
         let pool = self.executors.clone();
         let mut executor = pool.get().await;
@@ -84,7 +135,7 @@ impl JsPooler {
         executor
             .as_mut()
             .map_err(|e| anyhow::anyhow!("{e:?}"))?
-            .execute(input, script)
+            .execute(input, metadata, context, script, cancellation_token)
             .await
     }
 }
@@ -105,18 +156,24 @@ pub fn validate_script(src: &str) -> Result<()> {
 fn run_script_inner(
     runtime: &mut JsRuntime,
     input: TransformerInput,
+    metadata: JsObject,
+    context: TransformerContext,
     script: String,
 ) -> Result<TransformerOutput> {
     let input = serde_json::to_string(&input)?;
+    let metadata = serde_json::to_string(&metadata)?;
+    let context = serde_json::to_string(&context)?;
     let res = runtime.execute_script(
         "<anon>",
         format!(
             // Wrap the user script, and invocation of `handler`, in a self-calling closure.
             // The hope is we'll prevent the globals space from being polluted call after call.
+            // For the same reason `svix_context` is only global as far as the script can tell.
             r#"
     (function () {{
+        const svix_context = {context};
         {script}
-        return handler({input});
+        return handler({input}, {metadata});
     }})()
     "#,
         )
@@ -1,8 +1,12 @@
+use std::{num::NonZeroUsize, time::Duration};
+
 use deno_core::JsRuntime;
 use serde_json::json;
-use svix_bridge_types::{TransformerInput, TransformerOutput};
+use svix_bridge_types::{
+    CancellationToken, JsObject, TransformerContext, TransformerInput, TransformerOutput,
+};
 
-use super::{run_script_inner, validate_script};
+use super::{run_script_inner, validate_script, JsPooler};
 
 fn get_test_rt() -> JsRuntime {
     JsRuntime::new(Default::default())
@@ -18,7 +22,14 @@ fn test_happy_fn() {
     "#
     .to_string();
     let mut rt = get_test_rt();
-    let res = run_script_inner(&mut rt, json!({ "y": 456 }).into(), src).unwrap();
+    let res = run_script_inner(
+        &mut rt,
+        json!({ "y": 456 }).into(),
+        JsObject::new(),
+        TransformerContext::new(),
+        src,
+    )
+    .unwrap();
     match res {
         TransformerOutput::Object(v) => {
             assert_eq!(v["x"].as_i64(), Some(123));
@@ -38,7 +49,14 @@ fn test_invalid_output_bool() {
     .to_string();
 
     let mut rt = get_test_rt();
-    let res = run_script_inner(&mut rt, json!({}).into(), src).unwrap();
+    let res = run_script_inner(
+        &mut rt,
+        json!({}).into(),
+        JsObject::new(),
+        TransformerContext::new(),
+        src,
+    )
+    .unwrap();
     match res {
         TransformerOutput::Invalid => (),
         TransformerOutput::Object(_) => panic!("got unexpected return value"),
@@ -56,7 +74,14 @@ fn test_invalid_output_array() {
     "#
     .to_string();
     let mut rt = get_test_rt();
-    let res = run_script_inner(&mut rt, json!({}).into(), src).unwrap();
+    let res = run_script_inner(
+        &mut rt,
+        json!({}).into(),
+        JsObject::new(),
+        TransformerContext::new(),
+        src,
+    )
+    .unwrap();
     match res {
         TransformerOutput::Invalid => (),
         TransformerOutput::Object(_) => {
@@ -78,6 +103,8 @@ fn test_string_input() {
     let res = run_script_inner(
         &mut rt,
         TransformerInput::String(String::from(r#"{"x": 123}"#)),
+        JsObject::new(),
+        TransformerContext::new(),
         src,
     )
     .unwrap();
@@ -103,6 +130,8 @@ fn test_string_input2() {
     let res = run_script_inner(
         &mut rt,
         TransformerInput::String(String::from("Hello World")),
+        JsObject::new(),
+        TransformerContext::new(),
         src,
     )
     .unwrap();
@@ -114,6 +143,82 @@ fn test_string_input2() {
     }
 }
 
+/// The metadata is handed to the handler as its second argument.
+#[test]
+fn test_metadata() {
+    let src = r#"
+    function handler(input, metadata) {
+        return { ...input, "order": metadata.order_id };
+    }
+    "#
+    .to_string();
+    let metadata = json!({ "order_id": "123" }).as_object().unwrap().clone();
+    let mut rt = get_test_rt();
+    let res = run_script_inner(
+        &mut rt,
+        json!({ "y": 456 }).into(),
+        metadata,
+        TransformerContext::new(),
+        src,
+    )
+    .unwrap();
+    match res {
+        TransformerOutput::Object(v) => {
+            assert_eq!(v["y"].as_i64(), Some(456));
+            assert_eq!(v["order"].as_str(), Some("123"));
+        }
+        TransformerOutput::Invalid => panic!("got unexpected return value"),
+    }
+}
+
+/// The context is available to the script as the `svix_context` global.
+#[test]
+fn test_context() {
+    let src = r#"
+    function handler(input) {
+        return { ...input, "integration": svix_context.integration_id };
+    }
+    "#
+    .to_string();
+    let context = TransformerContext::from([("integration_id".to_owned(), json!("orders"))]);
+    let mut rt = get_test_rt();
+    let res = run_script_inner(
+        &mut rt,
+        json!({ "y": 456 }).into(),
+        JsObject::new(),
+        context,
+        src,
+    )
+    .unwrap();
+    match res {
+        TransformerOutput::Object(v) => {
+            assert_eq!(v["y"].as_i64(), Some(456));
+            assert_eq!(v["integration"].as_str(), Some("orders"));
+        }
+        TransformerOutput::Invalid => panic!("got unexpected return value"),
+    }
+
+    // It doesn't linger into the next script's run
+    let src = r#"
+    function handler(input) {
+        return { "defined": typeof svix_context.integration_id !== "undefined" };
+    }
+    "#
+    .to_string();
+    let res = run_script_inner(
+        &mut rt,
+        json!({}).into(),
+        JsObject::new(),
+        TransformerContext::new(),
+        src,
+    )
+    .unwrap();
+    match res {
+        TransformerOutput::Object(v) => assert_eq!(v["defined"].as_bool(), Some(false)),
+        TransformerOutput::Invalid => panic!("got unexpected return value"),
+    }
+}
+
 #[test]
 fn test_validate_script_bad_syntax_is_err() {
     assert!(validate_script("let 123 = ';").is_err());
@@ -135,3 +240,45 @@ fn test_validate_script_empty_is_ok() {
     assert!(validate_script("").is_ok());
     assert!(validate_script("    ").is_ok());
 }
+
+/// A script that never returns should be aborted when its token is cancelled, and the (only)
+/// executor in the pool should be able to pick up the next job afterwards.
+#[tokio::test]
+async fn test_cancelled_job_frees_executor() {
+    let pooler = JsPooler::new(NonZeroUsize::new(1).unwrap());
+
+    let token = CancellationToken::new();
+    let canceller = token.clone();
+    tokio::spawn(async move {
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        canceller.cancel();
+    });
+    let res = pooler
+        .run_script(
+            json!({}).into(),
+            JsObject::new(),
+            TransformerContext::new(),
+            "function handler(input) { while (true) {} }".to_string(),
+            token,
+        )
+        .await;
+    assert!(res.is_err());
+
+    let res = tokio::time::timeout(
+        Duration::from_secs(5),
+        pooler.run_script(
+            json!({ "y": 456 }).into(),
+            JsObject::new(),
+            TransformerContext::new(),
+            "function handler(input) { return input; }".to_string(),
+            CancellationToken::new(),
+        ),
+    )
+    .await
+    .expect("executor was not freed after cancellation")
+    .unwrap();
+    match res {
+        TransformerOutput::Object(v) => assert_eq!(v["y"].as_i64(), Some(456)),
+        TransformerOutput::Invalid => panic!("got unexpected return value"),
+    }
+}
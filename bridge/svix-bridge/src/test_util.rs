@@ -0,0 +1,60 @@
+//! Helpers shared by the unit tests of the webhook receivers and their outputs.
+
+use svix_bridge_types::{async_trait, BoxError, DeliveryMode, ForwardRequest, ReceiverOutput};
+use tokio::sync::mpsc;
+
+/// A [`ReceiverOutput`] which sends the payload of every request it handles down a channel.
+pub(crate) struct FakeReceiverOutput {
+    name: &'static str,
+    tx: mpsc::UnboundedSender<serde_json::Value>,
+    delivery_mode: DeliveryMode,
+    fail: bool,
+}
+
+impl FakeReceiverOutput {
+    pub fn new() -> (Self, mpsc::UnboundedReceiver<serde_json::Value>) {
+        Self::with_delivery_mode(DeliveryMode::Synchronous)
+    }
+
+    pub fn with_delivery_mode(
+        delivery_mode: DeliveryMode,
+    ) -> (Self, mpsc::UnboundedReceiver<serde_json::Value>) {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let output = Self {
+            name: "fake output",
+            tx,
+            delivery_mode,
+            fail: false,
+        };
+        (output, rx)
+    }
+
+    pub fn named(self, name: &'static str) -> Self {
+        Self { name, ..self }
+    }
+
+    /// Makes the output fail every request, after still sending its payload down the channel.
+    pub fn failing(self) -> Self {
+        Self { fail: true, ..self }
+    }
+}
+
+#[async_trait]
+impl ReceiverOutput for FakeReceiverOutput {
+    fn name(&self) -> &str {
+        self.name
+    }
+
+    async fn handle(&self, request: ForwardRequest) -> Result<(), BoxError> {
+        self.tx.send(request.payload)?;
+        if self.fail {
+            Err("fake failure".into())
+        } else {
+            Ok(())
+        }
+    }
+
+    fn delivery_mode(&self) -> DeliveryMode {
+        self.delivery_mode
+    }
+}
@@ -0,0 +1,219 @@
+use std::{
+    io::{self, ErrorKind},
+    path::PathBuf,
+    time::Duration,
+};
+
+use svix_bridge_types::{async_trait, BoxError, ForwardRequest, ReceiverOutput};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::UnixStream,
+};
+
+/// How long to wait before trying to connect again after a failed attempt.
+const RETRY_DELAY: Duration = Duration::from_millis(100);
+
+/// Sends each request as JSON to a process listening on a Unix domain socket.
+///
+/// Every request uses its own connection: the JSON is written, the write half of the connection is
+/// shut down, and the request counts as delivered once the other side closes the connection.
+pub struct UnixSocketOutput {
+    name: String,
+    socket_path: PathBuf,
+    timeout: Duration,
+    /// How many more times to try connecting after the first attempt fails.
+    max_retries: u32,
+}
+
+impl UnixSocketOutput {
+    pub fn new(name: String, socket_path: PathBuf, timeout: Duration, max_retries: u32) -> Self {
+        Self {
+            name,
+            socket_path,
+            timeout,
+            max_retries,
+        }
+    }
+
+    async fn connect(&self) -> io::Result<UnixStream> {
+        let mut retries = 0;
+        loop {
+            match UnixStream::connect(&self.socket_path).await {
+                Ok(stream) => return Ok(stream),
+                // The listening process may be (re)starting, so give it a chance to come up
+                Err(e) if retries < self.max_retries => {
+                    retries += 1;
+                    tracing::warn!(
+                        error = %e,
+                        socket_path = %self.socket_path.display(),
+                        retries,
+                        "failed to connect to unix socket, retrying"
+                    );
+                    tokio::time::sleep(RETRY_DELAY).await;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    async fn send(&self, body: &[u8]) -> io::Result<()> {
+        let mut stream = self.connect().await.map_err(|e| {
+            if e.kind() == ErrorKind::NotFound {
+                io::Error::new(
+                    e.kind(),
+                    format!("unix socket `{}` not found", self.socket_path.display()),
+                )
+            } else {
+                io::Error::new(
+                    e.kind(),
+                    format!(
+                        "failed to connect to unix socket `{}`: {e}",
+                        self.socket_path.display()
+                    ),
+                )
+            }
+        })?;
+        stream.write_all(body).await?;
+        stream.shutdown().await?;
+        // Wait for the other side to close the connection, which signals it has read the request
+        let mut rest = Vec::new();
+        stream.read_to_end(&mut rest).await?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl ReceiverOutput for UnixSocketOutput {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    async fn handle(&self, request: ForwardRequest) -> Result<(), BoxError> {
+        let body = serde_json::to_vec(&request)?;
+        tokio::time::timeout(self.timeout, self.send(&body))
+            .await
+            .map_err(|_| {
+                format!(
+                    "timed out sending to unix socket `{}`",
+                    self.socket_path.display()
+                )
+            })??;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{path::PathBuf, time::Duration};
+
+    use serde_json::json;
+    use svix_bridge_types::{ForwardRequest, ReceiverOutput};
+    use svix_ksuid::{KsuidLike as _, KsuidMs};
+    use tokio::{io::AsyncReadExt, net::UnixListener};
+
+    use super::UnixSocketOutput;
+
+    fn temp_dir() -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("svix-bridge-uds-{}", KsuidMs::new(None, None)));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn request() -> ForwardRequest {
+        ForwardRequest {
+            payload: json!({"a": true}),
+            method: Some("POST".to_owned()),
+            path: None,
+            signature_headers: None,
+        }
+    }
+
+    /// Accepts a single connection and returns what was sent on it.
+    async fn read_one(listener: &UnixListener) -> serde_json::Value {
+        let (mut stream, _) = listener.accept().await.unwrap();
+        let mut buf = Vec::new();
+        stream.read_to_end(&mut buf).await.unwrap();
+        serde_json::from_slice(&buf).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_sends_request() {
+        let dir = temp_dir();
+        let socket_path = dir.join("output.sock");
+        let listener = UnixListener::bind(&socket_path).unwrap();
+        let output =
+            UnixSocketOutput::new("uds".to_owned(), socket_path, Duration::from_secs(1), 0);
+        assert_eq!(output.name(), "uds");
+
+        let (res, received) = tokio::join!(output.handle(request()), read_one(&listener));
+        res.unwrap();
+        assert_eq!(received, json!({"payload": {"a": true}, "method": "POST"}));
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_retries_connecting() {
+        let dir = temp_dir();
+        let socket_path = dir.join("output.sock");
+        let output = UnixSocketOutput::new(
+            "uds".to_owned(),
+            socket_path.clone(),
+            Duration::from_secs(5),
+            10,
+        );
+
+        // The listener only comes up after the first attempts have failed
+        let listen = async {
+            tokio::time::sleep(Duration::from_millis(250)).await;
+            let listener = UnixListener::bind(&socket_path).unwrap();
+            read_one(&listener).await
+        };
+        let (res, received) = tokio::join!(output.handle(request()), listen);
+        res.unwrap();
+        assert_eq!(received["payload"], json!({"a": true}));
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_socket_not_found() {
+        let dir = temp_dir();
+        let socket_path = dir.join("missing.sock");
+        let output = UnixSocketOutput::new(
+            "uds".to_owned(),
+            socket_path.clone(),
+            Duration::from_secs(5),
+            2,
+        );
+
+        let err = output.handle(request()).await.unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            format!("unix socket `{}` not found", socket_path.display())
+        );
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_times_out_when_not_closed() {
+        let dir = temp_dir();
+        let socket_path = dir.join("output.sock");
+        let listener = UnixListener::bind(&socket_path).unwrap();
+        let output =
+            UnixSocketOutput::new("uds".to_owned(), socket_path, Duration::from_millis(100), 0);
+
+        // Accept the connection, but never close it
+        let accept = async {
+            let (stream, _) = listener.accept().await.unwrap();
+            tokio::time::sleep(Duration::from_millis(300)).await;
+            drop(stream);
+        };
+        let (res, ()) = tokio::join!(output.handle(request()), accept);
+        assert!(res.unwrap_err().to_string().starts_with("timed out"));
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,296 @@
+use std::{borrow::Cow, collections::BTreeMap, io, path::PathBuf};
+
+use chrono::{SecondsFormat, Utc};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use tokio::{fs::OpenOptions, io::AsyncWriteExt, sync::Mutex};
+
+use super::types::{IntegrationId, SerializableHeaderMap};
+use crate::config::{AuditLogConfig, AuditLogFormat};
+
+/// Headers whose values are credentials, so are never written to the audit log.
+const REDACTED_HEADERS: &[&str] = &["authorization", "cookie", "proxy-authorization"];
+
+const CSV_HEADER: &str = "ts,integration_id,method,path,headers,body_hash\n";
+
+/// Appends a record of each inbound request of a receiver to its audit log file, rotating the file
+/// once it grows past the configured size.
+pub struct AuditLog {
+    cfg: AuditLogConfig,
+    /// Held while writing, so records aren't interleaved and rotation only happens once.
+    lock: Mutex<()>,
+}
+
+#[derive(Serialize)]
+struct AuditRecord<'a> {
+    ts: String,
+    integration_id: &'a str,
+    method: &'a str,
+    path: &'a str,
+    headers: BTreeMap<String, String>,
+    body_hash: String,
+}
+
+impl AuditRecord<'_> {
+    fn to_json_line(&self) -> io::Result<String> {
+        let mut line = serde_json::to_string(self)?;
+        line.push('\n');
+        Ok(line)
+    }
+
+    fn to_csv_line(&self) -> io::Result<String> {
+        let headers = serde_json::to_string(&self.headers)?;
+        let fields: [&str; 6] = [
+            self.ts.as_str(),
+            self.integration_id,
+            self.method,
+            self.path,
+            &headers,
+            &self.body_hash,
+        ];
+        let mut line = fields.map(csv_field).join(",");
+        line.push('\n');
+        Ok(line)
+    }
+}
+
+fn csv_field(value: &str) -> Cow<'_, str> {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\"")).into()
+    } else {
+        value.into()
+    }
+}
+
+impl AuditLog {
+    pub fn new(cfg: AuditLogConfig) -> Self {
+        Self {
+            cfg,
+            lock: Mutex::new(()),
+        }
+    }
+
+    pub async fn record(
+        &self,
+        integration_id: &IntegrationId,
+        method: &http::Method,
+        path: &str,
+        headers: &SerializableHeaderMap,
+        body: &[u8],
+    ) -> io::Result<()> {
+        let mut record_headers = BTreeMap::<String, String>::new();
+        for (name, value) in headers {
+            let name = name.to_ascii_lowercase();
+            let value = if REDACTED_HEADERS.contains(&name.as_str()) {
+                Cow::Borrowed("[redacted]")
+            } else {
+                String::from_utf8_lossy(value)
+            };
+            // Repeated headers are combined, as they would be when folded into a single line
+            record_headers
+                .entry(name)
+                .and_modify(|existing| {
+                    existing.push_str(", ");
+                    existing.push_str(&value);
+                })
+                .or_insert_with(|| value.into_owned());
+        }
+
+        let record = AuditRecord {
+            ts: Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true),
+            integration_id: integration_id.as_ref(),
+            method: method.as_str(),
+            path,
+            headers: record_headers,
+            body_hash: format!("sha256:{:x}", Sha256::digest(body)),
+        };
+        let line = match self.cfg.format {
+            AuditLogFormat::JsonLines => record.to_json_line()?,
+            AuditLogFormat::Csv => record.to_csv_line()?,
+        };
+
+        let _guard = self.lock.lock().await;
+
+        let mut len = match tokio::fs::metadata(&self.cfg.file_path).await {
+            Ok(metadata) => metadata.len(),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => 0,
+            Err(e) => return Err(e),
+        };
+        if len > 0 && len + line.len() as u64 > self.cfg.max_file_bytes {
+            tokio::fs::rename(&self.cfg.file_path, self.rotated_path()).await?;
+            len = 0;
+        }
+
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.cfg.file_path)
+            .await?;
+        if len == 0 && self.cfg.format == AuditLogFormat::Csv {
+            file.write_all(CSV_HEADER.as_bytes()).await?;
+        }
+        file.write_all(line.as_bytes()).await?;
+        file.flush().await
+    }
+
+    /// Where the current file is moved to when it's rotated, which is its path with the time of
+    /// the rotation appended.
+    fn rotated_path(&self) -> PathBuf {
+        let mut path = self.cfg.file_path.clone().into_os_string();
+        path.push(format!(".{}", Utc::now().format("%Y%m%dT%H%M%S%.3fZ")));
+        path.into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use http::{HeaderMap, HeaderValue, Method};
+    use svix_ksuid::{KsuidLike as _, KsuidMs};
+
+    use super::AuditLog;
+    use crate::{
+        config::{AuditLogConfig, AuditLogFormat},
+        webhook_receiver::types::SerializableHeaderMap,
+    };
+
+    fn temp_dir() -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "svix-bridge-audit-log-{}",
+            KsuidMs::new(None, None)
+        ));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn headers() -> SerializableHeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert("content-type", HeaderValue::from_static("application/json"));
+        headers.insert("authorization", HeaderValue::from_static("Bearer secret"));
+        headers.append("x-forwarded-for", HeaderValue::from_static("10.0.0.1"));
+        headers.append("x-forwarded-for", HeaderValue::from_static("10.0.0.2"));
+        SerializableHeaderMap::Standard(headers)
+    }
+
+    #[tokio::test]
+    async fn test_json_lines() {
+        let dir = temp_dir();
+        let file_path = dir.join("audit.jsonl");
+        let audit_log = AuditLog::new(AuditLogConfig {
+            file_path: file_path.clone(),
+            format: AuditLogFormat::JsonLines,
+            max_file_bytes: 1024 * 1024,
+        });
+
+        for body in [&b"{\"a\":true}"[..], b"second"] {
+            audit_log
+                .record(
+                    &"integration".into(),
+                    &Method::POST,
+                    "/webhook/integration",
+                    &headers(),
+                    body,
+                )
+                .await
+                .unwrap();
+        }
+
+        let contents = std::fs::read_to_string(&file_path).unwrap();
+        let records: Vec<serde_json::Value> = contents
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(records.len(), 2);
+
+        let record = &records[0];
+        assert!(record["ts"].is_string());
+        assert_eq!(record["integration_id"], "integration");
+        assert_eq!(record["method"], "POST");
+        assert_eq!(record["path"], "/webhook/integration");
+        assert_eq!(
+            record["headers"],
+            serde_json::json!({
+                "authorization": "[redacted]",
+                "content-type": "application/json",
+                "x-forwarded-for": "10.0.0.1, 10.0.0.2",
+            })
+        );
+        // sha256 of `{"a":true}`
+        assert_eq!(
+            record["body_hash"],
+            "sha256:5daa0644c4a1c43ea018f7f1ef2944b90e0165ef1f2d5669ca89e5d4f69ac597"
+        );
+        // The body itself is never written
+        assert!(!contents.contains("\"a\":true") && !contents.contains("second"));
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_csv() {
+        let dir = temp_dir();
+        let file_path = dir.join("audit.csv");
+        let audit_log = AuditLog::new(AuditLogConfig {
+            file_path: file_path.clone(),
+            format: AuditLogFormat::Csv,
+            max_file_bytes: 1024 * 1024,
+        });
+
+        for _ in 0..2 {
+            audit_log
+                .record(&"integration".into(), &Method::PUT, "/p", &headers(), b"")
+                .await
+                .unwrap();
+        }
+
+        let contents = std::fs::read_to_string(&file_path).unwrap();
+        let lines: Vec<&str> = contents.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0], "ts,integration_id,method,path,headers,body_hash");
+        let (ts, rest) = lines[1].split_once(',').unwrap();
+        assert!(chrono::DateTime::parse_from_rfc3339(ts).is_ok());
+        assert_eq!(
+            rest,
+            r#"integration,PUT,/p,"{""authorization"":""[redacted]"",""content-type"":""application/json"",""x-forwarded-for"":""10.0.0.1, 10.0.0.2""}",sha256:e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"#
+        );
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_rotation() {
+        let dir = temp_dir();
+        let file_path = dir.join("audit.jsonl");
+        let audit_log = AuditLog::new(AuditLogConfig {
+            file_path: file_path.clone(),
+            format: AuditLogFormat::JsonLines,
+            // Every record goes in a new file
+            max_file_bytes: 1,
+        });
+
+        for _ in 0..3 {
+            audit_log
+                .record(&"integration".into(), &Method::POST, "/p", &headers(), b"")
+                .await
+                .unwrap();
+            // Rotated files are named after the time, down to the millisecond
+            tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+        }
+
+        let mut files: Vec<_> = std::fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect();
+        files.sort();
+        assert_eq!(files.len(), 3);
+        assert_eq!(files[0], "audit.jsonl");
+        assert!(files[1..].iter().all(|f| f.starts_with("audit.jsonl.")));
+        for file in files {
+            let contents = std::fs::read_to_string(dir.join(file)).unwrap();
+            assert_eq!(contents.lines().count(), 1);
+        }
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
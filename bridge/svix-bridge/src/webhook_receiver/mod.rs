@@ -1,28 +1,38 @@
-use std::{net::SocketAddr, sync::Arc, time::Duration};
+use std::{collections::HashMap, net::SocketAddr, sync::Arc, time::Duration};
 
 use axum::{
     body::Body,
-    extract::{Path, State},
+    extract::{DefaultBodyLimit, Path, State},
     routing::post,
     Router,
 };
+use chrono::SecondsFormat;
+use futures::future;
+use regex::Regex;
 use svix_bridge_types::{
     async_trait,
     svix::api::{Svix, V1MessageEventsSubscriptionParams},
-    ForwardRequest, PollerInput, ReceiverOutput, TransformationConfig, TransformerInput,
-    TransformerInputFormat, TransformerJob, TransformerOutput, TransformerTx,
+    BoxError, DeliveryMode, ForwardRequest, JsObject, PollerInput, ReceiverOutput,
+    TransformationConfig, TransformerContext, TransformerInput, TransformerInputFormat,
+    TransformerJob, TransformerOutput, TransformerTx,
 };
 use tracing::instrument;
-use types::{IntegrationId, IntegrationState, InternalState, SerializableRequest, Unvalidated};
+use types::{
+    IntegrationId, IntegrationState, InternalState, ProxyState, SerializableHeaderMap,
+    SerializableRequest, Unvalidated, Validated,
+};
 
 use crate::{
     config::{
-        MessageStreamBridgeConfig, PollerInputOpts, PollerReceiverConfig, WebhookReceiverConfig,
+        ErrorPolicy, LogLevel, MessageStreamBridgeConfig, OutputAuth, PollerInputOpts,
+        PollerReceiverConfig, TlsConfig, WebhookReceiverConfig,
     },
     webhook_receiver::types::SerializablePayload,
 };
 
+mod audit_log;
 mod config;
+mod tls;
 mod types;
 mod verification;
 
@@ -36,24 +46,109 @@ fn router() -> Router<InternalState, Body> {
             "/webhook/:integration_id/",
             post(route).put(route).get(route).patch(route),
         )
+        .fallback(route_by_pattern)
 }
 
 pub async fn run(
     listen_addr: SocketAddr,
+    max_payload_bytes: usize,
+    transformation_max_output_bytes: usize,
+    tls: Option<TlsConfig>,
     routes: Vec<WebhookReceiverConfig>,
     transformer_tx: TransformerTx,
 ) -> std::io::Result<()> {
     let state = InternalState::from_receiver_configs(routes, transformer_tx)
         .await
-        .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?;
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?
+        .with_transformation_max_output_bytes(transformation_max_output_bytes);
 
-    let router = router().with_state(state);
+    // Enforced when the body is read by the `SerializableRequest` extractor, which responds with
+    // a 413 for anything larger
+    let router = router()
+        .layer(DefaultBodyLimit::max(max_payload_bytes))
+        .with_state(state);
 
-    tracing::info!("Listening on: {listen_addr}");
-    axum::Server::bind(&listen_addr)
-        .serve(router.into_make_service())
-        .await
-        .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))
+    serve(listen_addr, tls, router).await
+}
+
+async fn serve(
+    listen_addr: SocketAddr,
+    tls: Option<TlsConfig>,
+    router: Router,
+) -> std::io::Result<()> {
+    match tls {
+        Some(tls) => {
+            let rustls_config = tls::load(tls).await?;
+            tracing::info!("Listening on: {listen_addr} (TLS)");
+            axum_server::bind_rustls(listen_addr, rustls_config)
+                .serve(router.into_make_service())
+                .await
+        }
+        None => {
+            tracing::info!("Listening on: {listen_addr}");
+            axum::Server::bind(&listen_addr)
+                .serve(router.into_make_service())
+                .await
+                .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))
+        }
+    }
+}
+
+async fn route(
+    Path(integration_id): Path<IntegrationId>,
+    State(state): State<InternalState>,
+    method: http::Method,
+    uri: http::Uri,
+    req: SerializableRequest<Unvalidated>,
+) -> http::StatusCode {
+    route_inner(integration_id, JsObject::new(), state, method, uri, req).await
+}
+
+/// Requests to paths which aren't a receiver's `path_id` are routed to the first receiver whose
+/// `path_pattern` matches the path, with the pattern's named capture groups handed to the
+/// transformation.
+async fn route_by_pattern(
+    State(state): State<InternalState>,
+    method: http::Method,
+    uri: http::Uri,
+    req: SerializableRequest<Unvalidated>,
+) -> http::StatusCode {
+    let Some((integration_id, metadata)) = match_path_pattern(&state.path_patterns, uri.path())
+    else {
+        tracing::trace!(path = uri.path(), "no path pattern matched");
+        return http::StatusCode::NOT_FOUND;
+    };
+    if ![
+        http::Method::POST,
+        http::Method::PUT,
+        http::Method::GET,
+        http::Method::PATCH,
+    ]
+    .contains(&method)
+    {
+        return http::StatusCode::METHOD_NOT_ALLOWED;
+    }
+    route_inner(integration_id, metadata, state, method, uri, req).await
+}
+
+/// Finds the receiver for a request to `path` by its path pattern, along with the values of the
+/// pattern's named capture groups. Only paths under `/webhook/` are considered.
+fn match_path_pattern(
+    path_patterns: &[(Regex, IntegrationId)],
+    path: &str,
+) -> Option<(IntegrationId, JsObject)> {
+    if !path.starts_with("/webhook/") {
+        return None;
+    }
+    path_patterns.iter().find_map(|(pattern, integration_id)| {
+        let captures = pattern.captures(path)?;
+        let metadata = pattern
+            .capture_names()
+            .flatten()
+            .filter_map(|name| Some((name.to_owned(), captures.name(name)?.as_str().into())))
+            .collect();
+        Some((integration_id.clone(), metadata))
+    })
 }
 
 #[instrument(
@@ -63,35 +158,91 @@ pub async fn run(
         integration_id = integration_id.as_ref(),
     )
 )]
-async fn route(
-    Path(integration_id): Path<IntegrationId>,
-    State(InternalState {
+async fn route_inner(
+    integration_id: IntegrationId,
+    metadata: JsObject,
+    InternalState {
         routes,
+        proxies,
+        audit_logs,
+        http_client,
         transformer_tx,
-    }): State<InternalState>,
+        transformation_max_output_bytes,
+        ..
+    }: InternalState,
+    method: http::Method,
+    uri: http::Uri,
     req: SerializableRequest<Unvalidated>,
 ) -> http::StatusCode {
-    if let Some(IntegrationState {
+    // Requests are recorded before they're verified, so that rejected ones are in the trail too
+    if let Some(audit_log) = audit_logs.get(&integration_id) {
+        if let Err(e) = audit_log
+            .record(
+                &integration_id,
+                &method,
+                uri.path(),
+                req.headers(),
+                req.payload().as_bytes(),
+            )
+            .await
+        {
+            tracing::error!("Error writing to the audit log: {}", e);
+            return http::StatusCode::INTERNAL_SERVER_ERROR;
+        }
+    }
+
+    if let Some(ProxyState {
+        verifier,
+        upstream_url,
+        auth,
+    }) = proxies.get(&integration_id)
+    {
+        match req.validate(verifier).await {
+            Ok(req) => proxy(&http_client, upstream_url, auth.as_ref(), method, req).await,
+            Err(code) => {
+                tracing::warn!("validation failed: {code}");
+                code
+            }
+        }
+    } else if let Some(IntegrationState {
         verifier,
         output,
         transformation,
+        forward_method,
+        forward_signature,
+        on_error,
     }) = routes.get(&integration_id)
     {
         match req.validate(verifier).await {
             Ok(req) => {
-                let payload = match parse_payload(
+                let context =
+                    transformer_context([("integration_id", integration_id.as_ref().into())]);
+                let mut payload = match parse_payload(
                     req.payload(),
                     transformation.as_ref(),
+                    metadata,
+                    context,
                     transformer_tx.clone(),
+                    transformation_max_output_bytes,
                 )
                 .await
                 {
                     Err(e) => return e,
                     Ok(p) => p,
                 };
-                match handle(payload, output.clone()).await {
-                    Ok(value) => value,
-                    Err(value) => return value,
+                if *forward_method {
+                    payload.method = Some(method.to_string());
+                    payload.path = Some(uri.path().to_owned());
+                }
+                if *forward_signature {
+                    payload.signature_headers = Some(signature_headers(req.headers()));
+                }
+                match handle(payload, output.clone(), *on_error).await {
+                    Ok(status) => status,
+                    Err(e) => {
+                        tracing::error!("Error forwarding request: {e}");
+                        http::StatusCode::INTERNAL_SERVER_ERROR
+                    }
                 }
             }
             Err(code) => {
@@ -105,18 +256,137 @@ async fn route(
     }
 }
 
-// FIXME: Really odd return type - artifact of being extracted from the HTTP server
+/// Headers which only apply to the connection made to the bridge, so aren't passed upstream.
+const NON_FORWARDED_HEADERS: &[&str] = &[
+    "connection",
+    "content-length",
+    "host",
+    "keep-alive",
+    "proxy-connection",
+    "te",
+    "trailer",
+    "transfer-encoding",
+    "upgrade",
+];
+
+/// Sends a verified request, with its original method, headers and body, to the `upstream_url`,
+/// responding with whatever status the upstream gave.
+///
+/// With `auth` set, the request's own `Authorization` header is replaced with its credentials.
+async fn proxy(
+    client: &reqwest::Client,
+    upstream_url: &str,
+    auth: Option<&OutputAuth>,
+    method: http::Method,
+    req: SerializableRequest<Validated>,
+) -> http::StatusCode {
+    let mut headers = http::HeaderMap::with_capacity(req.headers().len());
+    for (name, value) in req.headers() {
+        if NON_FORWARDED_HEADERS.contains(&name) {
+            continue;
+        }
+        match (
+            http::HeaderName::from_bytes(name.as_bytes()),
+            http::HeaderValue::from_bytes(value),
+        ) {
+            (Ok(name), Ok(value)) => {
+                headers.append(name, value);
+            }
+            _ => tracing::warn!(header = name, "skipping invalid header"),
+        }
+    }
+
+    let mut request = client.request(method, upstream_url);
+    match auth {
+        Some(OutputAuth::Bearer { token }) => {
+            headers.remove(http::header::AUTHORIZATION);
+            request = request.bearer_auth(token);
+        }
+        Some(OutputAuth::Basic { username, password }) => {
+            headers.remove(http::header::AUTHORIZATION);
+            request = request.basic_auth(username, Some(password));
+        }
+        None => {}
+    }
+
+    tracing::debug!("proxying request");
+    match request
+        .headers(headers)
+        .body(req.payload().as_bytes().to_vec())
+        .send()
+        .await
+    {
+        Ok(resp) => resp.status(),
+        Err(e) => {
+            tracing::error!("Error proxying request: {}", e);
+            http::StatusCode::BAD_GATEWAY
+        }
+    }
+}
+
+/// Headers a webhook's signature is made up of, which are needed to verify it again.
+const SIGNATURE_HEADERS: &[&str] = &[
+    "svix-id",
+    "svix-timestamp",
+    "svix-signature",
+    "webhook-id",
+    "webhook-timestamp",
+    "webhook-signature",
+];
+
+/// Picks the signature headers out of a verified request's headers, to be forwarded verbatim.
+fn signature_headers(headers: &SerializableHeaderMap) -> HashMap<String, String> {
+    headers
+        .into_iter()
+        .filter(|(name, _)| {
+            SIGNATURE_HEADERS
+                .iter()
+                .any(|header| header.eq_ignore_ascii_case(name))
+        })
+        .filter_map(|(name, value)| match std::str::from_utf8(value) {
+            Ok(value) => Some((name.to_ascii_lowercase(), value.to_owned())),
+            Err(_) => {
+                tracing::warn!(header = name, "skipping invalid header");
+                None
+            }
+        })
+        .collect()
+}
+
+/// Hands the payload to the output, giving the status to respond to the sender with.
+///
+/// Output failures are returned under [`ErrorPolicy::Propagate`], while
+/// [`ErrorPolicy::Absorb`] only logs them and reports success.
 async fn handle(
     payload: ForwardRequest,
     output: Arc<Box<dyn ReceiverOutput>>,
-) -> Result<http::StatusCode, http::StatusCode> {
+    on_error: ErrorPolicy,
+) -> Result<http::StatusCode, BoxError> {
     tracing::debug!("forwarding request");
     Ok(match output.handle(payload).await {
-        Ok(_) => http::StatusCode::NO_CONTENT,
-        Err(e) => {
-            tracing::error!("Error forwarding request: {}", e);
-            http::StatusCode::INTERNAL_SERVER_ERROR
-        }
+        Ok(_) => match output.delivery_mode() {
+            DeliveryMode::Synchronous => http::StatusCode::NO_CONTENT,
+            DeliveryMode::Asynchronous => http::StatusCode::ACCEPTED,
+        },
+        Err(e) => match on_error {
+            ErrorPolicy::Propagate => return Err(e),
+            ErrorPolicy::Absorb { log_level } => {
+                match log_level {
+                    LogLevel::Error => {
+                        tracing::error!("Error forwarding request (absorbed): {e}")
+                    }
+                    LogLevel::Warn => tracing::warn!("Error forwarding request (absorbed): {e}"),
+                    LogLevel::Info => tracing::info!("Error forwarding request (absorbed): {e}"),
+                    LogLevel::Debug => {
+                        tracing::debug!("Error forwarding request (absorbed): {e}")
+                    }
+                    LogLevel::Trace => {
+                        tracing::trace!("Error forwarding request (absorbed): {e}")
+                    }
+                }
+                http::StatusCode::OK
+            }
+        },
     })
 }
 
@@ -130,10 +400,16 @@ async fn handle(
 /// When a transformation is defined, we branch to see if it expects string or json input.
 ///
 /// For either case, we expect the value produced to match the schema of a [`ForwardRequest`].
+///
+/// The `metadata` is handed to the transformation, if any, alongside the payload. Transformations
+/// producing more than `max_output_bytes` of JSON are rejected with a 413.
 async fn parse_payload(
     payload: &SerializablePayload,
     transformation: Option<&TransformationConfig>,
+    metadata: JsObject,
+    context: TransformerContext,
     transformer_tx: TransformerTx,
+    max_output_bytes: usize,
 ) -> Result<ForwardRequest, http::StatusCode> {
     match transformation {
         Some(xform) => {
@@ -151,7 +427,15 @@ async fn parse_payload(
                     })?)
                 }
             };
-            transform(input, xform.source().clone(), transformer_tx).await
+            transform(
+                input,
+                metadata,
+                context,
+                xform.source().clone(),
+                transformer_tx,
+                max_output_bytes,
+            )
+            .await
         }
         // Keep the original payload as-is if there's no transformation specified, but stuff the
         // whole thing into the payload field.
@@ -162,17 +446,41 @@ async fn parse_payload(
                 tracing::error!("Unable to parse request body as json");
                 http::StatusCode::BAD_REQUEST
             })?,
+            method: None,
+            path: None,
+            signature_headers: None,
         }),
     }
 }
 
+/// The `svix_context` for a transformation: the given values describing where the payload came
+/// from, along with the `timestamp` it's being transformed at.
+fn transformer_context<const N: usize>(
+    values: [(&str, serde_json::Value); N],
+) -> TransformerContext {
+    values
+        .into_iter()
+        .map(|(key, value)| (key.to_owned(), value))
+        .chain([(
+            "timestamp".to_owned(),
+            chrono::Utc::now()
+                .to_rfc3339_opts(SecondsFormat::Millis, true)
+                .into(),
+        )])
+        .collect()
+}
+
 /// Attempts to run the payload through a js transformation.
 async fn transform(
     input: TransformerInput,
+    metadata: JsObject,
+    context: TransformerContext,
     script: String,
     tx: TransformerTx,
+    max_output_bytes: usize,
 ) -> Result<ForwardRequest, http::StatusCode> {
-    let (job, callback) = TransformerJob::new(script, input);
+    let (mut job, callback) = TransformerJob::with_metadata(script, input, metadata);
+    job.context = context;
     if let Err(e) = tx.send(job) {
         tracing::error!("transformations are not available: {}", e);
         return Err(http::StatusCode::INTERNAL_SERVER_ERROR);
@@ -181,13 +489,19 @@ async fn transform(
     match callback.await {
         // This is the only "good" outcome giving a RHS value for the assignment.
         // All other match arms should bail with a non-2xx status.
-        Ok(Ok(TransformerOutput::Object(obj))) => Ok(serde_json::from_value(
-            serde_json::Value::Object(obj),
-        )
-        .map_err(|e| {
-            tracing::error!("transformation produced invalid payload: {}", e);
-            http::StatusCode::INTERNAL_SERVER_ERROR
-        })?),
+        Ok(Ok(TransformerOutput::Object(obj))) => {
+            let obj = serde_json::Value::Object(obj);
+            if exceeds_serialized_len(&obj, max_output_bytes) {
+                tracing::error!(
+                    "transformation produced a payload larger than {max_output_bytes} bytes"
+                );
+                return Err(http::StatusCode::PAYLOAD_TOO_LARGE);
+            }
+            Ok(serde_json::from_value(obj).map_err(|e| {
+                tracing::error!("transformation produced invalid payload: {}", e);
+                http::StatusCode::INTERNAL_SERVER_ERROR
+            })?)
+        }
         Ok(Ok(TransformerOutput::Invalid)) => {
             tracing::error!("transformation produced invalid payload");
             Err(http::StatusCode::INTERNAL_SERVER_ERROR)
@@ -199,11 +513,38 @@ async fn transform(
     }
 }
 
+/// Whether `value` takes up more than `max_len` bytes once serialized as JSON.
+///
+/// Stops serializing as soon as the limit is passed, rather than buffering the whole thing.
+fn exceeds_serialized_len(value: &serde_json::Value, max_len: usize) -> bool {
+    struct Counter {
+        len: usize,
+        max_len: usize,
+    }
+
+    impl std::io::Write for Counter {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.len += buf.len();
+            if self.len > self.max_len {
+                return Err(std::io::ErrorKind::WriteZero.into());
+            }
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    serde_json::to_writer(&mut Counter { len: 0, max_len }, value).is_err()
+}
+
 struct SvixEventsPoller {
     name: String,
     input_opts: PollerInputOpts,
     transformation: Option<TransformationConfig>,
     transformer_tx: Option<TransformerTx>,
+    transformation_max_output_bytes: usize,
     svix_client: Svix,
     output: Arc<Box<dyn ReceiverOutput>>,
 }
@@ -227,6 +568,7 @@ impl PollerReceiverConfig {
     pub async fn into_poller_input(
         self,
         transformer_tx: TransformerTx,
+        transformation_max_output_bytes: usize,
     ) -> std::io::Result<Box<dyn PollerInput>> {
         let svix_client = self
             .input
@@ -245,6 +587,7 @@ impl PollerReceiverConfig {
             input_opts,
             transformation,
             transformer_tx: Some(transformer_tx.clone()),
+            transformation_max_output_bytes,
             svix_client,
             output,
         }))
@@ -252,18 +595,29 @@ impl PollerReceiverConfig {
 }
 
 async fn run_inner(poller: &SvixEventsPoller) -> ! {
+    let polls = poller
+        .input_opts
+        .app_ids()
+        .into_iter()
+        .map(|app_id| Box::pin(poll_app(poller, app_id)));
+    // None of these ever finish, so this waits on all of them
+    future::select_all(polls).await.0
+}
+
+/// Polls a single app, with its own iterator and backoff.
+async fn poll_app(poller: &SvixEventsPoller, app_id: &str) -> ! {
     const MIN_SLEEP: Duration = Duration::from_millis(10);
     const MAX_SLEEP: Duration = Duration::from_secs(300);
     const NO_SLEEP: Duration = Duration::ZERO;
     let mut sleep_time = NO_SLEEP;
 
     let PollerInputOpts::SvixEvents {
-        subscription_token:
-            MessageStreamBridgeConfig {
-                app_id,
-                subscription_id,
-                ..
-            },
+        subscription_token: MessageStreamBridgeConfig {
+            subscription_id, ..
+        },
+        max_messages_per_batch,
+        initial_since,
+        channels,
         ..
     } = &poller.input_opts;
 
@@ -276,18 +630,26 @@ async fn run_inner(poller: &SvixEventsPoller) -> ! {
             .message()
             .events_subscription(V1MessageEventsSubscriptionParams {
                 // FIXME: expose more params as poller input cfg
-                app_id: app_id.clone(),
+                app_id: app_id.to_owned(),
                 subscription_id: subscription_id.clone(),
-                limit: None,
+                // Capping the page size caps how many messages are handled before the iterator
+                // is committed and the next batch is requested
+                limit: max_messages_per_batch.map(|max| max as _),
                 iterator: iterator.clone(),
                 event_types: None,
-                channels: None,
-                after: None,
+                channels: channels.clone(),
+                // Only used to bootstrap: once there's an iterator it determines where to read from
+                after: if iterator.is_none() {
+                    initial_since.map(|since| since.to_rfc3339_opts(SecondsFormat::AutoSi, true))
+                } else {
+                    None
+                },
             })
             .await
         {
             Ok(resp) => {
                 let mut has_failure = false;
+                let is_empty = resp.data.is_empty();
                 tracing::trace!(count = resp.data.len(), "got messages");
                 'inner: for msg in resp.data.into_iter() {
                     let payload = match parse_payload(
@@ -298,10 +660,13 @@ async fn run_inner(poller: &SvixEventsPoller) -> ! {
                                 .expect("just fetched as json, must be serializable"),
                         ),
                         poller.transformation.as_ref(),
+                        JsObject::new(),
+                        transformer_context([("app_id", app_id.into())]),
                         poller
                             .transformer_tx
                             .clone()
                             .expect("transformer tx is required"),
+                        poller.transformation_max_output_bytes,
                     )
                     .await
                     {
@@ -315,12 +680,10 @@ async fn run_inner(poller: &SvixEventsPoller) -> ! {
                         }
                         Ok(p) => p,
                     };
-                    if let Err(status) = handle(payload, poller.output.clone()).await {
-                        // FIXME: need to refactor handle to not give http status codes so we can report what happened here.
-                        tracing::error!(
-                            status = status.as_u16(),
-                            "error while handling polled message"
-                        );
+                    if let Err(e) =
+                        handle(payload, poller.output.clone(), ErrorPolicy::Propagate).await
+                    {
+                        tracing::error!(error = %e, "error while handling polled message");
                         has_failure = true;
                         break 'inner;
                     }
@@ -339,8 +702,11 @@ async fn run_inner(poller: &SvixEventsPoller) -> ! {
                     );
                     // Update the iterator _after we've handled all the messages in the batch_.
                     iterator = Some(resp.iterator.clone());
-                    // If the iterator is "done" we can backoff to wait for new messages to arrive.
-                    sleep_time = if resp.done {
+                    // If the iterator is "done" and there was nothing to handle we can backoff to
+                    // wait for new messages to arrive. After handling messages, more are likely to
+                    // follow, so the next batch is requested straight away rather than after the
+                    // backoff left over from earlier failures or empty batches.
+                    sleep_time = if resp.done && is_empty {
                         // BACKOFF
                         (sleep_time * 2).clamp(MIN_SLEEP, MAX_SLEEP)
                     } else {
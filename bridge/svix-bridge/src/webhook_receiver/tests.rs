@@ -1,45 +1,63 @@
-use std::sync::Arc;
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
 
 use axum::{
     body::Body,
+    extract::DefaultBodyLimit,
     http::{Request, StatusCode},
 };
+use regex::Regex;
 use serde_json::json;
 use svix_bridge_types::{
-    async_trait, svix::webhooks::Webhook, BoxError, ForwardRequest, ReceiverOutput,
+    async_trait, svix::webhooks::Webhook, BoxError, DeliveryMode, ForwardRequest, ReceiverOutput,
     TransformationConfig, TransformerInput, TransformerInputFormat, TransformerJob,
     TransformerOutput,
 };
+use svix_ksuid::{KsuidLike as _, KsuidMs};
 use tower::{Service, ServiceExt};
+use wiremock::{
+    matchers::{body_bytes, header, method, path, query_param},
+    Mock, MockServer, ResponseTemplate,
+};
 
-use super::router;
-use crate::webhook_receiver::{
-    types::{IntegrationState, InternalState},
-    verification::{NoVerifier, SvixVerifier},
+use super::{router, run_inner, serve, SvixEventsPoller};
+use crate::{
+    config::{
+        default_transformation_max_output_bytes, AuditLogConfig, AuditLogFormat, ErrorPolicy,
+        LogLevel, MessageStreamBridgeConfig, OutputAuth, PollerInputOpts, TlsConfig,
+        WebhookReceiverConfig,
+    },
+    test_util::FakeReceiverOutput,
+    webhook_receiver::{
+        audit_log::AuditLog,
+        types::{transformation_warnings, IntegrationState, InternalState, ProxyState},
+        verification::{NoVerifier, SvixVerifier},
+    },
 };
 
-struct FakeReceiverOutput {
-    tx: tokio::sync::mpsc::UnboundedSender<serde_json::Value>,
+/// Like [`FakeReceiverOutput`], but hands over the whole [`ForwardRequest`] rather than only its
+/// payload.
+struct FakeForwardRequestOutput {
+    tx: tokio::sync::mpsc::UnboundedSender<ForwardRequest>,
 }
 
-impl FakeReceiverOutput {
-    pub fn new() -> (
-        Self,
-        tokio::sync::mpsc::UnboundedReceiver<serde_json::Value>,
-    ) {
+impl FakeForwardRequestOutput {
+    pub fn new() -> (Self, tokio::sync::mpsc::UnboundedReceiver<ForwardRequest>) {
         let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
         (Self { tx }, rx)
     }
 }
 
 #[async_trait]
-impl ReceiverOutput for FakeReceiverOutput {
+impl ReceiverOutput for FakeForwardRequestOutput {
     fn name(&self) -> &str {
-        "fake output"
+        "fake forward request output"
     }
 
     async fn handle(&self, request: ForwardRequest) -> Result<(), BoxError> {
-        self.tx.send(request.payload)?;
+        self.tx.send(request).map_err(|_| "receiver dropped")?;
         Ok(())
     }
 }
@@ -54,6 +72,9 @@ async fn test_forwarding_no_verification() {
             verifier: NoVerifier.into(),
             output: Arc::new(Box::new(a_output)),
             transformation: None,
+            forward_method: false,
+            forward_signature: false,
+            on_error: ErrorPolicy::Propagate,
         },
     )]
     .into_iter()
@@ -76,6 +97,92 @@ async fn test_forwarding_no_verification() {
     assert_eq!(json!(forwarded), json!({"a": true}));
 }
 
+/// Outputs which only accept payloads for delivery later are answered with a `202`, not a `204`.
+#[tokio::test]
+async fn test_forwarding_delivery_mode() {
+    for (delivery_mode, status) in [
+        (DeliveryMode::Synchronous, StatusCode::NO_CONTENT),
+        (DeliveryMode::Asynchronous, StatusCode::ACCEPTED),
+    ] {
+        let (tx, _rx) = tokio::sync::mpsc::unbounded_channel();
+        let (a_output, mut a_rx) = FakeReceiverOutput::with_delivery_mode(delivery_mode);
+        let state_map = [(
+            "a".into(),
+            IntegrationState {
+                verifier: NoVerifier.into(),
+                output: Arc::new(Box::new(a_output)),
+                transformation: None,
+                forward_method: false,
+                forward_signature: false,
+                on_error: ErrorPolicy::Propagate,
+            },
+        )]
+        .into_iter()
+        .collect();
+        let state = InternalState::new(state_map, tx);
+        let app = router().with_state(state);
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/webhook/a")
+                    .method("POST")
+                    .header("content-type", "application/json")
+                    .body(serde_json::to_vec(&json!({"a": true})).unwrap().into())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), status, "{delivery_mode:?}");
+        assert_eq!(a_rx.try_recv().unwrap(), json!({"a": true}));
+    }
+}
+
+/// Output failures are answered with a `500`, unless the receiver absorbs them.
+#[tokio::test]
+async fn test_forwarding_error_policy() {
+    for (on_error, status) in [
+        (ErrorPolicy::Propagate, StatusCode::INTERNAL_SERVER_ERROR),
+        (
+            ErrorPolicy::Absorb {
+                log_level: LogLevel::Warn,
+            },
+            StatusCode::OK,
+        ),
+    ] {
+        let (tx, _rx) = tokio::sync::mpsc::unbounded_channel();
+        let (a_output, a_rx) = FakeReceiverOutput::new();
+        // Makes the output fail
+        drop(a_rx);
+        let state_map = [(
+            "a".into(),
+            IntegrationState {
+                verifier: NoVerifier.into(),
+                output: Arc::new(Box::new(a_output)),
+                transformation: None,
+                forward_method: false,
+                forward_signature: false,
+                on_error,
+            },
+        )]
+        .into_iter()
+        .collect();
+        let state = InternalState::new(state_map, tx);
+        let app = router().with_state(state);
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/webhook/a")
+                    .method("POST")
+                    .header("content-type", "application/json")
+                    .body(serde_json::to_vec(&json!({"a": true})).unwrap().into())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), status, "{on_error:?}");
+    }
+}
+
 /// Registers 2 receivers and sends 1 request to each.
 #[tokio::test]
 async fn test_forwarding_multiple_receivers() {
@@ -89,6 +196,9 @@ async fn test_forwarding_multiple_receivers() {
                 verifier: NoVerifier.into(),
                 output: Arc::new(Box::new(a_output)),
                 transformation: None,
+                forward_method: false,
+                forward_signature: false,
+                on_error: ErrorPolicy::Propagate,
             },
         ),
         (
@@ -97,6 +207,9 @@ async fn test_forwarding_multiple_receivers() {
                 verifier: NoVerifier.into(),
                 output: Arc::new(Box::new(b_output)),
                 transformation: None,
+                forward_method: false,
+                forward_signature: false,
+                on_error: ErrorPolicy::Propagate,
             },
         ),
     ]
@@ -179,6 +292,9 @@ async fn test_transformation_json() {
                 transformation: Some(
                     "handler = (x) => ({ payload: {__TRANSFORMED__: true, ...x }})".into(),
                 ),
+                forward_method: false,
+                forward_signature: false,
+                on_error: ErrorPolicy::Propagate,
             },
         ),
         (
@@ -187,6 +303,9 @@ async fn test_transformation_json() {
                 verifier: NoVerifier.into(),
                 output: Arc::new(Box::new(b_output)),
                 transformation: None,
+                forward_method: false,
+                forward_signature: false,
+                on_error: ErrorPolicy::Propagate,
             },
         ),
     ]
@@ -268,6 +387,9 @@ async fn test_transformation_string() {
                 format: TransformerInputFormat::String,
                 src: String::from("handler = (x) => ({ payload: { got: x }})"),
             }),
+            forward_method: false,
+            forward_signature: false,
+            on_error: ErrorPolicy::Propagate,
         },
     )]
     .into_iter()
@@ -298,6 +420,196 @@ async fn test_transformation_string() {
     assert!(a_rx.try_recv().is_err());
 }
 
+/// Transformations producing more than the configured limit are rejected, without forwarding.
+#[tokio::test]
+async fn test_transformation_output_too_large() {
+    let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel::<TransformerJob>();
+    let _handle = tokio::spawn(async move {
+        while let Some(x) = rx.recv().await {
+            // Around 2MB, once serialized
+            let out = json!({ "payload": { "padding": "a".repeat(2 * 1024 * 1024) } });
+            x.callback_tx
+                .send(Ok(TransformerOutput::Object(
+                    out.as_object().cloned().unwrap(),
+                )))
+                .ok();
+        }
+    });
+
+    let (a_output, mut a_rx) = FakeReceiverOutput::new();
+    let state_map = [(
+        "transformed".into(),
+        IntegrationState {
+            verifier: NoVerifier.into(),
+            output: Arc::new(Box::new(a_output)),
+            transformation: Some(TransformationConfig::Explicit {
+                format: TransformerInputFormat::Json,
+                src: String::from(
+                    "handler = (x) => ({ payload: { padding: 'a'.repeat(2097152) }})",
+                ),
+            }),
+            forward_method: false,
+            forward_signature: false,
+            on_error: ErrorPolicy::Propagate,
+        },
+    )]
+    .into_iter()
+    .collect();
+    let state = InternalState::new(state_map, tx).with_transformation_max_output_bytes(1024 * 1024);
+
+    let mut app = router().with_state(state);
+
+    let request = Request::builder()
+        .uri("/webhook/transformed")
+        .method("POST")
+        .header("content-type", "application/json")
+        .body(serde_json::to_vec(&json!({"a": true})).unwrap().into())
+        .unwrap();
+
+    let response = ServiceExt::<Request<Body>>::ready(&mut app)
+        .await
+        .unwrap()
+        .call(request)
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+    assert!(a_rx.try_recv().is_err());
+}
+
+/// Requests to paths matching a receiver's path pattern are routed to it, with the named groups
+/// captured from the path handed to the transformation.
+#[tokio::test]
+async fn test_path_pattern() {
+    let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel::<TransformerJob>();
+    let _handle = tokio::spawn(async move {
+        while let Some(x) = rx.recv().await {
+            let out = json!({ "payload": { "input": x.input, "metadata": x.metadata } });
+            x.callback_tx
+                .send(Ok(TransformerOutput::Object(
+                    out.as_object().unwrap().clone(),
+                )))
+                .ok();
+        }
+    });
+
+    let (a_output, mut a_rx) = FakeReceiverOutput::new();
+    let state_map = [(
+        "orders".into(),
+        IntegrationState {
+            verifier: NoVerifier.into(),
+            output: Arc::new(Box::new(a_output)),
+            transformation: Some(
+                "handler = (x, metadata) => ({ payload: { input: x, metadata }})".into(),
+            ),
+            forward_method: false,
+            forward_signature: false,
+            on_error: ErrorPolicy::Propagate,
+        },
+    )]
+    .into_iter()
+    .collect();
+    let path_patterns = vec![(
+        Regex::new(r"^(?:/webhook/orders/(?P<order_id>\d+)(?:/(?P<item_id>\d+))?)$").unwrap(),
+        "orders".into(),
+    )];
+    let state = InternalState::new(state_map, tx).with_path_patterns(path_patterns);
+
+    let mut app = router().with_state(state);
+
+    for (uri, expected) in [
+        ("/webhook/orders", Some(json!({}))),
+        ("/webhook/orders/123", Some(json!({"order_id": "123"}))),
+        (
+            "/webhook/orders/456/7",
+            Some(json!({"order_id": "456", "item_id": "7"})),
+        ),
+        ("/webhook/orders/abc", None),
+        ("/webhook/orders/123/7/8", None),
+        ("/webhooks/orders/123", None),
+    ] {
+        let request = Request::builder()
+            .uri(uri)
+            .method("POST")
+            .header("content-type", "application/json")
+            .body(serde_json::to_vec(&json!({"a": true})).unwrap().into())
+            .unwrap();
+
+        let response = ServiceExt::<Request<Body>>::ready(&mut app)
+            .await
+            .unwrap()
+            .call(request)
+            .await
+            .unwrap();
+
+        match expected {
+            Some(metadata) => {
+                assert_eq!(response.status(), StatusCode::NO_CONTENT, "{uri}");
+                let forwarded = a_rx.try_recv().unwrap();
+                assert_eq!(
+                    json!(forwarded),
+                    json!({"input": {"a": true}, "metadata": metadata})
+                );
+            }
+            None => assert_eq!(response.status(), StatusCode::NOT_FOUND, "{uri}"),
+        }
+    }
+
+    assert!(a_rx.try_recv().is_err());
+}
+
+/// Transformations are told which receiver the payload came in through, and when.
+#[tokio::test]
+async fn test_transformation_context() {
+    let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel::<TransformerJob>();
+    let _handle = tokio::spawn(async move {
+        while let Some(x) = rx.recv().await {
+            let out = json!({ "payload": x.context });
+            x.callback_tx
+                .send(Ok(TransformerOutput::Object(
+                    out.as_object().unwrap().clone(),
+                )))
+                .ok();
+        }
+    });
+
+    let (a_output, mut a_rx) = FakeReceiverOutput::new();
+    let state_map = [(
+        "orders".into(),
+        IntegrationState {
+            verifier: NoVerifier.into(),
+            output: Arc::new(Box::new(a_output)),
+            transformation: Some("handler = () => ({ payload: svix_context })".into()),
+            forward_method: false,
+            forward_signature: false,
+            on_error: ErrorPolicy::Propagate,
+        },
+    )]
+    .into_iter()
+    .collect();
+    let state = InternalState::new(state_map, tx);
+
+    let app = router().with_state(state);
+
+    let response = app
+        .oneshot(
+            Request::builder()
+                .uri("/webhook/orders")
+                .method("POST")
+                .header("content-type", "application/json")
+                .body(serde_json::to_vec(&json!({"a": true})).unwrap().into())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+    let forwarded = json!(a_rx.try_recv().unwrap());
+    assert_eq!(forwarded["integration_id"], "orders");
+    let timestamp = forwarded["timestamp"].as_str().unwrap();
+    assert!(chrono::DateTime::parse_from_rfc3339(timestamp).is_ok());
+}
+
 // Two different bodies - one used during signing, then the other is what we send in the request.
 // This should result in a bad response status.
 #[tokio::test]
@@ -321,6 +633,9 @@ async fn test_forwarding_svix_verification_mismatch() {
             verifier: SvixVerifier::new(webhook).into(),
             output: Arc::new(Box::new(a_output)),
             transformation: None,
+            forward_method: false,
+            forward_signature: false,
+            on_error: ErrorPolicy::Propagate,
         },
     )]
     .into_iter()
@@ -336,7 +651,7 @@ async fn test_forwarding_svix_verification_mismatch() {
                 .header("content-type", "application/json")
                 .header("svix-id", "msg_valid")
                 .header("svix-signature", signature.clone())
-                .header("svix-timestamp", &format!("{timestamp}"))
+                .header("svix-timestamp", timestamp.to_string())
                 .body(sent_payload_bytes.into())
                 .unwrap(),
         )
@@ -369,6 +684,9 @@ async fn test_forwarding_svix_verification_match() {
             verifier: SvixVerifier::new(webhook).into(),
             output: Arc::new(Box::new(a_output)),
             transformation: None,
+            forward_method: false,
+            forward_signature: false,
+            on_error: ErrorPolicy::Propagate,
         },
     )]
     .into_iter()
@@ -383,13 +701,1059 @@ async fn test_forwarding_svix_verification_match() {
                 .header("content-type", "application/json")
                 .header("svix-id", "msg_valid")
                 .header("svix-signature", signature.clone())
-                .header("svix-timestamp", &format!("{timestamp}"))
+                .header("svix-timestamp", timestamp.to_string())
+                .body(payload_bytes.into())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+    let forwarded = a_rx.try_recv().unwrap();
+    assert_eq!(json!(forwarded), json!({"a": true}));
+}
+
+#[tokio::test]
+async fn test_forward_method() {
+    let (tx, _rx) = tokio::sync::mpsc::unbounded_channel();
+    let (a_output, mut a_rx) = FakeForwardRequestOutput::new();
+    let (b_output, mut b_rx) = FakeForwardRequestOutput::new();
+    let state_map = [
+        (
+            "with-method".into(),
+            IntegrationState {
+                verifier: NoVerifier.into(),
+                output: Arc::new(Box::new(a_output)),
+                transformation: None,
+                forward_method: true,
+                forward_signature: false,
+                on_error: ErrorPolicy::Propagate,
+            },
+        ),
+        (
+            "without-method".into(),
+            IntegrationState {
+                verifier: NoVerifier.into(),
+                output: Arc::new(Box::new(b_output)),
+                transformation: None,
+                forward_method: false,
+                forward_signature: false,
+                on_error: ErrorPolicy::Propagate,
+            },
+        ),
+    ]
+    .into_iter()
+    .collect();
+    let state = InternalState::new(state_map, tx);
+
+    let mut app = router().with_state(state);
+
+    for uri in ["/webhook/with-method", "/webhook/without-method"] {
+        let request = Request::builder()
+            .uri(uri)
+            .method("PUT")
+            .header("content-type", "application/json")
+            .body(serde_json::to_vec(&json!({"a": true})).unwrap().into())
+            .unwrap();
+
+        let response = ServiceExt::<Request<Body>>::ready(&mut app)
+            .await
+            .unwrap()
+            .call(request)
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+    }
+
+    let forwarded = a_rx.try_recv().unwrap();
+    assert_eq!(
+        serde_json::to_value(forwarded).unwrap(),
+        json!({"payload": {"a": true}, "method": "PUT", "path": "/webhook/with-method"})
+    );
+
+    let forwarded = b_rx.try_recv().unwrap();
+    assert_eq!(
+        serde_json::to_value(forwarded).unwrap(),
+        json!({"payload": {"a": true}})
+    );
+}
+
+#[tokio::test]
+async fn test_forward_signature() {
+    let (tx, _rx) = tokio::sync::mpsc::unbounded_channel();
+    let (a_output, mut a_rx) = FakeForwardRequestOutput::new();
+    let (b_output, mut b_rx) = FakeForwardRequestOutput::new();
+
+    let webhook = Arc::new(Webhook::new("whsec_C2FVsBQIhrscChlQIMV+b5sSYspob7oD").unwrap());
+    let state_map = [
+        (
+            "with-signature".into(),
+            IntegrationState {
+                verifier: SvixVerifier::new(webhook.clone()).into(),
+                output: Arc::new(Box::new(a_output)),
+                transformation: None,
+                forward_method: false,
+                forward_signature: true,
+                on_error: ErrorPolicy::Propagate,
+            },
+        ),
+        (
+            "without-signature".into(),
+            IntegrationState {
+                verifier: SvixVerifier::new(webhook.clone()).into(),
+                output: Arc::new(Box::new(b_output)),
+                transformation: None,
+                forward_method: false,
+                forward_signature: false,
+                on_error: ErrorPolicy::Propagate,
+            },
+        ),
+    ]
+    .into_iter()
+    .collect();
+    let state = InternalState::new(state_map, tx);
+
+    let mut app = router().with_state(state);
+
+    let payload_bytes = serde_json::to_vec(&json!({"a": true})).unwrap();
+    let timestamp = chrono::Utc::now().timestamp();
+    let signature = webhook
+        .sign("msg_valid", timestamp, &payload_bytes)
+        .unwrap();
+
+    for uri in ["/webhook/with-signature", "/webhook/without-signature"] {
+        let request = Request::builder()
+            .uri(uri)
+            .method("POST")
+            .header("content-type", "application/json")
+            .header("svix-id", "msg_valid")
+            .header("svix-signature", signature.clone())
+            .header("svix-timestamp", timestamp.to_string())
+            .header("x-custom", "not forwarded")
+            .body(payload_bytes.clone().into())
+            .unwrap();
+
+        let response = ServiceExt::<Request<Body>>::ready(&mut app)
+            .await
+            .unwrap()
+            .call(request)
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+    }
+
+    // The original headers are forwarded verbatim, so the webhook can be verified again
+    let forwarded = a_rx.try_recv().unwrap();
+    assert_eq!(
+        serde_json::to_value(&forwarded).unwrap(),
+        json!({
+            "payload": {"a": true},
+            "signature_headers": {
+                "svix-id": "msg_valid",
+                "svix-signature": signature,
+                "svix-timestamp": format!("{timestamp}"),
+            },
+        })
+    );
+    let headers: http::HeaderMap = forwarded
+        .signature_headers
+        .unwrap()
+        .iter()
+        .map(|(k, v)| (k.parse().unwrap(), v.parse().unwrap()))
+        .collect();
+    webhook.verify(&payload_bytes, &headers).unwrap();
+
+    let forwarded = b_rx.try_recv().unwrap();
+    assert_eq!(
+        serde_json::to_value(forwarded).unwrap(),
+        json!({"payload": {"a": true}})
+    );
+}
+
+#[tokio::test]
+async fn test_verify_only_proxy() {
+    let (tx, _rx) = tokio::sync::mpsc::unbounded_channel();
+
+    let webhook = Arc::new(Webhook::new("whsec_C2FVsBQIhrscChlQIMV+b5sSYspob7oD").unwrap());
+
+    let payload_bytes = b"not necessarily json".to_vec();
+    let timestamp = chrono::Utc::now().timestamp();
+    let signature = webhook
+        .sign("msg_valid", timestamp, &payload_bytes)
+        .unwrap();
+
+    let mock_server = MockServer::start().await;
+    Mock::given(method("PATCH"))
+        .and(path("/upstream"))
+        .and(header("content-type", "text/plain"))
+        .and(header("x-custom", "some value"))
+        .and(header("svix-id", "msg_valid"))
+        .and(header("svix-signature", signature.as_str()))
+        .and(header("svix-timestamp", format!("{timestamp}").as_str()))
+        .and(body_bytes(payload_bytes.clone()))
+        .respond_with(ResponseTemplate::new(202))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    let proxies = [(
+        "a".into(),
+        ProxyState {
+            verifier: SvixVerifier::new(webhook).into(),
+            upstream_url: format!("{}/upstream", mock_server.uri()),
+            auth: None,
+        },
+    )]
+    .into_iter()
+    .collect();
+    let state = InternalState::new(Default::default(), tx).with_proxies(proxies);
+    let app = router().with_state(state);
+    let response = app
+        .oneshot(
+            Request::builder()
+                .uri("/webhook/a")
+                .method("PATCH")
+                .header("host", "bridge.example.com")
+                .header("content-type", "text/plain")
+                .header("x-custom", "some value")
+                .header("svix-id", "msg_valid")
+                .header("svix-signature", signature.clone())
+                .header("svix-timestamp", timestamp.to_string())
                 .body(payload_bytes.into())
                 .unwrap(),
         )
         .await
         .unwrap();
+
+    // The upstream's status is passed back to the sender
+    assert_eq!(response.status(), StatusCode::ACCEPTED);
+
+    let received = mock_server.received_requests().await.unwrap();
+    assert_eq!(received.len(), 1);
+    // The host header is for the connection to the bridge, so it's not passed along
+    assert_ne!(
+        received[0].headers.get("host").unwrap(),
+        "bridge.example.com"
+    );
+}
+
+#[tokio::test]
+async fn test_verify_only_proxy_auth() {
+    for (auth, expected) in [
+        (
+            OutputAuth::Bearer {
+                token: "secret-token".to_owned(),
+            },
+            "Bearer secret-token",
+        ),
+        (
+            OutputAuth::Basic {
+                username: "bridge".to_owned(),
+                password: "secret-password".to_owned(),
+            },
+            // base64 of `bridge:secret-password`
+            "Basic YnJpZGdlOnNlY3JldC1wYXNzd29yZA==",
+        ),
+    ] {
+        let (tx, _rx) = tokio::sync::mpsc::unbounded_channel();
+
+        let mock_server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(header("authorization", expected))
+            .respond_with(ResponseTemplate::new(204))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let proxies = [(
+            "a".into(),
+            ProxyState {
+                verifier: NoVerifier.into(),
+                upstream_url: mock_server.uri(),
+                auth: Some(auth),
+            },
+        )]
+        .into_iter()
+        .collect();
+        let state = InternalState::new(Default::default(), tx).with_proxies(proxies);
+        let app = router().with_state(state);
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/webhook/a")
+                    .method("POST")
+                    .header("content-type", "application/json")
+                    // Replaced, rather than passed along next to the configured credentials
+                    .header("authorization", "Bearer from-the-sender")
+                    .body(serde_json::to_vec(&json!({"a": true})).unwrap().into())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+
+        let received = mock_server.received_requests().await.unwrap();
+        assert_eq!(received.len(), 1);
+        assert_eq!(
+            received[0].headers.get_all("authorization").iter().count(),
+            1
+        );
+    }
+}
+
+#[tokio::test]
+async fn test_verify_only_proxy_verification_mismatch() {
+    let (tx, _rx) = tokio::sync::mpsc::unbounded_channel();
+
+    let webhook = Arc::new(Webhook::new("whsec_C2FVsBQIhrscChlQIMV+b5sSYspob7oD").unwrap());
+
+    let mock_server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .respond_with(ResponseTemplate::new(204))
+        .expect(0)
+        .mount(&mock_server)
+        .await;
+
+    let proxies = [(
+        "a".into(),
+        ProxyState {
+            verifier: SvixVerifier::new(webhook).into(),
+            upstream_url: mock_server.uri(),
+            auth: None,
+        },
+    )]
+    .into_iter()
+    .collect();
+    let state = InternalState::new(Default::default(), tx).with_proxies(proxies);
+    let app = router().with_state(state);
+    let response = app
+        .oneshot(
+            Request::builder()
+                .uri("/webhook/a")
+                .method("POST")
+                .header("content-type", "application/json")
+                .header("svix-id", "msg_invalid")
+                .header("svix-signature", "v1,invalid")
+                .header(
+                    "svix-timestamp",
+                    &format!("{}", chrono::Utc::now().timestamp()),
+                )
+                .body(serde_json::to_vec(&json!({"a": true})).unwrap().into())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+/// Every request is recorded in the audit log, including those failing verification.
+#[tokio::test]
+async fn test_audit_log() {
+    let (tx, _rx) = tokio::sync::mpsc::unbounded_channel();
+    let (a_output, mut a_rx) = FakeReceiverOutput::new();
+
+    let webhook = Arc::new(Webhook::new("whsec_C2FVsBQIhrscChlQIMV+b5sSYspob7oD").unwrap());
+    let payload_bytes = serde_json::to_vec(&json!({"a": true})).unwrap();
+    let timestamp = chrono::Utc::now().timestamp();
+    let signature = webhook
+        .sign("msg_valid", timestamp, &payload_bytes)
+        .unwrap();
+
+    let file_path = std::env::temp_dir().join(format!(
+        "svix-bridge-test-audit-log-{}.jsonl",
+        KsuidMs::new(None, None)
+    ));
+    let state_map = [(
+        "a".into(),
+        IntegrationState {
+            verifier: SvixVerifier::new(webhook).into(),
+            output: Arc::new(Box::new(a_output)),
+            transformation: None,
+            forward_method: false,
+            forward_signature: false,
+            on_error: ErrorPolicy::Propagate,
+        },
+    )]
+    .into_iter()
+    .collect();
+    let audit_logs = [(
+        "a".into(),
+        AuditLog::new(AuditLogConfig {
+            file_path: file_path.clone(),
+            format: AuditLogFormat::JsonLines,
+            max_file_bytes: 1024 * 1024,
+        }),
+    )]
+    .into_iter()
+    .collect();
+    let state = InternalState::new(state_map, tx).with_audit_logs(audit_logs);
+    let mut app = router().with_state(state);
+
+    for (msg_id, signature) in [
+        ("msg_valid", signature.as_str()),
+        ("msg_invalid", "v1,invalid"),
+    ] {
+        let request = Request::builder()
+            .uri("/webhook/a")
+            .method("POST")
+            .header("content-type", "application/json")
+            .header("svix-id", msg_id)
+            .header("svix-signature", signature)
+            .header("svix-timestamp", timestamp.to_string())
+            .body(payload_bytes.clone().into())
+            .unwrap();
+        let response = ServiceExt::<Request<Body>>::ready(&mut app)
+            .await
+            .unwrap()
+            .call(request)
+            .await
+            .unwrap();
+        let expected = if msg_id == "msg_valid" {
+            StatusCode::NO_CONTENT
+        } else {
+            StatusCode::BAD_REQUEST
+        };
+        assert_eq!(response.status(), expected);
+    }
+    assert!(a_rx.try_recv().is_ok());
+
+    let contents = std::fs::read_to_string(&file_path).unwrap();
+    std::fs::remove_file(&file_path).unwrap();
+    let records: Vec<serde_json::Value> = contents
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(records.len(), 2);
+    for (record, msg_id) in records.iter().zip(["msg_valid", "msg_invalid"]) {
+        assert_eq!(record["integration_id"], "a");
+        assert_eq!(record["method"], "POST");
+        assert_eq!(record["path"], "/webhook/a");
+        assert_eq!(record["headers"]["svix-id"], msg_id);
+        assert!(record["body_hash"].as_str().unwrap().starts_with("sha256:"));
+    }
+}
+
+#[tokio::test]
+async fn test_duplicate_path_ids_is_err() {
+    let (tx, _rx) = tokio::sync::mpsc::unbounded_channel();
+    let routes: Vec<WebhookReceiverConfig> = serde_yaml::from_str(
+        r#"
+        - name: "first"
+          input:
+            type: "webhook"
+            path_id: "shared"
+            verification:
+              type: "none"
+          mode:
+            type: "verify-only"
+            upstream_url: "http://localhost:8080/a"
+        - name: "second"
+          input:
+            type: "webhook"
+            path_id: "shared"
+            verification:
+              type: "none"
+          mode:
+            type: "verify-only"
+            upstream_url: "http://localhost:8080/b"
+        "#,
+    )
+    .unwrap();
+
+    let err = InternalState::from_receiver_configs(routes, tx)
+        .await
+        .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "receivers `first` and `second` both use the path_id `shared`"
+    );
+}
+
+#[test]
+fn test_transformation_warnings() {
+    let routes: Vec<WebhookReceiverConfig> = serde_yaml::from_str(
+        r#"
+        - name: "valid"
+          input:
+            type: "webhook"
+            path_id: "valid"
+            verification:
+              type: "none"
+          transformation: "handler = (x) => ({ payload: x })"
+        - name: "invalid"
+          input:
+            type: "webhook"
+            path_id: "invalid"
+            verification:
+              type: "none"
+          transformation: "function handler(x) { return { payload: x }"
+        - name: "untransformed"
+          input:
+            type: "webhook"
+            path_id: "untransformed"
+            verification:
+              type: "none"
+        "#,
+    )
+    .unwrap();
+
+    let warnings = transformation_warnings(&routes);
+    assert_eq!(warnings.len(), 1);
+    assert!(warnings[0].starts_with("failed to parse transformation for receiver `invalid`"));
+    assert!(transformation_warnings(&routes[..1]).is_empty());
+}
+
+#[tokio::test]
+async fn test_max_payload_bytes() {
+    const LIMIT: usize = 64;
+
+    let (tx, _rx) = tokio::sync::mpsc::unbounded_channel();
+    let (a_output, mut a_rx) = FakeReceiverOutput::new();
+    let state_map = [(
+        "a".into(),
+        IntegrationState {
+            verifier: NoVerifier.into(),
+            output: Arc::new(Box::new(a_output)),
+            transformation: None,
+            forward_method: false,
+            forward_signature: false,
+            on_error: ErrorPolicy::Propagate,
+        },
+    )]
+    .into_iter()
+    .collect();
+    let state = InternalState::new(state_map, tx);
+    let mut app = router()
+        .layer(DefaultBodyLimit::max(LIMIT))
+        .with_state(state);
+
+    // `{"a":""}` plus the padding
+    let body_of_len =
+        |len: usize| serde_json::to_vec(&json!({ "a": "x".repeat(len - 8) })).unwrap();
+
+    let at_limit = body_of_len(LIMIT);
+    assert_eq!(at_limit.len(), LIMIT);
+    let request = Request::builder()
+        .uri("/webhook/a")
+        .method("POST")
+        .header("content-type", "application/json")
+        .body(at_limit.into())
+        .unwrap();
+    let response = ServiceExt::<Request<Body>>::ready(&mut app)
+        .await
+        .unwrap()
+        .call(request)
+        .await
+        .unwrap();
     assert_eq!(response.status(), StatusCode::NO_CONTENT);
+    assert!(a_rx.try_recv().is_ok());
+
+    let over_limit = body_of_len(LIMIT + 1);
+    let request = Request::builder()
+        .uri("/webhook/a")
+        .method("POST")
+        .header("content-type", "application/json")
+        .body(over_limit.into())
+        .unwrap();
+    let response = ServiceExt::<Request<Body>>::ready(&mut app)
+        .await
+        .unwrap()
+        .call(request)
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+    assert!(a_rx.try_recv().is_err());
+}
+
+#[tokio::test]
+async fn test_tls() {
+    let (tx, _rx) = tokio::sync::mpsc::unbounded_channel();
+    let (a_output, mut a_rx) = FakeReceiverOutput::new();
+    let state_map = [(
+        "a".into(),
+        IntegrationState {
+            verifier: NoVerifier.into(),
+            output: Arc::new(Box::new(a_output)),
+            transformation: None,
+            forward_method: false,
+            forward_signature: false,
+            on_error: ErrorPolicy::Propagate,
+        },
+    )]
+    .into_iter()
+    .collect();
+    let state = InternalState::new(state_map, tx);
+
+    let cert = rcgen::generate_simple_self_signed(vec!["localhost".to_owned()]).unwrap();
+    let cert_pem = cert.serialize_pem().unwrap();
+    let dir =
+        std::env::temp_dir().join(format!("svix-bridge-test-tls-{}", KsuidMs::new(None, None)));
+    std::fs::create_dir_all(&dir).unwrap();
+    let tls = TlsConfig {
+        cert_path: dir.join("cert.pem"),
+        key_path: dir.join("key.pem"),
+    };
+    std::fs::write(&tls.cert_path, &cert_pem).unwrap();
+    std::fs::write(&tls.key_path, cert.serialize_private_key_pem()).unwrap();
+
+    let listen_addr = std::net::TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap();
+    let handle = tokio::spawn(serve(listen_addr, Some(tls), router().with_state(state)));
+
+    let client = reqwest::Client::builder()
+        .add_root_certificate(reqwest::Certificate::from_pem(cert_pem.as_bytes()).unwrap())
+        .resolve("localhost", listen_addr)
+        .build()
+        .unwrap();
+    let url = format!("https://localhost:{}/webhook/a", listen_addr.port());
+    let mut response = None;
+    // The server may take a moment to start listening
+    for _ in 0..50 {
+        match client
+            .post(&url)
+            .header("content-type", "application/json")
+            .body(serde_json::to_vec(&json!({"a": true})).unwrap())
+            .send()
+            .await
+        {
+            Ok(resp) => {
+                response = Some(resp);
+                break;
+            }
+            Err(e) if e.is_connect() => {
+                tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+            }
+            Err(e) => panic!("request failed: {e}"),
+        }
+    }
+    handle.abort();
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    assert_eq!(
+        response.expect("server never started").status(),
+        StatusCode::NO_CONTENT
+    );
     let forwarded = a_rx.try_recv().unwrap();
     assert_eq!(json!(forwarded), json!({"a": true}));
 }
+
+#[tokio::test]
+async fn test_poller_max_messages_per_batch() {
+    let mock_server = MockServer::start().await;
+    let message = |i: usize| {
+        json!({
+            "eventType": "testing.things",
+            "id": format!("msg_{i}"),
+            "payload": { "i": i },
+            "timestamp": "2024-01-01T00:00:00Z",
+        })
+    };
+    Mock::given(method("GET"))
+        .and(path("/api/v1/app/app_1/events/subscription/sub_1"))
+        .and(query_param("limit", "2"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "data": [message(0), message(1)],
+            "done": false,
+            "iterator": "next",
+        })))
+        .mount(&mock_server)
+        .await;
+
+    let (tx, _rx) = tokio::sync::mpsc::unbounded_channel();
+    let (output, mut output_rx) = FakeReceiverOutput::new();
+    let input_opts = PollerInputOpts::SvixEvents {
+        subscription_token: MessageStreamBridgeConfig {
+            token: "testsk_token".to_owned(),
+            app_id: "app_1".to_owned(),
+            subscription_id: "sub_1".to_owned(),
+        },
+        svix_options: Some(svix_bridge_types::SvixOptions {
+            debug: false,
+            server_url: Some(mock_server.uri()),
+            timeout_secs: None,
+        }),
+        max_messages_per_batch: Some(2),
+        initial_since: None,
+        channels: None,
+        app_ids: None,
+    };
+    let poller = SvixEventsPoller {
+        name: "poller".to_owned(),
+        svix_client: input_opts.svix_client().unwrap(),
+        input_opts,
+        transformation: None,
+        transformer_tx: Some(tx),
+        transformation_max_output_bytes: default_transformation_max_output_bytes(),
+        output: Arc::new(Box::new(output)),
+    };
+    let handle = tokio::spawn(async move { run_inner(&poller).await });
+
+    // The first batch stops at the limit, and the next one is requested from its iterator
+    let first = output_rx.recv().await.unwrap();
+    let second = output_rx.recv().await.unwrap();
+    assert_eq!(first["id"], "msg_0");
+    assert_eq!(second["id"], "msg_1");
+
+    let third = output_rx.recv().await.unwrap();
+    assert_eq!(third["id"], "msg_0");
+    handle.abort();
+
+    let requests = mock_server.received_requests().await.unwrap();
+    assert!(requests.len() >= 2);
+    for request in &requests {
+        assert!(request
+            .url
+            .query_pairs()
+            .any(|(key, value)| key == "limit" && value == "2"));
+    }
+    assert!(requests[1]
+        .url
+        .query_pairs()
+        .any(|(key, value)| key == "iterator" && value == "next"));
+}
+
+/// A batch of messages following failed polls is followed by the next poll straight away, rather
+/// than after the backoff the failures built up.
+#[tokio::test]
+async fn test_poller_backoff_reset() {
+    const FAILURES: u64 = 6;
+
+    let mock_server = MockServer::start().await;
+    let polled_at = Arc::new(Mutex::new(Vec::new()));
+    let respond = |response: ResponseTemplate| {
+        let polled_at = polled_at.clone();
+        move |_: &wiremock::Request| {
+            polled_at.lock().unwrap().push(Instant::now());
+            response.clone()
+        }
+    };
+    // Backs off for 10ms, 20ms, ... up to 320ms
+    Mock::given(method("GET"))
+        .and(path("/api/v1/app/app_1/events/subscription/sub_1"))
+        .respond_with(respond(ResponseTemplate::new(500)))
+        .up_to_n_times(FAILURES)
+        .mount(&mock_server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/api/v1/app/app_1/events/subscription/sub_1"))
+        .respond_with(respond(ResponseTemplate::new(200).set_body_json(json!({
+            "data": [{
+                "eventType": "testing.things",
+                "id": "msg_1",
+                "payload": { "a": true },
+                "timestamp": "2024-01-01T00:00:00Z",
+            }],
+            "done": true,
+            "iterator": "next",
+        }))))
+        .up_to_n_times(1)
+        .mount(&mock_server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/api/v1/app/app_1/events/subscription/sub_1"))
+        .respond_with(respond(ResponseTemplate::new(200).set_body_json(json!({
+            "data": [],
+            "done": true,
+            "iterator": "next",
+        }))))
+        .mount(&mock_server)
+        .await;
+
+    let (tx, _rx) = tokio::sync::mpsc::unbounded_channel();
+    let (output, mut output_rx) = FakeReceiverOutput::new();
+    let input_opts = PollerInputOpts::SvixEvents {
+        subscription_token: MessageStreamBridgeConfig {
+            token: "testsk_token".to_owned(),
+            app_id: "app_1".to_owned(),
+            subscription_id: "sub_1".to_owned(),
+        },
+        svix_options: Some(svix_bridge_types::SvixOptions {
+            debug: false,
+            server_url: Some(mock_server.uri()),
+            timeout_secs: None,
+        }),
+        max_messages_per_batch: None,
+        initial_since: None,
+        channels: None,
+        app_ids: None,
+    };
+    let poller = SvixEventsPoller {
+        name: "poller".to_owned(),
+        svix_client: input_opts.svix_client().unwrap(),
+        input_opts,
+        transformation: None,
+        transformer_tx: Some(tx),
+        transformation_max_output_bytes: default_transformation_max_output_bytes(),
+        output: Arc::new(Box::new(output)),
+    };
+    let handle = tokio::spawn(async move { run_inner(&poller).await });
+
+    let msg = output_rx.recv().await.unwrap();
+    assert_eq!(msg["id"], "msg_1");
+
+    // The poll after the batch
+    let polls = FAILURES as usize + 2;
+    tokio::time::timeout(Duration::from_secs(5), async {
+        while polled_at.lock().unwrap().len() < polls {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .unwrap();
+    handle.abort();
+
+    // Without the reset, the backoff built up by the failures would have doubled to 640ms
+    let polled_at = polled_at.lock().unwrap();
+    let since_batch = polled_at[polls - 1] - polled_at[polls - 2];
+    assert!(since_batch < Duration::from_millis(200), "{since_batch:?}");
+}
+
+#[tokio::test]
+async fn test_poller_initial_since() {
+    let mock_server = MockServer::start().await;
+    let message = |id: &str| {
+        json!({
+            "eventType": "testing.things",
+            "id": id,
+            "payload": {},
+            "timestamp": "2024-01-01T00:00:00Z",
+        })
+    };
+    Mock::given(method("GET"))
+        .and(path("/api/v1/app/app_1/events/subscription/sub_1"))
+        .and(query_param("after", "2024-01-01T00:00:00Z"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "data": [message("msg_backlog")],
+            "done": false,
+            "iterator": "next",
+        })))
+        .mount(&mock_server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/api/v1/app/app_1/events/subscription/sub_1"))
+        .and(query_param("iterator", "next"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "data": [message("msg_next")],
+            "done": true,
+            "iterator": "next",
+        })))
+        .mount(&mock_server)
+        .await;
+
+    let (tx, _rx) = tokio::sync::mpsc::unbounded_channel();
+    let (output, mut output_rx) = FakeReceiverOutput::new();
+    let input_opts = PollerInputOpts::SvixEvents {
+        subscription_token: MessageStreamBridgeConfig {
+            token: "testsk_token".to_owned(),
+            app_id: "app_1".to_owned(),
+            subscription_id: "sub_1".to_owned(),
+        },
+        svix_options: Some(svix_bridge_types::SvixOptions {
+            debug: false,
+            server_url: Some(mock_server.uri()),
+            timeout_secs: None,
+        }),
+        max_messages_per_batch: None,
+        initial_since: Some("2024-01-01T00:00:00Z".parse().unwrap()),
+        channels: None,
+        app_ids: None,
+    };
+    let poller = SvixEventsPoller {
+        name: "poller".to_owned(),
+        svix_client: input_opts.svix_client().unwrap(),
+        input_opts,
+        transformation: None,
+        transformer_tx: Some(tx),
+        transformation_max_output_bytes: default_transformation_max_output_bytes(),
+        output: Arc::new(Box::new(output)),
+    };
+    let handle = tokio::spawn(async move { run_inner(&poller).await });
+
+    // The first request starts from `initial_since`, after which the iterator takes over
+    let first = output_rx.recv().await.unwrap();
+    let second = output_rx.recv().await.unwrap();
+    assert_eq!(first["id"], "msg_backlog");
+    assert_eq!(second["id"], "msg_next");
+    handle.abort();
+
+    let requests = mock_server.received_requests().await.unwrap();
+    assert!(requests.len() >= 2);
+    assert!(requests[0]
+        .url
+        .query_pairs()
+        .all(|(key, _)| key != "iterator"));
+    for request in &requests[1..] {
+        assert!(request.url.query_pairs().all(|(key, _)| key != "after"));
+    }
+}
+
+#[tokio::test]
+async fn test_poller_channels() {
+    let mock_server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/api/v1/app/app_1/events/subscription/sub_1"))
+        .and(query_param("channels", "tenant-a,tenant-b"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "data": [{
+                "eventType": "testing.things",
+                "id": "msg_1",
+                "payload": {},
+                "timestamp": "2024-01-01T00:00:00Z",
+            }],
+            "done": true,
+            "iterator": "next",
+        })))
+        .mount(&mock_server)
+        .await;
+
+    let (tx, _rx) = tokio::sync::mpsc::unbounded_channel();
+    let (output, mut output_rx) = FakeReceiverOutput::new();
+    let input_opts = PollerInputOpts::SvixEvents {
+        subscription_token: MessageStreamBridgeConfig {
+            token: "testsk_token".to_owned(),
+            app_id: "app_1".to_owned(),
+            subscription_id: "sub_1".to_owned(),
+        },
+        svix_options: Some(svix_bridge_types::SvixOptions {
+            debug: false,
+            server_url: Some(mock_server.uri()),
+            timeout_secs: None,
+        }),
+        max_messages_per_batch: None,
+        initial_since: None,
+        channels: Some(vec!["tenant-a".to_owned(), "tenant-b".to_owned()]),
+        app_ids: None,
+    };
+    let poller = SvixEventsPoller {
+        name: "poller".to_owned(),
+        svix_client: input_opts.svix_client().unwrap(),
+        input_opts,
+        transformation: None,
+        transformer_tx: Some(tx),
+        transformation_max_output_bytes: default_transformation_max_output_bytes(),
+        output: Arc::new(Box::new(output)),
+    };
+    let handle = tokio::spawn(async move { run_inner(&poller).await });
+
+    // Only requests filtering on the configured channels get a response
+    let first = output_rx.recv().await.unwrap();
+    assert_eq!(first["id"], "msg_1");
+    handle.abort();
+
+    let requests = mock_server.received_requests().await.unwrap();
+    assert!(!requests.is_empty());
+    for request in &requests {
+        assert!(request
+            .url
+            .query_pairs()
+            .any(|(key, value)| key == "channels" && value == "tenant-a,tenant-b"));
+    }
+}
+
+#[tokio::test]
+async fn test_poller_app_ids() {
+    let mock_server = MockServer::start().await;
+    let message = |id: String| {
+        json!({
+            "eventType": "testing.things",
+            "id": id,
+            "payload": {},
+            "timestamp": "2024-01-01T00:00:00Z",
+        })
+    };
+    // `app_3` isn't mocked, so polling it always fails
+    for app_id in ["app_1", "app_2"] {
+        let events_path = format!("/api/v1/app/{app_id}/events/subscription/sub_1");
+        Mock::given(method("GET"))
+            .and(path(events_path.as_str()))
+            .and(query_param("after", "2024-01-01T00:00:00Z"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "data": [message(format!("{app_id}_backlog"))],
+                "done": false,
+                "iterator": format!("{app_id}_next"),
+            })))
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path(events_path.as_str()))
+            .and(query_param("iterator", format!("{app_id}_next")))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "data": [message(format!("{app_id}_next"))],
+                "done": true,
+                "iterator": format!("{app_id}_next"),
+            })))
+            .mount(&mock_server)
+            .await;
+    }
+
+    let (tx, _rx) = tokio::sync::mpsc::unbounded_channel();
+    let (output, mut output_rx) = FakeReceiverOutput::new();
+    let input_opts = PollerInputOpts::SvixEvents {
+        subscription_token: MessageStreamBridgeConfig {
+            token: "testsk_token".to_owned(),
+            app_id: "app_1".to_owned(),
+            subscription_id: "sub_1".to_owned(),
+        },
+        svix_options: Some(svix_bridge_types::SvixOptions {
+            debug: false,
+            server_url: Some(mock_server.uri()),
+            timeout_secs: None,
+        }),
+        max_messages_per_batch: None,
+        initial_since: Some("2024-01-01T00:00:00Z".parse().unwrap()),
+        channels: None,
+        app_ids: Some(vec![
+            "app_1".to_owned(),
+            "app_2".to_owned(),
+            "app_3".to_owned(),
+        ]),
+    };
+    let poller = SvixEventsPoller {
+        name: "poller".to_owned(),
+        svix_client: input_opts.svix_client().unwrap(),
+        input_opts,
+        transformation: None,
+        transformer_tx: Some(tx),
+        transformation_max_output_bytes: default_transformation_max_output_bytes(),
+        output: Arc::new(Box::new(output)),
+    };
+    let handle = tokio::spawn(async move { run_inner(&poller).await });
+
+    // Both working apps get through their backlog, despite the failing one
+    let mut received = std::collections::HashSet::new();
+    while received.len() < 4 {
+        let msg = output_rx.recv().await.unwrap();
+        received.insert(msg["id"].as_str().unwrap().to_owned());
+    }
+    handle.abort();
+    assert_eq!(
+        received,
+        ["app_1_backlog", "app_1_next", "app_2_backlog", "app_2_next"]
+            .into_iter()
+            .map(ToOwned::to_owned)
+            .collect()
+    );
+
+    // Each app moves on using its own iterator, and the failing one never gets one
+    let requests = mock_server.received_requests().await.unwrap();
+    for app_id in ["app_1", "app_2", "app_3"] {
+        let iterators: Vec<_> = requests
+            .iter()
+            .filter(|request| request.url.path().contains(&format!("/app/{app_id}/")))
+            .filter_map(|request| {
+                request
+                    .url
+                    .query_pairs()
+                    .find(|(key, _)| key == "iterator")
+                    .map(|(_, value)| value.into_owned())
+            })
+            .collect();
+        if app_id == "app_3" {
+            assert!(iterators.is_empty());
+        } else {
+            assert!(!iterators.is_empty());
+            assert!(iterators.iter().all(|it| *it == format!("{app_id}_next")));
+        }
+    }
+}
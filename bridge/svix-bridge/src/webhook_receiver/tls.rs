@@ -0,0 +1,60 @@
+//! TLS termination for webhook receivers.
+//!
+//! The certificate and key files are polled for changes, and reloaded whenever they do change,
+//! so that renewed certificates are picked up without restarting Bridge.
+
+use std::{io, time::Duration};
+
+use axum_server::tls_rustls::RustlsConfig;
+use notify::{PollWatcher, RecursiveMode, Watcher};
+
+use crate::config::TlsConfig;
+
+/// How often the certificate and key files are checked for changes.
+const POLL_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Loads the certificate and key, and starts watching their files for changes.
+pub(super) async fn load(cfg: TlsConfig) -> io::Result<RustlsConfig> {
+    let rustls_config = RustlsConfig::from_pem_file(&cfg.cert_path, &cfg.key_path).await?;
+    watch(cfg, rustls_config.clone())?;
+    Ok(rustls_config)
+}
+
+fn watch(cfg: TlsConfig, rustls_config: RustlsConfig) -> io::Result<()> {
+    // A single slot is enough: if a reload is already pending, it'll read the latest files anyway.
+    let (tx, mut rx) = tokio::sync::mpsc::channel(1);
+    let mut watcher = PollWatcher::new(
+        move |res: notify::Result<notify::Event>| match res {
+            Ok(_) => {
+                let _ = tx.try_send(());
+            }
+            Err(e) => tracing::warn!("failed to check TLS certificate files for changes: {e}"),
+        },
+        notify::Config::default().with_poll_interval(POLL_INTERVAL),
+    )
+    .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+    for path in [&cfg.cert_path, &cfg.key_path] {
+        watcher
+            .watch(path, RecursiveMode::NonRecursive)
+            .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+    }
+
+    tokio::spawn(async move {
+        // Polling stops once the watcher is dropped
+        let _watcher = watcher;
+        while rx.recv().await.is_some() {
+            // When the certificate is only half updated, for example if the key has been written
+            // but the certificate hasn't yet, this fails and the previous one is kept until the
+            // next change.
+            match rustls_config
+                .reload_from_pem_file(&cfg.cert_path, &cfg.key_path)
+                .await
+            {
+                Ok(()) => tracing::info!("Reloaded TLS certificate"),
+                Err(e) => tracing::error!("Failed to reload TLS certificate: {e}"),
+            }
+        }
+    });
+
+    Ok(())
+}
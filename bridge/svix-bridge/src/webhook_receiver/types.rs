@@ -8,20 +8,37 @@ use axum::{
     BoxError,
 };
 use http::{HeaderMap, HeaderValue, Request};
+use regex::Regex;
 use serde::{Deserialize, Serialize};
 use svix_bridge_types::{
     svix, ReceiverInputOpts, ReceiverOutput, TransformationConfig, TransformerTx, WebhookVerifier,
 };
 
-use super::verification::{NoVerifier, SvixVerifier, VerificationMethod, Verifier};
-use crate::config::WebhookReceiverConfig;
+use super::{
+    audit_log::AuditLog,
+    verification::{NoVerifier, SvixVerifier, VerificationMethod, Verifier},
+};
+use crate::config::{
+    default_transformation_max_output_bytes, ErrorPolicy, OutputAuth, ReceiverMode,
+    WebhookReceiverConfig,
+};
 
 #[derive(Clone)]
 /// The [`InternalState`] is passed to the Axum route and is used to map the "IntegrationId" in the
 /// URL to the configured [`Verifier`] and [`Forwarder`] variants.
 pub struct InternalState {
     pub routes: Arc<HashMap<IntegrationId, IntegrationState>>,
+    /// Routes for receivers in `verify-only` mode, which proxy the raw request upstream.
+    pub proxies: Arc<HashMap<IntegrationId, ProxyState>>,
+    /// The audit logs of the receivers which have one, whatever their mode.
+    pub audit_logs: Arc<HashMap<IntegrationId, AuditLog>>,
+    /// The path patterns of the receivers which have one, in the order they're configured in. These
+    /// are anchored, so only match whole paths.
+    pub path_patterns: Arc<Vec<(Regex, IntegrationId)>>,
+    pub http_client: reqwest::Client,
     pub transformer_tx: TransformerTx,
+    /// The largest object, serialized as JSON, that a transformation may produce.
+    pub transformation_max_output_bytes: usize,
 }
 
 impl std::fmt::Debug for InternalState {
@@ -45,15 +62,64 @@ impl InternalState {
     ) -> Self {
         InternalState {
             routes: Arc::new(state_map),
+            proxies: Arc::new(HashMap::new()),
+            audit_logs: Arc::new(HashMap::new()),
+            path_patterns: Arc::new(Vec::new()),
+            http_client: reqwest::Client::new(),
             transformer_tx,
+            transformation_max_output_bytes: default_transformation_max_output_bytes(),
         }
     }
 
+    /// Sets the largest object, serialized as JSON, that a transformation may produce.
+    pub fn with_transformation_max_output_bytes(mut self, max_bytes: usize) -> Self {
+        self.transformation_max_output_bytes = max_bytes;
+        self
+    }
+
+    /// Adds the given `verify-only` routes to an [`InternalState`].
+    pub fn with_proxies(mut self, proxies: HashMap<IntegrationId, ProxyState>) -> Self {
+        self.proxies = Arc::new(proxies);
+        self
+    }
+
+    /// Adds the given audit logs to an [`InternalState`].
+    pub fn with_audit_logs(mut self, audit_logs: HashMap<IntegrationId, AuditLog>) -> Self {
+        self.audit_logs = Arc::new(audit_logs);
+        self
+    }
+
+    /// Adds the given path patterns to an [`InternalState`], which are tried in order.
+    pub fn with_path_patterns(mut self, path_patterns: Vec<(Regex, IntegrationId)>) -> Self {
+        self.path_patterns = Arc::new(path_patterns);
+        self
+    }
+
     pub async fn from_receiver_configs(
         routes: Vec<WebhookReceiverConfig>,
         transformer_tx: TransformerTx,
     ) -> Result<Self> {
+        let mut names_by_id: HashMap<&str, &str> = HashMap::new();
+        for cfg in &routes {
+            let path_id = cfg.input.path_id();
+            if let Some(other) = names_by_id.insert(path_id, &cfg.name) {
+                anyhow::bail!(
+                    "receivers `{other}` and `{}` both use the path_id `{path_id}`",
+                    cfg.name
+                );
+            }
+        }
+
+        // Left to fail when a webhook comes in, rather than keeping the other receivers from
+        // starting
+        for warning in transformation_warnings(&routes) {
+            tracing::warn!("{warning}");
+        }
+
         let mut state_map = HashMap::new();
+        let mut proxies = HashMap::new();
+        let mut audit_logs = HashMap::new();
+        let mut path_patterns = Vec::new();
 
         for cfg in routes {
             let verifier = match &cfg.input {
@@ -73,20 +139,60 @@ impl InternalState {
                 } => NoVerifier.into(),
             };
 
+            let integration_id = IntegrationId(cfg.input.path_id().to_string());
+            if let Some(audit_log) = &cfg.audit_log {
+                audit_logs.insert(integration_id.clone(), AuditLog::new(audit_log.clone()));
+            }
+            if let Some(path_pattern) = &cfg.path_pattern {
+                path_patterns.push((path_pattern.clone(), integration_id.clone()));
+            }
+            if let ReceiverMode::VerifyOnly { upstream_url, auth } = &cfg.mode {
+                proxies.insert(
+                    integration_id,
+                    ProxyState {
+                        verifier,
+                        upstream_url: upstream_url.clone(),
+                        auth: auth.clone(),
+                    },
+                );
+                continue;
+            }
+
             state_map.insert(
-                IntegrationId(cfg.input.path_id().to_string()),
+                integration_id,
                 IntegrationState {
                     verifier,
                     transformation: cfg.transformation.clone(),
+                    forward_method: cfg.forward_method,
+                    forward_signature: cfg.forward_signature,
+                    on_error: cfg.on_error,
                     output: Arc::new(cfg.into_receiver_output().await?),
                 },
             );
         }
 
-        Ok(InternalState::new(state_map, transformer_tx))
+        Ok(InternalState::new(state_map, transformer_tx)
+            .with_proxies(proxies)
+            .with_audit_logs(audit_logs)
+            .with_path_patterns(path_patterns))
     }
 }
 
+/// Checks that the receivers' transformations parse, giving a warning for each one which doesn't.
+pub(crate) fn transformation_warnings(routes: &[WebhookReceiverConfig]) -> Vec<String> {
+    routes
+        .iter()
+        .filter_map(|cfg| {
+            let tc = cfg.transformation.as_ref()?;
+            let e = crate::runtime::validate_script(tc.source()).err()?;
+            Some(format!(
+                "failed to parse transformation for receiver `{}`: {e:?}",
+                cfg.name
+            ))
+        })
+        .collect()
+}
+
 /// Each [`IntegrationId`] is a valid route for webhooks to be dispatched to managed by this server,
 /// and each [`IntegrationId`] has an associated configuration which defines how the webhook is
 /// verified (the [`VerificationScheme`]) and where the webhook is routed to once it is verified
@@ -134,6 +240,23 @@ pub struct IntegrationState {
     pub verifier: Verifier,
     pub output: Arc<Box<dyn ReceiverOutput>>,
     pub transformation: Option<TransformationConfig>,
+    /// Whether to add the inbound request's method and path to the [`ForwardRequest`].
+    pub forward_method: bool,
+    /// Whether to add the inbound request's signature headers to the [`ForwardRequest`].
+    pub forward_signature: bool,
+    /// How to respond when the output fails to handle a request.
+    pub on_error: ErrorPolicy,
+}
+
+/// The state associated with an [`IntegrationId`] whose receiver is in `verify-only` mode.
+///
+/// Once verified, requests are proxied as-is to the `upstream_url` rather than being handed to a
+/// [`ReceiverOutput`].
+#[derive(Clone)]
+pub struct ProxyState {
+    pub verifier: Verifier,
+    pub upstream_url: String,
+    pub auth: Option<OutputAuth>,
 }
 
 /// The [`RequestFromParts`] is a structure consisting of all relevant parts of the HTTP request to
@@ -387,6 +510,13 @@ impl SerializablePayload {
         })
     }
 
+    pub fn as_bytes(&self) -> &[u8] {
+        match self {
+            Self::Standard(v) => v,
+            Self::StringSerializable(s) => s.as_bytes(),
+        }
+    }
+
     pub fn as_string(&self) -> Result<String> {
         match self {
             Self::Standard(v) => Ok(String::from_utf8(v.clone())?),
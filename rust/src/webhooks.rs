@@ -28,13 +28,32 @@ pub enum WebhookError {
 
     #[error("payload invalid")]
     InvalidPayload,
+
+    #[error("invalid public key")]
+    InvalidPublicKey,
+
+    #[error("signing requires a secret, not a public key")]
+    SigningRequiresSecret,
 }
 
+/// Verifies the signatures of webhooks sent by Svix.
+///
+/// Endpoints are either signed with a symmetric secret (`whsec_...`), in which case the same
+/// secret is used to verify their webhooks, or with an Ed25519 key pair, in which case only the
+/// public key (`whpk_...`) is needed.
 pub struct Webhook {
-    key: Vec<u8>,
+    key: Key,
+}
+
+enum Key {
+    /// Signs and verifies `v1` (HMAC-SHA256) signatures
+    Symmetric(Vec<u8>),
+    /// Verifies `v1a` (Ed25519) signatures
+    Ed25519(ed25519_compact::PublicKey),
 }
 
 const PREFIX: &str = "whsec_";
+const PUBLIC_KEY_PREFIX: &str = "whpk_";
 const SVIX_MSG_ID_KEY: &str = "svix-id";
 const SVIX_MSG_SIGNATURE_KEY: &str = "svix-signature";
 const SVIX_MSG_TIMESTAMP_KEY: &str = "svix-timestamp";
@@ -43,20 +62,57 @@ const UNBRANDED_MSG_SIGNATURE_KEY: &str = "webhook-signature";
 const UNBRANDED_MSG_TIMESTAMP_KEY: &str = "webhook-timestamp";
 const TOLERANCE_IN_SECONDS: i64 = 5 * 60;
 const SIGNATURE_VERSION: &str = "v1";
+const ED25519_SIGNATURE_VERSION: &str = "v1a";
 
 impl Webhook {
+    /// Creates a verifier from an endpoint's signing secret, or from its public key when it's
+    /// signed with an Ed25519 key pair.
     pub fn new(secret: &str) -> Result<Self, WebhookError> {
+        if let Some(public_key) = secret.strip_prefix(PUBLIC_KEY_PREFIX) {
+            let public_key = base64::decode(public_key)?;
+            let public_key = ed25519_compact::PublicKey::from_slice(&public_key)
+                .map_err(|_| WebhookError::InvalidPublicKey)?;
+            return Ok(Webhook {
+                key: Key::Ed25519(public_key),
+            });
+        }
+
         let secret = secret.strip_prefix(PREFIX).unwrap_or(secret);
         let key = base64::decode(secret)?;
 
-        Ok(Webhook { key })
+        Ok(Webhook {
+            key: Key::Symmetric(key),
+        })
     }
 
     pub fn from_bytes(secret: Vec<u8>) -> Result<Self, WebhookError> {
-        Ok(Webhook { key: secret })
+        Ok(Webhook {
+            key: Key::Symmetric(secret),
+        })
     }
 
     pub fn verify<HM: HeaderMap>(&self, payload: &[u8], headers: &HM) -> Result<(), WebhookError> {
+        self.verify_inner(payload, headers, true)
+    }
+
+    /// Like [`Webhook::verify`], but accepts webhooks whatever their timestamp.
+    ///
+    /// Checking the timestamp is what protects against replay attacks, so this should only be
+    /// used where that's taken care of some other way.
+    pub fn verify_ignoring_timestamp<HM: HeaderMap>(
+        &self,
+        payload: &[u8],
+        headers: &HM,
+    ) -> Result<(), WebhookError> {
+        self.verify_inner(payload, headers, false)
+    }
+
+    fn verify_inner<HM: HeaderMap>(
+        &self,
+        payload: &[u8],
+        headers: &HM,
+        enforce_tolerance: bool,
+    ) -> Result<(), WebhookError> {
         let msg_id = Self::get_header(headers, SVIX_MSG_ID_KEY, UNBRANDED_MSG_ID_KEY, "id")?;
         let msg_signature = Self::get_header(
             headers,
@@ -72,45 +128,75 @@ impl Webhook {
         )
         .and_then(Self::parse_timestamp)?;
 
-        Self::verify_timestamp(msg_ts)?;
-
-        let versioned_signature = self.sign(msg_id, msg_ts, payload)?;
-        let expected_signature = versioned_signature
-            .split_once(',')
-            .map(|x| x.1)
-            .ok_or(WebhookError::InvalidSignature)?;
-
-        msg_signature
-            .split(' ')
-            .filter_map(|x| x.split_once(','))
-            .filter(|x| x.0 == SIGNATURE_VERSION)
-            .any(|x| {
-                (x.1.len() == expected_signature.len())
-                    && (x
-                        .1
-                        .bytes()
-                        .zip(expected_signature.bytes())
-                        .fold(0, |acc, (a, b)| acc | (a ^ b))
-                        == 0)
-            })
-            .then_some(())
-            .ok_or(WebhookError::InvalidSignature)
+        if enforce_tolerance {
+            Self::verify_timestamp(msg_ts)?;
+        }
+
+        let signatures = msg_signature.split(' ').filter_map(|x| x.split_once(','));
+
+        let verified = match &self.key {
+            Key::Symmetric(key) => {
+                let versioned_signature = Self::sign_with(key, msg_id, msg_ts, payload)?;
+                let expected_signature = versioned_signature
+                    .split_once(',')
+                    .map(|x| x.1)
+                    .ok_or(WebhookError::InvalidSignature)?;
+
+                signatures.filter(|x| x.0 == SIGNATURE_VERSION).any(|x| {
+                    (x.1.len() == expected_signature.len())
+                        && (x
+                            .1
+                            .bytes()
+                            .zip(expected_signature.bytes())
+                            .fold(0, |acc, (a, b)| acc | (a ^ b))
+                            == 0)
+                })
+            }
+            Key::Ed25519(public_key) => {
+                let to_sign = Self::to_sign(msg_id, msg_ts, payload)?;
+                signatures
+                    .filter(|x| x.0 == ED25519_SIGNATURE_VERSION)
+                    .filter_map(|x| base64::decode(x.1).ok())
+                    .filter_map(|x| ed25519_compact::Signature::from_slice(&x).ok())
+                    .any(|signature| public_key.verify(&to_sign, &signature).is_ok())
+            }
+        };
+
+        verified.then_some(()).ok_or(WebhookError::InvalidSignature)
     }
 
+    /// Signs a webhook the way Svix does, which is only possible with a symmetric secret.
     pub fn sign(
         &self,
         msg_id: &str,
         timestamp: i64,
         payload: &[u8],
     ) -> Result<String, WebhookError> {
-        let payload = std::str::from_utf8(payload).map_err(|_| WebhookError::InvalidPayload)?;
-        let to_sign = format!("{msg_id}.{timestamp}.{payload}",);
-        let signed = hmac_sha256::HMAC::mac(to_sign.as_bytes(), &self.key);
+        match &self.key {
+            Key::Symmetric(key) => Self::sign_with(key, msg_id, timestamp, payload),
+            Key::Ed25519(_) => Err(WebhookError::SigningRequiresSecret),
+        }
+    }
+
+    fn sign_with(
+        key: &[u8],
+        msg_id: &str,
+        timestamp: i64,
+        payload: &[u8],
+    ) -> Result<String, WebhookError> {
+        let to_sign = Self::to_sign(msg_id, timestamp, payload)?;
+        let signed = hmac_sha256::HMAC::mac(to_sign.as_bytes(), key);
         let encoded = base64::encode(signed);
 
         Ok(format!("{SIGNATURE_VERSION},{encoded}"))
     }
 
+    /// The content signatures are made over
+    fn to_sign(msg_id: &str, timestamp: i64, payload: &[u8]) -> Result<String, WebhookError> {
+        let payload = std::str::from_utf8(payload).map_err(|_| WebhookError::InvalidPayload)?;
+        Ok(format!("{msg_id}.{timestamp}.{payload}"))
+    }
+
     fn get_header<'a, HM: HeaderMap>(
         headers: &'a HM,
         svix_hdr: &'static str,
@@ -191,7 +277,7 @@ mod tests {
     use time::OffsetDateTime;
 
     use super::{
-        Webhook, SVIX_MSG_ID_KEY, SVIX_MSG_SIGNATURE_KEY, SVIX_MSG_TIMESTAMP_KEY,
+        Webhook, WebhookError, SVIX_MSG_ID_KEY, SVIX_MSG_SIGNATURE_KEY, SVIX_MSG_TIMESTAMP_KEY,
         UNBRANDED_MSG_ID_KEY, UNBRANDED_MSG_SIGNATURE_KEY, UNBRANDED_MSG_TIMESTAMP_KEY,
     };
 
@@ -378,6 +464,101 @@ mod tests {
         assert!(wh.verify(payload, &headers).is_err());
     }
 
+    // Reference vectors, matching what the server signs `test_generate_msg_headers_with_signing_key`
+    // webhooks with
+    const VECTOR_MSG_ID: &str = "msg_p5jXN8AQM9LWM0D4loKWxJek";
+    const VECTOR_TIMESTAMP: i64 = 1614265330;
+    const VECTOR_PAYLOAD: &[u8] = br#"{"test": 2432232314}"#;
+    const VECTOR_SECRET: &str = "whsec_MfKQ9r8GKYqrTwjUPD8ILPZIo2LaLaSw";
+    const VECTOR_SIGNATURE: &str = "v1,g0hM9SsE+OTPJTGt/tmIKtSyZlE3uFJELVlNIOLJ1OE=";
+    const VECTOR_PUBLIC_KEY: &str = "whpk_1SiA4o9hyqTCpIqC5V9HUakiiaeACeqfZTInDBbOir4=";
+    const VECTOR_ED25519_SIGNATURE: &str = "v1a,hnO3f9T8Ytu9HwrXslvumlUpqtNVqkhqw/enGzPCXe5BdqzCInXqYXFymVJaA7AZdpXwVLPo3mNl8EM+m7TBAg==";
+
+    fn get_vector_headers(signature: &str) -> HeaderMap {
+        let mut headers = get_svix_headers(VECTOR_MSG_ID, signature);
+        headers.insert(
+            SVIX_MSG_TIMESTAMP_KEY,
+            VECTOR_TIMESTAMP.to_string().parse().unwrap(),
+        );
+        headers
+    }
+
+    #[test]
+    fn test_reference_vectors() {
+        let wh = Webhook::new(VECTOR_SECRET).unwrap();
+        assert_eq!(
+            wh.sign(VECTOR_MSG_ID, VECTOR_TIMESTAMP, VECTOR_PAYLOAD)
+                .unwrap(),
+            VECTOR_SIGNATURE
+        );
+        wh.verify_ignoring_timestamp(VECTOR_PAYLOAD, &get_vector_headers(VECTOR_SIGNATURE))
+            .unwrap();
+
+        let wh = Webhook::new(VECTOR_PUBLIC_KEY).unwrap();
+        wh.verify_ignoring_timestamp(
+            VECTOR_PAYLOAD,
+            &get_vector_headers(VECTOR_ED25519_SIGNATURE),
+        )
+        .unwrap();
+
+        // The timestamps of the vectors are long past
+        assert!(matches!(
+            wh.verify(
+                VECTOR_PAYLOAD,
+                &get_vector_headers(VECTOR_ED25519_SIGNATURE)
+            ),
+            Err(WebhookError::TimestampTooOldError)
+        ));
+    }
+
+    #[test]
+    fn test_verify_ed25519() {
+        let wh = Webhook::new(VECTOR_PUBLIC_KEY).unwrap();
+
+        // Endpoints may be signed with several keys at once, of different types
+        let multi_sig = format!("{VECTOR_SIGNATURE} {VECTOR_ED25519_SIGNATURE}");
+        wh.verify_ignoring_timestamp(VECTOR_PAYLOAD, &get_vector_headers(&multi_sig))
+            .unwrap();
+
+        // An Ed25519 signature under the HMAC version, or an HMAC one, doesn't verify
+        for signature in [
+            VECTOR_SIGNATURE.to_owned(),
+            VECTOR_ED25519_SIGNATURE.replace("v1a,", "v1,"),
+            "v1a,invalid".to_owned(),
+        ] {
+            assert!(matches!(
+                wh.verify_ignoring_timestamp(VECTOR_PAYLOAD, &get_vector_headers(&signature)),
+                Err(WebhookError::InvalidSignature)
+            ));
+        }
+
+        // Nor does one over a different payload
+        assert!(wh
+            .verify_ignoring_timestamp(
+                br#"{"test": 2432232315}"#,
+                &get_vector_headers(VECTOR_ED25519_SIGNATURE)
+            )
+            .is_err());
+
+        // Public keys can only verify
+        assert!(matches!(
+            wh.sign(VECTOR_MSG_ID, VECTOR_TIMESTAMP, VECTOR_PAYLOAD),
+            Err(WebhookError::SigningRequiresSecret)
+        ));
+    }
+
+    #[test]
+    fn test_invalid_public_key() {
+        assert!(matches!(
+            Webhook::new("whpk_MfKQ9r8GKYqrTwjUPD8ILPZIo2LaLaSw"),
+            Err(WebhookError::InvalidPublicKey)
+        ));
+        assert!(matches!(
+            Webhook::new("whpk_%"),
+            Err(WebhookError::InvalidSecret(_))
+        ));
+    }
+
     #[test]
     fn test_missing_headers() {
         let secret = "whsec_C2FVsBQIhrscChlQIMV+b5sSYspob7oD".to_owned();
@@ -0,0 +1,3 @@
+DROP TABLE messagetag;
+
+ALTER TABLE message DROP COLUMN tags;
@@ -0,0 +1,14 @@
+ALTER TABLE message ADD COLUMN tags jsonb;
+
+CREATE TABLE messagetag (
+    msg_id character varying NOT NULL COLLATE pg_catalog."C",
+    tag character varying NOT NULL COLLATE pg_catalog."C",
+    created_at timestamp with time zone NOT NULL
+);
+
+ALTER TABLE ONLY messagetag
+    ADD CONSTRAINT pk_messagetag PRIMARY KEY (msg_id, tag);
+
+ALTER TABLE messagetag ADD CONSTRAINT fk_messagetag_msg_id_message FOREIGN KEY(msg_id) REFERENCES message (id) ON DELETE CASCADE;
+
+CREATE INDEX ix_messagetag_tag_msg_id ON messagetag (tag, msg_id);
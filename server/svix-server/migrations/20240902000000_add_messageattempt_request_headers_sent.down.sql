@@ -0,0 +1 @@
+ALTER TABLE messageattempt DROP COLUMN request_headers_sent;
@@ -0,0 +1 @@
+ALTER TABLE messageattempt ADD COLUMN request_headers_sent jsonb;
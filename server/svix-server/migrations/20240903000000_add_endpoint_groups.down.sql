@@ -0,0 +1,3 @@
+ALTER TABLE endpoint DROP COLUMN group_id;
+
+DROP TABLE endpointgroup;
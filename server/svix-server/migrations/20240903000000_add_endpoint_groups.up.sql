@@ -0,0 +1,23 @@
+CREATE TABLE endpointgroup (
+    id character varying NOT NULL COLLATE pg_catalog."C",
+    created_at timestamp with time zone NOT NULL,
+    updated_at timestamp with time zone NOT NULL,
+    app_id character varying NOT NULL COLLATE pg_catalog."C",
+    name character varying NOT NULL,
+    default_headers jsonb,
+    retry_schedule_override jsonb,
+    rate_limit integer
+);
+
+ALTER TABLE ONLY endpointgroup
+    ADD CONSTRAINT pk_endpointgroup PRIMARY KEY (id);
+
+ALTER TABLE endpointgroup ADD CONSTRAINT fk_endpointgroup_app_id_application FOREIGN KEY(app_id) REFERENCES application (id) ON DELETE CASCADE;
+
+CREATE INDEX ix_endpointgroup_app_id ON endpointgroup (app_id);
+
+ALTER TABLE endpoint ADD COLUMN group_id character varying COLLATE pg_catalog."C";
+
+ALTER TABLE endpoint ADD CONSTRAINT fk_endpoint_group_id_endpointgroup FOREIGN KEY(group_id) REFERENCES endpointgroup (id) ON DELETE SET NULL;
+
+CREATE INDEX ix_endpoint_group_id ON endpoint (group_id);
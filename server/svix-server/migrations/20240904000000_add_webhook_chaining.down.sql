@@ -0,0 +1,3 @@
+ALTER TABLE message DROP COLUMN chain_depth;
+
+ALTER TABLE endpoint DROP COLUMN response_webhook_event_type;
@@ -0,0 +1,3 @@
+ALTER TABLE endpoint ADD COLUMN response_webhook_event_type character varying;
+
+ALTER TABLE message ADD COLUMN chain_depth smallint NOT NULL DEFAULT 0;
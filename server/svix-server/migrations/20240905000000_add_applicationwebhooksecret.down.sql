@@ -0,0 +1,7 @@
+UPDATE endpoint SET key = applicationwebhooksecret.key
+    FROM applicationwebhooksecret
+    WHERE endpoint.app_id = applicationwebhooksecret.id AND endpoint.key IS NULL;
+
+ALTER TABLE endpoint ALTER COLUMN key SET NOT NULL;
+
+DROP TABLE applicationwebhooksecret;
@@ -0,0 +1,15 @@
+CREATE TABLE applicationwebhooksecret (
+    id character varying NOT NULL COLLATE pg_catalog."C",
+    created_at timestamp with time zone NOT NULL,
+    updated_at timestamp with time zone NOT NULL,
+    key bytea NOT NULL,
+    old_keys jsonb
+);
+
+ALTER TABLE ONLY applicationwebhooksecret
+    ADD CONSTRAINT pk_applicationwebhooksecret PRIMARY KEY (id);
+
+ALTER TABLE applicationwebhooksecret ADD CONSTRAINT fk_applicationwebhooksecret_id_application FOREIGN KEY(id) REFERENCES application (id) ON DELETE CASCADE;
+
+-- Endpoints without a key of their own are signed with their application's secret
+ALTER TABLE endpoint ALTER COLUMN key DROP NOT NULL;
@@ -0,0 +1 @@
+DELETE FROM eventtype WHERE id = 'evtype_2lRqGzQ5b1M8VnC3yT0kXwJ4hPd';
@@ -0,0 +1,3 @@
+INSERT INTO eventtype(
+    created_at, updated_at, id, org_id, description, deleted, schemas, name, feature_flag)
+    VALUES (CURRENT_TIMESTAMP, CURRENT_TIMESTAMP, 'evtype_2lRqGzQ5b1M8VnC3yT0kXwJ4hPd', 'org_00000000000SvixManagement00', 'Sent when an endpoint has failed to deliver a configured number of messages in a row.', false, '{"1": {"type": "object", "title": "EndpointFailureCountThresholdEvent", "examples": [{"data": {"appId": "app_1srOrx2ZWZBpBUvZwXKQmoEYga2", "appUid": "unique-app-identifier", "failSince": "1970-01-01T00:00:00", "endpointId": "ep_1srOrx2ZWZBpBUvZwXKQmoEYga2", "endpointUid": "unique-endpoint-identifier", "failureCount": 5}, "type": "endpoint.failure_count_threshold"}], "required": ["data"], "properties": {"data": {"$ref": "#/definitions/EndpointFailureCountEventData"}, "type": {"enum": ["endpoint.failure_count_threshold"], "type": "string", "title": "Type", "default": "endpoint.failure_count_threshold"}}, "definitions": {"EndpointFailureCountEventData": {"type": "object", "title": "EndpointFailureCountEventData", "required": ["appId", "endpointId", "failureCount", "failSince"], "properties": {"appId": {"type": "string", "title": "Appid", "example": "app_1srOrx2ZWZBpBUvZwXKQmoEYga2"}, "appUid": {"type": "string", "title": "Appuid", "example": "unique-app-identifier", "pattern": "^[a-zA-Z0-9\\-_.]+$", "nullable": true, "maxLength": 256, "minLength": 1, "description": "Optional unique identifier for the application"}, "failSince": {"type": "string", "title": "Failsince", "format": "date-time"}, "endpointId": {"type": "string", "title": "Endpointid", "example": "ep_1srOrx2ZWZBpBUvZwXKQmoEYga2"}, "endpointUid": {"type": "string", "title": "Endpointuid", "example": "unique-endpoint-identifier", "pattern": "^[a-zA-Z0-9\\-_.]+$", "nullable": true, "maxLength": 256, "minLength": 1, "description": "Optional unique identifier for the endpoint"}, "failureCount": {"type": "integer", "title": "Failurecount", "example": 5}}}}, "description": "Sent when an endpoint has failed to deliver a configured number of messages in a row.\nIt''s a warning that the endpoint may soon be disabled."}}', 'endpoint.failure_count_threshold', NULL);
@@ -0,0 +1,6 @@
+DELETE FROM eventtype WHERE id = 'evtype_2lSb7nHqZ4kT1xWc9Pf3RvYd0Mg';
+
+-- Expired deliveries are recorded as failed ones
+UPDATE messagedestination SET status = 2 WHERE status = 4;
+
+ALTER TABLE message DROP COLUMN deadline;
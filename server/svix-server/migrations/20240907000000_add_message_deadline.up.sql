@@ -0,0 +1,5 @@
+ALTER TABLE message ADD COLUMN deadline timestamp with time zone;
+
+INSERT INTO eventtype(
+    created_at, updated_at, id, org_id, description, deleted, schemas, name, feature_flag)
+    VALUES (CURRENT_TIMESTAMP, CURRENT_TIMESTAMP, 'evtype_2lSb7nHqZ4kT1xWc9Pf3RvYd0Mg', 'org_00000000000SvixManagement00', 'Sent when a message isn''t delivered to an endpoint because its deadline has passed.', false, '{"1": {"type": "object", "title": "MessageExpiredEvent", "examples": [{"data": {"appId": "app_1srOrx2ZWZBpBUvZwXKQmoEYga2", "appUid": "unique-app-identifier", "msgId": "msg_1srOrx2ZWZBpBUvZwXKQmoEYga2", "msgEventId": "unique-msg-identifier", "endpointId": "ep_1srOrx2ZWZBpBUvZwXKQmoEYga2", "deadline": "1970-01-01T00:00:00"}, "type": "message.expired"}], "required": ["data"], "properties": {"data": {"$ref": "#/definitions/MessageExpiredEventData"}, "type": {"enum": ["message.expired"], "type": "string", "title": "Type", "default": "message.expired"}}, "definitions": {"MessageExpiredEventData": {"type": "object", "title": "MessageExpiredEventData", "required": ["appId", "msgId", "endpointId", "deadline"], "properties": {"appId": {"type": "string", "title": "Appid", "example": "app_1srOrx2ZWZBpBUvZwXKQmoEYga2"}, "appUid": {"type": "string", "title": "Appuid", "example": "unique-app-identifier", "pattern": "^[a-zA-Z0-9\\-_.]+$", "nullable": true, "maxLength": 256, "minLength": 1, "description": "Optional unique identifier for the application"}, "msgId": {"type": "string", "title": "Msgid", "example": "msg_1srOrx2ZWZBpBUvZwXKQmoEYga2"}, "msgEventId": {"type": "string", "title": "Msgeventid", "example": "unique-msg-identifier", "pattern": "^[a-zA-Z0-9\\-_.]+$", "nullable": true, "maxLength": 256, "minLength": 1, "description": "Optional unique identifier for the message"}, "endpointId": {"type": "string", "title": "Endpointid", "example": "ep_1srOrx2ZWZBpBUvZwXKQmoEYga2"}, "deadline": {"type": "string", "title": "Deadline", "format": "date-time"}}}}, "description": "Sent when a message isn''t delivered to an endpoint because its deadline has passed."}}', 'message.expired', NULL);
@@ -0,0 +1 @@
+ALTER TABLE messagedestination DROP COLUMN first_attempt_at;
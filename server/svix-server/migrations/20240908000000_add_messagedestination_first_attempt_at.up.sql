@@ -0,0 +1 @@
+ALTER TABLE messagedestination ADD COLUMN first_attempt_at timestamp with time zone;
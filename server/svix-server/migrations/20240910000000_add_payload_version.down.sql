@@ -0,0 +1,6 @@
+-- Incompatible deliveries are recorded as failed ones
+UPDATE messagedestination SET status = 2 WHERE status = 6;
+
+ALTER TABLE endpoint DROP COLUMN min_payload_version;
+
+ALTER TABLE message DROP COLUMN payload_version;
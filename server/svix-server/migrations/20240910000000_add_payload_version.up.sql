@@ -0,0 +1,3 @@
+ALTER TABLE message ADD COLUMN payload_version character varying;
+
+ALTER TABLE endpoint ADD COLUMN min_payload_version character varying;
@@ -0,0 +1 @@
+ALTER TABLE endpoint DROP COLUMN http_method;
@@ -0,0 +1 @@
+ALTER TABLE endpoint ADD COLUMN http_method character varying NOT NULL DEFAULT 'POST';
@@ -0,0 +1 @@
+ALTER TABLE messageattempt DROP COLUMN endpoint_ip;
@@ -0,0 +1 @@
+ALTER TABLE messageattempt ADD COLUMN endpoint_ip text;
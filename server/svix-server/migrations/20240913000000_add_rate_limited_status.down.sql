@@ -0,0 +1,2 @@
+-- Rate limited deliveries are still waiting to be sent
+UPDATE messagedestination SET status = 1 WHERE status = 7;
@@ -0,0 +1,2 @@
+-- Rate limited message destinations are recorded with a new status (7). Statuses are stored as
+-- numbers, so the schema doesn't change, but rolling back needs the down migration.
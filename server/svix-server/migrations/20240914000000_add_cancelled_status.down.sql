@@ -0,0 +1,2 @@
+-- Cancelled deliveries are recorded as failed ones
+UPDATE messagedestination SET status = 2 WHERE status = 8;
@@ -0,0 +1,3 @@
+-- Deliveries cancelled because their application was deleted are recorded with a new status (8).
+-- Statuses are stored as numbers, so the schema doesn't change, but rolling back needs the down
+-- migration.
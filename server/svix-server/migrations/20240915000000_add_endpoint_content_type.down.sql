@@ -0,0 +1 @@
+ALTER TABLE endpoint DROP COLUMN content_type;
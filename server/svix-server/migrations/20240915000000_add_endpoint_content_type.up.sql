@@ -0,0 +1 @@
+ALTER TABLE endpoint ADD COLUMN content_type character varying;
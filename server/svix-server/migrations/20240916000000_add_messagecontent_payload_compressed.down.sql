@@ -0,0 +1,4 @@
+-- Compressed payloads can't be decompressed here, so they're dropped as if they had expired
+DELETE FROM messagecontent WHERE payload IS NULL;
+ALTER TABLE messagecontent DROP COLUMN payload_compressed;
+ALTER TABLE messagecontent ALTER COLUMN payload SET NOT NULL;
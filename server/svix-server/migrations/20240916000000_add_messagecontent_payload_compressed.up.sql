@@ -0,0 +1,2 @@
+ALTER TABLE messagecontent ALTER COLUMN payload DROP NOT NULL;
+ALTER TABLE messagecontent ADD COLUMN payload_compressed bytea;
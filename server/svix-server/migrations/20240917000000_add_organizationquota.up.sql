@@ -0,0 +1,13 @@
+CREATE TABLE organizationquota (
+    created_at timestamp with time zone NOT NULL,
+    updated_at timestamp with time zone NOT NULL,
+    org_id character varying NOT NULL COLLATE pg_catalog."C",
+    messages_per_month bigint NOT NULL,
+    messages_this_month bigint NOT NULL DEFAULT 0,
+    reset_at timestamp with time zone NOT NULL
+);
+
+ALTER TABLE ONLY organizationquota
+    ADD CONSTRAINT organizationquota_pkey PRIMARY KEY (org_id);
+
+CREATE INDEX ix_organizationquota_reset_at ON organizationquota USING btree (reset_at);
@@ -0,0 +1,2 @@
+DELETE FROM eventtype WHERE id = 'evtype_2lU5fc2TrVyd1XtjKgfJ7zdB9Dg';
+DELETE FROM eventtype WHERE id = 'evtype_2lULO0VEpKa9pUgQ2vsudtuL0VA';
@@ -0,0 +1,7 @@
+INSERT INTO eventtype(
+    created_at, updated_at, id, org_id, description, deleted, schemas, name, feature_flag)
+    VALUES (CURRENT_TIMESTAMP, CURRENT_TIMESTAMP, 'evtype_2lU5fc2TrVyd1XtjKgfJ7zdB9Dg', 'org_00000000000SvixManagement00', 'Sent when an endpoint''s circuit breaker opens after it has failed a number of deliveries in a row.', false, '{"1": {"type": "object", "title": "EndpointCircuitOpenEvent", "examples": [{"data": {"appId": "app_1srOrx2ZWZBpBUvZwXKQmoEYga2", "appUid": "unique-app-identifier", "endpointId": "ep_1srOrx2ZWZBpBUvZwXKQmoEYga2", "openUntil": "1970-01-01T00:00:00", "consecutiveFailures": 5}, "type": "endpoint.circuit_open"}], "required": ["data"], "properties": {"data": {"$ref": "#/definitions/EndpointCircuitOpenEventData"}, "type": {"enum": ["endpoint.circuit_open"], "type": "string", "title": "Type", "default": "endpoint.circuit_open"}}, "definitions": {"EndpointCircuitOpenEventData": {"type": "object", "title": "EndpointCircuitOpenEventData", "required": ["appId", "endpointId", "consecutiveFailures"], "properties": {"appId": {"type": "string", "title": "Appid", "example": "app_1srOrx2ZWZBpBUvZwXKQmoEYga2"}, "appUid": {"type": "string", "title": "Appuid", "example": "unique-app-identifier", "pattern": "^[a-zA-Z0-9\\-_.]+$", "nullable": true, "maxLength": 256, "minLength": 1, "description": "Optional unique identifier for the application"}, "endpointId": {"type": "string", "title": "Endpointid", "example": "ep_1srOrx2ZWZBpBUvZwXKQmoEYga2"}, "openUntil": {"type": "string", "title": "Openuntil", "format": "date-time", "nullable": true, "description": "When deliveries to the endpoint resume, or `null` once the circuit has closed"}, "consecutiveFailures": {"type": "integer", "title": "Consecutivefailures", "example": 5, "description": "The number of deliveries which failed in a row"}}}}, "description": "Sent when an endpoint''s circuit breaker opens after it has failed a number of deliveries in a row.\nDeliveries to it are put off until `openUntil`."}}', 'endpoint.circuit_open', NULL);
+
+INSERT INTO eventtype(
+    created_at, updated_at, id, org_id, description, deleted, schemas, name, feature_flag)
+    VALUES (CURRENT_TIMESTAMP, CURRENT_TIMESTAMP, 'evtype_2lULO0VEpKa9pUgQ2vsudtuL0VA', 'org_00000000000SvixManagement00', 'Sent when a delivery to an endpoint whose circuit breaker was open succeeds, closing it again.', false, '{"1": {"type": "object", "title": "EndpointCircuitClosedEvent", "examples": [{"data": {"appId": "app_1srOrx2ZWZBpBUvZwXKQmoEYga2", "appUid": "unique-app-identifier", "endpointId": "ep_1srOrx2ZWZBpBUvZwXKQmoEYga2", "openUntil": null, "consecutiveFailures": 5}, "type": "endpoint.circuit_closed"}], "required": ["data"], "properties": {"data": {"$ref": "#/definitions/EndpointCircuitClosedEventData"}, "type": {"enum": ["endpoint.circuit_closed"], "type": "string", "title": "Type", "default": "endpoint.circuit_closed"}}, "definitions": {"EndpointCircuitClosedEventData": {"type": "object", "title": "EndpointCircuitClosedEventData", "required": ["appId", "endpointId", "consecutiveFailures"], "properties": {"appId": {"type": "string", "title": "Appid", "example": "app_1srOrx2ZWZBpBUvZwXKQmoEYga2"}, "appUid": {"type": "string", "title": "Appuid", "example": "unique-app-identifier", "pattern": "^[a-zA-Z0-9\\-_.]+$", "nullable": true, "maxLength": 256, "minLength": 1, "description": "Optional unique identifier for the application"}, "endpointId": {"type": "string", "title": "Endpointid", "example": "ep_1srOrx2ZWZBpBUvZwXKQmoEYga2"}, "openUntil": {"type": "string", "title": "Openuntil", "format": "date-time", "nullable": true, "description": "When deliveries to the endpoint resume, or `null` once the circuit has closed"}, "consecutiveFailures": {"type": "integer", "title": "Consecutivefailures", "example": 5, "description": "The number of deliveries which failed in a row"}}}}, "description": "Sent when a delivery to an endpoint whose circuit breaker was open succeeds, closing it again."}}', 'endpoint.circuit_closed', NULL);
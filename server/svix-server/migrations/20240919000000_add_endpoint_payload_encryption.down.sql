@@ -0,0 +1,2 @@
+ALTER TABLE endpoint DROP COLUMN payload_encryption_key;
+ALTER TABLE endpoint DROP COLUMN payload_encryption_enabled;
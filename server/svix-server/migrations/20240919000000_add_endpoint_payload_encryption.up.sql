@@ -0,0 +1,2 @@
+ALTER TABLE endpoint ADD COLUMN payload_encryption_enabled boolean NOT NULL DEFAULT false;
+ALTER TABLE endpoint ADD COLUMN payload_encryption_key bytea;
@@ -0,0 +1,2 @@
+ALTER TABLE endpoint DROP COLUMN dispatch_transformation;
+ALTER TABLE endpoint DROP COLUMN dispatch_transformation_enabled;
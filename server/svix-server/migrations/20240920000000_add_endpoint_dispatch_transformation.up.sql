@@ -0,0 +1,2 @@
+ALTER TABLE endpoint ADD COLUMN dispatch_transformation_enabled boolean NOT NULL DEFAULT false;
+ALTER TABLE endpoint ADD COLUMN dispatch_transformation text;
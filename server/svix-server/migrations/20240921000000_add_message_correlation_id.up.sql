@@ -0,0 +1 @@
+ALTER TABLE message ADD COLUMN correlation_id character varying;
@@ -0,0 +1 @@
+ALTER TABLE endpoint ADD COLUMN custom_data jsonb;
@@ -0,0 +1,10 @@
+CREATE TABLE organizationratelimit (
+    created_at timestamp with time zone NOT NULL,
+    updated_at timestamp with time zone NOT NULL,
+    org_id character varying NOT NULL COLLATE pg_catalog."C",
+    capacity integer NOT NULL CHECK (capacity > 0),
+    refill_per_second integer NOT NULL CHECK (refill_per_second > 0)
+);
+
+ALTER TABLE ONLY organizationratelimit
+    ADD CONSTRAINT organizationratelimit_pkey PRIMARY KEY (org_id);
@@ -0,0 +1,2 @@
+DROP INDEX ix_messagedestination_last_attempt_at_unfinished;
+ALTER TABLE messagedestination DROP COLUMN last_attempt_at;
@@ -0,0 +1,4 @@
+ALTER TABLE messagedestination ADD COLUMN last_attempt_at timestamp with time zone;
+
+-- Only destinations still being delivered (pending or sending) are scanned for stuck deliveries
+CREATE INDEX ix_messagedestination_last_attempt_at_unfinished ON messagedestination USING btree (last_attempt_at) WHERE status IN (1, 3);
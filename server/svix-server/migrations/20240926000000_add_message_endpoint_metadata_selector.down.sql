@@ -0,0 +1,2 @@
+DROP INDEX ix_endpointmetadata_data;
+ALTER TABLE message DROP COLUMN endpoint_metadata_selector;
@@ -0,0 +1,4 @@
+ALTER TABLE message ADD COLUMN endpoint_metadata_selector jsonb;
+
+-- For filtering endpoints by their metadata when listing them
+CREATE INDEX ix_endpointmetadata_data ON endpointmetadata USING gin (data jsonb_path_ops);
@@ -0,0 +1,2 @@
+DROP INDEX ix_messagedestination_last_activity_unfinished;
+CREATE INDEX ix_messagedestination_last_attempt_at_unfinished ON messagedestination USING btree (last_attempt_at) WHERE status IN (1, 3);
@@ -0,0 +1,5 @@
+DROP INDEX ix_messagedestination_last_attempt_at_unfinished;
+
+-- Destinations which were never attempted count from their creation, and rate limited ones are
+-- still to be delivered too
+CREATE INDEX ix_messagedestination_last_activity_unfinished ON messagedestination USING btree (COALESCE(last_attempt_at, created_at)) WHERE status IN (1, 3, 7);
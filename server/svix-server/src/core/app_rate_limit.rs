@@ -0,0 +1,407 @@
+// SPDX-FileCopyrightText: © 2022 Svix Authors
+// SPDX-License-Identifier: MIT
+
+//! Per-application rate limiting of dispatches, so that an application sending a burst of
+//! messages can't keep the worker from dispatching other applications' webhooks.
+//!
+//! Each application has a token bucket, with the capacity and refill rate its organization has in
+//! the `organizationratelimit` table. Buckets are kept in memory, so every worker instance limits
+//! its own dispatches. Organizations without a rate limit have no limit.
+//!
+//! Dispatches put off by the limit are spread out over the time it takes the bucket to refill for
+//! all of them, rather than all being retried as soon as the next token is available.
+
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use dashmap::DashMap;
+use rand::Rng;
+use sea_orm::{DatabaseConnection, EntityTrait};
+
+use crate::{
+    core::{
+        endpoint_rate_limit::RateLimitState,
+        types::{ApplicationId, OrganizationId},
+    },
+    db::models::organizationratelimit,
+    error::Result,
+};
+
+/// How long an organization's rate limit is used for before it's read from the database again.
+/// Also how long a bucket has to be unused before it's dropped.
+const LIMIT_REFRESH_INTERVAL: Duration = Duration::from_secs(60);
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct BucketLimit {
+    capacity: f64,
+    refill_per_second: f64,
+}
+
+impl From<organizationratelimit::Model> for BucketLimit {
+    fn from(model: organizationratelimit::Model) -> Self {
+        Self {
+            capacity: f64::from(model.capacity),
+            refill_per_second: f64::from(model.refill_per_second),
+        }
+    }
+}
+
+/// A token bucket for a single application
+#[derive(Debug)]
+pub struct RateLimiter {
+    /// `None` if the application has no limit
+    limit: Option<BucketLimit>,
+    tokens: f64,
+    /// Roughly how many put off dispatches are yet to be retried. Goes down as the bucket refills,
+    /// as each token refilled is meant for one of them.
+    waiting: f64,
+    last_refill: Instant,
+    /// When `limit` was read from the database
+    loaded_at: Instant,
+}
+
+impl RateLimiter {
+    fn new(limit: Option<BucketLimit>, now: Instant) -> Self {
+        Self {
+            limit,
+            // Starts out full, so that an application can always make a burst of dispatches
+            tokens: limit.map_or(0.0, |l| l.capacity),
+            waiting: 0.0,
+            last_refill: now,
+            loaded_at: now,
+        }
+    }
+
+    /// Replaces the limit with one freshly read from the database, keeping the tokens left.
+    fn reload(&mut self, limit: Option<BucketLimit>, now: Instant) {
+        self.refill(now);
+        if self.limit.is_none() {
+            self.tokens = limit.map_or(0.0, |l| l.capacity);
+        }
+        self.limit = limit;
+        self.loaded_at = now;
+        self.refill(now);
+    }
+
+    fn refill(&mut self, now: Instant) {
+        if let Some(limit) = self.limit {
+            let elapsed = now.saturating_duration_since(self.last_refill);
+            let refilled = elapsed.as_secs_f64() * limit.refill_per_second;
+            self.tokens = (self.tokens + refilled).min(limit.capacity);
+            self.waiting = (self.waiting - refilled).max(0.0);
+        }
+        self.last_refill = now;
+    }
+
+    /// Takes a token out of the bucket, unless it's empty. A dispatch that's put off is told to
+    /// retry after the ones already waiting, once the bucket has refilled for all of them.
+    fn try_acquire(&mut self, now: Instant) -> RateLimitState {
+        let Some(limit) = self.limit else {
+            return RateLimitState::Allowed;
+        };
+
+        self.refill(now);
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            RateLimitState::Allowed
+        } else {
+            let retry_in = (1.0 + self.waiting - self.tokens) / limit.refill_per_second;
+            self.waiting += 1.0;
+            RateLimitState::Limited {
+                retry_in: Duration::from_secs_f64(retry_in),
+            }
+        }
+    }
+
+    /// A random delay of up to the time it takes to refill a token, so that put off dispatches
+    /// which were given the same place in line don't all come back at once.
+    fn jitter(&self) -> Duration {
+        self.limit.map_or(Duration::ZERO, |l| {
+            Duration::from_secs_f64(rand::thread_rng().gen_range(0.0..1.0) / l.refill_per_second)
+        })
+    }
+
+    /// Whether the bucket can be dropped, as it hasn't been used for a while and one created
+    /// anew would be the same.
+    fn is_idle(&mut self, now: Instant) -> bool {
+        if now.saturating_duration_since(self.last_refill) < LIMIT_REFRESH_INTERVAL {
+            return false;
+        }
+
+        self.refill(now);
+        self.limit
+            .map_or(true, |l| self.tokens >= l.capacity && self.waiting == 0.0)
+    }
+}
+
+/// The rate limiters of all the applications the worker has recently dispatched webhooks for
+#[derive(Clone)]
+pub struct AppRateLimiter {
+    buckets: Arc<DashMap<ApplicationId, RateLimiter>>,
+    last_pruned: Arc<Mutex<Instant>>,
+}
+
+impl Default for AppRateLimiter {
+    fn default() -> Self {
+        Self {
+            buckets: Arc::default(),
+            last_pruned: Arc::new(Mutex::new(Instant::now())),
+        }
+    }
+}
+
+impl AppRateLimiter {
+    /// Takes a token out of the application's bucket, unless it's empty.
+    pub async fn acquire(
+        &self,
+        db: &DatabaseConnection,
+        org_id: &OrganizationId,
+        app_id: &ApplicationId,
+    ) -> Result<RateLimitState> {
+        let now = Instant::now();
+        self.prune(now);
+
+        let stale = !matches!(
+            self.buckets.get(app_id),
+            Some(bucket) if now.saturating_duration_since(bucket.loaded_at) < LIMIT_REFRESH_INTERVAL
+        );
+        if stale {
+            // Not holding on to the bucket while reading the limit, as that would block other
+            // tasks from using the map
+            let limit = organizationratelimit::Entity::secure_find(org_id.clone())
+                .one(db)
+                .await?
+                .map(BucketLimit::from);
+            self.buckets
+                .entry(app_id.clone())
+                .and_modify(|bucket| bucket.reload(limit, now))
+                .or_insert_with(|| RateLimiter::new(limit, now));
+        }
+
+        Ok(self.try_acquire(app_id, now))
+    }
+
+    /// Drops the buckets of applications which haven't dispatched anything for a while, at most
+    /// once every [`LIMIT_REFRESH_INTERVAL`].
+    fn prune(&self, now: Instant) {
+        {
+            let mut last_pruned = self.last_pruned.lock().expect("Lock poisoned");
+            if now.saturating_duration_since(*last_pruned) < LIMIT_REFRESH_INTERVAL {
+                return;
+            }
+            *last_pruned = now;
+        }
+
+        self.buckets.retain(|_, bucket| !bucket.is_idle(now));
+    }
+
+    fn try_acquire(&self, app_id: &ApplicationId, now: Instant) -> RateLimitState {
+        self.buckets
+            .get_mut(app_id)
+            .map_or(RateLimitState::Allowed, |mut bucket| {
+                match bucket.try_acquire(now) {
+                    RateLimitState::Limited { retry_in } => RateLimitState::Limited {
+                        retry_in: retry_in + bucket.jitter(),
+                    },
+                    RateLimitState::Allowed => RateLimitState::Allowed,
+                }
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::{AppRateLimiter, BucketLimit, RateLimiter};
+    use crate::core::{
+        endpoint_rate_limit::RateLimitState,
+        types::{ApplicationId, BaseId},
+    };
+
+    const LIMIT: BucketLimit = BucketLimit {
+        capacity: 10.0,
+        refill_per_second: 2.0,
+    };
+
+    #[test]
+    fn test_drains_under_burst() {
+        let now = Instant::now();
+        let mut bucket = RateLimiter::new(Some(LIMIT), now);
+
+        for _ in 0..10 {
+            assert_eq!(bucket.try_acquire(now), RateLimitState::Allowed);
+        }
+        assert_eq!(
+            bucket.try_acquire(now),
+            RateLimitState::Limited {
+                retry_in: Duration::from_millis(500)
+            }
+        );
+    }
+
+    #[test]
+    fn test_refills_over_time() {
+        let start = Instant::now();
+        let mut bucket = RateLimiter::new(Some(LIMIT), start);
+        for _ in 0..10 {
+            bucket.try_acquire(start);
+        }
+
+        // Two tokens a second
+        let now = start + Duration::from_millis(1250);
+        for _ in 0..2 {
+            assert_eq!(bucket.try_acquire(now), RateLimitState::Allowed);
+        }
+        assert_eq!(
+            bucket.try_acquire(now),
+            RateLimitState::Limited {
+                retry_in: Duration::from_millis(250)
+            }
+        );
+
+        // Never fills up past its capacity
+        let now = start + Duration::from_secs(60);
+        for _ in 0..10 {
+            assert_eq!(bucket.try_acquire(now), RateLimitState::Allowed);
+        }
+        assert!(matches!(
+            bucket.try_acquire(now),
+            RateLimitState::Limited { .. }
+        ));
+    }
+
+    #[test]
+    fn test_spreads_out_put_off_dispatches() {
+        let start = Instant::now();
+        let mut bucket = RateLimiter::new(Some(LIMIT), start);
+        for _ in 0..10 {
+            bucket.try_acquire(start);
+        }
+
+        // Each dispatch put off is given the next token after the ones already waiting
+        for i in 1..=3 {
+            assert_eq!(
+                bucket.try_acquire(start),
+                RateLimitState::Limited {
+                    retry_in: Duration::from_millis(500 * i)
+                }
+            );
+        }
+
+        // And the first of them finds its token when it's retried
+        let now = start + Duration::from_millis(500);
+        assert_eq!(bucket.try_acquire(now), RateLimitState::Allowed);
+        assert_eq!(
+            bucket.try_acquire(now),
+            RateLimitState::Limited {
+                retry_in: Duration::from_millis(1500)
+            }
+        );
+    }
+
+    #[test]
+    fn test_no_limit() {
+        let now = Instant::now();
+        let mut bucket = RateLimiter::new(None, now);
+        for _ in 0..1000 {
+            assert_eq!(bucket.try_acquire(now), RateLimitState::Allowed);
+        }
+    }
+
+    #[test]
+    fn test_reload() {
+        let now = Instant::now();
+        let mut bucket = RateLimiter::new(None, now);
+
+        // Newly limited applications start with a full bucket
+        bucket.reload(Some(LIMIT), now);
+        assert_eq!(bucket.tokens, 10.0);
+
+        // Tokens left are kept, but capped at the new capacity
+        bucket.try_acquire(now);
+        bucket.reload(
+            Some(BucketLimit {
+                capacity: 100.0,
+                refill_per_second: 2.0,
+            }),
+            now,
+        );
+        assert_eq!(bucket.tokens, 9.0);
+        bucket.reload(
+            Some(BucketLimit {
+                capacity: 5.0,
+                refill_per_second: 2.0,
+            }),
+            now,
+        );
+        assert_eq!(bucket.tokens, 5.0);
+
+        bucket.reload(None, now);
+        assert_eq!(bucket.try_acquire(now), RateLimitState::Allowed);
+    }
+
+    #[test]
+    fn test_prevents_starvation() {
+        let now = Instant::now();
+        let limiter = AppRateLimiter::default();
+        let noisy_app = ApplicationId::new(None, None);
+        let quiet_app = ApplicationId::new(None, None);
+        for app_id in [&noisy_app, &quiet_app] {
+            limiter
+                .buckets
+                .insert(app_id.clone(), RateLimiter::new(Some(LIMIT), now));
+        }
+
+        // A burst from one application only drains its own bucket
+        let allowed = (0..10_000)
+            .filter(|_| limiter.try_acquire(&noisy_app, now) == RateLimitState::Allowed)
+            .count();
+        assert_eq!(allowed, 10);
+        assert_eq!(
+            limiter.try_acquire(&quiet_app, now),
+            RateLimitState::Allowed
+        );
+
+        // Applications the worker hasn't loaded a limit for yet aren't held up
+        assert_eq!(
+            limiter.try_acquire(&ApplicationId::new(None, None), now),
+            RateLimitState::Allowed
+        );
+    }
+
+    #[test]
+    fn test_prunes_idle_buckets() {
+        let start = Instant::now();
+        let limiter = AppRateLimiter::default();
+        let idle_app = ApplicationId::new(None, None);
+        let busy_app = ApplicationId::new(None, None);
+        let unlimited_app = ApplicationId::new(None, None);
+        limiter
+            .buckets
+            .insert(idle_app.clone(), RateLimiter::new(Some(LIMIT), start));
+        limiter
+            .buckets
+            .insert(busy_app.clone(), RateLimiter::new(Some(LIMIT), start));
+        limiter
+            .buckets
+            .insert(unlimited_app.clone(), RateLimiter::new(None, start));
+        limiter.try_acquire(&idle_app, start);
+
+        let now = start + Duration::from_secs(90);
+        limiter.try_acquire(&busy_app, start + Duration::from_secs(60));
+        limiter.prune(now);
+        assert!(!limiter.buckets.contains_key(&idle_app));
+        assert!(!limiter.buckets.contains_key(&unlimited_app));
+        assert!(limiter.buckets.contains_key(&busy_app));
+
+        // Pruned at most once per interval
+        limiter
+            .buckets
+            .insert(idle_app.clone(), RateLimiter::new(Some(LIMIT), start));
+        limiter.prune(now + Duration::from_secs(30));
+        assert!(limiter.buckets.contains_key(&idle_app));
+    }
+}
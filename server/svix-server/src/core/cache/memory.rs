@@ -1,26 +1,32 @@
 // SPDX-FileCopyrightText: © 2022 Svix Authors
 // SPDX-License-Identifier: MIT
 
-use std::{collections::HashMap, sync::Arc};
+use std::{
+    collections::{hash_map::Entry, HashMap},
+    sync::Arc,
+};
 
 use axum::async_trait;
+#[cfg(test)]
+use tokio::sync::broadcast;
 use tokio::{
     sync::RwLock,
     task,
     time::{sleep, Duration, Instant},
 };
 
-use super::{Cache, CacheBehavior, CacheKey, Result};
+use super::{Cache, CacheBehavior, CacheKey, CacheStats, Result};
 
 #[derive(Debug)]
 struct ValueWrapper {
     value: Vec<u8>,
-    ttl: Duration,
+    /// `None` if the value never expires
+    ttl: Option<Duration>,
     timer: Instant,
 }
 
 impl ValueWrapper {
-    fn new(value: Vec<u8>, ttl: Duration) -> ValueWrapper {
+    fn new(value: Vec<u8>, ttl: Option<Duration>) -> ValueWrapper {
         ValueWrapper {
             value,
             ttl,
@@ -31,6 +37,9 @@ impl ValueWrapper {
 
 type State = HashMap<Vec<u8>, ValueWrapper>;
 type SharedState = Arc<RwLock<State>>;
+/// The key prefix each [`MemoryCache::watch`] is for, and where to send the keys set under it
+#[cfg(test)]
+type Watchers = Arc<std::sync::Mutex<Vec<(Vec<u8>, broadcast::Sender<(Vec<u8>, Vec<u8>)>)>>>;
 
 pub fn new() -> Cache {
     let shared_state = Arc::new(RwLock::new(State::new()));
@@ -46,12 +55,48 @@ pub fn new() -> Cache {
         }
     });
 
-    MemoryCache { map: shared_state }.into()
+    MemoryCache {
+        map: shared_state,
+        #[cfg(test)]
+        watchers: Watchers::default(),
+    }
+    .into()
 }
 
 #[derive(Clone)]
 pub struct MemoryCache {
     map: SharedState,
+    #[cfg(test)]
+    watchers: Watchers,
+}
+
+impl MemoryCache {
+    /// Receives the key and value every time a key starting with `key_prefix` is set, so tests can
+    /// check what was written without polling the cache.
+    #[cfg(test)]
+    pub fn watch(&self, key_prefix: &str) -> broadcast::Receiver<(Vec<u8>, Vec<u8>)> {
+        let (tx, rx) = broadcast::channel(64);
+        self.watchers
+            .lock()
+            .unwrap()
+            .push((key_prefix.as_bytes().to_owned(), tx));
+        rx
+    }
+
+    #[cfg(test)]
+    fn notify(&self, key: &[u8], value: &[u8]) {
+        let mut watchers = self.watchers.lock().unwrap();
+        // Dropping the watches nobody is listening to anymore
+        watchers.retain(|(_, tx)| tx.receiver_count() > 0);
+        for (prefix, tx) in watchers.iter() {
+            if key.starts_with(prefix) {
+                let _ = tx.send((key.to_owned(), value.to_owned()));
+            }
+        }
+    }
+
+    #[cfg(not(test))]
+    fn notify(&self, _key: &[u8], _value: &[u8]) {}
 }
 
 #[async_trait]
@@ -70,25 +115,54 @@ impl CacheBehavior for MemoryCache {
             .map(|wrapper| wrapper.value.clone()))
     }
 
-    async fn set_raw(&self, key: &[u8], value: &[u8], ttl: Duration) -> Result<()> {
+    async fn set_raw(&self, key: &[u8], value: &[u8], ttl: Option<Duration>) -> Result<()> {
         self.map
             .write()
             .await
             .insert(key.to_owned(), ValueWrapper::new(value.to_owned(), ttl));
+        self.notify(key, value);
         Ok(())
     }
 
     async fn set_raw_if_not_exists(&self, key: &[u8], value: &[u8], ttl: Duration) -> Result<bool> {
         let mut lock = self.map.write().await;
+        let wrapper = ValueWrapper::new(value.to_owned(), Some(ttl));
+
+        // The check and the insert happen under the same write lock, so only one caller can win.
+        // Expired entries that haven't been cleaned up yet count as not existing.
+        match lock.entry(key.to_owned()) {
+            Entry::Occupied(entry) if check_is_expired(entry.get()) => return Ok(false),
+            Entry::Occupied(mut entry) => {
+                entry.insert(wrapper);
+            }
+            Entry::Vacant(entry) => {
+                entry.insert(wrapper);
+            }
+        }
+        self.notify(key, value);
+        Ok(true)
+    }
 
-        // TODO: use HashMap::try_insert when stable
-        // https://github.com/rust-lang/rust/issues/82766
-        if !lock.contains_key(key) {
-            lock.insert(key.to_owned(), ValueWrapper::new(value.to_owned(), ttl));
-            return Ok(true);
+    async fn compare_and_swap_raw(
+        &self,
+        key: &[u8],
+        expected: Option<&[u8]>,
+        new: &[u8],
+        ttl: Option<Duration>,
+    ) -> Result<bool> {
+        let mut lock = self.map.write().await;
+
+        let current = lock
+            .get(key)
+            .filter(|wrapper| check_is_expired(wrapper))
+            .map(|wrapper| wrapper.value.as_slice());
+        if current != expected {
+            return Ok(false);
         }
 
-        Ok(false)
+        lock.insert(key.to_owned(), ValueWrapper::new(new.to_owned(), ttl));
+        self.notify(key, new);
+        Ok(true)
     }
 
     async fn delete<T: CacheKey>(&self, key: &T) -> Result<()> {
@@ -96,10 +170,56 @@ impl CacheBehavior for MemoryCache {
 
         Ok(())
     }
+
+    async fn scan_keys(&self, prefix: &str) -> Result<Vec<String>> {
+        self.map
+            .read()
+            .await
+            .iter()
+            .filter(|(key, wrapper)| {
+                key.starts_with(prefix.as_bytes()) && check_is_expired(wrapper)
+            })
+            .map(|(key, _)| String::from_utf8(key.clone()).map_err(Into::into))
+            .collect()
+    }
+
+    async fn stats(&self) -> Result<CacheStats> {
+        let map = self.map.read().await;
+        let live = map.iter().filter(|(_, wrapper)| check_is_expired(wrapper));
+
+        let (key_count, memory_bytes) = live.fold((0, 0), |(count, bytes), (key, wrapper)| {
+            // Leaves out the map's own overhead, so this is a lower bound
+            let entry_bytes = key.len() + wrapper.value.len() + std::mem::size_of::<ValueWrapper>();
+            (count + 1, bytes + entry_bytes as u64)
+        });
+
+        Ok(CacheStats {
+            backend: "memory",
+            key_count: Some(key_count),
+            memory_bytes: Some(memory_bytes),
+        })
+    }
+
+    async fn touch<T: CacheKey>(&self, key: &T, ttl: Duration) -> Result<bool> {
+        Ok(self
+            .map
+            .write()
+            .await
+            .get_mut(key.as_ref().as_bytes())
+            .filter(|wrapper| check_is_expired(wrapper))
+            .map(|wrapper| {
+                wrapper.ttl = Some(ttl);
+                wrapper.timer = Instant::now();
+            })
+            .is_some())
+    }
 }
 
 fn check_is_expired(vw: &ValueWrapper) -> bool {
-    vw.timer.elapsed().as_millis() <= vw.ttl.as_millis()
+    match vw.ttl {
+        Some(ttl) => vw.timer.elapsed().as_millis() <= ttl.as_millis(),
+        None => true,
+    }
 }
 
 #[cfg(test)]
@@ -158,15 +278,15 @@ mod tests {
 
         // Create
         assert!(cache
-            .set(&first_key, &first_val_a, Duration::from_secs(30),)
+            .set(&first_key, &first_val_a, Some(Duration::from_secs(30)),)
             .await
             .is_ok());
         assert!(cache
-            .set(&second_key, &second_val_a, Duration::from_secs(30),)
+            .set(&second_key, &second_val_a, Some(Duration::from_secs(30)),)
             .await
             .is_ok());
         assert!(cache
-            .set_string(&third_key, &third_val_a, Duration::from_secs(30),)
+            .set_string(&third_key, &third_val_a, Some(Duration::from_secs(30)),)
             .await
             .is_ok());
 
@@ -180,15 +300,15 @@ mod tests {
 
         // Update (overwrite)
         assert!(cache
-            .set(&first_key, &first_val_b, Duration::from_secs(30),)
+            .set(&first_key, &first_val_b, Some(Duration::from_secs(30)),)
             .await
             .is_ok());
         assert!(cache
-            .set(&second_key, &second_val_b, Duration::from_secs(30),)
+            .set(&second_key, &second_val_b, Some(Duration::from_secs(30)),)
             .await
             .is_ok());
         assert!(cache
-            .set_string(&third_key, &third_val_b, Duration::from_secs(30),)
+            .set_string(&third_key, &third_val_b, Some(Duration::from_secs(30)),)
             .await
             .is_ok());
 
@@ -217,13 +337,51 @@ mod tests {
         let key = TestKeyA::new("key".to_owned());
 
         assert!(cache
-            .set(&key, &TestValA(1), Duration::from_secs(1),)
+            .set(&key, &TestValA(1), Some(Duration::from_secs(1)),)
             .await
             .is_ok());
         tokio::time::sleep(std::time::Duration::from_millis(1200)).await;
         assert_eq!(cache.get::<TestValA>(&key).await.unwrap(), None);
     }
 
+    #[tokio::test]
+    async fn test_cache_no_ttl() {
+        let cache = new();
+        let key = TestKeyA::new("no_ttl_key".to_owned());
+
+        cache
+            .set(&key, &TestValA(1), Some(Duration::from_secs(1)))
+            .await
+            .unwrap();
+        // Overwriting the value without a TTL also drops its expiry
+        cache.set(&key, &TestValA(2), None).await.unwrap();
+        tokio::time::sleep(std::time::Duration::from_millis(1200)).await;
+        assert_eq!(cache.get(&key).await.unwrap(), Some(TestValA(2)));
+    }
+
+    #[tokio::test]
+    async fn test_cache_touch() {
+        let cache = new();
+        let key = TestKeyA::new("touch_test_key".to_owned());
+
+        assert!(!cache.touch(&key, Duration::from_secs(1)).await.unwrap());
+
+        cache
+            .set(&key, &TestValA(1), Some(Duration::from_secs(1)))
+            .await
+            .unwrap();
+        tokio::time::sleep(std::time::Duration::from_millis(600)).await;
+        assert!(cache.touch(&key, Duration::from_secs(1)).await.unwrap());
+
+        // Past the original expiry, but within the new one
+        tokio::time::sleep(std::time::Duration::from_millis(600)).await;
+        assert_eq!(cache.get(&key).await.unwrap(), Some(TestValA(1)));
+
+        tokio::time::sleep(std::time::Duration::from_millis(600)).await;
+        assert_eq!(cache.get::<TestValA>(&key).await.unwrap(), None);
+        assert!(!cache.touch(&key, Duration::from_secs(1)).await.unwrap());
+    }
+
     #[tokio::test]
     async fn test_cache_nx_status() {
         let cache = new();
@@ -243,4 +401,249 @@ mod tests {
 
         assert!(cache.delete(&key).await.is_ok());
     }
+
+    #[tokio::test]
+    async fn test_cache_nx_expired() {
+        let cache = new();
+        let key = TestKeyA::new("nx_expired_test_key".to_owned());
+
+        assert!(cache
+            .set_if_not_exists(&key, &TestValA(1), Duration::from_millis(100))
+            .await
+            .unwrap());
+        tokio::time::sleep(Duration::from_millis(200)).await;
+
+        // The expired value hasn't been cleaned up yet, but doesn't block setting a new one
+        assert!(cache
+            .set_if_not_exists(&key, &TestValA(2), Duration::from_secs(30))
+            .await
+            .unwrap());
+        assert_eq!(cache.get(&key).await.unwrap(), Some(TestValA(2)));
+    }
+
+    #[tokio::test]
+    async fn test_cache_compare_and_swap() {
+        let cache = new();
+        let key = TestKeyA::new("cas_test_key".to_owned());
+
+        // A missing key only matches `None`
+        assert!(!cache
+            .compare_and_swap(&key, Some(&TestValA(0)), &TestValA(1), None)
+            .await
+            .unwrap());
+        assert!(cache
+            .compare_and_swap(&key, None, &TestValA(1), None)
+            .await
+            .unwrap());
+        assert_eq!(cache.get(&key).await.unwrap(), Some(TestValA(1)));
+
+        assert!(!cache
+            .compare_and_swap(&key, None, &TestValA(2), None)
+            .await
+            .unwrap());
+        assert!(!cache
+            .compare_and_swap(&key, Some(&TestValA(3)), &TestValA(2), None)
+            .await
+            .unwrap());
+        assert_eq!(cache.get(&key).await.unwrap(), Some(TestValA(1)));
+
+        assert!(cache
+            .compare_and_swap(
+                &key,
+                Some(&TestValA(1)),
+                &TestValA(2),
+                Some(Duration::from_secs(30))
+            )
+            .await
+            .unwrap());
+        assert_eq!(cache.get(&key).await.unwrap(), Some(TestValA(2)));
+
+        assert!(cache.delete(&key).await.is_ok());
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_cache_cas_concurrent() {
+        let cache = new();
+        let key = TestKeyA::new("cas_concurrent_test_key".to_owned());
+
+        // Every increment retries until it wins, so none of them are lost
+        let tasks: Vec<_> = (0..100)
+            .map(|_| {
+                let cache = cache.clone();
+                let key = key.clone();
+                tokio::spawn(async move {
+                    loop {
+                        let current = cache.get::<TestValA>(&key).await.unwrap();
+                        let next = TestValA(current.as_ref().map_or(1, |v| v.0 + 1));
+                        if cache
+                            .compare_and_swap(&key, current.as_ref(), &next, None)
+                            .await
+                            .unwrap()
+                        {
+                            break;
+                        }
+                    }
+                })
+            })
+            .collect();
+        for task in tasks {
+            task.await.unwrap();
+        }
+
+        assert_eq!(cache.get(&key).await.unwrap(), Some(TestValA(100)));
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_cache_nx_concurrent() {
+        for round in 0..10 {
+            let cache = new();
+            let key = TestKeyA::new(format!("nx_concurrent_test_key_{round}"));
+
+            let tasks: Vec<_> = (0..100)
+                .map(|i| {
+                    let cache = cache.clone();
+                    let key = key.clone();
+                    tokio::spawn(async move {
+                        cache
+                            .set_if_not_exists(&key, &TestValA(i), Duration::from_secs(30))
+                            .await
+                            .unwrap()
+                            .then_some(i)
+                    })
+                })
+                .collect();
+
+            let mut winners = Vec::new();
+            for task in tasks {
+                winners.extend(task.await.unwrap());
+            }
+
+            assert_eq!(winners.len(), 1);
+            assert_eq!(cache.get(&key).await.unwrap(), Some(TestValA(winners[0])));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_cache_scan_keys() {
+        let cache = new();
+
+        for (id, ttl) in [
+            ("scan_1", Duration::from_secs(30)),
+            ("scan_2", Duration::from_secs(30)),
+            ("scan_expired", Duration::from_millis(1)),
+            ("other", Duration::from_secs(30)),
+        ] {
+            cache
+                .set(&TestKeyA::new(id.to_owned()), &TestValA(0), Some(ttl))
+                .await
+                .unwrap();
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+
+        let mut keys = cache.scan_keys("SVIX_TEST_KEY_A_scan_").await.unwrap();
+        keys.sort();
+        assert_eq!(keys, ["SVIX_TEST_KEY_A_scan_1", "SVIX_TEST_KEY_A_scan_2"]);
+        assert!(cache.scan_keys("SVIX_NOTHING_").await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_cache_get_pipeline() {
+        let cache = new();
+        let keys: Vec<_> = (0..3)
+            .map(|i| TestKeyA::new(format!("pipeline_{i}")))
+            .collect();
+
+        cache
+            .set(&keys[0], &TestValA(0), Some(Duration::from_secs(30)))
+            .await
+            .unwrap();
+        // Not a valid `TestValA`
+        cache
+            .set_raw(
+                keys[1].as_ref().as_bytes(),
+                b"\"nope\"",
+                Some(Duration::from_secs(30)),
+            )
+            .await
+            .unwrap();
+
+        let fetched = cache
+            .get_pipeline::<TestValA>(&keys.iter().collect::<Vec<_>>())
+            .await
+            .unwrap();
+        assert_eq!(fetched.len(), 3);
+        assert_eq!(fetched[0].as_ref().unwrap(), &Some(TestValA(0)));
+        assert!(fetched[1].is_err());
+        assert_eq!(fetched[2].as_ref().unwrap(), &None);
+    }
+
+    #[tokio::test]
+    async fn test_cache_stats() {
+        let cache = new();
+        let stats = cache.stats().await.unwrap();
+        assert_eq!(stats.backend, "memory");
+        assert_eq!(stats.key_count, Some(0));
+        assert_eq!(stats.memory_bytes, Some(0));
+
+        let key = TestKeyA::new("1".to_owned());
+        cache
+            .set(&key, &TestValA(1), Some(Duration::from_secs(30)))
+            .await
+            .unwrap();
+        let expired = TestKeyA::new("2".to_owned());
+        cache
+            .set(&expired, &TestValA(2), Some(Duration::ZERO))
+            .await
+            .unwrap();
+        sleep(Duration::from_millis(10)).await;
+
+        // Expired entries which haven't been cleaned up yet aren't counted
+        let stats = cache.stats().await.unwrap();
+        assert_eq!(stats.key_count, Some(1));
+        assert!(stats.memory_bytes.unwrap() >= (key.as_ref().len() + "1".len()) as u64);
+    }
+
+    #[tokio::test]
+    async fn test_cache_watch() {
+        let cache = new();
+        let Cache::Memory(memory) = &cache else {
+            unreachable!("`new` always makes a memory cache");
+        };
+        let mut rx = memory.watch("SVIX_TEST_KEY_A_watch_");
+
+        let key = TestKeyA::new("watch_1".to_owned());
+        cache
+            .set(&key, &TestValA(1), Some(Duration::from_secs(30)))
+            .await
+            .unwrap();
+        // Neither of these is a write to a watched key
+        cache
+            .set(&TestKeyA::new("other".to_owned()), &TestValA(2), None)
+            .await
+            .unwrap();
+        assert!(!cache
+            .set_if_not_exists(&key, &TestValA(3), Duration::from_secs(30))
+            .await
+            .unwrap());
+
+        assert_eq!(
+            rx.try_recv().unwrap(),
+            (key.as_ref().as_bytes().to_owned(), b"1".to_vec())
+        );
+        assert!(matches!(
+            rx.try_recv(),
+            Err(broadcast::error::TryRecvError::Empty)
+        ));
+
+        // Swaps are writes too
+        assert!(cache
+            .compare_and_swap(&key, Some(&TestValA(1)), &TestValA(4), None)
+            .await
+            .unwrap());
+        assert_eq!(
+            rx.recv().await.unwrap(),
+            (key.as_ref().as_bytes().to_owned(), b"4".to_vec())
+        );
+        assert!(rx.is_empty());
+    }
 }
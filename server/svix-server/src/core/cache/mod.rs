@@ -27,7 +27,7 @@ pub enum Error {
     DeserializationBytes(#[from] FromUtf8Error),
 
     #[error("Redis pool error: {0}")]
-    Pool(#[from] bb8::RunError<RedisError>),
+    Pool(#[from] crate::redis::PoolError),
 
     #[error("Redis database error: {0}")]
     Database(#[from] RedisError),
@@ -116,6 +116,15 @@ macro_rules! string_kv_def {
 #[cfg(test)]
 pub(crate) use string_kv_def;
 
+/// How much the cache is holding, for keeping an eye on its growth. Figures the backend can't
+/// tell are left out.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CacheStats {
+    pub backend: &'static str,
+    pub key_count: Option<u64>,
+    pub memory_bytes: Option<u64>,
+}
+
 #[derive(Clone)]
 #[enum_dispatch]
 pub enum Cache {
@@ -160,6 +169,45 @@ pub trait CacheBehavior: Sync + Send {
 
     async fn get_raw(&self, key: &[u8]) -> Result<Option<Vec<u8>>>;
 
+    /// Fetches several independent keys at once. Failing to fetch the batch fails the whole
+    /// call, whereas a value that can't be deserialized only fails its own entry in the output.
+    async fn get_pipeline<T: CacheValue>(
+        &self,
+        keys: &[&T::Key],
+    ) -> Result<Vec<Result<Option<T>>>> {
+        let raw_keys: Vec<&[u8]> = keys.iter().map(|key| key.as_ref().as_bytes()).collect();
+        let raw_keys = &raw_keys;
+
+        let fetched = run_with_retries(
+            || async move { self.get_raw_pipeline(raw_keys).await },
+            |e| self.should_retry(e),
+            RETRY_SCHEDULE,
+        )
+        .await?;
+
+        Ok(fetched
+            .into_iter()
+            .map(|x| {
+                x.map(|x| {
+                    String::from_utf8(x)
+                        .map_err(|e| e.into())
+                        .and_then(|json| serde_json::from_str(&json).map_err(|e| e.into()))
+                })
+                .transpose()
+            })
+            .collect())
+    }
+
+    /// Fetches several raw keys at once. By default this is just one `get_raw` call per key, but
+    /// backends may override it with something cheaper.
+    async fn get_raw_pipeline(&self, keys: &[&[u8]]) -> Result<Vec<Option<Vec<u8>>>> {
+        let mut out = Vec::with_capacity(keys.len());
+        for key in keys {
+            out.push(self.get_raw(key).await?);
+        }
+        Ok(out)
+    }
+
     async fn get_string<T: StringCacheKey>(&self, key: &T) -> Result<Option<String>> {
         run_with_retries(
             || async move {
@@ -174,7 +222,13 @@ pub trait CacheBehavior: Sync + Send {
         .await
     }
 
-    async fn set<T: CacheValue>(&self, key: &T::Key, value: &T, ttl: Duration) -> Result<()> {
+    /// Sets `key` to `value`, expiring after `ttl`, or never if it's `None`.
+    async fn set<T: CacheValue>(
+        &self,
+        key: &T::Key,
+        value: &T,
+        ttl: Option<Duration>,
+    ) -> Result<()> {
         run_with_retries(
             || async move {
                 self.set_raw(
@@ -190,13 +244,13 @@ pub trait CacheBehavior: Sync + Send {
         .await
     }
 
-    async fn set_raw(&self, key: &[u8], value: &[u8], ttl: Duration) -> Result<()>;
+    async fn set_raw(&self, key: &[u8], value: &[u8], ttl: Option<Duration>) -> Result<()>;
 
     async fn set_string<T: StringCacheKey>(
         &self,
         key: &T,
         value: &str,
-        ttl: Duration,
+        ttl: Option<Duration>,
     ) -> Result<()> {
         run_with_retries(
             || async move {
@@ -209,8 +263,54 @@ pub trait CacheBehavior: Sync + Send {
         .await
     }
 
+    /// Sets `key` to `new` only if it currently holds `expected`, with `None` meaning that the key
+    /// doesn't exist. Returns whether the value was swapped, so that callers can read the value
+    /// again and retry if they lost a race.
+    async fn compare_and_swap<T: CacheValue>(
+        &self,
+        key: &T::Key,
+        expected: Option<&T>,
+        new: &T,
+        ttl: Option<Duration>,
+    ) -> Result<bool> {
+        run_with_retries(
+            || async move {
+                let expected = expected.map(serde_json::to_string).transpose()?;
+                self.compare_and_swap_raw(
+                    key.as_ref().as_bytes(),
+                    expected.as_ref().map(|x| x.as_bytes()),
+                    serde_json::to_string(new)?.as_bytes(),
+                    ttl,
+                )
+                .await
+            },
+            |e| self.should_retry(e),
+            RETRY_SCHEDULE,
+        )
+        .await
+    }
+
+    async fn compare_and_swap_raw(
+        &self,
+        key: &[u8],
+        expected: Option<&[u8]>,
+        new: &[u8],
+        ttl: Option<Duration>,
+    ) -> Result<bool>;
+
     async fn delete<T: CacheKey>(&self, key: &T) -> Result<()>;
 
+    /// Lists the keys that start with `prefix`. This walks through every key in the cache, so it
+    /// should only be used where it's rare, such as API calls for debugging.
+    async fn scan_keys(&self, prefix: &str) -> Result<Vec<String>>;
+
+    /// Reports how many keys the cache holds, and how much memory they take up.
+    async fn stats(&self) -> Result<CacheStats>;
+
+    /// Resets the expiry of an existing key to `ttl` from now without rewriting its value.
+    /// Returns whether the key existed.
+    async fn touch<T: CacheKey>(&self, key: &T, ttl: Duration) -> Result<bool>;
+
     async fn set_if_not_exists<T: CacheValue>(
         &self,
         key: &T::Key,
@@ -5,7 +5,7 @@ use std::time::Duration;
 
 use axum::async_trait;
 
-use super::{Cache, CacheBehavior, CacheKey, CacheValue, Result, StringCacheKey};
+use super::{Cache, CacheBehavior, CacheKey, CacheStats, CacheValue, Result, StringCacheKey};
 
 pub fn new() -> Cache {
     tracing::warn!("Running with caching disabled will negatively affect performance. Idempotency is not supported without a cache.");
@@ -33,11 +33,16 @@ impl CacheBehavior for NoCache {
         Ok(None)
     }
 
-    async fn set<T: CacheValue>(&self, _key: &T::Key, _value: &T, _ttl: Duration) -> Result<()> {
+    async fn set<T: CacheValue>(
+        &self,
+        _key: &T::Key,
+        _value: &T,
+        _ttl: Option<Duration>,
+    ) -> Result<()> {
         Ok(())
     }
 
-    async fn set_raw(&self, _key: &[u8], _value: &[u8], _ttl: Duration) -> Result<()> {
+    async fn set_raw(&self, _key: &[u8], _value: &[u8], _ttl: Option<Duration>) -> Result<()> {
         Ok(())
     }
 
@@ -45,22 +50,59 @@ impl CacheBehavior for NoCache {
         &self,
         _key: &T,
         _value: &str,
-        _ttl: Duration,
+        _ttl: Option<Duration>,
     ) -> Result<()> {
         Ok(())
     }
 
+    async fn compare_and_swap<T: CacheValue>(
+        &self,
+        _key: &T::Key,
+        _expected: Option<&T>,
+        _new: &T,
+        _ttl: Option<Duration>,
+    ) -> Result<bool> {
+        Ok(true)
+    }
+
+    async fn compare_and_swap_raw(
+        &self,
+        _key: &[u8],
+        _expected: Option<&[u8]>,
+        _new: &[u8],
+        _ttl: Option<Duration>,
+    ) -> Result<bool> {
+        Ok(true)
+    }
+
     async fn delete<T: CacheKey>(&self, _key: &T) -> Result<()> {
         Ok(())
     }
 
+    async fn scan_keys(&self, _prefix: &str) -> Result<Vec<String>> {
+        Ok(Vec::new())
+    }
+
+    async fn stats(&self) -> Result<CacheStats> {
+        Ok(CacheStats {
+            backend: "none",
+            key_count: Some(0),
+            memory_bytes: Some(0),
+        })
+    }
+
+    async fn touch<T: CacheKey>(&self, _key: &T, _ttl: Duration) -> Result<bool> {
+        Ok(false)
+    }
+
+    // Nothing is ever stored, so there's never an existing value in the way of setting one
     async fn set_if_not_exists<T: CacheValue>(
         &self,
         _key: &T::Key,
         _value: &T,
         _ttl: Duration,
     ) -> Result<bool> {
-        Ok(false)
+        Ok(true)
     }
 
     async fn set_raw_if_not_exists(
@@ -69,7 +111,7 @@ impl CacheBehavior for NoCache {
         _value: &[u8],
         _ttl: Duration,
     ) -> Result<bool> {
-        Ok(false)
+        Ok(true)
     }
 
     async fn set_string_if_not_exists<T: StringCacheKey>(
@@ -78,6 +120,52 @@ impl CacheBehavior for NoCache {
         _value: &str,
         _ttl: Duration,
     ) -> Result<bool> {
-        Ok(false)
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use serde::{Deserialize, Serialize};
+
+    use super::{
+        super::{kv_def, string_kv_def, CacheBehavior, CacheValue},
+        new,
+    };
+
+    #[derive(Deserialize, Serialize, Debug, PartialEq)]
+    struct TestVal(usize);
+    kv_def!(TestKey, TestVal);
+
+    string_kv_def!(StringTestKey);
+
+    #[tokio::test]
+    async fn test_set_if_not_exists_always_succeeds() {
+        let cache = new();
+        let key = TestKey("SVIX_TEST_KEY_NONE".to_owned());
+
+        // Setting the same key again succeeds too, since the first value was never stored
+        for i in 0..2 {
+            assert!(cache
+                .set_if_not_exists(&key, &TestVal(i), Duration::from_secs(30))
+                .await
+                .unwrap());
+            assert_eq!(cache.get::<TestVal>(&key).await.unwrap(), None);
+        }
+
+        assert!(cache
+            .set_raw_if_not_exists(b"SVIX_TEST_KEY_NONE_RAW", b"1", Duration::from_secs(30))
+            .await
+            .unwrap());
+        assert!(cache
+            .set_string_if_not_exists(
+                &StringTestKey("SVIX_TEST_KEY_NONE_STRING".to_owned()),
+                "1",
+                Duration::from_secs(30),
+            )
+            .await
+            .unwrap());
     }
 }
@@ -1,13 +1,80 @@
 // SPDX-FileCopyrightText: © 2022 Svix Authors
 // SPDX-License-Identifier: MIT
 
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 use axum::async_trait;
-use redis::AsyncCommands as _;
+use once_cell::sync::Lazy;
+use redis::{AsyncCommands as _, FromRedisValue, RedisResult};
 
-use super::{Cache, CacheBehavior, CacheKey, Error, Result};
-use crate::redis::RedisManager;
+use super::{Cache, CacheBehavior, CacheKey, CacheStats, Error, Result};
+use crate::redis::{PooledConnection, RedisManager};
+
+/// A Lua script, along with its SHA1 hash, which Redis caches scripts by
+struct LuaScript {
+    code: &'static str,
+    hash: String,
+}
+
+impl LuaScript {
+    fn new(code: &'static str) -> Self {
+        Self {
+            code,
+            hash: redis::Script::new(code).get_hash().to_owned(),
+        }
+    }
+
+    /// Runs the script with `EVALSHA`, so that only its hash is sent. If Redis doesn't have it
+    /// cached (such as after a restart, or on a cluster node that hasn't run it yet), the whole
+    /// script is sent with `EVAL` instead, which also caches it for next time.
+    async fn invoke<C, T>(&self, con: &mut C, keys: &[&[u8]], args: &[&[u8]]) -> RedisResult<T>
+    where
+        C: redis::aio::ConnectionLike + Send,
+        T: FromRedisValue,
+    {
+        let cmd = |name: &str, script: &str| {
+            let mut cmd = redis::cmd(name);
+            cmd.arg(script).arg(keys.len());
+            for key in keys {
+                cmd.arg(*key);
+            }
+            for arg in args {
+                cmd.arg(*arg);
+            }
+            cmd
+        };
+
+        match cmd("EVALSHA", &self.hash).query_async(con).await {
+            Err(e) if e.kind() == redis::ErrorKind::NoScriptError => {
+                cmd("EVAL", self.code).query_async(con).await
+            }
+            res => res,
+        }
+    }
+}
+
+/// Swaps `KEYS[1]` to `ARGV[3]` if it holds `ARGV[2]`, or doesn't exist when `ARGV[1]` is `0`.
+/// `ARGV[4]` is the TTL in milliseconds, or `0` for none.
+static COMPARE_AND_SWAP_SCRIPT: Lazy<LuaScript> = Lazy::new(|| {
+    LuaScript::new(
+        r#"
+local current = redis.call('GET', KEYS[1])
+if ARGV[1] == '1' then
+    if current ~= ARGV[2] then
+        return 0
+    end
+elseif current then
+    return 0
+end
+if ARGV[4] == '0' then
+    redis.call('SET', KEYS[1], ARGV[3])
+else
+    redis.call('SET', KEYS[1], ARGV[3], 'PX', ARGV[4])
+end
+return 1
+"#,
+    )
+});
 
 pub fn new(redis: RedisManager) -> Cache {
     RedisCache { redis }.into()
@@ -18,36 +85,76 @@ pub struct RedisCache {
     redis: RedisManager,
 }
 
+impl RedisCache {
+    async fn get_conn(&self) -> Result<PooledConnection<'_>> {
+        let start = Instant::now();
+        self.redis.get().await.map_err(|e| {
+            e.record(start.elapsed());
+            e.into()
+        })
+    }
+}
+
 #[async_trait]
 impl CacheBehavior for RedisCache {
     fn should_retry(&self, e: &Error) -> bool {
-        matches!(e, Error::Pool(_) | Error::Database(_))
+        match e {
+            Error::Pool(e) => e.is_transient(),
+            Error::Database(_) => true,
+            _ => false,
+        }
     }
 
     async fn get_raw(&self, key: &[u8]) -> Result<Option<Vec<u8>>> {
-        let mut pool = self.redis.get().await?;
+        let mut pool = self.get_conn().await?;
 
         let fetched: Option<Vec<u8>> = pool.get(key).await?;
 
         Ok(fetched)
     }
 
-    async fn set_raw(&self, key: &[u8], value: &[u8], ttl: Duration) -> Result<()> {
-        let mut pool = self.redis.get().await?;
+    async fn get_raw_pipeline(&self, keys: &[&[u8]]) -> Result<Vec<Option<Vec<u8>>>> {
+        let mut pool = self.get_conn().await?;
+
+        // The keys of a single pipeline may live on different nodes of a cluster, so fall back to
+        // fetching them one by one in that case
+        if pool.is_clustered() {
+            let mut out = Vec::with_capacity(keys.len());
+            for key in keys {
+                out.push(pool.get(*key).await?);
+            }
+            return Ok(out);
+        }
 
-        pool.pset_ex(
-            key,
-            value,
-            ttl.as_millis().try_into().map_err(|e| {
-                Error::Input(format!("Duration given cannot be converted to usize: {e}"))
-            })?,
-        )
+        let mut pipe = redis::pipe();
+        for key in keys {
+            let _ = pipe.get(*key);
+        }
+
+        let fetched: Vec<Option<Vec<u8>>> = pool.query_async_pipeline(pipe).await?;
+
+        Ok(fetched)
+    }
+
+    async fn set_raw(&self, key: &[u8], value: &[u8], ttl: Option<Duration>) -> Result<()> {
+        let mut pool = self.get_conn().await?;
+
+        match ttl {
+            Some(ttl) => pool.pset_ex(
+                key,
+                value,
+                ttl.as_millis().try_into().map_err(|e| {
+                    Error::Input(format!("Duration given cannot be converted to usize: {e}"))
+                })?,
+            ),
+            None => pool.set(key, value),
+        }
         .await
         .map_err(Into::into)
     }
 
     async fn set_raw_if_not_exists(&self, key: &[u8], value: &[u8], ttl: Duration) -> Result<bool> {
-        let mut pool = self.redis.get().await?;
+        let mut pool = self.get_conn().await?;
 
         let mut cmd = redis::Cmd::set(key, value);
 
@@ -64,24 +171,144 @@ impl CacheBehavior for RedisCache {
         Ok(res.is_some())
     }
 
+    async fn compare_and_swap_raw(
+        &self,
+        key: &[u8],
+        expected: Option<&[u8]>,
+        new: &[u8],
+        ttl: Option<Duration>,
+    ) -> Result<bool> {
+        let mut pool = self.get_conn().await?;
+
+        let ttl_as_millis: u64 = match ttl {
+            Some(ttl) => ttl.as_millis().try_into().map_err(|e| {
+                Error::Input(format!("Duration given cannot be converted to usize: {e}"))
+            })?,
+            None => 0,
+        };
+
+        let has_expected: &[u8] = if expected.is_some() { b"1" } else { b"0" };
+        let swapped: bool = COMPARE_AND_SWAP_SCRIPT
+            .invoke(
+                &mut pool,
+                &[key],
+                &[
+                    has_expected,
+                    expected.unwrap_or_default(),
+                    new,
+                    ttl_as_millis.to_string().as_bytes(),
+                ],
+            )
+            .await?;
+
+        Ok(swapped)
+    }
+
     async fn delete<T: CacheKey>(&self, key: &T) -> Result<()> {
-        let mut pool = self.redis.get().await?;
+        let mut pool = self.get_conn().await?;
 
         let _: () = pool.del(key.as_ref()).await?;
 
         Ok(())
     }
+
+    async fn scan_keys(&self, prefix: &str) -> Result<Vec<String>> {
+        let mut pool = self.get_conn().await?;
+
+        let pattern = format!("{}*", escape_glob(prefix));
+        pool.scan_match(&pattern).await.map_err(Into::into)
+    }
+
+    async fn stats(&self) -> Result<CacheStats> {
+        let mut pool = self.get_conn().await?;
+
+        // `INFO` only describes the node it's sent to, so it doesn't say anything about a cluster
+        // as a whole
+        if pool.is_clustered() {
+            return Ok(CacheStats {
+                backend: "redis-cluster",
+                key_count: None,
+                memory_bytes: None,
+            });
+        }
+
+        let mut cmd = redis::cmd("INFO");
+        cmd.arg("keyspace");
+        let keyspace: String = pool.query_async(cmd).await?;
+
+        let mut cmd = redis::cmd("INFO");
+        cmd.arg("memory");
+        let memory: String = pool.query_async(cmd).await?;
+
+        Ok(CacheStats {
+            backend: "redis",
+            key_count: Some(keyspace_key_count(&keyspace)),
+            memory_bytes: info_field(&memory, "used_memory").and_then(|v| v.parse().ok()),
+        })
+    }
+
+    async fn touch<T: CacheKey>(&self, key: &T, ttl: Duration) -> Result<bool> {
+        let mut pool = self.get_conn().await?;
+
+        pool.pexpire(
+            key.as_ref(),
+            ttl.as_millis().try_into().map_err(|e| {
+                Error::Input(format!("Duration given cannot be converted to i64: {e}"))
+            })?,
+        )
+        .await
+        .map_err(Into::into)
+    }
+}
+
+/// The value of `field` in the output of `INFO`, which is made of `field:value` lines
+fn info_field<'a>(info: &'a str, field: &str) -> Option<&'a str> {
+    info.lines().find_map(|line| {
+        line.trim_end()
+            .split_once(':')
+            .filter(|(name, _)| *name == field)
+            .map(|(_, value)| value)
+    })
+}
+
+/// The total number of keys across all databases, from the output of `INFO keyspace`. It lists
+/// each database holding keys as a line like `db0:keys=1,expires=0,avg_ttl=0`.
+fn keyspace_key_count(info: &str) -> u64 {
+    info.lines()
+        .filter(|line| line.starts_with("db"))
+        .filter_map(|line| {
+            let (_, stats) = line.trim_end().split_once(':')?;
+            stats
+                .split(',')
+                .find_map(|stat| stat.strip_prefix("keys="))
+                .and_then(|keys| keys.parse::<u64>().ok())
+        })
+        .sum()
+}
+
+/// Escapes the characters with a special meaning in the patterns of `SCAN ... MATCH`
+fn escape_glob(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        if matches!(c, '*' | '?' | '[' | ']' | '\\') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
 }
 
 #[cfg(test)]
 mod tests {
+    use std::collections::HashSet;
+
     use serde::{Deserialize, Serialize};
 
     use super::{
         super::{kv_def, string_kv_def, CacheValue},
         *,
     };
-    use crate::cfg::Configuration;
+    use crate::{cfg::Configuration, redis::RedisTlsConfig};
 
     // Test structures
 
@@ -111,7 +338,11 @@ mod tests {
     }
 
     async fn get_pool(cfg: &Configuration) -> RedisManager {
-        RedisManager::from_cache_backend(&cfg.cache_backend()).await
+        RedisManager::from_cache_backend(
+            &cfg.cache_backend(),
+            &RedisTlsConfig::from_configuration(&cfg),
+        )
+        .await
     }
 
     #[tokio::test]
@@ -139,15 +370,15 @@ mod tests {
 
         // Create
         assert!(cache
-            .set(&first_key, &first_val_a, Duration::from_secs(30),)
+            .set(&first_key, &first_val_a, Some(Duration::from_secs(30)),)
             .await
             .is_ok());
         assert!(cache
-            .set(&second_key, &second_val_a, Duration::from_secs(30),)
+            .set(&second_key, &second_val_a, Some(Duration::from_secs(30)),)
             .await
             .is_ok());
         assert!(cache
-            .set_string(&third_key, &third_val_a, Duration::from_secs(30),)
+            .set_string(&third_key, &third_val_a, Some(Duration::from_secs(30)),)
             .await
             .is_ok());
 
@@ -161,15 +392,15 @@ mod tests {
 
         // Update (overwrite)
         assert!(cache
-            .set(&first_key, &first_val_b, Duration::from_secs(30),)
+            .set(&first_key, &first_val_b, Some(Duration::from_secs(30)),)
             .await
             .is_ok());
         assert!(cache
-            .set(&second_key, &second_val_b, Duration::from_secs(30),)
+            .set(&second_key, &second_val_b, Some(Duration::from_secs(30)),)
             .await
             .is_ok());
         assert!(cache
-            .set_string(&third_key, &third_val_b, Duration::from_secs(30),)
+            .set_string(&third_key, &third_val_b, Some(Duration::from_secs(30)),)
             .await
             .is_ok());
 
@@ -204,13 +435,66 @@ mod tests {
         let key = TestKeyA::new("key".to_owned());
 
         assert!(cache
-            .set(&key, &TestValA(1), Duration::from_secs(1),)
+            .set(&key, &TestValA(1), Some(Duration::from_secs(1)),)
             .await
             .is_ok());
         tokio::time::sleep(std::time::Duration::from_millis(1200)).await;
         assert_eq!(cache.get::<TestValA>(&key).await.unwrap(), None);
     }
 
+    #[tokio::test]
+    #[ignore]
+    async fn test_cache_no_ttl() {
+        dotenvy::dotenv().ok();
+        let cfg = crate::cfg::load().unwrap();
+
+        let redis_pool = get_pool(&cfg).await;
+        let cache = super::new(redis_pool);
+
+        let key = TestKeyA::new("no_ttl_key".to_owned());
+
+        cache
+            .set(&key, &TestValA(1), Some(Duration::from_secs(1)))
+            .await
+            .unwrap();
+        // Overwriting the value without a TTL also drops its expiry
+        cache.set(&key, &TestValA(2), None).await.unwrap();
+        tokio::time::sleep(std::time::Duration::from_millis(1200)).await;
+        assert_eq!(cache.get(&key).await.unwrap(), Some(TestValA(2)));
+
+        cache.delete(&key).await.unwrap();
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_cache_touch() {
+        dotenvy::dotenv().ok();
+        let cfg = crate::cfg::load().unwrap();
+
+        let redis_pool = get_pool(&cfg).await;
+        let cache = super::new(redis_pool);
+
+        let key = TestKeyA::new("touch_test_key".to_owned());
+        cache.delete(&key).await.unwrap();
+
+        assert!(!cache.touch(&key, Duration::from_secs(1)).await.unwrap());
+
+        cache
+            .set(&key, &TestValA(1), Some(Duration::from_secs(1)))
+            .await
+            .unwrap();
+        tokio::time::sleep(std::time::Duration::from_millis(600)).await;
+        assert!(cache.touch(&key, Duration::from_secs(1)).await.unwrap());
+
+        // Past the original expiry, but within the new one
+        tokio::time::sleep(std::time::Duration::from_millis(600)).await;
+        assert_eq!(cache.get(&key).await.unwrap(), Some(TestValA(1)));
+
+        tokio::time::sleep(std::time::Duration::from_millis(600)).await;
+        assert_eq!(cache.get::<TestValA>(&key).await.unwrap(), None);
+        assert!(!cache.touch(&key, Duration::from_secs(1)).await.unwrap());
+    }
+
     #[tokio::test]
     #[ignore]
     async fn test_cache_nx_status() {
@@ -236,4 +520,288 @@ mod tests {
 
         assert!(cache.delete(&key).await.is_ok());
     }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_cache_compare_and_swap() {
+        dotenvy::dotenv().ok();
+        let cfg = crate::cfg::load().unwrap();
+
+        let redis_pool = get_pool(&cfg).await;
+        let cache = super::new(redis_pool);
+
+        let key = TestKeyA::new("cas_test_key".to_owned());
+        // Left over from an earlier run that failed part way through
+        assert!(cache.delete(&key).await.is_ok());
+
+        // A missing key only matches `None`
+        assert!(!cache
+            .compare_and_swap(&key, Some(&TestValA(0)), &TestValA(1), None)
+            .await
+            .unwrap());
+        assert!(cache
+            .compare_and_swap(&key, None, &TestValA(1), None)
+            .await
+            .unwrap());
+        assert_eq!(cache.get(&key).await.unwrap(), Some(TestValA(1)));
+
+        assert!(!cache
+            .compare_and_swap(&key, None, &TestValA(2), None)
+            .await
+            .unwrap());
+        assert!(!cache
+            .compare_and_swap(&key, Some(&TestValA(3)), &TestValA(2), None)
+            .await
+            .unwrap());
+        assert_eq!(cache.get(&key).await.unwrap(), Some(TestValA(1)));
+
+        assert!(cache
+            .compare_and_swap(
+                &key,
+                Some(&TestValA(1)),
+                &TestValA(2),
+                Some(Duration::from_secs(30))
+            )
+            .await
+            .unwrap());
+        assert_eq!(cache.get(&key).await.unwrap(), Some(TestValA(2)));
+
+        assert!(cache.delete(&key).await.is_ok());
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_cache_scan_keys() {
+        dotenvy::dotenv().ok();
+        let cfg = crate::cfg::load().unwrap();
+
+        let redis_pool = get_pool(&cfg).await;
+        let cache = super::new(redis_pool);
+
+        // Enough keys to need several `SCAN` calls
+        let keys: Vec<_> = (0..2500)
+            .map(|i| TestKeyA::new(format!("scan_{i}")))
+            .collect();
+        for key in &keys {
+            cache
+                .set(key, &TestValA(0), Some(Duration::from_secs(30)))
+                .await
+                .unwrap();
+        }
+        // Glob characters in the prefix are matched literally
+        let glob_key = TestKeyA::new("scan*glob".to_owned());
+        cache
+            .set(&glob_key, &TestValA(0), Some(Duration::from_secs(30)))
+            .await
+            .unwrap();
+
+        let mut scanned = cache.scan_keys("SVIX_TEST_KEY_A_scan_").await.unwrap();
+        scanned.sort();
+        let mut expected: Vec<_> = keys.iter().map(|key| key.as_ref().to_owned()).collect();
+        expected.sort();
+        assert_eq!(scanned, expected);
+
+        assert_eq!(
+            cache.scan_keys("SVIX_TEST_KEY_A_scan*").await.unwrap(),
+            [glob_key.as_ref()]
+        );
+
+        for key in keys.iter().chain([&glob_key]) {
+            cache.delete(key).await.unwrap();
+        }
+    }
+
+    /// Runs scripts like Redis does, keeping a log of the commands it's sent
+    #[derive(Default)]
+    struct MockRedis {
+        cached_scripts: HashSet<String>,
+        commands: Vec<String>,
+    }
+
+    impl redis::aio::ConnectionLike for MockRedis {
+        fn req_packed_command<'a>(
+            &'a mut self,
+            cmd: &'a redis::Cmd,
+        ) -> redis::RedisFuture<'a, redis::Value> {
+            let args: Vec<String> = cmd
+                .args_iter()
+                .map(|arg| match arg {
+                    redis::Arg::Simple(arg) => String::from_utf8_lossy(arg).into_owned(),
+                    redis::Arg::Cursor => unreachable!(),
+                })
+                .collect();
+            self.commands.push(args[0].clone());
+
+            let res = match args[0].as_str() {
+                "EVALSHA" if self.cached_scripts.contains(&args[1]) => Ok(redis::Value::Int(1)),
+                "EVALSHA" => Err((redis::ErrorKind::NoScriptError, "No matching script").into()),
+                "EVAL" => {
+                    self.cached_scripts
+                        .insert(redis::Script::new(&args[1]).get_hash().to_owned());
+                    Ok(redis::Value::Int(1))
+                }
+                other => panic!("unexpected command {other}"),
+            };
+            Box::pin(async move { res })
+        }
+
+        fn req_packed_commands<'a>(
+            &'a mut self,
+            _cmd: &'a redis::Pipeline,
+            _offset: usize,
+            _count: usize,
+        ) -> redis::RedisFuture<'a, Vec<redis::Value>> {
+            unimplemented!()
+        }
+
+        fn get_db(&self) -> i64 {
+            0
+        }
+    }
+
+    #[tokio::test]
+    async fn test_lua_script_evalsha() {
+        async fn invoke(con: &mut MockRedis) -> RedisResult<bool> {
+            COMPARE_AND_SWAP_SCRIPT
+                .invoke(con, &[b"key".as_slice()], &[b"0".as_slice()])
+                .await
+        }
+
+        let mut con = MockRedis::default();
+
+        // The script is only sent in full the first time, when Redis doesn't have it yet
+        assert!(invoke(&mut con).await.unwrap());
+        assert_eq!(con.commands, ["EVALSHA", "EVAL"]);
+
+        con.commands.clear();
+        assert!(invoke(&mut con).await.unwrap());
+        assert!(invoke(&mut con).await.unwrap());
+        assert_eq!(con.commands, ["EVALSHA", "EVALSHA"]);
+
+        // Such as after Redis restarts
+        con.cached_scripts.clear();
+        con.commands.clear();
+        assert!(invoke(&mut con).await.unwrap());
+        assert_eq!(con.commands, ["EVALSHA", "EVAL"]);
+    }
+
+    #[test]
+    fn test_escape_glob() {
+        assert_eq!(escape_glob("SVIX_KEY_abc"), "SVIX_KEY_abc");
+        assert_eq!(escape_glob(r"a*b?c[d]e\f"), r"a\*b\?c\[d\]e\\f");
+    }
+
+    #[test]
+    fn test_info_parsing() {
+        let keyspace =
+            "# Keyspace\r\ndb0:keys=12,expires=3,avg_ttl=0\r\ndb2:keys=5,expires=0,avg_ttl=0\r\n";
+        assert_eq!(keyspace_key_count(keyspace), 17);
+        assert_eq!(keyspace_key_count("# Keyspace\r\n"), 0);
+
+        let memory = "# Memory\r\nused_memory:1048576\r\nused_memory_human:1.00M\r\n";
+        assert_eq!(info_field(memory, "used_memory"), Some("1048576"));
+        assert_eq!(info_field(memory, "used_memory_rss"), None);
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_cache_stats() {
+        dotenvy::dotenv().ok();
+        let cfg = crate::cfg::load().unwrap();
+
+        let redis_pool = get_pool(&cfg).await;
+        let cache = super::new(redis_pool);
+
+        let key = TestKeyA::new("stats".to_owned());
+        cache
+            .set(&key, &TestValA(1), Some(Duration::from_secs(30)))
+            .await
+            .unwrap();
+
+        let stats = cache.stats().await.unwrap();
+        if stats.backend == "redis" {
+            assert!(stats.key_count.unwrap() >= 1);
+            assert!(stats.memory_bytes.unwrap() > 0);
+        }
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_cache_get_pipeline() {
+        dotenvy::dotenv().ok();
+        let cfg = crate::cfg::load().unwrap();
+
+        let redis_pool = get_pool(&cfg).await;
+        let cache = super::new(redis_pool);
+
+        let keys: Vec<_> = (0..3)
+            .map(|i| TestKeyA::new(format!("pipeline_{i}")))
+            .collect();
+
+        cache
+            .set(&keys[0], &TestValA(0), Some(Duration::from_secs(30)))
+            .await
+            .unwrap();
+        // Not a valid `TestValA`
+        cache
+            .set_raw(
+                keys[1].as_ref().as_bytes(),
+                b"\"nope\"",
+                Some(Duration::from_secs(30)),
+            )
+            .await
+            .unwrap();
+
+        let fetched = cache
+            .get_pipeline::<TestValA>(&keys.iter().collect::<Vec<_>>())
+            .await
+            .unwrap();
+        assert_eq!(fetched.len(), 3);
+        assert_eq!(fetched[0].as_ref().unwrap(), &Some(TestValA(0)));
+        assert!(fetched[1].is_err());
+        assert_eq!(fetched[2].as_ref().unwrap(), &None);
+
+        for key in &keys {
+            cache.delete(key).await.unwrap();
+        }
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_cache_get_pipeline_matches_sequential_gets() {
+        dotenvy::dotenv().ok();
+        let cfg = crate::cfg::load().unwrap();
+
+        let redis_pool = get_pool(&cfg).await;
+        let cache = super::new(redis_pool);
+
+        let keys: Vec<_> = (0..50)
+            .map(|i| TestKeyA::new(format!("pipeline_latency_{i}")))
+            .collect();
+        for (i, key) in keys.iter().enumerate() {
+            cache
+                .set(key, &TestValA(i), Some(Duration::from_secs(30)))
+                .await
+                .unwrap();
+        }
+
+        let mut sequential = Vec::with_capacity(keys.len());
+        for key in &keys {
+            sequential.push(cache.get::<TestValA>(key).await.unwrap());
+        }
+
+        let pipelined: Vec<_> = cache
+            .get_pipeline::<TestValA>(&keys.iter().collect::<Vec<_>>())
+            .await
+            .unwrap()
+            .into_iter()
+            .map(Result::unwrap)
+            .collect();
+
+        assert_eq!(pipelined, sequential);
+
+        for key in &keys {
+            cache.delete(key).await.unwrap();
+        }
+    }
 }
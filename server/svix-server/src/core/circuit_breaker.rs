@@ -0,0 +1,470 @@
+// SPDX-FileCopyrightText: © 2022 Svix Authors
+// SPDX-License-Identifier: MIT
+
+//! A per-endpoint circuit breaker, which short-circuits dispatching to an endpoint for a cooldown
+//! period after it has failed a number of consecutive deliveries.
+//!
+//! Once the cooldown is over, a single trial delivery is let through to decide whether the
+//! circuit closes again, while the other deliveries due are put off until it's done.
+//!
+//! The state is kept in the [`Cache`] so that it is shared by all worker instances. For that
+//! reason it's tracked with wall-clock times rather than [`std::time::Instant`]s, and updated
+//! with compare-and-swap so that concurrent deliveries don't overwrite each other's updates.
+
+use std::time::Duration;
+
+use chrono::Utc;
+use sea_orm::prelude::DateTimeUtc;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    core::{
+        cache::{kv_def, Cache, CacheBehavior, CacheKey, CacheValue},
+        types::{ApplicationId, EndpointId, OrganizationId},
+    },
+    error::{Error, Result},
+};
+
+/// How long failures are remembered for if the endpoint doesn't fail again
+const FAILURE_EXPIRY: Duration = Duration::from_secs(60 * 60);
+
+/// How long other deliveries are put off for while the trial delivery is being made. Longer than
+/// a delivery normally takes, so that another trial is only let through if the first one never
+/// reported back.
+const TRIAL_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// How many times updating the state is tried when it keeps being changed concurrently
+const MAX_SWAP_ATTEMPTS: usize = 10;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CircuitState {
+    /// Deliveries are made as usual
+    Closed,
+    /// Deliveries are put off until the given time, which is either the end of the cooldown or
+    /// when the trial delivery being made is expected to be over
+    Open { until: DateTimeUtc },
+    /// The cooldown has elapsed and no trial delivery is being made. The next delivery is the
+    /// trial one, and decides whether the circuit closes again or goes back to being open.
+    HalfOpen,
+}
+
+/// A change in the state of the circuit that's worth telling the endpoint's owner about
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CircuitTransition {
+    /// The circuit has opened, either for the first time or because the trial delivery made once
+    /// it was half-open failed
+    Opened {
+        until: DateTimeUtc,
+        consecutive_failures: u32,
+    },
+    /// A delivery succeeded while the circuit was open or half-open, so it has closed again
+    Closed { consecutive_failures: u32 },
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+pub struct CircuitBreakerCacheValue {
+    consecutive_failures: u32,
+    open_until: Option<DateTimeUtc>,
+    /// Set once the cooldown is over, for as long as the trial delivery is let take. Left out
+    /// when unset, so that values written before it existed can still be swapped.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    trial_until: Option<DateTimeUtc>,
+}
+
+kv_def!(CircuitBreakerCacheKey, CircuitBreakerCacheValue);
+
+impl CircuitBreakerCacheKey {
+    pub fn new(
+        org_id: &OrganizationId,
+        app_id: &ApplicationId,
+        endp_id: &EndpointId,
+    ) -> CircuitBreakerCacheKey {
+        CircuitBreakerCacheKey(format!("SVIX_CIRCUIT_BREAKER_{org_id}_{app_id}_{endp_id}"))
+    }
+}
+
+impl CircuitBreakerCacheValue {
+    fn state(&self, now: DateTimeUtc) -> CircuitState {
+        match (self.open_until, self.trial_until) {
+            (Some(until), _) if now < until => CircuitState::Open { until },
+            (Some(_), Some(until)) if now < until => CircuitState::Open { until },
+            (Some(_), _) => CircuitState::HalfOpen,
+            (None, _) => CircuitState::Closed,
+        }
+    }
+
+    /// Whether the cooldown is still going, as opposed to being over or never having started
+    fn is_cooling_down(&self, now: DateTimeUtc) -> bool {
+        matches!(self.open_until, Some(until) if now < until)
+    }
+
+    fn with_trial(&self, now: DateTimeUtc) -> Self {
+        Self {
+            trial_until: Some(
+                now + chrono::Duration::from_std(TRIAL_TIMEOUT).expect("Timeout is too large"),
+            ),
+            ..self.clone()
+        }
+    }
+
+    fn with_failure(&self, now: DateTimeUtc, threshold: u32, cooldown: Duration) -> Self {
+        let consecutive_failures = self.consecutive_failures.saturating_add(1);
+        let open = match self.open_until {
+            None => consecutive_failures >= threshold,
+            // Deliveries that were already in flight when the circuit opened don't extend the
+            // cooldown
+            Some(_) if self.is_cooling_down(now) => {
+                return Self {
+                    consecutive_failures,
+                    ..self.clone()
+                }
+            }
+            // The trial delivery failed, so go straight back to being open
+            Some(_) => true,
+        };
+
+        Self {
+            consecutive_failures,
+            open_until: open.then(|| {
+                // The cooldown is limited by the config's validation, so always fits
+                now + chrono::Duration::from_std(cooldown).expect("Cooldown is too large")
+            }),
+            trial_until: None,
+        }
+    }
+}
+
+pub struct CircuitBreaker<'a> {
+    cache: &'a Cache,
+    key: CircuitBreakerCacheKey,
+    /// The number of consecutive failures after which the circuit opens. Zero disables the
+    /// circuit breaker.
+    threshold: u16,
+    cooldown: Duration,
+}
+
+impl<'a> CircuitBreaker<'a> {
+    pub fn new(
+        cache: &'a Cache,
+        org_id: &OrganizationId,
+        app_id: &ApplicationId,
+        endp_id: &EndpointId,
+        threshold: u16,
+        cooldown: Duration,
+    ) -> Self {
+        Self {
+            cache,
+            key: CircuitBreakerCacheKey::new(org_id, app_id, endp_id),
+            threshold,
+            cooldown,
+        }
+    }
+
+    fn is_enabled(&self) -> bool {
+        self.threshold > 0
+    }
+
+    async fn get(&self) -> Result<Option<CircuitBreakerCacheValue>> {
+        self.cache
+            .get::<CircuitBreakerCacheValue>(&self.key)
+            .await
+            .map_err(Error::cache)
+    }
+
+    /// Replaces the state, unless it has been changed since it was read as `current`
+    async fn swap(
+        &self,
+        current: Option<&CircuitBreakerCacheValue>,
+        new: &CircuitBreakerCacheValue,
+    ) -> Result<bool> {
+        self.cache
+            .compare_and_swap(
+                &self.key,
+                current,
+                new,
+                Some(std::cmp::max(FAILURE_EXPIRY, self.cooldown * 2)),
+            )
+            .await
+            .map_err(Error::cache)
+    }
+
+    pub async fn state(&self) -> Result<CircuitState> {
+        if !self.is_enabled() {
+            return Ok(CircuitState::Closed);
+        }
+
+        Ok(self.get().await?.unwrap_or_default().state(Utc::now()))
+    }
+
+    /// Checks whether a delivery can be made. If the cooldown is over, the first delivery to
+    /// call this is let through as the trial one, and is told so with [`CircuitState::HalfOpen`].
+    /// Other deliveries are then told the circuit is open until the trial is expected to be over.
+    pub async fn acquire(&self) -> Result<CircuitState> {
+        if !self.is_enabled() {
+            return Ok(CircuitState::Closed);
+        }
+
+        for _ in 0..MAX_SWAP_ATTEMPTS {
+            let now = Utc::now();
+            let current = self.get().await?;
+            let Some(value) = current.as_ref() else {
+                return Ok(CircuitState::Closed);
+            };
+
+            let state = value.state(now);
+            if state != CircuitState::HalfOpen {
+                return Ok(state);
+            }
+
+            if self.swap(current.as_ref(), &value.with_trial(now)).await? {
+                return Ok(CircuitState::HalfOpen);
+            }
+        }
+
+        Err(Error::generic(format!(
+            "Failed to update the circuit breaker state {}",
+            self.key.as_ref()
+        )))
+    }
+
+    /// Closes the circuit and resets the failure count. Returns the transition if the circuit
+    /// wasn't closed already.
+    pub async fn record_success(&self) -> Result<Option<CircuitTransition>> {
+        if !self.is_enabled() {
+            return Ok(None);
+        }
+
+        let Some(value) = self.get().await? else {
+            return Ok(None);
+        };
+
+        self.cache.delete(&self.key).await.map_err(Error::cache)?;
+
+        Ok(value.open_until.map(|_| CircuitTransition::Closed {
+            consecutive_failures: value.consecutive_failures,
+        }))
+    }
+
+    /// Counts a failed delivery. Returns the transition if it opened the circuit.
+    pub async fn record_failure(&self) -> Result<Option<CircuitTransition>> {
+        if !self.is_enabled() {
+            return Ok(None);
+        }
+
+        for _ in 0..MAX_SWAP_ATTEMPTS {
+            let now = Utc::now();
+            let current = self.get().await?;
+            let previous = current.clone().unwrap_or_default();
+            let value = previous.with_failure(now, self.threshold.into(), self.cooldown);
+
+            if !self.swap(current.as_ref(), &value).await? {
+                continue;
+            }
+
+            return Ok(match value.open_until {
+                Some(until) if value.open_until != previous.open_until => {
+                    Some(CircuitTransition::Opened {
+                        until,
+                        consecutive_failures: value.consecutive_failures,
+                    })
+                }
+                _ => None,
+            });
+        }
+
+        Err(Error::generic(format!(
+            "Failed to update the circuit breaker state {}",
+            self.key.as_ref()
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use chrono::Utc;
+
+    use super::{CircuitBreaker, CircuitBreakerCacheValue, CircuitState, CircuitTransition};
+    use crate::core::{
+        cache::memory,
+        types::{ApplicationId, BaseId, EndpointId, OrganizationId},
+    };
+
+    const COOLDOWN: Duration = Duration::from_secs(60);
+
+    #[test]
+    fn test_opens_after_threshold() {
+        let now = Utc::now();
+
+        let value = CircuitBreakerCacheValue::default();
+        assert_eq!(value.state(now), CircuitState::Closed);
+
+        let value = value.with_failure(now, 3, COOLDOWN);
+        assert_eq!(value.state(now), CircuitState::Closed);
+        let value = value.with_failure(now, 3, COOLDOWN);
+        assert_eq!(value.state(now), CircuitState::Closed);
+
+        let value = value.with_failure(now, 3, COOLDOWN);
+        let until = now + chrono::Duration::from_std(COOLDOWN).unwrap();
+        assert_eq!(value.state(now), CircuitState::Open { until });
+
+        // Failures while open don't extend the cooldown
+        let later = now + chrono::Duration::seconds(10);
+        let value = value.with_failure(later, 3, COOLDOWN);
+        assert_eq!(value.state(later), CircuitState::Open { until });
+    }
+
+    #[test]
+    fn test_half_open_after_cooldown() {
+        let now = Utc::now();
+
+        let value = CircuitBreakerCacheValue::default().with_failure(now, 1, COOLDOWN);
+        assert!(matches!(value.state(now), CircuitState::Open { .. }));
+
+        let after_cooldown = now + chrono::Duration::from_std(COOLDOWN).unwrap();
+        assert_eq!(value.state(after_cooldown), CircuitState::HalfOpen);
+
+        // A failed trial delivery opens the circuit again, for another full cooldown
+        let value = value.with_failure(after_cooldown, 1, COOLDOWN);
+        assert_eq!(
+            value.state(after_cooldown),
+            CircuitState::Open {
+                until: after_cooldown + chrono::Duration::from_std(COOLDOWN).unwrap()
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_circuit_breaker_with_cache() {
+        let cache = memory::new();
+        let org_id = OrganizationId::new(None, None);
+        let app_id = ApplicationId::new(None, None);
+        let endp_id = EndpointId::new(None, None);
+
+        let breaker = CircuitBreaker::new(&cache, &org_id, &app_id, &endp_id, 2, COOLDOWN);
+        assert_eq!(breaker.state().await.unwrap(), CircuitState::Closed);
+
+        breaker.record_failure().await.unwrap();
+        assert_eq!(breaker.state().await.unwrap(), CircuitState::Closed);
+
+        // A success resets the count of consecutive failures
+        breaker.record_success().await.unwrap();
+        breaker.record_failure().await.unwrap();
+        assert_eq!(breaker.state().await.unwrap(), CircuitState::Closed);
+
+        breaker.record_failure().await.unwrap();
+        assert!(matches!(
+            breaker.state().await.unwrap(),
+            CircuitState::Open { .. }
+        ));
+
+        breaker.record_success().await.unwrap();
+        assert_eq!(breaker.state().await.unwrap(), CircuitState::Closed);
+    }
+
+    #[tokio::test]
+    async fn test_circuit_transitions() {
+        let cache = memory::new();
+        let org_id = OrganizationId::new(None, None);
+        let app_id = ApplicationId::new(None, None);
+        let endp_id = EndpointId::new(None, None);
+        let cooldown = Duration::from_millis(100);
+
+        let breaker = CircuitBreaker::new(&cache, &org_id, &app_id, &endp_id, 2, cooldown);
+        assert_eq!(breaker.record_success().await.unwrap(), None);
+        assert_eq!(breaker.record_failure().await.unwrap(), None);
+
+        let Some(CircuitTransition::Opened {
+            consecutive_failures: 2,
+            ..
+        }) = breaker.record_failure().await.unwrap()
+        else {
+            panic!("The circuit should have opened");
+        };
+        // Failures while open don't open it again
+        assert_eq!(breaker.record_failure().await.unwrap(), None);
+
+        // Once half-open, a failed trial delivery opens it again
+        tokio::time::sleep(cooldown * 2).await;
+        assert_eq!(breaker.state().await.unwrap(), CircuitState::HalfOpen);
+        assert!(matches!(
+            breaker.record_failure().await.unwrap(),
+            Some(CircuitTransition::Opened {
+                consecutive_failures: 4,
+                ..
+            })
+        ));
+
+        tokio::time::sleep(cooldown * 2).await;
+        assert_eq!(
+            breaker.record_success().await.unwrap(),
+            Some(CircuitTransition::Closed {
+                consecutive_failures: 4
+            })
+        );
+        assert_eq!(breaker.record_success().await.unwrap(), None);
+
+        // Failures below the threshold aren't worth reporting when they're reset either
+        assert_eq!(breaker.record_failure().await.unwrap(), None);
+        assert_eq!(breaker.record_success().await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_single_trial_delivery() {
+        let cache = memory::new();
+        let org_id = OrganizationId::new(None, None);
+        let app_id = ApplicationId::new(None, None);
+        let endp_id = EndpointId::new(None, None);
+        let cooldown = Duration::from_millis(100);
+
+        let breaker = CircuitBreaker::new(&cache, &org_id, &app_id, &endp_id, 1, cooldown);
+        assert_eq!(breaker.acquire().await.unwrap(), CircuitState::Closed);
+        breaker.record_failure().await.unwrap();
+        assert!(matches!(
+            breaker.acquire().await.unwrap(),
+            CircuitState::Open { .. }
+        ));
+
+        // Only the first delivery once the cooldown is over is let through
+        tokio::time::sleep(cooldown * 2).await;
+        assert_eq!(breaker.acquire().await.unwrap(), CircuitState::HalfOpen);
+        let CircuitState::Open { until } = breaker.acquire().await.unwrap() else {
+            panic!("Only one trial delivery should be let through");
+        };
+        assert!(until > Utc::now() + chrono::Duration::from_std(cooldown).unwrap());
+
+        breaker.record_success().await.unwrap();
+        assert_eq!(breaker.acquire().await.unwrap(), CircuitState::Closed);
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_failures_are_all_counted() {
+        let cache = memory::new();
+        let org_id = OrganizationId::new(None, None);
+        let app_id = ApplicationId::new(None, None);
+        let endp_id = EndpointId::new(None, None);
+
+        let breaker = CircuitBreaker::new(&cache, &org_id, &app_id, &endp_id, 100, COOLDOWN);
+        futures::future::try_join_all((0..5).map(|_| breaker.record_failure()))
+            .await
+            .unwrap();
+
+        let value = breaker.get().await.unwrap().unwrap();
+        assert_eq!(value.consecutive_failures, 5);
+    }
+
+    #[tokio::test]
+    async fn test_disabled_circuit_breaker() {
+        let cache = memory::new();
+        let org_id = OrganizationId::new(None, None);
+        let app_id = ApplicationId::new(None, None);
+        let endp_id = EndpointId::new(None, None);
+
+        let breaker = CircuitBreaker::new(&cache, &org_id, &app_id, &endp_id, 0, COOLDOWN);
+        for _ in 0..10 {
+            breaker.record_failure().await.unwrap();
+        }
+        assert_eq!(breaker.state().await.unwrap(), CircuitState::Closed);
+    }
+}
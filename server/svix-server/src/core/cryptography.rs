@@ -8,6 +8,16 @@ use chacha20poly1305::{
     Key, XChaCha20Poly1305, XNonce,
 };
 use ed25519_compact::*;
+use openssl::{
+    derive::Deriver,
+    error::ErrorStack,
+    hash::MessageDigest,
+    md::Md,
+    pkey::{Id, PKey, Private, Public},
+    pkey_ctx::PkeyCtx,
+    rsa::{Padding, Rsa},
+    sign::{Signer, Verifier},
+};
 use rand::Rng;
 
 use crate::error::Result;
@@ -37,6 +47,14 @@ impl AsymmetricKey {
     pub fn pubkey(&self) -> &[u8] {
         &self.0.pk[..]
     }
+
+    /// The PEM-encoded public key, in the SubjectPublicKeyInfo format
+    pub fn pubkey_pem(&self) -> Result<String> {
+        let pem = PKey::public_key_from_raw_bytes(self.pubkey(), Id::ED25519)
+            .and_then(|key| key.public_key_to_pem())
+            .map_err(|_| crate::error::Error::generic("Failed encoding key."))?;
+        Ok(String::from_utf8(pem).expect("PEM is always ASCII"))
+    }
 }
 
 impl Debug for AsymmetricKey {
@@ -55,6 +73,93 @@ impl PartialEq for AsymmetricKey {
     }
 }
 
+// RSA signature keys, which sign with RSASSA-PKCS1-v1_5 using SHA-256
+#[derive(Clone)]
+pub struct RsaKey(pub Rsa<Private>);
+
+impl RsaKey {
+    pub const GENERATED_BITS: u32 = 2048;
+    /// Smaller keys are not considered secure anymore
+    pub const MIN_BITS: u32 = 2048;
+
+    pub fn generate() -> Result<RsaKey> {
+        Ok(RsaKey(Rsa::generate(Self::GENERATED_BITS).map_err(
+            |_| crate::error::Error::generic("Failed generating key."),
+        )?))
+    }
+
+    /// Parses a PKCS#1 DER-encoded private key
+    pub fn from_slice(bytes: &[u8]) -> Result<Self> {
+        Ok(RsaKey(Rsa::private_key_from_der(bytes).map_err(|_| {
+            crate::error::Error::generic("Failed parsing key.")
+        })?))
+    }
+
+    pub fn from_base64(b64: &str) -> Result<Self> {
+        let bytes = base64::decode(b64)
+            .map_err(|_| crate::error::Error::generic("Failed parsing base64"))?;
+
+        Self::from_slice(bytes.as_slice())
+    }
+
+    /// The PKCS#1 DER-encoded private key
+    pub fn private_key_der(&self) -> Vec<u8> {
+        self.0
+            .private_key_to_der()
+            .expect("RSA private keys can always be encoded")
+    }
+
+    /// The PKCS#1 DER-encoded public key
+    pub fn pubkey(&self) -> Vec<u8> {
+        self.0
+            .public_key_to_der_pkcs1()
+            .expect("RSA public keys can always be encoded")
+    }
+
+    /// The PEM-encoded public key, in the SubjectPublicKeyInfo format most libraries expect
+    pub fn pubkey_pem(&self) -> String {
+        let pem = self
+            .0
+            .public_key_to_pem()
+            .expect("RSA public keys can always be encoded");
+        String::from_utf8(pem).expect("PEM is always ASCII")
+    }
+
+    pub fn sign(&self, bytes: &[u8]) -> Result<Vec<u8>> {
+        let sign = || {
+            let key = PKey::from_rsa(self.0.clone())?;
+            let mut signer = Signer::new(MessageDigest::sha256(), &key)?;
+            signer.set_rsa_padding(Padding::PKCS1)?;
+            signer.sign_oneshot_to_vec(bytes)
+        };
+        sign().map_err(|_| crate::error::Error::generic("Failed signing with key."))
+    }
+
+    pub fn verify(&self, bytes: &[u8], signature: &[u8]) -> Result<bool> {
+        let verify = || {
+            let key = PKey::from_rsa(self.0.clone())?;
+            let mut verifier = Verifier::new(MessageDigest::sha256(), &key)?;
+            verifier.set_rsa_padding(Padding::PKCS1)?;
+            verifier.verify_oneshot(signature, bytes)
+        };
+        verify().map_err(|_| crate::error::Error::generic("Failed verifying signature."))
+    }
+}
+
+impl Debug for RsaKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "<RsaKey sk=*** pk={}>", base64::encode(self.pubkey()))
+    }
+}
+
+impl PartialEq for RsaKey {
+    fn eq(&self, other: &Self) -> bool {
+        self.private_key_der() == other.private_key_der()
+    }
+}
+
+impl Eq for RsaKey {}
+
 #[derive(Clone, Debug)]
 pub struct Encryption(Option<Key>);
 
@@ -109,9 +214,125 @@ impl Default for Encryption {
     }
 }
 
+/// The X25519 public key of an endpoint whose webhook payloads are encrypted, so that only the
+/// holder of the private key can read them.
+///
+/// Each payload is encrypted for a new (ephemeral) key pair. Endpoints decrypt it by following the
+/// same steps, so they are part of the wire format:
+///
+/// 1. The X25519 shared secret of the ephemeral key pair and the endpoint's key is computed.
+/// 2. The 32 byte encryption key is derived from it with HKDF-SHA256 (RFC 5869), salted with the
+///    ephemeral public key followed by the endpoint's public key, with [`Self::KDF_INFO`] as the
+///    `info`.
+/// 3. The payload is encrypted with XChaCha20-Poly1305, as [`Encryption`] does: the result is a
+///    random 24 byte nonce followed by the ciphertext and its tag.
+///
+/// The ephemeral public key is sent along with the payload, so that the endpoint can compute the
+/// same shared secret with its private key.
+#[derive(Clone)]
+pub struct PayloadEncryptionKey(PKey<Public>);
+
+impl PayloadEncryptionKey {
+    pub const KEY_SIZE: usize = 32;
+
+    /// Versions the construction above, so that a later one derives different keys
+    pub const KDF_INFO: &'static [u8] = b"svix-payload-encryption-v1";
+
+    pub fn from_slice(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() != Self::KEY_SIZE {
+            return Err(crate::error::Error::generic("Invalid key length."));
+        }
+        Ok(PayloadEncryptionKey(
+            PKey::public_key_from_raw_bytes(bytes, Id::X25519)
+                .map_err(|_| crate::error::Error::generic("Failed parsing key."))?,
+        ))
+    }
+
+    pub fn from_base64(b64: &str) -> Result<Self> {
+        let bytes = base64::decode(b64)
+            .map_err(|_| crate::error::Error::generic("Failed parsing base64"))?;
+
+        Self::from_slice(bytes.as_slice())
+    }
+
+    pub fn pubkey(&self) -> Vec<u8> {
+        self.0
+            .raw_public_key()
+            .expect("X25519 public keys can always be encoded")
+    }
+
+    /// Encrypts `data`, returning the ephemeral public key it was encrypted for along with the
+    /// ciphertext
+    pub fn seal(&self, data: &[u8]) -> Result<(Vec<u8>, Vec<u8>)> {
+        let exchange = || -> Result<_, ErrorStack> {
+            let ephemeral = PKey::generate_x25519()?;
+            let mut deriver = Deriver::new(&ephemeral)?;
+            deriver.set_peer(&self.0)?;
+            Ok((ephemeral.raw_public_key()?, deriver.derive_to_vec()?))
+        };
+        let (ephemeral_pubkey, shared_secret) =
+            exchange().map_err(|_| crate::error::Error::generic("Key exchange failed"))?;
+
+        let key = sealing_key(&shared_secret, &ephemeral_pubkey, &self.pubkey())?;
+        Ok((ephemeral_pubkey, Encryption::new(key).encrypt(data)?))
+    }
+
+    /// Decrypts a payload sealed for the public key of `private_key`, as its endpoint would
+    pub fn open(
+        private_key: &PKey<Private>,
+        ephemeral_pubkey: &[u8],
+        ciphertext: &[u8],
+    ) -> Result<Vec<u8>> {
+        if ciphertext.len() < Encryption::NONCE_SIZE {
+            return Err(crate::error::Error::generic("Ciphertext too short"));
+        }
+
+        let exchange = || -> Result<_, ErrorStack> {
+            let ephemeral = PKey::public_key_from_raw_bytes(ephemeral_pubkey, Id::X25519)?;
+            let mut deriver = Deriver::new(private_key)?;
+            deriver.set_peer(&ephemeral)?;
+            Ok((private_key.raw_public_key()?, deriver.derive_to_vec()?))
+        };
+        let (pubkey, shared_secret) =
+            exchange().map_err(|_| crate::error::Error::generic("Key exchange failed"))?;
+
+        let key = sealing_key(&shared_secret, ephemeral_pubkey, &pubkey)?;
+        Encryption::new(key).decrypt(ciphertext)
+    }
+}
+
+impl Debug for PayloadEncryptionKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "<PayloadEncryptionKey pk={}>",
+            base64::encode(self.pubkey())
+        )
+    }
+}
+
+/// Derives the key a payload is encrypted with, as described on [`PayloadEncryptionKey`]
+fn sealing_key(shared_secret: &[u8], ephemeral_pubkey: &[u8], pubkey: &[u8]) -> Result<[u8; 32]> {
+    let derive = || -> Result<_, ErrorStack> {
+        let mut ctx = PkeyCtx::new_id(Id::HKDF)?;
+        ctx.derive_init()?;
+        ctx.set_hkdf_md(Md::sha256())?;
+        ctx.set_hkdf_key(shared_secret)?;
+        ctx.set_hkdf_salt(&[ephemeral_pubkey, pubkey].concat())?;
+        ctx.add_hkdf_info(PayloadEncryptionKey::KDF_INFO)?;
+
+        let mut key = [0; 32];
+        ctx.derive(Some(&mut key))?;
+        Ok(key)
+    };
+    derive().map_err(|_| crate::error::Error::generic("Key derivation failed"))
+}
+
 #[cfg(test)]
 mod tests {
-    use super::Encryption;
+    use openssl::pkey::PKey;
+
+    use super::{sealing_key, AsymmetricKey, Encryption, PayloadEncryptionKey, RsaKey};
 
     #[test]
     fn test_encryption() {
@@ -121,4 +342,68 @@ mod tests {
         let clear2 = encryption.decrypt(&ciphertext).unwrap();
         assert_eq!(&clear[..], &clear2[..]);
     }
+
+    #[test]
+    fn test_rsa_key() {
+        let key = RsaKey::generate().unwrap();
+        assert_eq!(key.0.size() * 8, RsaKey::GENERATED_BITS);
+
+        let parsed = RsaKey::from_base64(&base64::encode(key.private_key_der())).unwrap();
+        assert_eq!(parsed, key);
+
+        let signature = key.sign(b"hello world").unwrap();
+        assert!(parsed.verify(b"hello world", &signature).unwrap());
+        assert!(!parsed.verify(b"hello world!", &signature).unwrap());
+
+        assert!(key.pubkey_pem().starts_with("-----BEGIN PUBLIC KEY-----"));
+
+        assert!(RsaKey::from_base64("aGVsbG8gd29ybGQ=").is_err());
+    }
+
+    #[test]
+    fn test_asymmetric_key_pem() {
+        let key = AsymmetricKey::generate();
+        let pem = key.pubkey_pem().unwrap();
+        assert!(pem.starts_with("-----BEGIN PUBLIC KEY-----"));
+
+        let parsed = openssl::pkey::PKey::public_key_from_pem(pem.as_bytes()).unwrap();
+        assert_eq!(parsed.raw_public_key().unwrap(), key.pubkey());
+    }
+
+    #[test]
+    fn test_payload_encryption_key() {
+        let private_key = PKey::generate_x25519().unwrap();
+        let pubkey = private_key.raw_public_key().unwrap();
+        let key = PayloadEncryptionKey::from_base64(&base64::encode(&pubkey)).unwrap();
+        assert_eq!(key.pubkey(), pubkey);
+
+        let clear = b"hello world";
+        let (ephemeral_pubkey, ciphertext) = key.seal(clear).unwrap();
+        assert_ne!(ephemeral_pubkey, pubkey);
+        assert!(!ciphertext.windows(clear.len()).any(|w| w == clear));
+        let clear2 =
+            PayloadEncryptionKey::open(&private_key, &ephemeral_pubkey, &ciphertext).unwrap();
+        assert_eq!(&clear[..], &clear2[..]);
+
+        // Each payload is sealed with a new ephemeral key
+        let (ephemeral_pubkey2, _) = key.seal(clear).unwrap();
+        assert_ne!(ephemeral_pubkey, ephemeral_pubkey2);
+
+        // Only the endpoint's private key opens it
+        let other_key = PKey::generate_x25519().unwrap();
+        assert!(PayloadEncryptionKey::open(&other_key, &ephemeral_pubkey, &ciphertext).is_err());
+
+        assert!(PayloadEncryptionKey::from_slice(&pubkey[1..]).is_err());
+        assert!(PayloadEncryptionKey::from_base64("not base64").is_err());
+    }
+
+    #[test]
+    fn test_sealing_key() {
+        // Endpoints derive the key themselves, so it must not change for a given `KDF_INFO`
+        let key = sealing_key(&[1; 32], &[2; 32], &[3; 32]).unwrap();
+        assert_eq!(
+            base64::encode(key),
+            "GJ168eVN0UqLPN0cOh0AXzHA2e0hRO02KwZTKk0SKDQ="
+        );
+    }
 }
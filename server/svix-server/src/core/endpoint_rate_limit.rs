@@ -0,0 +1,393 @@
+// SPDX-FileCopyrightText: © 2022 Svix Authors
+// SPDX-License-Identifier: MIT
+
+//! Per-endpoint rate limiting of deliveries, for endpoints which can only take so many webhooks
+//! per minute.
+//!
+//! Deliveries are counted per one-minute window in the [`Cache`], so that the count is shared by
+//! all worker instances. The number of deliveries made over the last minute is then estimated
+//! from the counts of the current and the previous window, which smooths out bursts at the edges
+//! of windows.
+
+use std::time::Duration;
+
+use chrono::Utc;
+use sea_orm::prelude::DateTimeUtc;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    core::{
+        cache::{kv_def, Cache, CacheBehavior, CacheKey, CacheValue},
+        types::EndpointId,
+    },
+    error::{Error, Result},
+};
+
+/// The period endpoint rate limits apply to
+const WINDOW: Duration = Duration::from_secs(60);
+
+/// How many times counting a delivery is tried when the count keeps being changed concurrently
+const MAX_SWAP_ATTEMPTS: usize = 10;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RateLimitState {
+    /// The delivery can be made
+    Allowed,
+    /// The delivery would exceed the endpoint's rate limit, so it should be tried again later
+    Limited { retry_in: Duration },
+}
+
+/// The number of deliveries made in one window
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct EndpointRateLimitCacheValue(u32);
+
+kv_def!(EndpointRateLimitCacheKey, EndpointRateLimitCacheValue);
+
+impl EndpointRateLimitCacheKey {
+    /// `window_start` is the Unix timestamp (in seconds) at which the window starts
+    pub fn new(endp_id: &EndpointId, window_start: i64) -> EndpointRateLimitCacheKey {
+        EndpointRateLimitCacheKey(format!("SVIX_RATELIMIT_{endp_id}_{window_start}"))
+    }
+}
+
+/// The start of the window `now` falls into, as a Unix timestamp in seconds, and how long ago
+/// that was
+fn window_start(now: DateTimeUtc) -> (i64, Duration) {
+    let window_millis = WINDOW.as_millis() as i64;
+    let now_millis = now.timestamp_millis();
+    let elapsed_millis = now_millis.rem_euclid(window_millis);
+    (
+        (now_millis - elapsed_millis) / 1000,
+        Duration::from_millis(elapsed_millis as u64),
+    )
+}
+
+/// The estimated number of deliveries made over the last [`WINDOW`], assuming the previous
+/// window's deliveries were spread evenly across it
+fn sliding_count(previous: u32, current: u32, elapsed: Duration) -> f64 {
+    let previous_weight = 1.0 - elapsed.as_secs_f64() / WINDOW.as_secs_f64();
+    f64::from(previous) * previous_weight + f64::from(current)
+}
+
+/// Whether one more delivery is allowed given the counts of the previous and current windows
+fn limit_state(previous: u32, current: u32, elapsed: Duration, limit: u16) -> RateLimitState {
+    if sliding_count(previous, current, elapsed) >= f64::from(limit) {
+        // The estimate may still be over the limit at the start of the next window, in which
+        // case the delivery is simply put off again
+        RateLimitState::Limited {
+            retry_in: WINDOW - elapsed,
+        }
+    } else {
+        RateLimitState::Allowed
+    }
+}
+
+pub struct EndpointRateLimit<'a> {
+    cache: &'a Cache,
+    endp_id: &'a EndpointId,
+    /// The maximum number of deliveries per minute, or `None` for no limit
+    limit: Option<u16>,
+}
+
+impl<'a> EndpointRateLimit<'a> {
+    pub fn new(cache: &'a Cache, endp_id: &'a EndpointId, limit: Option<u16>) -> Self {
+        Self {
+            cache,
+            endp_id,
+            limit,
+        }
+    }
+
+    /// Checks whether a delivery to the endpoint would exceed its rate limit. The delivery isn't
+    /// counted until [`Self::acquire`] is called, so that it can still be put off for other reasons
+    /// without using up the endpoint's allowance.
+    pub async fn check(&self) -> Result<RateLimitState> {
+        self.check_at(Utc::now()).await
+    }
+
+    async fn check_at(&self, now: DateTimeUtc) -> Result<RateLimitState> {
+        let Some(limit) = self.limit else {
+            return Ok(RateLimitState::Allowed);
+        };
+
+        let (window_start, elapsed) = window_start(now);
+        let previous_key =
+            EndpointRateLimitCacheKey::new(self.endp_id, window_start - WINDOW.as_secs() as i64);
+        let current_key = EndpointRateLimitCacheKey::new(self.endp_id, window_start);
+
+        // Counts that can't be read are treated as no deliveries, rather than blocking them all
+        let counts = self
+            .cache
+            .get_pipeline::<EndpointRateLimitCacheValue>(&[&previous_key, &current_key])
+            .await
+            .map_err(Error::cache)?;
+        let [previous, current] = [0, 1].map(|i| match counts.get(i) {
+            Some(Ok(Some(EndpointRateLimitCacheValue(count)))) => *count,
+            _ => 0,
+        });
+
+        Ok(limit_state(previous, current, elapsed, limit))
+    }
+
+    /// Counts a delivery to the endpoint towards its rate limit, unless concurrent deliveries have
+    /// reached it since it was checked. The count is only swapped in if no other delivery changed
+    /// it in the meantime, so deliveries can't exceed the limit together.
+    pub async fn acquire(&self) -> Result<RateLimitState> {
+        self.acquire_at(Utc::now()).await
+    }
+
+    async fn acquire_at(&self, now: DateTimeUtc) -> Result<RateLimitState> {
+        let Some(limit) = self.limit else {
+            return Ok(RateLimitState::Allowed);
+        };
+
+        let (window_start, elapsed) = window_start(now);
+        let previous_key =
+            EndpointRateLimitCacheKey::new(self.endp_id, window_start - WINDOW.as_secs() as i64);
+        let current_key = EndpointRateLimitCacheKey::new(self.endp_id, window_start);
+
+        // The previous window is over, so its count doesn't change anymore
+        let previous = match self.cache.get(&previous_key).await {
+            Ok(Some(EndpointRateLimitCacheValue(count))) => count,
+            _ => 0,
+        };
+
+        for _ in 0..MAX_SWAP_ATTEMPTS {
+            let current_value = self
+                .cache
+                .get::<EndpointRateLimitCacheValue>(&current_key)
+                .await
+                .map_err(Error::cache)?;
+            let current = current_value
+                .as_ref()
+                .map_or(0, |EndpointRateLimitCacheValue(count)| *count);
+
+            let state = limit_state(previous, current, elapsed, limit);
+            if state != RateLimitState::Allowed {
+                return Ok(state);
+            }
+
+            // Kept for two windows, as it's used as the previous window's count during the next
+            // one
+            let swapped = self
+                .cache
+                .compare_and_swap(
+                    &current_key,
+                    current_value.as_ref(),
+                    &EndpointRateLimitCacheValue(current.saturating_add(1)),
+                    Some(WINDOW * 2),
+                )
+                .await
+                .map_err(Error::cache)?;
+            if swapped {
+                return Ok(RateLimitState::Allowed);
+            }
+        }
+
+        Err(Error::generic(format!(
+            "Failed to count a delivery to endpoint {}",
+            self.endp_id
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use chrono::{TimeZone, Utc};
+    use sea_orm::prelude::DateTimeUtc;
+
+    use super::{sliding_count, window_start, EndpointRateLimit, RateLimitState};
+    use crate::core::{
+        cache::memory,
+        types::{BaseId, EndpointId},
+    };
+
+    /// Checks the limit and counts the delivery if it's allowed, like the worker does
+    async fn check_and_acquire_at(
+        rate_limit: &EndpointRateLimit<'_>,
+        now: DateTimeUtc,
+    ) -> RateLimitState {
+        match rate_limit.check_at(now).await.unwrap() {
+            RateLimitState::Allowed => rate_limit.acquire_at(now).await.unwrap(),
+            limited => limited,
+        }
+    }
+
+    #[test]
+    fn test_window_start() {
+        let now = Utc.timestamp_millis_opt(1_700_000_012_500).unwrap();
+        assert_eq!(
+            window_start(now),
+            (1_699_999_980, Duration::from_millis(32_500))
+        );
+
+        let now = Utc.timestamp_opt(1_699_999_980, 0).unwrap();
+        assert_eq!(window_start(now), (1_699_999_980, Duration::ZERO));
+    }
+
+    #[test]
+    fn test_sliding_count() {
+        assert_eq!(sliding_count(10, 2, Duration::ZERO), 12.0);
+        assert_eq!(sliding_count(10, 2, Duration::from_secs(15)), 9.5);
+        assert_eq!(sliding_count(10, 2, Duration::from_secs(30)), 7.0);
+        assert_eq!(sliding_count(0, 2, Duration::from_secs(30)), 2.0);
+    }
+
+    #[tokio::test]
+    async fn test_under_and_at_limit() {
+        let cache = memory::new();
+        let endp_id = EndpointId::new(None, None);
+        let rate_limit = EndpointRateLimit::new(&cache, &endp_id, Some(3));
+
+        // 10 seconds into a window
+        let now = Utc.timestamp_opt(1_699_999_990, 0).unwrap();
+        for _ in 0..3 {
+            assert_eq!(
+                check_and_acquire_at(&rate_limit, now).await,
+                RateLimitState::Allowed
+            );
+        }
+
+        // Once at the limit, deliveries are put off until the start of the next window
+        assert_eq!(
+            check_and_acquire_at(&rate_limit, now).await,
+            RateLimitState::Limited {
+                retry_in: Duration::from_secs(50)
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_over_limit_slides_into_next_window() {
+        let cache = memory::new();
+        let endp_id = EndpointId::new(None, None);
+        let rate_limit = EndpointRateLimit::new(&cache, &endp_id, Some(4));
+
+        let window = Utc.timestamp_opt(1_699_999_980, 0).unwrap();
+        for _ in 0..4 {
+            assert_eq!(
+                check_and_acquire_at(&rate_limit, window).await,
+                RateLimitState::Allowed
+            );
+        }
+        for _ in 0..3 {
+            assert!(matches!(
+                check_and_acquire_at(&rate_limit, window).await,
+                RateLimitState::Limited { .. }
+            ));
+        }
+
+        // The previous window's deliveries still count at the start of the next one
+        let next_window = window + chrono::Duration::seconds(60);
+        assert_eq!(
+            check_and_acquire_at(&rate_limit, next_window).await,
+            RateLimitState::Limited {
+                retry_in: Duration::from_secs(60)
+            }
+        );
+
+        // But only half of them count halfway through it
+        let halfway = next_window + chrono::Duration::seconds(30);
+        for _ in 0..2 {
+            assert_eq!(
+                check_and_acquire_at(&rate_limit, halfway).await,
+                RateLimitState::Allowed
+            );
+        }
+        assert!(matches!(
+            check_and_acquire_at(&rate_limit, halfway).await,
+            RateLimitState::Limited { .. }
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_limits_are_per_endpoint() {
+        let cache = memory::new();
+        let now = Utc::now();
+
+        let endp_id = EndpointId::new(None, None);
+        let rate_limit = EndpointRateLimit::new(&cache, &endp_id, Some(1));
+        assert_eq!(
+            check_and_acquire_at(&rate_limit, now).await,
+            RateLimitState::Allowed
+        );
+        assert!(matches!(
+            check_and_acquire_at(&rate_limit, now).await,
+            RateLimitState::Limited { .. }
+        ));
+
+        let other_endp_id = EndpointId::new(None, None);
+        let other = EndpointRateLimit::new(&cache, &other_endp_id, Some(1));
+        assert_eq!(
+            check_and_acquire_at(&other, now).await,
+            RateLimitState::Allowed
+        );
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_deliveries_stay_within_limit() {
+        let cache = memory::new();
+        let endp_id = EndpointId::new(None, None);
+        let rate_limit = EndpointRateLimit::new(&cache, &endp_id, Some(5));
+
+        // Every delivery passes the check before any of them is counted
+        let now = Utc::now();
+        for _ in 0..10 {
+            assert_eq!(
+                rate_limit.check_at(now).await.unwrap(),
+                RateLimitState::Allowed
+            );
+        }
+
+        // But counting them lets no more through than the limit
+        let states = futures::future::try_join_all((0..10).map(|_| rate_limit.acquire_at(now)))
+            .await
+            .unwrap();
+        let allowed = states
+            .iter()
+            .filter(|state| **state == RateLimitState::Allowed)
+            .count();
+        assert_eq!(allowed, 5);
+    }
+
+    #[tokio::test]
+    async fn test_no_limit() {
+        let cache = memory::new();
+        let endp_id = EndpointId::new(None, None);
+
+        let rate_limit = EndpointRateLimit::new(&cache, &endp_id, None);
+        for _ in 0..100 {
+            assert_eq!(
+                check_and_acquire_at(&rate_limit, Utc::now()).await,
+                RateLimitState::Allowed
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_check_does_not_count() {
+        let cache = memory::new();
+        let endp_id = EndpointId::new(None, None);
+        let rate_limit = EndpointRateLimit::new(&cache, &endp_id, Some(1));
+
+        // Deliveries put off after being checked don't use up the limit
+        let now = Utc::now();
+        for _ in 0..3 {
+            assert_eq!(
+                rate_limit.check_at(now).await.unwrap(),
+                RateLimitState::Allowed
+            );
+        }
+
+        assert_eq!(
+            rate_limit.acquire_at(now).await.unwrap(),
+            RateLimitState::Allowed
+        );
+        assert!(matches!(
+            rate_limit.check_at(now).await.unwrap(),
+            RateLimitState::Limited { .. }
+        ));
+    }
+}
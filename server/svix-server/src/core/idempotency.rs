@@ -328,7 +328,7 @@ where
             body: bytes.clone().map(|b| b.to_vec()),
         };
 
-        if cache.set(key, &resp, expiry_default()).await.is_err() {
+        if cache.set(key, &resp, Some(expiry_default())).await.is_err() {
             return Ok(StatusCode::INTERNAL_SERVER_ERROR.into_response());
         }
 
@@ -1,4 +1,7 @@
-use std::{collections::HashSet, time::Duration};
+use std::{
+    collections::{HashMap, HashSet},
+    time::Duration,
+};
 
 use chrono::{DateTime, FixedOffset, Utc};
 use sea_orm::{DatabaseConnection, DatabaseTransaction, TransactionTrait};
@@ -7,14 +10,17 @@ use serde::{Deserialize, Serialize};
 use super::types::EventTypeName;
 use crate::{
     core::{
-        cache::{kv_def, Cache, CacheBehavior, CacheKey, CacheValue},
+        cache::{self, kv_def, Cache, CacheBehavior, CacheKey, CacheValue},
         types::{
-            ApplicationId, ApplicationUid, EndpointHeaders, EndpointId, EndpointSecretInternal,
-            EventChannelSet, EventTypeNameSet, ExpiringSigningKeys, MessageAttemptTriggerType,
-            OrganizationId,
+            metadata::Metadata, ApplicationId, ApplicationUid, EndpointGroupId, EndpointHeaders,
+            EndpointId, EndpointSecretInternal, EventChannelSet, EventTypeNameSet,
+            ExpiringSigningKeys, HttpMethod, MessageAttemptTriggerType, OrganizationId,
+            PayloadVersion, RetrySchedule,
         },
     },
-    db::models::{application, endpoint},
+    db::models::{
+        application, applicationwebhooksecret, endpoint, endpointgroup, endpointmetadata,
+    },
     error::{Error, Result},
 };
 
@@ -37,11 +43,29 @@ impl CreateMessageApp {
         db: &DatabaseTransaction,
         app: application::Model,
     ) -> Result<CreateMessageApp> {
+        let groups: HashMap<EndpointGroupId, endpointgroup::Model> =
+            endpointgroup::Entity::secure_find(app.id.clone())
+                .all(db)
+                .await?
+                .into_iter()
+                .map(|group| (group.id.clone(), group))
+                .collect();
+
+        let app_secret = applicationwebhooksecret::Entity::secure_find(app.id.clone())
+            .one(db)
+            .await?;
+
         let endpoints = endpoint::Entity::secure_find(app.id.clone())
+            .find_also_related(endpointmetadata::Entity)
             .all(db)
             .await?
             .into_iter()
-            .map(TryInto::try_into)
+            .map(|(endp, metadata)| {
+                let group = endp.group_id.as_ref().and_then(|id| groups.get(id));
+                let mut endp = CreateMessageEndpoint::from_model(endp, app_secret.as_ref())?;
+                endp.metadata = metadata.map(|m| m.data).unwrap_or_default();
+                endp.inherit_from(group)
+            })
             .collect::<Result<Vec<_>>>()?;
 
         Ok(CreateMessageApp {
@@ -72,12 +96,29 @@ impl CreateMessageApp {
         let cache_key = AppEndpointKey::new(&org_id, &app_id);
 
         // First check Redis
-        if let Ok(Some(cma)) = cache.get::<CreateMessageApp>(&cache_key).await {
-            if cma.deleted {
-                return Ok(None);
-            } else {
-                return Ok(Some(cma));
+        match cache.get::<CreateMessageApp>(&cache_key).await {
+            Ok(Some(cma)) => {
+                if cma.deleted {
+                    return Ok(None);
+                } else {
+                    return Ok(Some(cma));
+                }
             }
+            Ok(None) => {}
+            // The cached value can't be read, e.g. because it was cached by a version with a
+            // different format, so evict it rather than tripping over it until it expires
+            Err(
+                e @ (cache::Error::Deserialization(_)
+                | cache::Error::DeserializationBytes(_)
+                | cache::Error::DeserializationOther),
+            ) => {
+                tracing::warn!("Evicting unreadable cached application {app_id}: {e}");
+                if let Err(e) = cache.delete(&cache_key).await {
+                    tracing::warn!("Failed evicting cached application {app_id}: {e}");
+                }
+            }
+            // Any other cache error is ignored, falling back to PostgreSQL
+            Err(_) => {}
         }
 
         // Then check PostgreSQL
@@ -98,7 +139,7 @@ impl CreateMessageApp {
         let out = Self::fetch_from_pg_by_model(&db, app).await?;
 
         // Insert it into Redis
-        let _ = cache.set(&cache_key, &out, ttl).await;
+        let _ = cache.set(&cache_key, &out, Some(ttl)).await;
 
         if out.deleted {
             return Ok(None);
@@ -107,11 +148,19 @@ impl CreateMessageApp {
         Ok(Some(out))
     }
 
+    /// The endpoint with the given ID, unless it's deleted
+    pub fn endpoint(&self, endp_id: &EndpointId) -> Option<&CreateMessageEndpoint> {
+        self.endpoints
+            .iter()
+            .find(|endpoint| &endpoint.id == endp_id && !endpoint.deleted)
+    }
+
     pub fn filtered_endpoints(
         &self,
         trigger_type: MessageAttemptTriggerType,
         event_type: &EventTypeName,
         channels: Option<&EventChannelSet>,
+        endpoint_metadata_selector: Option<&Metadata>,
     ) -> Vec<CreateMessageEndpoint> {
         self
         .endpoints
@@ -138,6 +187,11 @@ impl CreateMessageApp {
                         .as_ref()
                         .map(|x| !x.0.is_disjoint(channels.map(|x| &x.0).unwrap_or(&HashSet::new())))
                         .unwrap_or(true)
+                    &&
+                        // If the message has a metadata selector, only endpoints whose metadata matches it
+                        endpoint_metadata_selector
+                        .map(|selector| endpoint.metadata.matches(selector))
+                        .unwrap_or(true)
             ))})
         .cloned()
         .collect()
@@ -156,6 +210,22 @@ pub struct CreateMessageEndpoint {
     // Same type as the `DateTimeWithTimeZone from SeaORM used in the endpoint model
     pub first_failure_at: Option<DateTime<FixedOffset>>,
     pub headers: Option<EndpointHeaders>,
+    /// Overrides the configured retry schedule when set. Inherited from the endpoint's group.
+    pub retry_schedule: Option<Vec<Duration>>,
+    /// When set, the bodies of successful responses are sent as new messages of this event type
+    pub response_webhook_event_type: Option<EventTypeName>,
+    /// Messages with an older payload version aren't sent to the endpoint
+    pub min_payload_version: Option<PayloadVersion>,
+    pub http_method: HttpMethod,
+    pub content_type: Option<String>,
+    /// The X25519 public key payloads are encrypted for, only set if payload encryption is enabled
+    pub payload_encryption_key: Option<Vec<u8>>,
+    /// The code payloads are transformed with before being sent, only set if the transformation is
+    /// enabled
+    pub dispatch_transformation: Option<String>,
+    /// Signed in place of the message ID when set
+    pub custom_signature_prefix: Option<String>,
+    pub metadata: Metadata,
     pub disabled: bool,
     pub deleted: bool,
     // outside of this module, valid_signing_keys should be used instead
@@ -177,17 +247,73 @@ impl CreateMessageEndpoint {
             None => vec![&self.key],
         }
     }
+
+    /// Whether a message with the given payload version is sent to the endpoint. Messages without
+    /// a version predate versioning, so they're always sent.
+    pub fn accepts_payload_version(&self, version: Option<&PayloadVersion>) -> bool {
+        match (&self.min_payload_version, version) {
+            (Some(min), Some(version)) => version >= min,
+            _ => true,
+        }
+    }
+
+    /// Fills in the settings of the endpoint's group. Settings made on the endpoint itself take
+    /// precedence over the group's.
+    fn inherit_from(mut self, group: Option<&endpointgroup::Model>) -> Result<Self> {
+        let Some(group) = group else {
+            return Ok(self);
+        };
+
+        if let Some(EndpointHeaders(group_headers)) = &group.default_headers {
+            let mut headers = group_headers.clone();
+            if let Some(EndpointHeaders(own_headers)) = self.headers.take() {
+                // Header names are case-insensitive
+                headers.retain(|k, _| !own_headers.keys().any(|own| own.eq_ignore_ascii_case(k)));
+                headers.extend(own_headers);
+            }
+            self.headers = Some(EndpointHeaders(headers));
+        }
+
+        if self.rate_limit.is_none() {
+            self.rate_limit = group
+                .rate_limit
+                .map(|v| v.try_into())
+                .transpose()
+                .map_err(|_| Error::validation("Endpoint group rate limit out of bounds"))?;
+        }
+
+        self.retry_schedule = group
+            .retry_schedule_override
+            .as_ref()
+            .map(RetrySchedule::durations);
+
+        Ok(self)
+    }
 }
 
-impl TryFrom<endpoint::Model> for CreateMessageEndpoint {
-    type Error = Error;
+impl CreateMessageEndpoint {
+    /// Endpoints without a secret of their own are signed with the application's secret, along
+    /// with its old keys that are still valid.
+    fn from_model(
+        m: endpoint::Model,
+        app_secret: Option<&applicationwebhooksecret::Model>,
+    ) -> Result<CreateMessageEndpoint> {
+        let (key, old_signing_keys) = match (m.key, app_secret) {
+            (Some(key), _) => (key, m.old_keys),
+            (None, Some(app_secret)) => (app_secret.key.clone(), app_secret.old_keys.clone()),
+            (None, None) => {
+                return Err(Error::generic(format!(
+                    "Endpoint {} has no signing secret",
+                    m.id
+                )))
+            }
+        };
 
-    fn try_from(m: endpoint::Model) -> Result<CreateMessageEndpoint> {
         Ok(CreateMessageEndpoint {
             id: m.id,
             url: m.url,
-            key: m.key,
-            old_signing_keys: m.old_keys,
+            key,
+            old_signing_keys,
             event_types_ids: m.event_types_ids,
             channels: m.channels,
             rate_limit: m
@@ -197,6 +323,19 @@ impl TryFrom<endpoint::Model> for CreateMessageEndpoint {
                 .map_err(|_| Error::validation("Endpoint rate limit out of bounds"))?,
             first_failure_at: m.first_failure_at,
             headers: m.headers,
+            retry_schedule: None,
+            response_webhook_event_type: m.response_webhook_event_type,
+            min_payload_version: m.min_payload_version,
+            http_method: m.http_method,
+            content_type: m.content_type,
+            payload_encryption_key: m
+                .payload_encryption_key
+                .filter(|_| m.payload_encryption_enabled),
+            dispatch_transformation: m
+                .dispatch_transformation
+                .filter(|_| m.dispatch_transformation_enabled),
+            custom_signature_prefix: m.custom_signature_prefix,
+            metadata: Metadata::default(),
             disabled: m.disabled,
             deleted: m.deleted,
         })
@@ -208,30 +347,68 @@ impl AppEndpointKey {
     // FIXME: Rewrite doc comment when AppEndpointValue members are known
     /// Returns a key for fetching all cached endpoints for a given organization and application.
     pub fn new(org: &OrganizationId, app: &ApplicationId) -> AppEndpointKey {
-        AppEndpointKey(format!("SVIX_CACHE_APP_v3_{org}_{app}"))
+        AppEndpointKey(format!("SVIX_CACHE_APP_v12_{org}_{app}"))
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use std::{collections::HashMap, time::Duration};
+
     use chrono::Utc;
 
-    use super::CreateMessageEndpoint;
-    use crate::core::{
-        cryptography::Encryption,
-        types::{
-            EndpointId, EndpointSecret, EndpointSecretInternal, ExpiringSigningKey,
-            ExpiringSigningKeys,
+    use super::{CreateMessageApp, CreateMessageEndpoint};
+    use crate::{
+        core::{
+            cryptography::Encryption,
+            types::{
+                metadata::Metadata, ApplicationId, BaseId, EndpointGroupId, EndpointHeaders,
+                EndpointId, EndpointSecret, EndpointSecretInternal, EventTypeName,
+                ExpiringSigningKey, ExpiringSigningKeys, MessageAttemptTriggerType, OrganizationId,
+                PayloadVersion, RetrySchedule,
+            },
         },
+        db::models::{applicationwebhooksecret, endpoint, endpointgroup},
     };
 
-    #[test]
-    fn test_valid_signing_keys() {
-        let key = EndpointSecretInternal::from_endpoint_secret(
-            EndpointSecret::Symmetric(base64::decode("MfKQ9r8GKYqrTwjUPD8ILPZIo2LaLaSw").unwrap()),
+    fn test_endpoint(key: EndpointSecretInternal) -> CreateMessageEndpoint {
+        CreateMessageEndpoint {
+            id: EndpointId::from("Test".to_string()),
+            url: "".to_string(),
+            key,
+            old_signing_keys: None,
+            event_types_ids: None,
+            channels: None,
+            rate_limit: None,
+            first_failure_at: None,
+            headers: None,
+            retry_schedule: None,
+            response_webhook_event_type: None,
+            min_payload_version: None,
+            http_method: Default::default(),
+            content_type: None,
+            payload_encryption_key: None,
+            dispatch_transformation: None,
+            custom_signature_prefix: None,
+            metadata: Metadata::default(),
+            disabled: false,
+            deleted: false,
+        }
+    }
+
+    fn test_key() -> EndpointSecretInternal {
+        EndpointSecretInternal::from_endpoint_secret(
+            EndpointSecret::Symmetric(
+                base64::decode("MfKQ9r8GKYqrTwjUPD8ILPZIo2LaLaSwMfKQ9r8GKYqr").unwrap(),
+            ),
             &Encryption::new_noop(),
         )
-        .unwrap();
+        .unwrap()
+    }
+
+    #[test]
+    fn test_valid_signing_keys() {
+        let key = test_key();
 
         let unexpired_old_key = ExpiringSigningKey {
             key: key.clone(),
@@ -249,21 +426,223 @@ mod tests {
         ]));
 
         let cme = CreateMessageEndpoint {
-            id: EndpointId::from("Test".to_string()),
-            url: "".to_string(),
-            key,
             old_signing_keys,
+            ..test_endpoint(key)
+        };
+
+        let keys = cme.valid_signing_keys();
+
+        assert_eq!(keys.len(), 2);
+    }
+
+    #[test]
+    fn test_inherit_from_group() {
+        let now = Utc::now();
+        let group = endpointgroup::Model {
+            id: EndpointGroupId::new(None, None),
+            created_at: now.into(),
+            updated_at: now.into(),
+            app_id: ApplicationId::new(None, None),
+            name: "group".to_owned(),
+            default_headers: Some(EndpointHeaders(
+                [
+                    ("x-group".to_owned(), "group".to_owned()),
+                    ("x-shared".to_owned(), "group".to_owned()),
+                ]
+                .into(),
+            )),
+            retry_schedule_override: Some(RetrySchedule(vec![1, 2])),
+            rate_limit: Some(10),
+        };
+
+        // Without any settings of its own, the endpoint takes all of the group's
+        let cme = test_endpoint(test_key())
+            .inherit_from(Some(&group))
+            .unwrap();
+        assert_eq!(cme.headers, group.default_headers);
+        assert_eq!(cme.rate_limit, Some(10));
+        assert_eq!(
+            cme.retry_schedule,
+            Some(vec![Duration::from_secs(1), Duration::from_secs(2)])
+        );
+
+        // The endpoint's own settings win on conflict
+        let cme = CreateMessageEndpoint {
+            headers: Some(EndpointHeaders(
+                [
+                    ("X-Shared".to_owned(), "endpoint".to_owned()),
+                    ("x-endpoint".to_owned(), "endpoint".to_owned()),
+                ]
+                .into(),
+            )),
+            rate_limit: Some(5),
+            ..test_endpoint(test_key())
+        }
+        .inherit_from(Some(&group))
+        .unwrap();
+        assert_eq!(
+            cme.headers,
+            Some(EndpointHeaders(
+                [
+                    ("x-group".to_owned(), "group".to_owned()),
+                    ("X-Shared".to_owned(), "endpoint".to_owned()),
+                    ("x-endpoint".to_owned(), "endpoint".to_owned()),
+                ]
+                .into(),
+            ))
+        );
+        assert_eq!(cme.rate_limit, Some(5));
+
+        // Endpoints without a group are left as-is
+        let cme = test_endpoint(test_key()).inherit_from(None).unwrap();
+        assert_eq!(cme.headers, None);
+        assert_eq!(cme.rate_limit, None);
+        assert_eq!(cme.retry_schedule, None);
+    }
+
+    #[test]
+    fn test_fallback_to_app_secret() {
+        let now = Utc::now();
+        let app_id = ApplicationId::new(None, None);
+        let endp_key = test_key();
+        let app_key = EndpointSecretInternal::generate_symmetric(&Encryption::new_noop()).unwrap();
+
+        let endp = endpoint::Model {
+            id: EndpointId::new(None, None),
+            created_at: now.into(),
+            updated_at: now.into(),
+            app_id: app_id.clone(),
+            key: Some(endp_key.clone()),
+            url: "".to_owned(),
+            description: "".to_owned(),
             event_types_ids: None,
-            channels: None,
+            version: 1,
             rate_limit: None,
+            deleted: false,
+            disabled: false,
             first_failure_at: None,
+            uid: None,
+            old_keys: None,
+            channels: None,
             headers: None,
-            disabled: false,
-            deleted: false,
+            group_id: None,
+            response_webhook_event_type: None,
+            min_payload_version: None,
+            http_method: Default::default(),
+            content_type: None,
+            payload_encryption_enabled: false,
+            payload_encryption_key: None,
+            dispatch_transformation_enabled: false,
+            dispatch_transformation: None,
+            custom_data: None,
+            custom_signature_prefix: None,
+        };
+        let app_secret = applicationwebhooksecret::Model {
+            id: app_id,
+            created_at: now.into(),
+            updated_at: now.into(),
+            key: app_key.clone(),
+            old_keys: Some(ExpiringSigningKeys(vec![ExpiringSigningKey {
+                key: endp_key.clone(),
+                expiration: now + chrono::Duration::hours(1),
+            }])),
         };
 
-        let keys = cme.valid_signing_keys();
+        // The endpoint's own key takes precedence
+        let cme = CreateMessageEndpoint::from_model(endp.clone(), Some(&app_secret)).unwrap();
+        assert_eq!(cme.valid_signing_keys(), vec![&endp_key]);
 
-        assert_eq!(keys.len(), 2);
+        // Otherwise the application's keys are used
+        let endp = endpoint::Model { key: None, ..endp };
+        let cme = CreateMessageEndpoint::from_model(endp.clone(), Some(&app_secret)).unwrap();
+        assert_eq!(cme.valid_signing_keys(), vec![&app_key, &endp_key]);
+
+        assert!(CreateMessageEndpoint::from_model(endp, None).is_err());
+    }
+
+    #[test]
+    fn test_accepts_payload_version() {
+        let version = |v: &str| PayloadVersion(v.parse().unwrap());
+
+        let mut endp = test_endpoint(test_key());
+        assert!(endp.accepts_payload_version(None));
+        assert!(endp.accepts_payload_version(Some(&version("0.1.0"))));
+
+        endp.min_payload_version = Some(version("1.2.0"));
+        assert!(endp.accepts_payload_version(Some(&version("1.2.0"))));
+        assert!(endp.accepts_payload_version(Some(&version("1.10.0"))));
+        assert!(endp.accepts_payload_version(Some(&version("2.0.0"))));
+        assert!(!endp.accepts_payload_version(Some(&version("1.1.9"))));
+        // Pre-releases come before the release itself
+        assert!(!endp.accepts_payload_version(Some(&version("1.2.0-beta.1"))));
+        // Unversioned messages are always sent
+        assert!(endp.accepts_payload_version(None));
+    }
+
+    #[test]
+    fn test_filtered_endpoints_metadata_selector() {
+        let metadata = |pairs: &[(&str, &str)]| {
+            Metadata::from(
+                pairs
+                    .iter()
+                    .map(|(k, v)| (k.to_string(), v.to_string()))
+                    .collect::<HashMap<_, _>>(),
+            )
+        };
+        let endp_with = |id: &str, pairs: &[(&str, &str)]| CreateMessageEndpoint {
+            id: EndpointId::from(id.to_owned()),
+            metadata: metadata(pairs),
+            ..test_endpoint(test_key())
+        };
+        let app = CreateMessageApp {
+            id: ApplicationId::new(None, None),
+            uid: None,
+            org_id: OrganizationId::new(None, None),
+            rate_limit: None,
+            endpoints: vec![
+                endp_with("prod-payments", &[("env", "prod"), ("team", "payments")]),
+                endp_with("prod-search", &[("env", "prod"), ("team", "search")]),
+                endp_with("untagged", &[]),
+            ],
+            deleted: false,
+        };
+        let event_type = EventTypeName("user.signup".to_owned());
+        let filtered = |trigger_type, selector: &[(&str, &str)]| {
+            let selector = metadata(selector);
+            app.filtered_endpoints(trigger_type, &event_type, None, Some(&selector))
+                .into_iter()
+                .map(|endp| endp.id.to_string())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            filtered(MessageAttemptTriggerType::Scheduled, &[("env", "prod")]),
+            ["prod-payments", "prod-search"]
+        );
+        assert_eq!(
+            filtered(
+                MessageAttemptTriggerType::Scheduled,
+                &[("env", "prod"), ("team", "payments")]
+            ),
+            ["prod-payments"]
+        );
+        assert!(filtered(MessageAttemptTriggerType::Scheduled, &[("env", "dev")]).is_empty());
+        // An empty selector, or none at all, matches every endpoint
+        assert_eq!(filtered(MessageAttemptTriggerType::Scheduled, &[]).len(), 3);
+        assert_eq!(
+            app.filtered_endpoints(
+                MessageAttemptTriggerType::Scheduled,
+                &event_type,
+                None,
+                None
+            )
+            .len(),
+            3
+        );
+        // Manual attempts go through regardless
+        assert_eq!(
+            filtered(MessageAttemptTriggerType::Manual, &[("env", "dev")]).len(),
+            3
+        );
     }
 }
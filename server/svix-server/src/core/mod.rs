@@ -1,11 +1,15 @@
 // SPDX-FileCopyrightText: © 2022 Svix Authors
 // SPDX-License-Identifier: MIT
 
+pub mod app_rate_limit;
 pub mod cache;
+pub mod circuit_breaker;
 pub mod cryptography;
+pub mod endpoint_rate_limit;
 pub mod idempotency;
 pub mod message_app;
 pub mod operational_webhooks;
+pub mod org_quota;
 pub mod otel_spans;
 pub mod permissions;
 pub mod retry;
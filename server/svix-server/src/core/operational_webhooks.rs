@@ -3,7 +3,14 @@
 
 //! Module defining an interface for sending webhook events about the service.
 
-use std::sync::Arc;
+use std::{
+    collections::{hash_map::Entry, HashMap},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
 
 use chrono::{DateTime, Utc};
 use http::StatusCode;
@@ -35,6 +42,19 @@ pub struct EndpointDisabledEventData {
     pub fail_since: DateTime<Utc>,
 }
 
+/// Sent when an endpoint has failed `op_webhooks_failure_count_threshold` times in a row, before it
+/// gets disabled.
+#[derive(Debug, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct EndpointFailureCountEventData {
+    pub app_id: ApplicationId,
+    pub app_uid: Option<ApplicationUid>,
+    pub endpoint_id: EndpointId,
+    pub endpoint_uid: Option<EndpointUid>,
+    pub failure_count: u32,
+    pub fail_since: DateTime<Utc>,
+}
+
 /// Sent when an endpoint is created, updated, or deleted
 #[derive(Debug, Serialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
@@ -88,6 +108,31 @@ pub struct MessageAttemptEvent {
     pub last_attempt: MessageAttempetLast,
 }
 
+/// Sent when a message wasn't delivered to an endpoint because its deadline had passed.
+#[derive(Debug, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct MessageExpiredEventData {
+    pub app_id: ApplicationId,
+    pub app_uid: Option<ApplicationUid>,
+    pub msg_id: MessageId,
+    pub msg_event_id: Option<MessageUid>,
+    pub endpoint_id: EndpointId,
+    pub deadline: DateTime<Utc>,
+}
+
+/// Sent when an endpoint's circuit breaker opens, or closes again.
+#[derive(Debug, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct EndpointCircuitEvent {
+    pub app_id: ApplicationId,
+    pub app_uid: Option<ApplicationUid>,
+    pub endpoint_id: EndpointId,
+    /// When deliveries to the endpoint resume, or `null` once the circuit has closed
+    pub open_until: Option<DateTime<Utc>>,
+    /// The number of deliveries which failed in a row
+    pub consecutive_failures: u32,
+}
+
 #[derive(Debug, Serialize)]
 #[serde(tag = "type", content = "data")]
 pub enum OperationalWebhook {
@@ -99,24 +144,80 @@ pub enum OperationalWebhook {
     EndpointUpdated(EndpointEvent),
     #[serde(rename = "endpoint.deleted")]
     EndpointDeleted(EndpointEvent),
+    #[serde(rename = "endpoint.failure_count_threshold")]
+    FailureCountThreshold(EndpointFailureCountEventData),
+    #[serde(rename = "endpoint.circuit_open")]
+    EndpointCircuitOpen(EndpointCircuitEvent),
+    #[serde(rename = "endpoint.circuit_closed")]
+    EndpointCircuitClosed(EndpointCircuitEvent),
     #[serde(rename = "message.attempt.exhausted")]
     MessageAttemptExhausted(MessageAttemptEvent),
     #[serde(rename = "message.attempt.failing")]
     MessageAttemptFailing(MessageAttemptEvent),
+    #[serde(rename = "message.expired")]
+    MessageExpired(MessageExpiredEventData),
 }
 
 pub type OperationalWebhookSender = Arc<OperationalWebhookSenderInner>;
 
+/// Events of the same type for the same organization are sent right away once this many have been
+/// collected, rather than at the end of the batch interval
+const MAX_BATCH_SIZE: usize = 100;
+
+/// How operational webhooks are sent
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OperationalWebhookMode {
+    /// Every event is sent as soon as it happens
+    Single,
+    /// Events of the same type for the same organization are collected for `interval`, then sent
+    /// together in a single webhook whose `data` is an array of the events' data. It's an array
+    /// even when a single event was collected, so receivers only have to handle one shape.
+    Batched { interval: Duration },
+}
+
+impl OperationalWebhookMode {
+    /// Batched every `batch_interval_ms`, or not batched at all if it's zero
+    pub fn from_batch_interval_ms(batch_interval_ms: u64) -> Self {
+        if batch_interval_ms == 0 {
+            Self::Single
+        } else {
+            Self::Batched {
+                interval: Duration::from_millis(batch_interval_ms),
+            }
+        }
+    }
+}
+
+struct Batch {
+    /// Tells the batch apart from later ones for the same key, so that its timer doesn't send
+    /// those early
+    id: u64,
+    data: Vec<serde_json::Value>,
+}
+
+/// The batches being collected, by recipient organization and event type
+type Batches = HashMap<(String, String), Batch>;
+
 pub struct OperationalWebhookSenderInner {
     signing_config: Arc<JwtSigningConfig>,
     url: Option<String>,
+    mode: OperationalWebhookMode,
+    batches: Arc<Mutex<Batches>>,
+    next_batch_id: AtomicU64,
 }
 
 impl OperationalWebhookSenderInner {
-    pub fn new(keys: Arc<JwtSigningConfig>, url: Option<String>) -> Arc<Self> {
+    pub fn new(
+        keys: Arc<JwtSigningConfig>,
+        url: Option<String>,
+        mode: OperationalWebhookMode,
+    ) -> Arc<Self> {
         Arc::new(Self {
             signing_config: keys,
             url,
+            mode,
+            batches: Default::default(),
+            next_batch_id: AtomicU64::new(0),
         })
     }
 
@@ -127,16 +228,6 @@ impl OperationalWebhookSenderInner {
     ) -> Result<()> {
         let Some(url) = &self.url else { return Ok(()) };
 
-        let op_webhook_token =
-            generate_management_token(&self.signing_config).map_err(Error::generic)?;
-        let svix_api = Svix::new(
-            op_webhook_token,
-            Some(SvixOptions {
-                server_url: Some(url.to_string()),
-                ..Default::default()
-            }),
-        );
-
         let payload = serde_json::to_value(payload)
             .map_err(|_| HttpError::internal_server_error(None, None))?;
 
@@ -150,45 +241,153 @@ impl OperationalWebhookSenderInner {
 
         let recipient_org_id = recipient_org_id.to_string();
 
-        tokio::spawn(async move {
-            // This sends a webhook under the Svix management organization. This organization contains
-            // applications which are each a regular organization. The recipient's OrganizationId is the
-            // app UID to use.
-            let resp = svix_api
-                .message()
-                .create(
-                    recipient_org_id.clone(),
-                    MessageIn {
-                        event_type,
-                        payload,
-                        ..MessageIn::default()
-                    },
-                    None,
-                )
-                .await;
-
-            match resp {
-                Ok(_) => {}
-                // Ignore 404s because not every org will have an associated application
-                Err(svix::error::Error::Http(svix::error::HttpErrorContent {
-                    status: StatusCode::NOT_FOUND,
-                    ..
-                })) => {
-                    tracing::warn!(
-                        "Operational webhooks are enabled but no listener set for {}",
-                        recipient_org_id,
-                    );
+        let interval = match self.mode {
+            OperationalWebhookMode::Single => {
+                let svix_api = svix_api(&self.signing_config, url)?;
+                tokio::spawn(send(svix_api, recipient_org_id, event_type, payload));
+                return Ok(());
+            }
+            OperationalWebhookMode::Batched { interval } => interval,
+        };
+
+        let data = match payload {
+            serde_json::Value::Object(mut payload) => payload.remove("data"),
+            _ => None,
+        }
+        .ok_or_else(|| HttpError::internal_server_error(None, None))?;
+        let key = (recipient_org_id, event_type);
+
+        let mut batches = self.batches.lock().unwrap();
+        match batches.entry(key.clone()) {
+            Entry::Occupied(mut entry) => {
+                entry.get_mut().data.push(data);
+                if entry.get().data.len() >= MAX_BATCH_SIZE {
+                    let batch = entry.remove();
+                    let svix_api = svix_api(&self.signing_config, url)?;
+                    let (recipient_org_id, event_type) = key;
+                    let payload = batch_payload(&event_type, batch.data);
+                    tokio::spawn(send(svix_api, recipient_org_id, event_type, payload));
+                }
+            }
+            Entry::Vacant(entry) => {
+                let id = self.next_batch_id.fetch_add(1, Ordering::Relaxed);
+                entry.insert(Batch {
+                    id,
+                    data: vec![data],
+                });
+
+                let batches = self.batches.clone();
+                let signing_config = self.signing_config.clone();
+                let url = url.clone();
+                tokio::spawn(async move {
+                    tokio::time::sleep(interval).await;
+
+                    let data = {
+                        let mut batches = batches.lock().unwrap();
+                        match batches.entry(key.clone()) {
+                            Entry::Occupied(entry) if entry.get().id == id => entry.remove().data,
+                            // Already sent because it filled up
+                            _ => return,
+                        }
+                    };
+
+                    match svix_api(&signing_config, &url) {
+                        Ok(svix_api) => {
+                            let (recipient_org_id, event_type) = key;
+                            let payload = batch_payload(&event_type, data);
+                            send(svix_api, recipient_org_id, event_type, payload).await;
+                        }
+                        Err(e) => {
+                            tracing::error!("Failed sending operational webhook batch: {e}");
+                        }
+                    }
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Sends the batches still being collected right away, so that they aren't lost when the
+    /// server shuts down
+    pub async fn flush(&self) {
+        let Some(url) = &self.url else { return };
+
+        let batches = std::mem::take(&mut *self.batches.lock().unwrap());
+        let sends = batches.into_iter().filter_map(|(key, batch)| {
+            match svix_api(&self.signing_config, url) {
+                Ok(svix_api) => {
+                    let (recipient_org_id, event_type) = key;
+                    let payload = batch_payload(&event_type, batch.data);
+                    Some(send(svix_api, recipient_org_id, event_type, payload))
                 }
                 Err(e) => {
-                    tracing::error!(
-                        "Failed sending operational webhook for {} {}",
-                        recipient_org_id,
-                        e.to_string()
-                    );
+                    tracing::error!("Failed sending operational webhook batch: {e}");
+                    None
                 }
             }
         });
+        futures::future::join_all(sends).await;
+    }
+}
 
-        Ok(())
+/// A client for the API operational webhooks are sent through
+fn svix_api(signing_config: &JwtSigningConfig, url: &str) -> Result<Svix> {
+    let op_webhook_token = generate_management_token(signing_config).map_err(Error::generic)?;
+    Ok(Svix::new(
+        op_webhook_token,
+        Some(SvixOptions {
+            server_url: Some(url.to_string()),
+            ..Default::default()
+        }),
+    ))
+}
+
+/// The payload of a batch of events, whose `data` is always an array
+fn batch_payload(event_type: &str, data: Vec<serde_json::Value>) -> serde_json::Value {
+    serde_json::json!({ "type": event_type, "data": data })
+}
+
+async fn send(
+    svix_api: Svix,
+    recipient_org_id: String,
+    event_type: String,
+    payload: serde_json::Value,
+) {
+    // This sends a webhook under the Svix management organization. This organization contains
+    // applications which are each a regular organization. The recipient's OrganizationId is the
+    // app UID to use.
+    let resp = svix_api
+        .message()
+        .create(
+            recipient_org_id.clone(),
+            MessageIn {
+                event_type,
+                payload,
+                ..MessageIn::default()
+            },
+            None,
+        )
+        .await;
+
+    match resp {
+        Ok(_) => {}
+        // Ignore 404s because not every org will have an associated application
+        Err(svix::error::Error::Http(svix::error::HttpErrorContent {
+            status: StatusCode::NOT_FOUND,
+            ..
+        })) => {
+            tracing::warn!(
+                "Operational webhooks are enabled but no listener set for {}",
+                recipient_org_id,
+            );
+        }
+        Err(e) => {
+            tracing::error!(
+                "Failed sending operational webhook for {} {}",
+                recipient_org_id,
+                e.to_string()
+            );
+        }
     }
 }
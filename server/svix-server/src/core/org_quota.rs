@@ -0,0 +1,313 @@
+// SPDX-FileCopyrightText: © 2022 Svix Authors
+// SPDX-License-Identifier: MIT
+
+//! Monthly quotas on the number of messages an organization can create.
+//!
+//! Quotas are stored in the `organizationquota` table, along with how many messages have been
+//! created since the last reset. That count is mirrored in the [`Cache`], where it's incremented
+//! with compare-and-swap so that concurrent requests, even across instances, can't take the
+//! organization over its quota. Organizations without a quota have no limit.
+
+use std::{sync::atomic::Ordering, time::Duration};
+
+use chrono::{Months, Utc};
+use sea_orm::{
+    prelude::{DateTimeUtc, DateTimeWithTimeZone},
+    sea_query::Expr,
+    ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    core::{
+        cache::{kv_def, Cache, CacheBehavior, CacheKey, CacheValue},
+        types::OrganizationId,
+    },
+    db::models::organizationquota,
+    error::{Error, Result},
+};
+
+/// How many times to retry when another request changes the count in between reading and
+/// updating it
+const MAX_SWAP_ATTEMPTS: usize = 10;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum QuotaState {
+    /// The message can be created, and has been counted
+    Allowed,
+    /// The organization has used up its quota until it's reset
+    Exceeded { retry_in: Duration },
+}
+
+/// The number of messages created since the quota was last reset
+#[derive(Debug, Deserialize, Serialize)]
+pub struct OrgQuotaCacheValue(i64);
+
+kv_def!(OrgQuotaCacheKey, OrgQuotaCacheValue);
+
+impl OrgQuotaCacheKey {
+    /// `reset_at` is the Unix timestamp (in seconds) of the quota's next reset, so that each
+    /// month is counted under its own key
+    pub fn new(org_id: &OrganizationId, reset_at: i64) -> OrgQuotaCacheKey {
+        OrgQuotaCacheKey(format!("SVIX_ORG_QUOTA_{org_id}_{reset_at}"))
+    }
+}
+
+/// Counts a message created by the organization, unless it would exceed its monthly quota.
+///
+/// Without a cache, the count is read from the database instead, so concurrent requests may slip
+/// past the quota.
+pub async fn acquire(
+    db: &DatabaseConnection,
+    cache: &Cache,
+    org_id: &OrganizationId,
+) -> Result<QuotaState> {
+    acquire_at(db, cache, org_id, Utc::now()).await
+}
+
+async fn acquire_at(
+    db: &DatabaseConnection,
+    cache: &Cache,
+    org_id: &OrganizationId,
+    now: DateTimeUtc,
+) -> Result<QuotaState> {
+    let mut quota = organizationquota::Entity::secure_find(org_id.clone())
+        .one(db)
+        .await?;
+
+    // The background task may not have got around to resetting it yet
+    if let Some(expired) = quota.as_ref().filter(|q| q.reset_at <= now) {
+        reset_quota(db, expired, now).await?;
+        quota = organizationquota::Entity::secure_find(org_id.clone())
+            .one(db)
+            .await?;
+    }
+
+    let Some(quota) = quota else {
+        return Ok(QuotaState::Allowed);
+    };
+
+    let reset_at: DateTimeUtc = quota.reset_at.into();
+    let retry_in = (reset_at - now).to_std().unwrap_or_default();
+    let key = OrgQuotaCacheKey::new(org_id, reset_at.timestamp());
+
+    for _ in 0..MAX_SWAP_ATTEMPTS {
+        let current = cache
+            .get::<OrgQuotaCacheValue>(&key)
+            .await
+            .map_err(Error::cache)?;
+        // Taken from the database the first time the month's count is needed
+        let count = current
+            .as_ref()
+            .map_or(quota.messages_this_month, |OrgQuotaCacheValue(count)| {
+                *count
+            });
+
+        if count >= quota.messages_per_month {
+            return Ok(QuotaState::Exceeded { retry_in });
+        }
+
+        let swapped = cache
+            .compare_and_swap(
+                &key,
+                current.as_ref(),
+                &OrgQuotaCacheValue(count + 1),
+                Some(retry_in.max(Duration::from_secs(1))),
+            )
+            .await
+            .map_err(Error::cache)?;
+
+        if swapped {
+            let counted = organizationquota::Entity::update_many()
+                .col_expr(
+                    organizationquota::Column::MessagesThisMonth,
+                    Expr::col(organizationquota::Column::MessagesThisMonth).add(1),
+                )
+                .filter(organizationquota::Column::OrgId.eq(org_id.clone()))
+                .filter(organizationquota::Column::ResetAt.eq(quota.reset_at))
+                .exec(db)
+                .await;
+            if let Err(e) = counted {
+                // The request fails, so it mustn't use up the quota
+                if let Err(e) = release_cached(cache, &key, retry_in).await {
+                    tracing::warn!("Failed to give back the message to the quota: {e}");
+                }
+                return Err(e.into());
+            }
+
+            return Ok(QuotaState::Allowed);
+        }
+    }
+
+    Err(Error::generic(format!(
+        "Failed to count a message against the quota of organization {org_id}"
+    )))
+}
+
+/// Gives back the count taken by [`acquire`] for a message which then failed to be created.
+pub async fn release(
+    db: &DatabaseConnection,
+    cache: &Cache,
+    org_id: &OrganizationId,
+) -> Result<()> {
+    let Some(quota) = organizationquota::Entity::secure_find(org_id.clone())
+        .one(db)
+        .await?
+    else {
+        return Ok(());
+    };
+
+    let reset_at: DateTimeUtc = quota.reset_at.into();
+    let retry_in = (reset_at - Utc::now()).to_std().unwrap_or_default();
+    let key = OrgQuotaCacheKey::new(org_id, reset_at.timestamp());
+
+    release_cached(cache, &key, retry_in).await?;
+
+    organizationquota::Entity::update_many()
+        .col_expr(
+            organizationquota::Column::MessagesThisMonth,
+            Expr::col(organizationquota::Column::MessagesThisMonth).sub(1),
+        )
+        .filter(organizationquota::Column::OrgId.eq(org_id.clone()))
+        .filter(organizationquota::Column::ResetAt.eq(quota.reset_at))
+        .filter(organizationquota::Column::MessagesThisMonth.gt(0))
+        .exec(db)
+        .await?;
+
+    Ok(())
+}
+
+/// Takes one off the month's cached count, if there is one
+async fn release_cached(cache: &Cache, key: &OrgQuotaCacheKey, retry_in: Duration) -> Result<()> {
+    for _ in 0..MAX_SWAP_ATTEMPTS {
+        let current = cache
+            .get::<OrgQuotaCacheValue>(key)
+            .await
+            .map_err(Error::cache)?;
+        let Some(OrgQuotaCacheValue(count)) = &current else {
+            // The count hasn't been cached yet, so there's only the database's
+            return Ok(());
+        };
+
+        if cache
+            .compare_and_swap(
+                key,
+                current.as_ref(),
+                &OrgQuotaCacheValue((*count - 1).max(0)),
+                Some(retry_in.max(Duration::from_secs(1))),
+            )
+            .await
+            .map_err(Error::cache)?
+        {
+            return Ok(());
+        }
+    }
+
+    Err(Error::generic(format!(
+        "Failed to give back a message to the quota {}",
+        key.as_ref()
+    )))
+}
+
+/// The first monthly reset after `now`
+fn next_reset_at(
+    mut reset_at: DateTimeWithTimeZone,
+    now: DateTimeUtc,
+) -> Option<DateTimeWithTimeZone> {
+    while reset_at <= now {
+        reset_at = reset_at.checked_add_months(Months::new(1))?;
+    }
+    Some(reset_at)
+}
+
+/// Zeroes the quota's count and moves its reset to next month. Does nothing if it's already been
+/// reset by somebody else.
+async fn reset_quota(
+    db: &DatabaseConnection,
+    quota: &organizationquota::Model,
+    now: DateTimeUtc,
+) -> Result<()> {
+    let next = next_reset_at(quota.reset_at, now)
+        .ok_or_else(|| Error::generic("Quota reset date out of range"))?;
+
+    organizationquota::Entity::update_many()
+        .col_expr(
+            organizationquota::Column::MessagesThisMonth,
+            Expr::value(0i64),
+        )
+        .col_expr(organizationquota::Column::ResetAt, Expr::value(next))
+        .col_expr(
+            organizationquota::Column::UpdatedAt,
+            Expr::value(DateTimeWithTimeZone::from(now)),
+        )
+        .filter(organizationquota::Column::OrgId.eq(quota.org_id.clone()))
+        .filter(organizationquota::Column::ResetAt.eq(quota.reset_at))
+        .exec(db)
+        .await?;
+
+    Ok(())
+}
+
+/// Resets every quota whose month is up, returning how many were reset.
+pub async fn reset_expired_quotas(db: &DatabaseConnection) -> Result<usize> {
+    let now = Utc::now();
+    let expired = organizationquota::Entity::find()
+        .filter(organizationquota::Column::ResetAt.lte(now))
+        .all(db)
+        .await?;
+
+    for quota in &expired {
+        reset_quota(db, quota, now).await?;
+    }
+
+    Ok(expired.len())
+}
+
+/// Periodically resets the quotas whose month is up, until the server shuts down.
+pub async fn quota_reset_loop(db: &DatabaseConnection) -> Result<()> {
+    // Kept short so that it doesn't hold up shutting down for long
+    const INTERVAL: Duration = Duration::from_secs(10);
+
+    let mut interval = tokio::time::interval(INTERVAL);
+    while !crate::SHUTTING_DOWN.load(Ordering::SeqCst) {
+        interval.tick().await;
+
+        match reset_expired_quotas(db).await {
+            Ok(0) => {}
+            Ok(count) => tracing::debug!("reset {count} organization quotas"),
+            Err(e) => tracing::error!("Failed to reset organization quotas: {e}"),
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{DateTime, TimeZone, Utc};
+
+    use super::next_reset_at;
+
+    #[test]
+    fn test_next_reset_at() {
+        let reset_at = DateTime::parse_from_rfc3339("2024-01-31T00:00:00+00:00").unwrap();
+
+        // Not due yet
+        let now = Utc.with_ymd_and_hms(2024, 1, 15, 0, 0, 0).unwrap();
+        assert_eq!(next_reset_at(reset_at, now), Some(reset_at));
+
+        // Months without the day reset on their last day, and later months carry on from there
+        let now = Utc.with_ymd_and_hms(2024, 1, 31, 0, 0, 0).unwrap();
+        assert_eq!(
+            next_reset_at(reset_at, now),
+            DateTime::parse_from_rfc3339("2024-02-29T00:00:00+00:00").ok()
+        );
+
+        // Months missed while the server was down are skipped over
+        let now = Utc.with_ymd_and_hms(2024, 4, 10, 0, 0, 0).unwrap();
+        assert_eq!(
+            next_reset_at(reset_at, now),
+            DateTime::parse_from_rfc3339("2024-04-29T00:00:00+00:00").ok()
+        );
+    }
+}
@@ -6,7 +6,7 @@ use axum::{
 };
 
 use super::{
-    security::{permissions_from_bearer, AccessLevel, Permissions},
+    security::{management_org_id, permissions_from_bearer, AccessLevel, Permissions},
     types::{ApplicationId, ApplicationIdOrUid, FeatureFlagSet, OrganizationId},
 };
 use crate::{
@@ -73,6 +73,26 @@ impl FromRequestParts<AppState> for Organization {
     }
 }
 
+/// The Svix management organization, for routes that operate on the server as a whole rather than
+/// on a single organization's data.
+pub struct Management;
+
+impl OperationInput for Management {}
+
+#[async_trait]
+impl FromRequestParts<AppState> for Management {
+    type Rejection = Error;
+
+    async fn from_request_parts(parts: &mut Parts, state: &AppState) -> Result<Self> {
+        let Organization { org_id } = Organization::from_request_parts(parts, state).await?;
+        if org_id != management_org_id() {
+            return Err(HttpError::permission_denied(None, None).into());
+        }
+
+        Ok(Self)
+    }
+}
+
 pub struct Application {
     pub app: application::Model,
 }
@@ -17,6 +17,23 @@ impl Metadata {
     pub fn is_empty(&self) -> bool {
         self.0.is_empty()
     }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &String)> {
+        self.0.iter()
+    }
+
+    /// Whether every key in `selector` is set here, to the same value
+    pub fn matches(&self, selector: &Metadata) -> bool {
+        selector
+            .iter()
+            .all(|(key, value)| self.0.get(key) == Some(value))
+    }
+}
+
+impl From<HashMap<String, String>> for Metadata {
+    fn from(metadata: HashMap<String, String>) -> Self {
+        Self(metadata)
+    }
 }
 
 impl<'de> Deserialize<'de> for Metadata {
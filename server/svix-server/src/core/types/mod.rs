@@ -2,8 +2,9 @@
 // SPDX-License-Identifier: MIT
 
 use std::{
-    collections::{HashMap, HashSet},
+    collections::{BTreeMap, HashMap, HashSet},
     ops::Deref,
+    time::Duration,
 };
 
 use chrono::{DateTime, Utc};
@@ -16,11 +17,14 @@ use serde::{Deserialize, Serialize};
 use svix_ksuid::*;
 use validator::{Validate, ValidationErrors};
 
-use crate::v1::utils::validation_error;
+use crate::{
+    error::{HttpError, ValidationErrorItem},
+    v1::utils::validation_error,
+};
 
 pub mod metadata;
 
-use super::cryptography::{AsymmetricKey, Encryption};
+use super::cryptography::{AsymmetricKey, Encryption, RsaKey};
 
 const ALL_ERROR: &str = "__all__";
 
@@ -563,6 +567,12 @@ create_id_type!(
 create_id_type!(MessageEndpointId, "msgep_");
 create_id_type!(EventTypeId, "evtype_");
 create_id_type!(QueueBackgroundTaskId, "qtask_");
+create_id_type!(MessageCorrelationId, "corr_");
+create_id_type!(
+    EndpointGroupId,
+    "epgrp_",
+    crate::core::types::StringSchema::schema_for_ids("epgrp_")
+);
 
 create_all_id_types!(ApplicationId, ApplicationUid, ApplicationIdOrUid, "app_");
 create_all_id_types!(EndpointId, EndpointUid, EndpointIdOrUid, "ep_");
@@ -632,6 +642,359 @@ impl Validate for EventTypeNameSet {
     }
 }
 
+string_wrapper!(
+    MessageTag,
+    crate::core::types::StringSchema {
+        string_validation: Some(schemars::schema::StringValidation {
+            max_length: Some(64),
+            min_length: None,
+            pattern: Some(r"^[a-zA-Z0-9\-]+$".to_string()),
+        }),
+        example: Some("project-1337".to_string()),
+    }
+);
+
+impl Validate for MessageTag {
+    fn validate(&self) -> Result<(), validator::ValidationErrors> {
+        const MAX_LENGTH: usize = 64;
+        static RE: Lazy<Regex> = Lazy::new(|| Regex::new(r"^[a-zA-Z0-9\-]+$").unwrap());
+        let mut errors = ValidationErrors::new();
+        if self.0.is_empty() {
+            errors.add(
+                ALL_ERROR,
+                validation_error(
+                    Some("length"),
+                    Some("String must be at least one character"),
+                ),
+            );
+        } else if self.0.len() > MAX_LENGTH {
+            errors.add(
+                ALL_ERROR,
+                validation_error(Some("length"), Some("String too long")),
+            );
+        } else if !RE.is_match(&self.0) {
+            errors.add(
+                ALL_ERROR,
+                validation_error(
+                    Some("illegal_string_pattern"),
+                    Some("String must match the following pattern: [a-zA-Z0-9\\-]."),
+                ),
+            );
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[schemars(transparent)]
+pub struct MessageTagSet(pub HashSet<MessageTag>);
+json_wrapper!(MessageTagSet);
+
+impl Validate for MessageTagSet {
+    fn validate(&self) -> Result<(), validator::ValidationErrors> {
+        for item in self.0.iter() {
+            item.validate()?;
+        }
+        Ok(())
+    }
+}
+
+/// Key-value pairs which are covered by a message's signature along with its payload, so they can't
+/// be swapped out without invalidating it.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[schemars(transparent)]
+pub struct MessageSignedMetadata(pub HashMap<String, String>);
+json_wrapper!(MessageSignedMetadata);
+
+impl MessageSignedMetadata {
+    const MAX_ENTRIES: usize = 10;
+    const MAX_VALUE_LENGTH: usize = 256;
+
+    /// The entries as a JSON object with its keys sorted, which is the form that's both signed and
+    /// sent to endpoints
+    pub fn to_canonical_json(&self) -> String {
+        let sorted: BTreeMap<_, _> = self.0.iter().collect();
+        serde_json::to_string(&sorted).expect("Error serializing signed metadata")
+    }
+}
+
+impl Validate for MessageSignedMetadata {
+    fn validate(&self) -> Result<(), validator::ValidationErrors> {
+        let mut errors = ValidationErrors::new();
+        if self.0.len() > Self::MAX_ENTRIES {
+            errors.add(
+                ALL_ERROR,
+                validation_error(
+                    Some("length"),
+                    Some("At most 10 signed metadata entries are allowed"),
+                ),
+            );
+        }
+        for (key, value) in &self.0 {
+            validate_limited_str(key)?;
+            // The metadata is sent in a header, so it must be a valid header value
+            if value.len() > Self::MAX_VALUE_LENGTH
+                || !value.bytes().all(|b| b == b' ' || b.is_ascii_graphic())
+            {
+                errors.add(
+                    ALL_ERROR,
+                    validation_error(
+                        Some("invalid_value"),
+                        Some(
+                            "Signed metadata values must be at most 256 printable ASCII characters",
+                        ),
+                    ),
+                );
+            }
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+/// The semantic version of the schema a message's payload follows
+#[derive(Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize)]
+pub struct PayloadVersion(pub semver::Version);
+
+common_jsonschema_impl!(
+    PayloadVersion,
+    crate::core::types::StringSchema {
+        string_validation: None,
+        example: Some("1.2.3".to_string()),
+    }
+);
+
+impl std::fmt::Display for PayloadVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl From<PayloadVersion> for sea_orm::Value {
+    fn from(v: PayloadVersion) -> Self {
+        Self::String(Some(Box::new(v.0.to_string())))
+    }
+}
+
+impl sea_orm::TryGetable for PayloadVersion {
+    fn try_get_by<I: sea_orm::ColIdx>(
+        res: &sea_orm::QueryResult,
+        index: I,
+    ) -> Result<Self, sea_orm::TryGetError> {
+        match String::try_get_by(res, index) {
+            Ok(v) => v
+                .parse()
+                .map(PayloadVersion)
+                .map_err(|x| sea_orm::TryGetError::DbErr(sea_orm::DbErr::Type(x.to_string()))),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn try_get(
+        res: &sea_orm::QueryResult,
+        pre: &str,
+        col: &str,
+    ) -> Result<Self, sea_orm::TryGetError> {
+        match String::try_get(res, pre, col) {
+            Ok(v) => v
+                .parse()
+                .map(PayloadVersion)
+                .map_err(|x| sea_orm::TryGetError::DbErr(sea_orm::DbErr::Type(x.to_string()))),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+impl sea_orm::sea_query::Nullable for PayloadVersion {
+    fn null() -> sea_orm::Value {
+        sea_orm::Value::String(None)
+    }
+}
+
+impl sea_orm::sea_query::ValueType for PayloadVersion {
+    fn try_from(v: sea_orm::Value) -> Result<Self, sea_orm::sea_query::ValueTypeErr> {
+        match v {
+            sea_orm::Value::String(Some(x)) => x
+                .parse()
+                .map(PayloadVersion)
+                .map_err(|_| sea_orm::sea_query::ValueTypeErr),
+            _ => Err(sea_orm::sea_query::ValueTypeErr),
+        }
+    }
+
+    fn type_name() -> String {
+        stringify!(PayloadVersion).to_owned()
+    }
+
+    fn column_type() -> sea_orm::sea_query::ColumnType {
+        String::column_type()
+    }
+
+    fn array_type() -> sea_orm::sea_query::ArrayType {
+        String::array_type()
+    }
+}
+
+/// The HTTP method webhooks are sent to an endpoint with
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+pub struct HttpMethod(pub http::Method);
+
+impl HttpMethod {
+    /// The methods webhooks may be sent with, all of which carry a body
+    pub const ALLOWED: [http::Method; 3] =
+        [http::Method::POST, http::Method::PUT, http::Method::PATCH];
+}
+
+impl Default for HttpMethod {
+    fn default() -> Self {
+        Self(http::Method::POST)
+    }
+}
+
+common_jsonschema_impl!(
+    HttpMethod,
+    crate::core::types::StringSchema {
+        string_validation: Some(schemars::schema::StringValidation {
+            max_length: None,
+            min_length: None,
+            pattern: Some(r"^(POST|PUT|PATCH)$".to_string()),
+        }),
+        example: Some("POST".to_string()),
+    }
+);
+
+impl std::fmt::Display for HttpMethod {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl std::str::FromStr for HttpMethod {
+    type Err = http::method::InvalidMethod;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        http::Method::from_bytes(s.as_bytes()).map(HttpMethod)
+    }
+}
+
+impl Serialize for HttpMethod {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.0.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for HttpMethod {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+impl Validate for HttpMethod {
+    fn validate(&self) -> Result<(), validator::ValidationErrors> {
+        let mut errors = ValidationErrors::new();
+        if !Self::ALLOWED.contains(&self.0) {
+            errors.add(
+                ALL_ERROR,
+                validation_error(
+                    Some("invalid_value"),
+                    Some("The HTTP method must be one of POST, PUT or PATCH"),
+                ),
+            );
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+impl From<HttpMethod> for sea_orm::Value {
+    fn from(v: HttpMethod) -> Self {
+        Self::String(Some(Box::new(v.0.to_string())))
+    }
+}
+
+impl sea_orm::TryGetable for HttpMethod {
+    fn try_get_by<I: sea_orm::ColIdx>(
+        res: &sea_orm::QueryResult,
+        index: I,
+    ) -> Result<Self, sea_orm::TryGetError> {
+        match String::try_get_by(res, index) {
+            Ok(v) => v.parse().map_err(|x: http::method::InvalidMethod| {
+                sea_orm::TryGetError::DbErr(sea_orm::DbErr::Type(x.to_string()))
+            }),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn try_get(
+        res: &sea_orm::QueryResult,
+        pre: &str,
+        col: &str,
+    ) -> Result<Self, sea_orm::TryGetError> {
+        match String::try_get(res, pre, col) {
+            Ok(v) => v.parse().map_err(|x: http::method::InvalidMethod| {
+                sea_orm::TryGetError::DbErr(sea_orm::DbErr::Type(x.to_string()))
+            }),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+impl sea_orm::sea_query::Nullable for HttpMethod {
+    fn null() -> sea_orm::Value {
+        sea_orm::Value::String(None)
+    }
+}
+
+impl sea_orm::sea_query::ValueType for HttpMethod {
+    fn try_from(v: sea_orm::Value) -> Result<Self, sea_orm::sea_query::ValueTypeErr> {
+        match v {
+            sea_orm::Value::String(Some(x)) => {
+                x.parse().map_err(|_| sea_orm::sea_query::ValueTypeErr)
+            }
+            _ => Err(sea_orm::sea_query::ValueTypeErr),
+        }
+    }
+
+    fn type_name() -> String {
+        stringify!(HttpMethod).to_owned()
+    }
+
+    fn column_type() -> sea_orm::sea_query::ColumnType {
+        String::column_type()
+    }
+
+    fn array_type() -> sea_orm::sea_query::ArrayType {
+        String::array_type()
+    }
+}
+
+/// A retry schedule, as the number of seconds to wait before each retry
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[schemars(transparent)]
+pub struct RetrySchedule(pub Vec<u32>);
+json_wrapper!(RetrySchedule);
+
+impl RetrySchedule {
+    pub fn durations(&self) -> Vec<Duration> {
+        self.0
+            .iter()
+            .map(|secs| Duration::from_secs((*secs).into()))
+            .collect()
+    }
+}
+
 #[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct ExpiringSigningKeys(pub Vec<ExpiringSigningKey>);
 json_wrapper!(ExpiringSigningKeys);
@@ -648,6 +1011,7 @@ pub enum EndpointSecretType {
     Hmac256 = 1,
     Ed25519 = 2,
     // Reserved = 3,
+    Rsa = 4,
 }
 
 impl EndpointSecretType {
@@ -655,6 +1019,7 @@ impl EndpointSecretType {
         match self {
             EndpointSecretType::Hmac256 => "whsec_",
             EndpointSecretType::Ed25519 => "whsk_",
+            EndpointSecretType::Rsa => "whrsk_",
         }
     }
 
@@ -662,6 +1027,7 @@ impl EndpointSecretType {
         match self {
             EndpointSecretType::Hmac256 => "whsec_",
             EndpointSecretType::Ed25519 => "whpk_",
+            EndpointSecretType::Rsa => "whrpk_",
         }
     }
 }
@@ -731,7 +1097,7 @@ impl EndpointSecretInternal {
     }
 
     pub fn generate_symmetric(encryption: &Encryption) -> crate::error::Result<Self> {
-        let buf: [u8; Self::KEY_SIZE] = rand::thread_rng().gen();
+        let buf: [u8; EndpointSecret::SYMMETRIC_KEY_MIN_SIZE] = rand::thread_rng().gen();
         Self::new(encryption, EndpointSecretType::Hmac256, &buf)
     }
 
@@ -740,6 +1106,11 @@ impl EndpointSecretInternal {
         Self::new(encryption, EndpointSecretType::Ed25519, key.0.sk.as_slice())
     }
 
+    pub fn generate_rsa(encryption: &Encryption) -> crate::error::Result<Self> {
+        let key = RsaKey::generate()?;
+        Self::new(encryption, EndpointSecretType::Rsa, &key.private_key_der())
+    }
+
     fn into_vec(mut self) -> Vec<u8> {
         let marker: u8 = self.marker.to_u8();
 
@@ -779,6 +1150,7 @@ impl EndpointSecretInternal {
             EndpointSecretType::Ed25519 => {
                 EndpointSecret::Asymmetric(AsymmetricKey::from_slice(&key[..])?)
             }
+            EndpointSecretType::Rsa => EndpointSecret::Rsa(RsaKey::from_slice(&key[..])?),
         })
     }
 
@@ -788,11 +1160,21 @@ impl EndpointSecretInternal {
     ) -> crate::error::Result<Self> {
         Ok(match endpoint_secret {
             EndpointSecret::Symmetric(key) => {
+                EndpointSecret::check_symmetric_key_size(&key).map_err(|msg| {
+                    HttpError::unprocessable_entity(vec![ValidationErrorItem {
+                        loc: vec!["body".to_owned(), "key".to_owned()],
+                        msg: msg.to_owned(),
+                        ty: "value_error".to_owned(),
+                    }])
+                })?;
                 Self::new(encryption, EndpointSecretType::Hmac256, &key)?
             }
             EndpointSecret::Asymmetric(key) => {
                 Self::new(encryption, EndpointSecretType::Ed25519, key.0.sk.as_slice())?
             }
+            EndpointSecret::Rsa(key) => {
+                Self::new(encryption, EndpointSecretType::Rsa, &key.private_key_der())?
+            }
         })
     }
 
@@ -807,6 +1189,7 @@ impl EndpointSecretInternal {
                 .sk
                 .sign(bytes, None)
                 .to_vec(),
+            EndpointSecretType::Rsa => RsaKey::from_slice(&key[..]).unwrap().sign(bytes).unwrap(),
         }
     }
 
@@ -934,15 +1317,27 @@ impl sea_orm::sea_query::ValueType for EndpointSecretInternal {
 pub enum EndpointSecret {
     Symmetric(Vec<u8>),
     Asymmetric(AsymmetricKey),
+    Rsa(RsaKey),
 }
 
 impl EndpointSecret {
     // IMPORTANT: has to be at least 24 bytes because of how we encode the type (and legacy ones
-    // didn't have type encoded).
-    // XXX Also: can't change withuot breaking from_vec
-    const KEY_SIZE: usize = 24;
-    // Needed because of rust limitations
-    const KEY_SIZE_MAX: usize = 75;
+    // didn't have type encoded). Keys shorter than 32 bytes are also weak for HMAC-SHA256, though
+    // existing 24 byte keys are still used for signing.
+    const SYMMETRIC_KEY_MIN_SIZE: usize = 32;
+    // Longer keys are hashed down by HMAC-SHA256 anyway, so there's no point accepting huge ones
+    const SYMMETRIC_KEY_MAX_SIZE: usize = 256;
+
+    /// Checks a new symmetric key is within the accepted size bounds
+    fn check_symmetric_key_size(key: &[u8]) -> Result<(), &'static str> {
+        if key.len() < Self::SYMMETRIC_KEY_MIN_SIZE {
+            Err("Symmetric signing key must be at least 32 bytes")
+        } else if key.len() > Self::SYMMETRIC_KEY_MAX_SIZE {
+            Err("Symmetric signing key must be no longer than 256 bytes")
+        } else {
+            Ok(())
+        }
+    }
 
     pub fn serialize_secret_key(&self) -> String {
         match self {
@@ -960,6 +1355,13 @@ impl EndpointSecret {
                     &base64::encode(key.0.sk.as_slice())
                 )
             }
+            Self::Rsa(key) => {
+                format!(
+                    "{}{}",
+                    EndpointSecretType::Rsa.secret_prefix(),
+                    &base64::encode(key.private_key_der())
+                )
+            }
         }
     }
 
@@ -979,6 +1381,13 @@ impl EndpointSecret {
                     &base64::encode(key.pubkey())
                 )
             }
+            Self::Rsa(key) => {
+                format!(
+                    "{}{}",
+                    EndpointSecretType::Rsa.public_prefix(),
+                    &base64::encode(key.pubkey())
+                )
+            }
         }
     }
 }
@@ -1009,6 +1418,15 @@ impl<'de> Deserialize<'de> for EndpointSecret {
                     )
                     .map_err(|e| Error::custom(e.to_string()))?,
                 ))
+            } else if string.starts_with(EndpointSecretType::Rsa.secret_prefix()) {
+                Ok(Self::Rsa(
+                    RsaKey::from_base64(
+                        string
+                            .get(EndpointSecretType::Rsa.secret_prefix().len()..)
+                            .ok_or(invalid_prefix)?,
+                    )
+                    .map_err(|e| Error::custom(e.to_string()))?,
+                ))
             } else if string.starts_with(EndpointSecretType::Hmac256.secret_prefix()) {
                 Ok(Self::Symmetric(
                     string
@@ -1031,11 +1449,8 @@ impl Validate for EndpointSecret {
 
         match self {
             Self::Symmetric(bytes) => {
-                if bytes.len() < Self::KEY_SIZE || bytes.len() > Self::KEY_SIZE_MAX {
-                    errors.add(
-                        ALL_ERROR,
-                        validation_error(Some("length"), Some("secret length invalid")),
-                    );
+                if let Err(msg) = Self::check_symmetric_key_size(bytes) {
+                    errors.add(ALL_ERROR, validation_error(Some("length"), Some(msg)));
                 }
             }
             Self::Asymmetric(key) => {
@@ -1051,6 +1466,22 @@ impl Validate for EndpointSecret {
                     );
                 }
             }
+            Self::Rsa(key) => {
+                if key.0.size() * 8 < RsaKey::MIN_BITS {
+                    errors.add(
+                        ALL_ERROR,
+                        validation_error(
+                            Some("length"),
+                            Some("RSA key must be at least 2048 bits"),
+                        ),
+                    );
+                } else if !key.0.check_key().unwrap_or(false) {
+                    errors.add(
+                        ALL_ERROR,
+                        validation_error(Some("invalid_key"), Some("Invalid RSA key")),
+                    );
+                }
+            }
         }
 
         if errors.is_empty() {
@@ -1067,7 +1498,7 @@ impl JsonSchema for EndpointSecret {
     }
 
     fn json_schema(gen: &mut schemars::gen::SchemaGenerator) -> schemars::schema::Schema {
-        const KEY_PATTERN: &str = "^(whsec_)?[a-zA-Z0-9+/=]{32,100}$";
+        const KEY_PATTERN: &str = "^(whsec_)?[a-zA-Z0-9+/=]{43,344}$";
         let mut schema = String::json_schema(gen);
         if let schemars::schema::Schema::Object(ref mut obj) = schema {
             obj.string = Some(Box::new(schemars::schema::StringValidation {
@@ -1075,12 +1506,14 @@ impl JsonSchema for EndpointSecret {
                 ..Default::default()
             }));
             obj.metadata = Some(Box::new(schemars::schema::Metadata{
-                description: Some("The endpoint's verification secret. If `null` is passed, a secret is automatically generated. Format: `base64` encoded random bytes optionally prefixed with `whsec_`. Recommended size: 24.".to_string()),
+                description: Some("The endpoint's verification secret. If `null` is passed, a secret is automatically generated. Format: `base64` encoded random bytes optionally prefixed with `whsec_`. Must be between 32 and 256 bytes long. Recommended size: 32.".to_string()),
                 .. Default::default()
             }));
             obj.extensions.insert(
                 "example".to_string(),
-                serde_json::Value::String("whsec_C2FVsBQIhrscChlQIMV+b5sSYspob7oD".to_string()),
+                serde_json::Value::String(
+                    "whsec_C2FVsBQIhrscChlQIMV+b5sSYspob7oD+b5sSYspob7oD".to_string(),
+                ),
             );
         }
         schema
@@ -1321,13 +1754,24 @@ pub enum MessageStatus {
     Pending = 1,
     Fail = 2,
     Sending = 3,
+    /// The message's deadline passed before it could be delivered
+    Expired = 4,
+    /// A pre-dispatch hook chose not to send the message
+    Filtered = 5,
+    /// The message's payload version is below the endpoint's minimum, so it wasn't sent
+    Incompatible = 6,
+    /// Sending the message would exceed the endpoint's rate limit, so it's waiting to be sent
+    RateLimited = 7,
+    /// The message can no longer be delivered, e.g. because its application or endpoint was deleted
+    /// or its payload expired
+    Cancelled = 8,
 }
 
 jsonschema_for_repr_enum! {
     MessageStatus,
     i16,
-    "The sending status of the message:\n- Success = 0\n- Pending = 1\n- Fail = 2\n- Sending = 3",
-    Success, Pending, Fail, Sending
+    "The sending status of the message:\n- Success = 0\n- Pending = 1\n- Fail = 2\n- Sending = 3\n- Expired = 4\n- Filtered = 5\n- Incompatible = 6\n- RateLimited = 7\n- Cancelled = 8",
+    Success, Pending, Fail, Sending, Expired, Filtered, Incompatible, RateLimited, Cancelled
 }
 
 #[repr(i16)]
@@ -1387,13 +1831,15 @@ pub type FeatureFlagSet = HashSet<FeatureFlag>;
 mod tests {
     use std::collections::HashMap;
 
+    use serde_json::json;
     use validator::Validate;
 
     use super::{
         validate_header_map, ApplicationId, ApplicationUid, EndpointHeaders, EndpointHeadersPatch,
-        EndpointSecret, EventChannel, EventTypeName,
+        EndpointSecret, EndpointSecretInternal, EndpointSecretType, EventChannel, EventTypeName,
+        HttpMethod, MessageTag,
     };
-    use crate::core::cryptography::AsymmetricKey;
+    use crate::core::cryptography::{AsymmetricKey, Encryption, RsaKey};
 
     #[test]
     fn test_id_validation() {
@@ -1467,6 +1913,46 @@ mod tests {
         assert!(evt_name.validate().is_err());
     }
 
+    #[test]
+    fn test_message_tag_validation() {
+        // Underscores and dots are not allowed, unlike channels
+        let tag = MessageTag("my_tag".to_owned());
+        assert!(tag.validate().is_err());
+        let tag = MessageTag("my.tag".to_owned());
+        assert!(tag.validate().is_err());
+
+        // Check all allowed
+        let tag = MessageTag("azAZ09-".to_owned());
+        tag.validate().unwrap();
+
+        // Check length
+        let tag = MessageTag("X".repeat(64));
+        tag.validate().unwrap();
+        let tag = MessageTag("X".repeat(65));
+        assert!(tag.validate().is_err());
+
+        let tag = MessageTag("".to_owned());
+        assert!(tag.validate().is_err());
+    }
+
+    #[test]
+    fn test_http_method_validation() {
+        assert_eq!(HttpMethod::default().0, http::Method::POST);
+
+        for method in ["POST", "PUT", "PATCH"] {
+            let method: HttpMethod = serde_json::from_value(json!(method)).unwrap();
+            method.validate().unwrap();
+        }
+        for method in ["GET", "DELETE", "HEAD", "post", "CUSTOM"] {
+            let method: HttpMethod = serde_json::from_value(json!(method)).unwrap();
+            assert!(method.validate().is_err());
+        }
+        assert!(serde_json::from_value::<HttpMethod>(json!("NOT A METHOD")).is_err());
+
+        let method = HttpMethod(http::Method::PATCH);
+        assert_eq!(serde_json::to_value(&method).unwrap(), json!("PATCH"));
+    }
+
     #[test]
     fn test_endpoint_headers_validation() {
         let hdr_map = HashMap::from([
@@ -1538,13 +2024,93 @@ mod tests {
 
         let secret =
             EndpointSecret::Symmetric(base64::decode("C2FVsBQIhrscChlQIMV+b5sSYspob7oD").unwrap());
+        assert!(secret.validate().is_err());
+
+        let secret = EndpointSecret::Symmetric(vec![1; 31]);
+        assert!(secret.validate().is_err());
+
+        let secret = EndpointSecret::Symmetric(vec![1; 32]);
         secret.validate().unwrap();
 
+        let secret = EndpointSecret::Symmetric(vec![1; 256]);
+        secret.validate().unwrap();
+
+        let secret = EndpointSecret::Symmetric(vec![1; 257]);
+        assert!(secret.validate().is_err());
+
         let secret = EndpointSecret::Asymmetric(AsymmetricKey::from_base64("6Xb/dCcHpPea21PS1N9VY/NZW723CEc77N4rJCubMbfVKIDij2HKpMKkioLlX0dRqSKJp4AJ6p9lMicMFs6Kvg==").unwrap());
         secret.validate().unwrap();
 
         let secret = EndpointSecret::Asymmetric(AsymmetricKey::from_base64("6Xb/dCcHpPea21PS1N9VY/NZW723CEc77N4rJCubMbfVKIDij2HKpMKkioLlaaaaaaaaaaAJ6p9lMicMFs6Kvg==").unwrap());
         assert!(secret.validate().is_err());
+
+        let secret = EndpointSecret::Rsa(RsaKey::generate().unwrap());
+        secret.validate().unwrap();
+
+        // Too small RSA key
+        let secret = EndpointSecret::Rsa(RsaKey(openssl::rsa::Rsa::generate(1024).unwrap()));
+        assert!(secret.validate().is_err());
+    }
+
+    #[test]
+    fn test_symmetric_endpoint_secret_size() {
+        let encryption = Encryption::new_noop();
+        for size in [31, 257] {
+            let err = EndpointSecretInternal::from_endpoint_secret(
+                EndpointSecret::Symmetric(vec![1; size]),
+                &encryption,
+            )
+            .unwrap_err();
+            let crate::error::ErrorType::Http(err) = err.typ else {
+                panic!("expected an HTTP error");
+            };
+            assert_eq!(err.status, http::StatusCode::UNPROCESSABLE_ENTITY);
+        }
+
+        let internal = EndpointSecretInternal::from_endpoint_secret(
+            EndpointSecret::Symmetric(vec![1; 32]),
+            &encryption,
+        )
+        .unwrap();
+        assert_eq!(
+            internal.into_endpoint_secret(&encryption).unwrap(),
+            EndpointSecret::Symmetric(vec![1; 32])
+        );
+
+        // Generated keys are long enough to be configured again, for example on another endpoint
+        let generated = EndpointSecretInternal::generate_symmetric(&encryption)
+            .unwrap()
+            .into_endpoint_secret(&encryption)
+            .unwrap();
+        generated.validate().unwrap();
+    }
+
+    #[test]
+    fn test_rsa_endpoint_secret() {
+        let key = RsaKey::generate().unwrap();
+        let secret = EndpointSecret::Rsa(key.clone());
+        assert!(secret.serialize_secret_key().starts_with("whrsk_"));
+        assert!(secret.serialize_public_key().starts_with("whrpk_"));
+
+        let js = serde_json::json!({ "key": secret.serialize_secret_key() });
+        let ep = serde_json::from_value::<EndpointSecretTestStruct>(js).unwrap();
+        assert_eq!(ep.key, secret);
+
+        // Only the private key can be used to sign
+        let js = serde_json::json!({ "key": secret.serialize_public_key() });
+        assert!(serde_json::from_value::<EndpointSecretTestStruct>(js).is_err());
+
+        let encryption = Encryption::new([1; 32]);
+        let internal =
+            EndpointSecretInternal::from_endpoint_secret(secret.clone(), &encryption).unwrap();
+        assert_eq!(internal.type_(), &EndpointSecretType::Rsa);
+        assert_eq!(
+            internal.clone().into_endpoint_secret(&encryption).unwrap(),
+            secret
+        );
+
+        let signature = internal.sign(&encryption, b"hello world");
+        assert!(key.verify(b"hello world", &signature).unwrap());
     }
 
     #[derive(serde::Deserialize)]
@@ -1578,7 +2144,7 @@ mod tests {
         }
 
         // Too long secret
-        let js = serde_json::json!({ "key": "whsec_V09IYXZUaFJoSnFobnpJQkpPMXdpdGFNWnJsRzAxdXZCeTVndVpwRmxSSXFsc0oyYzBTRWRUekJhYnlaZ0JSRGNPQ3BGZG1xYjFVVmRGQ3UK" });
+        let js = serde_json::json!({ "key": format!("whsec_{}", base64::encode([1; 257])) });
         let ep = serde_json::from_value::<EndpointSecretTestStruct>(js).unwrap();
         assert!(ep.key.validate().is_err());
 
@@ -1,6 +1,11 @@
 // SPDX-FileCopyrightText: © 2022 Svix Authors
 // SPDX-License-Identifier: MIT
 
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
+
 use sea_orm::{
     ColumnTrait, DatabaseConnection, DbBackend, DeleteResult, EntityTrait, QueryFilter,
     SqlxPostgresConnector,
@@ -30,6 +35,51 @@ pub async fn init_db(cfg: &Configuration) -> DatabaseConnection {
     SqlxPostgresConnector::from_sqlx_postgres_pool(connect(&cfg.db_dsn, cfg.db_pool_max_size).await)
 }
 
+/// The primary database, which all writes go to, and its read replicas, which reads that can
+/// tolerate some replication lag are spread across.
+#[derive(Clone)]
+pub struct DbPool {
+    primary: DatabaseConnection,
+    replicas: Arc<Vec<DatabaseConnection>>,
+    next_replica: Arc<AtomicUsize>,
+}
+
+impl DbPool {
+    pub fn new(primary: DatabaseConnection, replicas: Vec<DatabaseConnection>) -> Self {
+        Self {
+            primary,
+            replicas: Arc::new(replicas),
+            next_replica: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+
+    /// The primary, for writes and for reads which must see the latest data
+    pub fn write(&self) -> &DatabaseConnection {
+        &self.primary
+    }
+
+    /// The next read replica in turn, or the primary if there are none
+    pub fn read(&self) -> &DatabaseConnection {
+        if self.replicas.is_empty() {
+            return &self.primary;
+        }
+
+        let i = self.next_replica.fetch_add(1, Ordering::Relaxed) % self.replicas.len();
+        &self.replicas[i]
+    }
+}
+
+pub async fn init_db_pool(cfg: &Configuration) -> DbPool {
+    let mut replicas = Vec::new();
+    for dsn in cfg.db_read_replica_dsns.iter().flatten() {
+        replicas.push(SqlxPostgresConnector::from_sqlx_postgres_pool(
+            connect(dsn, cfg.db_pool_max_size).await,
+        ));
+    }
+
+    DbPool::new(init_db(cfg).await, replicas)
+}
+
 pub async fn run_migrations(cfg: &Configuration) {
     let db = connect(&cfg.db_dsn, cfg.db_pool_max_size).await;
     MIGRATIONS.run(&db).await.unwrap();
@@ -118,3 +168,39 @@ pub async fn wipe_org(cfg: &Configuration, org_id: OrganizationId) {
         .await
         .unwrap_or_else(|_| panic!("Error deleting event types associated with org ID {org_id}"));
 }
+
+#[cfg(test)]
+mod tests {
+    use sea_orm::DatabaseConnection;
+
+    use super::DbPool;
+
+    #[test]
+    fn test_db_pool_routing() {
+        let pool = DbPool::new(
+            DatabaseConnection::Disconnected,
+            vec![
+                DatabaseConnection::Disconnected,
+                DatabaseConnection::Disconnected,
+            ],
+        );
+
+        // Writes always go to the primary
+        assert!(std::ptr::eq(pool.write(), &pool.primary));
+        assert!(std::ptr::eq(pool.write(), &pool.primary));
+
+        // Reads go to each replica in turn, and never to the primary
+        assert!(std::ptr::eq(pool.read(), &pool.replicas[0]));
+        assert!(std::ptr::eq(pool.read(), &pool.replicas[1]));
+        assert!(std::ptr::eq(pool.read(), &pool.replicas[0]));
+
+        // Clones share the rotation
+        let clone = pool.clone();
+        assert!(std::ptr::eq(clone.read(), &clone.replicas[1]));
+        assert!(std::ptr::eq(pool.read(), &pool.replicas[0]));
+
+        // Without replicas, reads go to the primary
+        let pool = DbPool::new(DatabaseConnection::Disconnected, vec![]);
+        assert!(std::ptr::eq(pool.read(), &pool.primary));
+    }
+}
@@ -32,6 +32,8 @@ pub enum Relation {
     Message,
     #[sea_orm(has_one = "super::applicationmetadata::Entity")]
     Metadata,
+    #[sea_orm(has_one = "super::applicationwebhooksecret::Entity")]
+    WebhookSecret,
 }
 
 impl Related<super::endpoint::Entity> for Entity {
@@ -52,6 +54,12 @@ impl Related<super::applicationmetadata::Entity> for Entity {
     }
 }
 
+impl Related<super::applicationwebhooksecret::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::WebhookSecret.def()
+    }
+}
+
 impl Model {
     pub async fn fetch_or_create_metadata(
         &self,
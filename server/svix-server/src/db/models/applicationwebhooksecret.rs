@@ -0,0 +1,68 @@
+// SPDX-FileCopyrightText: © 2022 Svix Authors
+// SPDX-License-Identifier: MIT
+
+use chrono::Utc;
+use sea_orm::{entity::prelude::*, ActiveValue::Set};
+
+use crate::core::types::{ApplicationId, EndpointSecretInternal, ExpiringSigningKeys};
+
+/// A signing secret shared by all of an application's endpoints that don't have a secret of their
+/// own
+#[derive(Clone, Debug, PartialEq, Eq, DeriveEntityModel)]
+#[sea_orm(table_name = "applicationwebhooksecret")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub id: ApplicationId,
+    pub created_at: DateTimeWithTimeZone,
+    pub updated_at: DateTimeWithTimeZone,
+    pub key: EndpointSecretInternal,
+    pub old_keys: Option<ExpiringSigningKeys>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(
+        belongs_to = "super::application::Entity",
+        from = "Column::Id",
+        to = "super::application::Column::Id",
+        on_update = "NoAction",
+        on_delete = "Cascade"
+    )]
+    Application,
+}
+
+impl Related<super::application::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Application.def()
+    }
+}
+
+#[axum::async_trait]
+impl ActiveModelBehavior for ActiveModel {
+    async fn before_save<C>(mut self, _db: &C, _insert: bool) -> Result<Self, DbErr>
+    where
+        C: ConnectionTrait,
+    {
+        self.updated_at = Set(Utc::now().into());
+        Ok(self)
+    }
+}
+
+impl ActiveModel {
+    pub fn new(app_id: ApplicationId, key: EndpointSecretInternal) -> Self {
+        let timestamp = Utc::now();
+        Self {
+            id: Set(app_id),
+            created_at: Set(timestamp.into()),
+            updated_at: Set(timestamp.into()),
+            key: Set(key),
+            old_keys: Set(None),
+        }
+    }
+}
+
+impl Entity {
+    pub fn secure_find(app_id: ApplicationId) -> Select<Entity> {
+        Self::find().filter(Column::Id.eq(app_id))
+    }
+}
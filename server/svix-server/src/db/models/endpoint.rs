@@ -4,12 +4,12 @@
 use chrono::Utc;
 use sea_orm::{entity::prelude::*, ActiveValue::Set, Condition, IntoActiveModel};
 
-use super::endpointmetadata;
+use super::{endpointgroup, endpointmetadata};
 use crate::{
     core::types::{
-        ApplicationId, BaseId, EndpointHeaders, EndpointId, EndpointIdOrUid,
-        EndpointSecretInternal, EndpointUid, EventChannelSet, EventTypeNameSet,
-        ExpiringSigningKeys,
+        ApplicationId, BaseId, EndpointGroupId, EndpointHeaders, EndpointId, EndpointIdOrUid,
+        EndpointSecretInternal, EndpointUid, EventChannelSet, EventTypeName, EventTypeNameSet,
+        ExpiringSigningKeys, HttpMethod, PayloadVersion,
     },
     error,
 };
@@ -22,7 +22,8 @@ pub struct Model {
     pub created_at: DateTimeWithTimeZone,
     pub updated_at: DateTimeWithTimeZone,
     pub app_id: ApplicationId,
-    pub key: EndpointSecretInternal,
+    /// When `None`, the endpoint is signed with its application's secret
+    pub key: Option<EndpointSecretInternal>,
     pub url: String,
     pub description: String,
     pub event_types_ids: Option<EventTypeNameSet>,
@@ -35,6 +36,26 @@ pub struct Model {
     pub old_keys: Option<ExpiringSigningKeys>,
     pub channels: Option<EventChannelSet>,
     pub headers: Option<EndpointHeaders>,
+    pub group_id: Option<EndpointGroupId>,
+    pub response_webhook_event_type: Option<EventTypeName>,
+    /// Messages with an older payload version aren't sent to the endpoint
+    pub min_payload_version: Option<PayloadVersion>,
+    pub http_method: HttpMethod,
+    /// The `Content-Type` webhooks are sent with, `application/json` if unset
+    pub content_type: Option<String>,
+    /// Whether webhook payloads are encrypted for `payload_encryption_key`
+    pub payload_encryption_enabled: bool,
+    /// The endpoint's raw X25519 public key
+    pub payload_encryption_key: Option<Vec<u8>>,
+    /// Whether `dispatch_transformation` is run on webhook payloads
+    pub dispatch_transformation_enabled: bool,
+    /// The code of the script webhook payloads are transformed with before being sent
+    pub dispatch_transformation: Option<String>,
+    /// Operator-defined data about the endpoint, always a JSON object
+    #[sea_orm(column_type = "JsonBinary", nullable)]
+    pub custom_data: Option<Json>,
+    /// Signed in place of the message ID, for receivers which verify signatures that way
+    pub custom_signature_prefix: Option<String>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
@@ -47,6 +68,14 @@ pub enum Relation {
         on_delete = "Cascade"
     )]
     Application,
+    #[sea_orm(
+        belongs_to = "super::endpointgroup::Entity",
+        from = "Column::GroupId",
+        to = "super::endpointgroup::Column::Id",
+        on_update = "NoAction",
+        on_delete = "SetNull"
+    )]
+    Group,
     #[sea_orm(has_many = "super::messagedestination::Entity")]
     Messagedestination,
     #[sea_orm(has_one = "super::endpointmetadata::Entity")]
@@ -59,6 +88,12 @@ impl Related<super::application::Entity> for Entity {
     }
 }
 
+impl Related<endpointgroup::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Group.def()
+    }
+}
+
 impl Related<super::messagedestination::Entity> for Entity {
     fn to() -> RelationDef {
         Relation::Messagedestination.def()
@@ -83,7 +118,7 @@ impl ActiveModelBehavior for ActiveModel {
 }
 
 impl ActiveModel {
-    pub fn new(app_id: ApplicationId, key: EndpointSecretInternal) -> Self {
+    pub fn new(app_id: ApplicationId, key: Option<EndpointSecretInternal>) -> Self {
         let timestamp = Utc::now();
         Self {
             id: Set(EndpointId::new(timestamp.into(), None)),
@@ -91,6 +126,8 @@ impl ActiveModel {
             created_at: Set(timestamp.into()),
             updated_at: Set(timestamp.into()),
             deleted: Set(false),
+            payload_encryption_enabled: Set(false),
+            dispatch_transformation_enabled: Set(false),
             key: Set(key),
             ..ActiveModelTrait::default()
         }
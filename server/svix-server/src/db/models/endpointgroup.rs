@@ -0,0 +1,82 @@
+// SPDX-FileCopyrightText: © 2022 Svix Authors
+// SPDX-License-Identifier: MIT
+
+use chrono::Utc;
+use sea_orm::{entity::prelude::*, ActiveValue::Set};
+
+use crate::core::types::{ApplicationId, BaseId, EndpointGroupId, EndpointHeaders, RetrySchedule};
+
+/// Settings shared by all the endpoints in the group. Endpoints may override any of them.
+#[derive(Clone, Debug, PartialEq, Eq, DeriveEntityModel)]
+#[sea_orm(table_name = "endpointgroup")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub id: EndpointGroupId,
+    pub created_at: DateTimeWithTimeZone,
+    pub updated_at: DateTimeWithTimeZone,
+    pub app_id: ApplicationId,
+    pub name: String,
+    pub default_headers: Option<EndpointHeaders>,
+    pub retry_schedule_override: Option<RetrySchedule>,
+    pub rate_limit: Option<i32>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(
+        belongs_to = "super::application::Entity",
+        from = "Column::AppId",
+        to = "super::application::Column::Id",
+        on_update = "NoAction",
+        on_delete = "Cascade"
+    )]
+    Application,
+    #[sea_orm(has_many = "super::endpoint::Entity")]
+    Endpoint,
+}
+
+impl Related<super::application::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Application.def()
+    }
+}
+
+impl Related<super::endpoint::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Endpoint.def()
+    }
+}
+
+#[axum::async_trait]
+impl ActiveModelBehavior for ActiveModel {
+    async fn before_save<C>(mut self, _db: &C, _insert: bool) -> Result<Self, DbErr>
+    where
+        C: ConnectionTrait,
+    {
+        self.updated_at = Set(Utc::now().into());
+        Ok(self)
+    }
+}
+
+impl ActiveModel {
+    pub fn new(app_id: ApplicationId) -> Self {
+        let timestamp = Utc::now();
+        Self {
+            id: Set(EndpointGroupId::new(timestamp.into(), None)),
+            app_id: Set(app_id),
+            created_at: Set(timestamp.into()),
+            updated_at: Set(timestamp.into()),
+            ..ActiveModelTrait::default()
+        }
+    }
+}
+
+impl Entity {
+    pub fn secure_find(app_id: ApplicationId) -> Select<Entity> {
+        Self::find().filter(Column::AppId.eq(app_id))
+    }
+
+    pub fn secure_find_by_id(app_id: ApplicationId, id: EndpointGroupId) -> Select<Entity> {
+        Self::secure_find(app_id).filter(Column::Id.eq(id))
+    }
+}
@@ -5,8 +5,9 @@ use chrono::Utc;
 use sea_orm::{entity::prelude::*, ActiveValue::Set, Condition};
 
 use crate::core::types::{
-    ApplicationId, BaseId, EventChannelSet, EventTypeName, MessageId, MessageIdOrUid, MessageUid,
-    OrganizationId,
+    metadata::Metadata, ApplicationId, BaseId, EventChannelSet, EventTypeName,
+    MessageCorrelationId, MessageId, MessageIdOrUid, MessageSignedMetadata, MessageTagSet,
+    MessageUid, OrganizationId, PayloadVersion,
 };
 
 #[derive(Clone, Debug, PartialEq, Eq, DeriveEntityModel)]
@@ -22,7 +23,22 @@ pub struct Model {
     #[sea_orm(column_type = "JsonBinary", column_name = "payload", nullable)]
     pub legacy_payload: Option<Json>,
     pub channels: Option<EventChannelSet>,
+    pub tags: Option<MessageTagSet>,
     pub expiration: DateTimeWithTimeZone,
+    /// How many responses to webhooks led to this message being sent. Zero unless it was created
+    /// by an endpoint's `response_webhook_event_type`.
+    pub chain_depth: i16,
+    /// The message isn't delivered (or retried) after this time
+    pub deadline: Option<DateTimeWithTimeZone>,
+    /// Covered by the signature of the message along with its payload
+    pub signed_metadata: Option<MessageSignedMetadata>,
+    /// The version of the schema the payload follows
+    pub payload_version: Option<PayloadVersion>,
+    /// Sent with every attempt to deliver the message, so that they can be tied together. Unset
+    /// for messages created before it was introduced.
+    pub correlation_id: Option<MessageCorrelationId>,
+    /// Only endpoints whose metadata matches it are sent the message
+    pub endpoint_metadata_selector: Option<Metadata>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
@@ -39,6 +55,8 @@ pub enum Relation {
     Messagedestination,
     #[sea_orm(has_one = "super::messagecontent::Entity")]
     Messagecontent,
+    #[sea_orm(has_many = "super::messagetag::Entity")]
+    Messagetag,
 }
 
 impl Related<super::application::Entity> for Entity {
@@ -59,11 +77,18 @@ impl Related<super::messagecontent::Entity> for Entity {
     }
 }
 
+impl Related<super::messagetag::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Messagetag.def()
+    }
+}
+
 impl ActiveModelBehavior for ActiveModel {
     fn new() -> Self {
         let timestamp = Utc::now();
         Self {
             id: Set(MessageId::new(timestamp.into(), None)),
+            correlation_id: Set(Some(MessageCorrelationId::new(timestamp.into(), None))),
             created_at: Set(timestamp.into()),
             ..ActiveModelTrait::default()
         }
@@ -25,6 +25,9 @@ pub struct Model {
     pub response: String,
     pub ended_at: Option<DateTimeWithTimeZone>,
     pub trigger_type: MessageAttemptTriggerType,
+    #[sea_orm(column_type = "JsonBinary", nullable)]
+    pub request_headers_sent: Option<Json>,
+    pub endpoint_ip: Option<String>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
@@ -1,10 +1,16 @@
 // SPDX-FileCopyrightText: © 2022 Svix Authors
 // SPDX-License-Identifier: MIT
 
+use std::io::{Read as _, Write as _};
+
 use chrono::Utc;
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
 use sea_orm::{entity::prelude::*, ActiveValue::Set};
 
-use crate::core::types::MessageId;
+use crate::{
+    core::types::MessageId,
+    error::{Error, Result},
+};
 
 #[derive(Clone, Debug, PartialEq, Eq, DeriveEntityModel)]
 #[sea_orm(table_name = "messagecontent")]
@@ -12,8 +18,25 @@ pub struct Model {
     #[sea_orm(primary_key, auto_increment = false)]
     pub id: MessageId,
     pub created_at: DateTimeWithTimeZone,
-    pub payload: Vec<u8>,
+    /// Unset when the payload is stored in `payload_compressed` instead
+    pub payload: Option<Vec<u8>>,
     pub expiration: DateTimeWithTimeZone,
+    /// The gzip-compressed payload, for payloads over `compress_payload_threshold_bytes`
+    pub payload_compressed: Option<Vec<u8>>,
+}
+
+impl Model {
+    /// The payload, decompressed if it was stored compressed. `None` if neither column is set.
+    /// Errors if the compressed payload can't be decompressed.
+    pub fn into_payload(self) -> Result<Option<Vec<u8>>> {
+        match (self.payload, self.payload_compressed) {
+            (Some(payload), _) => Ok(Some(payload)),
+            (None, Some(compressed)) => decompress_payload(&compressed).map(Some).map_err(|e| {
+                Error::generic(format!("Failed to decompress payload of {}: {e}", self.id))
+            }),
+            (None, None) => Ok(None),
+        }
+    }
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
@@ -35,19 +58,110 @@ impl Related<super::message::Entity> for Entity {
 impl ActiveModelBehavior for ActiveModel {}
 
 impl ActiveModel {
-    pub fn new(msg_id: MessageId, payload: Vec<u8>) -> Self {
+    /// Payloads longer than `compress_threshold` bytes are stored gzip-compressed.
+    pub fn new(
+        msg_id: MessageId,
+        payload: Vec<u8>,
+        compress_threshold: Option<usize>,
+    ) -> Result<Self> {
+        let (payload, payload_compressed) = match compress_threshold {
+            Some(threshold) if payload.len() > threshold => {
+                let compressed = compress_payload(&payload)
+                    .map_err(|e| Error::generic(format!("Failed to compress payload: {e}")))?;
+                (None, Some(compressed))
+            }
+            _ => (Some(payload), None),
+        };
+
         let timestamp = Utc::now();
-        Self {
+        Ok(Self {
             id: Set(msg_id),
             created_at: Set(timestamp.into()),
             payload: Set(payload),
+            payload_compressed: Set(payload_compressed),
             ..ActiveModelTrait::default()
-        }
+        })
     }
 }
 
+fn compress_payload(payload: &[u8]) -> std::io::Result<Vec<u8>> {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(payload)?;
+    encoder.finish()
+}
+
+fn decompress_payload(compressed: &[u8]) -> std::io::Result<Vec<u8>> {
+    let mut payload = Vec::new();
+    GzDecoder::new(compressed).read_to_end(&mut payload)?;
+    Ok(payload)
+}
+
 impl Entity {
     pub fn secure_find_by_id_in(ids: Vec<MessageId>) -> Select<Entity> {
         Self::find().filter(Column::Id.is_in(ids))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use sea_orm::ActiveValue::Set;
+
+    use super::{compress_payload, ActiveModel, Model};
+    use crate::core::types::{BaseId, MessageId};
+
+    fn into_model(content: ActiveModel) -> Model {
+        let (Set(payload), Set(payload_compressed)) = (content.payload, content.payload_compressed)
+        else {
+            panic!("payload columns not set");
+        };
+        let now = chrono::Utc::now().into();
+        Model {
+            id: MessageId::new(None, None),
+            created_at: now,
+            payload,
+            expiration: now,
+            payload_compressed,
+        }
+    }
+
+    #[test]
+    fn test_payload_compression() {
+        let payload = br#"{"test": "value"}"#.to_vec();
+
+        for threshold in [None, Some(payload.len())] {
+            let content =
+                ActiveModel::new(MessageId::new(None, None), payload.clone(), threshold).unwrap();
+            let content = into_model(content);
+            assert_eq!(content.payload.as_ref(), Some(&payload));
+            assert_eq!(content.payload_compressed, None);
+            assert_eq!(content.into_payload().unwrap(), Some(payload.clone()));
+        }
+
+        let content =
+            ActiveModel::new(MessageId::new(None, None), payload.clone(), Some(1)).unwrap();
+        let content = into_model(content);
+        assert_eq!(content.payload, None);
+        assert_eq!(
+            content.payload_compressed.as_ref(),
+            Some(&compress_payload(&payload).unwrap())
+        );
+        assert_eq!(content.into_payload().unwrap(), Some(payload));
+    }
+
+    #[test]
+    fn test_payload_missing() {
+        let content = ActiveModel::new(MessageId::new(None, None), b"{}".to_vec(), None).unwrap();
+        let mut content = into_model(content);
+        content.payload = None;
+        assert_eq!(content.into_payload().unwrap(), None);
+    }
+
+    #[test]
+    fn test_payload_corrupt() {
+        let content =
+            ActiveModel::new(MessageId::new(None, None), b"{}".to_vec(), Some(1)).unwrap();
+        let mut content = into_model(content);
+        content.payload_compressed = Some(b"not gzip".to_vec());
+        assert!(content.into_payload().is_err());
+    }
+}
@@ -17,6 +17,11 @@ pub struct Model {
     pub endp_id: EndpointId,
     pub status: MessageStatus,
     pub next_attempt: Option<DateTimeWithTimeZone>,
+    /// When the first HTTP call to the endpoint was made, which may be well after `created_at` if
+    /// the message spent time queued
+    pub first_attempt_at: Option<DateTimeWithTimeZone>,
+    /// When the latest attempt, successful or not, was made
+    pub last_attempt_at: Option<DateTimeWithTimeZone>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
@@ -0,0 +1,48 @@
+// SPDX-FileCopyrightText: © 2022 Svix Authors
+// SPDX-License-Identifier: MIT
+
+use chrono::Utc;
+use sea_orm::{entity::prelude::*, ActiveValue::Set};
+
+use crate::core::types::{MessageId, MessageTag};
+
+#[derive(Clone, Debug, PartialEq, Eq, DeriveEntityModel)]
+#[sea_orm(table_name = "messagetag")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub msg_id: MessageId,
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub tag: MessageTag,
+    pub created_at: DateTimeWithTimeZone,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(
+        belongs_to = "super::message::Entity",
+        from = "Column::MsgId",
+        to = "super::message::Column::Id",
+        on_update = "NoAction",
+        on_delete = "Cascade"
+    )]
+    Message,
+}
+
+impl Related<super::message::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Message.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
+
+impl ActiveModel {
+    pub fn new(msg_id: MessageId, tag: MessageTag) -> Self {
+        let timestamp = Utc::now();
+        Self {
+            msg_id: Set(msg_id),
+            tag: Set(tag),
+            created_at: Set(timestamp.into()),
+        }
+    }
+}
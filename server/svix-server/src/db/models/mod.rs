@@ -3,10 +3,15 @@
 
 pub mod application;
 pub mod applicationmetadata;
+pub mod applicationwebhooksecret;
 pub mod endpoint;
+pub mod endpointgroup;
 pub mod endpointmetadata;
 pub mod eventtype;
 pub mod message;
 pub mod messageattempt;
 pub mod messagecontent;
 pub mod messagedestination;
+pub mod messagetag;
+pub mod organizationquota;
+pub mod organizationratelimit;
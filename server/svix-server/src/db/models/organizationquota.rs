@@ -0,0 +1,62 @@
+// SPDX-FileCopyrightText: © 2022 Svix Authors
+// SPDX-License-Identifier: MIT
+
+use chrono::Utc;
+use sea_orm::{entity::prelude::*, ActiveValue::Set};
+
+use crate::core::types::OrganizationId;
+
+/// The number of messages an organization may send each month. Organizations without one have no
+/// limit.
+///
+/// Counts are `bigint`s in the database, as Postgres has no unsigned integer types.
+#[derive(Clone, Debug, PartialEq, Eq, DeriveEntityModel)]
+#[sea_orm(table_name = "organizationquota")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub org_id: OrganizationId,
+    pub created_at: DateTimeWithTimeZone,
+    pub updated_at: DateTimeWithTimeZone,
+    pub messages_per_month: i64,
+    pub messages_this_month: i64,
+    /// When `messages_this_month` next goes back to zero
+    pub reset_at: DateTimeWithTimeZone,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+#[axum::async_trait]
+impl ActiveModelBehavior for ActiveModel {
+    async fn before_save<C>(mut self, _db: &C, _insert: bool) -> Result<Self, DbErr>
+    where
+        C: ConnectionTrait,
+    {
+        self.updated_at = Set(Utc::now().into());
+        Ok(self)
+    }
+}
+
+impl ActiveModel {
+    pub fn new(
+        org_id: OrganizationId,
+        messages_per_month: i64,
+        reset_at: DateTimeWithTimeZone,
+    ) -> Self {
+        let timestamp = Utc::now();
+        Self {
+            org_id: Set(org_id),
+            created_at: Set(timestamp.into()),
+            updated_at: Set(timestamp.into()),
+            messages_per_month: Set(messages_per_month),
+            messages_this_month: Set(0),
+            reset_at: Set(reset_at),
+        }
+    }
+}
+
+impl Entity {
+    pub fn secure_find(org_id: OrganizationId) -> Select<Entity> {
+        Self::find().filter(Column::OrgId.eq(org_id))
+    }
+}
@@ -0,0 +1,55 @@
+// SPDX-FileCopyrightText: © 2022 Svix Authors
+// SPDX-License-Identifier: MIT
+
+use chrono::Utc;
+use sea_orm::{entity::prelude::*, ActiveValue::Set};
+
+use crate::core::types::OrganizationId;
+
+/// How quickly each of an organization's applications may have webhooks dispatched, as a token
+/// bucket. Organizations without one have no limit.
+#[derive(Clone, Debug, PartialEq, Eq, DeriveEntityModel)]
+#[sea_orm(table_name = "organizationratelimit")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub org_id: OrganizationId,
+    pub created_at: DateTimeWithTimeZone,
+    pub updated_at: DateTimeWithTimeZone,
+    /// The most webhooks an application can dispatch in a burst
+    pub capacity: i32,
+    /// How many more webhooks an application can dispatch for every second that passes
+    pub refill_per_second: i32,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+#[axum::async_trait]
+impl ActiveModelBehavior for ActiveModel {
+    async fn before_save<C>(mut self, _db: &C, _insert: bool) -> Result<Self, DbErr>
+    where
+        C: ConnectionTrait,
+    {
+        self.updated_at = Set(Utc::now().into());
+        Ok(self)
+    }
+}
+
+impl ActiveModel {
+    pub fn new(org_id: OrganizationId, capacity: i32, refill_per_second: i32) -> Self {
+        let timestamp = Utc::now();
+        Self {
+            org_id: Set(org_id),
+            created_at: Set(timestamp.into()),
+            updated_at: Set(timestamp.into()),
+            capacity: Set(capacity),
+            refill_per_second: Set(refill_per_second),
+        }
+    }
+}
+
+impl Entity {
+    pub fn secure_find(org_id: OrganizationId) -> Select<Entity> {
+        Self::find().filter(Column::OrgId.eq(org_id))
+    }
+}
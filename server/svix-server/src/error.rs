@@ -1,7 +1,7 @@
 // SPDX-FileCopyrightText: © 2022 Svix Authors
 // SPDX-License-Identifier: MIT
 
-use std::{error, fmt, panic::Location};
+use std::{error, fmt, panic::Location, time::Duration};
 
 use aide::OperationOutput;
 use axum::{
@@ -9,6 +9,7 @@ use axum::{
     response::{IntoResponse, Response},
     Json,
 };
+use http::{header, HeaderValue};
 use hyper::StatusCode;
 use schemars::JsonSchema;
 use sea_orm::{DbErr, RuntimeErr, TransactionError};
@@ -179,6 +180,13 @@ impl<E: error::Error + 'static> From<bb8::RunError<E>> for Error {
     }
 }
 
+impl From<crate::redis::PoolError> for Error {
+    #[track_caller]
+    fn from(value: crate::redis::PoolError) -> Self {
+        Error::queue(value)
+    }
+}
+
 impl From<ExtensionRejection> for Error {
     #[track_caller]
     fn from(value: ExtensionRejection) -> Self {
@@ -307,6 +315,8 @@ pub struct ValidationErrorItem {
 pub struct HttpError {
     pub status: StatusCode,
     body: HttpErrorBody,
+    /// Sent as the `Retry-After` header, rounded up to whole seconds
+    retry_after: Option<Duration>,
 }
 
 impl HttpError {
@@ -314,9 +324,16 @@ impl HttpError {
         Self {
             status,
             body: HttpErrorBody::Standard(StandardHttpError { code, detail }),
+            retry_after: None,
         }
     }
 
+    /// Tells the client how long to wait before trying again
+    pub fn with_retry_after(mut self, retry_after: Duration) -> Self {
+        self.retry_after = Some(retry_after);
+        self
+    }
+
     pub fn bad_request(code: Option<String>, detail: Option<String>) -> Self {
         Self::new_standard(
             StatusCode::BAD_REQUEST,
@@ -357,10 +374,19 @@ impl HttpError {
         )
     }
 
+    pub fn too_many_requests(code: Option<String>, detail: Option<String>) -> Self {
+        Self::new_standard(
+            StatusCode::TOO_MANY_REQUESTS,
+            code.unwrap_or_else(|| "rate_limited".to_owned()),
+            detail.unwrap_or_else(|| "Too many requests.".to_owned()),
+        )
+    }
+
     pub fn unprocessable_entity(detail: Vec<ValidationErrorItem>) -> Self {
         Self {
             status: StatusCode::UNPROCESSABLE_ENTITY,
             body: HttpErrorBody::Validation(ValidationHttpError { detail }),
+            retry_after: None,
         }
     }
 
@@ -411,7 +437,13 @@ impl fmt::Display for HttpError {
 
 impl IntoResponse for HttpError {
     fn into_response(self) -> Response {
-        (self.status, Json(self.body)).into_response()
+        let mut resp = (self.status, Json(self.body)).into_response();
+        if let Some(retry_after) = self.retry_after {
+            let secs = retry_after.as_secs() + u64::from(retry_after.subsec_nanos() > 0);
+            resp.headers_mut()
+                .insert(header::RETRY_AFTER, HeaderValue::from(secs));
+        }
+        resp
     }
 }
 
@@ -16,9 +16,10 @@ use cfg::ConfigurationInner;
 use opentelemetry_otlp::WithExportConfig;
 use opentelemetry_sdk::runtime::Tokio;
 use queue::TaskQueueProducer;
-use redis::RedisManager;
+use redis::{RedisManager, RedisTlsConfig};
 use sea_orm::DatabaseConnection;
 use sentry::integrations::tracing::EventFilter;
+use tokio::sync::broadcast;
 use tower::layer::layer_fn;
 use tower_http::{
     cors::{AllowHeaders, Any, CorsLayer},
@@ -32,11 +33,17 @@ use crate::{
         cache,
         cache::Cache,
         idempotency::IdempotencyService,
-        operational_webhooks::{OperationalWebhookSender, OperationalWebhookSenderInner},
+        operational_webhooks::{
+            OperationalWebhookMode, OperationalWebhookSender, OperationalWebhookSenderInner,
+        },
+        org_quota::quota_reset_loop,
+        webhook_http_client::WebhookClient,
     },
-    db::init_db,
+    db::init_db_pool,
     expired_message_cleaner::expired_message_cleaner_loop,
-    worker::queue_handler,
+    message_retention::message_retention_loop,
+    stuck_deliveries::stuck_delivery_loop,
+    worker::{new_webhook_client, queue_handler},
 };
 
 pub mod cfg;
@@ -44,9 +51,11 @@ pub mod core;
 pub mod db;
 pub mod error;
 pub mod expired_message_cleaner;
+pub mod message_retention;
 pub mod openapi;
 pub mod queue;
 pub mod redis;
+pub mod stuck_deliveries;
 pub mod v1;
 pub mod worker;
 
@@ -54,7 +63,9 @@ const CRATE_NAME: &str = env!("CARGO_CRATE_NAME");
 
 pub static SHUTTING_DOWN: AtomicBool = AtomicBool::new(false);
 
-async fn graceful_shutdown_handler() {
+/// Waits for a shutdown signal, then flags that the server is shutting down and tells the worker
+/// to stop through `shutdown_tx`.
+async fn graceful_shutdown_handler(shutdown_tx: broadcast::Sender<()>) {
     let ctrl_c = async {
         tokio::signal::ctrl_c()
             .await
@@ -78,7 +89,9 @@ async fn graceful_shutdown_handler() {
     }
 
     tracing::info!("Received shutdown signal. Shutting down gracefully...");
-    SHUTTING_DOWN.store(true, Ordering::SeqCst)
+    SHUTTING_DOWN.store(true, Ordering::SeqCst);
+    // Nobody listening just means the worker is off
+    let _ = shutdown_tx.send(());
 }
 
 #[tracing::instrument(name = "app_start", level = "trace", skip_all)]
@@ -93,6 +106,7 @@ pub struct AppState {
     cfg: Configuration,
     cache: Cache,
     op_webhooks: OperationalWebhookSender,
+    webhook_client: WebhookClient,
 }
 
 // Made public for the purpose of E2E testing in which a queue prefix is necessary to avoid tests
@@ -103,7 +117,7 @@ pub async fn run_with_prefix(
     listener: Option<TcpListener>,
 ) {
     tracing::debug!("DB: Initializing pool");
-    let pool = init_db(&cfg).await;
+    let pool = init_db_pool(&cfg).await;
     tracing::debug!("DB: Started");
 
     tracing::debug!("Cache: Initializing {:?}", cfg.cache_type);
@@ -112,7 +126,11 @@ pub async fn run_with_prefix(
         CacheBackend::None => cache::none::new(),
         CacheBackend::Memory => cache::memory::new(),
         CacheBackend::Redis(_) | CacheBackend::RedisCluster(_) => {
-            let mgr = RedisManager::from_cache_backend(&cache_backend).await;
+            let mgr = RedisManager::from_cache_backend(
+                &cache_backend,
+                &RedisTlsConfig::from_configuration(&cfg),
+            )
+            .await;
             cache::redis::new(mgr)
         }
     };
@@ -125,6 +143,7 @@ pub async fn run_with_prefix(
     let op_webhook_sender = OperationalWebhookSenderInner::new(
         cfg.jwt_signing_config.clone(),
         cfg.operational_webhook_address.clone(),
+        OperationalWebhookMode::from_batch_interval_ms(cfg.op_webhooks_batch_interval_ms),
     );
 
     // OpenAPI/aide must be initialized before any routers are constructed
@@ -135,11 +154,12 @@ pub async fn run_with_prefix(
     let svc_cache = cache.clone();
     // build our application with a route
     let app_state = AppState {
-        db: pool.clone(),
+        db: pool.write().clone(),
         queue_tx: queue_tx.clone(),
         cfg: cfg.clone(),
         cache: cache.clone(),
         op_webhooks: op_webhook_sender.clone(),
+        webhook_client: new_webhook_client(&cfg),
     };
     let v1_router = v1::router().with_state::<()>(app_state);
 
@@ -170,8 +190,17 @@ pub async fn run_with_prefix(
     let with_api = cfg.api_enabled;
     let with_worker = cfg.worker_enabled;
     let listen_address = cfg.listen_address;
-
-    let (server, worker_loop, expired_message_cleaner_loop) = tokio::join!(
+    let (shutdown_tx, shutdown_rx) = broadcast::channel(1);
+    let stuck_delivery_queue_tx = queue_tx.clone();
+
+    let (
+        server,
+        worker_loop,
+        expired_message_cleaner_loop,
+        quota_reset_loop,
+        message_retention_loop,
+        stuck_delivery_loop,
+    ) = tokio::join!(
         async {
             if with_api {
                 if let Some(l) = listener {
@@ -179,18 +208,18 @@ pub async fn run_with_prefix(
                     axum::Server::from_tcp(l)
                         .expect("Error starting http server")
                         .serve(svc)
-                        .with_graceful_shutdown(graceful_shutdown_handler())
+                        .with_graceful_shutdown(graceful_shutdown_handler(shutdown_tx.clone()))
                         .await
                 } else {
                     tracing::debug!("API: Listening on {}", listen_address);
                     axum::Server::bind(&listen_address)
                         .serve(svc)
-                        .with_graceful_shutdown(graceful_shutdown_handler())
+                        .with_graceful_shutdown(graceful_shutdown_handler(shutdown_tx.clone()))
                         .await
                 }
             } else {
                 tracing::debug!("API: off");
-                graceful_shutdown_handler().await;
+                graceful_shutdown_handler(shutdown_tx.clone()).await;
                 Ok(())
             }
         },
@@ -203,7 +232,8 @@ pub async fn run_with_prefix(
                     pool.clone(),
                     queue_tx,
                     queue_rx,
-                    op_webhook_sender,
+                    op_webhook_sender.clone(),
+                    shutdown_rx,
                 )
                 .await
             } else {
@@ -214,17 +244,51 @@ pub async fn run_with_prefix(
         async {
             if with_worker {
                 tracing::debug!("Expired message cleaner: Started");
-                expired_message_cleaner_loop(&pool).await
+                expired_message_cleaner_loop(pool.write()).await
             } else {
                 tracing::debug!("Expired message cleaner: off");
                 Ok(())
             }
+        },
+        async {
+            if with_worker {
+                tracing::debug!("Organization quota reset: Started");
+                quota_reset_loop(pool.write()).await
+            } else {
+                tracing::debug!("Organization quota reset: off");
+                Ok(())
+            }
+        },
+        async {
+            if with_worker {
+                tracing::debug!("Message retention: Started");
+                message_retention_loop(pool.write(), cfg.message_retention_days).await
+            } else {
+                tracing::debug!("Message retention: off");
+                Ok(())
+            }
+        },
+        async {
+            if with_worker {
+                tracing::debug!("Stuck delivery detector: Started");
+                let max_retry_delay = cfg.retry_schedule.iter().max().copied().unwrap_or_default();
+                stuck_delivery_loop(pool.write(), &stuck_delivery_queue_tx, max_retry_delay).await
+            } else {
+                tracing::debug!("Stuck delivery detector: off");
+                Ok(())
+            }
         }
     );
 
+    // Nothing sends operational webhooks anymore, so the batches left can go out
+    op_webhook_sender.flush().await;
+
     server.expect("Error initializing server");
     worker_loop.expect("Error initializing worker");
-    expired_message_cleaner_loop.expect("Error initializing expired message cleaner")
+    expired_message_cleaner_loop.expect("Error initializing expired message cleaner");
+    quota_reset_loop.expect("Error initializing organization quota reset");
+    message_retention_loop.expect("Error initializing message retention");
+    stuck_delivery_loop.expect("Error initializing stuck delivery detector")
 }
 
 pub fn setup_tracing(
@@ -0,0 +1,131 @@
+// SPDX-FileCopyrightText: © 2022 Svix Authors
+// SPDX-License-Identifier: MIT
+
+//! Deletes messages once they're older than `message_retention_days`, so that the message tables
+//! don't grow forever.
+//!
+//! Messages are deleted in small batches, each in its own short transaction, along with their
+//! attempts, destinations and content. Tags are deleted by the database, through their foreign key.
+
+use std::{
+    sync::atomic::Ordering,
+    time::{Duration, Instant},
+};
+
+use chrono::{DateTime, Utc};
+use sea_orm::{
+    ColumnTrait, ConnectionTrait, DatabaseConnection, DbErr, EntityTrait, QueryFilter, Statement,
+    TransactionTrait,
+};
+
+use crate::{
+    core::types::{BaseId, MessageId},
+    db::models::{message, messageattempt, messagecontent, messagedestination},
+    error::Result,
+};
+
+/// Deletes up to `limit` messages created before `before`, along with everything belonging to
+/// them. Returns how many messages were deleted.
+pub async fn delete_messages_before(
+    pool: &DatabaseConnection,
+    before: DateTime<Utc>,
+    limit: u32,
+) -> Result<u64> {
+    let tx = pool.begin().await?;
+
+    // Message IDs are ordered by creation time, so this only needs the primary key
+    let ids = tx
+        .query_all(Statement::from_sql_and_values(
+            pool.get_database_backend(),
+            r#"
+        SELECT id FROM message
+        WHERE id < $1
+        ORDER BY id
+        LIMIT $2
+        FOR UPDATE SKIP LOCKED
+    "#,
+            [MessageId::start_id(before).0.into(), limit.into()],
+        ))
+        .await?
+        .iter()
+        .map(|row| row.try_get_by_index(0).map(MessageId))
+        .collect::<std::result::Result<Vec<_>, _>>()
+        .map_err(DbErr::from)?;
+    if ids.is_empty() {
+        return Ok(0);
+    }
+
+    // Attempts would otherwise be deleted through their destinations' foreign key, which isn't
+    // indexed
+    messageattempt::Entity::delete_many()
+        .filter(messageattempt::Column::MsgId.is_in(ids.clone()))
+        .exec(&tx)
+        .await?;
+    messagedestination::Entity::delete_many()
+        .filter(messagedestination::Column::MsgId.is_in(ids.clone()))
+        .exec(&tx)
+        .await?;
+    messagecontent::Entity::delete_many()
+        .filter(messagecontent::Column::Id.is_in(ids.clone()))
+        .exec(&tx)
+        .await?;
+    let res = message::Entity::delete_many()
+        .filter(message::Column::Id.is_in(ids))
+        .exec(&tx)
+        .await?;
+
+    tx.commit().await?;
+    Ok(res.rows_affected)
+}
+
+/// Deletes messages older than `retention_days` until the server shuts down, in batches.
+pub async fn message_retention_loop(pool: &DatabaseConnection, retention_days: u32) -> Result<()> {
+    // When fewer messages than the batch size have been deleted, take a nap for this long
+    const IDLE: Duration = Duration::from_secs(60 * 60);
+    const ON_ERROR: Duration = Duration::from_secs(10);
+    const BATCH_SIZE: u32 = 1_000;
+
+    if retention_days == 0 {
+        tracing::info!("Message retention is disabled, messages are kept forever");
+        return Ok(());
+    }
+
+    let mut sleep_time = None;
+    loop {
+        if let Some(duration) = sleep_time {
+            // Checking for the shutdown signal every so often, rather than sleeping for the whole
+            // duration, so that shutting down isn't held up
+            let sleep_start = Instant::now();
+            let mut interval = tokio::time::interval(Duration::from_secs(10));
+            while sleep_start.elapsed() < duration {
+                if crate::SHUTTING_DOWN.load(Ordering::SeqCst) {
+                    return Ok(());
+                }
+                interval.tick().await;
+            }
+        }
+
+        let start = Instant::now();
+        let before = Utc::now() - chrono::Duration::days(retention_days.into());
+        match delete_messages_before(pool, before, BATCH_SIZE).await {
+            Err(err) => {
+                tracing::error!("Failed to delete old messages: {err}");
+                sleep_time = Some(ON_ERROR);
+            }
+            Ok(deleted) => {
+                if deleted > 0 {
+                    tracing::debug!(elapsed =? start.elapsed(), "deleted {deleted} old messages");
+                }
+
+                // When we see full batches, don't sleep at all
+                sleep_time = (deleted < BATCH_SIZE.into()).then_some(IDLE);
+            }
+        }
+
+        if crate::SHUTTING_DOWN.load(Ordering::SeqCst) {
+            break;
+        }
+    }
+
+    Ok(())
+}
@@ -23,7 +23,7 @@ pub fn initialize_openapi() -> OpenApi {
         },
         {
             "name": "Application specific",
-            "tags": ["Authentication", "Endpoint", "Message", "Message Attempt", "Integration"]
+            "tags": ["Authentication", "Endpoint", "Endpoint Group", "Message", "Message Attempt", "Integration"]
         },
         {
             "name": "Utility",
@@ -70,6 +70,10 @@ pub fn initialize_openapi() -> OpenApi {
                 name: "Endpoint".to_owned(),
                 ..openapi::Tag::default()
             },
+            openapi::Tag {
+                name: "Endpoint Group".to_owned(),
+                ..openapi::Tag::default()
+            },
             openapi::Tag {
                 name: "Integration".to_owned(),
                 ..openapi::Tag::default()
@@ -264,7 +268,8 @@ mod webhooks {
     use schemars::JsonSchema;
 
     use crate::core::operational_webhooks::{
-        EndpointDisabledEventData, EndpointEvent, MessageAttemptEvent,
+        EndpointCircuitEvent, EndpointDisabledEventData, EndpointEvent,
+        EndpointFailureCountEventData, MessageAttemptEvent, MessageExpiredEventData,
     };
 
     /// Documents the webhook specified by the type `T`.
@@ -401,6 +406,20 @@ mod webhooks {
         common_: EndpointEvent,
     }
 
+    #[derive(JsonSchema)]
+    #[allow(unused)]
+    struct EndpointCircuitOpenEventData {
+        #[serde(flatten)]
+        common_: EndpointCircuitEvent,
+    }
+
+    #[derive(JsonSchema)]
+    #[allow(unused)]
+    struct EndpointCircuitClosedEventData {
+        #[serde(flatten)]
+        common_: EndpointCircuitEvent,
+    }
+
     #[derive(JsonSchema)]
     #[allow(unused)]
     struct MessageAttemptExhaustedEventData {
@@ -439,6 +458,24 @@ mod webhooks {
         "endpoint.disabled",
         "Sent when an endpoint has been automatically disabled after continuous failures."
     );
+    webhook_event!(
+        EndpointFailureCountThresholdEvent,
+        EndpointFailureCountEventData,
+        "endpoint.failure_count_threshold",
+        "Sent when an endpoint has failed to deliver a configured number of messages in a row.\nIt's a warning that the endpoint may soon be disabled."
+    );
+    webhook_event!(
+        EndpointCircuitOpenEvent,
+        EndpointCircuitOpenEventData,
+        "endpoint.circuit_open",
+        "Sent when an endpoint's circuit breaker opens after it has failed a number of deliveries in a row.\nDeliveries to it are put off until `openUntil`."
+    );
+    webhook_event!(
+        EndpointCircuitClosedEvent,
+        EndpointCircuitClosedEventData,
+        "endpoint.circuit_closed",
+        "Sent when a delivery to an endpoint whose circuit breaker was open succeeds, closing it again."
+    );
     webhook_event!(
         MessageAttemptExhaustedEvent,
         MessageAttemptExhaustedEventData,
@@ -451,17 +488,27 @@ mod webhooks {
         "message.attempt.failing",
         "Sent after a message has been failing for a few times.\nIt's sent on the fourth failure. It complements `message.attempt.exhausted` which is sent after the last failure."
     );
+    webhook_event!(
+        MessageExpiredEvent,
+        MessageExpiredEventData,
+        "message.expired",
+        "Sent when a message isn't delivered to an endpoint because its deadline has passed."
+    );
 
     /// Generates documentation for operational webhooks in the Redoc `x-webhooks`
     /// format. For more info see https://redocly.com/docs/api-reference-docs/specification-extensions/x-webhooks/
     pub(super) fn webhooks() -> HashMap<String, openapi::PathItem> {
         HashMap::from([
+            document_webhook::<EndpointCircuitClosedEvent>(),
+            document_webhook::<EndpointCircuitOpenEvent>(),
             document_webhook::<EndpointCreatedEvent>(),
             document_webhook::<EndpointDeletedEvent>(),
             document_webhook::<EndpointDisabledEvent>(),
+            document_webhook::<EndpointFailureCountThresholdEvent>(),
             document_webhook::<EndpointUpdatedEvent>(),
             document_webhook::<MessageAttemptExhaustedEvent>(),
             document_webhook::<MessageAttemptFailingEvent>(),
+            document_webhook::<MessageExpiredEvent>(),
         ])
     }
 }
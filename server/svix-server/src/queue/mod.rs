@@ -142,12 +142,22 @@ impl QueueTask {
 #[derive(Clone)]
 pub struct TaskQueueProducer {
     inner: Arc<DynScheduledProducer>,
+    /// Set for Redis queues, so that [`Self::send_many`] can add tasks to it in one go
+    main_stream: Option<redis::MainStream>,
 }
 
 impl TaskQueueProducer {
     pub fn new(inner: impl ScheduledQueueProducer + 'static) -> Self {
         Self {
             inner: Arc::new(inner.into_dyn_scheduled()),
+            main_stream: None,
+        }
+    }
+
+    fn with_main_stream(self, main_stream: redis::MainStream) -> Self {
+        Self {
+            main_stream: Some(main_stream),
+            ..self
         }
     }
 
@@ -169,6 +179,30 @@ impl TaskQueueProducer {
         )
         .await
     }
+
+    /// Sends all the tasks, each with its own delay.
+    ///
+    /// With a Redis queue, the tasks without a delay are added to the queue with a single pipeline
+    /// rather than a round trip each, which is much faster when there are many of them. Delayed
+    /// tasks, and all tasks with other queues, are sent one by one like with [`Self::send`].
+    pub async fn send_many(&self, tasks: Vec<(QueueTask, Option<Duration>)>) -> Result<()> {
+        let Some(main_stream) = &self.main_stream else {
+            for (task, delay) in tasks {
+                self.send(task, delay).await?;
+            }
+            return Ok(());
+        };
+
+        let (immediate, delayed): (Vec<_>, Vec<_>) =
+            tasks.into_iter().partition(|(_, delay)| delay.is_none());
+        let immediate: Vec<_> = immediate.into_iter().map(|(task, _)| task).collect();
+        main_stream.add_many(&immediate).await?;
+
+        for (task, delay) in delayed {
+            self.send(task, delay).await?;
+        }
+        Ok(())
+    }
 }
 
 pub struct TaskQueueConsumer {
@@ -184,9 +218,19 @@ impl TaskQueueConsumer {
 
     pub async fn receive_all(&mut self) -> Result<Vec<TaskQueueDelivery>> {
         const MAX_MESSAGES: usize = 128;
+        self.receive(MAX_MESSAGES).await
+    }
+
+    /// Like [`Self::receive_all`], but takes at most one task off the queue. Returns `None` if no
+    /// task arrived in time.
+    pub async fn receive_one(&mut self) -> Result<Option<TaskQueueDelivery>> {
+        Ok(self.receive(1).await?.pop())
+    }
+
+    async fn receive(&mut self, max_messages: usize) -> Result<Vec<TaskQueueDelivery>> {
         // FIXME(onelson): need to figure out what deadline/duration to use here
         self.inner
-            .receive_all(MAX_MESSAGES, Duration::from_secs(30))
+            .receive_all(max_messages, Duration::from_secs(30))
             .await
             .map_err(Into::into)
             .trace()?
@@ -273,3 +317,86 @@ impl TryFrom<Delivery> for TaskQueueDelivery {
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use omniqueue::backends::InMemoryBackend;
+
+    use super::{MessageTask, QueueTask, TaskQueueConsumer, TaskQueueProducer};
+    use crate::core::types::{
+        ApplicationId, BaseId, EndpointId, MessageAttemptTriggerType, MessageId,
+    };
+
+    async fn in_memory_pair() -> (TaskQueueProducer, TaskQueueConsumer) {
+        let (producer, consumer) = InMemoryBackend::builder().build_pair().await.unwrap();
+        (
+            TaskQueueProducer::new(producer),
+            TaskQueueConsumer::new(consumer),
+        )
+    }
+
+    // Time is paused, so waiting for tasks that never arrive doesn't slow the test down
+    #[tokio::test(start_paused = true)]
+    async fn test_receive_one_empty_queue() {
+        let (_producer, mut consumer) = in_memory_pair().await;
+        assert!(consumer.receive_one().await.unwrap().is_none());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_send_many() {
+        let (producer, mut consumer) = in_memory_pair().await;
+        let tasks: Vec<_> = (0..3)
+            .map(|_| {
+                MessageTask::new_task(
+                    MessageId::new(None, None),
+                    ApplicationId::new(None, None),
+                    EndpointId::new(None, None),
+                    MessageAttemptTriggerType::Scheduled,
+                )
+            })
+            .collect();
+        let task = |n: usize| tasks[n].clone();
+
+        producer
+            .send_many(vec![
+                (task(0), None),
+                (task(1), Some(Duration::from_secs(10))),
+                (task(2), None),
+            ])
+            .await
+            .unwrap();
+        producer.send_many(Vec::new()).await.unwrap();
+
+        // The tasks without a delay are received right away, in order
+        for n in [0, 2] {
+            let delivery = consumer.receive_one().await.unwrap().unwrap();
+            assert_eq!(*delivery.task, task(n));
+            delivery.ack().await.unwrap();
+        }
+
+        // And the delayed one once it's due
+        tokio::time::sleep(Duration::from_secs(10)).await;
+        let delivery = consumer.receive_one().await.unwrap().unwrap();
+        assert_eq!(*delivery.task, task(1));
+        delivery.ack().await.unwrap();
+        assert!(consumer.receive_one().await.unwrap().is_none());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_receive_one() {
+        let (producer, mut consumer) = in_memory_pair().await;
+        for _ in 0..2 {
+            producer.send(QueueTask::HealthCheck, None).await.unwrap();
+        }
+
+        // Tasks are taken off the queue one at a time
+        for _ in 0..2 {
+            let delivery = consumer.receive_one().await.unwrap().unwrap();
+            assert_eq!(*delivery.task, QueueTask::HealthCheck);
+            delivery.ack().await.unwrap();
+        }
+        assert!(consumer.receive_one().await.unwrap().is_none());
+    }
+}
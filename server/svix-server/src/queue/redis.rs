@@ -27,7 +27,11 @@
 // have generic return types. This is cleaner than the turbofish operator in my opinion.
 #![allow(clippy::let_unit_value)]
 
-use std::{num::NonZeroUsize, sync::Arc, time::Duration};
+use std::{
+    num::NonZeroUsize,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
 use omniqueue::backends::{RedisBackend, RedisConfig};
 use redis::{AsyncCommands as _, RedisResult};
@@ -35,8 +39,8 @@ use redis::{AsyncCommands as _, RedisResult};
 use super::{QueueTask, TaskQueueConsumer, TaskQueueProducer};
 use crate::{
     cfg::{Configuration, QueueType},
-    error::Result,
-    redis::{PooledConnection, RedisManager},
+    error::{Error, Result},
+    redis::{PoolError, PooledConnection, RedisManager, RedisTlsConfig},
 };
 
 /// This is the key of the main queue. As a KV store, redis places the entire stream under this key.
@@ -74,6 +78,43 @@ const GENERATE_STREAM_ID: &str = "*";
 /// used with the entire [`QueueTask`] as the value in serialized JSON
 const QUEUE_KV_KEY: &str = "data";
 
+/// A Redis queue's main stream, which [`TaskQueueProducer::send_many`] adds tasks to directly, in
+/// the same format as omniqueue
+#[derive(Clone)]
+pub(super) struct MainStream {
+    pool: RedisManager,
+    key: String,
+}
+
+impl MainStream {
+    /// Adds the tasks to the stream with pipelines of `XADD`s.
+    ///
+    /// Unlike single sends, this isn't retried, since the pipeline may have been partly applied.
+    pub(super) async fn add_many(&self, tasks: &[QueueTask]) -> Result<()> {
+        const PIPELINE_SIZE: usize = 1000;
+
+        if tasks.is_empty() {
+            return Ok(());
+        }
+
+        let start = Instant::now();
+        let mut conn = self.pool.get().await.map_err(|e| {
+            e.record(start.elapsed());
+            e
+        })?;
+        for chunk in tasks.chunks(PIPELINE_SIZE) {
+            let mut pipe = redis::pipe();
+            for task in chunk {
+                let payload = serde_json::to_string(task)
+                    .map_err(|e| Error::queue(format!("Failed to encode queue task: {e}")))?;
+                let _ = pipe.xadd(&self.key, GENERATE_STREAM_ID, &[(QUEUE_KV_KEY, payload)]);
+            }
+            let _: () = pipe.query_async(&mut conn).await?;
+        }
+        Ok(())
+    }
+}
+
 /// Generates a [`TaskQueueProducer`] and a [`TaskQueueConsumer`] backed by Redis.
 pub async fn new_pair(
     cfg: &Configuration,
@@ -93,11 +134,12 @@ pub async fn new_pair(
 /// Runs Redis queue migrations with the given delay schedule. Migrations are run on this schedule
 /// such that if an old instance of the server is online after the migrations are made, that no data
 /// will be lost assuming the old server is taken offline before the last scheduled delay.
-async fn run_migration_schedule(delays: &[Duration], pool: RedisManager) {
-    let mut conn = pool
-        .get()
-        .await
-        .expect("Error retrieving connection from Redis pool");
+async fn run_migration_schedule(delays: &[Duration], pool: RedisManager) -> Result<(), PoolError> {
+    let start = Instant::now();
+    let mut conn = pool.get().await.map_err(|e| {
+        e.record(start.elapsed());
+        e
+    })?;
 
     for delay in delays {
         // drain legacy queues:
@@ -114,6 +156,8 @@ async fn run_migration_schedule(delays: &[Duration], pool: RedisManager) {
 
         tokio::time::sleep(*delay).await;
     }
+
+    Ok(())
 }
 
 /// An inner function allowing key constants to be variable for testing purposes
@@ -133,16 +177,24 @@ async fn new_pair_inner(
     // - `queue::new_pair` if the queue type is redis and a DSN is set
     // - redis tests that only makes sense to run with the DSN set
     let dsn = cfg.redis_dsn.as_deref().unwrap();
-    let pool =
-        RedisManager::from_queue_backend(&cfg.queue_backend(), cfg.redis_pool_max_size).await;
+    let pool = RedisManager::from_queue_backend(
+        &cfg.queue_backend(),
+        cfg.redis_pool_max_size,
+        &RedisTlsConfig::from_configuration(&cfg),
+    )
+    .await;
 
     // Create the stream and consumer group for the MAIN queue should it not already exist. The
     // consumer is created automatically upon use so it does not have to be created here.
     {
-        let mut conn = pool
-            .get()
-            .await
-            .expect("Error retrieving connection from Redis pool");
+        let start = Instant::now();
+        let mut conn = match pool.get().await {
+            Ok(conn) => conn,
+            Err(e) => {
+                e.record(start.elapsed());
+                panic!("Error retrieving connection from Redis pool: {e}");
+            }
+        };
 
         let consumer_group_resp: RedisResult<()> = conn
             .xgroup_create_mkstream(&main_queue_name, WORKERS_GROUP, 0i8)
@@ -171,6 +223,11 @@ async fn new_pair_inner(
         .try_into()
         .expect("Pending duration out of bounds");
 
+    let main_stream = MainStream {
+        pool: pool.clone(),
+        key: main_queue_name.clone(),
+    };
+
     // Migrate v1 queues to v2 and v2 queues to v3 on a loop with exponential backoff.
     tokio::spawn(async move {
         let delays = [
@@ -192,7 +249,9 @@ async fn new_pair_inner(
             Duration::from_secs(60 * 60 * 24),
         ];
 
-        run_migration_schedule(&delays, pool).await;
+        if let Err(e) = run_migration_schedule(&delays, pool).await {
+            tracing::error!("Error running Redis queue migrations: {e}");
+        }
     });
 
     let config = RedisConfig {
@@ -215,7 +274,7 @@ async fn new_pair_inner(
                 .await
                 .expect("Error initializing redis-cluster queue");
 
-            let producer = TaskQueueProducer::new(producer);
+            let producer = TaskQueueProducer::new(producer).with_main_stream(main_stream);
             let consumer = TaskQueueConsumer::new(consumer);
             (producer, consumer)
         }
@@ -225,7 +284,7 @@ async fn new_pair_inner(
                 .await
                 .expect("Error initializing redis queue");
 
-            let producer = TaskQueueProducer::new(producer);
+            let producer = TaskQueueProducer::new(producer).with_main_stream(main_stream);
             let consumer = TaskQueueConsumer::new(consumer);
             (producer, consumer)
         }
@@ -356,11 +415,16 @@ pub mod tests {
         cfg::Configuration,
         core::types::{ApplicationId, EndpointId, MessageAttemptTriggerType, MessageId},
         queue::{MessageTask, QueueTask, TaskQueueConsumer, TaskQueueProducer},
-        redis::RedisManager,
+        redis::{RedisManager, RedisTlsConfig},
     };
 
     async fn get_pool(cfg: &Configuration) -> RedisManager {
-        RedisManager::from_queue_backend(&cfg.queue_backend(), cfg.redis_pool_max_size).await
+        RedisManager::from_queue_backend(
+            &cfg.queue_backend(),
+            cfg.redis_pool_max_size,
+            &RedisTlsConfig::from_configuration(&cfg),
+        )
+        .await
     }
 
     #[tokio::test]
@@ -825,4 +889,62 @@ pub mod tests {
             panic!("received more than the expected number of tasks, rest: {items:?}");
         }
     }
+
+    fn test_task(num: usize) -> QueueTask {
+        QueueTask::MessageV1(MessageTask {
+            msg_id: MessageId(format!("TestMessageID{num}")),
+            app_id: ApplicationId("TestApplicationID".to_owned()),
+            endpoint_id: EndpointId("TestEndpointID".to_owned()),
+            trigger_type: MessageAttemptTriggerType::Manual,
+            attempt_count: 0,
+        })
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_send_many() {
+        let cfg = crate::cfg::load().unwrap();
+
+        let (p, mut c) = new_pair_inner(
+            &cfg,
+            Duration::from_secs(45),
+            "",
+            "{test}_send_many",
+            "{test}_send_many_delayed",
+            "{test}_send_many_delayed_lock",
+        )
+        .await;
+        flush_stale_queue_items(p.clone(), &mut c).await;
+
+        p.send_many(
+            (0..10)
+                .map(|num| {
+                    let delay = (num == 5).then_some(Duration::from_millis(500));
+                    (test_task(num), delay)
+                })
+                .collect(),
+        )
+        .await
+        .unwrap();
+
+        // The tasks added with the pipeline are received like the ones sent one by one, in order,
+        // and the delayed one once it's due
+        let mut received = Vec::new();
+        while received.len() < 10 {
+            let recv = timeout(Duration::from_secs(5), c.receive_all())
+                .await
+                .expect("`c.receive()` has timed out")
+                .unwrap();
+            for delivery in recv {
+                received.push((*delivery.task).clone());
+                delivery.ack().await.unwrap();
+            }
+        }
+        let expected: Vec<_> = (0..10)
+            .filter(|&num| num != 5)
+            .chain([5])
+            .map(test_task)
+            .collect();
+        assert_eq!(received, expected);
+    }
 }
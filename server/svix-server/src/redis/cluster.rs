@@ -1,8 +1,11 @@
 use axum::async_trait;
 use redis::{
     cluster::{ClusterClient, ClusterClientBuilder},
-    cluster_routing::{MultipleNodeRoutingInfo, ResponsePolicy, RoutingInfo},
-    ErrorKind, FromRedisValue, IntoConnectionInfo, RedisError,
+    cluster_async::ClusterConnection,
+    cluster_routing::{
+        MultipleNodeRoutingInfo, ResponsePolicy, RoutingInfo, SingleNodeRoutingInfo,
+    },
+    ErrorKind, FromRedisValue, IntoConnectionInfo, RedisError, RedisResult, TlsCertificates,
 };
 
 /// ConnectionManager that implements `bb8::ManageConnection` and supports
@@ -15,9 +18,14 @@ pub struct RedisClusterConnectionManager {
 impl RedisClusterConnectionManager {
     pub fn new<T: IntoConnectionInfo>(
         info: T,
+        certs: Option<TlsCertificates>,
     ) -> Result<RedisClusterConnectionManager, RedisError> {
+        let mut builder = ClusterClientBuilder::new(vec![info]).retries(0);
+        if let Some(certs) = certs {
+            builder = builder.certs(certs);
+        }
         Ok(RedisClusterConnectionManager {
-            client: ClusterClientBuilder::new(vec![info]).retries(0).build()?,
+            client: builder.build()?,
         })
     }
 }
@@ -52,3 +60,93 @@ impl bb8::ManageConnection for RedisClusterConnectionManager {
         false
     }
 }
+
+/// Returns the addresses of the cluster's master nodes, as listed by `CLUSTER NODES`
+pub(super) async fn master_nodes(conn: &mut ClusterConnection) -> RedisResult<Vec<String>> {
+    let nodes = conn
+        .route_command(
+            redis::cmd("CLUSTER").arg("NODES"),
+            RoutingInfo::SingleNode(SingleNodeRoutingInfo::Random),
+        )
+        .await
+        .and_then(|v| String::from_redis_value(&v))?;
+    Ok(parse_master_nodes(&nodes))
+}
+
+/// Returns the keys matching the glob-style `pattern` on every master node of the cluster
+pub(super) async fn scan_match(
+    conn: &mut ClusterConnection,
+    pattern: &str,
+) -> RedisResult<Vec<String>> {
+    let mut keys = Vec::new();
+    for node in master_nodes(conn).await? {
+        let Some((host, port)) = node
+            .rsplit_once(':')
+            .and_then(|(host, port)| Some((host.to_owned(), port.parse().ok()?)))
+        else {
+            tracing::warn!("Skipping Redis node with invalid address {node} while scanning");
+            continue;
+        };
+
+        let mut cursor = 0;
+        loop {
+            let (next_cursor, batch): (u64, Vec<String>) = conn
+                .route_command(
+                    &super::scan_cmd(cursor, pattern),
+                    RoutingInfo::SingleNode(SingleNodeRoutingInfo::ByAddress {
+                        host: host.clone(),
+                        port,
+                    }),
+                )
+                .await
+                .and_then(|v| FromRedisValue::from_redis_value(&v))?;
+            keys.extend(batch);
+            if next_cursor == 0 {
+                break;
+            }
+            cursor = next_cursor;
+        }
+    }
+
+    Ok(keys)
+}
+
+/// Parses the output of `CLUSTER NODES`, which has a line per node in the form
+/// `<id> <ip:port@cport[,hostname]> <flags> ...`, keeping the masters that aren't failing.
+fn parse_master_nodes(nodes: &str) -> Vec<String> {
+    nodes
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let addr = fields.nth(1)?;
+            let flags: Vec<_> = fields.next()?.split(',').collect();
+            if !flags.contains(&"master") || flags.contains(&"fail") {
+                return None;
+            }
+            let addr = addr.split_once('@').map_or(addr, |(addr, _)| addr);
+            Some(addr.to_owned())
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_master_nodes;
+
+    #[test]
+    fn test_parse_master_nodes() {
+        let nodes = "\
+07c37dfeb235213a872192d90877d0cd55635b91 127.0.0.1:30004@31004,hostname4 slave e7d1eecce10fd6bb5eb35b9f99a514335d9ba9ca 0 1426238317239 4 connected
+67ed2db8d677e59ec4a4cefb06858cf2a1a89fa1 127.0.0.1:30002@31002,hostname2 master - 0 1426238316232 2 connected 5461-10922
+292f8b365bb7edb5e285caf0b7e6ddc7265d2f4f 127.0.0.1:30003@31003,hostname3 master - 0 1426238318243 3 connected 10923-16383
+e7d1eecce10fd6bb5eb35b9f99a514335d9ba9ca 127.0.0.1:30001@31001,hostname1 myself,master - 0 0 1 connected 0-5460
+824fe116063bc5fcf9f4ffd895bc17aee7731ac3 127.0.0.1:30006@31006,hostname6 master,fail - 1426238317741 1426238316232 6 disconnected
+";
+
+        assert_eq!(
+            parse_master_nodes(nodes),
+            vec!["127.0.0.1:30002", "127.0.0.1:30003", "127.0.0.1:30001"]
+        );
+        assert!(parse_master_nodes("").is_empty());
+    }
+}
@@ -1,16 +1,209 @@
 mod cluster;
 
-use std::time::Duration;
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex, Weak},
+    time::Duration,
+};
 
 use bb8::{Pool, RunError};
 use bb8_redis::RedisConnectionManager;
-use redis::{FromRedisValue, RedisError, RedisResult};
+use futures::{future::BoxFuture, FutureExt};
+use once_cell::sync::Lazy;
+use opentelemetry::metrics::{Counter, Histogram, Unit};
+use redis::{
+    ClientTlsConfig, ConnectionInfo, FromRedisValue, IntoConnectionInfo, RedisError, RedisResult,
+    TlsCertificates,
+};
 
 pub use self::cluster::RedisClusterConnectionManager;
-use crate::cfg::{CacheBackend, QueueBackend};
+use crate::cfg::{CacheBackend, Configuration, QueueBackend};
 
 pub const REDIS_CONN_TIMEOUT: Duration = Duration::from_secs(2);
 
+/// Connections left for other Redis clients (other instances, the unpooled cache connection,
+/// `redis-cli`...) when checking a pool's size against `maxclients`
+const MAX_CLIENTS_HEADROOM: u32 = 10;
+
+static POOL_EXHAUSTED_WAIT: Lazy<Histogram<f64>> = Lazy::new(|| {
+    opentelemetry::global::meter("svix.com")
+        .f64_histogram("svix.redis.pool_exhausted_wait")
+        .with_description("Time spent waiting on an exhausted Redis pool before giving up")
+        .with_unit(Unit::new("s"))
+        .init()
+});
+
+static POOL_TIMEOUTS: Lazy<Counter<u64>> = Lazy::new(|| {
+    opentelemetry::global::meter("svix.com")
+        .u64_counter("svix.redis.pool_timeouts")
+        .with_description("Number of timeouts while establishing a new Redis connection")
+        .init()
+});
+
+static RECONNECT_ATTEMPTS: Lazy<Counter<u64>> = Lazy::new(|| {
+    opentelemetry::global::meter("svix.com")
+        .u64_counter("svix.redis.reconnect_attempts")
+        .with_description(
+            "Number of times a dropped unpooled Redis cluster connection was replaced",
+        )
+        .init()
+});
+
+/// Errors returned when retrieving a connection from a [`RedisManager`]
+#[derive(Debug, thiserror::Error)]
+pub enum PoolError {
+    /// Every connection in the pool was in use for the whole timeout. This is transient and
+    /// callers should back off.
+    #[error("Redis pool exhausted")]
+    PoolExhausted,
+
+    /// Establishing a connection failed, e.g. because it was refused or authentication failed.
+    #[error("Redis connection failed: {0}")]
+    ConnectionFailed(#[from] RedisError),
+
+    /// The pool had room for a new connection, but establishing it took too long.
+    #[error("timed out getting a Redis connection")]
+    TimedOut,
+}
+
+impl From<RunError<RedisError>> for PoolError {
+    fn from(value: RunError<RedisError>) -> Self {
+        match value {
+            RunError::User(e) => Self::ConnectionFailed(e),
+            RunError::TimedOut => Self::TimedOut,
+        }
+    }
+}
+
+impl PoolError {
+    /// bb8 reports the same timeout whether all connections are busy or a new one is slow to
+    /// connect, so the pool state is needed to tell the two apart.
+    fn from_run_error(value: RunError<RedisError>, exhausted: bool) -> Self {
+        match value {
+            RunError::TimedOut if exhausted => Self::PoolExhausted,
+            e => e.into(),
+        }
+    }
+
+    /// Records the error in the relevant metric, `waited` being how long the caller waited
+    /// for a connection.
+    pub fn record(&self, waited: Duration) {
+        match self {
+            Self::PoolExhausted => POOL_EXHAUSTED_WAIT.record(waited.as_secs_f64(), &[]),
+            Self::TimedOut => POOL_TIMEOUTS.add(1, &[]),
+            Self::ConnectionFailed(e) => tracing::error!("Failed connecting to Redis: {e}"),
+        }
+    }
+
+    /// Whether retrying may succeed. Authentication failures are permanent.
+    pub fn is_transient(&self) -> bool {
+        match self {
+            Self::PoolExhausted | Self::TimedOut => true,
+            Self::ConnectionFailed(e) => e.kind() != redis::ErrorKind::AuthenticationFailed,
+        }
+    }
+}
+
+fn pool_is_exhausted(state: bb8::State, max_size: u32) -> bool {
+    state.idle_connections == 0 && state.connections >= max_size
+}
+
+/// Warns if a pool of `max_conns` connections may not fit within the server's `maxclients`, in
+/// which case connections are refused once the pool grows. This is best-effort: `CONFIG` is often
+/// disallowed, e.g. on managed Redis, in which case nothing is checked.
+async fn check_max_clients<C: redis::aio::ConnectionLike + Send>(con: &mut C, max_conns: u32) {
+    let config: RedisResult<HashMap<String, u32>> = redis::cmd("CONFIG")
+        .arg("GET")
+        .arg("maxclients")
+        .query_async(con)
+        .await;
+    match config.map(|config| config.get("maxclients").copied()) {
+        Ok(Some(max_clients)) if max_conns + MAX_CLIENTS_HEADROOM > max_clients => {
+            tracing::warn!(
+                "redis_pool_max_size ({max_conns}) is too close to the Redis server's maxclients \
+                 ({max_clients}), so Redis may refuse connections"
+            );
+        }
+        Ok(_) => {}
+        Err(e) => tracing::debug!("Couldn't check Redis maxclients: {e}"),
+    }
+}
+
+/// The certificates used when connecting to Redis over TLS, read from the files given in the
+/// configuration
+#[derive(Clone, Default)]
+pub struct RedisTlsConfig {
+    ca_cert: Option<Vec<u8>>,
+    client_cert_and_key: Option<(Vec<u8>, Vec<u8>)>,
+}
+
+impl RedisTlsConfig {
+    /// Panics if one of the files can't be read, like the rest of the Redis initialization
+    pub fn from_configuration(cfg: &Configuration) -> Self {
+        let read = |path: &std::path::Path| {
+            std::fs::read(path).unwrap_or_else(|e| {
+                panic!(
+                    "Error reading Redis TLS certificate file {}: {e}",
+                    path.display()
+                )
+            })
+        };
+
+        Self {
+            ca_cert: cfg.redis_tls_ca_cert.as_deref().map(read),
+            client_cert_and_key: cfg
+                .redis_tls_client_cert
+                .as_deref()
+                .zip(cfg.redis_tls_client_key.as_deref())
+                .map(|(cert, key)| (read(cert), read(key))),
+        }
+    }
+
+    /// The certificates to connect to `dsn` with, if it's a `rediss://` DSN and any were
+    /// configured
+    fn certificates(&self, dsn: &str) -> Option<TlsCertificates> {
+        if !dsn.starts_with("rediss://")
+            || (self.ca_cert.is_none() && self.client_cert_and_key.is_none())
+        {
+            return None;
+        }
+
+        Some(TlsCertificates {
+            client_tls: self
+                .client_cert_and_key
+                .clone()
+                .map(|(client_cert, client_key)| ClientTlsConfig {
+                    client_cert,
+                    client_key,
+                }),
+            root_cert: self.ca_cert.clone(),
+        })
+    }
+
+    fn connection_info(&self, dsn: &str) -> RedisResult<ConnectionInfo> {
+        match self.certificates(dsn) {
+            Some(certs) => Ok(redis::Client::build_with_tls(dsn, certs)?
+                .get_connection_info()
+                .clone()),
+            None => dsn.into_connection_info(),
+        }
+    }
+}
+
+/// Runs [`check_max_clients`] in the background, so startup isn't held up by it
+fn spawn_max_clients_check<M>(pool: Pool<M>, max_conns: u32)
+where
+    M: bb8::ManageConnection<Error = RedisError>,
+    M::Connection: redis::aio::ConnectionLike + Send,
+{
+    tokio::spawn(async move {
+        match pool.get().await {
+            Ok(mut con) => check_max_clients(&mut *con, max_conns).await,
+            Err(e) => tracing::debug!("Couldn't check Redis maxclients: {e}"),
+        }
+    });
+}
+
 #[derive(Clone, Debug)]
 pub enum RedisManager {
     Clustered(ClusteredRedisPool),
@@ -20,43 +213,67 @@ pub enum RedisManager {
 }
 
 impl RedisManager {
-    async fn new_pooled(dsn: &str, clustered: bool, max_conns: u16) -> Self {
+    async fn new_pooled(dsn: &str, clustered: bool, max_conns: u16, tls: &RedisTlsConfig) -> Self {
         if clustered {
-            let mgr = RedisClusterConnectionManager::new(dsn)
+            let mgr = RedisClusterConnectionManager::new(dsn, tls.certificates(dsn))
                 .expect("Error initializing redis cluster client");
             let pool = bb8::Pool::builder()
                 .max_size(max_conns.into())
                 .build(mgr)
                 .await
                 .expect("Error initializing redis cluster connection pool");
-            let pool = ClusteredRedisPool { pool };
+            spawn_max_clients_check(pool.clone(), max_conns.into());
+            let pool = Arc::new(pool);
+            register_pool_stats_gauges(Arc::downgrade(&pool));
+            let pool = ClusteredRedisPool {
+                pool,
+                max_size: max_conns.into(),
+            };
             RedisManager::Clustered(pool)
         } else {
-            let mgr = RedisConnectionManager::new(dsn).expect("Error initializing redis client");
+            let mgr = tls
+                .connection_info(dsn)
+                .and_then(RedisConnectionManager::new)
+                .expect("Error initializing redis client");
             let pool = bb8::Pool::builder()
                 .max_size(max_conns.into())
                 .build(mgr)
                 .await
                 .expect("Error initializing redis connection pool");
-            let pool = NonClusteredRedisPool { pool };
+            spawn_max_clients_check(pool.clone(), max_conns.into());
+            let pool = NonClusteredRedisPool {
+                pool,
+                max_size: max_conns.into(),
+            };
             RedisManager::NonClustered(pool)
         }
     }
 
-    async fn new_unpooled(dsn: &str, clustered: bool) -> Self {
+    async fn new_unpooled(dsn: &str, clustered: bool, tls: &RedisTlsConfig) -> Self {
         if clustered {
-            let cli = redis::cluster::ClusterClient::builder(vec![dsn])
+            let mut builder = redis::cluster::ClusterClient::builder(vec![dsn])
                 .retries(1)
-                .connection_timeout(REDIS_CONN_TIMEOUT)
+                .connection_timeout(REDIS_CONN_TIMEOUT);
+            if let Some(certs) = tls.certificates(dsn) {
+                builder = builder.certs(certs);
+            }
+            let cli = builder
                 .build()
                 .expect("Error initializing redis-unpooled cluster client");
             let con = cli
                 .get_async_connection()
                 .await
                 .expect("Failed to get redis-cluster-unpooled connection");
+            let con = ReconnectingConnection::new(con, move || {
+                let cli = cli.clone();
+                async move { cli.get_async_connection().await }.boxed()
+            });
             RedisManager::ClusteredUnpooled(ClusteredRedisUnpooled { con })
         } else {
-            let cli = redis::Client::open(dsn).expect("Error initializing redis unpooled client");
+            let cli = tls
+                .connection_info(dsn)
+                .and_then(redis::Client::open)
+                .expect("Error initializing redis unpooled client");
             let con = redis::aio::ConnectionManager::new_with_backoff_and_timeouts(
                 cli,
                 2,
@@ -71,23 +288,30 @@ impl RedisManager {
         }
     }
 
-    pub async fn from_cache_backend(cache_backend: &CacheBackend<'_>) -> Self {
+    pub async fn from_cache_backend(
+        cache_backend: &CacheBackend<'_>,
+        tls: &RedisTlsConfig,
+    ) -> Self {
         match cache_backend {
-            CacheBackend::Redis(dsn) => Self::new_unpooled(dsn, false).await,
-            CacheBackend::RedisCluster(dsn) => Self::new_unpooled(dsn, true).await,
+            CacheBackend::Redis(dsn) => Self::new_unpooled(dsn, false, tls).await,
+            CacheBackend::RedisCluster(dsn) => Self::new_unpooled(dsn, true, tls).await,
             _ => panic!("Queue type not supported with redis"),
         }
     }
 
-    pub async fn from_queue_backend(queue_backend: &QueueBackend<'_>, max_conns: u16) -> Self {
+    pub async fn from_queue_backend(
+        queue_backend: &QueueBackend<'_>,
+        max_conns: u16,
+        tls: &RedisTlsConfig,
+    ) -> Self {
         match queue_backend {
-            QueueBackend::Redis(dsn) => Self::new_pooled(dsn, false, max_conns).await,
-            QueueBackend::RedisCluster(dsn) => Self::new_pooled(dsn, true, max_conns).await,
+            QueueBackend::Redis(dsn) => Self::new_pooled(dsn, false, max_conns, tls).await,
+            QueueBackend::RedisCluster(dsn) => Self::new_pooled(dsn, true, max_conns, tls).await,
             _ => panic!("Queue type not supported with redis"),
         }
     }
 
-    pub async fn get(&self) -> Result<PooledConnection<'_>, RunError<RedisError>> {
+    pub async fn get(&self) -> Result<PooledConnection<'_>, PoolError> {
         match self {
             Self::Clustered(pool) => pool.get().await,
             Self::NonClustered(pool) => pool.get().await,
@@ -99,33 +323,188 @@ impl RedisManager {
 
 #[derive(Clone, Debug)]
 pub struct ClusteredRedisPool {
-    pool: Pool<RedisClusterConnectionManager>,
+    pool: Arc<Pool<RedisClusterConnectionManager>>,
+    max_size: u32,
 }
 
 impl ClusteredRedisPool {
-    pub async fn get(&self) -> Result<PooledConnection<'_>, RunError<RedisError>> {
-        let con = ClusteredPooledConnection {
-            con: self.pool.get().await?,
-        };
+    pub async fn get(&self) -> Result<PooledConnection<'_>, PoolError> {
+        let con = self.pool.get().await.map_err(|e| {
+            PoolError::from_run_error(e, pool_is_exhausted(self.pool.state(), self.max_size))
+        })?;
+        let con = ClusteredPooledConnection { con };
         Ok(PooledConnection::Clustered(con))
     }
+
+    /// Returns the pool's connection counts.
+    ///
+    /// Every pooled connection is a cluster connection which holds its own connection to each
+    /// node, so there are no separate counts for each node of the cluster.
+    pub fn pool_stats(&self) -> PoolStats {
+        PoolStats::from_state(self.pool.state())
+    }
+}
+
+/// The connections of a clustered pool
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PoolStats {
+    pub active_connections: u32,
+    pub idle_connections: u32,
+}
+
+impl PoolStats {
+    fn new(connections: u32, idle_connections: u32) -> Self {
+        Self {
+            active_connections: connections.saturating_sub(idle_connections),
+            idle_connections,
+        }
+    }
+
+    fn from_state(state: bb8::State) -> Self {
+        Self::new(state.connections, state.idle_connections)
+    }
+}
+
+/// Reports the pool's [`PoolStats`] in the pool connection gauges whenever metrics are collected,
+/// for as long as the pool is around
+fn register_pool_stats_gauges(pool: Weak<Pool<RedisClusterConnectionManager>>) {
+    let meter = opentelemetry::global::meter("svix.com");
+
+    let active_pool = pool.clone();
+    meter
+        .u64_observable_gauge("svix.redis.pool_active_connections")
+        .with_description("Number of pooled Redis cluster connections in use")
+        .with_callback(move |observer| {
+            if let Some(pool) = active_pool.upgrade() {
+                observer.observe(
+                    PoolStats::from_state(pool.state())
+                        .active_connections
+                        .into(),
+                    &[],
+                );
+            }
+        })
+        .init();
+
+    meter
+        .u64_observable_gauge("svix.redis.pool_idle_connections")
+        .with_description("Number of idle pooled Redis cluster connections")
+        .with_callback(move |observer| {
+            if let Some(pool) = pool.upgrade() {
+                observer.observe(
+                    PoolStats::from_state(pool.state()).idle_connections.into(),
+                    &[],
+                );
+            }
+        })
+        .init();
 }
 
 #[derive(Clone)]
 pub struct ClusteredRedisUnpooled {
-    con: redis::cluster_async::ClusterConnection,
+    con: ReconnectingConnection<redis::cluster_async::ClusterConnection>,
 }
 
 impl ClusteredRedisUnpooled {
-    pub async fn get(&self) -> Result<PooledConnection<'_>, RunError<RedisError>> {
+    pub async fn get(&self) -> Result<PooledConnection<'_>, PoolError> {
         Ok(PooledConnection::ClusteredUnpooled(
             ClusteredUnpooledConnection {
-                con: self.con.clone(),
+                con: self.con.latest(),
             },
         ))
     }
 }
 
+type Connect<C> = Arc<dyn Fn() -> BoxFuture<'static, RedisResult<C>> + Send + Sync>;
+
+/// A long-lived connection which is replaced with a new one when a command finds it dropped, e.g.
+/// after the cluster fails over. The command is then sent once more on the new connection.
+///
+/// Clones share the replacement, so connections handed out afterwards don't start off stale.
+#[derive(Clone)]
+struct ReconnectingConnection<C> {
+    con: C,
+    latest: Arc<Mutex<C>>,
+    connect: Connect<C>,
+}
+
+impl<C: Clone> ReconnectingConnection<C> {
+    fn new(
+        con: C,
+        connect: impl Fn() -> BoxFuture<'static, RedisResult<C>> + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            latest: Arc::new(Mutex::new(con.clone())),
+            con,
+            connect: Arc::new(connect),
+        }
+    }
+
+    /// A clone using the most recent connection
+    fn latest(&self) -> Self {
+        Self {
+            con: self.latest.lock().unwrap().clone(),
+            ..self.clone()
+        }
+    }
+
+    async fn reconnect(&mut self) -> RedisResult<()> {
+        RECONNECT_ATTEMPTS.add(1, &[]);
+        let con = (self.connect)().await?;
+        *self.latest.lock().unwrap() = con.clone();
+        self.con = con;
+        Ok(())
+    }
+}
+
+/// Whether the error means the connection is gone, so that a new one may succeed
+fn is_dropped_connection(err: &RedisError) -> bool {
+    err.is_connection_dropped() || err.is_connection_refusal()
+}
+
+impl<C> redis::aio::ConnectionLike for ReconnectingConnection<C>
+where
+    C: redis::aio::ConnectionLike + Clone + Send + Sync,
+{
+    fn req_packed_command<'a>(
+        &'a mut self,
+        cmd: &'a redis::Cmd,
+    ) -> redis::RedisFuture<'a, redis::Value> {
+        Box::pin(async move {
+            match self.con.req_packed_command(cmd).await {
+                Err(e) if is_dropped_connection(&e) => {
+                    tracing::warn!("Redis connection dropped, reconnecting: {e}");
+                    self.reconnect().await?;
+                    self.con.req_packed_command(cmd).await
+                }
+                res => res,
+            }
+        })
+    }
+
+    fn req_packed_commands<'a>(
+        &'a mut self,
+        cmd: &'a redis::Pipeline,
+        offset: usize,
+        count: usize,
+    ) -> redis::RedisFuture<'a, Vec<redis::Value>> {
+        Box::pin(async move {
+            match self.con.req_packed_commands(cmd, offset, count).await {
+                Err(e) if is_dropped_connection(&e) => {
+                    tracing::warn!("Redis connection dropped, reconnecting: {e}");
+                    self.reconnect().await?;
+                    self.con.req_packed_commands(cmd, offset, count).await
+                }
+                res => res,
+            }
+        })
+    }
+
+    fn get_db(&self) -> i64 {
+        self.con.get_db()
+    }
+}
+
 impl std::fmt::Debug for ClusteredRedisUnpooled {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("ClusteredRedisUnpooled").finish()
@@ -138,7 +517,7 @@ pub struct NonClusteredRedisUnpooled {
 }
 
 impl NonClusteredRedisUnpooled {
-    pub async fn get(&self) -> Result<PooledConnection<'_>, RunError<RedisError>> {
+    pub async fn get(&self) -> Result<PooledConnection<'_>, PoolError> {
         Ok(PooledConnection::NonClusteredUnpooled(
             NonClusteredUnpooledConnection {
                 con: self.con.clone(),
@@ -156,11 +535,14 @@ impl std::fmt::Debug for NonClusteredRedisUnpooled {
 #[derive(Clone, Debug)]
 pub struct NonClusteredRedisPool {
     pool: Pool<RedisConnectionManager>,
+    max_size: u32,
 }
 
 impl NonClusteredRedisPool {
-    pub async fn get(&self) -> Result<PooledConnection<'_>, RunError<RedisError>> {
-        let con = self.pool.get().await?;
+    pub async fn get(&self) -> Result<PooledConnection<'_>, PoolError> {
+        let con = self.pool.get().await.map_err(|e| {
+            PoolError::from_run_error(e, pool_is_exhausted(self.pool.state(), self.max_size))
+        })?;
         let con = NonClusteredPooledConnection { con };
         Ok(PooledConnection::NonClustered(con))
     }
@@ -174,6 +556,13 @@ pub enum PooledConnection<'a> {
 }
 
 impl PooledConnection<'_> {
+    pub fn is_clustered(&self) -> bool {
+        matches!(
+            self,
+            PooledConnection::Clustered(_) | PooledConnection::ClusteredUnpooled(_)
+        )
+    }
+
     pub async fn query_async<T: FromRedisValue>(&mut self, cmd: redis::Cmd) -> RedisResult<T> {
         cmd.query_async(self).await
     }
@@ -184,6 +573,44 @@ impl PooledConnection<'_> {
     ) -> RedisResult<T> {
         pipe.query_async(self).await
     }
+
+    /// Returns all keys matching the glob-style `pattern`. Keys are iterated with `SCAN` so that
+    /// the server isn't blocked, and on a cluster every master node is scanned.
+    pub async fn scan_match(&mut self, pattern: &str) -> RedisResult<Vec<String>> {
+        match self {
+            PooledConnection::Clustered(conn) => cluster::scan_match(&mut conn.con, pattern).await,
+            PooledConnection::ClusteredUnpooled(conn) => {
+                cluster::scan_match(&mut conn.con.con, pattern).await
+            }
+            PooledConnection::NonClustered(_) | PooledConnection::NonClusteredUnpooled(_) => {
+                let mut keys = Vec::new();
+                let mut cursor = 0;
+                loop {
+                    let (next_cursor, batch): (u64, Vec<String>) =
+                        self.query_async(scan_cmd(cursor, pattern)).await?;
+                    keys.extend(batch);
+                    if next_cursor == 0 {
+                        break;
+                    }
+                    cursor = next_cursor;
+                }
+                Ok(keys)
+            }
+        }
+    }
+}
+
+/// How many keys each `SCAN` call looks at. This is only a hint to Redis.
+const SCAN_COUNT: u64 = 1000;
+
+fn scan_cmd(cursor: u64, pattern: &str) -> redis::Cmd {
+    let mut cmd = redis::cmd("SCAN");
+    cmd.arg(cursor)
+        .arg("MATCH")
+        .arg(pattern)
+        .arg("COUNT")
+        .arg(SCAN_COUNT);
+    cmd
 }
 
 impl redis::aio::ConnectionLike for PooledConnection<'_> {
@@ -281,7 +708,7 @@ impl<'a> ClusteredPooledConnection<'a> {
 }
 
 pub struct ClusteredUnpooledConnection {
-    con: redis::cluster_async::ClusterConnection,
+    con: ReconnectingConnection<redis::cluster_async::ClusterConnection>,
 }
 
 impl ClusteredUnpooledConnection {
@@ -299,9 +726,270 @@ impl ClusteredUnpooledConnection {
 
 #[cfg(test)]
 mod tests {
-    use redis::AsyncCommands;
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    };
+
+    use bb8::RunError;
+    use futures::{future::BoxFuture, FutureExt};
+    use redis::{AsyncCommands, ErrorKind, RedisError, RedisFuture, RedisResult, Value};
+
+    use super::{
+        check_max_clients, PoolError, PoolStats, ReconnectingConnection, RedisManager,
+        RedisTlsConfig,
+    };
+
+    #[test]
+    fn test_pool_error_from_run_error() {
+        let err = PoolError::from_run_error(RunError::TimedOut, true);
+        assert!(matches!(err, PoolError::PoolExhausted));
+        assert!(err.is_transient());
+
+        let err = PoolError::from_run_error(RunError::TimedOut, false);
+        assert!(matches!(err, PoolError::TimedOut));
+        assert!(err.is_transient());
+
+        let err: PoolError =
+            RunError::User(RedisError::from((ErrorKind::IoError, "connection refused"))).into();
+        assert!(matches!(err, PoolError::ConnectionFailed(_)));
+        assert!(err.is_transient());
+
+        // An exhausted pool doesn't change how connection errors are reported
+        let err = PoolError::from_run_error(
+            RunError::User(RedisError::from((
+                ErrorKind::AuthenticationFailed,
+                "bad auth",
+            ))),
+            true,
+        );
+        assert!(matches!(err, PoolError::ConnectionFailed(_)));
+        assert!(!err.is_transient());
+    }
+
+    #[test]
+    fn test_redis_tls_config() {
+        let dir = std::env::temp_dir().join(format!("svix-redis-tls-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        for name in ["ca.pem", "client.pem", "client.key"] {
+            std::fs::write(dir.join(name), name).unwrap();
+        }
+
+        let mut cfg = crate::cfg::load().unwrap();
+        let cfg_mut = Arc::make_mut(&mut cfg);
+        cfg_mut.redis_tls_ca_cert = Some(dir.join("ca.pem"));
+        cfg_mut.redis_tls_client_cert = Some(dir.join("client.pem"));
+        cfg_mut.redis_tls_client_key = Some(dir.join("client.key"));
+        let tls = RedisTlsConfig::from_configuration(&cfg);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let certs = tls.certificates("rediss://redis:6379").unwrap();
+        assert_eq!(certs.root_cert.as_deref(), Some(&b"ca.pem"[..]));
+        let client_tls = certs.client_tls.unwrap();
+        assert_eq!(client_tls.client_cert, b"client.pem");
+        assert_eq!(client_tls.client_key, b"client.key");
+
+        // Only used for TLS connections
+        assert!(tls.certificates("redis://redis:6379").is_none());
+        assert!(tls.connection_info("redis://redis:6379").is_ok());
+
+        // Nor when there are none to use
+        let tls = RedisTlsConfig::default();
+        assert!(tls.certificates("rediss://redis:6379").is_none());
+        assert!(tls.connection_info("rediss://redis:6379").is_ok());
+    }
+
+    #[test]
+    fn test_pool_stats() {
+        assert_eq!(
+            PoolStats::new(5, 2),
+            PoolStats {
+                active_connections: 3,
+                idle_connections: 2,
+            }
+        );
+    }
+
+    /// Answers `CONFIG GET maxclients` with the given value, or fails like Redis does when
+    /// `CONFIG` is disallowed if there's none
+    #[derive(Clone)]
+    struct MaxClientsConnection(Option<&'static str>);
+
+    impl redis::aio::ConnectionLike for MaxClientsConnection {
+        fn req_packed_command<'a>(&'a mut self, _cmd: &'a redis::Cmd) -> RedisFuture<'a, Value> {
+            let res = match self.0 {
+                Some(max_clients) => Ok(Value::Bulk(vec![
+                    Value::Data(b"maxclients".to_vec()),
+                    Value::Data(max_clients.as_bytes().to_vec()),
+                ])),
+                None => Err(RedisError::from((
+                    ErrorKind::ResponseError,
+                    "unknown command 'CONFIG'",
+                ))),
+            };
+            Box::pin(async move { res })
+        }
+
+        fn req_packed_commands<'a>(
+            &'a mut self,
+            _cmd: &'a redis::Pipeline,
+            _offset: usize,
+            _count: usize,
+        ) -> RedisFuture<'a, Vec<Value>> {
+            unimplemented!("pipelines aren't used when checking maxclients")
+        }
 
-    use super::RedisManager;
+        fn get_db(&self) -> i64 {
+            0
+        }
+    }
+
+    /// Fails every command with a broken pipe once `dropped`, like a connection to a node which
+    /// has gone away
+    #[derive(Clone)]
+    struct DroppableConnection {
+        id: usize,
+        dropped: bool,
+    }
+
+    impl redis::aio::ConnectionLike for DroppableConnection {
+        fn req_packed_command<'a>(&'a mut self, _cmd: &'a redis::Cmd) -> RedisFuture<'a, Value> {
+            let res = if self.dropped {
+                Err(std::io::Error::from(std::io::ErrorKind::BrokenPipe).into())
+            } else {
+                Ok(Value::Int(self.id as i64))
+            };
+            Box::pin(async move { res })
+        }
+
+        fn req_packed_commands<'a>(
+            &'a mut self,
+            _cmd: &'a redis::Pipeline,
+            _offset: usize,
+            _count: usize,
+        ) -> RedisFuture<'a, Vec<Value>> {
+            unimplemented!("pipelines aren't used when testing reconnects")
+        }
+
+        fn get_db(&self) -> i64 {
+            0
+        }
+    }
+
+    #[tokio::test]
+    async fn test_reconnect_dropped_connection() {
+        let connects = Arc::new(AtomicUsize::new(0));
+        let mut con = ReconnectingConnection::new(
+            DroppableConnection {
+                id: 0,
+                dropped: false,
+            },
+            {
+                let connects = connects.clone();
+                move || {
+                    let id = connects.fetch_add(1, Ordering::SeqCst) + 1;
+                    async move { Ok(DroppableConnection { id, dropped: false }) }.boxed()
+                }
+            },
+        );
+
+        let id: usize = redis::cmd("PING").query_async(&mut con).await.unwrap();
+        assert_eq!(id, 0);
+        assert_eq!(connects.load(Ordering::SeqCst), 0);
+
+        // The command which finds the connection dropped is sent again on a new one
+        con.con.dropped = true;
+        let id: usize = redis::cmd("PING").query_async(&mut con).await.unwrap();
+        assert_eq!(id, 1);
+        assert_eq!(connects.load(Ordering::SeqCst), 1);
+
+        // Which is what connections handed out afterwards use
+        let mut other = con.latest();
+        let id: usize = redis::cmd("PING").query_async(&mut other).await.unwrap();
+        assert_eq!(id, 1);
+
+        // Other errors are returned as they are
+        let mut con = ReconnectingConnection::new(
+            MaxClientsConnection(None),
+            || -> BoxFuture<'static, RedisResult<MaxClientsConnection>> {
+                unreachable!("the connection is never dropped")
+            },
+        );
+        let err = redis::cmd("CONFIG")
+            .query_async::<_, Value>(&mut con)
+            .await
+            .unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::ResponseError);
+    }
+
+    /// Collects the logs written to it
+    #[derive(Clone, Default)]
+    struct Logs(Arc<Mutex<Vec<u8>>>);
+
+    impl Logs {
+        fn take(&self) -> String {
+            String::from_utf8(std::mem::take(&mut *self.0.lock().unwrap())).unwrap()
+        }
+    }
+
+    impl std::io::Write for Logs {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_check_max_clients() {
+        let logs = Logs::default();
+        let subscriber = tracing_subscriber::fmt()
+            .with_max_level(tracing::Level::WARN)
+            .with_ansi(false)
+            .with_writer({
+                let logs = logs.clone();
+                move || logs.clone()
+            })
+            .finish();
+        let _guard = tracing::subscriber::set_default(subscriber);
+
+        check_max_clients(&mut MaxClientsConnection(Some("25")), 20).await;
+        let warning = logs.take();
+        assert!(warning.contains("WARN"), "{warning}");
+        assert!(warning.contains("redis_pool_max_size (20)"), "{warning}");
+        assert!(warning.contains("maxclients (25)"), "{warning}");
+
+        check_max_clients(&mut MaxClientsConnection(Some("10000")), 20).await;
+        assert_eq!(logs.take(), "");
+
+        // Not being allowed to check isn't a problem
+        check_max_clients(&mut MaxClientsConnection(None), 20).await;
+        assert_eq!(logs.take(), "");
+    }
+
+    #[tokio::test]
+    // run with `cargo test -- --ignored redis` only when a redis cluster is up and configured
+    #[ignore]
+    async fn test_redis_cluster_pool_stats() {
+        dotenvy::dotenv().ok();
+        let cfg = crate::cfg::load().unwrap();
+
+        let RedisManager::Clustered(pool) = RedisManager::from_queue_backend(
+            &cfg.queue_backend(),
+            cfg.redis_pool_max_size,
+            &RedisTlsConfig::from_configuration(&cfg),
+        )
+        .await
+        else {
+            panic!("Queue backend isn't a redis cluster");
+        };
+
+        let _con = pool.get().await.unwrap();
+        assert!(pool.pool_stats().active_connections >= 1);
+    }
 
     // Ensure basic set/get works -- should test sharding as well:
     #[tokio::test]
@@ -311,7 +999,11 @@ mod tests {
         dotenvy::dotenv().ok();
         let cfg = crate::cfg::load().unwrap();
 
-        let mgr = RedisManager::from_cache_backend(&cfg.cache_backend()).await;
+        let mgr = RedisManager::from_cache_backend(
+            &cfg.cache_backend(),
+            &RedisTlsConfig::from_configuration(&cfg),
+        )
+        .await;
         let mut conn = mgr.get().await.unwrap();
 
         for (val, key) in "abcdefghijklmnopqrstuvwxyz".chars().enumerate() {
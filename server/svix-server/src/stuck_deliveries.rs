@@ -0,0 +1,170 @@
+// SPDX-FileCopyrightText: © 2022 Svix Authors
+// SPDX-License-Identifier: MIT
+
+//! Sends the tasks of deliveries which should have been retried long ago to the queue again.
+//!
+//! A delivery's next task is only queued once its latest attempt is done, so a worker crashing in
+//! between (or a task getting lost by the queue) leaves the delivery pending forever. A delivery
+//! counts as stuck once its last attempt (or its creation, if it was never attempted) is over twice
+//! the longest delay of the `retry_schedule` ago, and its next attempt is overdue by more than that
+//! delay. Short retry schedules are treated as taking at least [`MIN_RETRY_DELAY`], so that
+//! deliveries in flight aren't mistaken for stuck.
+//!
+//! Should the original task only be waiting in a backed up queue, the worker drops whichever of
+//! the two is handled last, and deliveries which can't be made anymore are cancelled by the worker
+//! instead of being queued again forever.
+
+use std::{collections::HashMap, sync::atomic::Ordering, time::Duration};
+
+use chrono::Utc;
+use sea_orm::{
+    prelude::DateTimeWithTimeZone,
+    sea_query::{Expr, LockBehavior, LockType},
+    ColumnTrait, Condition, DatabaseConnection, EntityTrait, FromQueryResult, QueryFilter,
+    QueryOrder, QuerySelect, TransactionTrait,
+};
+
+use crate::{
+    core::types::{MessageAttemptTriggerType, MessageEndpointId, MessageStatus},
+    db::models::{message, messageattempt, messagedestination},
+    error::Result,
+    queue::{MessageTask, QueueTask, TaskQueueProducer},
+};
+
+/// Leaves time for attempts in flight to finish, and for tasks in a backed up queue to be handled
+const MIN_RETRY_DELAY: Duration = Duration::from_secs(10 * 60);
+
+/// When a delivery was last worked on, matching the expression of the
+/// `ix_messagedestination_last_activity_unfinished` index
+const LAST_ACTIVITY: &str =
+    "COALESCE(messagedestination.last_attempt_at, messagedestination.created_at)";
+
+#[derive(Debug, FromQueryResult)]
+struct AttemptCountQueryOut {
+    msg_dest_id: MessageEndpointId,
+    count: i64,
+}
+
+/// Queues the next task of up to `limit` stuck deliveries, returning how many were queued.
+///
+/// `max_retry_delay` is the longest delay of the `retry_schedule`.
+pub async fn requeue_stuck_deliveries(
+    db: &DatabaseConnection,
+    queue_tx: &TaskQueueProducer,
+    max_retry_delay: Duration,
+    limit: u64,
+) -> Result<usize> {
+    let now = Utc::now();
+    let max_retry_delay = chrono::Duration::from_std(max_retry_delay.max(MIN_RETRY_DELAY))
+        .expect("Error parsing duration");
+
+    let tx = db.begin().await?;
+
+    // Locked so that other instances skip the same deliveries
+    let stuck = messagedestination::Entity::find()
+        .filter(messagedestination::Column::Status.is_in([
+            MessageStatus::Pending,
+            MessageStatus::Sending,
+            MessageStatus::RateLimited,
+        ]))
+        .filter(Expr::cust_with_values(
+            format!("{LAST_ACTIVITY} < $1"),
+            [DateTimeWithTimeZone::from(now - max_retry_delay * 2)],
+        ))
+        // Endpoints with a longer retry schedule of their own may still be waiting for a retry
+        .filter(
+            Condition::any()
+                .add(messagedestination::Column::NextAttempt.is_null())
+                .add(messagedestination::Column::NextAttempt.lt(now - max_retry_delay)),
+        )
+        .order_by_asc(Expr::cust(LAST_ACTIVITY))
+        .limit(limit)
+        .lock_with_behavior(LockType::Update, LockBehavior::SkipLocked)
+        .all(&tx)
+        .await?;
+    if stuck.is_empty() {
+        return Ok(0);
+    }
+
+    let app_ids = message::Entity::find()
+        .filter(message::Column::Id.is_in(stuck.iter().map(|dest| dest.msg_id.clone())))
+        .all(&tx)
+        .await?
+        .into_iter()
+        .map(|msg| (msg.id, msg.app_id))
+        .collect::<HashMap<_, _>>();
+
+    // Carries on with the retry schedule where each delivery left off
+    let attempt_counts = messageattempt::Entity::find()
+        .select_only()
+        .column(messageattempt::Column::MsgDestId)
+        .column_as(messageattempt::Column::Id.count(), "count")
+        .filter(messageattempt::Column::MsgDestId.is_in(stuck.iter().map(|dest| dest.id.clone())))
+        .filter(messageattempt::Column::TriggerType.eq(MessageAttemptTriggerType::Scheduled))
+        .group_by(messageattempt::Column::MsgDestId)
+        .into_model::<AttemptCountQueryOut>()
+        .all(&tx)
+        .await?
+        .into_iter()
+        .map(|AttemptCountQueryOut { msg_dest_id, count }| (msg_dest_id, count))
+        .collect::<HashMap<_, _>>();
+
+    let mut tasks = Vec::with_capacity(stuck.len());
+    for dest in &stuck {
+        let Some(app_id) = app_ids.get(&dest.msg_id) else {
+            continue;
+        };
+        let attempt_count = attempt_counts.get(&dest.id).copied().unwrap_or(0);
+
+        tasks.push((
+            QueueTask::MessageV1(MessageTask {
+                msg_id: dest.msg_id.clone(),
+                app_id: app_id.clone(),
+                endpoint_id: dest.endp_id.clone(),
+                trigger_type: MessageAttemptTriggerType::Scheduled,
+                attempt_count: attempt_count.try_into().unwrap_or(u16::MAX),
+            }),
+            None,
+        ));
+    }
+
+    // Marked as due now, so that they aren't queued again until they're overdue once more
+    messagedestination::Entity::update_many()
+        .col_expr(
+            messagedestination::Column::NextAttempt,
+            Expr::value(Some(DateTimeWithTimeZone::from(now))),
+        )
+        .filter(messagedestination::Column::Id.is_in(stuck.iter().map(|dest| dest.id.clone())))
+        .exec(&tx)
+        .await?;
+
+    let count = tasks.len();
+    queue_tx.send_many(tasks).await?;
+    tx.commit().await?;
+
+    Ok(count)
+}
+
+/// Periodically queues the tasks of stuck deliveries again, until the server shuts down.
+pub async fn stuck_delivery_loop(
+    db: &DatabaseConnection,
+    queue_tx: &TaskQueueProducer,
+    max_retry_delay: Duration,
+) -> Result<()> {
+    // Kept short so that it doesn't hold up shutting down for long
+    const INTERVAL: Duration = Duration::from_secs(10);
+    const BATCH_SIZE: u64 = 1_000;
+
+    let mut interval = tokio::time::interval(INTERVAL);
+    while !crate::SHUTTING_DOWN.load(Ordering::SeqCst) {
+        interval.tick().await;
+
+        match requeue_stuck_deliveries(db, queue_tx, max_retry_delay, BATCH_SIZE).await {
+            Ok(0) => {}
+            Ok(count) => tracing::warn!("queued {count} stuck deliveries again"),
+            Err(e) => tracing::error!("Failed to queue stuck deliveries again: {e}"),
+        }
+    }
+
+    Ok(())
+}
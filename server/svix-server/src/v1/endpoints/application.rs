@@ -1,6 +1,8 @@
 // SPDX-FileCopyrightText: © 2022 Svix Authors
 // SPDX-License-Identifier: MIT
 
+use std::iter;
+
 use aide::axum::{
     routing::{get_with, post_with},
     ApiRouter,
@@ -9,7 +11,7 @@ use axum::{
     extract::{Path, State},
     Json,
 };
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, Duration, Utc};
 use futures::FutureExt;
 use schemars::JsonSchema;
 use sea_orm::{ActiveModelTrait, ActiveValue::Set, TransactionTrait};
@@ -19,21 +21,29 @@ use validator::{Validate, ValidationError};
 
 use crate::{
     core::{
+        cache::CacheBehavior,
+        message_app::AppEndpointKey,
         permissions,
-        types::{metadata::Metadata, ApplicationId, ApplicationUid},
+        types::{
+            metadata::Metadata, ApplicationId, ApplicationUid, EndpointSecret,
+            EndpointSecretInternal, ExpiringSigningKey, ExpiringSigningKeys,
+        },
     },
-    db::models::{application, applicationmetadata},
+    db::models::{application, applicationmetadata, applicationwebhooksecret},
     error::{http_error_on_conflict, HttpError, Result, Traceable},
-    v1::utils::{
-        apply_pagination, openapi_tag,
-        patch::{
-            patch_field_non_nullable, patch_field_nullable, UnrequiredField,
-            UnrequiredNullableField,
+    v1::{
+        endpoints::endpoint::generate_secret,
+        utils::{
+            apply_pagination, openapi_tag,
+            patch::{
+                patch_field_non_nullable, patch_field_nullable, UnrequiredField,
+                UnrequiredNullableField,
+            },
+            validate_no_control_characters, validate_no_control_characters_unrequired,
+            validation_error, ApplicationPath, IteratorDirection, JsonStatusUpsert, ListResponse,
+            ModelIn, ModelOut, NoContent, Ordering, Pagination, PaginationLimit,
+            ReversibleIterator, ValidatedJson, ValidatedQuery,
         },
-        validate_no_control_characters, validate_no_control_characters_unrequired,
-        validation_error, ApplicationPath, IteratorDirection, JsonStatusUpsert, ListResponse,
-        ModelIn, ModelOut, NoContent, Ordering, Pagination, PaginationLimit, ReversibleIterator,
-        ValidatedJson, ValidatedQuery,
     },
     AppState,
 };
@@ -192,6 +202,21 @@ impl From<(application::Model, applicationmetadata::Model)> for ApplicationOut {
     }
 }
 
+#[derive(Clone, Debug, Default, PartialEq, Eq, Validate, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ApplicationWebhookSecretIn {
+    /// The new secret. Automatically generated when `null` (recommended).
+    #[validate]
+    #[serde(default)]
+    pub key: Option<EndpointSecret>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ApplicationWebhookSecretOut {
+    pub key: EndpointSecret,
+}
+
 /// List of all the organization's applications.
 #[aide_annotate(op_id = "v1.application.list")]
 async fn list_applications(
@@ -373,16 +398,133 @@ async fn patch_application(
     Ok(Json((app, metadata).into()))
 }
 
+/// Get the application's signing secret.
+///
+/// Endpoints created without a secret of their own are signed with it.
+#[aide_annotate(op_id = "v1.application.get-secret")]
+async fn get_application_secret(
+    State(AppState { ref db, cfg, .. }): State<AppState>,
+    permissions::OrganizationWithApplication { app }: permissions::OrganizationWithApplication,
+) -> Result<Json<ApplicationWebhookSecretOut>> {
+    let app_secret = applicationwebhooksecret::Entity::secure_find(app.id)
+        .one(db)
+        .await?
+        .ok_or_else(|| HttpError::not_found(None, None))?;
+    Ok(Json(ApplicationWebhookSecretOut {
+        key: app_secret.key.into_endpoint_secret(&cfg.encryption)?,
+    }))
+}
+
+/// Set or rotate the application's signing secret. The previous secret will be valid for the next
+/// 24 hours.
+///
+/// Endpoints created without a secret of their own are signed with it.
+#[aide_annotate(op_id = "v1.application.update-secret")]
+async fn update_application_secret(
+    State(AppState {
+        ref db,
+        ref cfg,
+        ref cache,
+        ..
+    }): State<AppState>,
+    permissions::OrganizationWithApplication { app }: permissions::OrganizationWithApplication,
+    ValidatedJson(data): ValidatedJson<ApplicationWebhookSecretIn>,
+) -> Result<Json<ApplicationWebhookSecretOut>> {
+    let key = if let Some(key) = data.key {
+        EndpointSecretInternal::from_endpoint_secret(key, &cfg.encryption)?
+    } else {
+        generate_secret(&cfg.encryption, &cfg.default_signature_type)?
+    };
+
+    let app_secret = match applicationwebhooksecret::Entity::secure_find(app.id.clone())
+        .one(db)
+        .await?
+    {
+        Some(mut app_secret) => {
+            let now = Utc::now();
+            let old_keys: Vec<_> = app_secret
+                .old_keys
+                .take()
+                .map(|x| x.0)
+                .unwrap_or_default()
+                .into_iter()
+                .filter(|x| x.expiration > now)
+                .collect();
+            if old_keys.len() + 1 > ExpiringSigningKeys::MAX_OLD_KEYS {
+                return Err(HttpError::bad_request(
+                    Some("limit_reached".to_owned()),
+                    Some(format!(
+                        "You can only rotate a key {} times within the last {}.",
+                        ExpiringSigningKeys::MAX_OLD_KEYS,
+                        ExpiringSigningKeys::OLD_KEY_EXPIRY_HOURS
+                    )),
+                )
+                .into());
+            }
+
+            let last_key = ExpiringSigningKey {
+                key: app_secret.key.clone(),
+                expiration: now + Duration::hours(ExpiringSigningKeys::OLD_KEY_EXPIRY_HOURS),
+            };
+
+            applicationwebhooksecret::ActiveModel {
+                key: Set(key),
+                old_keys: Set(Some(ExpiringSigningKeys(
+                    iter::once(last_key).chain(old_keys).collect(),
+                ))),
+                ..app_secret.into()
+            }
+            .update(db)
+            .await?
+        }
+        None => {
+            applicationwebhooksecret::ActiveModel::new(app.id.clone(), key)
+                .insert(db)
+                .await?
+        }
+    };
+
+    // The secret is cached along with the endpoints it signs, so drop it to have it take effect
+    // right away
+    if let Err(e) = cache
+        .delete(&AppEndpointKey::new(&app.org_id, &app.id))
+        .await
+    {
+        tracing::warn!(
+            error = &e as &dyn std::error::Error,
+            "Failed to invalidate the cached application"
+        );
+    }
+
+    Ok(Json(ApplicationWebhookSecretOut {
+        key: app_secret.key.into_endpoint_secret(&cfg.encryption)?,
+    }))
+}
+
 /// Delete an application.
 #[aide_annotate(op_id = "v1.application.delete")]
 async fn delete_application(
-    State(AppState { ref db, .. }): State<AppState>,
+    State(AppState {
+        ref db, ref cache, ..
+    }): State<AppState>,
     permissions::OrganizationWithApplication { app }: permissions::OrganizationWithApplication,
 ) -> Result<NoContent> {
+    let cache_key = AppEndpointKey::new(&app.org_id, &app.id);
+
     let mut app: application::ActiveModel = app.into();
     app.deleted = Set(true);
     app.uid = Set(None); // We don't want deleted UIDs to clash
     app.update(db).await?;
+
+    // So that the worker stops delivering the application's messages right away, rather than once
+    // the cached application expires
+    if let Err(e) = cache.delete(&cache_key).await {
+        tracing::warn!(
+            error = &e as &dyn std::error::Error,
+            "Failed to invalidate the cached application"
+        );
+    }
+
     Ok(NoContent)
 }
 
@@ -401,6 +543,14 @@ pub fn router() -> ApiRouter<AppState> {
                 .put_with(update_application, update_application_operation)
                 .patch_with(patch_application, patch_application_operation)
                 .delete_with(delete_application, delete_application_operation),
+            &tag,
+        )
+        .api_route_with(
+            "/app/:app_id/secret",
+            get_with(get_application_secret, get_application_secret_operation).put_with(
+                update_application_secret,
+                update_application_secret_operation,
+            ),
             tag,
         )
 }
@@ -14,7 +14,7 @@ use axum::{
 use chrono::{DateTime, Utc};
 use hyper::StatusCode;
 use schemars::JsonSchema;
-use sea_orm::{entity::prelude::*, IntoActiveModel, QueryOrder, QuerySelect};
+use sea_orm::{entity::prelude::*, FromQueryResult, IntoActiveModel, QueryOrder, QuerySelect};
 use serde::{Deserialize, Deserializer, Serialize};
 use serde_json::value::RawValue;
 use svix_server_derive::{aide_annotate, ModelOut};
@@ -74,6 +74,16 @@ pub struct MessageAttemptOut {
     pub trigger_type: MessageAttemptTriggerType,
     pub msg_id: MessageId,
     pub endpoint_id: EndpointId,
+    /// The headers sent with the request, with sensitive ones removed.
+    ///
+    /// Only recorded when `store_request_headers` is enabled in the server configuration.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_headers_sent: Option<serde_json::Value>,
+    /// The IP address the request was sent to.
+    ///
+    /// Only recorded when `store_endpoint_ip` is enabled in the server configuration.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub endpoint_ip: Option<String>,
 
     pub id: MessageAttemptId,
 
@@ -93,6 +103,8 @@ impl From<messageattempt::Model> for MessageAttemptOut {
             trigger_type: model.trigger_type,
             msg_id: model.msg_id,
             endpoint_id: model.endp_id,
+            request_headers_sent: model.request_headers_sent,
+            endpoint_ip: model.endpoint_ip,
 
             id: model.id,
             created_at: model.created_at.into(),
@@ -100,6 +112,43 @@ impl From<messageattempt::Model> for MessageAttemptOut {
     }
 }
 
+/// A message attempt without its response or request details, for cheaply polling a message's
+/// delivery status
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, ModelOut, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct MessageAttemptSummaryOut {
+    pub id: MessageAttemptId,
+    pub status: MessageStatus,
+    #[schemars(example = "example_status_code")]
+    pub response_status_code: i16,
+    pub endpoint_id: EndpointId,
+    #[serde(rename = "timestamp")]
+    pub created_at: DateTime<Utc>,
+}
+
+/// The columns of a [`messageattempt::Model`] needed for a [`MessageAttemptSummaryOut`], so that
+/// potentially large response bodies aren't read from the database at all
+#[derive(Debug, FromQueryResult)]
+struct MessageAttemptSummary {
+    id: MessageAttemptId,
+    status: MessageStatus,
+    response_status_code: i16,
+    endp_id: EndpointId,
+    created_at: DateTimeWithTimeZone,
+}
+
+impl From<MessageAttemptSummary> for MessageAttemptSummaryOut {
+    fn from(summary: MessageAttemptSummary) -> Self {
+        Self {
+            id: summary.id,
+            status: summary.status,
+            response_status_code: summary.response_status_code,
+            endpoint_id: summary.endp_id,
+            created_at: summary.created_at.into(),
+        }
+    }
+}
+
 /// A model containing information on a given message plus additional fields on the last attempt for
 /// that message.
 #[derive(Clone, Debug, PartialEq, Eq, Serialize, JsonSchema)]
@@ -257,12 +306,16 @@ async fn list_attempted_messages(
     // `find_also_related` can't be used multiple times in a query, so rather
     // than build a complicated custom query, just query all the content
     // data separately. Hopefully this isn't too painful:
-    let mut msg_content_map = messagecontent::Entity::secure_find_by_id_in(msg_ids)
+    let mut msg_content_map = HashMap::new();
+    for content in messagecontent::Entity::secure_find_by_id_in(msg_ids)
         .all(db)
         .await?
-        .into_iter()
-        .map(|content| (content.id, content.payload))
-        .collect::<HashMap<MessageId, Vec<u8>>>();
+    {
+        let id = content.id.clone();
+        if let Some(payload) = content.into_payload()? {
+            msg_content_map.insert(id, payload);
+        }
+    }
 
     let into = |(dest, msg): (messagedestination::Model, Option<message::Model>)| {
         let msg =
@@ -547,6 +600,10 @@ pub struct MessageEndpointOut {
     id: EndpointId,
     status: MessageStatus,
     next_attempt: Option<DateTime<Utc>>,
+    /// When the message was first attempted to this endpoint, as opposed to when it was created
+    first_attempt_at: Option<DateTime<Utc>>,
+    /// When the message was last attempted to this endpoint, successfully or not
+    last_attempt_at: Option<DateTime<Utc>>,
 }
 
 impl ModelOut for MessageEndpointOut {
@@ -562,6 +619,8 @@ impl MessageEndpointOut {
             endpoint: endp.into(),
             status: dest.status,
             next_attempt: dest.next_attempt.map(Into::into),
+            first_attempt_at: dest.first_attempt_at.map(Into::into),
+            last_attempt_at: dest.last_attempt_at.map(Into::into),
         }
     }
 }
@@ -800,6 +859,58 @@ async fn get_messageattempt(
     Ok(Json(attempt.into()))
 }
 
+/// List the status of a message's attempts, without their responses.
+///
+/// Lighter than "List Attempts by Msg" for polling a message's delivery status, as endpoints'
+/// response bodies can be large.
+///
+/// `msg_id`: Use a message id or a message `eventId`
+#[aide_annotate(op_id = "v1.message-attempt.list-summary-by-msg")]
+async fn list_attempt_summaries(
+    State(AppState { ref db, .. }): State<AppState>,
+    ValidatedQuery(pagination): ValidatedQuery<
+        PaginationDescending<ReversibleIterator<MessageAttemptId>>,
+    >,
+    Path(ApplicationMsgPath { msg_id, .. }): Path<ApplicationMsgPath>,
+    permissions::Application { app }: permissions::Application,
+) -> Result<Json<ListResponse<MessageAttemptSummaryOut>>> {
+    let PaginationLimit(limit) = pagination.limit;
+    let msg = message::Entity::secure_find_by_id_or_uid(app.id, msg_id)
+        .one(db)
+        .await?
+        .ok_or_else(|| HttpError::not_found(None, None))?;
+
+    let (query, iter_direction) = filter_and_paginate_time_limited(
+        messageattempt::Entity::secure_find_by_msg(msg.id),
+        messageattempt::Column::Id,
+        limit,
+        pagination.iterator,
+        None,
+        None,
+    );
+    let out = query
+        .select_only()
+        .columns([
+            messageattempt::Column::Id,
+            messageattempt::Column::Status,
+            messageattempt::Column::ResponseStatusCode,
+            messageattempt::Column::EndpId,
+            messageattempt::Column::CreatedAt,
+        ])
+        .into_model::<MessageAttemptSummary>()
+        .all(db)
+        .await?
+        .into_iter()
+        .map(Into::into)
+        .collect();
+
+    Ok(Json(MessageAttemptSummaryOut::list_response(
+        out,
+        limit as usize,
+        iter_direction,
+    )))
+}
+
 /// Resend a message to the specified endpoint.
 #[aide_annotate(op_id = "v1.message-attempt.resend")]
 async fn resend_webhook(
@@ -820,7 +931,9 @@ async fn resend_webhook(
         .ok_or_else(|| HttpError::not_found(None, None))?;
 
     let msg_content = match msg_content {
-        Some(m) => serde_json::from_slice(&m.payload).ok(),
+        Some(m) => m
+            .into_payload()?
+            .and_then(|payload| serde_json::from_slice(&payload).ok()),
         None => msg.legacy_payload,
     };
     if msg_content.is_none() {
@@ -893,6 +1006,11 @@ pub fn router() -> ApiRouter<AppState> {
             get_with(list_messageattempts, list_messageattempts_operation),
             &tag,
         )
+        .api_route_with(
+            "/app/:app_id/msg/:msg_id/attempt/summary",
+            get_with(list_attempt_summaries, list_attempt_summaries_operation),
+            &tag,
+        )
         .api_route_with(
             "/app/:app_id/msg/:msg_id/attempt/:attempt_id",
             get_with(get_messageattempt, get_messageattempt_operation),
@@ -9,32 +9,43 @@ use svix_server_derive::aide_annotate;
 use url::Url;
 
 use self::hack::EventTypeNameResult;
-use super::{EndpointIn, EndpointOut, EndpointPatch, EndpointUpdate};
+use super::{EndpointIn, EndpointOut, EndpointPatch, EndpointUpdate, ListEndpointsQueryParams};
 use crate::{
     cfg::Configuration,
     core::{
         operational_webhooks::{EndpointEvent, OperationalWebhook, OperationalWebhookSender},
         permissions,
-        types::{EndpointId, EventTypeName, EventTypeNameSet, OrganizationId},
+        types::{
+            ApplicationId, EndpointGroupId, EndpointId, EventTypeName, EventTypeNameSet,
+            OrganizationId,
+        },
+    },
+    db::models::{
+        application, applicationwebhooksecret, endpoint, endpointgroup, endpointmetadata, eventtype,
     },
-    db::models::{application, endpoint, endpointmetadata, eventtype},
     error::{http_error_on_conflict, HttpError, Result, Traceable, ValidationErrorItem},
     v1::utils::{
         apply_pagination,
         patch::{patch_field_non_nullable, UnrequiredField, UnrequiredNullableField},
-        ApplicationEndpointPath, ApplicationPath, IteratorDirection, JsonStatus, JsonStatusUpsert,
-        ListResponse, ModelIn, ModelOut, NoContent, Ordering, Pagination, PaginationLimit,
-        ReversibleIterator, ValidatedJson, ValidatedQuery,
+        ApplicationEndpointPath, ApplicationPath, EndpointMetadataQueryParams, IteratorDirection,
+        JsonStatus, JsonStatusUpsert, ListResponse, ModelIn, ModelOut, NoContent, Ordering,
+        Pagination, PaginationLimit, ReversibleIterator, ValidatedJson, ValidatedQuery,
     },
     AppState,
 };
 
 /// List the application's endpoints.
+///
+/// When `custom_data_key` and `custom_data_value` are both given, only endpoints whose custom data
+/// has that key set to that string are returned. Likewise, only endpoints whose metadata has every
+/// `metadata[key]=value` pair given are returned.
 #[aide_annotate(op_id = "v1.endpoint.list")]
 pub(super) async fn list_endpoints(
     State(AppState { ref db, .. }): State<AppState>,
     _: Path<ApplicationPath>,
     ValidatedQuery(pagination): ValidatedQuery<Pagination<ReversibleIterator<EndpointId>>>,
+    ValidatedQuery(params): ValidatedQuery<ListEndpointsQueryParams>,
+    EndpointMetadataQueryParams(metadata): EndpointMetadataQueryParams,
     permissions::Application { app }: permissions::Application,
 ) -> Result<Json<ListResponse<EndpointOut>>> {
     let PaginationLimit(limit) = pagination.limit;
@@ -43,8 +54,22 @@ pub(super) async fn list_endpoints(
         .as_ref()
         .map_or(IteratorDirection::Normal, |iter| iter.direction());
 
+    let mut query = endpoint::Entity::secure_find(app.id);
+    if let Some(custom_data) = params.custom_data_filter() {
+        query = query.filter(Expr::cust_with_values(
+            "endpoint.custom_data @> $1",
+            [custom_data],
+        ));
+    }
+    if let Some(metadata) = metadata {
+        query = query.filter(Expr::cust_with_values(
+            "endpoint.id IN (SELECT id FROM endpointmetadata WHERE data @> $1)",
+            [metadata],
+        ));
+    }
+
     let query = apply_pagination(
-        endpoint::Entity::secure_find(app.id),
+        query,
         endpoint::Column::Id,
         limit,
         iterator,
@@ -76,7 +101,18 @@ async fn create_endp_from_data(
     app: application::Model,
     mut data: EndpointIn,
 ) -> Result<(endpoint::Model, endpointmetadata::Model)> {
-    let key = data.key_take_or_generate(&cfg.encryption, &cfg.default_signature_type)?;
+    // Endpoints created without a key of their own are signed with the application's secret, if
+    // it has one
+    let key = if data.key.is_none()
+        && applicationwebhooksecret::Entity::secure_find(app.id.clone())
+            .one(db)
+            .await?
+            .is_some()
+    {
+        None
+    } else {
+        Some(data.key_take_or_generate(&cfg.encryption, &cfg.default_signature_type)?)
+    };
 
     let mut endp = endpoint::ActiveModel::new(app.id, key);
     let metadata =
@@ -103,7 +139,8 @@ async fn create_endp_from_data(
 
 /// Create a new endpoint for the application.
 ///
-/// When `secret` is `null` the secret is automatically generated (recommended)
+/// When `secret` is `null` the secret is automatically generated (recommended), unless the
+/// application has a secret, in which case the endpoint is signed with it.
 #[aide_annotate(op_id = "v1.endpoint.create")]
 pub(super) async fn create_endpoint(
     State(AppState {
@@ -119,6 +156,9 @@ pub(super) async fn create_endpoint(
     if let Some(ref event_types_ids) = data.event_types_ids {
         validate_event_types(db, event_types_ids, &app.org_id).await?;
     }
+    if let Some(ref group_id) = data.group_id {
+        validate_endpoint_group(db, group_id, &app.id).await?;
+    }
     validate_endpoint_url(&data.url, cfg.endpoint_https_only)?;
 
     let (endp, metadata) = create_endp_from_data(db, cfg, &op_webhooks, app, data)
@@ -188,6 +228,9 @@ pub(super) async fn update_endpoint(
     if let Some(ref event_types_ids) = data.event_types_ids {
         validate_event_types(db, event_types_ids, &app.org_id).await?;
     }
+    if let Some(ref group_id) = data.group_id {
+        validate_endpoint_group(db, group_id, &app.id).await?;
+    }
     validate_endpoint_url(&data.url, cfg.endpoint_https_only)?;
 
     let models = endpoint::ActiveModel::fetch_with_metadata(db, app.id.clone(), endpoint_id)
@@ -226,6 +269,9 @@ pub(super) async fn patch_endpoint(
     if let UnrequiredNullableField::Some(ref event_types_ids) = data.event_types_ids {
         validate_event_types(db, event_types_ids, &app.org_id).await?;
     }
+    if let UnrequiredNullableField::Some(ref group_id) = data.group_id {
+        validate_endpoint_group(db, group_id, &app.id).await?;
+    }
     if let UnrequiredField::Some(url) = &data.url {
         validate_endpoint_url(url, cfg.endpoint_https_only)?;
     }
@@ -336,6 +382,27 @@ async fn validate_event_types(
     }
 }
 
+async fn validate_endpoint_group(
+    db: &DatabaseConnection,
+    group_id: &EndpointGroupId,
+    app_id: &ApplicationId,
+) -> Result<()> {
+    let group = endpointgroup::Entity::secure_find_by_id(app_id.clone(), group_id.clone())
+        .one(db)
+        .await?;
+
+    if group.is_some() {
+        Ok(())
+    } else {
+        Err(HttpError::unprocessable_entity(vec![ValidationErrorItem {
+            loc: vec!["body".to_owned(), "groupId".to_owned()],
+            msg: format!("The endpoint group {group_id} doesn't exist"),
+            ty: "value_error".to_owned(),
+        }])
+        .into())
+    }
+}
+
 fn validate_endpoint_url(url: &Url, https_only: bool) -> Result<()> {
     if !https_only {
         return Ok(());
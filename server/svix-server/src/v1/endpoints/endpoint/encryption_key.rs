@@ -0,0 +1,55 @@
+use axum::{
+    extract::{Path, State},
+    Json,
+};
+use sea_orm::ActiveModelTrait;
+use svix_server_derive::aide_annotate;
+
+use super::{EndpointEncryptionKeyIn, EndpointEncryptionKeyOut};
+use crate::{
+    core::permissions,
+    db::models::endpoint,
+    error::{HttpError, Result},
+    v1::utils::{ApplicationEndpointPath, ModelIn, NoContent, ValidatedJson},
+    AppState,
+};
+
+/// Get the public key webhook payloads are encrypted for, and whether they're encrypted
+#[aide_annotate(op_id = "v1.endpoint.get-encryption-key")]
+pub(super) async fn get_endpoint_encryption_key(
+    State(AppState { ref db, .. }): State<AppState>,
+    Path(ApplicationEndpointPath { endpoint_id, .. }): Path<ApplicationEndpointPath>,
+    permissions::Application { app }: permissions::Application,
+) -> Result<Json<EndpointEncryptionKeyOut>> {
+    let endp = endpoint::Entity::secure_find_by_id_or_uid(app.id, endpoint_id)
+        .one(db)
+        .await?
+        .ok_or_else(|| HttpError::not_found(None, None))?;
+
+    Ok(Json(endp.into()))
+}
+
+/// Set the X25519 public key webhook payloads are encrypted for.
+///
+/// Once enabled, payloads are encrypted with XChaCha20-Poly1305, using a key derived with
+/// HKDF-SHA256 (`svix-payload-encryption-v1` as its info) from the shared secret of an ephemeral
+/// X25519 key pair, whose public key is sent in the `svix-encrypted-key` header. The webhook's
+/// signature is of the decrypted payload.
+#[aide_annotate(op_id = "v1.endpoint.update-encryption-key")]
+pub(super) async fn update_endpoint_encryption_key(
+    State(AppState { ref db, .. }): State<AppState>,
+    Path(ApplicationEndpointPath { endpoint_id, .. }): Path<ApplicationEndpointPath>,
+    permissions::Application { app }: permissions::Application,
+    ValidatedJson(data): ValidatedJson<EndpointEncryptionKeyIn>,
+) -> Result<NoContent> {
+    let endp = endpoint::Entity::secure_find_by_id_or_uid(app.id.clone(), endpoint_id)
+        .one(db)
+        .await?
+        .ok_or_else(|| HttpError::not_found(None, None))?;
+
+    let mut endp: endpoint::ActiveModel = endp.into();
+    data.update_model(&mut endp);
+    endp.update(db).await?;
+
+    Ok(NoContent)
+}
@@ -1,9 +1,12 @@
 // SPDX-FileCopyrightText: © 2022 Svix Authors
 // SPDX-License-Identifier: MIT
 mod crud;
+mod encryption_key;
 mod headers;
 mod recovery;
 mod secrets;
+mod test_webhook;
+mod transformation;
 
 use std::collections::{HashMap, HashSet};
 
@@ -23,17 +26,20 @@ use svix_server_derive::{aide_annotate, ModelIn, ModelOut};
 use url::Url;
 use validator::{Validate, ValidationError};
 
-use self::secrets::generate_secret;
+pub(crate) use self::secrets::generate_secret;
+pub use self::test_webhook::EndpointTestOut;
 use super::message::{create_message_inner, MessageIn, MessageOut, RawPayload};
 use crate::{
     cfg::DefaultSignatureType,
     core::{
-        cryptography::Encryption,
+        cache::CacheBehavior,
+        cryptography::{Encryption, PayloadEncryptionKey},
         permissions,
         types::{
-            metadata::Metadata, BaseId, EndpointHeaders, EndpointHeadersPatch, EndpointId,
-            EndpointSecret, EndpointSecretInternal, EndpointUid, EventChannelSet, EventTypeName,
-            EventTypeNameSet, MessageEndpointId, MessageStatus,
+            metadata::Metadata, BaseId, EndpointGroupId, EndpointHeaders, EndpointHeadersPatch,
+            EndpointId, EndpointSecret, EndpointSecretInternal, EndpointUid, EventChannelSet,
+            EventTypeName, EventTypeNameSet, HttpMethod, MessageEndpointId, MessageStatus,
+            PayloadVersion,
         },
     },
     db::models::{endpoint, eventtype, messagedestination},
@@ -47,6 +53,7 @@ use crate::{
         validate_no_control_characters, validate_no_control_characters_unrequired,
         validation_error, ApplicationEndpointPath, ModelIn, ValidatedJson,
     },
+    worker::{FailureCacheKey, FailureCacheValue, SuccessCacheKey, SuccessCacheValue},
     AppState,
 };
 
@@ -110,10 +117,109 @@ fn validate_url_unrequired(val: &UnrequiredField<Url>) -> Result<(), ValidationE
     }
 }
 
+/// Validates that the content type is a MIME type which can be sent as a header value
+pub fn validate_content_type(content_type: &str) -> Result<(), ValidationError> {
+    // `mime` accepts an empty subtype, e.g. `application/`
+    let is_mime = content_type
+        .parse::<mime::Mime>()
+        .is_ok_and(|mime| !mime.subtype().as_str().is_empty());
+    if !is_mime || http::HeaderValue::from_str(content_type).is_err() {
+        return Err(validation_error(
+            Some("content_type"),
+            Some("Content types must be valid MIME types, e.g. application/json"),
+        ));
+    }
+    Ok(())
+}
+
+fn validate_content_type_unrequired_nullable(
+    content_type: &UnrequiredNullableField<String>,
+) -> Result<(), ValidationError> {
+    match content_type {
+        UnrequiredNullableField::Absent | UnrequiredNullableField::None => Ok(()),
+        UnrequiredNullableField::Some(content_type) => validate_content_type(content_type),
+    }
+}
+
+/// Validates that custom data, if set, is a JSON object
+fn validate_custom_data(custom_data: &serde_json::Value) -> Result<(), ValidationError> {
+    if custom_data.is_object() {
+        Ok(())
+    } else {
+        Err(validation_error(
+            Some("custom_data"),
+            Some("Custom data must be a JSON object"),
+        ))
+    }
+}
+
+fn validate_custom_data_unrequired_nullable(
+    custom_data: &UnrequiredNullableField<serde_json::Value>,
+) -> Result<(), ValidationError> {
+    match custom_data {
+        UnrequiredNullableField::Absent | UnrequiredNullableField::None => Ok(()),
+        UnrequiredNullableField::Some(custom_data) => validate_custom_data(custom_data),
+    }
+}
+
+/// Validates that a custom signature prefix is a non-empty line of at most 256 characters
+fn validate_custom_signature_prefix(prefix: &str) -> Result<(), ValidationError> {
+    if prefix.is_empty() || prefix.len() > 256 || prefix.chars().any(char::is_control) {
+        return Err(validation_error(
+            Some("custom_signature_prefix"),
+            Some("Signature prefixes must be 1 to 256 characters long, without control characters"),
+        ));
+    }
+    Ok(())
+}
+
+fn validate_custom_signature_prefix_unrequired_nullable(
+    prefix: &UnrequiredNullableField<String>,
+) -> Result<(), ValidationError> {
+    match prefix {
+        UnrequiredNullableField::Absent | UnrequiredNullableField::None => Ok(()),
+        UnrequiredNullableField::Some(prefix) => validate_custom_signature_prefix(prefix),
+    }
+}
+
+const MAX_METADATA_KEYS: usize = 20;
+const MAX_METADATA_KEY_LEN: usize = 64;
+const MAX_METADATA_VALUE_LEN: usize = 256;
+
+/// Validates that endpoint metadata has at most 20 keys, each of at most 64 characters, with
+/// values of at most 256 characters
+pub fn validate_endpoint_metadata(metadata: &Metadata) -> Result<(), ValidationError> {
+    let too_long = metadata.iter().any(|(key, value)| {
+        key.is_empty()
+            || key.chars().count() > MAX_METADATA_KEY_LEN
+            || value.chars().count() > MAX_METADATA_VALUE_LEN
+    });
+    if metadata.iter().count() > MAX_METADATA_KEYS || too_long {
+        return Err(validation_error(
+            Some("metadata"),
+            Some("Metadata may have up to 20 keys of 1 to 64 characters, with values of up to 256 characters"),
+        ));
+    }
+    Ok(())
+}
+
+fn validate_endpoint_metadata_unrequired(
+    metadata: &UnrequiredField<Metadata>,
+) -> Result<(), ValidationError> {
+    match metadata {
+        UnrequiredField::Absent => Ok(()),
+        UnrequiredField::Some(metadata) => validate_endpoint_metadata(metadata),
+    }
+}
+
 fn example_channel_set() -> Vec<&'static str> {
     vec!["project_123", "group_2"]
 }
 
+fn example_content_type() -> &'static str {
+    "application/json"
+}
+
 fn example_endpoint_description() -> &'static str {
     "An example endpoint name"
 }
@@ -146,6 +252,8 @@ pub struct EndpointIn {
     #[schemars(example = "example_endpoint_description")]
     pub description: String,
 
+    /// The maximum number of messages sent to the endpoint per minute. Messages over the limit
+    /// wait until they can be sent.
     #[validate(range(min = 1, message = "Endpoint rate limits must be at least one if set"))]
     #[serde(skip_serializing_if = "Option::is_none")]
     pub rate_limit: Option<u16>,
@@ -186,8 +294,50 @@ pub struct EndpointIn {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub key: Option<EndpointSecret>,
 
+    #[validate(custom = "validate_endpoint_metadata")]
     #[serde(default)]
     pub metadata: Metadata,
+
+    /// The group whose settings this endpoint inherits. Settings made on the endpoint take
+    /// precedence over the group's.
+    #[validate]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub group_id: Option<EndpointGroupId>,
+
+    /// When set, the body of each successful response from this endpoint is sent as a new message
+    /// of this event type, which allows chaining webhooks.
+    #[validate]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub response_webhook_event_type: Option<EventTypeName>,
+
+    /// Messages with a lower `payloadVersion` aren't sent to this endpoint, and are marked as
+    /// incompatible instead. Messages without a version are always sent.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_payload_version: Option<PayloadVersion>,
+
+    /// The HTTP method webhooks are sent with: `POST` (the default), `PUT` or `PATCH`.
+    #[validate]
+    #[serde(default)]
+    pub http_method: HttpMethod,
+
+    /// The `Content-Type` webhooks are sent with, e.g. `application/cloudevents+json`. Defaults
+    /// to `application/json`.
+    #[validate(custom = "validate_content_type")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schemars(example = "example_content_type")]
+    pub content_type: Option<String>,
+
+    /// Arbitrary data about the endpoint, which must be a JSON object. Endpoints can be listed by
+    /// their custom data with `custom_data_key` and `custom_data_value`.
+    #[validate(custom = "validate_custom_data")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub custom_data: Option<serde_json::Value>,
+
+    /// Signed in place of the message ID, i.e. webhooks are signed as
+    /// `{prefix}.{timestamp}.{body}`, for receivers using SDKs which verify signatures that way.
+    #[validate(custom = "validate_custom_signature_prefix")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub custom_signature_prefix: Option<String>,
 }
 
 impl EndpointIn {
@@ -221,6 +371,13 @@ impl ModelIn for EndpointIn {
             channels,
             key: _,
             metadata: _,
+            group_id,
+            response_webhook_event_type,
+            min_payload_version,
+            http_method,
+            content_type,
+            custom_data,
+            custom_signature_prefix,
         } = self;
 
         model.description = Set(description);
@@ -231,6 +388,13 @@ impl ModelIn for EndpointIn {
         model.disabled = Set(disabled);
         model.event_types_ids = Set(event_types_ids);
         model.channels = Set(channels);
+        model.group_id = Set(group_id);
+        model.response_webhook_event_type = Set(response_webhook_event_type);
+        model.min_payload_version = Set(min_payload_version);
+        model.http_method = Set(http_method);
+        model.content_type = Set(content_type);
+        model.custom_data = Set(custom_data);
+        model.custom_signature_prefix = Set(custom_signature_prefix);
     }
 }
 
@@ -242,6 +406,8 @@ struct EndpointUpdate {
     #[schemars(example = "example_endpoint_description")]
     pub description: String,
 
+    /// The maximum number of messages sent to the endpoint per minute. Messages over the limit
+    /// wait until they can be sent.
     #[validate(range(min = 1, message = "Endpoint rate limits must be at least one if set"))]
     #[serde(skip_serializing_if = "Option::is_none")]
     pub rate_limit: Option<u16>,
@@ -277,8 +443,47 @@ struct EndpointUpdate {
     #[schemars(example = "example_channel_set", length(min = 1, max = 10))]
     pub channels: Option<EventChannelSet>,
 
+    #[validate(custom = "validate_endpoint_metadata")]
     #[serde(default)]
     pub metadata: Metadata,
+
+    /// The group whose settings this endpoint inherits. Settings made on the endpoint take
+    /// precedence over the group's.
+    #[validate]
+    #[serde(default)]
+    pub group_id: Option<EndpointGroupId>,
+    /// When set, the body of each successful response from this endpoint is sent as a new message
+    /// of this event type, which allows chaining webhooks.
+    #[validate]
+    #[serde(default)]
+    pub response_webhook_event_type: Option<EventTypeName>,
+    /// Messages with a lower `payloadVersion` aren't sent to this endpoint, and are marked as
+    /// incompatible instead. Messages without a version are always sent.
+    #[serde(default)]
+    pub min_payload_version: Option<PayloadVersion>,
+    /// The HTTP method webhooks are sent with: `POST` (the default), `PUT` or `PATCH`.
+    #[validate]
+    #[serde(default)]
+    pub http_method: HttpMethod,
+
+    /// The `Content-Type` webhooks are sent with, e.g. `application/cloudevents+json`. Defaults
+    /// to `application/json`.
+    #[validate(custom = "validate_content_type")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schemars(example = "example_content_type")]
+    pub content_type: Option<String>,
+
+    /// Arbitrary data about the endpoint, which must be a JSON object. Endpoints can be listed by
+    /// their custom data with `custom_data_key` and `custom_data_value`.
+    #[validate(custom = "validate_custom_data")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub custom_data: Option<serde_json::Value>,
+
+    /// Signed in place of the message ID, i.e. webhooks are signed as
+    /// `{prefix}.{timestamp}.{body}`, for receivers using SDKs which verify signatures that way.
+    #[validate(custom = "validate_custom_signature_prefix")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub custom_signature_prefix: Option<String>,
 }
 
 impl ModelIn for EndpointUpdate {
@@ -296,6 +501,13 @@ impl ModelIn for EndpointUpdate {
             event_types_ids,
             channels,
             metadata: _,
+            group_id,
+            response_webhook_event_type,
+            min_payload_version,
+            http_method,
+            content_type,
+            custom_data,
+            custom_signature_prefix,
         } = self;
 
         model.description = Set(description);
@@ -306,6 +518,13 @@ impl ModelIn for EndpointUpdate {
         model.disabled = Set(disabled);
         model.event_types_ids = Set(event_types_ids);
         model.channels = Set(channels);
+        model.group_id = Set(group_id);
+        model.response_webhook_event_type = Set(response_webhook_event_type);
+        model.min_payload_version = Set(min_payload_version);
+        model.http_method = Set(http_method);
+        model.content_type = Set(content_type);
+        model.custom_data = Set(custom_data);
+        model.custom_signature_prefix = Set(custom_signature_prefix);
     }
 }
 
@@ -322,6 +541,13 @@ impl EndpointUpdate {
             event_types_ids,
             channels,
             metadata,
+            group_id,
+            response_webhook_event_type,
+            min_payload_version,
+            http_method,
+            content_type,
+            custom_data,
+            custom_signature_prefix,
         } = self;
 
         EndpointIn {
@@ -334,6 +560,13 @@ impl EndpointUpdate {
             event_types_ids,
             channels,
             metadata,
+            group_id,
+            response_webhook_event_type,
+            min_payload_version,
+            http_method,
+            content_type,
+            custom_data,
+            custom_signature_prefix,
 
             key: None,
         }
@@ -348,6 +581,8 @@ pub struct EndpointPatch {
     #[validate(custom = "validate_no_control_characters_unrequired")]
     pub description: UnrequiredField<String>,
 
+    /// The maximum number of messages sent to the endpoint per minute. Messages over the limit
+    /// wait until they can be sent.
     #[validate(custom = "validate_rate_limit_patch")]
     #[serde(default, skip_serializing_if = "UnrequiredNullableField::is_absent")]
     pub rate_limit: UnrequiredNullableField<u16>,
@@ -387,9 +622,38 @@ pub struct EndpointPatch {
     #[serde(skip_serializing_if = "UnrequiredNullableField::is_absent")]
     pub key: UnrequiredNullableField<EndpointSecret>,
 
+    #[validate(custom = "validate_endpoint_metadata_unrequired")]
     #[serde(default)]
     #[serde(skip_serializing_if = "UnrequiredField::is_absent")]
     pub metadata: UnrequiredField<Metadata>,
+
+    #[validate]
+    #[serde(default, skip_serializing_if = "UnrequiredNullableField::is_absent")]
+    pub group_id: UnrequiredNullableField<EndpointGroupId>,
+
+    #[validate]
+    #[serde(default, skip_serializing_if = "UnrequiredNullableField::is_absent")]
+    pub response_webhook_event_type: UnrequiredNullableField<EventTypeName>,
+
+    #[serde(default, skip_serializing_if = "UnrequiredNullableField::is_absent")]
+    pub min_payload_version: UnrequiredNullableField<PayloadVersion>,
+
+    #[validate]
+    #[serde(default, skip_serializing_if = "UnrequiredField::is_absent")]
+    pub http_method: UnrequiredField<HttpMethod>,
+
+    #[validate(custom = "validate_content_type_unrequired_nullable")]
+    #[serde(default, skip_serializing_if = "UnrequiredNullableField::is_absent")]
+    #[schemars(example = "example_content_type")]
+    pub content_type: UnrequiredNullableField<String>,
+
+    #[validate(custom = "validate_custom_data_unrequired_nullable")]
+    #[serde(default, skip_serializing_if = "UnrequiredNullableField::is_absent")]
+    pub custom_data: UnrequiredNullableField<serde_json::Value>,
+
+    #[validate(custom = "validate_custom_signature_prefix_unrequired_nullable")]
+    #[serde(default, skip_serializing_if = "UnrequiredNullableField::is_absent")]
+    pub custom_signature_prefix: UnrequiredNullableField<String>,
 }
 
 impl ModelIn for EndpointPatch {
@@ -408,6 +672,13 @@ impl ModelIn for EndpointPatch {
             channels,
             key: _,
             metadata: _,
+            group_id,
+            response_webhook_event_type,
+            min_payload_version,
+            http_method,
+            content_type,
+            custom_data,
+            custom_signature_prefix,
         } = self;
 
         let map = |x: u16| -> i32 { x.into() };
@@ -421,6 +692,13 @@ impl ModelIn for EndpointPatch {
         patch_field_non_nullable!(model, disabled);
         patch_field_nullable!(model, event_types_ids);
         patch_field_nullable!(model, channels);
+        patch_field_nullable!(model, group_id);
+        patch_field_nullable!(model, response_webhook_event_type);
+        patch_field_nullable!(model, min_payload_version);
+        patch_field_non_nullable!(model, http_method);
+        patch_field_nullable!(model, content_type);
+        patch_field_nullable!(model, custom_data);
+        patch_field_nullable!(model, custom_signature_prefix);
     }
 }
 
@@ -482,6 +760,26 @@ pub struct EndpointOutCommon {
     /// List of message channels this endpoint listens to (omit for all)
     #[schemars(example = "example_channel_set", length(min = 1, max = 10))]
     pub channels: Option<EventChannelSet>,
+    /// The group whose settings this endpoint inherits
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub group_id: Option<EndpointGroupId>,
+    /// The event type that successful responses from this endpoint are sent as
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub response_webhook_event_type: Option<EventTypeName>,
+    /// The lowest payload version of the messages sent to this endpoint
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_payload_version: Option<PayloadVersion>,
+    /// The HTTP method webhooks are sent to this endpoint with
+    pub http_method: HttpMethod,
+    /// The `Content-Type` webhooks are sent to this endpoint with, if not `application/json`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub content_type: Option<String>,
+    /// Arbitrary data about the endpoint
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub custom_data: Option<serde_json::Value>,
+    /// Signed in place of the message ID, if set
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub custom_signature_prefix: Option<String>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
@@ -498,6 +796,13 @@ impl From<endpoint::Model> for EndpointOutCommon {
             disabled: model.disabled,
             event_types_ids: model.event_types_ids,
             channels: model.channels,
+            group_id: model.group_id,
+            response_webhook_event_type: model.response_webhook_event_type,
+            min_payload_version: model.min_payload_version,
+            http_method: model.http_method,
+            content_type: model.content_type,
+            custom_data: model.custom_data,
+            custom_signature_prefix: model.custom_signature_prefix,
             created_at: model.created_at.into(),
             updated_at: model.updated_at.into(),
         }
@@ -524,6 +829,34 @@ impl From<(endpoint::Model, Metadata)> for EndpointOut {
     }
 }
 
+#[derive(Clone, Debug, Deserialize, Validate, JsonSchema)]
+#[validate(schema(function = "validate_custom_data_filter"))]
+pub struct ListEndpointsQueryParams {
+    /// Only include endpoints whose custom data has this key, set to `custom_data_value`
+    custom_data_key: Option<String>,
+    /// The string value `custom_data_key` must be set to
+    custom_data_value: Option<String>,
+}
+
+impl ListEndpointsQueryParams {
+    /// The object endpoints' custom data must contain to match the filter, if any
+    fn custom_data_filter(self) -> Option<serde_json::Value> {
+        let (key, value) = self.custom_data_key.zip(self.custom_data_value)?;
+        Some(serde_json::json!({ key: value }))
+    }
+}
+
+fn validate_custom_data_filter(params: &ListEndpointsQueryParams) -> Result<(), ValidationError> {
+    if params.custom_data_key.is_some() == params.custom_data_value.is_some() {
+        Ok(())
+    } else {
+        Err(validation_error(
+            Some("custom_data"),
+            Some("custom_data_key and custom_data_value must be given together"),
+        ))
+    }
+}
+
 #[derive(Default, Clone, Debug, PartialEq, Eq, Validate, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct EndpointSecretRotateIn {
@@ -538,6 +871,123 @@ pub struct EndpointSecretOut {
     pub key: EndpointSecret,
 }
 
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct EndpointPublicKeyOut {
+    /// The PEM-encoded public key
+    #[schemars(example = "public_key_pem_example")]
+    pub key: String,
+}
+
+fn public_key_pem_example() -> &'static str {
+    "-----BEGIN PUBLIC KEY-----\nMCowBQYDK2VwAyEA1SiA4o9hyqTCpIqC5V9HUakiiaeACeqfZTInDBbOir4=\n-----END PUBLIC KEY-----\n"
+}
+
+/// Validates that the key is a base64-encoded X25519 public key
+fn validate_payload_encryption_key(key: &str) -> Result<(), ValidationError> {
+    if PayloadEncryptionKey::from_base64(key).is_err() {
+        return Err(validation_error(
+            Some("key"),
+            Some("Keys must be base64-encoded X25519 public keys"),
+        ));
+    }
+    Ok(())
+}
+
+fn payload_encryption_key_example() -> &'static str {
+    "hSvEyS4p3ZNCwbfQUy2jEb5KHFyhY/Xz+O/tsDSoZlQ="
+}
+
+fn default_true() -> bool {
+    true
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Validate, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct EndpointEncryptionKeyIn {
+    /// The base64-encoded X25519 public key webhook payloads are encrypted for
+    #[validate(custom = "validate_payload_encryption_key")]
+    #[schemars(example = "payload_encryption_key_example")]
+    pub key: String,
+    /// Whether webhook payloads are encrypted, which defaults to `true`
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+}
+
+impl ModelIn for EndpointEncryptionKeyIn {
+    type ActiveModel = endpoint::ActiveModel;
+
+    fn update_model(self, model: &mut Self::ActiveModel) {
+        let EndpointEncryptionKeyIn { key, enabled } = self;
+        // Validated already
+        let key = PayloadEncryptionKey::from_base64(&key).map(|key| key.pubkey());
+        model.payload_encryption_key = Set(key.ok());
+        model.payload_encryption_enabled = Set(enabled);
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Default, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct EndpointEncryptionKeyOut {
+    /// The base64-encoded X25519 public key webhook payloads are encrypted for, if one was set
+    #[schemars(example = "payload_encryption_key_example")]
+    pub key: Option<String>,
+    pub enabled: bool,
+}
+
+impl From<endpoint::Model> for EndpointEncryptionKeyOut {
+    fn from(endp: endpoint::Model) -> Self {
+        Self {
+            key: endp.payload_encryption_key.map(base64::encode),
+            enabled: endp.payload_encryption_enabled,
+        }
+    }
+}
+
+fn dispatch_transformation_example() -> &'static str {
+    "function handler(webhook) {\n  webhook.payload.foo = 'bar';\n  return webhook;\n}"
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Validate, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct EndpointTransformationIn {
+    /// The code of the script webhook payloads are transformed with before being sent to the
+    /// endpoint
+    #[validate(length(min = 1, max = 51200))]
+    #[schemars(example = "dispatch_transformation_example")]
+    pub code: Option<String>,
+    /// Whether webhook payloads are transformed, which defaults to `true`
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+}
+
+impl ModelIn for EndpointTransformationIn {
+    type ActiveModel = endpoint::ActiveModel;
+
+    fn update_model(self, model: &mut Self::ActiveModel) {
+        let EndpointTransformationIn { code, enabled } = self;
+        model.dispatch_transformation = Set(code);
+        model.dispatch_transformation_enabled = Set(enabled);
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Default, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct EndpointTransformationOut {
+    #[schemars(example = "dispatch_transformation_example")]
+    pub code: Option<String>,
+    pub enabled: bool,
+}
+
+impl From<endpoint::Model> for EndpointTransformationOut {
+    fn from(endp: endpoint::Model) -> Self {
+        Self {
+            code: endp.dispatch_transformation,
+            enabled: endp.dispatch_transformation_enabled,
+        }
+    }
+}
+
 #[derive(Clone, Debug, PartialEq, Eq, Validate, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct RecoverIn {
@@ -689,6 +1139,16 @@ pub struct EndpointStatsOut {
     pub pending: i64,
     pub sending: i64,
     pub fail: i64,
+    pub expired: i64,
+    pub filtered: i64,
+    pub incompatible: i64,
+    #[serde(rename = "rateLimited")]
+    pub rate_limited: i64,
+    pub cancelled: i64,
+    /// When a message was last delivered to the endpoint successfully, if it was recently enough
+    /// to be remembered. Not affected by `since` and `until`.
+    #[serde(rename = "lastDeliverySuccessAt")]
+    pub last_delivery_success_at: Option<DateTime<Utc>>,
 }
 
 #[derive(Debug, FromQueryResult)]
@@ -700,7 +1160,9 @@ pub struct EndpointStatsQueryOut {
 /// Get basic statistics for the endpoint.
 #[aide_annotate(op_id = "v1.endpoint.get-stats")]
 async fn endpoint_stats(
-    State(AppState { ref db, .. }): State<AppState>,
+    State(AppState {
+        ref db, ref cache, ..
+    }): State<AppState>,
     Path(ApplicationEndpointPath { endpoint_id, .. }): Path<ApplicationEndpointPath>,
     Query(range): Query<EndpointStatsRange>,
     permissions::Application { app }: permissions::Application,
@@ -708,12 +1170,18 @@ async fn endpoint_stats(
     let (since, until) = range.validate_unwrap_or_default()?;
 
     let endpoint =
-        crate::db::models::endpoint::Entity::secure_find_by_id_or_uid(app.id, endpoint_id)
+        crate::db::models::endpoint::Entity::secure_find_by_id_or_uid(app.id.clone(), endpoint_id)
             .one(db)
             .await?
             .ok_or_else(|| HttpError::not_found(None, None))?
             .id;
 
+    let last_delivery_success_at = cache
+        .get::<SuccessCacheValue>(&SuccessCacheKey::new(&app.org_id, &app.id, &endpoint))
+        .await
+        .map_err(error::Error::cache)?
+        .map(|SuccessCacheValue { last_success_at }| last_success_at);
+
     let query_out: Vec<EndpointStatsQueryOut> =
         messagedestination::Entity::secure_find_by_endpoint(endpoint)
             .select_only()
@@ -735,9 +1203,100 @@ async fn endpoint_stats(
         pending: query_out.remove(&MessageStatus::Pending).unwrap_or(0),
         fail: query_out.remove(&MessageStatus::Fail).unwrap_or(0),
         sending: query_out.remove(&MessageStatus::Sending).unwrap_or(0),
+        expired: query_out.remove(&MessageStatus::Expired).unwrap_or(0),
+        filtered: query_out.remove(&MessageStatus::Filtered).unwrap_or(0),
+        incompatible: query_out.remove(&MessageStatus::Incompatible).unwrap_or(0),
+        rate_limited: query_out.remove(&MessageStatus::RateLimited).unwrap_or(0),
+        cancelled: query_out.remove(&MessageStatus::Cancelled).unwrap_or(0),
+        last_delivery_success_at,
     }))
 }
 
+#[derive(Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct EndpointFailureStatusOut {
+    pub endpoint_id: EndpointId,
+    /// When the endpoint started failing
+    pub first_failure_at: DateTime<Utc>,
+    /// The number of messages in a row that couldn't be delivered to the endpoint since then
+    pub failure_count: u32,
+}
+
+/// List the application's endpoints which are currently failing.
+///
+/// An endpoint starts failing once a message couldn't be delivered to it after all of its
+/// attempts, and stops when it successfully receives a message again. Endpoints which haven't
+/// received anything since failing are forgotten after twice the time it takes for an endpoint to
+/// be disabled. Nothing is listed if the server runs without a cache.
+#[aide_annotate(op_id = "v1.endpoint.list-failure-status")]
+async fn list_failure_status(
+    State(AppState {
+        ref db, ref cache, ..
+    }): State<AppState>,
+    permissions::Application { app }: permissions::Application,
+) -> error::Result<Json<Vec<EndpointFailureStatusOut>>> {
+    let prefix = FailureCacheKey::prefix_for_app(&app.org_id, &app.id);
+    let mut endp_ids: Vec<_> = cache
+        .scan_keys(&prefix)
+        .await
+        .map_err(error::Error::cache)?
+        .into_iter()
+        .filter_map(|key| {
+            key.strip_prefix(&prefix)
+                .map(|id| EndpointId(id.to_owned()))
+        })
+        .collect();
+    if endp_ids.is_empty() {
+        return Ok(Json(Vec::new()));
+    }
+
+    // Deleted endpoints are only forgotten once their failure status expires
+    let existing: HashSet<_> = endpoint::Entity::secure_find(app.id.clone())
+        .filter(endpoint::Column::Id.is_in(endp_ids.clone()))
+        .all(db)
+        .await?
+        .into_iter()
+        .map(|endp| endp.id)
+        .collect();
+    endp_ids.retain(|id| existing.contains(id));
+    // IDs sort by creation time, so endpoints are listed oldest first
+    endp_ids.sort_by(|a, b| a.0.cmp(&b.0));
+
+    let keys: Vec<_> = endp_ids
+        .iter()
+        .map(|endp_id| FailureCacheKey::new(&app.org_id, &app.id, endp_id))
+        .collect();
+    let values = cache
+        .get_pipeline::<FailureCacheValue>(&keys.iter().collect::<Vec<_>>())
+        .await
+        .map_err(error::Error::cache)?;
+
+    // Values may have expired since the keys were listed
+    let out = endp_ids
+        .into_iter()
+        .zip(values)
+        .filter_map(|(endpoint_id, value)| {
+            let FailureCacheValue {
+                first_failure_at,
+                failure_count,
+            } = match value {
+                Ok(value) => value?,
+                Err(e) => {
+                    tracing::warn!("Failed to read the failure status of {endpoint_id}: {e}");
+                    return None;
+                }
+            };
+            Some(EndpointFailureStatusOut {
+                endpoint_id,
+                first_failure_at,
+                failure_count,
+            })
+        })
+        .collect();
+
+    Ok(Json(out))
+}
+
 #[derive(Deserialize, JsonSchema, Validate)]
 #[serde(rename_all = "camelCase")]
 struct EventExampleIn {
@@ -762,6 +1321,7 @@ async fn send_example(
         ref db,
         queue_tx,
         cache,
+        cfg,
         ..
     }) = state;
 
@@ -798,10 +1358,25 @@ async fn send_example(
         payload: RawPayload::from_string(example).unwrap(),
         uid: None,
         payload_retention_period: 90,
+        tags: None,
+        deadline: None,
+        signed_metadata: None,
+        payload_version: None,
+        endpoint_metadata_selector: None,
     };
 
-    let create_message =
-        create_message_inner(db, queue_tx, cache, false, Some(endpoint.id), msg_in, app).await?;
+    let create_message = create_message_inner(
+        db,
+        queue_tx,
+        cache,
+        false,
+        Some(endpoint.id),
+        0,
+        cfg.compress_payload_threshold_bytes,
+        msg_in,
+        app,
+    )
+    .await?;
 
     Ok(Json(create_message))
 }
@@ -831,6 +1406,14 @@ pub fn router() -> ApiRouter<AppState> {
             ),
             &tag,
         )
+        .api_route_with(
+            "/app/:app_id/endpoint/:endpoint_id/public-key",
+            get_with(
+                secrets::get_endpoint_public_key,
+                secrets::get_endpoint_public_key_operation,
+            ),
+            &tag,
+        )
         .api_route_with(
             "/app/:app_id/endpoint/:endpoint_id/secret/rotate",
             post_with(
@@ -839,6 +1422,11 @@ pub fn router() -> ApiRouter<AppState> {
             ),
             &tag,
         )
+        .api_route_with(
+            "/app/:app_id/failure-status",
+            get_with(list_failure_status, list_failure_status_operation),
+            &tag,
+        )
         .api_route_with(
             "/app/:app_id/endpoint/:endpoint_id/stats",
             get_with(endpoint_stats, endpoint_stats_operation),
@@ -849,6 +1437,14 @@ pub fn router() -> ApiRouter<AppState> {
             post_with(send_example, send_example_operation),
             &tag,
         )
+        .api_route_with(
+            "/app/:app_id/endpoint/:endpoint_id/test",
+            post_with(
+                test_webhook::test_endpoint,
+                test_webhook::test_endpoint_operation,
+            ),
+            &tag,
+        )
         .api_route_with(
             "/app/:app_id/endpoint/:endpoint_id/recover",
             post_with(
@@ -857,6 +1453,30 @@ pub fn router() -> ApiRouter<AppState> {
             ),
             &tag,
         )
+        .api_route_with(
+            "/app/:app_id/endpoint/:endpoint_id/encryption-key",
+            get_with(
+                encryption_key::get_endpoint_encryption_key,
+                encryption_key::get_endpoint_encryption_key_operation,
+            )
+            .put_with(
+                encryption_key::update_endpoint_encryption_key,
+                encryption_key::update_endpoint_encryption_key_operation,
+            ),
+            &tag,
+        )
+        .api_route_with(
+            "/app/:app_id/endpoint/:endpoint_id/transformation",
+            get_with(
+                transformation::get_endpoint_transformation,
+                transformation::get_endpoint_transformation_operation,
+            )
+            .put_with(
+                transformation::update_endpoint_transformation,
+                transformation::update_endpoint_transformation_operation,
+            ),
+            &tag,
+        )
         .api_route_with(
             "/app/:app_id/endpoint/:endpoint_id/headers",
             get_with(
@@ -883,7 +1503,10 @@ mod tests {
     use serde_json::json;
     use validator::Validate;
 
-    use super::{validate_url, EndpointHeadersOut, EndpointHeadersPatchIn, EndpointIn};
+    use super::{
+        validate_content_type, validate_url, EndpointHeadersOut, EndpointHeadersPatchIn,
+        EndpointIn, EndpointPatch,
+    };
     use crate::core::types::EndpointHeaders;
 
     const URL_VALID: &str = "https://www.example.com";
@@ -1028,4 +1651,35 @@ mod tests {
         assert!(invalid_scheme.validate().is_err());
         assert!(invalid_format.is_err());
     }
+
+    #[test]
+    fn test_content_type_validation() {
+        for valid in [
+            "application/json",
+            "application/cloudevents+json",
+            "application/json; charset=utf-8",
+            "text/plain",
+        ] {
+            assert!(validate_content_type(valid).is_ok(), "{valid}");
+        }
+        for invalid in ["", "json", "application/", "application/json\n", "a b/c"] {
+            assert!(validate_content_type(invalid).is_err(), "{invalid}");
+        }
+
+        let valid: EndpointIn = serde_json::from_value(json!({
+            "url": URL_VALID,
+            "contentType": "application/cloudevents+json"
+        }))
+        .unwrap();
+        assert!(valid.validate().is_ok());
+        let invalid: EndpointIn =
+            serde_json::from_value(json!({"url": URL_VALID, "contentType": "json"})).unwrap();
+        assert!(invalid.validate().is_err());
+
+        let unset: EndpointPatch = serde_json::from_value(json!({"contentType": null})).unwrap();
+        assert!(unset.validate().is_ok());
+        let invalid: EndpointPatch =
+            serde_json::from_value(json!({"contentType": "json"})).unwrap();
+        assert!(invalid.validate().is_err());
+    }
 }
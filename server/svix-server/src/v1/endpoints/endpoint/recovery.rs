@@ -50,19 +50,19 @@ async fn bulk_recover_failed_messages(
         let cur_len = items.len() as u64;
         iterator = items.last().map(|x| x.id.clone());
 
-        for msg_dest in items {
-            queue_tx
-                .send(
-                    MessageTask::new_task(
-                        msg_dest.msg_id,
-                        app.id.clone(),
-                        msg_dest.endp_id,
-                        MessageAttemptTriggerType::Manual,
-                    ),
-                    None,
-                )
-                .await?;
-        }
+        let tasks = items
+            .into_iter()
+            .map(|msg_dest| {
+                let task = MessageTask::new_task(
+                    msg_dest.msg_id,
+                    app.id.clone(),
+                    msg_dest.endp_id,
+                    MessageAttemptTriggerType::Manual,
+                );
+                (task, None)
+            })
+            .collect();
+        queue_tx.send_many(tasks).await?;
 
         num_done += cur_len;
         if cur_len < BATCH_SIZE || num_done > RECOVER_LIMIT {
@@ -1,36 +1,53 @@
-use std::iter;
-
 use axum::{
     extract::{Path, State},
     Json,
 };
 use chrono::{Duration, Utc};
-use sea_orm::{ActiveModelTrait, ActiveValue::Set};
+use sea_orm::{ActiveModelTrait, ActiveValue::Set, DatabaseConnection};
 use svix_server_derive::aide_annotate;
 
-use super::{EndpointSecretOut, EndpointSecretRotateIn};
+use super::{EndpointPublicKeyOut, EndpointSecretOut, EndpointSecretRotateIn};
 use crate::{
     cfg::DefaultSignatureType,
     core::{
         cryptography::Encryption,
         operational_webhooks::{EndpointEvent, OperationalWebhook},
         permissions,
-        types::{EndpointSecretInternal, ExpiringSigningKey, ExpiringSigningKeys},
+        types::{EndpointSecret, EndpointSecretInternal, ExpiringSigningKey, ExpiringSigningKeys},
     },
-    db::models::endpoint,
+    db::models::{applicationwebhooksecret, endpoint},
     error::{HttpError, Result},
     v1::utils::{ApplicationEndpointPath, NoContent, ValidatedJson},
     AppState,
 };
 
-pub(super) fn generate_secret(
+pub(crate) fn generate_secret(
     encryption: &Encryption,
     sig_type: &DefaultSignatureType,
 ) -> Result<EndpointSecretInternal> {
     match sig_type {
         DefaultSignatureType::Hmac256 => EndpointSecretInternal::generate_symmetric(encryption),
         DefaultSignatureType::Ed25519 => EndpointSecretInternal::generate_asymmetric(encryption),
+        DefaultSignatureType::Rsa => EndpointSecretInternal::generate_rsa(encryption),
+    }
+}
+
+/// The key the endpoint is signed with, which is the application's secret if the endpoint doesn't
+/// have one of its own
+async fn endpoint_signing_key(
+    db: &DatabaseConnection,
+    endp: &endpoint::Model,
+) -> Result<Option<EndpointSecretInternal>> {
+    if let Some(key) = &endp.key {
+        return Ok(Some(key.clone()));
     }
+
+    Ok(
+        applicationwebhooksecret::Entity::secure_find(endp.app_id.clone())
+            .one(db)
+            .await?
+            .map(|app_secret| app_secret.key),
+    )
 }
 
 /// Get the endpoint's signing secret.
@@ -47,12 +64,50 @@ pub(super) async fn get_endpoint_secret(
         .one(db)
         .await?
         .ok_or_else(|| HttpError::not_found(None, None))?;
+    let key = endpoint_signing_key(db, &endp)
+        .await?
+        .ok_or_else(|| HttpError::not_found(None, None))?;
     Ok(Json(EndpointSecretOut {
-        key: endp.key.into_endpoint_secret(&cfg.encryption)?,
+        key: key.into_endpoint_secret(&cfg.encryption)?,
     }))
 }
 
+/// Get the public key of the endpoint's signing secret, PEM-encoded.
+///
+/// Only asymmetric (Ed25519 and RSA) secrets have a public key that can be shared with the
+/// consumer of the webhooks to verify their signatures.
+#[aide_annotate(op_id = "v1.endpoint.get-public-key")]
+pub(super) async fn get_endpoint_public_key(
+    State(AppState { ref db, cfg, .. }): State<AppState>,
+    Path(ApplicationEndpointPath { endpoint_id, .. }): Path<ApplicationEndpointPath>,
+    permissions::Application { app }: permissions::Application,
+) -> Result<Json<EndpointPublicKeyOut>> {
+    let endp = endpoint::Entity::secure_find_by_id_or_uid(app.id, endpoint_id)
+        .one(db)
+        .await?
+        .ok_or_else(|| HttpError::not_found(None, None))?;
+    let key = endpoint_signing_key(db, &endp)
+        .await?
+        .ok_or_else(|| HttpError::not_found(None, None))?;
+
+    let key = match key.into_endpoint_secret(&cfg.encryption)? {
+        EndpointSecret::Symmetric(_) => {
+            return Err(HttpError::bad_request(
+                Some("symmetric_key".to_owned()),
+                Some("The endpoint's secret is symmetric, so it has no public key.".to_owned()),
+            )
+            .into());
+        }
+        EndpointSecret::Asymmetric(key) => key.pubkey_pem()?,
+        EndpointSecret::Rsa(key) => key.pubkey_pem(),
+    };
+
+    Ok(Json(EndpointPublicKeyOut { key }))
+}
+
 /// Rotates the endpoint's signing secret.  The previous secret will be valid for the next 24 hours.
+///
+/// Endpoints signed with the application's secret get a secret of their own.
 #[aide_annotate(op_id = "v1.endpoint.rotate-secret")]
 pub(super) async fn rotate_endpoint_secret(
     State(AppState {
@@ -71,10 +126,12 @@ pub(super) async fn rotate_endpoint_secret(
         .ok_or_else(|| HttpError::not_found(None, None))?;
 
     let now = Utc::now();
-    let last_key = ExpiringSigningKey {
-        key: endp.key.clone(),
-        expiration: now + Duration::hours(ExpiringSigningKeys::OLD_KEY_EXPIRY_HOURS),
-    };
+    let last_key = endpoint_signing_key(db, &endp)
+        .await?
+        .map(|key| ExpiringSigningKey {
+            key,
+            expiration: now + Duration::hours(ExpiringSigningKeys::OLD_KEY_EXPIRY_HOURS),
+        });
 
     if let Some(ref old_keys) = endp.old_keys {
         if old_keys.0.len() + 1 > ExpiringSigningKeys::MAX_OLD_KEYS {
@@ -93,14 +150,15 @@ pub(super) async fn rotate_endpoint_secret(
     let old_keys = endp.old_keys.take();
 
     let endp = endpoint::ActiveModel {
-        key: Set(if let Some(key) = data.key {
+        key: Set(Some(if let Some(key) = data.key {
             EndpointSecretInternal::from_endpoint_secret(key, &cfg.encryption)?
         } else {
             generate_secret(&cfg.encryption, &cfg.default_signature_type)?
-        }),
+        })),
 
         old_keys: Set(Some(ExpiringSigningKeys(
-            iter::once(last_key)
+            last_key
+                .into_iter()
                 .chain(
                     old_keys
                         .map(|x| x.0.into_iter())
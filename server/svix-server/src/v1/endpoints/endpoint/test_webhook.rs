@@ -0,0 +1,173 @@
+use std::time::Duration;
+
+use axum::{
+    extract::{Path, State},
+    Json,
+};
+use chrono::Utc;
+use schemars::JsonSchema;
+use sea_orm::prelude::DateTimeUtc;
+use serde::{Deserialize, Serialize};
+use svix_server_derive::aide_annotate;
+
+use crate::{
+    core::{
+        cache::{kv_def, Cache, CacheBehavior, CacheKey, CacheValue},
+        message_app::CreateMessageApp,
+        permissions,
+        types::{ApplicationId, EndpointId, OrganizationId},
+    },
+    db::models::endpoint,
+    error::{Error, HttpError, Result},
+    v1::utils::ApplicationEndpointPath,
+    worker::send_test_webhook,
+    AppState,
+};
+
+const TEST_WEBHOOK_PAYLOAD: &str = r#"{"test": true}"#;
+
+/// How many test webhooks can be sent to an endpoint within [`TEST_RATE_LIMIT_PERIOD`]
+const TEST_RATE_LIMIT: usize = 10;
+const TEST_RATE_LIMIT_PERIOD: Duration = Duration::from_secs(60);
+
+/// When the most recent test webhooks were sent to an endpoint
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct EndpointTestTimesCacheValue(Vec<DateTimeUtc>);
+
+kv_def!(EndpointTestTimesCacheKey, EndpointTestTimesCacheValue);
+
+impl EndpointTestTimesCacheKey {
+    pub fn new(org_id: &OrganizationId, app_id: &ApplicationId, endp_id: &EndpointId) -> Self {
+        Self(format!(
+            "SVIX_ENDPOINT_TEST_TIMES_{org_id}_{app_id}_{endp_id}"
+        ))
+    }
+}
+
+/// Records a test of the endpoint, failing if it has already been tested [`TEST_RATE_LIMIT`]
+/// times in the last [`TEST_RATE_LIMIT_PERIOD`].
+///
+/// Concurrent tests of the same endpoint may slip through, which is fine as this is only about
+/// preventing endpoints from being flooded with tests. Without a cache there's no limit.
+async fn record_endpoint_test(cache: &Cache, key: &EndpointTestTimesCacheKey) -> Result<()> {
+    let now = Utc::now();
+    let period_start =
+        now - chrono::Duration::from_std(TEST_RATE_LIMIT_PERIOD).expect("Period is too large");
+
+    let EndpointTestTimesCacheValue(mut times) = cache
+        .get::<EndpointTestTimesCacheValue>(key)
+        .await
+        .map_err(Error::cache)?
+        .unwrap_or_default();
+    times.retain(|time| *time > period_start);
+
+    if times.len() >= TEST_RATE_LIMIT {
+        return Err(HttpError::too_many_requests(
+            None,
+            Some(format!(
+                "Endpoints can only be tested {TEST_RATE_LIMIT} times per minute."
+            )),
+        )
+        .into());
+    }
+
+    times.push(now);
+    cache
+        .set(
+            key,
+            &EndpointTestTimesCacheValue(times),
+            Some(TEST_RATE_LIMIT_PERIOD),
+        )
+        .await
+        .map_err(Error::cache)
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct EndpointTestOut {
+    /// The status code the endpoint responded with, or `0` if it couldn't be reached
+    pub status_code: u16,
+    /// How long it took for the endpoint to respond, in milliseconds
+    pub latency_ms: u64,
+    /// The body the endpoint responded with, or the error if it couldn't be reached
+    pub response_body: String,
+}
+
+/// Send a test webhook to the endpoint, to check that it's reachable.
+///
+/// The webhook has a `{"test": true}` payload and is signed and sent just like messages are, but
+/// no message is created and no attempt is recorded. An endpoint can be tested up to 10 times per
+/// minute.
+#[aide_annotate(op_id = "v1.endpoint.test")]
+pub(super) async fn test_endpoint(
+    State(AppState {
+        ref db,
+        ref cache,
+        ref cfg,
+        ref webhook_client,
+        ..
+    }): State<AppState>,
+    Path(ApplicationEndpointPath { endpoint_id, .. }): Path<ApplicationEndpointPath>,
+    permissions::OrganizationWithApplication { app }: permissions::OrganizationWithApplication,
+) -> Result<Json<EndpointTestOut>> {
+    let endp = endpoint::Entity::secure_find_by_id_or_uid(app.id.clone(), endpoint_id)
+        .one(db)
+        .await?
+        .ok_or_else(|| HttpError::not_found(None, None))?;
+
+    record_endpoint_test(
+        cache,
+        &EndpointTestTimesCacheKey::new(&app.org_id, &app.id, &endp.id),
+    )
+    .await?;
+
+    // The endpoint is sent to with the same settings it's dispatched to with, e.g. the headers
+    // and signing secret inherited from its group and application
+    let create_message_app = CreateMessageApp::layered_fetch(
+        cache,
+        db,
+        Some(app.clone()),
+        app.org_id.clone(),
+        app.id.clone(),
+        Duration::from_secs(30),
+    )
+    .await?
+    .ok_or_else(|| HttpError::not_found(None, None))?;
+    let endp = create_message_app
+        .endpoint(&endp.id)
+        .ok_or_else(|| HttpError::not_found(None, None))?;
+
+    let res = send_test_webhook(cfg, webhook_client, endp, TEST_WEBHOOK_PAYLOAD).await?;
+
+    Ok(Json(EndpointTestOut {
+        status_code: res.status_code,
+        latency_ms: res.latency.as_millis().try_into().unwrap_or(u64::MAX),
+        response_body: res.response_body,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{record_endpoint_test, EndpointTestTimesCacheKey, TEST_RATE_LIMIT};
+    use crate::core::{
+        cache::memory,
+        types::{ApplicationId, BaseId, EndpointId, OrganizationId},
+    };
+
+    #[tokio::test]
+    async fn test_endpoint_test_rate_limit() {
+        let cache = memory::new();
+        let org_id = OrganizationId::new(None, None);
+        let app_id = ApplicationId::new(None, None);
+
+        let key = EndpointTestTimesCacheKey::new(&org_id, &app_id, &EndpointId::new(None, None));
+        for _ in 0..TEST_RATE_LIMIT {
+            record_endpoint_test(&cache, &key).await.unwrap();
+        }
+        assert!(record_endpoint_test(&cache, &key).await.is_err());
+
+        // Other endpoints have their own limit
+        let key = EndpointTestTimesCacheKey::new(&org_id, &app_id, &EndpointId::new(None, None));
+        record_endpoint_test(&cache, &key).await.unwrap();
+    }
+}
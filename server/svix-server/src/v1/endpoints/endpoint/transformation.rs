@@ -0,0 +1,54 @@
+use axum::{
+    extract::{Path, State},
+    Json,
+};
+use sea_orm::ActiveModelTrait;
+use svix_server_derive::aide_annotate;
+
+use super::{EndpointTransformationIn, EndpointTransformationOut};
+use crate::{
+    core::permissions,
+    db::models::endpoint,
+    error::{HttpError, Result},
+    v1::utils::{ApplicationEndpointPath, ModelIn, NoContent, ValidatedJson},
+    AppState,
+};
+
+/// Get the transformation webhook payloads are run through before being sent to the endpoint
+#[aide_annotate(op_id = "v1.endpoint.transformation-get")]
+pub(super) async fn get_endpoint_transformation(
+    State(AppState { ref db, .. }): State<AppState>,
+    Path(ApplicationEndpointPath { endpoint_id, .. }): Path<ApplicationEndpointPath>,
+    permissions::Application { app }: permissions::Application,
+) -> Result<Json<EndpointTransformationOut>> {
+    let endp = endpoint::Entity::secure_find_by_id_or_uid(app.id, endpoint_id)
+        .one(db)
+        .await?
+        .ok_or_else(|| HttpError::not_found(None, None))?;
+
+    Ok(Json(endp.into()))
+}
+
+/// Set the transformation webhook payloads are run through before being sent to the endpoint.
+///
+/// The transformed payload is the one signed and sent. Transformations are run by the transformer
+/// the server was built with; while there's none, webhooks to endpoints with an enabled
+/// transformation fail instead of being sent untransformed.
+#[aide_annotate(op_id = "v1.endpoint.transformation-update")]
+pub(super) async fn update_endpoint_transformation(
+    State(AppState { ref db, .. }): State<AppState>,
+    Path(ApplicationEndpointPath { endpoint_id, .. }): Path<ApplicationEndpointPath>,
+    permissions::Application { app }: permissions::Application,
+    ValidatedJson(data): ValidatedJson<EndpointTransformationIn>,
+) -> Result<NoContent> {
+    let endp = endpoint::Entity::secure_find_by_id_or_uid(app.id.clone(), endpoint_id)
+        .one(db)
+        .await?
+        .ok_or_else(|| HttpError::not_found(None, None))?;
+
+    let mut endp: endpoint::ActiveModel = endp.into();
+    data.update_model(&mut endp);
+    endp.update(db).await?;
+
+    Ok(NoContent)
+}
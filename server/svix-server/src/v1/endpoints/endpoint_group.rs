@@ -0,0 +1,292 @@
+// SPDX-FileCopyrightText: © 2022 Svix Authors
+// SPDX-License-Identifier: MIT
+
+use aide::axum::{
+    routing::{get_with, post_with},
+    ApiRouter,
+};
+use axum::{
+    extract::{Path, State},
+    Json,
+};
+use chrono::{DateTime, Utc};
+use schemars::JsonSchema;
+use sea_orm::{entity::prelude::*, ActiveValue::Set};
+use serde::{Deserialize, Serialize};
+use svix_server_derive::{aide_annotate, ModelIn, ModelOut};
+use validator::{Validate, ValidationError};
+
+use crate::{
+    core::{
+        permissions,
+        types::{EndpointGroupId, EndpointHeaders, RetrySchedule},
+    },
+    db::models::endpointgroup,
+    error::{HttpError, Result},
+    v1::utils::{
+        apply_pagination, openapi_tag, validate_no_control_characters, validation_error,
+        ApplicationEndpointGroupPath, ApplicationPath, IteratorDirection, JsonStatus, ListResponse,
+        ModelIn, ModelOut, NoContent, Ordering, Pagination, PaginationLimit, ReversibleIterator,
+        ValidatedJson, ValidatedQuery,
+    },
+    AppState,
+};
+
+/// The longest delay that can be set between retries, in seconds
+const MAX_RETRY_DELAY_SECS: u32 = 24 * 60 * 60;
+
+fn example_endpoint_group_name() -> &'static str {
+    "Partner endpoints"
+}
+
+fn example_retry_schedule() -> Vec<u32> {
+    vec![5, 300, 1800]
+}
+
+fn validate_retry_schedule_override(schedule: &RetrySchedule) -> Result<(), ValidationError> {
+    if schedule
+        .0
+        .iter()
+        .all(|delay| (1..=MAX_RETRY_DELAY_SECS).contains(delay))
+    {
+        Ok(())
+    } else {
+        Err(validation_error(
+            Some("range"),
+            Some("Retry delays must be between 1 and 86400 seconds"),
+        ))
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, Validate, ModelIn, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct EndpointGroupIn {
+    #[validate(
+        length(
+            min = 1,
+            message = "Endpoint group names must be at least one character"
+        ),
+        custom = "validate_no_control_characters"
+    )]
+    #[schemars(example = "example_endpoint_group_name")]
+    pub name: String,
+
+    /// Headers sent to every endpoint in the group, in addition to the endpoint's own headers
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default_headers: Option<EndpointHeaders>,
+
+    /// The number of seconds to wait before each retry, used instead of the server's retry
+    /// schedule
+    #[validate(custom = "validate_retry_schedule_override")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schemars(example = "example_retry_schedule")]
+    pub retry_schedule_override: Option<RetrySchedule>,
+
+    /// Used by the endpoints in the group which don't set a rate limit of their own
+    #[validate(range(
+        min = 1,
+        message = "Endpoint group rate limits must be at least one if set"
+    ))]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rate_limit: Option<u16>,
+}
+
+// FIXME: This can and should be a derive macro
+impl ModelIn for EndpointGroupIn {
+    type ActiveModel = endpointgroup::ActiveModel;
+
+    fn update_model(self, model: &mut Self::ActiveModel) {
+        let EndpointGroupIn {
+            name,
+            default_headers,
+            retry_schedule_override,
+            rate_limit,
+        } = self;
+
+        model.name = Set(name);
+        model.default_headers = Set(default_headers);
+        model.retry_schedule_override = Set(retry_schedule_override);
+        model.rate_limit = Set(rate_limit.map(|x| x.into()));
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, ModelOut, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct EndpointGroupOut {
+    pub id: EndpointGroupId,
+    #[schemars(example = "example_endpoint_group_name")]
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub default_headers: Option<EndpointHeaders>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schemars(example = "example_retry_schedule")]
+    pub retry_schedule_override: Option<RetrySchedule>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rate_limit: Option<u16>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+// FIXME: This can and should be a derive macro
+impl From<endpointgroup::Model> for EndpointGroupOut {
+    fn from(model: endpointgroup::Model) -> Self {
+        Self {
+            id: model.id,
+            name: model.name,
+            default_headers: model.default_headers,
+            retry_schedule_override: model.retry_schedule_override,
+            rate_limit: model.rate_limit.map(|x| x as u16),
+            created_at: model.created_at.into(),
+            updated_at: model.updated_at.into(),
+        }
+    }
+}
+
+/// List the application's endpoint groups.
+#[aide_annotate(op_id = "v1.endpoint-group.list")]
+async fn list_endpoint_groups(
+    State(AppState { ref db, .. }): State<AppState>,
+    _: Path<ApplicationPath>,
+    ValidatedQuery(pagination): ValidatedQuery<Pagination<ReversibleIterator<EndpointGroupId>>>,
+    permissions::Application { app }: permissions::Application,
+) -> Result<Json<ListResponse<EndpointGroupOut>>> {
+    let PaginationLimit(limit) = pagination.limit;
+    let iterator = pagination.iterator;
+    let iter_direction = iterator
+        .as_ref()
+        .map_or(IteratorDirection::Normal, |iter| iter.direction());
+
+    let query = apply_pagination(
+        endpointgroup::Entity::secure_find(app.id),
+        endpointgroup::Column::Id,
+        limit,
+        iterator,
+        pagination.order.unwrap_or(Ordering::Descending),
+    );
+
+    Ok(Json(EndpointGroupOut::list_response(
+        query.all(db).await?.into_iter().map(Into::into).collect(),
+        limit as usize,
+        iter_direction,
+    )))
+}
+
+/// Create a new endpoint group for the application.
+///
+/// Endpoints added to the group inherit its settings, unless they set their own.
+#[aide_annotate(op_id = "v1.endpoint-group.create")]
+async fn create_endpoint_group(
+    State(AppState { ref db, .. }): State<AppState>,
+    _: Path<ApplicationPath>,
+    permissions::Application { app }: permissions::Application,
+    ValidatedJson(data): ValidatedJson<EndpointGroupIn>,
+) -> Result<JsonStatus<201, EndpointGroupOut>> {
+    let mut group = endpointgroup::ActiveModel::new(app.id);
+    data.update_model(&mut group);
+    let group = group.insert(db).await?;
+
+    Ok(JsonStatus(group.into()))
+}
+
+/// Get an endpoint group.
+#[aide_annotate(op_id = "v1.endpoint-group.get")]
+async fn get_endpoint_group(
+    State(AppState { ref db, .. }): State<AppState>,
+    Path(ApplicationEndpointGroupPath { group_id, .. }): Path<ApplicationEndpointGroupPath>,
+    permissions::Application { app }: permissions::Application,
+) -> Result<Json<EndpointGroupOut>> {
+    let group = endpointgroup::Entity::secure_find_by_id(app.id, group_id)
+        .one(db)
+        .await?
+        .ok_or_else(|| HttpError::not_found(None, None))?;
+
+    Ok(Json(group.into()))
+}
+
+/// Update an endpoint group.
+#[aide_annotate(op_id = "v1.endpoint-group.update")]
+async fn update_endpoint_group(
+    State(AppState { ref db, .. }): State<AppState>,
+    Path(ApplicationEndpointGroupPath { group_id, .. }): Path<ApplicationEndpointGroupPath>,
+    permissions::Application { app }: permissions::Application,
+    ValidatedJson(data): ValidatedJson<EndpointGroupIn>,
+) -> Result<Json<EndpointGroupOut>> {
+    let group = endpointgroup::Entity::secure_find_by_id(app.id, group_id)
+        .one(db)
+        .await?
+        .ok_or_else(|| HttpError::not_found(None, None))?;
+
+    let mut group: endpointgroup::ActiveModel = group.into();
+    data.update_model(&mut group);
+    let group = group.update(db).await?;
+
+    Ok(Json(group.into()))
+}
+
+/// Delete an endpoint group.
+///
+/// Endpoints in the group are kept, but stop inheriting its settings.
+#[aide_annotate(op_id = "v1.endpoint-group.delete")]
+async fn delete_endpoint_group(
+    State(AppState { ref db, .. }): State<AppState>,
+    Path(ApplicationEndpointGroupPath { group_id, .. }): Path<ApplicationEndpointGroupPath>,
+    permissions::Application { app }: permissions::Application,
+) -> Result<NoContent> {
+    let group = endpointgroup::Entity::secure_find_by_id(app.id, group_id)
+        .one(db)
+        .await?
+        .ok_or_else(|| HttpError::not_found(None, None))?;
+
+    group.delete(db).await?;
+
+    Ok(NoContent)
+}
+
+pub fn router() -> ApiRouter<AppState> {
+    let tag = openapi_tag("Endpoint Group");
+    ApiRouter::new()
+        .api_route_with(
+            "/app/:app_id/endpoint-group",
+            post_with(create_endpoint_group, create_endpoint_group_operation)
+                .get_with(list_endpoint_groups, list_endpoint_groups_operation),
+            &tag,
+        )
+        .api_route_with(
+            "/app/:app_id/endpoint-group/:group_id",
+            get_with(get_endpoint_group, get_endpoint_group_operation)
+                .put_with(update_endpoint_group, update_endpoint_group_operation)
+                .delete_with(delete_endpoint_group, delete_endpoint_group_operation),
+            tag,
+        )
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use validator::Validate;
+
+    use super::EndpointGroupIn;
+
+    #[test]
+    fn test_endpoint_group_in_validation() {
+        let valid: EndpointGroupIn = serde_json::from_value(json!({
+            "name": "group",
+            "defaultHeaders": {"x-group": "1"},
+            "retryScheduleOverride": [1, 60, 86400],
+            "rateLimit": 1,
+        }))
+        .unwrap();
+        valid.validate().unwrap();
+
+        let invalid_cases = [
+            json!({ "name": "" }),
+            json!({ "name": "group", "retryScheduleOverride": [0] }),
+            json!({ "name": "group", "retryScheduleOverride": [86401] }),
+            json!({ "name": "group", "rateLimit": 0 }),
+        ];
+        for case in invalid_cases {
+            let group: EndpointGroupIn = serde_json::from_value(case.clone()).unwrap();
+            assert!(group.validate().is_err(), "{case} should be invalid");
+        }
+    }
+}
@@ -108,7 +108,7 @@ async fn health(
             &HealthCheckCacheKey("health_check_value".to_owned()),
             &HealthCheckCacheValue(()),
             // Expires after this time, so it won't pollute the DB
-            Duration::from_millis(100),
+            Some(Duration::from_millis(100)),
         )
         .await
         .into();
@@ -0,0 +1,45 @@
+// SPDX-FileCopyrightText: © 2022 Svix Authors
+// SPDX-License-Identifier: MIT
+
+//! Routes for operating the server itself, which aren't part of the public API and so are left
+//! out of the OpenAPI spec.
+
+use axum::{extract::State, routing::get, Json, Router};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    core::{cache::CacheBehavior, permissions},
+    error::{Error, Result},
+    AppState,
+};
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StoreStatsOut {
+    /// The kind of cache the server runs with: `memory`, `redis`, `redis-cluster` or `none`
+    pub backend: String,
+    /// The number of keys in the cache, if the backend can tell
+    pub key_count: Option<u64>,
+    /// The memory used by the cache in bytes, if the backend can tell
+    pub memory_bytes: Option<u64>,
+}
+
+/// Reports how much the cache holds. The cache keeps track of things like failing endpoints, so
+/// unexpected growth can be a sign of trouble. The numbers cover every organization, so only the
+/// management organization can read them.
+async fn store_stats(
+    State(AppState { cache, .. }): State<AppState>,
+    _: permissions::Management,
+) -> Result<Json<StoreStatsOut>> {
+    let stats = cache.stats().await.map_err(Error::cache)?;
+
+    Ok(Json(StoreStatsOut {
+        backend: stats.backend.to_owned(),
+        key_count: stats.key_count,
+        memory_bytes: stats.memory_bytes,
+    }))
+}
+
+pub fn router() -> Router<AppState> {
+    Router::new().route("/internal/store-stats", get(store_stats))
+}
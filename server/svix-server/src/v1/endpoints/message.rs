@@ -1,41 +1,59 @@
 // SPDX-FileCopyrightText: © 2022 Svix Authors
 // SPDX-License-Identifier: MIT
 
-use aide::axum::{
-    routing::{delete_with, get_with, post_with},
-    ApiRouter,
+use std::collections::HashMap;
+
+use aide::{
+    axum::{
+        routing::{delete_with, get_with, post_with},
+        ApiRouter,
+    },
+    openapi::{
+        HeaderStyle, Operation, Parameter, ParameterData, ParameterSchemaOrContent, ReferenceOr,
+        SchemaObject,
+    },
+    OperationInput,
 };
 use axum::{
-    extract::{Path, State},
+    async_trait,
+    extract::{FromRequestParts, Path, State},
     Json,
 };
 use chrono::{DateTime, Duration, Utc};
 use futures::FutureExt;
+use http::request::Parts;
 use hyper::StatusCode;
 use schemars::JsonSchema;
-use sea_orm::{entity::prelude::*, ActiveValue::Set, IntoActiveModel, TransactionTrait};
+use sea_orm::{
+    entity::prelude::*, sea_query::Query, ActiveValue::Set, IntoActiveModel, TransactionTrait,
+};
 use serde::{Deserialize, Serialize};
 use serde_json::value::RawValue;
 use svix_server_derive::{aide_annotate, ModelIn, ModelOut};
 use validator::{Validate, ValidationError};
 
+use super::endpoint::validate_endpoint_metadata;
 use crate::{
     core::{
-        cache::Cache,
+        cache::{kv_def, Cache, CacheBehavior, CacheKey, CacheValue},
         message_app::CreateMessageApp,
+        org_quota::{self, QuotaState},
         permissions,
         types::{
-            EndpointId, EventChannel, EventChannelSet, EventTypeName, EventTypeNameSet,
-            MessageAttemptTriggerType, MessageId, MessageUid,
+            metadata::Metadata, ApplicationId, EndpointId, EventChannel, EventChannelSet,
+            EventTypeName, EventTypeNameSet, MessageAttemptTriggerType, MessageId,
+            MessageSignedMetadata, MessageStatus, MessageTagSet, MessageUid, OrganizationId,
+            PayloadVersion,
         },
     },
-    db::models::{application, message, messagecontent},
+    db::models::{application, message, messagecontent, messagedestination, messagetag},
     error::{Error, HttpError, Result},
     queue::{MessageTaskBatch, TaskQueueProducer},
     v1::utils::{
         filter_and_paginate_time_limited, openapi_tag, validation_error, ApplicationMsgPath,
-        EventTypesQueryParams, JsonStatus, ListResponse, ModelIn, ModelOut, PaginationDescending,
-        PaginationLimit, ReversibleIterator, ValidatedJson, ValidatedQuery,
+        EventTypesQueryParams, JsonStatusReplayable, ListResponse, MessageTagsQueryParams, ModelIn,
+        ModelOut, PaginationDescending, PaginationLimit, ReversibleIterator, ValidatedJson,
+        ValidatedQuery,
     },
     AppState,
 };
@@ -52,6 +70,18 @@ pub fn validate_channels_msg(channels: &EventChannelSet) -> Result<(), Validatio
     }
 }
 
+pub fn validate_tags_msg(tags: &MessageTagSet) -> Result<(), ValidationError> {
+    let len = tags.0.len();
+    if !(1..=5).contains(&len) {
+        Err(validation_error(
+            Some("tags"),
+            Some("Tags must have at least 1 and at most 5 items, or be set to null."),
+        ))
+    } else {
+        Ok(())
+    }
+}
+
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct RawPayload(pub Box<RawValue>);
 
@@ -120,16 +150,45 @@ pub struct MessageIn {
     #[serde(skip_serializing_if = "Option::is_none")]
     #[schemars(example = "example_channel_set", length(min = 1, max = 5))]
     pub channels: Option<EventChannelSet>,
+    /// List of free-form labels that messages can be filtered by when listing them
+    #[validate(custom = "validate_tags_msg")]
+    #[validate]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schemars(example = "example_tag_set", length(min = 1, max = 5))]
+    pub tags: Option<MessageTagSet>,
     #[validate(range(min = 5, max = 90))]
     #[serde(default = "default_90")]
     #[schemars(example = "default_90")]
     pub payload_retention_period: i64,
+    /// Optional time after which the message is no longer relevant. Deliveries (including retries)
+    /// that haven't happened by then are skipped, and marked as expired.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub deadline: Option<DateTime<Utc>>,
+    /// Key-value pairs which are covered by the message's signature along with its payload, so
+    /// they can't be changed without invalidating it. They're sent to endpoints in the
+    /// `svix-signed-metadata` header.
+    #[validate]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub signed_metadata: Option<MessageSignedMetadata>,
+    /// The semantic version of the schema the payload follows. It's sent to endpoints in the
+    /// `svix-payload-version` header, and endpoints with a higher `minPayloadVersion` don't receive
+    /// the message.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub payload_version: Option<PayloadVersion>,
+    /// Only endpoints whose metadata has all of these key-value pairs receive the message
+    #[validate(custom = "validate_endpoint_metadata")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub endpoint_metadata_selector: Option<Metadata>,
 }
 
 fn example_channel_set() -> Vec<&'static str> {
     vec!["project_123", "group_2"]
 }
 
+fn example_tag_set() -> Vec<&'static str> {
+    vec!["project-123", "priority-high"]
+}
+
 fn example_payload() -> serde_json::Value {
     serde_json::json!({
         "email": "test@example.com",
@@ -146,7 +205,12 @@ impl ModelIn for MessageIn {
             uid,
             event_type,
             channels,
+            tags,
             payload_retention_period,
+            deadline,
+            signed_metadata,
+            payload_version,
+            endpoint_metadata_selector,
             ..
         } = self;
 
@@ -156,6 +220,11 @@ impl ModelIn for MessageIn {
         model.event_type = Set(event_type);
         model.expiration = Set(expiration.with_timezone(&Utc).into());
         model.channels = Set(channels);
+        model.tags = Set(tags);
+        model.deadline = Set(deadline.map(Into::into));
+        model.signed_metadata = Set(signed_metadata);
+        model.payload_version = Set(payload_version);
+        model.endpoint_metadata_selector = Set(endpoint_metadata_selector);
     }
 }
 
@@ -171,9 +240,24 @@ pub struct MessageOut {
     /// List of free-form identifiers that endpoints can filter by
     #[schemars(length(min = 1, max = 5), example = "example_channel_set")]
     pub channels: Option<EventChannelSet>,
+    /// List of free-form labels that messages can be filtered by when listing them
+    #[schemars(length(min = 1, max = 5), example = "example_tag_set")]
+    pub tags: Option<MessageTagSet>,
     pub id: MessageId,
     #[serde(rename = "timestamp")]
     pub created_at: DateTime<Utc>,
+    /// The time after which the message is no longer delivered, if any
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deadline: Option<DateTime<Utc>>,
+    /// Key-value pairs which are covered by the message's signature along with its payload
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signed_metadata: Option<MessageSignedMetadata>,
+    /// The semantic version of the schema the payload follows, if any
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub payload_version: Option<PayloadVersion>,
+    /// Only endpoints whose metadata has all of these key-value pairs receive the message
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub endpoint_metadata_selector: Option<Metadata>,
 }
 
 impl MessageOut {
@@ -198,8 +282,13 @@ impl MessageOut {
             event_type: model.event_type,
             payload,
             channels: model.channels,
+            tags: model.tags,
             id: model.id,
             created_at: model.created_at.into(),
+            deadline: model.deadline.map(Into::into),
+            signed_metadata: model.signed_metadata,
+            payload_version: model.payload_version,
+            endpoint_metadata_selector: model.endpoint_metadata_selector,
         }
     }
 
@@ -209,8 +298,13 @@ impl MessageOut {
             event_type: model.event_type,
             payload: RawPayload::from_string("{}".to_string()).expect("Can never fail"),
             channels: model.channels,
+            tags: model.tags,
             id: model.id,
             created_at: model.created_at.into(),
+            deadline: model.deadline.map(Into::into),
+            signed_metadata: model.signed_metadata,
+            payload_version: model.payload_version,
+            endpoint_metadata_selector: model.endpoint_metadata_selector,
         }
     }
 }
@@ -228,6 +322,9 @@ pub struct ListMessagesQueryParams {
     /// Filter response based on the channel
     #[validate]
     channel: Option<EventChannel>,
+    /// Filter response based on the delivery status, only including messages that have it for at
+    /// least one endpoint
+    status: Option<MessageStatus>,
     /// Only include items created before a certain date
     before: Option<DateTime<Utc>>,
     /// Only include items created after a certain date
@@ -242,17 +339,20 @@ pub struct ListMessagesQueryParams {
 /// The `before` parameter lets you filter all items created before a certain date and is ignored if an iterator is passed.
 /// The `after` parameter lets you filter all items created after a certain date and is ignored if an iterator is passed.
 /// `before` and `after` cannot be used simultaneously.
+/// The `tag` parameter can be passed multiple times, in which case only messages having all of the given tags are returned.
 #[aide_annotate(op_id = "v1.message.list")]
 async fn list_messages(
     State(AppState { ref db, .. }): State<AppState>,
     ValidatedQuery(pagination): ValidatedQuery<PaginationDescending<ReversibleIterator<MessageId>>>,
     ValidatedQuery(ListMessagesQueryParams {
         channel,
+        status,
         with_content,
         before,
         after,
     }): ValidatedQuery<ListMessagesQueryParams>,
     EventTypesQueryParams(event_types): EventTypesQueryParams,
+    MessageTagsQueryParams(tags): MessageTagsQueryParams,
     permissions::Application { app }: permissions::Application,
 ) -> Result<Json<ListResponse<MessageOut>>> {
     let PaginationLimit(limit) = pagination.limit;
@@ -267,6 +367,33 @@ async fn list_messages(
         query = query.filter(Expr::cust_with_values("channels @> $1", [channel.jsonb()]));
     }
 
+    if let Some(status) = status {
+        query = query.filter(
+            message::Column::Id.in_subquery(
+                Query::select()
+                    .column(messagedestination::Column::MsgId)
+                    .from(messagedestination::Entity)
+                    .and_where(messagedestination::Column::Status.eq(status))
+                    .to_owned(),
+            ),
+        );
+    }
+
+    if let Some(MessageTagSet(tags)) = tags {
+        // Every tag must match, so each one narrows the result down further
+        for tag in tags {
+            query = query.filter(
+                message::Column::Id.in_subquery(
+                    Query::select()
+                        .column(messagetag::Column::MsgId)
+                        .from(messagetag::Entity)
+                        .and_where(messagetag::Column::Tag.eq(tag))
+                        .to_owned(),
+                ),
+            );
+        }
+    }
+
     let (query, iter_direction) = filter_and_paginate_time_limited(
         query,
         message::Column::Id,
@@ -279,21 +406,109 @@ async fn list_messages(
 
     let msgs_and_content: Vec<(message::Model, Option<messagecontent::Model>)> =
         query.all(db).await?.into_iter().collect();
-    let into = |(msg, content): (message::Model, Option<messagecontent::Model>)| {
+    let into = |(msg, content): (message::Model, Option<messagecontent::Model>)| -> Result<_> {
         if with_content {
-            MessageOut::from_msg_and_payload(msg, content.map(|c| c.payload))
+            let payload = content.map(|c| c.into_payload()).transpose()?.flatten();
+            Ok(MessageOut::from_msg_and_payload(msg, payload))
         } else {
-            MessageOut::without_payload(msg)
+            Ok(MessageOut::without_payload(msg))
         }
     };
 
     Ok(Json(MessageOut::list_response(
-        msgs_and_content.into_iter().map(into).collect(),
+        msgs_and_content
+            .into_iter()
+            .map(into)
+            .collect::<Result<_>>()?,
         limit as usize,
         iter_direction,
     )))
 }
 
+/// The header with which clients can safely retry creating a message
+const IDEMPOTENCY_KEY_HEADER: &str = "x-svix-idempotency-key";
+
+/// The value of the optional [`IDEMPOTENCY_KEY_HEADER`]
+pub struct MessageIdempotencyKeyHeader(pub Option<String>);
+
+#[async_trait]
+impl<S> FromRequestParts<S> for MessageIdempotencyKeyHeader
+where
+    S: Send + Sync,
+{
+    type Rejection = Error;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self> {
+        let Some(value) = parts.headers.get(IDEMPOTENCY_KEY_HEADER) else {
+            return Ok(Self(None));
+        };
+
+        match value.to_str() {
+            Ok(key) if !key.is_empty() && key.len() <= 256 => Ok(Self(Some(key.to_owned()))),
+            _ => Err(HttpError::bad_request(
+                None,
+                Some(format!(
+                    "`{IDEMPOTENCY_KEY_HEADER}` must be between 1 and 256 visible ASCII characters"
+                )),
+            )
+            .into()),
+        }
+    }
+}
+
+impl OperationInput for MessageIdempotencyKeyHeader {
+    fn operation_input(ctx: &mut aide::gen::GenContext, operation: &mut Operation) {
+        let schema = String::json_schema(&mut ctx.schema);
+
+        operation
+            .parameters
+            .push(ReferenceOr::Item(Parameter::Header {
+            parameter_data: ParameterData {
+                name: IDEMPOTENCY_KEY_HEADER.to_owned(),
+                description: Some(
+                    "Key identifying the message, so that creating it again returns the original \
+                     message instead of creating a new one"
+                        .to_owned(),
+                ),
+                required: false,
+                deprecated: None,
+                format: ParameterSchemaOrContent::Schema(SchemaObject {
+                    json_schema: schema,
+                    external_docs: None,
+                    example: None,
+                }),
+                example: None,
+                examples: Default::default(),
+                explode: None,
+                extensions: Default::default(),
+            },
+            style: HeaderStyle::Simple,
+        }));
+    }
+}
+
+/// How long a request has to create its message once it's claimed an [`IDEMPOTENCY_KEY_HEADER`],
+/// after which the key can be claimed again
+const MESSAGE_IDEMPOTENCY_CLAIM_TTL: std::time::Duration = std::time::Duration::from_secs(30);
+
+/// Remembers the message created with a given [`IDEMPOTENCY_KEY_HEADER`]
+#[derive(Deserialize, Serialize)]
+pub enum MessageIdempotencyCacheValue {
+    /// Claimed by a request which is still creating the message
+    Creating,
+    Created {
+        msg_id: MessageId,
+    },
+}
+
+kv_def!(MessageIdempotencyCacheKey, MessageIdempotencyCacheValue);
+
+impl MessageIdempotencyCacheKey {
+    pub fn new(org_id: &OrganizationId, app_id: &ApplicationId, key: &str) -> Self {
+        Self(format!("SVIX_MSG_IDEMPOTENCY_{org_id}_{app_id}_{key}"))
+    }
+}
+
 #[derive(Debug, Deserialize, Validate, JsonSchema)]
 pub struct CreateMessageQueryParams {
     /// When `true` message payloads are included in the response
@@ -310,36 +525,184 @@ pub struct CreateMessageQueryParams {
 /// Messages can also have `channels`, which similar to event types let endpoints filter by them. Unlike event types, messages can have multiple channels, and channels don't imply a specific message content or schema.
 ///
 /// The `payload` property is the webhook's body (the actual webhook message). Svix supports payload sizes of up to ~350kb, though it's generally a good idea to keep webhook payloads small, probably no larger than 40kb.
+///
+/// When the `x-svix-idempotency-key` header is set, creating a message with the same key again (e.g. when retrying after a network error) returns the original message with a 200 status instead of creating a new one. While the original request is still creating its message, other requests with the same key get a 409 error.
+///
+/// Organizations with a monthly message quota get a 429 error once it's used up, with a `Retry-After` header giving the number of seconds until it's reset.
 #[aide_annotate(op_id = "v1.message.create")]
 async fn create_message(
     State(AppState {
         ref db,
         queue_tx,
         cache,
+        cfg,
         ..
     }): State<AppState>,
     ValidatedQuery(CreateMessageQueryParams { with_content }): ValidatedQuery<
         CreateMessageQueryParams,
     >,
+    MessageIdempotencyKeyHeader(idempotency_key): MessageIdempotencyKeyHeader,
     permissions::OrganizationWithApplication { app }: permissions::OrganizationWithApplication,
     ValidatedJson(data): ValidatedJson<MessageIn>,
-) -> Result<JsonStatus<202, MessageOut>> {
-    Ok(JsonStatus(
-        create_message_inner(db, queue_tx, cache, with_content, None, data, app).await?,
-    ))
+) -> Result<JsonStatusReplayable<MessageOut>> {
+    let cache_key = idempotency_key
+        .as_deref()
+        .map(|key| MessageIdempotencyCacheKey::new(&app.org_id, &app.id, key));
+
+    if let Some(cache_key) = &cache_key {
+        // Claimed before the message is created, so that concurrent retries don't both create one
+        while !cache
+            .set_if_not_exists(
+                cache_key,
+                &MessageIdempotencyCacheValue::Creating,
+                MESSAGE_IDEMPOTENCY_CLAIM_TTL,
+            )
+            .await
+            .map_err(Error::cache)?
+        {
+            let existing = cache
+                .get::<MessageIdempotencyCacheValue>(cache_key)
+                .await
+                .map_err(Error::cache)?;
+            match &existing {
+                Some(MessageIdempotencyCacheValue::Creating) => {
+                    return Err(HttpError::conflict(
+                        Some("idempotency_conflict".to_owned()),
+                        Some(
+                            "A message with this idempotency key is still being created."
+                                .to_owned(),
+                        ),
+                    )
+                    .into());
+                }
+                Some(MessageIdempotencyCacheValue::Created { msg_id }) => {
+                    if let Some((msg, content)) =
+                        message::Entity::secure_find_by_id(app.id.clone(), msg_id.clone())
+                            .find_also_related(messagecontent::Entity)
+                            .one(db)
+                            .await?
+                    {
+                        let msg = if with_content {
+                            MessageOut::from_msg_and_payload(
+                                msg,
+                                content.map(|c| c.into_payload()).transpose()?.flatten(),
+                            )
+                        } else {
+                            MessageOut::without_payload(msg)
+                        };
+                        return Ok(JsonStatusReplayable::Replayed(msg));
+                    }
+
+                    // The original message has been deleted since, so a new one is created in
+                    // its place, unless another request gets there first
+                    if cache
+                        .compare_and_swap(
+                            cache_key,
+                            existing.as_ref(),
+                            &MessageIdempotencyCacheValue::Creating,
+                            Some(MESSAGE_IDEMPOTENCY_CLAIM_TTL),
+                        )
+                        .await
+                        .map_err(Error::cache)?
+                    {
+                        break;
+                    }
+                }
+                // Expired in the meantime, so it can be claimed again
+                None => {}
+            }
+        }
+    }
+
+    let inserted = async {
+        let create_message_app = fetch_create_message_app(&cache, db, &app).await?;
+        let (msg, msg_content) = insert_message(
+            db,
+            &cache,
+            0,
+            cfg.compress_payload_threshold_bytes,
+            data,
+            app.clone(),
+        )
+        .await?;
+        Ok::<_, Error>((create_message_app, msg, msg_content))
+    }
+    .await;
+    let (create_message_app, msg, msg_content) = match inserted {
+        Ok(inserted) => inserted,
+        Err(e) => {
+            if let Some(cache_key) = &cache_key {
+                // Nothing was written, so let go of the key for retrying the request to create
+                // the message
+                if let Err(e) = cache.delete(cache_key).await {
+                    tracing::warn!("Failed to release the message's idempotency key: {e}");
+                }
+            }
+            return Err(e);
+        }
+    };
+
+    if let Some(cache_key) = &cache_key {
+        // Stored as soon as the message exists, so that retrying the request never creates it
+        // again, even if the rest of this one fails. Failing the request here would likewise only
+        // lead to it being created again.
+        if let Err(e) = cache
+            .set(
+                cache_key,
+                &MessageIdempotencyCacheValue::Created {
+                    msg_id: msg.id.clone(),
+                },
+                Some(cfg.message_idempotency_key_ttl),
+            )
+            .await
+        {
+            tracing::warn!("Failed to store the message's idempotency key: {e}");
+        }
+    }
+
+    queue_message(queue_tx, &create_message_app, None, &app, &msg).await?;
+
+    Ok(JsonStatusReplayable::Accepted(message_out(
+        with_content,
+        msg,
+        msg_content,
+    )?))
 }
 
+#[allow(clippy::too_many_arguments)]
 pub(crate) async fn create_message_inner(
     db: &DatabaseConnection,
     queue_tx: TaskQueueProducer,
     cache: Cache,
     with_content: bool,
     force_endpoint: Option<EndpointId>,
+    chain_depth: i16,
+    compress_payload_threshold: Option<usize>,
     data: MessageIn,
     app: application::Model,
 ) -> Result<MessageOut> {
-    let create_message_app = CreateMessageApp::layered_fetch(
+    let create_message_app = fetch_create_message_app(&cache, db, &app).await?;
+    let (msg, msg_content) = insert_message(
+        db,
         &cache,
+        chain_depth,
+        compress_payload_threshold,
+        data,
+        app.clone(),
+    )
+    .await?;
+    queue_message(queue_tx, &create_message_app, force_endpoint, &app, &msg).await?;
+
+    message_out(with_content, msg, msg_content)
+}
+
+async fn fetch_create_message_app(
+    cache: &Cache,
+    db: &DatabaseConnection,
+    app: &application::Model,
+) -> Result<CreateMessageApp> {
+    CreateMessageApp::layered_fetch(
+        cache,
         db,
         Some(app.clone()),
         app.org_id.clone(),
@@ -348,30 +711,90 @@ pub(crate) async fn create_message_inner(
     )
     .await?
     // Should never happen since you're giving it an existing Application, but just in case
-    .ok_or_else(|| Error::generic(format!("Application doesn't exist: {}", app.id)))?;
+    .ok_or_else(|| Error::generic(format!("Application doesn't exist: {}", app.id)))
+}
+
+/// Writes the message to the database, counting it against the organization's quota. Nothing is
+/// written if this fails.
+async fn insert_message(
+    db: &DatabaseConnection,
+    cache: &Cache,
+    chain_depth: i16,
+    compress_payload_threshold: Option<usize>,
+    data: MessageIn,
+    app: application::Model,
+) -> Result<(message::Model, messagecontent::Model)> {
+    // Counted against the quota before the message is created, and given back if that fails
+    let org_id = app.org_id.clone();
+    if let QuotaState::Exceeded { retry_in } = org_quota::acquire(db, cache, &org_id).await? {
+        return Err(HttpError::too_many_requests(
+            Some("quota_exceeded".to_owned()),
+            Some("The organization has used up its monthly message quota.".to_owned()),
+        )
+        .with_retry_after(retry_in)
+        .into());
+    }
 
     let payload = data.payload.to_string().into_bytes();
+    let tags = data.tags.clone();
     let msg = message::ActiveModel {
         app_id: Set(app.id.clone()),
         org_id: Set(app.org_id),
+        chain_depth: Set(chain_depth),
         ..data.into()
     };
 
-    let (msg, msg_content) = db
+    let created = db
         .transaction(|txn| {
             async move {
                 let msg = msg.insert(txn).await?;
-                let msg_content = messagecontent::ActiveModel::new(msg.id.clone(), payload);
+                let msg_content = messagecontent::ActiveModel::new(
+                    msg.id.clone(),
+                    payload,
+                    compress_payload_threshold,
+                )?;
                 let msg_content = msg_content.insert(txn).await?;
+                if let Some(MessageTagSet(tags)) = tags {
+                    messagetag::Entity::insert_many(
+                        tags.into_iter()
+                            .map(|tag| messagetag::ActiveModel::new(msg.id.clone(), tag)),
+                    )
+                    .exec(txn)
+                    .await?;
+                }
                 Ok((msg, msg_content))
             }
             .boxed()
         })
-        .await?;
+        .await;
 
+    match created {
+        Ok(created) => Ok(created),
+        Err(e) => {
+            if let Err(e) = org_quota::release(db, cache, &org_id).await {
+                tracing::warn!("Failed to give back the message to the quota: {e}");
+            }
+            Err(e.into())
+        }
+    }
+}
+
+/// Queues the message for dispatch, if any of the application's endpoints are to receive it
+async fn queue_message(
+    queue_tx: TaskQueueProducer,
+    create_message_app: &CreateMessageApp,
+    force_endpoint: Option<EndpointId>,
+    app: &application::Model,
+    msg: &message::Model,
+) -> Result<()> {
     let trigger_type = MessageAttemptTriggerType::Scheduled;
     if !create_message_app
-        .filtered_endpoints(trigger_type, &msg.event_type, msg.channels.as_ref())
+        .filtered_endpoints(
+            trigger_type,
+            &msg.event_type,
+            msg.channels.as_ref(),
+            msg.endpoint_metadata_selector.as_ref(),
+        )
         .is_empty()
     {
         queue_tx
@@ -387,13 +810,22 @@ pub(crate) async fn create_message_inner(
             .await?;
     }
 
-    let msg_out = if with_content {
-        MessageOut::from_msg_and_payload(msg, Some(msg_content.payload))
-    } else {
-        MessageOut::without_payload(msg)
-    };
+    Ok(())
+}
 
-    Ok(msg_out)
+fn message_out(
+    with_content: bool,
+    msg: message::Model,
+    msg_content: messagecontent::Model,
+) -> Result<MessageOut> {
+    if with_content {
+        Ok(MessageOut::from_msg_and_payload(
+            msg,
+            msg_content.into_payload()?,
+        ))
+    } else {
+        Ok(MessageOut::without_payload(msg))
+    }
 }
 
 #[derive(Debug, Deserialize, Validate, JsonSchema)]
@@ -417,7 +849,8 @@ async fn get_message(
         .await?
         .ok_or_else(|| HttpError::not_found(None, None))?;
     let msg_out = if with_content {
-        MessageOut::from_msg_and_payload(msg, msg_content.map(|c| c.payload))
+        let payload = msg_content.map(|c| c.into_payload()).transpose()?.flatten();
+        MessageOut::from_msg_and_payload(msg, payload)
     } else {
         MessageOut::without_payload(msg)
     };
@@ -449,6 +882,104 @@ async fn expunge_message_content(
     Ok(StatusCode::NO_CONTENT)
 }
 
+#[derive(Clone, Debug, Deserialize, Serialize, Validate, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct MessageStatusBatchIn {
+    /// The IDs of the messages to get the delivery status of, at most 50
+    #[validate(length(max = 50, message = "At most 50 message IDs can be checked at once"))]
+    #[validate]
+    pub ids: Vec<MessageId>,
+}
+
+/// A message's delivery status across all of the endpoints it's sent to
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum MessageDeliveryStatus {
+    /// Delivered to every endpoint
+    Success,
+    /// Still being delivered to at least one endpoint, or not sent to any endpoint yet
+    Pending,
+    /// Failed to be delivered to at least one endpoint, and no longer being retried
+    Fail,
+}
+
+impl MessageDeliveryStatus {
+    /// Combines the statuses of a message's destinations. A message without any hasn't been sent
+    /// to its endpoints yet, so it's still pending.
+    fn from_statuses(statuses: &[MessageStatus]) -> Self {
+        let any = |f: fn(&MessageStatus) -> bool| statuses.iter().any(f);
+        if statuses.is_empty() {
+            Self::Pending
+        } else if any(|s| {
+            matches!(
+                s,
+                MessageStatus::Pending | MessageStatus::Sending | MessageStatus::RateLimited
+            )
+        }) {
+            Self::Pending
+        } else if any(|s| {
+            matches!(
+                s,
+                MessageStatus::Fail | MessageStatus::Expired | MessageStatus::Cancelled
+            )
+        }) {
+            Self::Fail
+        } else {
+            Self::Success
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct MessageStatusBatchItem {
+    pub id: MessageId,
+    pub status: MessageDeliveryStatus,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct MessageStatusBatchOut {
+    pub results: Vec<MessageStatusBatchItem>,
+}
+
+/// Get the delivery status of several messages at once.
+///
+/// Results are in the order the IDs were given in. IDs which don't belong to a message of the
+/// application are left out.
+#[aide_annotate(op_id = "v1.message.status-batch")]
+async fn message_status_batch(
+    State(AppState { ref db, .. }): State<AppState>,
+    permissions::Application { app }: permissions::Application,
+    ValidatedJson(MessageStatusBatchIn { ids }): ValidatedJson<MessageStatusBatchIn>,
+) -> Result<Json<MessageStatusBatchOut>> {
+    if ids.is_empty() {
+        return Ok(Json(MessageStatusBatchOut { results: vec![] }));
+    }
+
+    let mut statuses: HashMap<MessageId, MessageDeliveryStatus> =
+        message::Entity::secure_find(app.id)
+            .filter(message::Column::Id.is_in(ids.clone()))
+            .find_with_related(messagedestination::Entity)
+            .all(db)
+            .await?
+            .into_iter()
+            .map(|(msg, destinations)| {
+                let statuses: Vec<_> = destinations.iter().map(|d| d.status).collect();
+                (msg.id, MessageDeliveryStatus::from_statuses(&statuses))
+            })
+            .collect();
+
+    let results = ids
+        .into_iter()
+        .filter_map(|id| {
+            let status = statuses.remove(&id)?;
+            Some(MessageStatusBatchItem { id, status })
+        })
+        .collect();
+    Ok(Json(MessageStatusBatchOut { results }))
+}
+
 pub fn router() -> ApiRouter<AppState> {
     let tag = openapi_tag("Message");
     ApiRouter::new()
@@ -458,6 +989,11 @@ pub fn router() -> ApiRouter<AppState> {
                 .get_with(list_messages, list_messages_operation),
             &tag,
         )
+        .api_route_with(
+            "/app/:app_id/msg/status-batch",
+            post_with(message_status_batch, message_status_batch_operation),
+            &tag,
+        )
         .api_route_with(
             "/app/:app_id/msg/:msg_id",
             get_with(get_message, get_message_operation),
@@ -477,8 +1013,9 @@ mod tests {
 
     use super::{
         default_true, CreateMessageQueryParams, GetMessageQueryParams, ListMessagesQueryParams,
-        MessageIn,
+        MessageDeliveryStatus, MessageIn,
     };
+    use crate::core::types::MessageStatus;
 
     const CHANNEL_INVALID: &str = "$$invalid-channel";
     const CHANNEL_VALID: &str = "valid-channel";
@@ -488,6 +1025,9 @@ mod tests {
     const EVENT_ID_VALID: &str = "valid-eventId";
     const EVENT_CHANNELS_INVALID: &[&str] = &["valid-event-channel", "&&invalid-event-channel"];
     const EVENT_CHANNELS_VALID: &[&str] = &["valid-event-channel1", "valid-event-channel2"];
+    const TAGS_INVALID: &[&str] = &["valid-tag", "invalid_tag"];
+    const TAGS_TOO_MANY: &[&str] = &["tag1", "tag2", "tag3", "tag4", "tag5", "tag6"];
+    const TAGS_VALID: &[&str] = &["valid-tag1", "valid-tag2"];
 
     #[test]
     fn test_message_in_validation() {
@@ -532,8 +1072,30 @@ mod tests {
         }))
         .unwrap();
 
+        let invalid_7: MessageIn = serde_json::from_value(json!({
+            "eventType": EVENT_TYPE_VALID,
+            "payload": {},
+            "tags": TAGS_INVALID
+        }))
+        .unwrap();
+
+        let invalid_8: MessageIn = serde_json::from_value(json!({
+            "eventType": EVENT_TYPE_VALID,
+            "payload": {},
+            "tags": TAGS_TOO_MANY
+        }))
+        .unwrap();
+
+        let invalid_9: MessageIn = serde_json::from_value(json!({
+            "eventType": EVENT_TYPE_VALID,
+            "payload": {},
+            "tags": [ "x".repeat(65) ]
+        }))
+        .unwrap();
+
         for m in [
-            invalid_1, invalid_2, invalid_3, invalid_4, invalid_5, invalid_6,
+            invalid_1, invalid_2, invalid_3, invalid_4, invalid_5, invalid_6, invalid_7, invalid_8,
+            invalid_9,
         ] {
             assert!(m.validate().is_err());
         }
@@ -542,7 +1104,8 @@ mod tests {
             "eventId": EVENT_ID_VALID,
             "eventType": EVENT_TYPE_VALID,
             "payload": {},
-            "channels": EVENT_CHANNELS_VALID
+            "channels": EVENT_CHANNELS_VALID,
+            "tags": TAGS_VALID
         }))
         .unwrap();
         valid.validate().unwrap();
@@ -575,4 +1138,24 @@ mod tests {
         let q: GetMessageQueryParams = serde_json::from_value(json!({})).unwrap();
         assert!(q.with_content);
     }
+
+    #[test]
+    fn test_message_delivery_status() {
+        use MessageStatus::*;
+
+        let status = MessageDeliveryStatus::from_statuses;
+        // Not sent to its endpoints yet
+        assert_eq!(status(&[]), MessageDeliveryStatus::Pending);
+        assert_eq!(
+            status(&[Success, Filtered, Incompatible]),
+            MessageDeliveryStatus::Success
+        );
+        assert_eq!(status(&[Success, Fail]), MessageDeliveryStatus::Fail);
+        assert_eq!(status(&[Expired, Success]), MessageDeliveryStatus::Fail);
+        assert_eq!(status(&[Success, Cancelled]), MessageDeliveryStatus::Fail);
+        // Deliveries still in flight take precedence over failed ones
+        assert_eq!(status(&[Fail, Pending]), MessageDeliveryStatus::Pending);
+        assert_eq!(status(&[Sending, Success]), MessageDeliveryStatus::Pending);
+        assert_eq!(status(&[RateLimited, Fail]), MessageDeliveryStatus::Pending);
+    }
 }
@@ -5,6 +5,8 @@ pub mod application;
 pub mod attempt;
 pub mod auth;
 pub mod endpoint;
+pub mod endpoint_group;
 pub mod event_type;
 pub mod health;
+pub mod internal;
 pub mod message;
@@ -13,14 +13,17 @@ pub mod endpoints;
 pub mod utils;
 
 pub fn router() -> ApiRouter<AppState> {
+    let internal_router: ApiRouter<AppState> = endpoints::internal::router().into();
     let ret: ApiRouter<AppState> = ApiRouter::new()
         .merge(endpoints::health::router())
         .merge(endpoints::auth::router())
         .merge(endpoints::application::router())
         .merge(endpoints::endpoint::router())
+        .merge(endpoints::endpoint_group::router())
         .merge(endpoints::event_type::router())
         .merge(endpoints::message::router())
         .merge(endpoints::attempt::router())
+        .merge(internal_router)
         .layer(
             TraceLayer::new_for_http()
                 .make_span_with(AxumOtelSpanCreator)
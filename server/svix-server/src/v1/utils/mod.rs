@@ -3,7 +3,7 @@
 
 use std::{
     borrow::Cow,
-    collections::HashSet,
+    collections::{HashMap, HashSet},
     error::Error as StdError,
     ops::Deref,
     time::{SystemTime, UNIX_EPOCH},
@@ -31,10 +31,12 @@ use validator::{Validate, ValidationError};
 
 use crate::{
     core::types::{
-        ApplicationIdOrUid, BaseId, EndpointIdOrUid, EventTypeName, EventTypeNameSet,
-        MessageAttemptId, MessageIdOrUid,
+        metadata::Metadata, ApplicationIdOrUid, BaseId, EndpointGroupId, EndpointIdOrUid,
+        EventTypeName, EventTypeNameSet, MessageAttemptId, MessageIdOrUid, MessageTag,
+        MessageTagSet,
     },
     error::{Error, HttpError, Result, ValidationErrorItem},
+    v1::endpoints::endpoint::validate_endpoint_metadata,
 };
 
 pub mod patch;
@@ -676,6 +678,100 @@ impl OperationInput for EventTypesQueryParams {
     }
 }
 
+// Same as `EventTypesQueryParams`, but for the repeatable `?tag=` parameter
+pub struct MessageTagsQueryParams(pub Option<MessageTagSet>);
+
+#[async_trait]
+impl<S> FromRequestParts<S> for MessageTagsQueryParams
+where
+    S: Send + Sync,
+{
+    type Rejection = Error;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self> {
+        let pairs = form_urlencoded::parse(parts.uri.query().unwrap_or_default().as_bytes());
+
+        let tags: HashSet<MessageTag> = pairs
+            .filter(|(key, _)|
+                // want to handle both `?tag=`, `?tag[]=`, and `?tag[1]=`
+                key == "tag" || (key.starts_with("tag[") && key.ends_with(']')))
+            .map(|(_, value)| MessageTag(value.into_owned()))
+            .collect();
+
+        if tags.is_empty() {
+            Ok(Self(None))
+        } else {
+            let tags = MessageTagSet(tags);
+            tags.validate().map_err(|e| {
+                HttpError::unprocessable_entity(validation_errors(vec!["query".to_owned()], e))
+            })?;
+            Ok(Self(Some(tags)))
+        }
+    }
+}
+
+impl OperationInput for MessageTagsQueryParams {
+    fn operation_input(ctx: &mut aide::gen::GenContext, operation: &mut aide::openapi::Operation) {
+        #[derive(JsonSchema)]
+        struct MessageTagsQueryParams {
+            /// Filter response based on the tags. Only messages having all of the given tags are included.
+            #[allow(unused)]
+            tag: Option<MessageTagSet>,
+        }
+
+        Query::<MessageTagsQueryParams>::operation_input(ctx, operation);
+    }
+}
+
+// Same as `EventTypesQueryParams`, but for `?metadata[key]=value` parameters, which can be passed
+// for several keys
+pub struct EndpointMetadataQueryParams(pub Option<Metadata>);
+
+#[async_trait]
+impl<S> FromRequestParts<S> for EndpointMetadataQueryParams
+where
+    S: Send + Sync,
+{
+    type Rejection = Error;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self> {
+        let pairs = form_urlencoded::parse(parts.uri.query().unwrap_or_default().as_bytes());
+
+        let metadata: HashMap<String, String> = pairs
+            .filter_map(|(key, value)| {
+                let key = key.strip_prefix("metadata[")?.strip_suffix(']')?;
+                Some((key.to_owned(), value.into_owned()))
+            })
+            .collect();
+
+        if metadata.is_empty() {
+            Ok(Self(None))
+        } else {
+            let metadata = Metadata::from(metadata);
+            validate_endpoint_metadata(&metadata).map_err(|e| {
+                let mut errors = validator::ValidationErrors::new();
+                errors.add("metadata", e);
+                HttpError::unprocessable_entity(validation_errors(vec!["query".to_owned()], errors))
+            })?;
+            Ok(Self(Some(metadata)))
+        }
+    }
+}
+
+impl OperationInput for EndpointMetadataQueryParams {
+    fn operation_input(ctx: &mut aide::gen::GenContext, operation: &mut aide::openapi::Operation) {
+        #[derive(JsonSchema)]
+        struct EndpointMetadataQueryParams {
+            /// Filter response based on the metadata, passed as `metadata[key]=value`. Only
+            /// endpoints having all of the given key-value pairs are included.
+            #[allow(unused)]
+            metadata: Option<Metadata>,
+        }
+
+        Query::<EndpointMetadataQueryParams>::operation_input(ctx, operation);
+    }
+}
+
 pub async fn api_not_implemented() -> Result<()> {
     Err(HttpError::not_implemented(None, None).into())
 }
@@ -730,6 +826,12 @@ pub struct ApplicationEndpointPath {
     pub endpoint_id: EndpointIdOrUid,
 }
 
+#[derive(Deserialize, JsonSchema)]
+pub struct ApplicationEndpointGroupPath {
+    pub app_id: ApplicationIdOrUid,
+    pub group_id: EndpointGroupId,
+}
+
 #[derive(Deserialize, JsonSchema)]
 pub struct ApplicationMsgPath {
     pub app_id: ApplicationIdOrUid,
@@ -840,6 +942,52 @@ impl<T: JsonSchema + Serialize> OperationOutput for JsonStatusUpsert<T> {
     }
 }
 
+/// JsonStatusReplayable is a wrapper over `axum::extract::Json` as a handler
+/// output.
+///
+/// It is used by operations that accept a request for processing (HTTP 202
+/// ACCEPTED), but that may instead return the result of an identical earlier
+/// request (HTTP 200 OK) when it's repeated.
+pub enum JsonStatusReplayable<T: JsonSchema + Serialize> {
+    Accepted(T),
+    Replayed(T),
+}
+
+impl<T: JsonSchema + Serialize> IntoResponse for JsonStatusReplayable<T> {
+    fn into_response(self) -> axum::response::Response {
+        let (status, body) = match self {
+            JsonStatusReplayable::Accepted(v) => (StatusCode::ACCEPTED, v),
+            JsonStatusReplayable::Replayed(v) => (StatusCode::OK, v),
+        };
+        (status, axum::extract::Json(body)).into_response()
+    }
+}
+
+impl<T: JsonSchema + Serialize> OperationOutput for JsonStatusReplayable<T> {
+    type Inner = T;
+
+    fn operation_response(
+        ctx: &mut aide::gen::GenContext,
+        operation: &mut aide::openapi::Operation,
+    ) -> Option<aide::openapi::Response> {
+        axum::extract::Json::<T>::operation_response(ctx, operation)
+    }
+
+    fn inferred_responses(
+        ctx: &mut aide::gen::GenContext,
+        operation: &mut aide::openapi::Operation,
+    ) -> Vec<(Option<u16>, aide::openapi::Response)> {
+        if let Some(resp) = Self::operation_response(ctx, operation) {
+            vec![
+                (Some(StatusCode::OK.into()), resp.clone()),
+                (Some(StatusCode::ACCEPTED.into()), resp),
+            ]
+        } else {
+            vec![]
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use serde_json::json;
@@ -3,51 +3,216 @@
 
 use std::{
     collections::HashMap,
+    io::Read,
     sync::{
         atomic::{AtomicU64, AtomicUsize, Ordering},
-        Arc,
+        Arc, Mutex,
     },
-    time::Duration,
+    time::{Duration, Instant},
 };
 
 use chrono::Utc;
 use futures::future;
 use http::{HeaderValue, StatusCode, Version};
+use hyper::client::connect::HttpInfo;
 use once_cell::sync::Lazy;
+use opentelemetry::{
+    metrics::{Histogram, Unit},
+    KeyValue,
+};
 use rand::Rng;
 use sea_orm::{
     prelude::DateTimeUtc, ActiveModelBehavior, ActiveModelTrait, ColumnTrait, DatabaseConnection,
-    EntityTrait, QueryFilter, Set, TryIntoModel,
+    EntityTrait, PaginatorTrait, QueryFilter, Set, TryIntoModel,
 };
 use serde::{Deserialize, Serialize};
-use tokio::time::sleep;
+use tokio::{sync::broadcast, time::sleep};
 use tracing::Instrument;
+use url::Url;
 
 use crate::{
     cfg::Configuration,
     core::{
+        app_rate_limit::AppRateLimiter,
         cache::{kv_def, Cache, CacheBehavior, CacheKey, CacheValue},
-        cryptography::Encryption,
-        message_app::{CreateMessageApp, CreateMessageEndpoint},
+        circuit_breaker::{CircuitBreaker, CircuitState, CircuitTransition},
+        cryptography::{Encryption, PayloadEncryptionKey},
+        endpoint_rate_limit::{EndpointRateLimit, RateLimitState},
+        message_app::{AppEndpointKey, CreateMessageApp, CreateMessageEndpoint},
         operational_webhooks::{
-            EndpointDisabledEventData, MessageAttemptEvent, OperationalWebhook,
+            EndpointCircuitEvent, EndpointDisabledEventData, EndpointFailureCountEventData,
+            MessageAttemptEvent, MessageExpiredEventData, OperationalWebhook,
             OperationalWebhookSender,
         },
         types::{
             ApplicationId, ApplicationUid, BaseId, EndpointHeaders, EndpointId,
-            EndpointSecretInternal, EndpointSecretType, MessageAttemptId,
-            MessageAttemptTriggerType, MessageId, MessageStatus, MessageUid, OrganizationId,
+            EndpointSecretInternal, EndpointSecretType, EventTypeName, MessageAttemptId,
+            MessageAttemptTriggerType, MessageCorrelationId, MessageId, MessageSignedMetadata,
+            MessageStatus, MessageUid, OrganizationId, PayloadVersion,
+        },
+        webhook_http_client::{
+            Error as WebhookClientError, Request, RequestBuilder, WebhookClient,
+        },
+    },
+    db::{
+        models::{
+            application, endpoint, message, messageattempt, messagecontent, messagedestination,
         },
-        webhook_http_client::{Error as WebhookClientError, RequestBuilder, WebhookClient},
+        DbPool,
     },
-    db::models::{endpoint, message, messageattempt, messagecontent, messagedestination},
     error::{Error, ErrorType, HttpError, Result},
     queue::{MessageTask, QueueTask, TaskQueueConsumer, TaskQueueProducer},
-    v1::utils::get_unix_timestamp,
+    v1::{
+        endpoints::message::{
+            create_message_inner, validate_raw_payload_is_object, MessageIn, RawPayload,
+        },
+        utils::get_unix_timestamp,
+    },
 };
 
 pub type CaseSensitiveHeaderMap = HashMap<String, HeaderValue>;
 
+/// What to do with a webhook once a [`PreDispatchHook`] has run
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HookResult {
+    /// Go on to the next hook, or send the webhook if this was the last one
+    Continue,
+    /// Don't send the webhook, and mark its message destination as [`MessageStatus::Filtered`] so
+    /// it isn't retried. Later hooks aren't run.
+    Filter,
+}
+
+/// Runs just before each webhook is sent, to add behavior like auditing or rewriting the payload
+/// without forking the server.
+///
+/// Hooks run in the order they were registered with `register_pre_dispatch_hook`, which is only
+/// available with the `pre-dispatch-hooks` feature. The payload is signed after all hooks have run,
+/// so changes to it are covered by the signature. Headers a hook adds are sent along with the
+/// webhook, but can't replace the ones set by Svix.
+#[axum::async_trait]
+pub trait PreDispatchHook: Send + Sync {
+    async fn pre_dispatch(
+        &self,
+        msg: &message::Model,
+        endp: &CreateMessageEndpoint,
+        headers: &mut CaseSensitiveHeaderMap,
+        payload: &mut String,
+    ) -> HookResult;
+}
+
+/// A [`PreDispatchHook`] which sends every webhook as is
+pub struct NoopPreDispatchHook;
+
+#[axum::async_trait]
+impl PreDispatchHook for NoopPreDispatchHook {
+    async fn pre_dispatch(
+        &self,
+        _msg: &message::Model,
+        _endp: &CreateMessageEndpoint,
+        _headers: &mut CaseSensitiveHeaderMap,
+        _payload: &mut String,
+    ) -> HookResult {
+        HookResult::Continue
+    }
+}
+
+#[cfg(feature = "pre-dispatch-hooks")]
+static PRE_DISPATCH_HOOKS: std::sync::RwLock<Vec<Arc<dyn PreDispatchHook>>> =
+    std::sync::RwLock::new(Vec::new());
+
+/// Registers a hook to run before every webhook is sent, after the hooks registered before it
+#[cfg(feature = "pre-dispatch-hooks")]
+pub fn register_pre_dispatch_hook(hook: impl PreDispatchHook + 'static) {
+    PRE_DISPATCH_HOOKS
+        .write()
+        .expect("Pre-dispatch hooks lock poisoned")
+        .push(Arc::new(hook));
+}
+
+#[cfg(feature = "pre-dispatch-hooks")]
+fn pre_dispatch_hooks() -> Vec<Arc<dyn PreDispatchHook>> {
+    PRE_DISPATCH_HOOKS
+        .read()
+        .expect("Pre-dispatch hooks lock poisoned")
+        .clone()
+}
+
+#[cfg(not(feature = "pre-dispatch-hooks"))]
+fn pre_dispatch_hooks() -> Vec<Arc<dyn PreDispatchHook>> {
+    Vec::new()
+}
+
+/// Runs `hooks` in order, stopping at the first which filters the webhook out
+async fn run_pre_dispatch_hooks(
+    hooks: &[Arc<dyn PreDispatchHook>],
+    msg: &message::Model,
+    endp: &CreateMessageEndpoint,
+    headers: &mut CaseSensitiveHeaderMap,
+    payload: &mut String,
+) -> HookResult {
+    for hook in hooks {
+        if hook.pre_dispatch(msg, endp, headers, payload).await == HookResult::Filter {
+            return HookResult::Filter;
+        }
+    }
+    HookResult::Continue
+}
+
+/// Runs endpoints' dispatch transformations: the scripts their webhook payloads are rewritten with
+/// before being signed and sent.
+///
+/// The server has no script runtime of its own, so one has to be registered with
+/// `register_dispatch_transformer`, which is only available with the `dispatch-transformations`
+/// feature. Until then, webhooks to endpoints with an enabled transformation fail instead of being
+/// sent untransformed.
+#[axum::async_trait]
+pub trait DispatchTransformer: Send + Sync {
+    /// Returns `payload` as transformed by the script `code`
+    async fn transform(&self, code: &str, payload: String) -> Result<String>;
+}
+
+#[cfg(feature = "dispatch-transformations")]
+static DISPATCH_TRANSFORMER: std::sync::RwLock<Option<Arc<dyn DispatchTransformer>>> =
+    std::sync::RwLock::new(None);
+
+/// Sets the transformer dispatch transformations are run with, replacing any set before
+#[cfg(feature = "dispatch-transformations")]
+pub fn register_dispatch_transformer(transformer: impl DispatchTransformer + 'static) {
+    *DISPATCH_TRANSFORMER
+        .write()
+        .expect("Dispatch transformer lock poisoned") = Some(Arc::new(transformer));
+}
+
+#[cfg(feature = "dispatch-transformations")]
+fn dispatch_transformer() -> Option<Arc<dyn DispatchTransformer>> {
+    DISPATCH_TRANSFORMER
+        .read()
+        .expect("Dispatch transformer lock poisoned")
+        .clone()
+}
+
+#[cfg(not(feature = "dispatch-transformations"))]
+fn dispatch_transformer() -> Option<Arc<dyn DispatchTransformer>> {
+    None
+}
+
+/// Runs the endpoint's dispatch transformation on `payload`, if it has one
+async fn transform_payload(
+    transformer: Option<&dyn DispatchTransformer>,
+    endp: &CreateMessageEndpoint,
+    payload: String,
+) -> Result<String> {
+    let Some(code) = &endp.dispatch_transformation else {
+        return Ok(payload);
+    };
+    let Some(transformer) = transformer else {
+        return Err(Error::generic(
+            "The endpoint has a transformation, but no transformer is available to run it",
+        ));
+    };
+    transformer.transform(code, payload).await
+}
+
 // The maximum variation from the retry schedule when applying jitter to a resent webhook event in
 // percent deviation
 const JITTER_DELTA: f32 = 0.2;
@@ -55,11 +220,14 @@ const OVERLOAD_PENALTY_SECS: u64 = 60;
 
 const USER_AGENT: &str = concat!("Svix-Webhooks/", env!("CARGO_PKG_VERSION"));
 
-/// Send the MessageAttemptFailingEvent after exceeding this number of failed attempts
-const OP_WEBHOOKS_SEND_FAILING_EVENT_AFTER: usize = 4;
-
 const RESPONSE_MAX_SIZE: usize = 20000;
 
+/// How long to wait for an endpoint's hostname to resolve when `pre_resolve_dns` is set
+const DNS_PRE_RESOLVE_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Headers that are never stored with a message attempt, even if `store_request_headers` is set
+const SENSITIVE_HEADERS: &[&str] = &["authorization", "cookie", "x-api-key"];
+
 /// A simple struct noting the context of the wrapped [`DateTimeUtc`]. This struct is returned when
 /// you are to disable disable an endpoint. This is optionally returned by [`process_failure_cache`]
 /// which is to be called after all retry events are exhausted.
@@ -68,11 +236,74 @@ struct EndpointDisableInfo {
     first_failure_at: DateTimeUtc,
 }
 
+/// Returned by [`process_endpoint_failure`]
+struct EndpointFailureStatus {
+    /// How many times in a row the endpoint has failed, including this time
+    failure_count: u32,
+    first_failure_at: DateTimeUtc,
+    /// Set when the endpoint is to be disabled
+    disable: Option<EndpointDisableInfo>,
+}
+
 /// The first_failure_at time is only stored in Postgres after the endpoint has been disabled.
 /// Otherwise, it is stored in the cache with an expiration.
 #[derive(Deserialize, Serialize)]
 pub struct FailureCacheValue {
     pub first_failure_at: DateTimeUtc,
+    /// The number of consecutive failures since `first_failure_at`
+    #[serde(default)]
+    pub failure_count: u32,
+}
+
+/// How many times updating an endpoint's failure count is tried when it keeps being changed
+/// concurrently
+const MAX_FAILURE_SWAP_ATTEMPTS: usize = 10;
+
+/// An endpoint's consecutive failures, and when they're forgiven
+type EndpointFailureCounts = HashMap<(ApplicationId, EndpointId), (u32, Instant)>;
+
+/// The consecutive failures of the endpoints this worker last saw failing, reported in the
+/// `svix.endpoint.consecutive_failures` gauge whenever metrics are collected.
+///
+/// The counts are the endpoints' shared ones from the cache, so any worker reports the same value.
+/// They're removed once the endpoint succeeds or its failures are forgiven.
+static ENDPOINT_FAILURE_COUNTS: Lazy<Arc<Mutex<EndpointFailureCounts>>> = Lazy::new(|| {
+    let counts = Arc::<Mutex<EndpointFailureCounts>>::default();
+
+    let observed = counts.clone();
+    opentelemetry::global::meter("svix.com")
+        .u64_observable_gauge("svix.endpoint.consecutive_failures")
+        .with_description("Number of consecutive failed deliveries to an endpoint")
+        .with_callback(move |observer| {
+            let now = Instant::now();
+            let mut counts = observed.lock().unwrap();
+            counts.retain(|_, (_, forgiven_at)| *forgiven_at > now);
+            for ((app_id, endp_id), (count, _)) in counts.iter() {
+                observer.observe(
+                    (*count).into(),
+                    &endpoint_metric_attributes(app_id, endp_id),
+                );
+            }
+        })
+        .init();
+
+    counts
+});
+
+/// How long tasks waited in the queue after they became due
+static TASK_QUEUE_AGE: Lazy<Histogram<f64>> = Lazy::new(|| {
+    opentelemetry::global::meter("svix.com")
+        .f64_histogram("svix.task_queue_age")
+        .with_description("Time tasks spent waiting in the queue after they became due")
+        .with_unit(Unit::new("s"))
+        .init()
+});
+
+fn endpoint_metric_attributes(app_id: &ApplicationId, endp_id: &EndpointId) -> [KeyValue; 2] {
+    [
+        KeyValue::new("app_id", app_id.0.clone()),
+        KeyValue::new("endp_id", endp_id.0.clone()),
+    ]
 }
 
 kv_def!(FailureCacheKey, FailureCacheValue);
@@ -83,30 +314,108 @@ impl FailureCacheKey {
         app_id: &ApplicationId,
         endp_id: &EndpointId,
     ) -> FailureCacheKey {
-        FailureCacheKey(format!("SVIX_FAILURE_CACHE_{org_id}_{app_id}_{endp_id}"))
+        FailureCacheKey(format!("{}{endp_id}", Self::prefix_for_app(org_id, app_id)))
+    }
+
+    /// The prefix shared by the keys of all of the application's endpoints
+    pub fn prefix_for_app(org_id: &OrganizationId, app_id: &ApplicationId) -> String {
+        format!("SVIX_FAILURE_CACHE_{org_id}_{app_id}_")
+    }
+}
+
+/// When a message was last delivered to an endpoint, so that endpoints which haven't received
+/// anything in a while can be spotted
+#[derive(Deserialize, Serialize)]
+pub struct SuccessCacheValue {
+    pub last_success_at: DateTimeUtc,
+}
+
+kv_def!(SuccessCacheKey, SuccessCacheValue);
+
+impl SuccessCacheKey {
+    pub fn new(
+        org_id: &OrganizationId,
+        app_id: &ApplicationId,
+        endp_id: &EndpointId,
+    ) -> SuccessCacheKey {
+        SuccessCacheKey(format!("SVIX_SUCCESS_CACHE_{org_id}_{app_id}_{endp_id}"))
+    }
+}
+
+/// How long a successful delivery is remembered for
+const DELIVERED_CACHE_TTL: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+/// Set after a message has been successfully delivered to an endpoint. The same task may be
+/// received more than once (e.g. when a worker dies after making the HTTP call but before the task
+/// is acked), and this is what prevents the webhook from being sent again in that case.
+#[derive(Deserialize, Serialize)]
+pub struct DeliveredCacheValue {
+    pub delivered_at: DateTimeUtc,
+}
+
+kv_def!(DeliveredCacheKey, DeliveredCacheValue);
+
+impl DeliveredCacheKey {
+    pub fn new(msg_id: &MessageId, endp_id: &EndpointId) -> DeliveredCacheKey {
+        DeliveredCacheKey(format!("SVIX_DELIVERED_{msg_id}_{endp_id}"))
     }
 }
 
-/// Called upon the successful dispatch of an endpoint. Simply clears the cache of a
+/// Called upon the successful dispatch of an endpoint. Records the time of the delivery as the
+/// endpoint's [`SuccessCacheValue`] for `last_success_ttl`, and clears the cache of a
 /// [`FailureCacheKey`]/[`FailureCacheValue`] pair associated with a given endpoint. This is such
 /// that an endpoint that was previously not responding is not disabled after responding again.
 ///
-/// If the key value pair does not already exist in the cache, indicating that the endpoint never
-/// stopped responding, no operation is performed.
+/// If the failure key value pair does not already exist in the cache, indicating that the endpoint
+/// never stopped responding, it isn't touched.
 #[tracing::instrument(skip_all)]
 async fn process_endpoint_success(
     cache: &Cache,
     app_id: &ApplicationId,
     org_id: &OrganizationId,
     endp: &CreateMessageEndpoint,
+    last_success_ttl: Duration,
 ) -> Result<()> {
+    // Only used for monitoring, so failing to record it doesn't fail the delivery
+    if let Err(e) = cache
+        .set(
+            &SuccessCacheKey::new(org_id, app_id, &endp.id),
+            &SuccessCacheValue {
+                last_success_at: Utc::now(),
+            },
+            Some(last_success_ttl),
+        )
+        .await
+    {
+        tracing::warn!(
+            error = &e as &dyn std::error::Error,
+            "Failed to record the endpoint's last successful delivery"
+        );
+    }
+
+    ENDPOINT_FAILURE_COUNTS
+        .lock()
+        .unwrap()
+        .remove(&(app_id.clone(), endp.id.clone()));
+
     let key = FailureCacheKey::new(org_id, app_id, &endp.id);
 
-    cache.delete(&key).await.map_err(Error::cache)
+    if cache
+        .get::<FailureCacheValue>(&key)
+        .await
+        .map_err(Error::cache)?
+        .is_none()
+    {
+        return Ok(());
+    }
+
+    cache.delete(&key).await.map_err(Error::cache)?;
+
+    Ok(())
 }
 
-/// Called upon endpoint failure. Returns whether to disable the endpoint based on the time of first
-/// failure stored in the cache.
+/// Called upon endpoint failure. Counts the failure, and returns whether to disable the endpoint
+/// based on the time of first failure stored in the cache.
 ///
 /// If no failure has previously been reported, then now is cached as the time of first failure and
 /// the endpoint is not disabled.
@@ -121,74 +430,244 @@ async fn process_endpoint_failure(
     cache: &Cache,
     app_id: &ApplicationId,
     org_id: &OrganizationId,
-    endp: &CreateMessageEndpoint,
+    endp_id: &EndpointId,
     disable_in: Duration,
-) -> Result<Option<EndpointDisableInfo>> {
-    let key = FailureCacheKey::new(org_id, app_id, &endp.id);
-    let now = Utc::now();
+) -> Result<EndpointFailureStatus> {
+    let key = FailureCacheKey::new(org_id, app_id, endp_id);
+
+    // Concurrent failures of the same endpoint are all counted by swapping in the new count only
+    // if no other worker has changed it in the meantime
+    for _ in 0..MAX_FAILURE_SWAP_ATTEMPTS {
+        let now = Utc::now();
+        let current = cache
+            .get::<FailureCacheValue>(&key)
+            .await
+            .map_err(Error::generic)?;
 
-    // If it already exists in the cache, see if the grace period has already elapsed
-    if let Some(FailureCacheValue { first_failure_at }) = cache
-        .get::<FailureCacheValue>(&key)
-        .await
-        .map_err(Error::generic)?
-    {
-        if now - first_failure_at
-            > chrono::Duration::from_std(disable_in).expect("Given `disable_in` is too large")
-        {
-            Ok(Some(EndpointDisableInfo { first_failure_at }))
-        } else {
-            Ok(None)
-        }
-    }
-    // If it does not yet exist in the cache, set the first_failure_at value to now
-    else {
-        cache
-            .set(
-                &key,
-                &FailureCacheValue {
+        let (value, elapsed) = match &current {
+            Some(FailureCacheValue {
+                first_failure_at,
+                failure_count,
+            }) => (
+                FailureCacheValue {
+                    first_failure_at: *first_failure_at,
+                    failure_count: failure_count.saturating_add(1),
+                },
+                time_since_first_failure(now, *first_failure_at),
+            ),
+            // If it does not yet exist in the cache, set the first_failure_at value to now
+            None => (
+                FailureCacheValue {
                     first_failure_at: now,
+                    failure_count: 1,
                 },
-                // Failures are forgiven after double the `disable_in` `Duration` with the expiry of
-                // the Redis key
-                disable_in * 2,
-            )
+                Duration::ZERO,
+            ),
+        };
+
+        // Failures are forgiven after double the `disable_in` `Duration` since the first one with
+        // the expiry of the Redis key, so updating the count mustn't extend it
+        let expiry = (disable_in * 2)
+            .saturating_sub(elapsed)
+            .max(Duration::from_secs(1));
+        if !cache
+            .compare_and_swap(&key, current.as_ref(), &value, Some(expiry))
             .await
-            .map_err(Error::generic)?;
+            .map_err(Error::generic)?
+        {
+            continue;
+        }
+
+        ENDPOINT_FAILURE_COUNTS.lock().unwrap().insert(
+            (app_id.clone(), endp_id.clone()),
+            (value.failure_count, Instant::now() + expiry),
+        );
+
+        // See if the grace period has already elapsed
+        let disable = (elapsed > disable_in).then_some(EndpointDisableInfo {
+            first_failure_at: value.first_failure_at,
+        });
+
+        return Ok(EndpointFailureStatus {
+            failure_count: value.failure_count,
+            first_failure_at: value.first_failure_at,
+            disable,
+        });
+    }
+
+    Err(Error::generic(format!(
+        "Failed to count the failure of endpoint {endp_id}"
+    )))
+}
 
-        Ok(None)
+/// The time since the first failure of an endpoint.
+///
+/// The time of the first failure is wall-clock time shared between workers, so it can be in the
+/// future if the clock has since been adjusted backwards (eg. by an NTP sync). The time elapsed is
+/// then treated as zero, which extends the grace period until the clock catches up instead of
+/// disabling the endpoint early.
+fn time_since_first_failure(now: DateTimeUtc, first_failure_at: DateTimeUtc) -> Duration {
+    if now < first_failure_at {
+        tracing::warn!(
+            %now,
+            %first_failure_at,
+            "First endpoint failure is in the future, the system clock may have gone backwards"
+        );
+        return Duration::ZERO;
     }
+
+    (now - first_failure_at).to_std().unwrap_or_default()
 }
 
 /// Sign a message
-fn sign_msg(
+///
+/// The signed content starts with the message ID, unless the endpoint has a
+/// `custom_payload_prefix` to sign in its place.
+async fn sign_msg(
     main_secret: &Encryption,
     timestamp: i64,
     body: &str,
     msg_id: &MessageId,
+    custom_payload_prefix: Option<&str>,
+    signed_metadata: Option<&MessageSignedMetadata>,
     endpoint_signing_keys: &[&EndpointSecretInternal],
 ) -> String {
-    let to_sign = format!("{msg_id}.{timestamp}.{body}");
-    endpoint_signing_keys
+    let prefix = custom_payload_prefix.unwrap_or(&msg_id.0);
+    // The metadata is signed in the same form it's sent to endpoints in, so that they can verify it
+    let to_sign = match signed_metadata {
+        Some(metadata) => format!(
+            "{prefix}.{timestamp}.{}.{body}",
+            metadata.to_canonical_json()
+        ),
+        None => format!("{prefix}.{timestamp}.{body}"),
+    };
+
+    let rsa_keys = endpoint_signing_keys
         .iter()
-        .map(|x| {
-            let sig = x.sign(main_secret, to_sign.as_bytes());
-            let version = match x.type_() {
-                EndpointSecretType::Hmac256 => "v1",
-                EndpointSecretType::Ed25519 => "v1a",
-            };
-            format!("{version},{}", base64::encode(sig))
+        .filter(|x| *x.type_() == EndpointSecretType::Rsa)
+        .count();
+    let parallel = rsa_keys > PARALLEL_SIGNING_THRESHOLD;
+    sign_with_keys(
+        main_secret,
+        to_sign.into_bytes(),
+        endpoint_signing_keys,
+        parallel,
+    )
+    .await
+}
+
+/// Endpoints with more RSA signing keys than this (for example old keys which haven't expired
+/// yet) are signed with each RSA key on the blocking thread pool, in parallel. Other signatures
+/// are cheaper to compute than a task is to hand over, so they're always computed in place.
+const PARALLEL_SIGNING_THRESHOLD: usize = 3;
+
+fn sign_with_key(main_secret: &Encryption, to_sign: &[u8], key: &EndpointSecretInternal) -> String {
+    let sig = key.sign(main_secret, to_sign);
+    let version = match key.type_() {
+        EndpointSecretType::Hmac256 => "v1",
+        EndpointSecretType::Ed25519 => "v1a",
+        EndpointSecretType::Rsa => "v1r",
+    };
+    format!("{version},{}", base64::encode(sig))
+}
+
+/// Signs `to_sign` with each of the keys, returning the signatures in the same order, separated by
+/// spaces
+async fn sign_with_keys(
+    main_secret: &Encryption,
+    to_sign: Vec<u8>,
+    endpoint_signing_keys: &[&EndpointSecretInternal],
+    parallel: bool,
+) -> String {
+    if !parallel {
+        return endpoint_signing_keys
+            .iter()
+            .map(|&x| sign_with_key(main_secret, &to_sign, x))
+            .collect::<Vec<_>>()
+            .join(" ");
+    }
+
+    // Spawned up front, so that the other signatures are computed while they run
+    let to_sign: Arc<[u8]> = to_sign.into();
+    let rsa_tasks: Vec<_> = endpoint_signing_keys
+        .iter()
+        .map(|&x| {
+            (*x.type_() == EndpointSecretType::Rsa).then(|| {
+                let (main_secret, to_sign, x) = (main_secret.clone(), to_sign.clone(), x.clone());
+                tokio::task::spawn_blocking(move || sign_with_key(&main_secret, &to_sign, &x))
+            })
         })
-        .collect::<Vec<String>>()
-        .join(" ")
+        .collect();
+
+    let mut signatures = Vec::with_capacity(endpoint_signing_keys.len());
+    for (&x, task) in endpoint_signing_keys.iter().zip(rsa_tasks) {
+        signatures.push(match task {
+            Some(task) => task.await.expect("signing task panicked"),
+            None => sign_with_key(main_secret, &to_sign, x),
+        });
+    }
+    signatures.join(" ")
+}
+
+/// Sends the webhook with the endpoint's content type instead of `application/json`, if it has one
+fn set_content_type(
+    headers: &mut CaseSensitiveHeaderMap,
+    endp: &CreateMessageEndpoint,
+) -> Result<()> {
+    if let Some(content_type) = &endp.content_type {
+        let value = content_type
+            .parse()
+            .map_err(|e| Error::generic(format!("Error parsing content type: {e:?}")))?;
+        headers.insert("content-type".to_owned(), value);
+    }
+    Ok(())
+}
+
+/// Encrypts the payload for the endpoint's public key if it has payload encryption enabled, adding
+/// the ephemeral public key it was encrypted for to the headers. The encrypted payload is sent
+/// base64-encoded, as a JSON string.
+///
+/// This is done after the payload is signed, so the signature is of the decrypted payload.
+fn encrypt_payload(
+    headers: &mut CaseSensitiveHeaderMap,
+    payload: String,
+    endp: &CreateMessageEndpoint,
+    whitelabel_headers: bool,
+) -> Result<String> {
+    let Some(key) = &endp.payload_encryption_key else {
+        return Ok(payload);
+    };
+
+    let (ephemeral_pubkey, ciphertext) =
+        PayloadEncryptionKey::from_slice(key)?.seal(payload.as_bytes())?;
+    let name = if whitelabel_headers {
+        "webhook-encrypted-key"
+    } else {
+        "svix-encrypted-key"
+    };
+    let value = base64::encode(ephemeral_pubkey)
+        .parse()
+        .map_err(|e| Error::generic(format!("Error parsing encrypted key: {e:?}")))?;
+    headers.insert(name.to_owned(), value);
+
+    Ok(serde_json::Value::String(base64::encode(ciphertext)).to_string())
 }
 
 /// Generates a set of headers for any one webhook event
+///
+/// `attempt_count` is the number of attempts that were already made to deliver the message to the
+/// endpoint, so it's zero for the first one, and `msg_timestamp` is when the message was created.
+#[allow(clippy::too_many_arguments)]
 fn generate_msg_headers(
     timestamp: i64,
     msg_id: &MessageId,
     signatures: String,
+    attempt_count: u16,
+    msg_timestamp: i64,
     whitelabel_headers: bool,
+    environment: Option<&str>,
+    payload_version: Option<&PayloadVersion>,
+    correlation_id: Option<&MessageCorrelationId>,
     configured_headers: Option<&EndpointHeaders>,
     _endpoint_url: &str,
 ) -> Result<CaseSensitiveHeaderMap> {
@@ -204,14 +683,56 @@ fn generate_msg_headers(
     let signatures_str = signatures
         .parse()
         .map_err(|e| Error::generic(format!("Error parsing message signatures: {e:?}")))?;
+    let attempt = attempt_count.into();
+    let msg_timestamp = msg_timestamp.into();
     if whitelabel_headers {
         headers.insert("webhook-id".to_owned(), id_hdr);
         headers.insert("webhook-timestamp".to_owned(), timestamp);
         headers.insert("webhook-signature".to_owned(), signatures_str);
+        headers.insert("webhook-delivery-attempt".to_owned(), attempt);
+        headers.insert("webhook-message-timestamp".to_owned(), msg_timestamp);
     } else {
         headers.insert("svix-id".to_owned(), id_hdr);
         headers.insert("svix-timestamp".to_owned(), timestamp);
         headers.insert("svix-signature".to_owned(), signatures_str);
+        headers.insert("svix-delivery-attempt".to_owned(), attempt);
+        headers.insert("svix-message-timestamp".to_owned(), msg_timestamp);
+    }
+    // Not covered by the signature, it only tells the endpoint where the webhook came from
+    if let Some(environment) = environment {
+        let environment = environment
+            .parse()
+            .map_err(|e| Error::generic(format!("Error parsing webhook environment: {e:?}")))?;
+        let name = if whitelabel_headers {
+            "webhook-environment"
+        } else {
+            "svix-environment"
+        };
+        headers.insert(name.to_owned(), environment);
+    }
+    if let Some(payload_version) = payload_version {
+        let payload_version = payload_version
+            .to_string()
+            .parse()
+            .map_err(|e| Error::generic(format!("Error parsing payload version: {e:?}")))?;
+        let name = if whitelabel_headers {
+            "webhook-payload-version"
+        } else {
+            "svix-payload-version"
+        };
+        headers.insert(name.to_owned(), payload_version);
+    }
+    if let Some(correlation_id) = correlation_id {
+        let correlation_id = correlation_id
+            .0
+            .parse()
+            .map_err(|e| Error::generic(format!("Error parsing correlation id: {e:?}")))?;
+        let name = if whitelabel_headers {
+            "webhook-correlation-id"
+        } else {
+            "svix-correlation-id"
+        };
+        headers.insert(name.to_owned(), correlation_id);
     }
     headers.insert(
         "user-agent".to_owned(),
@@ -222,9 +743,16 @@ fn generate_msg_headers(
         "application/json".parse().unwrap(),
     );
     if let Some(configured_headers) = configured_headers {
-        for (k, v) in &configured_headers.0 {
+        // Sorted, so that when several configured headers only differ in case, the same one wins
+        // every time rather than whichever the `HashMap` happens to yield last
+        let mut configured_headers: Vec<_> = configured_headers.0.iter().collect();
+        configured_headers.sort_by_cached_key(|(k, _)| (k.to_ascii_lowercase(), *k));
+        for (k, v) in configured_headers {
             match v.parse() {
                 Ok(v) => {
+                    // Header names are case-insensitive, so this replaces any header of the same
+                    // name, whatever its case
+                    headers.retain(|existing, _| !existing.eq_ignore_ascii_case(k));
                     headers.insert(k.clone(), v);
                 }
                 Err(e) => {
@@ -237,33 +765,67 @@ fn generate_msg_headers(
     Ok(headers)
 }
 
+/// Serializes the headers sent with a webhook to a JSON object so they can be stored with the
+/// message attempt, leaving out [`SENSITIVE_HEADERS`] and any values that aren't valid strings.
+fn scrub_request_headers(headers: &CaseSensitiveHeaderMap) -> serde_json::Value {
+    headers
+        .iter()
+        .filter(|(k, _)| {
+            !SENSITIVE_HEADERS
+                .iter()
+                .any(|sensitive| k.eq_ignore_ascii_case(sensitive))
+        })
+        .filter_map(|(k, v)| Some((k.clone(), serde_json::Value::from(v.to_str().ok()?))))
+        .collect::<serde_json::Map<_, _>>()
+        .into()
+}
+
 #[derive(Clone)]
 struct WorkerContext<'a> {
     cfg: &'a Configuration,
     cache: &'a Cache,
     db: &'a DatabaseConnection,
+    /// A read replica, for reads which can tolerate some replication lag
+    read_db: &'a DatabaseConnection,
     queue_tx: &'a TaskQueueProducer,
     op_webhook_sender: &'a OperationalWebhookSender,
     webhook_client: &'a WebhookClient,
+    /// Keeps applications sending bursts of messages from starving the others
+    app_rate_limiter: &'a AppRateLimiter,
 }
 
 struct FailedDispatch(messageattempt::ActiveModel, Error);
-struct SuccessfulDispatch(messageattempt::ActiveModel);
+/// The attempt, and the URL the endpoint permanently moved to if the webhook was delivered there
+/// after following a redirect
+struct SuccessfulDispatch(messageattempt::ActiveModel, Option<Url>);
 
 #[allow(clippy::large_enum_variant)]
 enum IncompleteDispatch {
     Pending(PendingDispatch),
     #[allow(dead_code)]
     Failed(FailedDispatch),
+    /// A [`PreDispatchHook`] chose not to send the webhook
+    Filtered,
+    /// The endpoint's dispatch transformation couldn't be run
+    TransformationFailed(Error),
 }
 
 struct PendingDispatch {
     method: http::Method,
     url: String,
     headers: CaseSensitiveHeaderMap,
+    /// Set if the sent headers are to be stored with the message attempt
+    headers_to_store: Option<serde_json::Value>,
+    /// Whether the IP address the request is sent to is stored with the message attempt
+    store_endpoint_ip: bool,
     payload: String,
     request_timeout: u64,
     created_at: DateTimeUtc,
+    /// Whether a redirect response is followed, rather than recorded as a failure
+    follow_redirect: bool,
+    https_only: bool,
+    /// Whether the endpoint's hostname is looked up before sending the request
+    pre_resolve_dns: bool,
 }
 
 // Clippy fails to compute the first variant's size, stating it as
@@ -281,107 +843,327 @@ async fn prepare_dispatch(
         msg_task,
         payload,
         endp,
+        msg_created_at,
+        signed_metadata,
+        payload_version,
         ..
     }: DispatchContext<'_>,
+    msg: &message::Model,
 ) -> Result<IncompleteDispatch> {
+    let transformer = dispatch_transformer();
+    let mut payload =
+        match transform_payload(transformer.as_deref(), endp, payload.to_owned()).await {
+            Ok(payload) => payload,
+            Err(e) => return Ok(IncompleteDispatch::TransformationFailed(e)),
+        };
+    let mut hook_headers = CaseSensitiveHeaderMap::new();
+    let hooks = pre_dispatch_hooks();
+    if run_pre_dispatch_hooks(&hooks, msg, endp, &mut hook_headers, &mut payload).await
+        == HookResult::Filter
+    {
+        return Ok(IncompleteDispatch::Filtered);
+    }
+
     let attempt_created_at = Utc::now();
 
-    let headers = {
+    let mut headers = {
         let keys = endp.valid_signing_keys();
 
         let signatures = sign_msg(
             &cfg.encryption,
             attempt_created_at.timestamp(),
-            payload,
+            &payload,
             &msg_task.msg_id,
+            endp.custom_signature_prefix.as_deref(),
+            signed_metadata,
             &keys,
-        );
+        )
+        .await;
 
-        generate_msg_headers(
+        let mut headers = generate_msg_headers(
             attempt_created_at.timestamp(),
             &msg_task.msg_id,
             signatures,
+            msg_task.attempt_count,
+            msg_created_at.timestamp(),
             cfg.whitelabel_headers,
+            cfg.webhook_environment.as_deref(),
+            payload_version,
+            msg.correlation_id.as_ref(),
             endp.headers.as_ref(),
             &endp.url,
-        )?
+        )?;
+        set_content_type(&mut headers, endp)?;
+
+        if let Some(metadata) = signed_metadata {
+            let name = if cfg.whitelabel_headers {
+                "webhook-signed-metadata"
+            } else {
+                "svix-signed-metadata"
+            };
+            let value = metadata
+                .to_canonical_json()
+                .parse()
+                .map_err(|e| Error::generic(format!("Error parsing signed metadata: {e:?}")))?;
+            headers.insert(name.to_owned(), value);
+        }
+
+        for (name, value) in hook_headers {
+            if !headers.keys().any(|k| k.eq_ignore_ascii_case(&name)) {
+                headers.insert(name, value);
+            }
+        }
+
+        headers
     };
+    let payload = encrypt_payload(&mut headers, payload, endp, cfg.whitelabel_headers)?;
+
+    let headers_to_store = cfg
+        .store_request_headers
+        .then(|| scrub_request_headers(&headers));
 
     Ok(IncompleteDispatch::Pending(PendingDispatch {
-        method: http::Method::POST,
+        method: endp.http_method.0.clone(),
         url: endp.url.clone(),
         headers,
-        payload: payload.to_owned(),
+        headers_to_store,
+        // Behind a proxy, the address would be the proxy's rather than the endpoint's
+        store_endpoint_ip: cfg.store_endpoint_ip && cfg.proxy_config.is_none(),
+        payload,
         request_timeout: cfg.worker_request_timeout as _,
         created_at: attempt_created_at,
+        follow_redirect: cfg.allow_endpoint_redirect,
+        https_only: cfg.endpoint_https_only,
+        // Behind a proxy, it's the proxy which resolves the endpoint's hostname
+        pre_resolve_dns: cfg.pre_resolve_dns && cfg.proxy_config.is_none(),
     }))
 }
 
+fn build_request(
+    method: http::Method,
+    url: &str,
+    headers: CaseSensitiveHeaderMap,
+    payload: String,
+    request_timeout: u64,
+) -> Result<Request> {
+    RequestBuilder::new()
+        .method(method)
+        .uri_str(url)
+        .map_err(|e| Error::validation(format!("URL is invalid: {e:?}")))?
+        .headers(headers)
+        .body(payload.into(), HeaderValue::from_static("application/json"))
+        .version(Version::HTTP_11)
+        .timeout(Duration::from_secs(request_timeout))
+        .build()
+        .map_err(Error::generic)
+}
+
+/// Whether the URL's host resolves to at least one address within [`DNS_PRE_RESOLVE_TIMEOUT`].
+/// URLs which can't be parsed are left for the request to fail on.
+async fn resolves(url: &str) -> bool {
+    let Ok(url) = Url::parse(url) else {
+        return true;
+    };
+    // IP addresses have nothing to look up
+    let (Some(url::Host::Domain(host)), Some(port)) = (url.host(), url.port_or_known_default())
+    else {
+        return true;
+    };
+
+    let lookup = tokio::net::lookup_host((host, port));
+    match tokio::time::timeout(DNS_PRE_RESOLVE_TIMEOUT, lookup).await {
+        Ok(Ok(mut addrs)) => addrs.next().is_some(),
+        Ok(Err(e)) => {
+            tracing::debug!("Failed to resolve {host}: {e}");
+            false
+        }
+        Err(_) => {
+            tracing::debug!("Timed out resolving {host}");
+            false
+        }
+    }
+}
+
+/// The target of a redirect response, resolved against the URL the request was made to
+fn redirect_target(res: &hyper::Response<hyper::Body>, url: &str) -> Option<Url> {
+    if !res.status().is_redirection() {
+        return None;
+    }
+
+    let location = res.headers().get(http::header::LOCATION)?.to_str().ok()?;
+    Url::parse(url).ok()?.join(location).ok()
+}
+
+/// Whether webhooks may be sent to a redirect's target, which must pass the same checks as
+/// endpoint URLs
+fn is_valid_redirect_target(target: &Url, https_only: bool) -> bool {
+    match target.scheme() {
+        "https" => true,
+        "http" => !https_only,
+        _ => false,
+    }
+}
+
+/// The IP address of the server a response was received from, if the connection recorded it
+fn remote_ip(res: &hyper::Response<hyper::Body>) -> Option<String> {
+    res.extensions()
+        .get::<HttpInfo>()
+        .map(|info| info.remote_addr().ip().to_string())
+}
+
+/// Reads the body of an endpoint's response, truncated to [`RESPONSE_MAX_SIZE`]
+///
+/// Brotli encoded bodies are decompressed first, so that the truncation applies to the
+/// decompressed body.
+async fn read_response_body(res: hyper::Response<hyper::Body>) -> String {
+    let is_brotli = res
+        .headers()
+        .get(http::header::CONTENT_ENCODING)
+        .is_some_and(|encoding| encoding.as_bytes().eq_ignore_ascii_case(b"br"));
+
+    let bytes = match hyper::body::to_bytes(res.into_body()).await {
+        Ok(bytes) if is_brotli => match decompress_brotli(&bytes) {
+            Ok(decompressed) => decompressed,
+            Err(err) => {
+                tracing::debug!("Failed to decompress brotli response body: {err}");
+                bytes
+            }
+        },
+        Ok(bytes) => bytes,
+        Err(err) => return format!("Error reading response body: {err}"),
+    };
+
+    if bytes.len() > RESPONSE_MAX_SIZE {
+        bytes_to_string(bytes.slice(..RESPONSE_MAX_SIZE))
+    } else {
+        bytes_to_string(bytes)
+    }
+}
+
+/// Decompresses a brotli encoded body, stopping once there's more than [`RESPONSE_MAX_SIZE`] of
+/// it, so that small bodies which decompress to huge ones are never decompressed in full
+fn decompress_brotli(bytes: &[u8]) -> std::io::Result<bytes::Bytes> {
+    let mut decompressed = Vec::new();
+    brotli_decompressor::Decompressor::new(bytes, 4096)
+        .take(RESPONSE_MAX_SIZE as u64 + 1)
+        .read_to_end(&mut decompressed)?;
+    Ok(decompressed.into())
+}
+
 #[tracing::instrument(skip_all)]
 async fn make_http_call(
     DispatchContext { msg_task, endp, .. }: DispatchContext<'_>,
     PendingDispatch {
         method,
-        url,
+        mut url,
         headers,
+        headers_to_store,
+        store_endpoint_ip,
         payload,
         request_timeout,
         created_at,
+        follow_redirect,
+        https_only,
+        pre_resolve_dns,
     }: PendingDispatch,
     msg_dest: &messagedestination::Model,
     client: &WebhookClient,
 ) -> Result<CompletedDispatch> {
-    let req = RequestBuilder::new()
-        .method(method)
-        .uri_str(&url)
-        .map_err(|e| Error::validation(format!("URL is invalid: {e:?}")))?
-        .headers(headers)
-        .body(payload.into(), HeaderValue::from_static("application/json"))
-        .version(Version::HTTP_11)
-        .timeout(Duration::from_secs(request_timeout))
-        .build()
-        .map_err(Error::generic)?;
-
-    let attempt = messageattempt::ActiveModel {
+    let attempt = |url: String| messageattempt::ActiveModel {
         // Set both ID and created_at to the same timestamp
         id: Set(MessageAttemptId::new(created_at.into(), None)),
         created_at: Set(created_at.into()),
         msg_id: Set(msg_task.msg_id.clone()),
         endp_id: Set(endp.id.clone()),
         msg_dest_id: Set(msg_dest.id.clone()),
-        url: Set(endp.url.clone()),
+        url: Set(url),
         ended_at: Set(Some(Utc::now().into())),
         trigger_type: Set(msg_task.trigger_type),
+        request_headers_sent: Set(headers_to_store.clone()),
         ..Default::default()
     };
 
-    match client.execute(req).await {
-        Ok(res) => {
-            let status_code = res.status().as_u16() as i16;
-            let status = if res.status().is_success() {
-                MessageStatus::Success
+    // Otherwise a name which doesn't resolve looks like any other connection error, which may
+    // only be reported once the request times out
+    if pre_resolve_dns && !resolves(&url).await {
+        return Ok(CompletedDispatch::Failed(FailedDispatch(
+            messageattempt::ActiveModel {
+                response_status_code: Set(0),
+                response: Set("DNS resolution failed".to_owned()),
+                status: Set(MessageStatus::Fail),
+                ..attempt(url)
+            },
+            Error::generic("DNS resolution failed"),
+        )));
+    }
+
+    // Kept to send the webhook again if the endpoint redirects it elsewhere
+    let redirect_request =
+        follow_redirect.then(|| (method.clone(), headers.clone(), payload.clone()));
+    let req = build_request(method, &url, headers, payload, request_timeout)?;
+
+    let mut res = client.execute(req).await;
+    let mut moved_to = None;
+
+    // Only a single redirect is followed, so that endpoints can't send the worker around in
+    // circles
+    let redirect = match (&res, redirect_request) {
+        (Ok(first), Some(request)) => redirect_target(first, &url)
+            .filter(|target| is_valid_redirect_target(target, https_only))
+            .map(|target| {
+                let permanent = matches!(
+                    first.status(),
+                    StatusCode::MOVED_PERMANENTLY | StatusCode::PERMANENT_REDIRECT
+                );
+                (target, permanent, request)
+            }),
+        _ => None,
+    };
+    if let Some((target, permanent, (method, headers, payload))) = redirect {
+        tracing::debug!("Following redirect to {target}");
+        let req = build_request(method, target.as_str(), headers, payload, request_timeout)?;
+        res = client.execute(req).await;
+        url = target.to_string();
+        if permanent {
+            moved_to = Some(target);
+        }
+    }
+
+    let attempt = attempt(url.clone());
+
+    match res {
+        Ok(res) => {
+            let status_code = res.status().as_u16() as i16;
+            let status = if res.status().is_success() {
+                MessageStatus::Success
             } else {
                 MessageStatus::Fail
             };
 
+            let endpoint_ip = if store_endpoint_ip {
+                remote_ip(&res)
+            } else {
+                None
+            };
+
             let http_error = if !res.status().is_success() {
                 Some(WebhookClientError::FailureStatus(res.status()))
             } else {
                 None
             };
 
-            let body = match hyper::body::to_bytes(res.into_body()).await {
-                Ok(bytes) if bytes.len() > RESPONSE_MAX_SIZE => {
-                    bytes_to_string(bytes.slice(..RESPONSE_MAX_SIZE))
-                }
-                Ok(bytes) => bytes_to_string(bytes),
-                Err(err) => format!("Error reading response body: {err}"),
+            // Redirects are most likely the result of a misconfigured endpoint URL, so where they
+            // lead to is more useful than their body
+            let body = match redirect_target(&res, &url) {
+                Some(target) => serde_json::json!({ "redirect_to": target }).to_string(),
+                None => read_response_body(res).await,
             };
 
             let attempt = messageattempt::ActiveModel {
                 response_status_code: Set(status_code),
                 response: Set(body),
                 status: Set(status),
+                endpoint_ip: Set(endpoint_ip),
                 ..attempt
             };
 
@@ -390,7 +1172,9 @@ async fn make_http_call(
                     attempt,
                     Error::generic(err),
                 ))),
-                None => Ok(CompletedDispatch::Successful(SuccessfulDispatch(attempt))),
+                None => Ok(CompletedDispatch::Successful(SuccessfulDispatch(
+                    attempt, moved_to,
+                ))),
             }
         }
         Err(err) => Ok(CompletedDispatch::Failed(FailedDispatch(
@@ -405,33 +1189,305 @@ async fn make_http_call(
     }
 }
 
+/// The response of an endpoint to a [`send_test_webhook`] call
+pub struct TestDispatchResult {
+    /// The response's status code, or zero if no response was received
+    pub status_code: u16,
+    pub latency: Duration,
+    /// The response's body, or the error if no response was received
+    pub response_body: String,
+}
+
+/// Sends a test webhook to the endpoint, signed and with the same headers and timeout as regular
+/// deliveries, but without creating a message nor recording a message attempt.
+pub async fn send_test_webhook(
+    cfg: &Configuration,
+    client: &WebhookClient,
+    endp: &CreateMessageEndpoint,
+    payload: &str,
+) -> Result<TestDispatchResult> {
+    let now = Utc::now();
+    let msg_id = MessageId::new(now.into(), None);
+
+    let transformer = dispatch_transformer();
+    let payload = match transform_payload(transformer.as_deref(), endp, payload.to_owned()).await {
+        Ok(payload) => payload,
+        Err(e) => {
+            return Ok(TestDispatchResult {
+                status_code: 0,
+                latency: Duration::ZERO,
+                response_body: format!("Error transforming the payload: {e}"),
+            })
+        }
+    };
+
+    let signatures = sign_msg(
+        &cfg.encryption,
+        now.timestamp(),
+        &payload,
+        &msg_id,
+        endp.custom_signature_prefix.as_deref(),
+        None,
+        &endp.valid_signing_keys(),
+    )
+    .await;
+    let mut headers = generate_msg_headers(
+        now.timestamp(),
+        &msg_id,
+        signatures,
+        0,
+        now.timestamp(),
+        cfg.whitelabel_headers,
+        cfg.webhook_environment.as_deref(),
+        None,
+        None,
+        endp.headers.as_ref(),
+        &endp.url,
+    )?;
+    set_content_type(&mut headers, endp)?;
+    let payload = encrypt_payload(&mut headers, payload, endp, cfg.whitelabel_headers)?;
+
+    let req = build_request(
+        endp.http_method.0.clone(),
+        &endp.url,
+        headers,
+        payload,
+        cfg.worker_request_timeout as _,
+    )?;
+
+    let start = std::time::Instant::now();
+    Ok(match client.execute(req).await {
+        Ok(res) => {
+            let status_code = res.status().as_u16();
+            let latency = start.elapsed();
+            TestDispatchResult {
+                status_code,
+                latency,
+                response_body: read_response_body(res).await,
+            }
+        }
+        Err(err) => TestDispatchResult {
+            status_code: 0,
+            latency: start.elapsed(),
+            response_body: err.to_string(),
+        },
+    })
+}
+
+/// Puts off dispatching to an endpoint whose circuit breaker is open, until its cooldown is over or
+/// the trial delivery made after it is done. This isn't an attempt, so it counts towards neither
+/// the retry schedule nor disabling the endpoint.
+#[tracing::instrument(skip_all, fields(msg_dest_id = msg_dest.id.0))]
+async fn handle_circuit_open_dispatch(
+    WorkerContext { db, queue_tx, .. }: &WorkerContext<'_>,
+    DispatchContext { msg_task, .. }: DispatchContext<'_>,
+    msg_dest: messagedestination::Model,
+    open_until: DateTimeUtc,
+) -> Result<()> {
+    tracing::debug!("Circuit breaker open until {open_until}, putting off dispatch");
+
+    let msg_dest = messagedestination::ActiveModel {
+        next_attempt: Set(Some(open_until.into())),
+        ..msg_dest.into()
+    };
+    msg_dest.update(*db).await?;
+
+    let retry_in = (open_until - Utc::now()).to_std().unwrap_or_default();
+    queue_tx
+        .send(QueueTask::MessageV1(msg_task.clone()), Some(retry_in))
+        .await?;
+
+    Ok(())
+}
+
+/// Builds a failed attempt for a webhook which wasn't sent at all, with `response` as the reason
+fn unsent_dispatch(
+    DispatchContext { msg_task, endp, .. }: DispatchContext<'_>,
+    msg_dest: &messagedestination::Model,
+    response: String,
+) -> FailedDispatch {
+    let now = Utc::now();
+
+    let attempt = messageattempt::ActiveModel {
+        id: Set(MessageAttemptId::new(now.into(), None)),
+        created_at: Set(now.into()),
+        msg_id: Set(msg_task.msg_id.clone()),
+        endp_id: Set(endp.id.clone()),
+        msg_dest_id: Set(msg_dest.id.clone()),
+        url: Set(endp.url.clone()),
+        ended_at: Set(Some(now.into())),
+        trigger_type: Set(msg_task.trigger_type),
+        response_status_code: Set(0),
+        response: Set(response.clone()),
+        status: Set(MessageStatus::Fail),
+        ..Default::default()
+    };
+
+    FailedDispatch(attempt, Error::generic(response))
+}
+
 #[tracing::instrument(skip_all, fields(response_code, msg_dest_id = msg_dest.id.0))]
 async fn handle_successful_dispatch(
-    WorkerContext { cache, db, .. }: &WorkerContext<'_>,
-    DispatchContext {
+    worker_context: &WorkerContext<'_>,
+    dispatch_context: DispatchContext<'_>,
+    SuccessfulDispatch(mut attempt, moved_to): SuccessfulDispatch,
+    msg_dest: messagedestination::Model,
+) -> Result<()> {
+    let WorkerContext { cfg, cache, db, .. } = worker_context;
+    let DispatchContext {
         org_id,
         endp,
         app_id,
+        msg_task,
         ..
-    }: DispatchContext<'_>,
-    SuccessfulDispatch(mut attempt): SuccessfulDispatch,
-    msg_dest: messagedestination::Model,
-) -> Result<()> {
+    } = dispatch_context.clone();
+
+    // Recorded before anything else, so that the webhook isn't sent again even if the rest fails
+    if let Err(e) = cache
+        .set(
+            &DeliveredCacheKey::new(&msg_task.msg_id, &endp.id),
+            &DeliveredCacheValue {
+                delivered_at: Utc::now(),
+            },
+            Some(DELIVERED_CACHE_TTL),
+        )
+        .await
+    {
+        tracing::warn!(
+            error = &e as &dyn std::error::Error,
+            "Failed to record the successful delivery"
+        );
+    }
+
     attempt.ended_at = Set(Some(Utc::now().into()));
     let attempt = attempt.insert(*db).await?;
 
     let msg_dest = messagedestination::ActiveModel {
         status: Set(MessageStatus::Success),
         next_attempt: Set(None),
+        last_attempt_at: Set(Some(attempt.created_at)),
         ..msg_dest.into()
     };
     let _msg_dest = msg_dest.update(*db).await?;
 
-    process_endpoint_success(cache, app_id, org_id, endp).await?;
+    process_endpoint_success(cache, app_id, org_id, endp, cfg.endpoint_last_success_ttl).await?;
+
+    if let Some(url) = moved_to {
+        if let Err(e) = update_endpoint_url(db, cache, org_id, app_id, &endp.id, url).await {
+            tracing::warn!("Failed updating the URL of an endpoint that moved: {}", e);
+        }
+    }
 
     tracing::Span::current().record("response_code", attempt.response_status_code);
     tracing::info!("Webhook success.");
 
+    if let Some(event_type) = &endp.response_webhook_event_type {
+        if let Err(e) = send_response_webhook(
+            worker_context,
+            dispatch_context,
+            event_type,
+            &attempt.response,
+        )
+        .await
+        {
+            tracing::warn!("Failed sending the response as a webhook: {}", e);
+        }
+    }
+
+    Ok(())
+}
+
+/// Points an endpoint which permanently redirected a webhook to where it moved
+async fn update_endpoint_url(
+    db: &DatabaseConnection,
+    cache: &Cache,
+    org_id: &OrganizationId,
+    app_id: &ApplicationId,
+    endp_id: &EndpointId,
+    url: Url,
+) -> Result<()> {
+    let endp = endpoint::Entity::secure_find_by_id(app_id.clone(), endp_id.clone())
+        .one(db)
+        .await?
+        .ok_or_else(|| Error::generic(format!("Endpoint not found {app_id} {endp_id}")))?;
+
+    tracing::info!("Endpoint moved permanently, updating its URL to {url}");
+    let endp = endpoint::ActiveModel {
+        url: Set(url.into()),
+        ..endp.into()
+    };
+    endp.update(db).await?;
+
+    // The endpoint is cached along with its application, so drop it to have the new URL used
+    // right away
+    cache
+        .delete(&AppEndpointKey::new(org_id, app_id))
+        .await
+        .map_err(Error::cache)
+}
+
+/// Sends the body of a successful response on as a new message of the endpoint's
+/// `response_webhook_event_type`, which allows chaining webhooks. Chains are cut off after
+/// `max_webhook_chain_depth` messages, so endpoints which respond to each other can't loop forever.
+#[tracing::instrument(skip_all)]
+async fn send_response_webhook(
+    WorkerContext {
+        cfg,
+        cache,
+        db,
+        queue_tx,
+        ..
+    }: &WorkerContext<'_>,
+    DispatchContext {
+        org_id,
+        app_id,
+        chain_depth,
+        ..
+    }: DispatchContext<'_>,
+    event_type: &EventTypeName,
+    response: &str,
+) -> Result<()> {
+    if i32::from(chain_depth) >= i32::from(cfg.max_webhook_chain_depth) {
+        tracing::info!("Maximum webhook chain depth reached, not sending the response");
+        return Ok(());
+    }
+
+    let payload = RawPayload::from_string(response.trim().to_owned())
+        .ok()
+        .filter(|payload| validate_raw_payload_is_object(payload).is_ok())
+        .ok_or_else(|| Error::generic("Response body is not a JSON object"))?;
+
+    let app = application::Entity::secure_find_by_id(org_id.clone(), app_id.clone())
+        .one(*db)
+        .await?
+        .ok_or_else(|| Error::generic(format!("Application doesn't exist: {app_id}")))?;
+
+    let msg_in = MessageIn {
+        uid: None,
+        event_type: event_type.clone(),
+        payload,
+        channels: None,
+        tags: None,
+        payload_retention_period: 90,
+        deadline: None,
+        signed_metadata: None,
+        payload_version: None,
+        endpoint_metadata_selector: None,
+    };
+
+    create_message_inner(
+        db,
+        (*queue_tx).clone(),
+        (*cache).clone(),
+        false,
+        None,
+        chain_depth + 1,
+        cfg.compress_payload_threshold_bytes,
+        msg_in,
+        app,
+    )
+    .await?;
+
     Ok(())
 }
 
@@ -476,11 +1532,16 @@ async fn handle_failed_dispatch(
     tracing::Span::current().record("response_code", attempt.response_status_code);
     tracing::info!("Webhook failure.");
 
-    let retry_schedule = &cfg.retry_schedule;
+    let retry_schedule = endp.retry_schedule.as_ref().unwrap_or(&cfg.retry_schedule);
 
     let attempt_count = msg_task.attempt_count as usize;
     if msg_task.trigger_type == MessageAttemptTriggerType::Manual {
         tracing::debug!("Manual retry failed");
+        let msg_dest = messagedestination::ActiveModel {
+            last_attempt_at: Set(Some(attempt.created_at)),
+            ..msg_dest.into()
+        };
+        let _msg_dest = msg_dest.update(*db).await?;
         Ok(())
     } else if attempt_count < retry_schedule.len() {
         tracing::debug!(
@@ -496,11 +1557,12 @@ async fn handle_failed_dispatch(
             Utc::now() + chrono::Duration::from_std(retry_delay).expect("Error parsing duration");
         let msg_dest = messagedestination::ActiveModel {
             next_attempt: Set(Some(next_attempt_time.into())),
+            last_attempt_at: Set(Some(attempt.created_at)),
             ..msg_dest.into()
         };
         let _msg_dest = msg_dest.update(*db).await?;
 
-        if attempt_count == (OP_WEBHOOKS_SEND_FAILING_EVENT_AFTER - 1) {
+        if attempt_count == (cfg.op_webhooks_failing_event_after - 1) {
             if let Err(e) = op_webhook_sender
                 .send_operational_webhook(
                     org_id,
@@ -543,6 +1605,7 @@ async fn handle_failed_dispatch(
         let msg_dest = messagedestination::ActiveModel {
             status: Set(MessageStatus::Fail),
             next_attempt: Set(None),
+            last_attempt_at: Set(Some(attempt.created_at)),
             ..msg_dest.into()
         };
         let _msg_dest = msg_dest.update(*db).await?;
@@ -562,15 +1625,38 @@ async fn handle_failed_dispatch(
             )
             .await?;
 
-        match process_endpoint_failure(
+        let EndpointFailureStatus {
+            failure_count,
+            first_failure_at,
+            disable,
+        } = process_endpoint_failure(
             cache,
             app_id,
             org_id,
-            endp,
+            &endp.id,
             cfg.endpoint_failure_disable_after,
         )
-        .await?
+        .await?;
+
+        if cfg.op_webhooks_failure_count_threshold != 0
+            && failure_count == cfg.op_webhooks_failure_count_threshold
         {
+            op_webhook_sender
+                .send_operational_webhook(
+                    org_id,
+                    OperationalWebhook::FailureCountThreshold(EndpointFailureCountEventData {
+                        app_id: app_id.clone(),
+                        app_uid: app_uid.cloned(),
+                        endpoint_id: msg_task.endpoint_id.clone(),
+                        endpoint_uid: None,
+                        failure_count,
+                        fail_since: first_failure_at,
+                    }),
+                )
+                .await?;
+        }
+
+        match disable {
             None => Ok(()),
 
             Some(EndpointDisableInfo { first_failure_at }) => {
@@ -613,6 +1699,113 @@ async fn handle_failed_dispatch(
     }
 }
 
+/// Marks a message destination as expired instead of dispatching to it, because the message's
+/// deadline has passed.
+#[tracing::instrument(skip_all, fields(msg_dest_id = msg_dest.id.0))]
+async fn handle_expired_dispatch(
+    WorkerContext {
+        db,
+        op_webhook_sender,
+        ..
+    }: &WorkerContext<'_>,
+    DispatchContext {
+        org_id,
+        app_id,
+        app_uid,
+        msg_uid,
+        msg_task,
+        ..
+    }: DispatchContext<'_>,
+    msg_dest: messagedestination::Model,
+    deadline: DateTimeUtc,
+) -> Result<()> {
+    tracing::info!("Message deadline passed at {deadline}, skipping dispatch");
+
+    let msg_dest = messagedestination::ActiveModel {
+        status: Set(MessageStatus::Expired),
+        next_attempt: Set(None),
+        ..msg_dest.into()
+    };
+    msg_dest.update(*db).await?;
+
+    op_webhook_sender
+        .send_operational_webhook(
+            org_id,
+            OperationalWebhook::MessageExpired(MessageExpiredEventData {
+                app_id: app_id.clone(),
+                app_uid: app_uid.cloned(),
+                msg_id: msg_task.msg_id.clone(),
+                msg_event_id: msg_uid.cloned(),
+                endpoint_id: msg_task.endpoint_id.clone(),
+                deadline,
+            }),
+        )
+        .await
+}
+
+/// Lets the organization know that an endpoint's circuit breaker has opened or closed
+async fn send_circuit_operational_webhook(
+    WorkerContext {
+        op_webhook_sender, ..
+    }: &WorkerContext<'_>,
+    DispatchContext {
+        org_id,
+        app_id,
+        app_uid,
+        endp,
+        ..
+    }: DispatchContext<'_>,
+    transition: CircuitTransition,
+) -> Result<()> {
+    let event = |open_until, consecutive_failures| EndpointCircuitEvent {
+        app_id: app_id.clone(),
+        app_uid: app_uid.cloned(),
+        endpoint_id: endp.id.clone(),
+        open_until,
+        consecutive_failures,
+    };
+    let payload = match transition {
+        CircuitTransition::Opened {
+            until,
+            consecutive_failures,
+        } => OperationalWebhook::EndpointCircuitOpen(event(Some(until), consecutive_failures)),
+        CircuitTransition::Closed {
+            consecutive_failures,
+        } => OperationalWebhook::EndpointCircuitClosed(event(None, consecutive_failures)),
+    };
+
+    op_webhook_sender
+        .send_operational_webhook(org_id, payload)
+        .await
+}
+
+/// Puts off dispatching to an endpoint or application whose rate limit has been reached. This
+/// isn't an attempt, so the task is sent again as-is, without counting towards the retry schedule.
+#[tracing::instrument(skip_all, fields(msg_dest_id = msg_dest.id.0))]
+async fn handle_rate_limited_dispatch(
+    WorkerContext { db, queue_tx, .. }: &WorkerContext<'_>,
+    DispatchContext { msg_task, .. }: DispatchContext<'_>,
+    msg_dest: messagedestination::Model,
+    retry_in: Duration,
+) -> Result<()> {
+    tracing::debug!("Rate limited, retrying in {retry_in:?}");
+
+    let next_attempt_time =
+        Utc::now() + chrono::Duration::from_std(retry_in).expect("Error parsing duration");
+    let msg_dest = messagedestination::ActiveModel {
+        status: Set(MessageStatus::RateLimited),
+        next_attempt: Set(Some(next_attempt_time.into())),
+        ..msg_dest.into()
+    };
+    msg_dest.update(*db).await?;
+
+    queue_tx
+        .send(QueueTask::MessageV1(msg_task.clone()), Some(retry_in))
+        .await?;
+
+    Ok(())
+}
+
 #[derive(Clone)]
 struct DispatchContext<'a> {
     msg_task: &'a MessageTask,
@@ -622,6 +1815,11 @@ struct DispatchContext<'a> {
     app_id: &'a ApplicationId,
     app_uid: Option<&'a ApplicationUid>,
     msg_uid: Option<&'a MessageUid>,
+    msg_created_at: DateTimeUtc,
+    /// See [`message::Model::chain_depth`]
+    chain_depth: i16,
+    signed_metadata: Option<&'a MessageSignedMetadata>,
+    payload_version: Option<&'a PayloadVersion>,
 }
 
 /// Dispatches one webhook
@@ -639,14 +1837,23 @@ async fn dispatch_message_task(
     msg_task: MessageTask,
     payload: &str,
     endp: CreateMessageEndpoint,
-    msg_dest: messagedestination::Model,
+    mut msg_dest: messagedestination::Model,
 ) -> Result<()> {
-    let WorkerContext { webhook_client, .. } = worker_context;
+    let WorkerContext {
+        cfg,
+        cache,
+        db,
+        webhook_client,
+        app_rate_limiter,
+        ..
+    } = worker_context;
 
     tracing::trace!("Dispatch start");
 
-    if (msg_dest.status != MessageStatus::Pending && msg_dest.status != MessageStatus::Sending)
-        && (msg_task.trigger_type != MessageAttemptTriggerType::Manual)
+    if !matches!(
+        msg_dest.status,
+        MessageStatus::Pending | MessageStatus::Sending | MessageStatus::RateLimited
+    ) && (msg_task.trigger_type != MessageAttemptTriggerType::Manual)
     {
         // TODO: it happens when this message destination is "resent". This leads to 2 queue tasks with the same message destination
         tracing::warn!(
@@ -657,6 +1864,32 @@ async fn dispatch_message_task(
         return Ok(());
     }
 
+    // Manual retries are explicitly requested, so they're sent even if already delivered
+    if msg_task.trigger_type != MessageAttemptTriggerType::Manual {
+        match cache
+            .get::<DeliveredCacheValue>(&DeliveredCacheKey::new(&msg_task.msg_id, &endp.id))
+            .await
+        {
+            Ok(Some(_)) => {
+                tracing::info!("Message was already delivered to this endpoint, skipping dispatch");
+                let msg_dest = messagedestination::ActiveModel {
+                    status: Set(MessageStatus::Success),
+                    next_attempt: Set(None),
+                    ..msg_dest.into()
+                };
+                msg_dest.update(*db).await?;
+                return Ok(());
+            }
+            Ok(None) => {}
+            Err(e) => {
+                tracing::warn!(
+                    error = &e as &dyn std::error::Error,
+                    "Failed to check whether the message was already delivered"
+                );
+            }
+        }
+    }
+
     let dispatch_context = DispatchContext {
         msg_task: &msg_task,
         payload,
@@ -665,16 +1898,138 @@ async fn dispatch_message_task(
         app_id: &app.id,
         app_uid: app.uid.as_ref(),
         msg_uid: msg.uid.as_ref(),
+        msg_created_at: msg.created_at.with_timezone(&Utc),
+        chain_depth: msg.chain_depth,
+        signed_metadata: msg.signed_metadata.as_ref(),
+        payload_version: msg.payload_version.as_ref(),
+    };
+
+    // Late messages aren't useful, so neither the first attempt nor any retry is made after the
+    // deadline
+    if let Some(deadline) = msg.deadline {
+        let deadline = deadline.with_timezone(&Utc);
+        if Utc::now() > deadline {
+            return handle_expired_dispatch(worker_context, dispatch_context, msg_dest, deadline)
+                .await;
+        }
+    }
+
+    let circuit_breaker = CircuitBreaker::new(
+        cache,
+        &app.org_id,
+        &app.id,
+        &endp.id,
+        cfg.endpoint_circuit_breaker_threshold,
+        cfg.endpoint_circuit_breaker_cooldown,
+    );
+    // Manual retries are explicitly requested, so they always go through
+    let circuit_state = if msg_task.trigger_type == MessageAttemptTriggerType::Manual {
+        CircuitState::Closed
+    } else {
+        circuit_breaker.acquire().await?
     };
 
-    let dispatch = prepare_dispatch(worker_context, dispatch_context.clone()).await?;
+    if let CircuitState::Open { until } = circuit_state {
+        return handle_circuit_open_dispatch(worker_context, dispatch_context, msg_dest, until)
+            .await;
+    }
+
+    // Checked before preparing the dispatch, so that the payload isn't transformed and
+    // signed again every time the task is put off
+    let rate_limit = EndpointRateLimit::new(cache, &endp.id, endp.rate_limit);
+    if let RateLimitState::Limited { retry_in } = rate_limit.check().await? {
+        tracing::debug!("Endpoint rate limit reached");
+        return handle_rate_limited_dispatch(worker_context, dispatch_context, msg_dest, retry_in)
+            .await;
+    }
+
+    // Only after the endpoint's limit, so that dispatches it puts off don't use up the
+    // application's tokens
+    if let RateLimitState::Limited { retry_in } =
+        app_rate_limiter.acquire(db, &app.org_id, &app.id).await?
+    {
+        tracing::debug!("Application rate limit reached");
+        return handle_rate_limited_dispatch(worker_context, dispatch_context, msg_dest, retry_in)
+            .await;
+    }
+
+    // Only counted once both limits allow it, so that dispatches the application's limit puts
+    // off don't use up the endpoint's allowance. Concurrent dispatches may have reached the
+    // endpoint's limit since it was checked, in which case this one is put off after all.
+    if let RateLimitState::Limited { retry_in } = rate_limit.acquire().await? {
+        tracing::debug!("Endpoint rate limit reached");
+        return handle_rate_limited_dispatch(worker_context, dispatch_context, msg_dest, retry_in)
+            .await;
+    }
+
+    let dispatch = prepare_dispatch(worker_context, dispatch_context.clone(), msg).await?;
     let completed = match dispatch {
         IncompleteDispatch::Pending(pending) => {
+            let is_first_attempt =
+                msg_task.attempt_count == 0 && msg_dest.first_attempt_at.is_none();
+            let was_rate_limited = msg_dest.status == MessageStatus::RateLimited;
+            if is_first_attempt || was_rate_limited {
+                let mut update: messagedestination::ActiveModel = msg_dest.into();
+                // Unlike `created_at`, this excludes the time the message spent queued
+                if is_first_attempt {
+                    update.first_attempt_at = Set(Some(pending.created_at.into()));
+                }
+                if was_rate_limited {
+                    update.status = Set(MessageStatus::Sending);
+                }
+                msg_dest = update.update(*db).await?;
+            }
             make_http_call(dispatch_context.clone(), pending, &msg_dest, webhook_client).await?
         }
         IncompleteDispatch::Failed(failed) => CompletedDispatch::Failed(failed),
+        IncompleteDispatch::TransformationFailed(e) => {
+            tracing::warn!("Failed to transform the payload, skipping dispatch: {e}");
+            // Not the endpoint's fault, so it isn't counted by the circuit breaker
+            let failed = unsent_dispatch(
+                dispatch_context.clone(),
+                &msg_dest,
+                format!("Error transforming the payload: {e}"),
+            );
+            return handle_failed_dispatch(worker_context, dispatch_context, failed, msg_dest)
+                .await;
+        }
+        IncompleteDispatch::Filtered => {
+            tracing::debug!("Filtered out by a pre-dispatch hook, skipping dispatch");
+            messagedestination::ActiveModel {
+                status: Set(MessageStatus::Filtered),
+                next_attempt: Set(None),
+                ..msg_dest.into()
+            }
+            .update(*db)
+            .await?;
+            return Ok(());
+        }
     };
 
+    // The HTTP call has been made, so the attempt has to be recorded even if the circuit breaker
+    // can't be updated
+    let transition = match completed {
+        CompletedDispatch::Successful(_) => circuit_breaker.record_success().await,
+        CompletedDispatch::Failed(_) => circuit_breaker.record_failure().await,
+    }
+    .unwrap_or_else(|e| {
+        tracing::warn!("Failed updating the endpoint's circuit breaker: {}", e);
+        None
+    });
+    if let Some(transition) = transition {
+        // The attempt still has to be recorded, so failing to report the transition doesn't fail
+        // the delivery
+        if let Err(e) =
+            send_circuit_operational_webhook(worker_context, dispatch_context.clone(), transition)
+                .await
+        {
+            tracing::warn!(
+                "Failed sending the circuit breaker operational webhook: {}",
+                e
+            );
+        }
+    }
+
     match completed {
         CompletedDispatch::Successful(success) => {
             handle_successful_dispatch(worker_context, dispatch_context, success, msg_dest).await
@@ -696,7 +2051,7 @@ fn bytes_to_string(bytes: bytes::Bytes) -> String {
 #[tracing::instrument(
     skip_all,
     level = "error",
-    fields(msg_id, app_id, org_id, instance_id, task_type = queue_task.task_type())
+    fields(msg_id, correlation_id, app_id, org_id, instance_id, task_type = queue_task.task_type())
 )]
 async fn process_queue_task(
     worker_context: WorkerContext<'_>,
@@ -710,25 +2065,154 @@ async fn process_queue_task(
         })
 }
 
-/// Manages preparation and execution of a QueueTask type
-async fn process_queue_task_inner(
-    worker_context: WorkerContext<'_>,
-    queue_task: QueueTask,
-) -> Result<()> {
-    let WorkerContext { db, cache, .. }: WorkerContext<'_> = worker_context;
+/// When a task became due, if it's known.
+///
+/// Tasks don't record when they were enqueued, so this is the message's creation time for the
+/// first dispatch of new messages, and the destination's scheduled next attempt for retries.
+/// Manual resends and endpoint recoveries are for older messages, so their due time isn't known.
+fn task_due_at(
+    trigger_type: MessageAttemptTriggerType,
+    attempt_count: u16,
+    msg_id: &MessageId,
+    destination: Option<&messagedestination::Model>,
+) -> Option<DateTimeUtc> {
+    if trigger_type != MessageAttemptTriggerType::Scheduled {
+        return None;
+    }
+
+    match destination {
+        None => Some(msg_id.timestamp()),
+        Some(destination) if attempt_count > 0 => destination.next_attempt.map(Into::into),
+        Some(_) => None,
+    }
+}
+
+/// Records how long a task waited in the queue, returning whether it waited for longer than
+/// `warning_threshold`
+fn record_task_queue_age(
+    due_at: DateTimeUtc,
+    now: DateTimeUtc,
+    warning_threshold: Duration,
+) -> bool {
+    // Tasks may be picked up slightly early, or clocks may disagree
+    let age = (now - due_at).to_std().unwrap_or_default();
+    TASK_QUEUE_AGE.record(age.as_secs_f64(), &[]);
+
+    let exceeded = age > warning_threshold;
+    if exceeded {
+        tracing::warn!(
+            queue_age_secs = age.as_secs(),
+            "Task waited in the queue for longer than {}s",
+            warning_threshold.as_secs()
+        );
+    }
+    exceeded
+}
+
+/// Fetches a message and its content from a read replica, or from the primary if it hasn't reached
+/// the replica yet
+async fn fetch_message(
+    db: &DatabaseConnection,
+    read_db: &DatabaseConnection,
+    msg_id: &MessageId,
+) -> Result<(message::Model, Option<messagecontent::Model>)> {
+    let query =
+        || message::Entity::find_by_id(msg_id.clone()).find_also_related(messagecontent::Entity);
+
+    let found = match query().one(read_db).await? {
+        Some(found) => Some(found),
+        None => query().one(db).await?,
+    };
+
+    found.ok_or_else(|| Error::generic(format!("Unexpected: message doesn't exist {msg_id}")))
+}
+
+/// Cancels a destination which is still to be delivered but never can be, rather than leaving it
+/// pending forever
+async fn cancel_destination(
+    db: &DatabaseConnection,
+    destination: messagedestination::Model,
+) -> Result<()> {
+    if !matches!(
+        destination.status,
+        MessageStatus::Pending | MessageStatus::Sending | MessageStatus::RateLimited
+    ) {
+        return Ok(());
+    }
+
+    messagedestination::ActiveModel {
+        status: Set(MessageStatus::Cancelled),
+        next_attempt: Set(None),
+        ..destination.into()
+    }
+    .update(db)
+    .await?;
+
+    Ok(())
+}
+
+/// Cancels a destination which is still to be delivered if its message's application has been
+/// deleted
+async fn cancel_deleted_app_destination(
+    db: &DatabaseConnection,
+    msg: &message::Model,
+    destination: messagedestination::Model,
+) -> Result<()> {
+    let app_deleted = application::Entity::find_by_id(msg.app_id.clone())
+        .filter(application::Column::OrgId.eq(msg.org_id.clone()))
+        .filter(application::Column::Deleted.eq(true))
+        .one(db)
+        .await?
+        .is_some();
+    if !app_deleted {
+        return Ok(());
+    }
+
+    tracing::info!("Application was deleted, cancelling the delivery");
+    cancel_destination(db, destination).await
+}
+
+/// Whether the task is for a retry which has already been made. The stuck delivery detector may
+/// queue a delivery's retry again while the original task is still waiting in a backed up queue,
+/// in which case whichever of the two is handled last is a duplicate.
+async fn is_duplicate_retry(
+    db: &DatabaseConnection,
+    task: &MessageTask,
+    destination: &messagedestination::Model,
+) -> Result<bool> {
+    // Only retries are ever queued again
+    if task.trigger_type != MessageAttemptTriggerType::Scheduled || task.attempt_count == 0 {
+        return Ok(false);
+    }
+
+    let attempt_count = messageattempt::Entity::find()
+        .filter(messageattempt::Column::MsgDestId.eq(destination.id.clone()))
+        .filter(messageattempt::Column::TriggerType.eq(MessageAttemptTriggerType::Scheduled))
+        .count(db)
+        .await?;
+    Ok(attempt_count > task.attempt_count.into())
+}
+
+/// Manages preparation and execution of a QueueTask type
+async fn process_queue_task_inner(
+    worker_context: WorkerContext<'_>,
+    queue_task: QueueTask,
+) -> Result<()> {
+    let WorkerContext {
+        cfg,
+        db,
+        read_db,
+        cache,
+        queue_tx,
+        ..
+    }: WorkerContext<'_> = worker_context;
     let span = tracing::Span::current();
 
     let (mut msg, msg_content, force_endpoint, destination, trigger_type, attempt_count) =
         match queue_task {
             QueueTask::HealthCheck => return Ok(()),
             QueueTask::MessageV1(task) => {
-                let (msg, msg_content) = message::Entity::find_by_id(task.msg_id.clone())
-                    .find_also_related(messagecontent::Entity)
-                    .one(db)
-                    .await?
-                    .ok_or_else(|| {
-                        Error::generic(format!("Unexpected: message doesn't exist {}", task.msg_id))
-                    })?;
+                let (msg, msg_content) = fetch_message(db, read_db, &task.msg_id).await?;
 
                 let destination =
                     messagedestination::Entity::secure_find_by_msg(task.msg_id.clone())
@@ -742,6 +2226,14 @@ async fn process_queue_task_inner(
                             ))
                         })?;
 
+                if is_duplicate_retry(db, &task, &destination).await? {
+                    tracing::debug!(
+                        "Retry {} was already made, skipping duplicate task",
+                        task.attempt_count
+                    );
+                    return Ok(());
+                }
+
                 (
                     msg,
                     msg_content,
@@ -752,13 +2244,7 @@ async fn process_queue_task_inner(
                 )
             }
             QueueTask::MessageBatch(task) => {
-                let (msg, msg_content) = message::Entity::find_by_id(task.msg_id.clone())
-                    .find_also_related(messagecontent::Entity)
-                    .one(db)
-                    .await?
-                    .ok_or_else(|| {
-                        Error::generic(format!("Unexpected: message doesn't exist {}", task.msg_id))
-                    })?;
+                let (msg, msg_content) = fetch_message(db, read_db, &task.msg_id).await?;
                 (
                     msg,
                     msg_content,
@@ -771,11 +2257,21 @@ async fn process_queue_task_inner(
         };
 
     span.record("msg_id", &msg.id.0);
+    if let Some(correlation_id) = &msg.correlation_id {
+        span.record("correlation_id", &correlation_id.0);
+    }
     span.record("app_id", &msg.app_id.0);
     span.record("org_id", &msg.org_id.0);
 
+    if let Some(due_at) = task_due_at(trigger_type, attempt_count, &msg.id, destination.as_ref()) {
+        record_task_queue_age(due_at, Utc::now(), cfg.worker_max_queue_age_warning);
+    }
+
     let payload = msg_content
-        .and_then(|m| String::from_utf8(m.payload).ok())
+        .map(|m| m.into_payload())
+        .transpose()?
+        .flatten()
+        .and_then(|payload| String::from_utf8(payload).ok())
         .or_else(|| {
             msg.legacy_payload
                 .take()
@@ -784,25 +2280,51 @@ async fn process_queue_task_inner(
 
     let Some(payload) = payload else {
         tracing::warn!("Message payload is NULL; payload has most likely expired");
+        if let Some(destination) = destination {
+            cancel_destination(db, destination).await?;
+        }
         return Ok(());
     };
 
-    let Some(create_message_app) = CreateMessageApp::layered_fetch(
+    let create_message_app = match CreateMessageApp::layered_fetch(
         cache,
-        db,
+        read_db,
         None,
         msg.org_id.clone(),
         msg.app_id.clone(),
         Duration::from_secs(30),
     )
     .await?
-    else {
+    {
+        Some(app) => Some(app),
+        // The application may not have reached the replica yet
+        None => {
+            CreateMessageApp::layered_fetch(
+                cache,
+                db,
+                None,
+                msg.org_id.clone(),
+                msg.app_id.clone(),
+                Duration::from_secs(30),
+            )
+            .await?
+        }
+    };
+    let Some(create_message_app) = create_message_app else {
         tracing::info!("Application doesn't exist: {}", &msg.app_id);
+        if let Some(destination) = destination {
+            cancel_deleted_app_destination(db, &msg, destination).await?;
+        }
         return Ok(());
     };
 
     let endpoints: Vec<CreateMessageEndpoint> = create_message_app
-        .filtered_endpoints(trigger_type, &msg.event_type, msg.channels.as_ref())
+        .filtered_endpoints(
+            trigger_type,
+            &msg.event_type,
+            msg.channels.as_ref(),
+            msg.endpoint_metadata_selector.as_ref(),
+        )
         .iter()
         .filter(|endpoint| match force_endpoint.as_ref() {
             Some(endp_id) => endp_id == &endpoint.id,
@@ -811,17 +2333,36 @@ async fn process_queue_task_inner(
         .cloned()
         .collect();
 
+    let is_batch = destination.is_none();
+    if endpoints.is_empty() {
+        if let Some(destination) = destination {
+            tracing::info!("Endpoint was disabled or deleted, cancelling the delivery");
+            cancel_destination(db, destination).await?;
+        }
+        return Ok(());
+    }
+
     let destinations = match destination {
         Some(d) => vec![d],
         None => {
             let destinations: Vec<_> = endpoints
                 .iter()
-                .map(|endpoint| messagedestination::ActiveModel {
-                    msg_id: Set(msg.id.clone()),
-                    endp_id: Set(endpoint.id.clone()),
-                    next_attempt: Set(Some(Utc::now().into())),
-                    status: Set(MessageStatus::Sending),
-                    ..messagedestination::ActiveModel::new()
+                .map(|endpoint| {
+                    // Messages below the endpoint's minimum payload version are recorded, but
+                    // never sent
+                    let (status, next_attempt) =
+                        if endpoint.accepts_payload_version(msg.payload_version.as_ref()) {
+                            (MessageStatus::Sending, Some(Utc::now().into()))
+                        } else {
+                            (MessageStatus::Incompatible, None)
+                        };
+                    messagedestination::ActiveModel {
+                        msg_id: Set(msg.id.clone()),
+                        endp_id: Set(endpoint.id.clone()),
+                        next_attempt: Set(next_attempt),
+                        status: Set(status),
+                        ..messagedestination::ActiveModel::new()
+                    }
                 })
                 .collect();
 
@@ -837,9 +2378,10 @@ async fn process_queue_task_inner(
         }
     };
 
-    let futures = endpoints
+    let (tasks, futures): (Vec<_>, Vec<_>) = endpoints
         .into_iter()
         .zip(destinations)
+        .filter(|(_, destination)| destination.status != MessageStatus::Incompatible)
         .map(|(endpoint, destination)| {
             let task = MessageTask {
                 msg_id: msg.id.clone(),
@@ -849,19 +2391,32 @@ async fn process_queue_task_inner(
                 trigger_type,
             };
 
-            dispatch_message_task(
+            let dispatch = dispatch_message_task(
                 &worker_context,
                 &msg,
                 &create_message_app,
-                task,
+                task.clone(),
                 &payload,
                 endpoint,
                 destination,
-            )
-        });
+            );
+            (task, dispatch)
+        })
+        .unzip();
 
     let join = future::join_all(futures).await;
 
+    // Retrying the whole batch would dispatch to the endpoints which succeeded again, so only the
+    // failed dispatches are retried
+    if is_batch {
+        return requeue_failed_dispatches(
+            queue_tx,
+            cfg.retry_schedule.first().copied(),
+            tasks.into_iter().zip(join),
+        )
+        .await;
+    }
+
     let errs: Vec<_> = join.iter().filter(|x| x.is_err()).collect();
     if !errs.is_empty() {
         return Err(Error::generic(format!(
@@ -872,37 +2427,75 @@ async fn process_queue_task_inner(
     Ok(())
 }
 
+/// Sends the tasks of a batch's dispatches which failed unexpectedly again on their own, as their
+/// first retry
+async fn requeue_failed_dispatches(
+    queue_tx: &TaskQueueProducer,
+    retry_in: Option<Duration>,
+    dispatches: impl IntoIterator<Item = (MessageTask, Result<()>)>,
+) -> Result<()> {
+    let mut retries = Vec::new();
+    for (task, result) in dispatches {
+        let Err(e) = result else {
+            continue;
+        };
+
+        tracing::error!(
+            endp_id = task.endpoint_id.0.as_str(),
+            "Dispatch failed unexpectedly, retrying it: {e}"
+        );
+        let task = QueueTask::MessageV1(MessageTask {
+            attempt_count: 1,
+            ..task
+        });
+        retries.push((task, retry_in));
+    }
+
+    queue_tx.send_many(retries).await
+}
+
 pub static LAST_QUEUE_POLL: Lazy<AtomicU64> = Lazy::new(|| get_unix_timestamp().into());
 
 async fn update_last_poll_time() {
     LAST_QUEUE_POLL.swap(get_unix_timestamp(), Ordering::Relaxed);
 }
 
-/// Listens on the message queue for new tasks
+/// Creates the client webhooks are delivered with
+pub fn new_webhook_client(cfg: &Configuration) -> WebhookClient {
+    WebhookClient::new(
+        cfg.whitelist_subnets.clone(),
+        Some(Arc::new(vec!["backend".to_owned()])),
+        cfg.dangerous_disable_tls_verification,
+        cfg.proxy_config.as_ref(),
+    )
+}
+
+/// Listens on the message queue for new tasks, until a message is sent on `shutdown_rx` (or all
+/// its senders are dropped). It then waits for the tasks in flight to finish before returning.
 #[allow(clippy::too_many_arguments)]
 pub async fn queue_handler(
     cfg: &Configuration,
     cache: Cache,
-    db: DatabaseConnection,
+    db: DbPool,
     queue_tx: TaskQueueProducer,
     mut queue_rx: TaskQueueConsumer,
     op_webhook_sender: OperationalWebhookSender,
+    mut shutdown_rx: broadcast::Receiver<()>,
 ) -> Result<()> {
     static NUM_WORKERS: AtomicUsize = AtomicUsize::new(0);
 
     let task_limit = cfg.worker_max_tasks;
-    if task_limit == 0 {
+    let single_worker_mode = cfg.single_worker_mode;
+    if single_worker_mode {
+        tracing::info!("Worker processing tasks one at a time");
+    } else if task_limit == 0 {
         tracing::info!("Worker concurrent task limit: unlimited");
     } else {
         tracing::info!("Worker concurrent task limit: {}", task_limit);
     }
 
-    let webhook_client = WebhookClient::new(
-        cfg.whitelist_subnets.clone(),
-        Some(Arc::new(vec!["backend".to_owned()])),
-        cfg.dangerous_disable_tls_verification,
-        cfg.proxy_config.as_ref(),
-    );
+    let webhook_client = new_webhook_client(cfg);
+    let app_rate_limiter = AppRateLimiter::default();
 
     tokio::spawn(
         async move {
@@ -921,6 +2514,7 @@ pub async fn queue_handler(
         )),
     );
 
+    let mut consecutive_errors: u32 = 0;
     loop {
         if task_limit > 0 {
             let num_workers = NUM_WORKERS.load(Ordering::Relaxed);
@@ -930,28 +2524,24 @@ pub async fn queue_handler(
             }
         }
 
-        if crate::SHUTTING_DOWN.load(Ordering::SeqCst) {
-            tokio::join!(async move {
-                let mut interval = tokio::time::interval(Duration::from_millis(500));
-                loop {
-                    interval.tick().await;
-                    let num_workers = NUM_WORKERS.load(Ordering::Relaxed);
-                    if num_workers > 0 {
-                        tracing::info!(
-                            "{} active workers, waiting to shut down worker.",
-                            num_workers
-                        );
-                    } else {
-                        tracing::info!("No active workers, shutting down worker.");
-                        break;
-                    }
-                }
-            });
-            break;
-        }
-
-        match queue_rx.receive_all().await {
+        let receive = async {
+            if single_worker_mode {
+                queue_rx.receive_one().await.map(Vec::from_iter)
+            } else {
+                queue_rx.receive_all().await
+            }
+        };
+        // Waiting for tasks can take a while when the queue is empty, so shutting down doesn't
+        // wait for it to finish. Tasks which were being received but weren't acked are delivered
+        // again by the queue.
+        let batch = tokio::select! {
+            biased;
+            _ = shutdown_rx.recv() => break,
+            batch = receive => batch,
+        };
+        match batch {
             Ok(batch) => {
+                consecutive_errors = 0;
                 for delivery in batch {
                     let cfg = cfg.clone();
                     let cache = cache.clone();
@@ -960,16 +2550,19 @@ pub async fn queue_handler(
                     let queue_task = delivery.task.clone();
                     let op_webhook_sender = op_webhook_sender.clone();
                     let webhook_client = webhook_client.clone();
+                    let app_rate_limiter = app_rate_limiter.clone();
 
-                    tokio::spawn(async move {
+                    let task = async move {
                         NUM_WORKERS.fetch_add(1, Ordering::Relaxed);
                         let worker_context = WorkerContext {
                             cfg: &cfg,
-                            db: &db,
+                            db: db.write(),
+                            read_db: db.read(),
                             cache: &cache,
                             op_webhook_sender: &op_webhook_sender,
                             queue_tx: &queue_tx,
                             webhook_client: &webhook_client,
+                            app_rate_limiter: &app_rate_limiter,
                         };
 
                         let queue_task =
@@ -992,36 +2585,86 @@ pub async fn queue_handler(
                         }
 
                         NUM_WORKERS.fetch_sub(1, Ordering::Relaxed);
-                    });
+                    };
+
+                    if single_worker_mode {
+                        task.await;
+                    } else {
+                        tokio::spawn(task);
+                    }
                 }
             }
             Err(err) => {
-                tracing::error!("Error receiving task: {:?}", err);
-                sleep(tokio::time::Duration::from_millis(10)).await;
+                consecutive_errors = consecutive_errors.saturating_add(1);
+                let delay = cfg.queue_error_backoff.delay(consecutive_errors);
+                tracing::error!(
+                    "Error receiving task ({} in a row), retrying in {:?}: {:?}",
+                    consecutive_errors,
+                    delay,
+                    err
+                );
+                sleep(delay).await;
             }
         }
 
         update_last_poll_time().await;
     }
 
+    let mut interval = tokio::time::interval(Duration::from_millis(500));
+    loop {
+        interval.tick().await;
+        let num_workers = NUM_WORKERS.load(Ordering::Relaxed);
+        if num_workers > 0 {
+            tracing::info!(
+                "{} active workers, waiting to shut down worker.",
+                num_workers
+            );
+        } else {
+            tracing::info!("No active workers, shutting down worker.");
+            break;
+        }
+    }
+
     Ok(())
 }
 
 #[cfg(test)]
 mod tests {
-    use std::collections::HashMap;
+    use std::{collections::HashMap, io::Write, sync::Arc};
 
     use bytes::Bytes;
     use ed25519_compact::Signature;
-
-    use super::{bytes_to_string, generate_msg_headers, sign_msg, CaseSensitiveHeaderMap};
-    use crate::core::{
-        cryptography::{AsymmetricKey, Encryption},
-        types::{BaseId, EndpointHeaders, EndpointSecret, EndpointSecretInternal, MessageId},
+    use omniqueue::backends::InMemoryBackend;
+    use proptest::{prop_assert_eq, proptest};
+
+    use super::{
+        bytes_to_string, generate_msg_headers, is_valid_redirect_target, process_endpoint_failure,
+        read_response_body, record_task_queue_age, redirect_target, requeue_failed_dispatches,
+        resolves, run_pre_dispatch_hooks, scrub_request_headers, sign_msg, sign_with_keys,
+        task_due_at, time_since_first_failure, transform_payload, CaseSensitiveHeaderMap,
+        DispatchTransformer, HookResult, NoopPreDispatchHook, PreDispatchHook, RESPONSE_MAX_SIZE,
+    };
+    use crate::{
+        core::{
+            cache::memory,
+            cryptography::{AsymmetricKey, Encryption, RsaKey},
+            message_app::CreateMessageEndpoint,
+            types::{
+                ApplicationId, BaseId, EndpointHeaders, EndpointId, EndpointSecret,
+                EndpointSecretInternal, EventTypeName, MessageAttemptTriggerType,
+                MessageCorrelationId, MessageEndpointId, MessageId, MessageSignedMetadata,
+                MessageStatus, OrganizationId, PayloadVersion,
+            },
+        },
+        db::models::{message, messagedestination},
+        error::{Error, Result},
+        queue::{MessageTask, QueueTask, TaskQueueConsumer, TaskQueueProducer},
     };
 
     // [`generate_msg_headers`] tests
     const TIMESTAMP: i64 = 1;
+    const ATTEMPT_COUNT: u16 = 0;
+    const MSG_TIMESTAMP: i64 = 0;
     const WHITELABEL_HEADERS: bool = false;
     const BODY: &str = "{\"test\": \"body\"}";
     const ENDPOINT_SIGNING_KEYS: &[&EndpointSecretInternal] = &[];
@@ -1029,7 +2672,7 @@ mod tests {
 
     /// Utility function that returns the default set of headers before configurable header are
     /// accounted for
-    fn mock_headers() -> (CaseSensitiveHeaderMap, MessageId) {
+    async fn mock_headers() -> (CaseSensitiveHeaderMap, MessageId) {
         let id = MessageId::new(None, None);
 
         let signatures = sign_msg(
@@ -1037,16 +2680,24 @@ mod tests {
             TIMESTAMP,
             BODY,
             &id,
+            None,
+            None,
             ENDPOINT_SIGNING_KEYS,
-        );
+        )
+        .await;
 
         (
             generate_msg_headers(
                 TIMESTAMP,
                 &id,
                 signatures,
+                ATTEMPT_COUNT,
+                MSG_TIMESTAMP,
                 WHITELABEL_HEADERS,
                 None,
+                None,
+                None,
+                None,
                 ENDPOINT_URL,
             )
             .unwrap(),
@@ -1054,14 +2705,14 @@ mod tests {
         )
     }
 
-    #[test]
-    fn test_generate_msg_headers() {
+    #[tokio::test]
+    async fn test_generate_msg_headers() {
         // The headers to be given to [`generate_msg_headers`]
         let mut headers = HashMap::new();
         headers.insert("test_key".to_owned(), "value".to_owned());
 
         // The invalid key should be skipped over so it is not included in the expected
-        let (mut expected, id) = mock_headers();
+        let (mut expected, id) = mock_headers().await;
         let _ = expected.insert("test_key".to_owned(), "value".parse().unwrap());
 
         let signatures = sign_msg(
@@ -1069,14 +2720,22 @@ mod tests {
             TIMESTAMP,
             BODY,
             &id,
+            None,
+            None,
             ENDPOINT_SIGNING_KEYS,
-        );
+        )
+        .await;
 
         let actual = generate_msg_headers(
             TIMESTAMP,
             &id,
             signatures,
+            ATTEMPT_COUNT,
+            MSG_TIMESTAMP,
             WHITELABEL_HEADERS,
+            None,
+            None,
+            None,
             Some(&EndpointHeaders(headers)),
             ENDPOINT_URL,
         )
@@ -1085,17 +2744,102 @@ mod tests {
         assert_eq!(expected, actual);
     }
 
+    fn generate_msg_headers_with(configured: &[(&str, &str)]) -> CaseSensitiveHeaderMap {
+        let configured = EndpointHeaders(
+            configured
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+        );
+        generate_msg_headers(
+            TIMESTAMP,
+            &MessageId("msg_p5jXN8AQM9LWM0D4loKWxJek".to_owned()),
+            "v1,signature".to_owned(),
+            ATTEMPT_COUNT,
+            MSG_TIMESTAMP,
+            WHITELABEL_HEADERS,
+            None,
+            None,
+            None,
+            Some(&configured),
+            ENDPOINT_URL,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_generate_msg_headers_deterministic() {
+        let configured = [
+            ("X-Custom", "upper"),
+            ("x-custom", "lower"),
+            ("x-CUSTOM", "mixed"),
+            ("User-Agent", "custom agent"),
+            ("x-other", "other"),
+        ];
+
+        // Each `HashMap` iterates in its own order, so this covers several insertion orders
+        let first = generate_msg_headers_with(&configured);
+        for _ in 0..20 {
+            assert_eq!(generate_msg_headers_with(&configured), first);
+        }
+
+        // Only one of the headers differing in case is kept: the last one once sorted
+        // case-insensitively, then by their exact names
+        let custom: Vec<_> = first
+            .keys()
+            .filter(|k| k.eq_ignore_ascii_case("x-custom"))
+            .map(String::as_str)
+            .collect();
+        assert_eq!(custom, ["x-custom"]);
+        assert_eq!(first["x-custom"], "lower");
+
+        // Configured headers replace the default ones whatever their case
+        assert!(!first.contains_key("user-agent"));
+        assert_eq!(first["User-Agent"], "custom agent");
+        assert_eq!(first["x-other"], "other");
+    }
+
+    proptest! {
+        #[test]
+        fn prop_generate_msg_headers_case_insensitive_winner(
+            names in proptest::collection::hash_set("x-[a-cA-C]{1,3}", 1..10)
+        ) {
+            let configured: Vec<(&str, &str)> =
+                names.iter().map(|name| (name.as_str(), name.as_str())).collect();
+            let headers = generate_msg_headers_with(&configured);
+
+            for name in &names {
+                let lower = name.to_ascii_lowercase();
+                // The winner is the greatest of the names sharing its lowercase form
+                let winner = names
+                    .iter()
+                    .filter(|other| other.to_ascii_lowercase() == lower)
+                    .max_by_key(|other| (other.to_ascii_lowercase(), other.as_str()))
+                    .unwrap();
+                let kept: Vec<_> = headers
+                    .iter()
+                    .filter(|(k, _)| k.eq_ignore_ascii_case(name))
+                    .collect();
+                prop_assert_eq!(kept.len(), 1);
+                prop_assert_eq!(kept[0].0, winner);
+                prop_assert_eq!(kept[0].1, winner.as_str());
+            }
+        }
+    }
+
+    /// The key the Svix documentation's examples are signed with. It's shorter than new keys may be,
+    /// so it's loaded the way keys stored before the minimum size was raised are.
+    fn legacy_test_key() -> EndpointSecretInternal {
+        serde_json::from_value(serde_json::json!("whsec_MfKQ9r8GKYqrTwjUPD8ILPZIo2LaLaSw")).unwrap()
+    }
+
     // Tests endpoint signing keys -- expected values are fetched from the Svix documentation for a
     // direct comparison to the current implementation.
-    #[test]
-    fn test_generate_msg_headers_with_signing_key() {
+    #[tokio::test]
+    async fn test_generate_msg_headers_with_signing_key() {
         let test_timestamp = 1614265330;
         let test_body = "{\"test\": 2432232314}";
-        let test_key = EndpointSecretInternal::from_endpoint_secret(
-            EndpointSecret::Symmetric(base64::decode("MfKQ9r8GKYqrTwjUPD8ILPZIo2LaLaSw").unwrap()),
-            &Encryption::new_noop(),
-        )
-        .unwrap();
+        let test_key = legacy_test_key();
         let test_message_id = MessageId("msg_p5jXN8AQM9LWM0D4loKWxJek".to_owned());
 
         let expected_signature_str = "v1,g0hM9SsE+OTPJTGt/tmIKtSyZlE3uFJELVlNIOLJ1OE=";
@@ -1105,15 +2849,23 @@ mod tests {
             test_timestamp,
             test_body,
             &test_message_id,
+            None,
+            None,
             &[&test_key],
-        );
+        )
+        .await;
 
         let actual = generate_msg_headers(
             test_timestamp,
             &test_message_id,
             signatures,
+            ATTEMPT_COUNT,
+            MSG_TIMESTAMP,
             WHITELABEL_HEADERS,
             None,
+            None,
+            None,
+            None,
             ENDPOINT_URL,
         )
         .unwrap();
@@ -1124,9 +2876,298 @@ mod tests {
         );
     }
 
-    // Tests asymmetric signing keys
+    // Signed metadata is signed between the timestamp and the body, in its canonical form
+    #[tokio::test]
+    async fn test_sign_msg_with_signed_metadata() {
+        let test_timestamp = 1614265330;
+        let test_body = "{\"test\": 2432232314}";
+        let test_key = legacy_test_key();
+        let test_message_id = MessageId("msg_p5jXN8AQM9LWM0D4loKWxJek".to_owned());
+        let metadata = MessageSignedMetadata(HashMap::from([
+            ("tenant_id".to_owned(), "tenant_1".to_owned()),
+            ("order_id".to_owned(), "ord_1".to_owned()),
+        ]));
+        assert_eq!(
+            metadata.to_canonical_json(),
+            r#"{"order_id":"ord_1","tenant_id":"tenant_1"}"#
+        );
+
+        let encryption = Encryption::new_noop();
+        let keys = [&test_key];
+        let sign = |metadata| {
+            sign_msg(
+                &encryption,
+                test_timestamp,
+                test_body,
+                &test_message_id,
+                None,
+                metadata,
+                &keys,
+            )
+        };
+
+        // Signature of `msg_p5jXN8AQM9LWM0D4loKWxJek.1614265330.{"test": 2432232314}`
+        assert_eq!(
+            sign(None).await,
+            "v1,g0hM9SsE+OTPJTGt/tmIKtSyZlE3uFJELVlNIOLJ1OE="
+        );
+        // Signature of
+        // `msg_p5jXN8AQM9LWM0D4loKWxJek.1614265330.{"order_id":"ord_1","tenant_id":"tenant_1"}.{"test": 2432232314}`
+        assert_eq!(
+            sign(Some(&metadata)).await,
+            "v1,yYqeG7c2JRPuBJJvVVwERHPbkgwJ/PlT8saPJgNGAQE="
+        );
+    }
+
+    // A custom prefix is signed in place of the message ID
+    #[tokio::test]
+    async fn test_sign_msg_with_custom_payload_prefix() {
+        let test_timestamp = 1614265330;
+        let test_body = "{\"test\": 2432232314}";
+        let test_key = legacy_test_key();
+        let test_message_id = MessageId("msg_p5jXN8AQM9LWM0D4loKWxJek".to_owned());
+
+        let encryption = Encryption::new_noop();
+        let keys = [&test_key];
+        let sign = |msg_id, prefix| {
+            sign_msg(
+                &encryption,
+                test_timestamp,
+                test_body,
+                msg_id,
+                prefix,
+                None,
+                &keys,
+            )
+        };
+
+        let custom = sign(&test_message_id, Some("acme")).await;
+        assert_ne!(custom, sign(&test_message_id, None).await);
+        // Signature of `acme.1614265330.{"test": 2432232314}`
+        let acme_id = MessageId("acme".to_owned());
+        assert_eq!(custom, sign(&acme_id, None).await);
+    }
+
     #[test]
-    fn test_asymmetric_key_signing() {
+    fn test_generate_msg_headers_delivery_attempt() {
+        let id = MessageId::new(None, None);
+        let msg_timestamp = 1614265300;
+
+        for attempt_count in [0, 1, 5] {
+            for whitelabel_headers in [false, true] {
+                let headers = generate_msg_headers(
+                    TIMESTAMP,
+                    &id,
+                    String::new(),
+                    attempt_count,
+                    msg_timestamp,
+                    whitelabel_headers,
+                    None,
+                    None,
+                    None,
+                    None,
+                    ENDPOINT_URL,
+                )
+                .unwrap();
+
+                let prefix = if whitelabel_headers {
+                    "webhook"
+                } else {
+                    "svix"
+                };
+                assert_eq!(
+                    headers[&format!("{prefix}-delivery-attempt")],
+                    attempt_count.to_string()
+                );
+                assert_eq!(
+                    headers[&format!("{prefix}-message-timestamp")],
+                    msg_timestamp.to_string()
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_generate_msg_headers_environment() {
+        let id = MessageId::new(None, None);
+
+        for whitelabel_headers in [false, true] {
+            let header = if whitelabel_headers {
+                "webhook-environment"
+            } else {
+                "svix-environment"
+            };
+
+            let headers = generate_msg_headers(
+                TIMESTAMP,
+                &id,
+                String::new(),
+                ATTEMPT_COUNT,
+                MSG_TIMESTAMP,
+                whitelabel_headers,
+                Some("staging"),
+                None,
+                None,
+                None,
+                ENDPOINT_URL,
+            )
+            .unwrap();
+            assert_eq!(headers[header], "staging");
+
+            let headers = generate_msg_headers(
+                TIMESTAMP,
+                &id,
+                String::new(),
+                ATTEMPT_COUNT,
+                MSG_TIMESTAMP,
+                whitelabel_headers,
+                None,
+                None,
+                None,
+                None,
+                ENDPOINT_URL,
+            )
+            .unwrap();
+            assert!(headers.get(header).is_none());
+        }
+    }
+
+    #[test]
+    fn test_generate_msg_headers_payload_version() {
+        let id = MessageId::new(None, None);
+        let version = PayloadVersion("1.2.3-beta.1".parse().unwrap());
+
+        for whitelabel_headers in [false, true] {
+            let header = if whitelabel_headers {
+                "webhook-payload-version"
+            } else {
+                "svix-payload-version"
+            };
+
+            let headers = generate_msg_headers(
+                TIMESTAMP,
+                &id,
+                String::new(),
+                ATTEMPT_COUNT,
+                MSG_TIMESTAMP,
+                whitelabel_headers,
+                None,
+                Some(&version),
+                None,
+                None,
+                ENDPOINT_URL,
+            )
+            .unwrap();
+            assert_eq!(headers[header], "1.2.3-beta.1");
+
+            let headers = generate_msg_headers(
+                TIMESTAMP,
+                &id,
+                String::new(),
+                ATTEMPT_COUNT,
+                MSG_TIMESTAMP,
+                whitelabel_headers,
+                None,
+                None,
+                None,
+                None,
+                ENDPOINT_URL,
+            )
+            .unwrap();
+            assert!(headers.get(header).is_none());
+        }
+    }
+
+    #[test]
+    fn test_generate_msg_headers_correlation_id() {
+        let id = MessageId::new(None, None);
+        let correlation_id = MessageCorrelationId::new(None, None);
+
+        for whitelabel_headers in [false, true] {
+            let header = if whitelabel_headers {
+                "webhook-correlation-id"
+            } else {
+                "svix-correlation-id"
+            };
+
+            let headers = generate_msg_headers(
+                TIMESTAMP,
+                &id,
+                String::new(),
+                ATTEMPT_COUNT,
+                MSG_TIMESTAMP,
+                whitelabel_headers,
+                None,
+                None,
+                Some(&correlation_id),
+                None,
+                ENDPOINT_URL,
+            )
+            .unwrap();
+            assert_eq!(headers[header], correlation_id.0);
+
+            // Messages created before correlation IDs were introduced don't have one
+            let headers = generate_msg_headers(
+                TIMESTAMP,
+                &id,
+                String::new(),
+                ATTEMPT_COUNT,
+                MSG_TIMESTAMP,
+                whitelabel_headers,
+                None,
+                None,
+                None,
+                None,
+                ENDPOINT_URL,
+            )
+            .unwrap();
+            assert!(headers.get(header).is_none());
+        }
+    }
+
+    #[test]
+    fn test_time_since_first_failure() {
+        let now = chrono::Utc::now();
+        let disable_in = std::time::Duration::from_secs(60);
+
+        let first_failure_at = now - chrono::Duration::seconds(90);
+        let elapsed = time_since_first_failure(now, first_failure_at);
+        assert_eq!(elapsed, std::time::Duration::from_secs(90));
+        assert!(elapsed > disable_in);
+
+        // If the clock went backwards since the first failure, the grace period hasn't started yet
+        // rather than the endpoint being disabled
+        let first_failure_at = now + chrono::Duration::seconds(90);
+        let elapsed = time_since_first_failure(now, first_failure_at);
+        assert_eq!(elapsed, std::time::Duration::ZERO);
+        assert!(elapsed <= disable_in);
+    }
+
+    #[tokio::test]
+    async fn test_process_endpoint_failure_concurrently() {
+        let cache = memory::new();
+        let org_id = OrganizationId::new(None, None);
+        let app_id = ApplicationId::new(None, None);
+        let endp_id = EndpointId::new(None, None);
+        let disable_in = std::time::Duration::from_secs(60);
+
+        // Failures happening at the same time are all counted
+        let statuses = futures::future::join_all(
+            (0..20)
+                .map(|_| process_endpoint_failure(&cache, &app_id, &org_id, &endp_id, disable_in)),
+        )
+        .await;
+        let mut counts: Vec<_> = statuses
+            .into_iter()
+            .map(|status| status.unwrap().failure_count)
+            .collect();
+        counts.sort_unstable();
+        assert_eq!(counts, (1..=20).collect::<Vec<_>>());
+    }
+
+    // Tests asymmetric signing keys
+    #[tokio::test]
+    async fn test_asymmetric_key_signing() {
         let timestamp = 1614265330;
         let body = "{\"test\": 2432232314}";
         let asym_key = AsymmetricKey::from_base64("6Xb/dCcHpPea21PS1N9VY/NZW723CEc77N4rJCubMbfVKIDij2HKpMKkioLlX0dRqSKJp4AJ6p9lMicMFs6Kvg==").unwrap();
@@ -1142,8 +3183,11 @@ mod tests {
             timestamp,
             body,
             &msg_id,
+            None,
+            None,
             &[&test_key],
-        );
+        )
+        .await;
 
         let to_sign = format!("{msg_id}.{timestamp}.{body}");
         assert!(signatures.starts_with("v1a,"));
@@ -1157,9 +3201,452 @@ mod tests {
         assert_eq!(signatures, "v1a,hnO3f9T8Ytu9HwrXslvumlUpqtNVqkhqw/enGzPCXe5BdqzCInXqYXFymVJaA7AZdpXwVLPo3mNl8EM+m7TBAg==");
     }
 
+    // Tests RSA signing keys
+    #[tokio::test]
+    async fn test_rsa_key_signing() {
+        let timestamp = 1614265330;
+        let body = "{\"test\": 2432232314}";
+        let rsa_key = RsaKey::generate().unwrap();
+        let test_key = EndpointSecretInternal::from_endpoint_secret(
+            EndpointSecret::Rsa(rsa_key.clone()),
+            &Encryption::new_noop(),
+        )
+        .unwrap();
+        let msg_id = MessageId("msg_p5jXN8AQM9LWM0D4loKWxJek".to_owned());
+
+        let signatures = sign_msg(
+            &Encryption::new_noop(),
+            timestamp,
+            body,
+            &msg_id,
+            None,
+            None,
+            &[&test_key],
+        )
+        .await;
+
+        let to_sign = format!("{msg_id}.{timestamp}.{body}");
+        assert!(signatures.starts_with("v1r,"));
+        let sig = base64::decode(&signatures["v1r,".len()..]).unwrap();
+        assert_eq!(sig.len(), 256);
+        assert!(rsa_key.verify(to_sign.as_bytes(), &sig).unwrap());
+        assert!(!rsa_key.verify(b"something else", &sig).unwrap());
+    }
+
+    fn rsa_signing_keys(count: usize) -> Vec<EndpointSecretInternal> {
+        (0..count)
+            .map(|_| {
+                EndpointSecretInternal::from_endpoint_secret(
+                    EndpointSecret::Rsa(RsaKey::generate().unwrap()),
+                    &Encryption::new_noop(),
+                )
+                .unwrap()
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_sign_with_keys_parallel() {
+        let mut keys = rsa_signing_keys(3);
+        keys.push(EndpointSecretInternal::generate_symmetric(&Encryption::new_noop()).unwrap());
+        keys.push(EndpointSecretInternal::generate_asymmetric(&Encryption::new_noop()).unwrap());
+        let keys: Vec<_> = keys.iter().collect();
+
+        // All of these signatures are deterministic, so signing in parallel gives the same result,
+        // in the same order
+        let encryption = Encryption::new_noop();
+        let sequential = sign_with_keys(&encryption, b"to sign".to_vec(), &keys, false).await;
+        let parallel = sign_with_keys(&encryption, b"to sign".to_vec(), &keys, true).await;
+        assert_eq!(sequential, parallel);
+        let versions: Vec<_> = parallel
+            .split(' ')
+            .map(|sig| sig.split_once(',').unwrap().0)
+            .collect();
+        assert_eq!(versions, ["v1r", "v1r", "v1r", "v1", "v1a"]);
+    }
+
     #[test]
     fn test_bytes_to_string() {
         let b = Bytes::from_static(b"Hello, world.");
         assert_eq!(bytes_to_string(b), "Hello, world.");
     }
+
+    fn brotli_response(body: &[u8]) -> hyper::Response<hyper::Body> {
+        let mut compressed = Vec::new();
+        {
+            let mut writer = brotli::CompressorWriter::new(&mut compressed, 4096, 11, 22);
+            writer.write_all(body).unwrap();
+        }
+        hyper::Response::builder()
+            .header("content-encoding", "br")
+            .body(compressed.into())
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_resolves() {
+        // IP addresses don't need looking up
+        assert!(resolves("http://127.0.0.1:8071/webhook").await);
+        assert!(resolves("https://[::1]/").await);
+        // `.invalid` names never resolve
+        assert!(!resolves("https://svix-test.invalid/").await);
+        // Invalid URLs are left for the request to reject
+        assert!(resolves("not a url").await);
+    }
+
+    #[tokio::test]
+    async fn test_read_response_body_brotli() {
+        let res = brotli_response(b"{\"ok\": true}");
+        assert_eq!(read_response_body(res).await, "{\"ok\": true}");
+
+        // The encoding is case-insensitive
+        let mut res = brotli_response(b"Hello, world.");
+        res.headers_mut()
+            .insert("content-encoding", "BR".parse().unwrap());
+        assert_eq!(read_response_body(res).await, "Hello, world.");
+
+        // Uncompressed bodies are left alone
+        let res = hyper::Response::new("Hello, world.".into());
+        assert_eq!(read_response_body(res).await, "Hello, world.");
+    }
+
+    #[tokio::test]
+    async fn test_read_response_body_brotli_max_size() {
+        // Compresses down to well below the limit, but is truncated after being decompressed
+        let body = "a".repeat(RESPONSE_MAX_SIZE * 10);
+        let res = brotli_response(body.as_bytes());
+        assert_eq!(read_response_body(res).await, body[..RESPONSE_MAX_SIZE]);
+    }
+
+    #[tokio::test]
+    async fn test_read_response_body_invalid_brotli() {
+        // Bodies which claim to be brotli encoded but aren't are stored as they were received
+        let res = hyper::Response::builder()
+            .header("content-encoding", "br")
+            .body(vec![0xff, 0xfe, 0xfd].into())
+            .unwrap();
+        assert_eq!(
+            read_response_body(res).await,
+            base64::encode([0xff, 0xfe, 0xfd])
+        );
+    }
+
+    #[tokio::test]
+    async fn test_scrub_request_headers() {
+        let (mut headers, id) = mock_headers().await;
+        headers.insert("Authorization".to_owned(), "Bearer secret".parse().unwrap());
+        headers.insert("cookie".to_owned(), "session=secret".parse().unwrap());
+        headers.insert("X-API-KEY".to_owned(), "secret".parse().unwrap());
+        headers.insert(
+            "x-not-utf8".to_owned(),
+            http::HeaderValue::from_bytes(b"\xff").unwrap(),
+        );
+        headers.insert("x-custom".to_owned(), "value".parse().unwrap());
+
+        let scrubbed = scrub_request_headers(&headers);
+        let scrubbed = scrubbed.as_object().unwrap();
+
+        assert_eq!(scrubbed.len(), 8);
+        assert_eq!(scrubbed["svix-id"], id.0.as_str());
+        assert_eq!(scrubbed["x-custom"], "value");
+        assert!(scrubbed.contains_key("svix-signature"));
+        for k in ["Authorization", "cookie", "X-API-KEY", "x-not-utf8"] {
+            assert!(!scrubbed.contains_key(k));
+        }
+    }
+
+    #[test]
+    fn test_redirect_target() {
+        let response = |status: u16, location: Option<&str>| {
+            let mut builder = hyper::Response::builder().status(status);
+            if let Some(location) = location {
+                builder = builder.header(http::header::LOCATION, location);
+            }
+            builder.body(hyper::Body::empty()).unwrap()
+        };
+
+        let url = "https://example.com/webhooks/in";
+        assert_eq!(
+            redirect_target(&response(301, Some("https://example.org/new")), url)
+                .unwrap()
+                .as_str(),
+            "https://example.org/new"
+        );
+        // Relative targets are resolved against the endpoint's URL
+        assert_eq!(
+            redirect_target(&response(302, Some("/webhooks/new")), url)
+                .unwrap()
+                .as_str(),
+            "https://example.com/webhooks/new"
+        );
+        assert!(redirect_target(&response(301, None), url).is_none());
+        assert!(redirect_target(&response(200, Some("https://example.org/new")), url).is_none());
+
+        let https = url::Url::parse("https://example.org/").unwrap();
+        let http = url::Url::parse("http://example.org/").unwrap();
+        let ftp = url::Url::parse("ftp://example.org/").unwrap();
+        assert!(is_valid_redirect_target(&https, true));
+        assert!(is_valid_redirect_target(&http, false));
+        assert!(!is_valid_redirect_target(&http, true));
+        assert!(!is_valid_redirect_target(&ftp, false));
+    }
+
+    #[test]
+    fn test_task_queue_age() {
+        let now = chrono::Utc::now();
+        let threshold = std::time::Duration::from_secs(60);
+
+        // A new message's task is due as soon as the message is created
+        let msg_id = MessageId::new(Some(now - chrono::Duration::minutes(5)), None);
+        let due_at = task_due_at(MessageAttemptTriggerType::Scheduled, 0, &msg_id, None).unwrap();
+        assert_eq!(
+            due_at.timestamp_millis(),
+            msg_id.timestamp().timestamp_millis()
+        );
+        assert!(record_task_queue_age(due_at, now, threshold));
+
+        let recent_msg_id = MessageId::new(Some(now - chrono::Duration::seconds(5)), None);
+        let due_at = task_due_at(
+            MessageAttemptTriggerType::Scheduled,
+            0,
+            &recent_msg_id,
+            None,
+        )
+        .unwrap();
+        assert!(!record_task_queue_age(due_at, now, threshold));
+
+        // Tasks picked up slightly before they're due aren't a problem either
+        assert!(!record_task_queue_age(
+            now + chrono::Duration::seconds(1),
+            now,
+            threshold
+        ));
+
+        // Retries are due at the destination's next attempt, not when the message was created
+        let destination = messagedestination::Model {
+            id: MessageEndpointId::new(None, None),
+            created_at: msg_id.timestamp().into(),
+            updated_at: now.into(),
+            msg_id: msg_id.clone(),
+            endp_id: EndpointId::new(None, None),
+            status: MessageStatus::Sending,
+            next_attempt: Some((now - chrono::Duration::seconds(10)).into()),
+            first_attempt_at: Some(msg_id.timestamp().into()),
+            last_attempt_at: Some((now - chrono::Duration::seconds(20)).into()),
+        };
+        let due_at = task_due_at(
+            MessageAttemptTriggerType::Scheduled,
+            1,
+            &msg_id,
+            Some(&destination),
+        )
+        .unwrap();
+        assert_eq!(due_at, destination.next_attempt.unwrap());
+        assert!(!record_task_queue_age(due_at, now, threshold));
+
+        // Manual resends are of older messages, so there's no telling how long they waited
+        assert_eq!(
+            task_due_at(MessageAttemptTriggerType::Manual, 0, &msg_id, None),
+            None
+        );
+        assert_eq!(
+            task_due_at(
+                MessageAttemptTriggerType::Manual,
+                0,
+                &msg_id,
+                Some(&destination)
+            ),
+            None
+        );
+    }
+
+    /// Adds a header, and appends its name to the payload
+    struct NamedHook(&'static str);
+
+    #[axum::async_trait]
+    impl PreDispatchHook for NamedHook {
+        async fn pre_dispatch(
+            &self,
+            _msg: &message::Model,
+            _endp: &CreateMessageEndpoint,
+            headers: &mut CaseSensitiveHeaderMap,
+            payload: &mut String,
+        ) -> HookResult {
+            headers.insert(format!("x-hook-{}", self.0), "1".parse().unwrap());
+            payload.push_str(self.0);
+            HookResult::Continue
+        }
+    }
+
+    struct FilterHook;
+
+    #[axum::async_trait]
+    impl PreDispatchHook for FilterHook {
+        async fn pre_dispatch(
+            &self,
+            _msg: &message::Model,
+            _endp: &CreateMessageEndpoint,
+            _headers: &mut CaseSensitiveHeaderMap,
+            _payload: &mut String,
+        ) -> HookResult {
+            HookResult::Filter
+        }
+    }
+
+    #[tokio::test]
+    async fn test_run_pre_dispatch_hooks() {
+        let now = chrono::Utc::now();
+        let msg = message::Model {
+            id: MessageId::new(None, None),
+            created_at: now.into(),
+            org_id: OrganizationId::new(None, None),
+            app_id: ApplicationId::new(None, None),
+            event_type: EventTypeName("user.signup".to_owned()),
+            uid: None,
+            legacy_payload: None,
+            channels: None,
+            tags: None,
+            expiration: now.into(),
+            chain_depth: 0,
+            deadline: None,
+            signed_metadata: None,
+            payload_version: None,
+            correlation_id: None,
+            endpoint_metadata_selector: None,
+        };
+        let endp: CreateMessageEndpoint = serde_json::from_value(serde_json::json!({
+            "id": EndpointId::new(None, None),
+            "url": ENDPOINT_URL,
+            "key": EndpointSecretInternal::generate_symmetric(&Encryption::new_noop()).unwrap(),
+            "http_method": "POST",
+            "disabled": false,
+            "deleted": false,
+        }))
+        .unwrap();
+
+        let run = |hooks: Vec<Arc<dyn PreDispatchHook>>| {
+            let (msg, endp) = (&msg, &endp);
+            async move {
+                let mut headers = CaseSensitiveHeaderMap::new();
+                let mut payload = BODY.to_owned();
+                let res =
+                    run_pre_dispatch_hooks(&hooks, msg, endp, &mut headers, &mut payload).await;
+                let mut names: Vec<_> = headers.into_keys().collect();
+                names.sort();
+                (res, names, payload)
+            }
+        };
+
+        // The no-op hook leaves the webhook as is, as does having no hooks at all
+        let noop: Arc<dyn PreDispatchHook> = Arc::new(NoopPreDispatchHook);
+        for hooks in [vec![], vec![noop]] {
+            assert_eq!(
+                run(hooks).await,
+                (HookResult::Continue, vec![], BODY.to_owned())
+            );
+        }
+
+        // Hooks run in order
+        let (res, names, payload) =
+            run(vec![Arc::new(NamedHook("a")), Arc::new(NamedHook("b"))]).await;
+        assert_eq!(res, HookResult::Continue);
+        assert_eq!(names, ["x-hook-a", "x-hook-b"]);
+        assert_eq!(payload, format!("{BODY}ab"));
+
+        // Hooks after the one filtering the webhook out don't run
+        let (res, names, payload) = run(vec![
+            Arc::new(NamedHook("a")),
+            Arc::new(FilterHook),
+            Arc::new(NamedHook("b")),
+        ])
+        .await;
+        assert_eq!(res, HookResult::Filter);
+        assert_eq!(names, ["x-hook-a"]);
+        assert_eq!(payload, format!("{BODY}a"));
+    }
+
+    /// Wraps the payload in the transformation's code
+    struct WrappingTransformer;
+
+    #[axum::async_trait]
+    impl DispatchTransformer for WrappingTransformer {
+        async fn transform(&self, code: &str, payload: String) -> Result<String> {
+            Ok(format!("{code}({payload})"))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_transform_payload() {
+        let mut endp: CreateMessageEndpoint = serde_json::from_value(serde_json::json!({
+            "id": EndpointId::new(None, None),
+            "url": ENDPOINT_URL,
+            "key": EndpointSecretInternal::generate_symmetric(&Encryption::new_noop()).unwrap(),
+            "http_method": "POST",
+            "disabled": false,
+            "deleted": false,
+        }))
+        .unwrap();
+
+        // Endpoints without a transformation get the payload as is, transformer or not
+        for transformer in [None, Some(&WrappingTransformer as &dyn DispatchTransformer)] {
+            assert_eq!(
+                transform_payload(transformer, &endp, BODY.to_owned())
+                    .await
+                    .unwrap(),
+                BODY
+            );
+        }
+
+        endp.dispatch_transformation = Some("wrap".to_owned());
+        assert_eq!(
+            transform_payload(Some(&WrappingTransformer), &endp, BODY.to_owned())
+                .await
+                .unwrap(),
+            format!("wrap({BODY})")
+        );
+
+        // Rather than sending the payload untransformed
+        let err = transform_payload(None, &endp, BODY.to_owned())
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("no transformer is available"));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_requeue_failed_dispatches() {
+        let (producer, consumer) = InMemoryBackend::builder().build_pair().await.unwrap();
+        let (queue_tx, mut queue_rx) = (
+            TaskQueueProducer::new(producer),
+            TaskQueueConsumer::new(consumer),
+        );
+
+        let msg_id = MessageId::new(None, None);
+        let app_id = ApplicationId::new(None, None);
+        let tasks: Vec<_> = (0..3)
+            .map(|_| MessageTask {
+                msg_id: msg_id.clone(),
+                app_id: app_id.clone(),
+                endpoint_id: EndpointId::new(None, None),
+                trigger_type: MessageAttemptTriggerType::Scheduled,
+                attempt_count: 0,
+            })
+            .collect();
+
+        let results = vec![Ok(()), Err(Error::generic("failed")), Ok(())];
+        requeue_failed_dispatches(&queue_tx, None, tasks.iter().cloned().zip(results))
+            .await
+            .unwrap();
+
+        // Only the failed endpoint's task is sent again, as its first retry
+        let delivery = queue_rx.receive_one().await.unwrap().unwrap();
+        assert_eq!(
+            *delivery.task,
+            QueueTask::MessageV1(MessageTask {
+                attempt_count: 1,
+                ..tasks[1].clone()
+            })
+        );
+        delivery.ack().await.unwrap();
+        assert!(queue_rx.receive_one().await.unwrap().is_none());
+    }
 }
@@ -1,19 +1,29 @@
 // SPDX-FileCopyrightText: © 2022 Svix Authors
 // SPDX-License-Identifier: MIT
+use axum::http::HeaderMap;
 use reqwest::StatusCode;
 use serde::de::IgnoredAny;
+use svix::webhooks::Webhook;
 use svix_server::{
     cfg::CacheType,
     core::{
         security::generate_org_token,
-        types::{ApplicationUid, BaseId, OrganizationId},
+        types::{ApplicationUid, BaseId, EndpointSecret, OrganizationId},
+    },
+    v1::endpoints::{
+        application::{
+            ApplicationIn, ApplicationOut, ApplicationWebhookSecretIn, ApplicationWebhookSecretOut,
+        },
+        endpoint::{EndpointIn, EndpointSecretOut},
     },
-    v1::endpoints::application::{ApplicationIn, ApplicationOut},
 };
 
 use crate::utils::{
-    common_calls::{application_in, common_test_list, metadata},
-    get_default_test_config, start_svix_server,
+    common_calls::{
+        application_in, common_test_list, create_test_app, create_test_endpoint,
+        create_test_message, endpoint_in, metadata, post_endpoint,
+    },
+    get_default_test_config, start_svix_server, TestReceiver,
 };
 
 // NOTE: PATCHing must be tested exhaustively as if any of the boilerplate is missed then the
@@ -685,3 +695,131 @@ async fn test_idempotency() {
         _ => assert_eq!(app1, app2),
     };
 }
+
+async fn recv_signed(receiver: &mut TestReceiver) -> (HeaderMap, String) {
+    let headers = receiver.header_recv.recv().await.unwrap();
+    let body = receiver.data_recv.recv().await.unwrap().to_string();
+    (headers, body)
+}
+
+fn is_signed_with((headers, body): &(HeaderMap, String), secret: &EndpointSecret) -> bool {
+    let EndpointSecret::Symmetric(key) = secret else {
+        panic!("Shouldn't get here");
+    };
+    Webhook::new(&base64::encode(key))
+        .unwrap()
+        .verify(body.as_bytes(), headers)
+        .is_ok()
+}
+
+#[tokio::test]
+async fn test_application_secret() {
+    let (client, _jh) = start_svix_server().await;
+
+    let app_id = create_test_app(&client, "appSecretApp").await.unwrap().id;
+
+    let _: IgnoredAny = client
+        .get(
+            &format!("api/v1/app/{app_id}/secret/"),
+            StatusCode::NOT_FOUND,
+        )
+        .await
+        .unwrap();
+
+    // Created before the application has a secret, so it gets one of its own
+    let mut own_receiver = TestReceiver::start(StatusCode::OK);
+    let own_endp = create_test_endpoint(&client, &app_id, &own_receiver.endpoint)
+        .await
+        .unwrap();
+    let own_secret: EndpointSecretOut = client
+        .get(
+            &format!("api/v1/app/{app_id}/endpoint/{}/secret/", own_endp.id),
+            StatusCode::OK,
+        )
+        .await
+        .unwrap();
+
+    let app_secret: ApplicationWebhookSecretOut = client
+        .put(
+            &format!("api/v1/app/{app_id}/secret/"),
+            ApplicationWebhookSecretIn::default(),
+            StatusCode::OK,
+        )
+        .await
+        .unwrap();
+    let fetched: ApplicationWebhookSecretOut = client
+        .get(&format!("api/v1/app/{app_id}/secret/"), StatusCode::OK)
+        .await
+        .unwrap();
+    assert_eq!(fetched, app_secret);
+
+    // Created without a key after the application has a secret, so it falls back to it
+    let mut app_receiver = TestReceiver::start(StatusCode::OK);
+    let app_endp = create_test_endpoint(&client, &app_id, &app_receiver.endpoint)
+        .await
+        .unwrap();
+    let app_endp_secret: EndpointSecretOut = client
+        .get(
+            &format!("api/v1/app/{app_id}/endpoint/{}/secret/", app_endp.id),
+            StatusCode::OK,
+        )
+        .await
+        .unwrap();
+    assert_eq!(app_endp_secret.key, app_secret.key);
+
+    // An explicitly given key takes precedence over the application's secret
+    let explicit_key = EndpointSecret::Symmetric(
+        base64::decode("MfKQ9r8GKYqrTwjUPD8ILPZIo2LaLaSwMfKQ9r8GKYqr").unwrap(),
+    );
+    let mut explicit_receiver = TestReceiver::start(StatusCode::OK);
+    post_endpoint(
+        &client,
+        &app_id,
+        EndpointIn {
+            key: Some(explicit_key.clone()),
+            ..endpoint_in(&explicit_receiver.endpoint)
+        },
+    )
+    .await
+    .unwrap();
+
+    create_test_message(&client, &app_id, serde_json::json!({"test": "data1"}))
+        .await
+        .unwrap();
+
+    assert!(is_signed_with(
+        &recv_signed(&mut own_receiver).await,
+        &own_secret.key
+    ));
+    let app_signed = recv_signed(&mut app_receiver).await;
+    assert!(is_signed_with(&app_signed, &app_secret.key));
+    assert!(!is_signed_with(&app_signed, &own_secret.key));
+    assert!(is_signed_with(
+        &recv_signed(&mut explicit_receiver).await,
+        &explicit_key
+    ));
+
+    // Rotating the application's secret affects all endpoints without a secret of their own
+    let new_app_secret: ApplicationWebhookSecretOut = client
+        .put(
+            &format!("api/v1/app/{app_id}/secret/"),
+            ApplicationWebhookSecretIn::default(),
+            StatusCode::OK,
+        )
+        .await
+        .unwrap();
+    assert_ne!(new_app_secret.key, app_secret.key);
+
+    create_test_message(&client, &app_id, serde_json::json!({"test": "data2"}))
+        .await
+        .unwrap();
+
+    assert!(is_signed_with(
+        &recv_signed(&mut app_receiver).await,
+        &new_app_secret.key
+    ));
+    let own_signed = recv_signed(&mut own_receiver).await;
+    assert!(is_signed_with(&own_signed, &own_secret.key));
+    assert!(!is_signed_with(&own_signed, &new_app_secret.key));
+    recv_signed(&mut explicit_receiver).await;
+}
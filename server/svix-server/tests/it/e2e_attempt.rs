@@ -1,14 +1,19 @@
 // SPDX-FileCopyrightText: © 2022 Svix Authors
 // SPDX-License-Identifier: MIT
 
-use std::time::Duration;
+use std::{sync::Arc, time::Duration};
 
 use reqwest::StatusCode;
+use sea_orm::{sea_query::Expr, ColumnTrait, EntityTrait, PaginatorTrait, QueryFilter};
+use svix_ksuid::KsuidLike;
 use svix_server::{
-    core::types::{EndpointUid, MessageStatus},
+    core::types::{BaseId, EndpointUid, MessageAttemptTriggerType, MessageStatus, OrganizationId},
+    db::models::{message, messageattempt, messagedestination},
+    queue::{new_pair, MessageTask, QueueTask},
+    stuck_deliveries::requeue_stuck_deliveries,
     v1::{
         endpoints::{
-            attempt::{EndpointMessageOut, MessageAttemptOut},
+            attempt::{EndpointMessageOut, MessageAttemptOut, MessageAttemptSummaryOut},
             endpoint::{EndpointIn, EndpointOut},
         },
         utils::ListResponse,
@@ -21,7 +26,7 @@ use crate::utils::{
         endpoint_in, get_msg_attempt_list_and_assert_count,
     },
     get_default_test_config, run_with_retries, start_svix_server, start_svix_server_with_cfg,
-    TestReceiver,
+    start_svix_server_with_cfg_and_prefix, TestReceiver,
 };
 
 #[tokio::test]
@@ -435,6 +440,202 @@ async fn test_message_attempts() {
     }
 }
 
+#[tokio::test]
+async fn test_message_attempt_redirect() {
+    let mut cfg = get_default_test_config();
+    cfg.retry_schedule = vec![Duration::from_millis(1)];
+
+    let (client, _jh) = start_svix_server_with_cfg(&cfg).await;
+    let app_id = create_test_app(&client, "app").await.unwrap().id;
+
+    let mut target = TestReceiver::start(StatusCode::OK);
+    let redirect = TestReceiver::start_with_body(
+        StatusCode::MOVED_PERMANENTLY,
+        (
+            [(axum::http::header::LOCATION, target.endpoint.clone())],
+            (),
+        ),
+    );
+
+    let endp_id = create_test_endpoint(&client, &app_id, &redirect.endpoint)
+        .await
+        .unwrap()
+        .id;
+
+    let msg = create_test_message(&client, &app_id, serde_json::json!({"test": "data"}))
+        .await
+        .unwrap();
+
+    // Redirects aren't followed, but where they lead to is recorded
+    let list = get_msg_attempt_list_and_assert_count(&client, &app_id, &msg.id, 2)
+        .await
+        .unwrap();
+    for attempt in list.data {
+        assert_eq!(attempt.status, MessageStatus::Fail);
+        assert_eq!(attempt.response_status_code, 301);
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&attempt.response).unwrap(),
+            serde_json::json!({ "redirect_to": target.endpoint })
+        );
+    }
+    assert!(target.data_recv.try_recv().is_err());
+
+    let endp: EndpointOut = client
+        .get(
+            &format!("api/v1/app/{app_id}/endpoint/{endp_id}/"),
+            StatusCode::OK,
+        )
+        .await
+        .unwrap();
+    assert_eq!(endp.ep.url, redirect.endpoint);
+
+    redirect.jh.abort();
+    target.jh.abort();
+}
+
+#[tokio::test]
+async fn test_message_attempt_redirect_followed() {
+    let mut cfg = get_default_test_config();
+    cfg.allow_endpoint_redirect = true;
+
+    let (client, _jh) = start_svix_server_with_cfg(&cfg).await;
+
+    for (status_code, moves) in [
+        (StatusCode::FOUND, false),
+        (StatusCode::MOVED_PERMANENTLY, true),
+    ] {
+        let app_id = create_test_app(&client, "app").await.unwrap().id;
+
+        let mut target = TestReceiver::start(StatusCode::OK);
+        let redirect = TestReceiver::start_with_body(
+            status_code,
+            (
+                [(axum::http::header::LOCATION, target.endpoint.clone())],
+                (),
+            ),
+        );
+
+        let endp_id = create_test_endpoint(&client, &app_id, &redirect.endpoint)
+            .await
+            .unwrap()
+            .id;
+
+        let msg = create_test_message(&client, &app_id, serde_json::json!({"test": "data"}))
+            .await
+            .unwrap();
+
+        assert_eq!(
+            target.data_recv.recv().await.unwrap(),
+            serde_json::json!({"test": "data"})
+        );
+
+        let list = get_msg_attempt_list_and_assert_count(&client, &app_id, &msg.id, 1)
+            .await
+            .unwrap();
+        assert_eq!(list.data[0].status, MessageStatus::Success);
+        assert_eq!(list.data[0].url, target.endpoint);
+
+        // Only endpoints which moved permanently are updated
+        let expected_url = if moves {
+            &target.endpoint
+        } else {
+            &redirect.endpoint
+        };
+        run_with_retries(|| async {
+            let endp: EndpointOut = client
+                .get(
+                    &format!("api/v1/app/{app_id}/endpoint/{endp_id}/"),
+                    StatusCode::OK,
+                )
+                .await
+                .unwrap();
+            if endp.ep.url != *expected_url {
+                anyhow::bail!("Endpoint URL is {}, not {expected_url}", endp.ep.url);
+            }
+            Ok(())
+        })
+        .await
+        .unwrap();
+
+        redirect.jh.abort();
+        target.jh.abort();
+    }
+}
+
+#[tokio::test]
+async fn test_message_attempt_endpoint_ip() {
+    for store_endpoint_ip in [false, true] {
+        let mut cfg = get_default_test_config();
+        cfg.store_endpoint_ip = store_endpoint_ip;
+
+        let (client, _jh) = start_svix_server_with_cfg(&cfg).await;
+        let app_id = create_test_app(&client, "app").await.unwrap().id;
+
+        // The receiver listens on 127.0.0.1
+        let receiver = TestReceiver::start(StatusCode::OK);
+        create_test_endpoint(&client, &app_id, &receiver.endpoint)
+            .await
+            .unwrap();
+
+        let msg = create_test_message(&client, &app_id, serde_json::json!({"test": "data"}))
+            .await
+            .unwrap();
+
+        let list = get_msg_attempt_list_and_assert_count(&client, &app_id, &msg.id, 1)
+            .await
+            .unwrap();
+        let expected = store_endpoint_ip.then(|| "127.0.0.1".to_owned());
+        assert_eq!(list.data[0].endpoint_ip, expected);
+
+        receiver.jh.abort();
+    }
+}
+
+#[tokio::test]
+async fn test_message_destination_first_attempt_at() {
+    let mut cfg = get_default_test_config();
+    cfg.retry_schedule = vec![Duration::from_millis(1)];
+
+    let (client, _jh) = start_svix_server_with_cfg(&cfg).await;
+    let app_id = create_test_app(&client, "app").await.unwrap().id;
+
+    let receiver = TestReceiver::start(StatusCode::INTERNAL_SERVER_ERROR);
+    let endp_id = create_test_endpoint(&client, &app_id, &receiver.endpoint)
+        .await
+        .unwrap()
+        .id;
+
+    let msg = create_test_message(&client, &app_id, serde_json::json!({"test": "data"}))
+        .await
+        .unwrap();
+
+    let attempts = get_msg_attempt_list_and_assert_count(&client, &app_id, &msg.id, 2)
+        .await
+        .unwrap();
+    // Attempts are listed newest first
+    let first_attempt = attempts.data.last().unwrap();
+
+    let destinations: ListResponse<serde_json::Value> = client
+        .get(
+            &format!("api/v1/app/{app_id}/msg/{}/endpoint/", msg.id),
+            StatusCode::OK,
+        )
+        .await
+        .unwrap();
+    let [destination] = &destinations.data[..] else {
+        panic!("expected a single destination");
+    };
+    assert_eq!(destination["id"], endp_id.0);
+
+    // Retries don't move the time of the first attempt
+    let first_attempt_at: chrono::DateTime<chrono::Utc> =
+        serde_json::from_value(destination["firstAttemptAt"].clone()).unwrap();
+    assert_eq!(first_attempt_at, first_attempt.created_at);
+    assert!(first_attempt_at >= msg.created_at);
+
+    receiver.jh.abort();
+}
+
 #[tokio::test]
 async fn test_message_attempts_empty_retry_schedule() {
     let mut cfg = get_default_test_config();
@@ -1189,3 +1390,615 @@ async fn test_pagination_forward_and_back() {
     assert_eq!(&forward_msgs[0..10], &backwards_msgs[10..20]);
     assert_eq!(&forward_msgs[10..20], &backwards_msgs[0..10]);
 }
+
+#[tokio::test]
+async fn test_duplicate_task_is_not_redelivered() {
+    let cfg = get_default_test_config();
+    let prefix = svix_ksuid::Ksuid::new(None, None).to_string();
+    let (client, _jh) = start_svix_server_with_cfg_and_prefix(
+        &cfg,
+        OrganizationId::new(None, None),
+        prefix.clone(),
+    )
+    .await;
+
+    let cfg = Arc::new(cfg);
+    let db = svix_server::db::init_db(&cfg).await;
+    let (queue_tx, _) = new_pair(&cfg, Some(&prefix)).await;
+
+    let app_id = create_test_app(&client, "app1").await.unwrap().id;
+    let mut receiver = TestReceiver::start(StatusCode::OK);
+    let endp_id = create_test_endpoint(&client, &app_id, &receiver.endpoint)
+        .await
+        .unwrap()
+        .id;
+    let msg_id = create_test_message(&client, &app_id, serde_json::json!({"test": "data1"}))
+        .await
+        .unwrap()
+        .id;
+
+    receiver.data_recv.recv().await.unwrap();
+
+    let wait_for_success = || async {
+        let msg_dest = messagedestination::Entity::secure_find_by_endpoint(endp_id.clone())
+            .one(&db)
+            .await?
+            .unwrap();
+        anyhow::ensure!(msg_dest.status == MessageStatus::Success);
+        anyhow::Ok(())
+    };
+    run_with_retries(wait_for_success).await.unwrap();
+
+    // Simulate a worker dying after making the HTTP call, but before the task was marked as done
+    messagedestination::Entity::update_many()
+        .col_expr(
+            messagedestination::Column::Status,
+            Expr::value(MessageStatus::Sending),
+        )
+        .filter(messagedestination::Column::EndpId.eq(endp_id.clone()))
+        .exec(&db)
+        .await
+        .unwrap();
+    queue_tx
+        .send(
+            MessageTask::new_task(
+                msg_id,
+                app_id,
+                endp_id.clone(),
+                MessageAttemptTriggerType::Scheduled,
+            ),
+            None,
+        )
+        .await
+        .unwrap();
+
+    // The duplicate task completes the message destination without sending the webhook again
+    run_with_retries(wait_for_success).await.unwrap();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert!(receiver.data_recv.try_recv().is_err());
+}
+
+/// A delivery still due to be retried is cancelled once its application is deleted, rather than
+/// being left pending forever.
+#[tokio::test]
+async fn test_deleted_app_cancels_pending_delivery() {
+    let cfg = get_default_test_config();
+    let prefix = svix_ksuid::Ksuid::new(None, None).to_string();
+    let (client, _jh) = start_svix_server_with_cfg_and_prefix(
+        &cfg,
+        OrganizationId::new(None, None),
+        prefix.clone(),
+    )
+    .await;
+
+    let cfg = Arc::new(cfg);
+    let db = svix_server::db::init_db(&cfg).await;
+    let (queue_tx, _) = new_pair(&cfg, Some(&prefix)).await;
+
+    let app_id = create_test_app(&client, "app1").await.unwrap().id;
+    let mut receiver = TestReceiver::start(StatusCode::OK);
+    let endp_id = create_test_endpoint(&client, &app_id, &receiver.endpoint)
+        .await
+        .unwrap()
+        .id;
+    let msg_id = create_test_message(&client, &app_id, serde_json::json!({"test": "data1"}))
+        .await
+        .unwrap()
+        .id;
+
+    receiver.data_recv.recv().await.unwrap();
+
+    let wait_for_status = |status| {
+        let db = &db;
+        let endp_id = endp_id.clone();
+        async move {
+            let msg_dest = messagedestination::Entity::secure_find_by_endpoint(endp_id)
+                .one(db)
+                .await?
+                .unwrap();
+            anyhow::ensure!(msg_dest.status == status);
+            anyhow::Ok(())
+        }
+    };
+    run_with_retries(|| wait_for_status(MessageStatus::Success))
+        .await
+        .unwrap();
+
+    // Simulate the delivery waiting for a retry
+    messagedestination::Entity::update_many()
+        .col_expr(
+            messagedestination::Column::Status,
+            Expr::value(MessageStatus::Pending),
+        )
+        .filter(messagedestination::Column::EndpId.eq(endp_id.clone()))
+        .exec(&db)
+        .await
+        .unwrap();
+
+    client
+        .delete(&format!("api/v1/app/{app_id}/"), StatusCode::NO_CONTENT)
+        .await
+        .unwrap();
+
+    queue_tx
+        .send(
+            MessageTask::new_task(
+                msg_id,
+                app_id,
+                endp_id.clone(),
+                MessageAttemptTriggerType::Scheduled,
+            ),
+            None,
+        )
+        .await
+        .unwrap();
+
+    run_with_retries(|| wait_for_status(MessageStatus::Cancelled))
+        .await
+        .unwrap();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert!(receiver.data_recv.try_recv().is_err());
+}
+
+#[tokio::test]
+async fn test_stuck_delivery_is_requeued() {
+    let mut cfg = get_default_test_config();
+    cfg.retry_schedule = vec![Duration::from_secs(60 * 60)];
+    let prefix = svix_ksuid::Ksuid::new(None, None).to_string();
+    let (client, _jh) = start_svix_server_with_cfg_and_prefix(
+        &cfg,
+        OrganizationId::new(None, None),
+        prefix.clone(),
+    )
+    .await;
+
+    let cfg = Arc::new(cfg);
+    let db = svix_server::db::init_db(&cfg).await;
+    let (queue_tx, _) = new_pair(&cfg, Some(&prefix)).await;
+
+    let app_id = create_test_app(&client, "app1").await.unwrap().id;
+    let mut receiver = TestReceiver::start(StatusCode::INTERNAL_SERVER_ERROR);
+    let endp_id = create_test_endpoint(&client, &app_id, &receiver.endpoint)
+        .await
+        .unwrap()
+        .id;
+    create_test_message(&client, &app_id, serde_json::json!({"test": "data1"}))
+        .await
+        .unwrap();
+
+    receiver.data_recv.recv().await.unwrap();
+
+    let find_msg_dest = || async {
+        let msg_dest = messagedestination::Entity::secure_find_by_endpoint(endp_id.clone())
+            .one(&db)
+            .await?
+            .unwrap();
+        anyhow::ensure!(msg_dest.last_attempt_at.is_some());
+        anyhow::Ok(msg_dest)
+    };
+    let msg_dest = run_with_retries(find_msg_dest).await.unwrap();
+    assert_eq!(msg_dest.status, MessageStatus::Sending);
+    let next_attempt = msg_dest.next_attempt.unwrap();
+
+    // Waiting for its retry isn't being stuck
+    requeue_stuck_deliveries(&db, &queue_tx, cfg.retry_schedule[0], 1_000)
+        .await
+        .unwrap();
+    let msg_dest = find_msg_dest().await.unwrap();
+    assert_eq!(msg_dest.next_attempt, Some(next_attempt));
+
+    // Simulate a worker dying after the first attempt, before the retry was queued
+    let long_ago = chrono::Utc::now() - chrono::Duration::days(1);
+    messagedestination::Entity::update_many()
+        .col_expr(
+            messagedestination::Column::LastAttemptAt,
+            Expr::value(Some(sea_orm::prelude::DateTimeWithTimeZone::from(long_ago))),
+        )
+        .col_expr(
+            messagedestination::Column::NextAttempt,
+            Expr::value(Some(sea_orm::prelude::DateTimeWithTimeZone::from(long_ago))),
+        )
+        .filter(messagedestination::Column::EndpId.eq(endp_id.clone()))
+        .exec(&db)
+        .await
+        .unwrap();
+
+    // Whichever instance queues it again, the retry is made, and being the last one fails the
+    // delivery
+    requeue_stuck_deliveries(&db, &queue_tx, cfg.retry_schedule[0], 1_000)
+        .await
+        .unwrap();
+    receiver.data_recv.recv().await.unwrap();
+    run_with_retries(|| async {
+        let msg_dest = find_msg_dest().await?;
+        anyhow::ensure!(msg_dest.status == MessageStatus::Fail);
+        anyhow::ensure!(msg_dest.last_attempt_at.unwrap() > long_ago);
+        anyhow::Ok(())
+    })
+    .await
+    .unwrap();
+
+    receiver.jh.abort();
+}
+
+#[tokio::test]
+async fn test_stuck_unattempted_delivery_is_requeued() {
+    let mut cfg = get_default_test_config();
+    cfg.retry_schedule = vec![Duration::from_secs(60 * 60)];
+    let prefix = svix_ksuid::Ksuid::new(None, None).to_string();
+    let (client, _jh) = start_svix_server_with_cfg_and_prefix(
+        &cfg,
+        OrganizationId::new(None, None),
+        prefix.clone(),
+    )
+    .await;
+
+    let cfg = Arc::new(cfg);
+    let db = svix_server::db::init_db(&cfg).await;
+    let (queue_tx, _) = new_pair(&cfg, Some(&prefix)).await;
+
+    let app_id = create_test_app(&client, "app1").await.unwrap().id;
+    let mut receiver = TestReceiver::start(StatusCode::INTERNAL_SERVER_ERROR);
+    let endp_id = create_test_endpoint(&client, &app_id, &receiver.endpoint)
+        .await
+        .unwrap()
+        .id;
+    create_test_message(&client, &app_id, serde_json::json!({"test": "data1"}))
+        .await
+        .unwrap();
+
+    receiver.data_recv.recv().await.unwrap();
+
+    let find_msg_dest = || async {
+        let msg_dest = messagedestination::Entity::secure_find_by_endpoint(endp_id.clone())
+            .one(&db)
+            .await?
+            .unwrap();
+        anyhow::ensure!(msg_dest.last_attempt_at.is_some());
+        anyhow::Ok(msg_dest)
+    };
+    run_with_retries(find_msg_dest).await.unwrap();
+
+    // Simulate a delivery that was rate limited long ago, before its last attempt was ever recorded
+    let long_ago = chrono::Utc::now() - chrono::Duration::days(1);
+    messagedestination::Entity::update_many()
+        .col_expr(
+            messagedestination::Column::Status,
+            Expr::value(MessageStatus::RateLimited),
+        )
+        .col_expr(
+            messagedestination::Column::LastAttemptAt,
+            Expr::value(Option::<sea_orm::prelude::DateTimeWithTimeZone>::None),
+        )
+        .col_expr(
+            messagedestination::Column::CreatedAt,
+            Expr::value(sea_orm::prelude::DateTimeWithTimeZone::from(long_ago)),
+        )
+        .col_expr(
+            messagedestination::Column::NextAttempt,
+            Expr::value(Some(sea_orm::prelude::DateTimeWithTimeZone::from(long_ago))),
+        )
+        .filter(messagedestination::Column::EndpId.eq(endp_id.clone()))
+        .exec(&db)
+        .await
+        .unwrap();
+
+    // It's still stuck, so its retry is made
+    requeue_stuck_deliveries(&db, &queue_tx, cfg.retry_schedule[0], 1_000)
+        .await
+        .unwrap();
+    receiver.data_recv.recv().await.unwrap();
+    run_with_retries(|| async {
+        let msg_dest = find_msg_dest().await?;
+        anyhow::ensure!(msg_dest.status == MessageStatus::Fail);
+        anyhow::Ok(())
+    })
+    .await
+    .unwrap();
+
+    receiver.jh.abort();
+}
+
+#[tokio::test]
+async fn test_stuck_delivery_to_deleted_endpoint_is_cancelled() {
+    let mut cfg = get_default_test_config();
+    cfg.retry_schedule = vec![Duration::from_secs(60 * 60)];
+    let prefix = svix_ksuid::Ksuid::new(None, None).to_string();
+    let (client, _jh) = start_svix_server_with_cfg_and_prefix(
+        &cfg,
+        OrganizationId::new(None, None),
+        prefix.clone(),
+    )
+    .await;
+
+    let cfg = Arc::new(cfg);
+    let db = svix_server::db::init_db(&cfg).await;
+    let (queue_tx, _) = new_pair(&cfg, Some(&prefix)).await;
+
+    let app_id = create_test_app(&client, "app1").await.unwrap().id;
+    let mut receiver = TestReceiver::start(StatusCode::INTERNAL_SERVER_ERROR);
+    let endp_id = create_test_endpoint(&client, &app_id, &receiver.endpoint)
+        .await
+        .unwrap()
+        .id;
+    create_test_message(&client, &app_id, serde_json::json!({"test": "data1"}))
+        .await
+        .unwrap();
+
+    receiver.data_recv.recv().await.unwrap();
+
+    let find_msg_dest = || async {
+        let msg_dest = messagedestination::Entity::secure_find_by_endpoint(endp_id.clone())
+            .one(&db)
+            .await?
+            .unwrap();
+        anyhow::ensure!(msg_dest.last_attempt_at.is_some());
+        anyhow::Ok(msg_dest)
+    };
+    run_with_retries(find_msg_dest).await.unwrap();
+
+    client
+        .delete(
+            &format!("api/v1/app/{app_id}/endpoint/{endp_id}/"),
+            StatusCode::NO_CONTENT,
+        )
+        .await
+        .unwrap();
+
+    let long_ago = chrono::Utc::now() - chrono::Duration::days(1);
+    messagedestination::Entity::update_many()
+        .col_expr(
+            messagedestination::Column::LastAttemptAt,
+            Expr::value(Some(sea_orm::prelude::DateTimeWithTimeZone::from(long_ago))),
+        )
+        .col_expr(
+            messagedestination::Column::NextAttempt,
+            Expr::value(Some(sea_orm::prelude::DateTimeWithTimeZone::from(long_ago))),
+        )
+        .filter(messagedestination::Column::EndpId.eq(endp_id.clone()))
+        .exec(&db)
+        .await
+        .unwrap();
+
+    // The delivery can't be made anymore, so it's cancelled rather than queued again forever
+    requeue_stuck_deliveries(&db, &queue_tx, cfg.retry_schedule[0], 1_000)
+        .await
+        .unwrap();
+    run_with_retries(|| async {
+        let msg_dest = find_msg_dest().await?;
+        anyhow::ensure!(msg_dest.status == MessageStatus::Cancelled);
+        anyhow::Ok(())
+    })
+    .await
+    .unwrap();
+    assert!(receiver.data_recv.try_recv().is_err());
+
+    receiver.jh.abort();
+}
+
+#[tokio::test]
+async fn test_duplicate_retry_is_dropped() {
+    let mut cfg = get_default_test_config();
+    cfg.retry_schedule = vec![Duration::from_secs(60 * 60); 2];
+    let prefix = svix_ksuid::Ksuid::new(None, None).to_string();
+    let (client, _jh) = start_svix_server_with_cfg_and_prefix(
+        &cfg,
+        OrganizationId::new(None, None),
+        prefix.clone(),
+    )
+    .await;
+
+    let cfg = Arc::new(cfg);
+    let db = svix_server::db::init_db(&cfg).await;
+    let (queue_tx, _) = new_pair(&cfg, Some(&prefix)).await;
+
+    let app_id = create_test_app(&client, "app1").await.unwrap().id;
+    let mut receiver = TestReceiver::start(StatusCode::INTERNAL_SERVER_ERROR);
+    let endp_id = create_test_endpoint(&client, &app_id, &receiver.endpoint)
+        .await
+        .unwrap()
+        .id;
+    let msg_id = create_test_message(&client, &app_id, serde_json::json!({"test": "data1"}))
+        .await
+        .unwrap()
+        .id;
+
+    receiver.data_recv.recv().await.unwrap();
+
+    let wait_for_attempts = |count| {
+        let db = &db;
+        let endp_id = endp_id.clone();
+        async move {
+            let attempts = messageattempt::Entity::find()
+                .filter(messageattempt::Column::EndpId.eq(endp_id))
+                .count(db)
+                .await?;
+            anyhow::ensure!(attempts == count);
+            anyhow::Ok(())
+        }
+    };
+    run_with_retries(|| wait_for_attempts(1)).await.unwrap();
+
+    let retry = QueueTask::MessageV1(MessageTask {
+        msg_id,
+        app_id,
+        endpoint_id: endp_id.clone(),
+        trigger_type: MessageAttemptTriggerType::Scheduled,
+        attempt_count: 1,
+    });
+
+    queue_tx.send(retry.clone(), None).await.unwrap();
+    receiver.data_recv.recv().await.unwrap();
+    run_with_retries(|| wait_for_attempts(2)).await.unwrap();
+
+    // As if the stuck delivery detector had queued the retry again while the original was
+    // still waiting in the queue
+    queue_tx.send(retry, None).await.unwrap();
+    tokio::time::sleep(Duration::from_millis(500)).await;
+    assert!(receiver.data_recv.try_recv().is_err());
+    wait_for_attempts(2).await.unwrap();
+
+    receiver.jh.abort();
+}
+
+#[tokio::test]
+async fn test_correlation_id_is_sent_with_every_attempt() {
+    let mut cfg = get_default_test_config();
+    cfg.retry_schedule = (0..2).map(|_| Duration::from_millis(1)).collect();
+    let (client, _jh) = start_svix_server_with_cfg(&cfg).await;
+    let pool = svix_server::db::init_db(&cfg).await;
+
+    let app_id = create_test_app(&client, "app").await.unwrap().id;
+    let mut receiver = TestReceiver::start(StatusCode::INTERNAL_SERVER_ERROR);
+    create_test_endpoint(&client, &app_id, &receiver.endpoint)
+        .await
+        .unwrap();
+
+    let msg = create_test_message(&client, &app_id, serde_json::json!({"test": "data"}))
+        .await
+        .unwrap();
+    let correlation_id = message::Entity::find_by_id(msg.id.clone())
+        .one(&pool)
+        .await
+        .unwrap()
+        .unwrap()
+        .correlation_id
+        .unwrap();
+    assert!(correlation_id.0.starts_with("corr_"));
+
+    // The first attempt and both retries all carry the same one
+    for _ in 0..cfg.retry_schedule.len() + 1 {
+        let headers = receiver.header_recv.recv().await.unwrap();
+        assert_eq!(headers["svix-correlation-id"], correlation_id.0.as_str());
+    }
+
+    // While other messages get their own
+    let other = create_test_message(&client, &app_id, serde_json::json!({"test": "data"}))
+        .await
+        .unwrap();
+    let other = message::Entity::find_by_id(other.id)
+        .one(&pool)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_ne!(other.correlation_id, Some(correlation_id));
+
+    receiver.jh.abort();
+}
+
+#[tokio::test]
+async fn test_pre_resolve_dns_fails_unresolvable_endpoints() {
+    let mut cfg = get_default_test_config();
+    cfg.pre_resolve_dns = true;
+    cfg.retry_schedule = (0..2).map(|_| Duration::from_millis(1)).collect();
+    let (client, _jh) = start_svix_server_with_cfg(&cfg).await;
+
+    let app_id = create_test_app(&client, "app").await.unwrap().id;
+    // `.invalid` names are guaranteed never to resolve
+    let unresolvable = create_test_endpoint(&client, &app_id, "http://svix-test.invalid/")
+        .await
+        .unwrap();
+    let mut receiver = TestReceiver::start(StatusCode::OK);
+    create_test_endpoint(&client, &app_id, &receiver.endpoint)
+        .await
+        .unwrap();
+
+    create_test_message(&client, &app_id, serde_json::json!({"test": "data"}))
+        .await
+        .unwrap();
+
+    // Endpoints which do resolve are unaffected
+    receiver.data_recv.recv().await.unwrap();
+
+    // The first attempt and both retries fail without waiting for a connection to time out
+    let attempts: ListResponse<MessageAttemptOut> = run_with_retries(|| async {
+        let list: ListResponse<MessageAttemptOut> = client
+            .get(
+                &format!("api/v1/app/{app_id}/attempt/endpoint/{}/", unresolvable.id),
+                StatusCode::OK,
+            )
+            .await?;
+        if list.data.len() != cfg.retry_schedule.len() + 1 {
+            anyhow::bail!("Only {} attempts so far", list.data.len());
+        }
+        Ok(list)
+    })
+    .await
+    .unwrap();
+    for attempt in attempts.data {
+        assert_eq!(attempt.status, MessageStatus::Fail);
+        assert_eq!(attempt.response_status_code, 0);
+        assert_eq!(attempt.response, "DNS resolution failed");
+    }
+
+    receiver.jh.abort();
+}
+
+#[tokio::test]
+async fn test_list_attempt_summaries() {
+    let (client, _jh) = start_svix_server().await;
+
+    let app_id = create_test_app(&client, "app").await.unwrap().id;
+    let mut receiver = TestReceiver::start_with_body(
+        axum::http::StatusCode::OK,
+        axum::Json(serde_json::json!({"large": "a".repeat(10_000)})),
+    );
+    let endpoint_id = create_test_endpoint(&client, &app_id, &receiver.endpoint)
+        .await
+        .unwrap()
+        .id;
+
+    let msg_id = create_test_message(&client, &app_id, serde_json::json!({"test": "data"}))
+        .await
+        .unwrap()
+        .id;
+    receiver.data_recv.recv().await.unwrap();
+
+    let attempt = get_msg_attempt_list_and_assert_count(&client, &app_id, &msg_id, 1)
+        .await
+        .unwrap()
+        .data
+        .remove(0);
+    assert!(attempt.response.len() > 10_000);
+
+    let summaries: serde_json::Value = client
+        .get(
+            &format!("api/v1/app/{app_id}/msg/{msg_id}/attempt/summary/"),
+            StatusCode::OK,
+        )
+        .await
+        .unwrap();
+    let summaries = summaries["data"].as_array().unwrap();
+    assert_eq!(summaries.len(), 1);
+    assert_eq!(
+        summaries[0],
+        serde_json::json!({
+            "id": attempt.id,
+            "status": attempt.status,
+            "responseStatusCode": 200,
+            "endpointId": endpoint_id,
+            "timestamp": attempt.created_at,
+        })
+    );
+
+    // Other messages' attempts aren't included
+    let other_msg_id = create_test_message(&client, &app_id, serde_json::json!({"test": "data"}))
+        .await
+        .unwrap()
+        .id;
+    receiver.data_recv.recv().await.unwrap();
+    get_msg_attempt_list_and_assert_count(&client, &app_id, &other_msg_id, 1)
+        .await
+        .unwrap();
+    let summaries: ListResponse<MessageAttemptSummaryOut> = client
+        .get(
+            &format!("api/v1/app/{app_id}/msg/{msg_id}/attempt/summary/"),
+            StatusCode::OK,
+        )
+        .await
+        .unwrap();
+    assert_eq!(summaries.data.len(), 1);
+    assert_eq!(summaries.data[0].id, attempt.id);
+
+    receiver.jh.abort();
+}
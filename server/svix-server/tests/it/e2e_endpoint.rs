@@ -10,30 +10,32 @@ use std::{
 use anyhow::Result;
 use chrono::{DateTime, Utc};
 use ed25519_compact::Signature;
+use openssl::{hash::MessageDigest, pkey::PKey, rsa::Padding, sign::Verifier};
 use reqwest::{StatusCode, Url};
 use sea_orm::{
-    ActiveModelBehavior, ActiveModelTrait, ConnectionTrait, DatabaseBackend, QueryResult, Set,
-    Statement,
+    ActiveModelBehavior, ActiveModelTrait, ConnectionTrait, DatabaseBackend, EntityTrait,
+    QueryResult, Set, Statement,
 };
 use serde::{de::IgnoredAny, Deserialize};
 use svix::webhooks::Webhook;
 use svix_server::{
     cfg::DefaultSignatureType,
     core::{
-        cryptography::{AsymmetricKey, Encryption},
+        cryptography::{AsymmetricKey, Encryption, PayloadEncryptionKey, RsaKey},
         types::{
             ApplicationId, BaseId, EndpointHeaders, EndpointHeadersPatch, EndpointId,
             EndpointSecret, EndpointSecretInternal, EndpointUid, EventChannel, EventChannelSet,
-            EventTypeName, EventTypeNameSet, ExpiringSigningKeys, MessageEndpointId, MessageId,
-            MessageStatus, OrganizationId,
+            EventTypeName, EventTypeNameSet, ExpiringSigningKeys, HttpMethod, MessageEndpointId,
+            MessageId, MessageStatus, OrganizationId,
         },
     },
-    db::models::{message, messagedestination},
+    db::models::{endpoint, message, messagedestination},
     v1::{
         endpoints::{
             endpoint::{
-                EndpointHeadersIn, EndpointHeadersOut, EndpointHeadersPatchIn, EndpointIn,
-                EndpointOut, EndpointSecretOut, EndpointStatsOut, RecoverIn,
+                EndpointEncryptionKeyOut, EndpointHeadersIn, EndpointHeadersOut,
+                EndpointHeadersPatchIn, EndpointIn, EndpointOut, EndpointSecretOut,
+                EndpointStatsOut, EndpointTestOut, EndpointTransformationOut, RecoverIn,
             },
             event_type::EventTypeOut,
             message::{MessageIn, MessageOut, RawPayload},
@@ -46,7 +48,7 @@ use crate::utils::{
     common_calls::{
         common_test_list, create_test_app, create_test_endpoint, create_test_message,
         default_test_endpoint, delete_test_app, endpoint_in, event_type_in,
-        get_msg_attempt_list_and_assert_count, metadata, post_endpoint, put_endpoint,
+        get_msg_attempt_list_and_assert_count, message_in, metadata, post_endpoint, put_endpoint,
         recover_webhooks,
     },
     get_default_test_config, start_svix_server, start_svix_server_with_cfg,
@@ -1157,6 +1159,81 @@ async fn test_endpoint_rotate_signing_e2e() {
     }
 }
 
+#[tokio::test]
+async fn test_endpoint_rotate_signing_old_key_expires() {
+    let (client, _jh) = start_svix_server().await;
+
+    let app_id = create_test_app(&client, "app1").await.unwrap().id;
+
+    let mut receiver = TestReceiver::start(StatusCode::OK);
+
+    let endp = create_test_endpoint(&client, &app_id, &receiver.endpoint)
+        .await
+        .unwrap();
+
+    let old_secret: EndpointSecretOut = client
+        .get(
+            &format!("api/v1/app/{app_id}/endpoint/{}/secret/", endp.id),
+            StatusCode::OK,
+        )
+        .await
+        .unwrap();
+
+    client
+        .post_without_response(
+            &format!("api/v1/app/{app_id}/endpoint/{}/secret/rotate/", endp.id),
+            serde_json::json!({ "key": null }),
+            StatusCode::NO_CONTENT,
+        )
+        .await
+        .unwrap();
+
+    let new_secret: EndpointSecretOut = client
+        .get(
+            &format!("api/v1/app/{app_id}/endpoint/{}/secret/", endp.id),
+            StatusCode::OK,
+        )
+        .await
+        .unwrap();
+
+    // Move the end of the rotation window into the past
+    let cfg = Arc::new(get_default_test_config());
+    let db = svix_server::db::init_db(&cfg).await;
+    let model = endpoint::Entity::find_by_id(endp.id.clone())
+        .one(&db)
+        .await
+        .unwrap()
+        .unwrap();
+    let mut old_keys = model.old_keys.clone().unwrap();
+    assert_eq!(old_keys.0.len(), 1);
+    for key in &mut old_keys.0 {
+        key.expiration = Utc::now() - chrono::Duration::minutes(1);
+    }
+    let mut model: endpoint::ActiveModel = model.into();
+    model.old_keys = Set(Some(old_keys));
+    model.update(&db).await.unwrap();
+
+    let payload = serde_json::from_str(r#"{"test":"data1"}"#).unwrap();
+    let _msg = create_test_message(&client, &app_id, payload)
+        .await
+        .unwrap();
+
+    let last_headers = receiver.header_recv.recv().await.unwrap();
+    let last_body = receiver.data_recv.recv().await.unwrap().to_string();
+
+    let verify = |secret: EndpointSecretOut| {
+        let EndpointSecret::Symmetric(key) = secret.key else {
+            panic!("Shouldn't get here");
+        };
+        Webhook::new(&base64::encode(key))
+            .unwrap()
+            .verify(last_body.as_bytes(), &last_headers)
+    };
+
+    verify(new_secret).unwrap();
+    assert!(verify(old_secret).is_err());
+}
+
 #[tokio::test]
 async fn test_endpoint_rotate_signing_symmetric_and_asymmetric() {
     let (client, _jh) = start_svix_server().await;
@@ -1173,6 +1250,8 @@ async fn test_endpoint_rotate_signing_symmetric_and_asymmetric() {
     let secret_2 = EndpointSecret::Asymmetric(AsymmetricKey::from_base64("6Xb/dCcHpPea21PS1N9VY/NZW723CEc77N4rJCubMbfVKIDij2HKpMKkioLlX0dRqSKJp4AJ6p9lMicMFs6Kvg==").unwrap());
     // Long key
     let secret_3 = EndpointSecret::Symmetric(base64::decode("TUdfVE5UMnZlci1TeWxOYXQtX1ZlTW1kLTRtMFdhYmEwanIxdHJvenRCbmlTQ2hFdzBnbHhFbWdFaTJLdzQwSA==").unwrap());
+    // RSA key
+    let secret_4 = EndpointSecret::Rsa(RsaKey::generate().unwrap());
 
     let ep_in = EndpointIn {
         url: Url::parse(&receiver.endpoint).unwrap(),
@@ -1204,6 +1283,16 @@ async fn test_endpoint_rotate_signing_symmetric_and_asymmetric() {
         .await
         .unwrap();
 
+    // And then to RSA
+    client
+        .post_without_response(
+            &format!("api/v1/app/{app_id}/endpoint/{}/secret/rotate/", endp.id),
+            serde_json::json!({ "key": secret_4.serialize_secret_key() }),
+            StatusCode::NO_CONTENT,
+        )
+        .await
+        .unwrap();
+
     let raw_payload = r#"{"test":"data1"}"#;
     let payload = serde_json::from_str(raw_payload).unwrap();
     let _msg = create_test_message(&client, &app_id, payload)
@@ -1213,7 +1302,7 @@ async fn test_endpoint_rotate_signing_symmetric_and_asymmetric() {
     let last_headers = receiver.header_recv.recv().await.unwrap();
     let last_body = receiver.data_recv.recv().await.unwrap().to_string();
 
-    for sec in [secret_1, secret_2, secret_3] {
+    for sec in [secret_1, secret_2, secret_3, secret_4] {
         match sec {
             EndpointSecret::Symmetric(key) => {
                 let sec = base64::encode(key);
@@ -1248,10 +1337,213 @@ async fn test_endpoint_rotate_signing_symmetric_and_asymmetric() {
                         });
                 assert!(found);
             }
+            EndpointSecret::Rsa(key) => {
+                let msg_id = last_headers.get("svix-id").unwrap().to_str().unwrap();
+                let timestamp = last_headers
+                    .get("svix-timestamp")
+                    .unwrap()
+                    .to_str()
+                    .unwrap();
+                let signatures = last_headers
+                    .get("svix-signature")
+                    .unwrap()
+                    .to_str()
+                    .unwrap();
+                let to_sign = format!("{msg_id}.{timestamp}.{}", &last_body);
+                let found =
+                    signatures
+                        .split(' ')
+                        .filter(|x| x.starts_with("v1r,"))
+                        .any(|signature| {
+                            let sig = base64::decode(&signature["v1r,".len()..]).unwrap();
+                            key.verify(to_sign.as_bytes(), &sig).unwrap()
+                        });
+                assert!(found);
+            }
         }
     }
 }
 
+#[tokio::test]
+async fn test_endpoint_rsa_public_key() {
+    let (client, _jh) = start_svix_server().await;
+
+    let app_id = create_test_app(&client, "app1").await.unwrap().id;
+
+    let mut receiver = TestReceiver::start(StatusCode::OK);
+
+    let endp = post_endpoint(
+        &client,
+        &app_id,
+        EndpointIn {
+            url: Url::parse(&receiver.endpoint).unwrap(),
+            key: Some(EndpointSecret::Rsa(RsaKey::generate().unwrap())),
+            ..default_test_endpoint()
+        },
+    )
+    .await
+    .unwrap();
+
+    #[derive(Deserialize)]
+    pub struct EndpointPublicKeyOutTest {
+        pub key: String,
+    }
+
+    let public_key = client
+        .get::<EndpointPublicKeyOutTest>(
+            &format!("api/v1/app/{app_id}/endpoint/{}/public-key/", endp.id),
+            StatusCode::OK,
+        )
+        .await
+        .unwrap()
+        .key;
+    let public_key = PKey::public_key_from_pem(public_key.as_bytes()).unwrap();
+
+    let raw_payload = r#"{"test":"data1"}"#;
+    let payload = serde_json::from_str(raw_payload).unwrap();
+    create_test_message(&client, &app_id, payload)
+        .await
+        .unwrap();
+
+    let last_headers = receiver.header_recv.recv().await.unwrap();
+    let last_body = receiver.data_recv.recv().await.unwrap().to_string();
+
+    let header = |name: &str| last_headers.get(name).unwrap().to_str().unwrap().to_owned();
+    let to_sign = format!(
+        "{}.{}.{last_body}",
+        header("svix-id"),
+        header("svix-timestamp")
+    );
+    let signature = header("svix-signature");
+    let signature = base64::decode(signature.strip_prefix("v1r,").unwrap()).unwrap();
+
+    let mut verifier = Verifier::new(MessageDigest::sha256(), &public_key).unwrap();
+    verifier.set_rsa_padding(Padding::PKCS1).unwrap();
+    assert!(verifier
+        .verify_oneshot(&signature, to_sign.as_bytes())
+        .unwrap());
+
+    // Symmetric secrets have no public key
+    let endp = post_endpoint(&client, &app_id, default_test_endpoint())
+        .await
+        .unwrap();
+    client
+        .get::<IgnoredAny>(
+            &format!("api/v1/app/{app_id}/endpoint/{}/public-key/", endp.id),
+            StatusCode::BAD_REQUEST,
+        )
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn test_endpoint_test_webhook() {
+    let (client, _jh) = start_svix_server().await;
+
+    let app_id = create_test_app(&client, "app1").await.unwrap().id;
+
+    let mut receiver = TestReceiver::start_with_body(StatusCode::OK, "all good");
+    let endp = create_test_endpoint(&client, &app_id, &receiver.endpoint)
+        .await
+        .unwrap();
+
+    let out: EndpointTestOut = client
+        .post(
+            &format!("api/v1/app/{app_id}/endpoint/{}/test/", endp.id),
+            serde_json::json!({}),
+            StatusCode::OK,
+        )
+        .await
+        .unwrap();
+    assert_eq!(out.status_code, 200);
+    assert_eq!(out.response_body, "all good");
+
+    // The test webhook is signed like any other
+    let body = receiver.data_recv.recv().await.unwrap();
+    assert_eq!(body, serde_json::json!({"test": true}));
+    let headers = receiver.header_recv.recv().await.unwrap();
+    let secret = client
+        .get::<EndpointSecretOut>(
+            &format!("api/v1/app/{app_id}/endpoint/{}/secret/", endp.id),
+            StatusCode::OK,
+        )
+        .await
+        .unwrap();
+    let EndpointSecret::Symmetric(key) = &secret.key else {
+        panic!("Expected a symmetric key");
+    };
+    Webhook::new(&base64::encode(key))
+        .unwrap()
+        .verify(body.to_string().as_bytes(), &headers)
+        .unwrap();
+
+    // But no message nor attempt is recorded for it
+    let messages: ListResponse<MessageOut> = client
+        .get(&format!("api/v1/app/{app_id}/msg/"), StatusCode::OK)
+        .await
+        .unwrap();
+    assert!(messages.data.is_empty());
+    let attempts: ListResponse<IgnoredAny> = client
+        .get(
+            &format!("api/v1/app/{app_id}/attempt/endpoint/{}/", endp.id),
+            StatusCode::OK,
+        )
+        .await
+        .unwrap();
+    assert!(attempts.data.is_empty());
+
+    // Failing responses are reported as-is
+    receiver.set_response_status_code(StatusCode::INTERNAL_SERVER_ERROR);
+    let out: EndpointTestOut = client
+        .post(
+            &format!("api/v1/app/{app_id}/endpoint/{}/test/", endp.id),
+            serde_json::json!({}),
+            StatusCode::OK,
+        )
+        .await
+        .unwrap();
+    assert_eq!(out.status_code, 500);
+
+    // Endpoints can only be tested so many times per minute
+    for _ in 2..10 {
+        client
+            .post::<_, EndpointTestOut>(
+                &format!("api/v1/app/{app_id}/endpoint/{}/test/", endp.id),
+                serde_json::json!({}),
+                StatusCode::OK,
+            )
+            .await
+            .unwrap();
+    }
+    client
+        .post::<_, IgnoredAny>(
+            &format!("api/v1/app/{app_id}/endpoint/{}/test/", endp.id),
+            serde_json::json!({}),
+            StatusCode::TOO_MANY_REQUESTS,
+        )
+        .await
+        .unwrap();
+
+    // Unreachable endpoints are reported with a zero status code
+    let unreachable = TestReceiver::start(StatusCode::OK);
+    let unreachable_url = unreachable.endpoint.clone();
+    unreachable.jh.abort();
+    let _ = unreachable.jh.await;
+    let endp = create_test_endpoint(&client, &app_id, &unreachable_url)
+        .await
+        .unwrap();
+    let out: EndpointTestOut = client
+        .post(
+            &format!("api/v1/app/{app_id}/endpoint/{}/test/", endp.id),
+            serde_json::json!({}),
+            StatusCode::OK,
+        )
+        .await
+        .unwrap();
+    assert_eq!(out.status_code, 0);
+    assert!(!out.response_body.is_empty());
+}
+
 #[tokio::test]
 async fn test_endpoint_secret_config() {
     let mut cfg = get_default_test_config();
@@ -1459,19 +1751,33 @@ async fn test_invalid_endpoint_secret() {
     let app_id = create_test_app(&client, "app1").await.unwrap().id;
 
     let secret_too_short = "whsec_C2FVsBQIhrscChlQIM+b5sSYspob".to_owned();
-    let secret_too_long =
-        "whsec_V09IYXZUaFJoSnFobnpJQkpPMXdpdGFNWnJsRzAxdXZCeTVndVpwRmxSSXFsc0oyYzBTRWRUekJhYnlaZ0JSRGNPQ3BGZG1xYjFVVmRGQ3UK"
-            .to_owned();
+    // 24 bytes used to be accepted, but is too short for HMAC-SHA256
+    let secret_24_bytes = "whsec_C2FVsBQIhrscChlQIMV+b5sSYspob7oD".to_owned();
+    let secret_too_long = format!("whsec_{}", base64::encode([1; 257]));
     let invalid_prefix = "hwsec_C2FVsBQIhrscChlQIM+b5sSYspob7oDazfgh".to_owned();
 
-    for sec in [secret_too_short, secret_too_long, invalid_prefix] {
+    for (sec, msg) in [
+        (
+            secret_too_short,
+            Some("Symmetric signing key must be at least 32 bytes"),
+        ),
+        (
+            secret_24_bytes,
+            Some("Symmetric signing key must be at least 32 bytes"),
+        ),
+        (
+            secret_too_long,
+            Some("Symmetric signing key must be no longer than 256 bytes"),
+        ),
+        (invalid_prefix, None),
+    ] {
         let ep_in: serde_json::Value = serde_json::json!({
             "url": "http://www.example.com".to_owned(),
             "version": 1,
             "secret": sec,
         });
 
-        let _: IgnoredAny = client
+        let resp: serde_json::Value = client
             .post(
                 &format!("api/v1/app/{app_id}/endpoint/"),
                 ep_in,
@@ -1479,7 +1785,24 @@ async fn test_invalid_endpoint_secret() {
             )
             .await
             .unwrap();
+        if let Some(msg) = msg {
+            assert_eq!(resp["detail"][0]["msg"], msg);
+        }
     }
+
+    let ep_in: serde_json::Value = serde_json::json!({
+        "url": "http://www.example.com".to_owned(),
+        "version": 1,
+        "secret": format!("whsec_{}", base64::encode([1; 32])),
+    });
+    let _: IgnoredAny = client
+        .post(
+            &format!("api/v1/app/{app_id}/endpoint/"),
+            ep_in,
+            StatusCode::CREATED,
+        )
+        .await
+        .unwrap();
 }
 
 fn new_message_dest_at_time(
@@ -2009,6 +2332,11 @@ async fn test_msg_event_types_filter() {
                     payload: RawPayload::from_string("{}".to_string()).unwrap(),
                     uid: None,
                     payload_retention_period: 5,
+                    tags: None,
+                    deadline: None,
+                    signed_metadata: None,
+                    payload_version: None,
+                    endpoint_metadata_selector: None,
                 },
                 StatusCode::ACCEPTED,
             )
@@ -2058,6 +2386,11 @@ async fn test_msg_channels_filter() {
                     payload: RawPayload::from_string("{}".to_string()).unwrap(),
                     uid: None,
                     payload_retention_period: 5,
+                    tags: None,
+                    deadline: None,
+                    signed_metadata: None,
+                    payload_version: None,
+                    endpoint_metadata_selector: None,
                 },
                 StatusCode::ACCEPTED,
             )
@@ -2384,6 +2717,717 @@ async fn test_endpoint_https_only() {
         .unwrap();
 }
 
+#[tokio::test]
+async fn test_endpoint_http_method() {
+    let mut cfg = get_default_test_config();
+    cfg.retry_schedule = vec![Duration::from_millis(1)];
+    let (client, _jh) = start_svix_server_with_cfg(&cfg).await;
+
+    let app_id = create_test_app(&client, "App 1").await.unwrap().id;
+    // Only handles `POST` and `GET` requests, responding with a 405 to any other method
+    let mut receiver = TestReceiver::start(StatusCode::OK);
+
+    let endp = create_test_endpoint(&client, &app_id, &receiver.endpoint)
+        .await
+        .unwrap();
+    assert_eq!(endp.ep.http_method, HttpMethod(http::Method::POST));
+
+    for method in ["GET", "DELETE", "put"] {
+        let mut ep_in = serde_json::to_value(endpoint_in(&receiver.endpoint)).unwrap();
+        ep_in["httpMethod"] = serde_json::json!(method);
+        let _: IgnoredAny = client
+            .post(
+                &format!("api/v1/app/{app_id}/endpoint/"),
+                ep_in,
+                StatusCode::UNPROCESSABLE_ENTITY,
+            )
+            .await
+            .unwrap();
+    }
+
+    let put_endp = post_endpoint(
+        &client,
+        &app_id,
+        EndpointIn {
+            http_method: HttpMethod(http::Method::PUT),
+            ..endpoint_in(&receiver.endpoint)
+        },
+    )
+    .await
+    .unwrap();
+    assert_eq!(put_endp.ep.http_method, HttpMethod(http::Method::PUT));
+
+    let patched: EndpointOut = client
+        .patch(
+            &format!("api/v1/app/{app_id}/endpoint/{}/", put_endp.id),
+            serde_json::json!({ "httpMethod": "PATCH" }),
+            StatusCode::OK,
+        )
+        .await
+        .unwrap();
+    assert_eq!(patched.ep.http_method, HttpMethod(http::Method::PATCH));
+    let _: IgnoredAny = client
+        .patch(
+            &format!("api/v1/app/{app_id}/endpoint/{}/", put_endp.id),
+            serde_json::json!({ "httpMethod": "DELETE" }),
+            StatusCode::UNPROCESSABLE_ENTITY,
+        )
+        .await
+        .unwrap();
+
+    // Test webhooks are sent with the endpoint's method too
+    let out: EndpointTestOut = client
+        .post(
+            &format!("api/v1/app/{app_id}/endpoint/{}/test/", put_endp.id),
+            serde_json::json!({}),
+            StatusCode::OK,
+        )
+        .await
+        .unwrap();
+    assert_eq!(out.status_code, 405);
+
+    let msg = create_test_message(&client, &app_id, serde_json::json!({"test": "data"}))
+        .await
+        .unwrap();
+
+    // Received by the receiver as a `POST`
+    receiver.data_recv.recv().await.unwrap();
+
+    // While the `PATCH` requests are refused, both the first attempt and its retry
+    let list = get_msg_attempt_list_and_assert_count(&client, &app_id, &msg.id, 3)
+        .await
+        .unwrap();
+    for attempt in list.data {
+        if attempt.endpoint_id == endp.id {
+            assert_eq!(attempt.status, MessageStatus::Success);
+        } else {
+            assert_eq!(attempt.endpoint_id, put_endp.id);
+            assert_eq!(attempt.status, MessageStatus::Fail);
+            assert_eq!(attempt.response_status_code, 405);
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_endpoint_content_type() {
+    let (client, _jh) = start_svix_server().await;
+
+    let app_id = create_test_app(&client, "App 1").await.unwrap().id;
+    let mut receiver = TestReceiver::start(StatusCode::OK);
+
+    for content_type in ["json", "application/", "application/json\n"] {
+        let _: IgnoredAny = client
+            .post(
+                &format!("api/v1/app/{app_id}/endpoint/"),
+                EndpointIn {
+                    content_type: Some(content_type.to_owned()),
+                    ..endpoint_in(&receiver.endpoint)
+                },
+                StatusCode::UNPROCESSABLE_ENTITY,
+            )
+            .await
+            .unwrap();
+    }
+
+    let endp = post_endpoint(
+        &client,
+        &app_id,
+        EndpointIn {
+            content_type: Some("application/cloudevents+json".to_owned()),
+            ..endpoint_in(&receiver.endpoint)
+        },
+    )
+    .await
+    .unwrap();
+    assert_eq!(
+        endp.ep.content_type.as_deref(),
+        Some("application/cloudevents+json")
+    );
+
+    create_test_message(&client, &app_id, serde_json::json!({"test": "data"}))
+        .await
+        .unwrap();
+    receiver.data_recv.recv().await.unwrap();
+    let headers = receiver.header_recv.recv().await.unwrap();
+    assert_eq!(headers["content-type"], "application/cloudevents+json");
+
+    // Unsetting it goes back to `application/json`
+    let patched: EndpointOut = client
+        .patch(
+            &format!("api/v1/app/{app_id}/endpoint/{}/", endp.id),
+            serde_json::json!({ "contentType": null }),
+            StatusCode::OK,
+        )
+        .await
+        .unwrap();
+    assert_eq!(patched.ep.content_type, None);
+    let _: IgnoredAny = client
+        .patch(
+            &format!("api/v1/app/{app_id}/endpoint/{}/", endp.id),
+            serde_json::json!({ "contentType": "json" }),
+            StatusCode::UNPROCESSABLE_ENTITY,
+        )
+        .await
+        .unwrap();
+
+    create_test_message(&client, &app_id, serde_json::json!({"test": "data"}))
+        .await
+        .unwrap();
+    receiver.data_recv.recv().await.unwrap();
+    let headers = receiver.header_recv.recv().await.unwrap();
+    assert_eq!(headers["content-type"], "application/json");
+}
+
+#[tokio::test]
+async fn test_endpoint_custom_data() {
+    let (client, _jh) = start_svix_server().await;
+
+    let app_id = create_test_app(&client, "App 1").await.unwrap().id;
+
+    // Only objects are accepted
+    for custom_data in [serde_json::json!("production"), serde_json::json!([1, 2])] {
+        let _: IgnoredAny = client
+            .post(
+                &format!("api/v1/app/{app_id}/endpoint/"),
+                EndpointIn {
+                    custom_data: Some(custom_data),
+                    ..default_test_endpoint()
+                },
+                StatusCode::UNPROCESSABLE_ENTITY,
+            )
+            .await
+            .unwrap();
+    }
+
+    let prod = post_endpoint(
+        &client,
+        &app_id,
+        EndpointIn {
+            custom_data: Some(serde_json::json!({"env": "production", "team": "billing"})),
+            ..default_test_endpoint()
+        },
+    )
+    .await
+    .unwrap();
+    assert_eq!(
+        prod.ep.custom_data,
+        Some(serde_json::json!({"env": "production", "team": "billing"}))
+    );
+    let staging = post_endpoint(
+        &client,
+        &app_id,
+        EndpointIn {
+            custom_data: Some(serde_json::json!({"env": "staging"})),
+            ..default_test_endpoint()
+        },
+    )
+    .await
+    .unwrap();
+    let plain = create_test_endpoint(&client, &app_id, "http://example.com")
+        .await
+        .unwrap();
+    assert_eq!(plain.ep.custom_data, None);
+
+    let list: ListResponse<EndpointOut> = client
+        .get(
+            &format!(
+                "api/v1/app/{app_id}/endpoint/?custom_data_key=env&custom_data_value=production"
+            ),
+            StatusCode::OK,
+        )
+        .await
+        .unwrap();
+    assert_eq!(
+        list.data.iter().map(|e| &e.id).collect::<Vec<_>>(),
+        vec![&prod.id]
+    );
+
+    let list: ListResponse<EndpointOut> = client
+        .get(&format!("api/v1/app/{app_id}/endpoint/"), StatusCode::OK)
+        .await
+        .unwrap();
+    assert_eq!(list.data.len(), 3);
+
+    // Both halves of the filter are needed
+    let _: IgnoredAny = client
+        .get(
+            &format!("api/v1/app/{app_id}/endpoint/?custom_data_key=env"),
+            StatusCode::UNPROCESSABLE_ENTITY,
+        )
+        .await
+        .unwrap();
+
+    // Patching replaces it, and `null` clears it
+    let patched: EndpointOut = client
+        .patch(
+            &format!("api/v1/app/{app_id}/endpoint/{}/", staging.id),
+            serde_json::json!({ "customData": {"env": "production"} }),
+            StatusCode::OK,
+        )
+        .await
+        .unwrap();
+    assert_eq!(
+        patched.ep.custom_data,
+        Some(serde_json::json!({"env": "production"}))
+    );
+    let patched: EndpointOut = client
+        .patch(
+            &format!("api/v1/app/{app_id}/endpoint/{}/", prod.id),
+            serde_json::json!({ "customData": null }),
+            StatusCode::OK,
+        )
+        .await
+        .unwrap();
+    assert_eq!(patched.ep.custom_data, None);
+    let _: IgnoredAny = client
+        .patch(
+            &format!("api/v1/app/{app_id}/endpoint/{}/", prod.id),
+            serde_json::json!({ "customData": "production" }),
+            StatusCode::UNPROCESSABLE_ENTITY,
+        )
+        .await
+        .unwrap();
+
+    let list: ListResponse<EndpointOut> = client
+        .get(
+            &format!(
+                "api/v1/app/{app_id}/endpoint/?custom_data_key=env&custom_data_value=production"
+            ),
+            StatusCode::OK,
+        )
+        .await
+        .unwrap();
+    assert_eq!(
+        list.data.iter().map(|e| &e.id).collect::<Vec<_>>(),
+        vec![&staging.id]
+    );
+}
+
+#[tokio::test]
+async fn test_endpoint_metadata_filter() {
+    let (client, _jh) = start_svix_server().await;
+
+    let app_id = create_test_app(&client, "App 1").await.unwrap().id;
+
+    let too_many_keys = serde_json::to_string(
+        &(0..21)
+            .map(|i| (format!("key{i}"), "value".to_owned()))
+            .collect::<HashMap<_, _>>(),
+    )
+    .unwrap();
+    let too_long_key = format!(r#"{{"{}": "value"}}"#, "k".repeat(65));
+    let too_long_value = format!(r#"{{"key": "{}"}}"#, "v".repeat(257));
+    for invalid in [&too_many_keys, &too_long_key, &too_long_value] {
+        let _: IgnoredAny = client
+            .post(
+                &format!("api/v1/app/{app_id}/endpoint/"),
+                EndpointIn {
+                    metadata: metadata(invalid),
+                    ..default_test_endpoint()
+                },
+                StatusCode::UNPROCESSABLE_ENTITY,
+            )
+            .await
+            .unwrap();
+    }
+
+    let payments = post_endpoint(
+        &client,
+        &app_id,
+        EndpointIn {
+            metadata: metadata(r#"{"env": "prod", "team": "payments"}"#),
+            ..default_test_endpoint()
+        },
+    )
+    .await
+    .unwrap();
+    let search = post_endpoint(
+        &client,
+        &app_id,
+        EndpointIn {
+            metadata: metadata(r#"{"env": "prod", "team": "search"}"#),
+            ..default_test_endpoint()
+        },
+    )
+    .await
+    .unwrap();
+    let dev = post_endpoint(
+        &client,
+        &app_id,
+        EndpointIn {
+            metadata: metadata(r#"{"env": "dev"}"#),
+            ..default_test_endpoint()
+        },
+    )
+    .await
+    .unwrap();
+    create_test_endpoint(&client, &app_id, "http://example.com")
+        .await
+        .unwrap();
+
+    let list_ids = |query: &str| {
+        let url = format!("api/v1/app/{app_id}/endpoint/?{query}");
+        let client = &client;
+        async move {
+            let list: ListResponse<EndpointOut> = client.get(&url, StatusCode::OK).await.unwrap();
+            list.data.into_iter().map(|e| e.id).collect::<HashSet<_>>()
+        }
+    };
+
+    assert_eq!(
+        list_ids("metadata[env]=prod").await,
+        HashSet::from([payments.id.clone(), search.id.clone()])
+    );
+    assert_eq!(
+        list_ids("metadata[env]=prod&metadata[team]=payments").await,
+        HashSet::from([payments.id.clone()])
+    );
+    assert!(list_ids("metadata[env]=staging").await.is_empty());
+    assert_eq!(list_ids("").await.len(), 4);
+
+    // The filter is validated like the metadata itself
+    let _: IgnoredAny = client
+        .get(
+            &format!(
+                "api/v1/app/{app_id}/endpoint/?metadata[{}]=prod",
+                "k".repeat(65)
+            ),
+            StatusCode::UNPROCESSABLE_ENTITY,
+        )
+        .await
+        .unwrap();
+
+    // Patching is validated too, and replaces the metadata
+    let _: IgnoredAny = client
+        .patch(
+            &format!("api/v1/app/{app_id}/endpoint/{}/", dev.id),
+            serde_json::json!({ "metadata": serde_json::from_str::<serde_json::Value>(&too_many_keys).unwrap() }),
+            StatusCode::UNPROCESSABLE_ENTITY,
+        )
+        .await
+        .unwrap();
+    let _: EndpointOut = client
+        .patch(
+            &format!("api/v1/app/{app_id}/endpoint/{}/", dev.id),
+            serde_json::json!({ "metadata": {"env": "prod"} }),
+            StatusCode::OK,
+        )
+        .await
+        .unwrap();
+    assert_eq!(
+        list_ids("metadata[env]=prod").await,
+        HashSet::from([payments.id, search.id, dev.id])
+    );
+}
+
+#[tokio::test]
+async fn test_message_endpoint_metadata_selector() {
+    let (client, _jh) = start_svix_server().await;
+
+    let app_id = create_test_app(&client, "App 1").await.unwrap().id;
+    let mut prod_receiver = TestReceiver::start(StatusCode::OK);
+    let mut dev_receiver = TestReceiver::start(StatusCode::OK);
+
+    post_endpoint(
+        &client,
+        &app_id,
+        EndpointIn {
+            metadata: metadata(r#"{"env": "prod"}"#),
+            ..endpoint_in(&prod_receiver.endpoint)
+        },
+    )
+    .await
+    .unwrap();
+    post_endpoint(
+        &client,
+        &app_id,
+        EndpointIn {
+            metadata: metadata(r#"{"env": "dev"}"#),
+            ..endpoint_in(&dev_receiver.endpoint)
+        },
+    )
+    .await
+    .unwrap();
+
+    let msg: MessageOut = client
+        .post(
+            &format!("api/v1/app/{app_id}/msg/"),
+            MessageIn {
+                endpoint_metadata_selector: Some(metadata(r#"{"env": "prod"}"#)),
+                ..message_in("event.type", serde_json::json!({"selected": true})).unwrap()
+            },
+            StatusCode::ACCEPTED,
+        )
+        .await
+        .unwrap();
+    assert_eq!(
+        msg.endpoint_metadata_selector,
+        Some(metadata(r#"{"env": "prod"}"#))
+    );
+    assert_eq!(
+        prod_receiver.data_recv.recv().await.unwrap(),
+        serde_json::json!({"selected": true})
+    );
+
+    // Without a selector every endpoint is sent the message, so the first message the other
+    // endpoint gets is this one
+    create_test_message(&client, &app_id, serde_json::json!({"selected": false}))
+        .await
+        .unwrap();
+    assert_eq!(
+        prod_receiver.data_recv.recv().await.unwrap(),
+        serde_json::json!({"selected": false})
+    );
+    assert_eq!(
+        dev_receiver.data_recv.recv().await.unwrap(),
+        serde_json::json!({"selected": false})
+    );
+
+    // Selectors are validated like endpoint metadata
+    let _: IgnoredAny = client
+        .post(
+            &format!("api/v1/app/{app_id}/msg/"),
+            MessageIn {
+                endpoint_metadata_selector: Some(metadata(&format!(
+                    r#"{{"env": "{}"}}"#,
+                    "v".repeat(257)
+                ))),
+                ..message_in("event.type", serde_json::json!({})).unwrap()
+            },
+            StatusCode::UNPROCESSABLE_ENTITY,
+        )
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn test_endpoint_custom_signature_prefix() {
+    let (client, _jh) = start_svix_server().await;
+
+    let app_id = create_test_app(&client, "App 1").await.unwrap().id;
+    let mut receiver = TestReceiver::start(StatusCode::OK);
+
+    for prefix in ["", "acme\n"] {
+        let _: IgnoredAny = client
+            .post(
+                &format!("api/v1/app/{app_id}/endpoint/"),
+                EndpointIn {
+                    custom_signature_prefix: Some(prefix.to_owned()),
+                    ..endpoint_in(&receiver.endpoint)
+                },
+                StatusCode::UNPROCESSABLE_ENTITY,
+            )
+            .await
+            .unwrap();
+    }
+
+    let endp = post_endpoint(
+        &client,
+        &app_id,
+        EndpointIn {
+            custom_signature_prefix: Some("acme".to_owned()),
+            ..endpoint_in(&receiver.endpoint)
+        },
+    )
+    .await
+    .unwrap();
+    assert_eq!(endp.ep.custom_signature_prefix.as_deref(), Some("acme"));
+    let secret: EndpointSecretOut = client
+        .get(
+            &format!("api/v1/app/{app_id}/endpoint/{}/secret/", endp.id),
+            StatusCode::OK,
+        )
+        .await
+        .unwrap();
+    let EndpointSecret::Symmetric(key) = secret.key else {
+        panic!("Shouldn't get here");
+    };
+    let wh = Webhook::new(&base64::encode(key)).unwrap();
+
+    create_test_message(&client, &app_id, serde_json::json!({"test": "data"}))
+        .await
+        .unwrap();
+    let mut headers = receiver.header_recv.recv().await.unwrap();
+    let body = receiver.data_recv.recv().await.unwrap().to_string();
+
+    // The message ID is still sent, but the prefix is what's signed
+    assert!(headers["svix-id"].to_str().unwrap().starts_with("msg_"));
+    assert!(wh.verify(body.as_bytes(), &headers).is_err());
+    headers.insert("svix-id", "acme".parse().unwrap());
+    wh.verify(body.as_bytes(), &headers).unwrap();
+
+    // Without it, the message ID is signed again
+    let _: EndpointOut = client
+        .patch(
+            &format!("api/v1/app/{app_id}/endpoint/{}/", endp.id),
+            serde_json::json!({ "customSignaturePrefix": null }),
+            StatusCode::OK,
+        )
+        .await
+        .unwrap();
+
+    create_test_message(&client, &app_id, serde_json::json!({"test": "data"}))
+        .await
+        .unwrap();
+    let headers = receiver.header_recv.recv().await.unwrap();
+    let body = receiver.data_recv.recv().await.unwrap().to_string();
+    wh.verify(body.as_bytes(), &headers).unwrap();
+}
+
+#[tokio::test]
+async fn test_endpoint_payload_encryption() {
+    let (client, _jh) = start_svix_server().await;
+
+    let app_id = create_test_app(&client, "App 1").await.unwrap().id;
+    let mut receiver = TestReceiver::start(StatusCode::OK);
+    let endp = create_test_endpoint(&client, &app_id, &receiver.endpoint)
+        .await
+        .unwrap();
+    let url = format!("api/v1/app/{app_id}/endpoint/{}/encryption-key", endp.id);
+
+    let key_out: EndpointEncryptionKeyOut = client.get(&url, StatusCode::OK).await.unwrap();
+    assert_eq!(key_out, EndpointEncryptionKeyOut::default());
+
+    for key in ["not base64", "aGVsbG8gd29ybGQ="] {
+        let _: IgnoredAny = client
+            .put(
+                &url,
+                serde_json::json!({ "key": key }),
+                StatusCode::UNPROCESSABLE_ENTITY,
+            )
+            .await
+            .unwrap();
+    }
+
+    let private_key = PKey::generate_x25519().unwrap();
+    let pubkey = base64::encode(private_key.raw_public_key().unwrap());
+    client
+        .put_without_response(
+            &url,
+            serde_json::json!({ "key": pubkey }),
+            StatusCode::NO_CONTENT,
+        )
+        .await
+        .unwrap();
+    let key_out: EndpointEncryptionKeyOut = client.get(&url, StatusCode::OK).await.unwrap();
+    assert_eq!(key_out.key.as_deref(), Some(pubkey.as_str()));
+    assert!(key_out.enabled);
+
+    let payload = serde_json::json!({"test": "data"});
+    create_test_message(&client, &app_id, payload.clone())
+        .await
+        .unwrap();
+    let body = receiver.data_recv.recv().await.unwrap();
+    let headers = receiver.header_recv.recv().await.unwrap();
+
+    // Only the endpoint's private key decrypts the payload, whose signature is checked as usual
+    let ciphertext = base64::decode(body.as_str().unwrap()).unwrap();
+    let ephemeral_pubkey = base64::decode(headers["svix-encrypted-key"].as_bytes()).unwrap();
+    let decrypted =
+        PayloadEncryptionKey::open(&private_key, &ephemeral_pubkey, &ciphertext).unwrap();
+    assert_eq!(
+        serde_json::from_slice::<serde_json::Value>(&decrypted).unwrap(),
+        payload
+    );
+
+    let secret: EndpointSecretOut = client
+        .get(
+            &format!("api/v1/app/{app_id}/endpoint/{}/secret", endp.id),
+            StatusCode::OK,
+        )
+        .await
+        .unwrap();
+    let EndpointSecret::Symmetric(key) = secret.key else {
+        panic!("Shouldn't get here");
+    };
+    Webhook::new(&base64::encode(key))
+        .unwrap()
+        .verify(&decrypted, &headers)
+        .unwrap();
+
+    // Once disabled, payloads are sent as they are
+    client
+        .put_without_response(
+            &url,
+            serde_json::json!({ "key": pubkey, "enabled": false }),
+            StatusCode::NO_CONTENT,
+        )
+        .await
+        .unwrap();
+
+    create_test_message(&client, &app_id, payload.clone())
+        .await
+        .unwrap();
+    assert_eq!(receiver.data_recv.recv().await.unwrap(), payload);
+    let headers = receiver.header_recv.recv().await.unwrap();
+    assert!(!headers.contains_key("svix-encrypted-key"));
+}
+
+#[tokio::test]
+async fn test_endpoint_transformation() {
+    let (client, _jh) = start_svix_server().await;
+
+    let app_id = create_test_app(&client, "App 1").await.unwrap().id;
+    let mut receiver = TestReceiver::start(StatusCode::OK);
+    let endp = create_test_endpoint(&client, &app_id, &receiver.endpoint)
+        .await
+        .unwrap();
+    let url = format!("api/v1/app/{app_id}/endpoint/{}/transformation", endp.id);
+
+    let out: EndpointTransformationOut = client.get(&url, StatusCode::OK).await.unwrap();
+    assert_eq!(out, EndpointTransformationOut::default());
+
+    let _: IgnoredAny = client
+        .put(
+            &url,
+            serde_json::json!({ "code": "" }),
+            StatusCode::UNPROCESSABLE_ENTITY,
+        )
+        .await
+        .unwrap();
+
+    let code = "function handler(webhook) { return webhook; }";
+    client
+        .put_without_response(
+            &url,
+            serde_json::json!({ "code": code }),
+            StatusCode::NO_CONTENT,
+        )
+        .await
+        .unwrap();
+    let out: EndpointTransformationOut = client.get(&url, StatusCode::OK).await.unwrap();
+    assert_eq!(out.code.as_deref(), Some(code));
+    assert!(out.enabled);
+
+    // No transformer is registered in tests, so the webhook fails instead of being sent as is
+    let payload = serde_json::json!({"test": "data"});
+    let msg = create_test_message(&client, &app_id, payload.clone())
+        .await
+        .unwrap();
+    let attempts = get_msg_attempt_list_and_assert_count(&client, &app_id, &msg.id, 1)
+        .await
+        .unwrap();
+    assert_eq!(attempts.data[0].status, MessageStatus::Fail);
+    assert!(attempts.data[0]
+        .response
+        .starts_with("Error transforming the payload"));
+
+    // Once disabled, payloads are sent as they are (by the retry too, if it comes first)
+    client
+        .put_without_response(
+            &url,
+            serde_json::json!({ "code": code, "enabled": false }),
+            StatusCode::NO_CONTENT,
+        )
+        .await
+        .unwrap();
+
+    create_test_message(&client, &app_id, payload.clone())
+        .await
+        .unwrap();
+    assert_eq!(receiver.data_recv.recv().await.unwrap(), payload);
+}
+
 #[tokio::test]
 async fn test_send_example() {
     let cfg = get_default_test_config();
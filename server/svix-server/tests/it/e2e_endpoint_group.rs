@@ -0,0 +1,205 @@
+// SPDX-FileCopyrightText: © 2022 Svix Authors
+// SPDX-License-Identifier: MIT
+
+use reqwest::{StatusCode, Url};
+use serde::de::IgnoredAny;
+use svix_server::{
+    core::types::{BaseId, EndpointGroupId, EndpointHeaders, RetrySchedule},
+    v1::{
+        endpoints::{
+            endpoint::{EndpointIn, EndpointOut},
+            endpoint_group::{EndpointGroupIn, EndpointGroupOut},
+        },
+        utils::ListResponse,
+    },
+};
+
+use crate::utils::{
+    common_calls::{create_test_app, create_test_message, default_test_endpoint, post_endpoint},
+    start_svix_server, TestReceiver,
+};
+
+fn endpoint_group_in(name: &str) -> EndpointGroupIn {
+    EndpointGroupIn {
+        name: name.to_owned(),
+        default_headers: None,
+        retry_schedule_override: None,
+        rate_limit: None,
+    }
+}
+
+#[tokio::test]
+async fn test_endpoint_group_crud() {
+    let (client, _jh) = start_svix_server().await;
+
+    let app_id = create_test_app(&client, "app1").await.unwrap().id;
+
+    let group: EndpointGroupOut = client
+        .post(
+            &format!("api/v1/app/{app_id}/endpoint-group/"),
+            EndpointGroupIn {
+                rate_limit: Some(10),
+                retry_schedule_override: Some(RetrySchedule(vec![1, 2])),
+                ..endpoint_group_in("group")
+            },
+            StatusCode::CREATED,
+        )
+        .await
+        .unwrap();
+    assert_eq!(group.name, "group");
+    assert_eq!(group.rate_limit, Some(10));
+    assert_eq!(
+        group.retry_schedule_override,
+        Some(RetrySchedule(vec![1, 2]))
+    );
+
+    let url = format!("api/v1/app/{app_id}/endpoint-group/{}/", group.id);
+
+    let fetched: EndpointGroupOut = client.get(&url, StatusCode::OK).await.unwrap();
+    assert_eq!(fetched, group);
+
+    let list: ListResponse<EndpointGroupOut> = client
+        .get(
+            &format!("api/v1/app/{app_id}/endpoint-group/"),
+            StatusCode::OK,
+        )
+        .await
+        .unwrap();
+    assert_eq!(list.data, vec![group.clone()]);
+
+    let updated: EndpointGroupOut = client
+        .put(&url, endpoint_group_in("renamed"), StatusCode::OK)
+        .await
+        .unwrap();
+    assert_eq!(updated.id, group.id);
+    assert_eq!(updated.name, "renamed");
+    assert_eq!(updated.rate_limit, None);
+    assert_eq!(updated.retry_schedule_override, None);
+
+    client.delete(&url, StatusCode::NO_CONTENT).await.unwrap();
+    let _: IgnoredAny = client.get(&url, StatusCode::NOT_FOUND).await.unwrap();
+
+    // Groups can't be accessed through other applications
+    let other_app_id = create_test_app(&client, "app2").await.unwrap().id;
+    let group: EndpointGroupOut = client
+        .post(
+            &format!("api/v1/app/{app_id}/endpoint-group/"),
+            endpoint_group_in("group"),
+            StatusCode::CREATED,
+        )
+        .await
+        .unwrap();
+    let _: IgnoredAny = client
+        .get(
+            &format!("api/v1/app/{other_app_id}/endpoint-group/{}/", group.id),
+            StatusCode::NOT_FOUND,
+        )
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn test_endpoint_group_inheritance() {
+    let (client, _jh) = start_svix_server().await;
+
+    let app_id = create_test_app(&client, "app1").await.unwrap().id;
+
+    let group: EndpointGroupOut = client
+        .post(
+            &format!("api/v1/app/{app_id}/endpoint-group/"),
+            EndpointGroupIn {
+                default_headers: Some(EndpointHeaders(
+                    [
+                        ("x-group".to_owned(), "group".to_owned()),
+                        ("x-shared".to_owned(), "group".to_owned()),
+                    ]
+                    .into(),
+                )),
+                ..endpoint_group_in("group")
+            },
+            StatusCode::CREATED,
+        )
+        .await
+        .unwrap();
+
+    let mut receiver = TestReceiver::start(StatusCode::OK);
+
+    let endp = post_endpoint(
+        &client,
+        &app_id,
+        EndpointIn {
+            url: Url::parse(&receiver.endpoint).unwrap(),
+            group_id: Some(group.id.clone()),
+            ..default_test_endpoint()
+        },
+    )
+    .await
+    .unwrap();
+    assert_eq!(endp.ep.group_id, Some(group.id.clone()));
+
+    // The endpoint's own headers take precedence over the group's
+    client
+        .put_without_response(
+            &format!("api/v1/app/{app_id}/endpoint/{}/headers/", endp.id),
+            serde_json::json!({ "headers": { "x-shared": "endpoint" } }),
+            StatusCode::NO_CONTENT,
+        )
+        .await
+        .unwrap();
+
+    create_test_message(&client, &app_id, serde_json::json!({"test": "data1"}))
+        .await
+        .unwrap();
+
+    let last_headers = receiver.header_recv.recv().await.unwrap();
+    assert_eq!(last_headers.get("x-group").unwrap(), "group");
+    assert_eq!(last_headers.get("x-shared").unwrap(), "endpoint");
+
+    // Deleting the group keeps its endpoints
+    client
+        .delete(
+            &format!("api/v1/app/{app_id}/endpoint-group/{}/", group.id),
+            StatusCode::NO_CONTENT,
+        )
+        .await
+        .unwrap();
+    let endp: EndpointOut = client
+        .get(
+            &format!("api/v1/app/{app_id}/endpoint/{}/", endp.id),
+            StatusCode::OK,
+        )
+        .await
+        .unwrap();
+    assert_eq!(endp.ep.group_id, None);
+}
+
+#[tokio::test]
+async fn test_endpoint_with_unknown_group() {
+    let (client, _jh) = start_svix_server().await;
+
+    let app_id = create_test_app(&client, "app1").await.unwrap().id;
+    let other_app_id = create_test_app(&client, "app2").await.unwrap().id;
+
+    let other_group: EndpointGroupOut = client
+        .post(
+            &format!("api/v1/app/{other_app_id}/endpoint-group/"),
+            endpoint_group_in("group"),
+            StatusCode::CREATED,
+        )
+        .await
+        .unwrap();
+
+    for group_id in [EndpointGroupId::new(None, None), other_group.id] {
+        let _: IgnoredAny = client
+            .post(
+                &format!("api/v1/app/{app_id}/endpoint/"),
+                EndpointIn {
+                    group_id: Some(group_id),
+                    ..default_test_endpoint()
+                },
+                StatusCode::UNPROCESSABLE_ENTITY,
+            )
+            .await
+            .unwrap();
+    }
+}
@@ -0,0 +1,56 @@
+// SPDX-FileCopyrightText: © 2024 Svix Authors
+// SPDX-License-Identifier: MIT
+
+use reqwest::StatusCode;
+use serde::de::IgnoredAny;
+use svix_server::{
+    cfg::CacheType,
+    core::security::management_org_id,
+    v1::endpoints::{application::ApplicationOut, internal::StoreStatsOut},
+};
+
+use crate::utils::{
+    common_calls::{app_portal_access, application_in},
+    get_default_test_config, start_svix_server, start_svix_server_with_cfg_and_org_id,
+};
+
+#[tokio::test]
+async fn test_store_stats() {
+    let mut cfg = get_default_test_config();
+    cfg.cache_type = CacheType::Memory;
+    let (client, _jh) = start_svix_server_with_cfg_and_org_id(&cfg, management_org_id()).await;
+
+    let stats: StoreStatsOut = client
+        .get("api/v1/internal/store-stats/", StatusCode::OK)
+        .await
+        .unwrap();
+    assert_eq!(stats.backend, "memory");
+    assert!(stats.key_count.is_some());
+    assert!(stats.memory_bytes.is_some());
+}
+
+#[tokio::test]
+async fn test_store_stats_requires_management_token() {
+    let (client, _jh) = start_svix_server().await;
+
+    let _: IgnoredAny = client
+        .get("api/v1/internal/store-stats/", StatusCode::FORBIDDEN)
+        .await
+        .unwrap();
+
+    let app_id = client
+        .post::<_, ApplicationOut>(
+            "api/v1/app/",
+            application_in("TEST_APP_NAME"),
+            StatusCode::CREATED,
+        )
+        .await
+        .unwrap()
+        .id;
+    let app_client = app_portal_access(&client, &app_id, Default::default()).await;
+
+    let _: IgnoredAny = app_client
+        .get("api/v1/internal/store-stats/", StatusCode::FORBIDDEN)
+        .await
+        .unwrap();
+}
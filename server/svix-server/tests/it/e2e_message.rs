@@ -1,24 +1,41 @@
 // SPDX-FileCopyrightText: © 2022 Svix Authors
 // SPDX-License-Identifier: MIT
 
+use std::collections::{HashMap, HashSet};
+
 use chrono::{Duration, Utc};
 use reqwest::StatusCode;
-use sea_orm::{sea_query::Expr, ColumnTrait, EntityTrait, QueryFilter};
+use sea_orm::{
+    sea_query::Expr, ActiveModelBehavior, ActiveModelTrait, ColumnTrait, EntityTrait, QueryFilter,
+    Set,
+};
+use serde::de::IgnoredAny;
 use svix_server::{
-    db::models::messagecontent,
-    expired_message_cleaner,
+    core::types::{
+        ApplicationId, BaseId, EventTypeName, MessageAttemptId, MessageAttemptTriggerType,
+        MessageEndpointId, MessageId, MessageSignedMetadata, MessageStatus, MessageTag,
+        MessageTagSet, OrganizationId, PayloadVersion,
+    },
+    db::models::{message, messageattempt, messagecontent, messagedestination},
+    expired_message_cleaner, message_retention,
     v1::{
         endpoints::{
             attempt::MessageAttemptOut,
-            message::{MessageOut, RawPayload},
+            message::{
+                MessageDeliveryStatus, MessageIn, MessageOut, MessageStatusBatchOut, RawPayload,
+            },
         },
         utils::ListResponse,
     },
 };
 
 use crate::utils::{
-    common_calls::{create_test_app, create_test_endpoint, create_test_msg_with, message_in},
-    run_with_retries, start_svix_server, TestReceiver,
+    common_calls::{
+        create_test_app, create_test_endpoint, create_test_msg_with, endpoint_in, message_in,
+        post_endpoint,
+    },
+    get_default_test_config, run_with_retries, start_svix_server, start_svix_server_with_cfg,
+    TestClient, TestReceiver,
 };
 
 #[tokio::test]
@@ -102,6 +119,104 @@ async fn test_message_create_read_list() {
     assert!(list.data.contains(&message_3));
 }
 
+#[tokio::test]
+async fn test_message_tags() {
+    let (client, _jh) = start_svix_server().await;
+
+    let app_id = create_test_app(&client, "v1MessageTagsTestApp")
+        .await
+        .unwrap()
+        .id;
+
+    let mut msg_in = message_in(&app_id, serde_json::json!({"test": "value"})).unwrap();
+    msg_in.tags = Some(MessageTagSet(HashSet::from([
+        MessageTag("foo".to_owned()),
+        MessageTag("bar".to_owned()),
+    ])));
+    let message_1: MessageOut = client
+        .post(
+            &format!("api/v1/app/{}/msg/", &app_id),
+            msg_in,
+            StatusCode::ACCEPTED,
+        )
+        .await
+        .unwrap();
+    assert_eq!(message_1.tags.as_ref().unwrap().0.len(), 2);
+
+    let mut msg_in = message_in(&app_id, serde_json::json!({"test": "value2"})).unwrap();
+    msg_in.tags = Some(MessageTagSet(HashSet::from([MessageTag("foo".to_owned())])));
+    let message_2: MessageOut = client
+        .post(
+            &format!("api/v1/app/{}/msg/", &app_id),
+            msg_in,
+            StatusCode::ACCEPTED,
+        )
+        .await
+        .unwrap();
+
+    let message_3: MessageOut = client
+        .post(
+            &format!("api/v1/app/{}/msg/", &app_id),
+            message_in(&app_id, serde_json::json!({"test": "value3"})).unwrap(),
+            StatusCode::ACCEPTED,
+        )
+        .await
+        .unwrap();
+    assert_eq!(message_3.tags, None);
+
+    let list: ListResponse<MessageOut> = client
+        .get(
+            &format!("api/v1/app/{}/msg/?tag=foo", &app_id),
+            StatusCode::OK,
+        )
+        .await
+        .unwrap();
+    assert_eq!(list.data.len(), 2);
+    assert!(list.data.contains(&message_1));
+    assert!(list.data.contains(&message_2));
+
+    // All given tags must match
+    let list: ListResponse<MessageOut> = client
+        .get(
+            &format!("api/v1/app/{}/msg/?tag=foo&tag=bar", &app_id),
+            StatusCode::OK,
+        )
+        .await
+        .unwrap();
+    assert_eq!(list.data.len(), 1);
+    assert!(list.data.contains(&message_1));
+
+    let list: ListResponse<MessageOut> = client
+        .get(
+            &format!("api/v1/app/{}/msg/?tag=baz", &app_id),
+            StatusCode::OK,
+        )
+        .await
+        .unwrap();
+    assert!(list.data.is_empty());
+
+    let _: IgnoredAny = client
+        .get(
+            &format!("api/v1/app/{}/msg/?tag=invalid_tag", &app_id),
+            StatusCode::UNPROCESSABLE_ENTITY,
+        )
+        .await
+        .unwrap();
+
+    let mut msg_in = message_in(&app_id, serde_json::json!({"test": "value4"})).unwrap();
+    msg_in.tags = Some(MessageTagSet(HashSet::from([MessageTag(
+        "not allowed".to_owned(),
+    )])));
+    let _: IgnoredAny = client
+        .post(
+            &format!("api/v1/app/{}/msg/", &app_id),
+            msg_in,
+            StatusCode::UNPROCESSABLE_ENTITY,
+        )
+        .await
+        .unwrap();
+}
+
 #[tokio::test]
 async fn test_message_create_read_list_with_content() {
     let (client, _jh) = start_svix_server().await;
@@ -246,6 +361,60 @@ async fn test_failed_message_gets_recorded() {
     .unwrap();
 }
 
+/// Deliveries work with read replicas configured, here just the primary again, and attempts are
+/// recorded through the primary.
+#[tokio::test]
+async fn test_message_with_read_replicas() {
+    let mut cfg = get_default_test_config();
+    cfg.db_read_replica_dsns = Some(vec![cfg.db_dsn.clone(), cfg.db_dsn.clone()]);
+
+    let (client, _jh) = start_svix_server_with_cfg(&cfg).await;
+
+    let app_id = create_test_app(&client, "app").await.unwrap().id;
+    let mut receiver = TestReceiver::start(axum::http::StatusCode::OK);
+    create_test_endpoint(&client, &app_id, &receiver.endpoint)
+        .await
+        .unwrap();
+
+    for i in 0..3 {
+        let msg_res: MessageOut = client
+            .post(
+                &format!("api/v1/app/{app_id}/msg/"),
+                message_in(&app_id, serde_json::json!({ "test": i })).unwrap(),
+                StatusCode::ACCEPTED,
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(
+            receiver.data_recv.recv().await.unwrap(),
+            serde_json::json!({ "test": i })
+        );
+
+        run_with_retries(|| async {
+            let attempts: ListResponse<MessageAttemptOut> = client
+                .get(
+                    &format!("api/v1/app/{app_id}/attempt/msg/{}/", msg_res.id),
+                    StatusCode::OK,
+                )
+                .await
+                .unwrap();
+
+            if !attempts
+                .data
+                .iter()
+                .any(|x| x.status == MessageStatus::Success)
+            {
+                anyhow::bail!("could not find successful attempt");
+            }
+
+            Ok(())
+        })
+        .await
+        .unwrap();
+    }
+}
+
 #[tokio::test]
 async fn test_multiple_endpoints() {
     let (client, _jh) = start_svix_server().await;
@@ -421,6 +590,155 @@ async fn test_payload_retention_period() {
     assert!(content.is_none());
 }
 
+#[tokio::test]
+async fn test_message_retention() {
+    let cfg = get_default_test_config();
+    let (client, _jh) = start_svix_server_with_cfg(&cfg).await;
+    let pool = svix_server::db::init_db(&cfg).await;
+
+    let app_id = create_test_app(&client, "v1MessageRetentionTestApp")
+        .await
+        .unwrap()
+        .id;
+    let endp_id = create_test_endpoint(&client, &app_id, "https://example.com/")
+        .await
+        .unwrap()
+        .id;
+
+    // Messages from 100 and 80 days ago, each with a destination and an attempt
+    let now = Utc::now();
+    let mut msg_ids = Vec::new();
+    for age in [Duration::days(100), Duration::days(80)] {
+        let created_at = now - age;
+        let msg = message::ActiveModel {
+            id: Set(MessageId::new(created_at.into(), None)),
+            created_at: Set(created_at.into()),
+            app_id: Set(app_id.clone()),
+            org_id: Set(OrganizationId::new(None, None)),
+            event_type: Set(EventTypeName("test.retention".to_owned())),
+            expiration: Set(created_at.into()),
+            ..message::ActiveModel::new()
+        }
+        .insert(&pool)
+        .await
+        .unwrap();
+        let msg_dest = messagedestination::ActiveModel {
+            id: Set(MessageEndpointId::new(created_at.into(), None)),
+            created_at: Set(created_at.into()),
+            updated_at: Set(created_at.into()),
+            msg_id: Set(msg.id.clone()),
+            endp_id: Set(endp_id.clone()),
+            status: Set(MessageStatus::Success),
+            ..ActiveModelTrait::default()
+        }
+        .insert(&pool)
+        .await
+        .unwrap();
+        messageattempt::ActiveModel {
+            id: Set(MessageAttemptId::new(created_at.into(), None)),
+            created_at: Set(created_at.into()),
+            msg_id: Set(msg.id.clone()),
+            msg_dest_id: Set(msg_dest.id),
+            endp_id: Set(endp_id.clone()),
+            url: Set("https://example.com/".to_owned()),
+            status: Set(MessageStatus::Success),
+            response_status_code: Set(200),
+            response: Set(String::new()),
+            trigger_type: Set(MessageAttemptTriggerType::Scheduled),
+            ..ActiveModelTrait::default()
+        }
+        .insert(&pool)
+        .await
+        .unwrap();
+        msg_ids.push(msg.id);
+    }
+
+    // There may be old messages left over by other test runs too
+    while message_retention::delete_messages_before(&pool, now - Duration::days(90), 1000)
+        .await
+        .unwrap()
+        > 0
+    {}
+
+    let counts = |msg_id: MessageId| {
+        let pool = &pool;
+        async move {
+            let messages = message::Entity::find_by_id(msg_id.clone())
+                .all(pool)
+                .await
+                .unwrap()
+                .len();
+            let destinations = messagedestination::Entity::find()
+                .filter(messagedestination::Column::MsgId.eq(msg_id.clone()))
+                .all(pool)
+                .await
+                .unwrap()
+                .len();
+            let attempts = messageattempt::Entity::find()
+                .filter(messageattempt::Column::MsgId.eq(msg_id))
+                .all(pool)
+                .await
+                .unwrap()
+                .len();
+            (messages, destinations, attempts)
+        }
+    };
+
+    // Only the message older than the retention period is gone, with everything belonging to it
+    assert_eq!(counts(msg_ids[0].clone()).await, (0, 0, 0));
+    assert_eq!(counts(msg_ids[1].clone()).await, (1, 1, 1));
+}
+
+#[tokio::test]
+async fn test_payload_compression() {
+    let mut cfg = get_default_test_config();
+    cfg.compress_payload_threshold_bytes = Some(64);
+    let (client, _jh) = start_svix_server_with_cfg(&cfg).await;
+    let pool = svix_server::db::init_db(&cfg).await;
+
+    let app_id = create_test_app(&client, "v1MessageCompressionTestApp")
+        .await
+        .unwrap()
+        .id;
+    let mut receiver = TestReceiver::start(axum::http::StatusCode::OK);
+    create_test_endpoint(&client, &app_id, &receiver.endpoint)
+        .await
+        .unwrap();
+
+    let small = serde_json::json!({"test": "value"});
+    let large = serde_json::json!({"test": "value".repeat(100)});
+    for (payload, compressed) in [(small, false), (large, true)] {
+        let msg: MessageOut = client
+            .post(
+                &format!("api/v1/app/{app_id}/msg/"),
+                message_in(&app_id, payload.clone()).unwrap(),
+                StatusCode::ACCEPTED,
+            )
+            .await
+            .unwrap();
+        assert_eq!(msg.payload.0.get(), payload.to_string());
+
+        let content = messagecontent::Entity::find_by_id(msg.id.clone())
+            .one(&pool)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(content.payload.is_none(), compressed);
+        assert_eq!(content.payload_compressed.is_some(), compressed);
+
+        // Both sent and read back decompressed
+        assert_eq!(receiver.data_recv.recv().await.unwrap(), payload);
+        let msg: MessageOut = client
+            .get(
+                &format!("api/v1/app/{app_id}/msg/{}/", msg.id),
+                StatusCode::OK,
+            )
+            .await
+            .unwrap();
+        assert_eq!(msg.payload.0.get(), payload.to_string());
+    }
+}
+
 #[tokio::test]
 async fn test_expunge_message_payload() {
     let (client, _jh) = start_svix_server().await;
@@ -472,3 +790,550 @@ async fn test_expunge_message_payload() {
 
     assert_eq!(msg.payload.0.get(), r#"{"expired":true}"#);
 }
+
+#[tokio::test]
+async fn test_response_webhook_chaining() {
+    let mut cfg = get_default_test_config();
+    cfg.max_webhook_chain_depth = 1;
+    let (client, _jh) = start_svix_server_with_cfg(&cfg).await;
+
+    let app_id = create_test_app(&client, "v1MessageChainingTestApp")
+        .await
+        .unwrap()
+        .id;
+
+    // Responds to every message it gets, so without a maximum chain depth it would loop forever
+    let mut chaining_receiver = TestReceiver::start_with_body(
+        axum::http::StatusCode::OK,
+        axum::Json(serde_json::json!({"chained": true})),
+    );
+    let mut receiver = TestReceiver::start(axum::http::StatusCode::OK);
+
+    let chaining_endp = post_endpoint(
+        &client,
+        &app_id,
+        svix_server::v1::endpoints::endpoint::EndpointIn {
+            response_webhook_event_type: Some(EventTypeName("response.received".to_owned())),
+            ..endpoint_in(&chaining_receiver.endpoint)
+        },
+    )
+    .await
+    .unwrap();
+    assert_eq!(
+        chaining_endp.ep.response_webhook_event_type,
+        Some(EventTypeName("response.received".to_owned()))
+    );
+    create_test_endpoint(&client, &app_id, &receiver.endpoint)
+        .await
+        .unwrap();
+
+    let original: MessageOut = client
+        .post(
+            &format!("api/v1/app/{app_id}/msg/"),
+            message_in("user.signup", serde_json::json!({"test": "value"})).unwrap(),
+            StatusCode::ACCEPTED,
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(
+        receiver.data_recv.recv().await.unwrap(),
+        serde_json::json!({"test": "value"})
+    );
+    assert_eq!(
+        receiver.data_recv.recv().await.unwrap(),
+        serde_json::json!({"chained": true})
+    );
+    // Both messages reach the chaining endpoint too
+    chaining_receiver.data_recv.recv().await.unwrap();
+    chaining_receiver.data_recv.recv().await.unwrap();
+
+    let list = run_with_retries(|| async {
+        let list: ListResponse<MessageOut> = client
+            .get(&format!("api/v1/app/{app_id}/msg/"), StatusCode::OK)
+            .await
+            .unwrap();
+        if list.data.len() != 2 {
+            anyhow::bail!("list len {}, not 2", list.data.len());
+        }
+        Ok(list)
+    })
+    .await
+    .unwrap();
+
+    let chained = list.data.iter().find(|m| m.id != original.id).unwrap();
+    assert_eq!(
+        chained.event_type,
+        EventTypeName("response.received".to_owned())
+    );
+
+    // The response to the chained message isn't sent on, as the maximum depth was reached
+    tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+    assert!(receiver.data_recv.try_recv().is_err());
+    let list: ListResponse<MessageOut> = client
+        .get(&format!("api/v1/app/{app_id}/msg/"), StatusCode::OK)
+        .await
+        .unwrap();
+    assert_eq!(list.data.len(), 2);
+}
+
+/// Lists the application's messages that have the given delivery status for any endpoint
+async fn list_messages_with_status(
+    client: &TestClient,
+    app_id: &ApplicationId,
+    status: MessageStatus,
+) -> ListResponse<MessageOut> {
+    client
+        .get(
+            &format!("api/v1/app/{app_id}/msg/?status={}", status as i16),
+            StatusCode::OK,
+        )
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn test_message_deadline() {
+    let (client, _jh) = start_svix_server().await;
+
+    let app_id = create_test_app(&client, "v1MessageDeadlineTestApp")
+        .await
+        .unwrap()
+        .id;
+
+    let mut receiver = TestReceiver::start(axum::http::StatusCode::OK);
+    create_test_endpoint(&client, &app_id, &receiver.endpoint)
+        .await
+        .unwrap();
+
+    // A message whose deadline has already passed is never delivered
+    let deadline = Utc::now() - Duration::seconds(1);
+    let expired: MessageOut = client
+        .post(
+            &format!("api/v1/app/{app_id}/msg/"),
+            MessageIn {
+                deadline: Some(deadline),
+                ..message_in("sale.ending", serde_json::json!({"expired": "value"})).unwrap()
+            },
+            StatusCode::ACCEPTED,
+        )
+        .await
+        .unwrap();
+    assert_eq!(
+        expired.deadline.map(|d| d.timestamp_millis()),
+        Some(deadline.timestamp_millis())
+    );
+
+    // While one whose deadline is yet to come is
+    client
+        .post::<_, IgnoredAny>(
+            &format!("api/v1/app/{app_id}/msg/"),
+            MessageIn {
+                deadline: Some(Utc::now() + Duration::minutes(5)),
+                ..message_in("sale.ending", serde_json::json!({"test": "value"})).unwrap()
+            },
+            StatusCode::ACCEPTED,
+        )
+        .await
+        .unwrap();
+    assert_eq!(
+        receiver.data_recv.recv().await.unwrap(),
+        serde_json::json!({"test": "value"})
+    );
+
+    run_with_retries(|| async {
+        let list = list_messages_with_status(&client, &app_id, MessageStatus::Expired).await;
+        if list.data.len() != 1 {
+            anyhow::bail!("list len {}, not 1", list.data.len());
+        }
+        assert_eq!(list.data[0].id, expired.id);
+        Ok(())
+    })
+    .await
+    .unwrap();
+
+    let attempts: ListResponse<MessageAttemptOut> = client
+        .get(
+            &format!("api/v1/app/{app_id}/attempt/msg/{}/", expired.id),
+            StatusCode::OK,
+        )
+        .await
+        .unwrap();
+    assert!(attempts.data.is_empty());
+    assert!(receiver.data_recv.try_recv().is_err());
+}
+
+#[tokio::test]
+async fn test_message_deadline_stops_retries() {
+    let mut cfg = get_default_test_config();
+    cfg.retry_schedule = vec![std::time::Duration::from_secs(2)];
+    let (client, _jh) = start_svix_server_with_cfg(&cfg).await;
+
+    let app_id = create_test_app(&client, "v1MessageDeadlineRetryTestApp")
+        .await
+        .unwrap()
+        .id;
+
+    let mut receiver = TestReceiver::start(axum::http::StatusCode::INTERNAL_SERVER_ERROR);
+    create_test_endpoint(&client, &app_id, &receiver.endpoint)
+        .await
+        .unwrap();
+
+    let msg: MessageOut = client
+        .post(
+            &format!("api/v1/app/{app_id}/msg/"),
+            MessageIn {
+                deadline: Some(Utc::now() + Duration::seconds(1)),
+                ..message_in("sale.ending", serde_json::json!({"test": "value"})).unwrap()
+            },
+            StatusCode::ACCEPTED,
+        )
+        .await
+        .unwrap();
+
+    // The first attempt is made before the deadline, but the retry would be after it
+    receiver.data_recv.recv().await.unwrap();
+    tokio::time::sleep(std::time::Duration::from_secs(2)).await;
+
+    run_with_retries(|| async {
+        let list = list_messages_with_status(&client, &app_id, MessageStatus::Expired).await;
+        if list.data.len() != 1 {
+            anyhow::bail!("list len {}, not 1", list.data.len());
+        }
+        assert_eq!(list.data[0].id, msg.id);
+        Ok(())
+    })
+    .await
+    .unwrap();
+
+    assert!(receiver.data_recv.try_recv().is_err());
+    assert!(
+        list_messages_with_status(&client, &app_id, MessageStatus::Fail)
+            .await
+            .data
+            .is_empty()
+    );
+}
+
+#[tokio::test]
+async fn test_message_idempotency_key() {
+    let mut cfg = get_default_test_config();
+    cfg.message_idempotency_key_ttl = std::time::Duration::from_secs(1);
+    let (client, _jh) = start_svix_server_with_cfg(&cfg).await;
+
+    let app_id = create_test_app(&client, "v1MessageIdempotencyKeyTestApp")
+        .await
+        .unwrap()
+        .id;
+
+    let create = |key: &'static str, status: StatusCode| {
+        let client = &client;
+        let app_id = &app_id;
+        async move {
+            client
+                .post_with_header::<_, MessageOut>(
+                    &format!("api/v1/app/{app_id}/msg/"),
+                    ("x-svix-idempotency-key", key),
+                    message_in("sale.ending", serde_json::json!({"test": "value"})).unwrap(),
+                    status,
+                )
+                .await
+                .unwrap()
+        }
+    };
+
+    let first = create("order-1", StatusCode::ACCEPTED).await;
+
+    // Creating the message again returns the original one instead
+    let duplicate = create("order-1", StatusCode::OK).await;
+    assert_eq!(duplicate.id, first.id);
+    assert_eq!(duplicate.payload, first.payload);
+
+    // Different keys are different messages
+    let other = create("order-2", StatusCode::ACCEPTED).await;
+    assert_ne!(other.id, first.id);
+
+    // Once the key has expired a new message is created
+    tokio::time::sleep(std::time::Duration::from_millis(1500)).await;
+    let after_expiry = create("order-1", StatusCode::ACCEPTED).await;
+    assert_ne!(after_expiry.id, first.id);
+
+    // Of concurrent requests with the same key only one creates the message, with the others
+    // either replaying it or being told that it's still being created
+    let concurrent = futures::future::join_all((0..5).map(|_| {
+        client.post_with_header::<_, IgnoredAny>(
+            &format!("api/v1/app/{app_id}/msg/"),
+            ("x-svix-idempotency-key", "order-3"),
+            message_in("sale.ending", serde_json::json!({"test": "value"})).unwrap(),
+            StatusCode::ACCEPTED,
+        )
+    }))
+    .await;
+    assert_eq!(concurrent.iter().filter(|res| res.is_ok()).count(), 1);
+
+    let list: ListResponse<MessageOut> = client
+        .get(&format!("api/v1/app/{app_id}/msg/"), StatusCode::OK)
+        .await
+        .unwrap();
+    assert_eq!(list.data.len(), 4);
+}
+
+#[tokio::test]
+async fn test_message_signed_metadata() {
+    let (client, _jh) = start_svix_server().await;
+
+    let app_id = create_test_app(&client, "app").await.unwrap().id;
+    let mut receiver = TestReceiver::start(StatusCode::OK);
+    create_test_endpoint(&client, &app_id, &receiver.endpoint)
+        .await
+        .unwrap();
+
+    let signed_metadata = MessageSignedMetadata(HashMap::from([
+        ("tenant_id".to_owned(), "tenant_1".to_owned()),
+        ("order_id".to_owned(), "ord_1".to_owned()),
+    ]));
+    let msg: MessageOut = client
+        .post(
+            &format!("api/v1/app/{app_id}/msg/"),
+            MessageIn {
+                signed_metadata: Some(signed_metadata.clone()),
+                ..message_in("event.type", serde_json::json!({"test": "value"})).unwrap()
+            },
+            StatusCode::ACCEPTED,
+        )
+        .await
+        .unwrap();
+    assert_eq!(msg.signed_metadata, Some(signed_metadata));
+
+    // Endpoints are sent the metadata exactly as it was signed
+    let headers = receiver.header_recv.recv().await.unwrap();
+    assert_eq!(
+        headers["svix-signed-metadata"],
+        r#"{"order_id":"ord_1","tenant_id":"tenant_1"}"#
+    );
+
+    // Values have to be valid header values
+    let _: IgnoredAny = client
+        .post(
+            &format!("api/v1/app/{app_id}/msg/"),
+            MessageIn {
+                signed_metadata: Some(MessageSignedMetadata(HashMap::from([(
+                    "tenant_id".to_owned(),
+                    "tenant\n1".to_owned(),
+                )]))),
+                ..message_in("event.type", serde_json::json!({"test": "value"})).unwrap()
+            },
+            StatusCode::UNPROCESSABLE_ENTITY,
+        )
+        .await
+        .unwrap();
+
+    receiver.jh.abort();
+}
+
+#[tokio::test]
+async fn test_message_payload_version() {
+    let (client, _jh) = start_svix_server().await;
+
+    let app_id = create_test_app(&client, "app").await.unwrap().id;
+    let mut receiver = TestReceiver::start(StatusCode::OK);
+    let old_receiver = TestReceiver::start(StatusCode::OK);
+    let endp_id = create_test_endpoint(&client, &app_id, &receiver.endpoint)
+        .await
+        .unwrap()
+        .id;
+
+    let version = |v: &str| PayloadVersion(v.parse().unwrap());
+    let min_version_endp = post_endpoint(
+        &client,
+        &app_id,
+        svix_server::v1::endpoints::endpoint::EndpointIn {
+            min_payload_version: Some(version("2.0.0")),
+            ..endpoint_in(&old_receiver.endpoint)
+        },
+    )
+    .await
+    .unwrap();
+    assert_eq!(
+        min_version_endp.ep.min_payload_version,
+        Some(version("2.0.0"))
+    );
+
+    let msg: MessageOut = client
+        .post(
+            &format!("api/v1/app/{app_id}/msg/"),
+            MessageIn {
+                payload_version: Some(version("1.2.3")),
+                ..message_in("event.type", serde_json::json!({"test": "value"})).unwrap()
+            },
+            StatusCode::ACCEPTED,
+        )
+        .await
+        .unwrap();
+    assert_eq!(msg.payload_version, Some(version("1.2.3")));
+
+    let headers = receiver.header_recv.recv().await.unwrap();
+    assert_eq!(headers["svix-payload-version"], "1.2.3");
+
+    // The endpoint requiring a newer version isn't sent the message, which is recorded as such
+    let destinations = run_with_retries(|| async {
+        let destinations: ListResponse<serde_json::Value> = client
+            .get(
+                &format!("api/v1/app/{app_id}/msg/{}/endpoint/", msg.id),
+                StatusCode::OK,
+            )
+            .await
+            .unwrap();
+        if destinations.data.len() != 2
+            || destinations
+                .data
+                .iter()
+                .any(|d| d["status"] == MessageStatus::Sending as i16)
+        {
+            anyhow::bail!("message not dispatched to both endpoints yet");
+        }
+        Ok(destinations)
+    })
+    .await
+    .unwrap();
+    for destination in destinations.data {
+        let expected = if destination["id"] == endp_id.0 {
+            MessageStatus::Success
+        } else {
+            assert_eq!(destination["id"], min_version_endp.id.0);
+            MessageStatus::Incompatible
+        };
+        assert_eq!(destination["status"], expected as i16);
+    }
+
+    let attempts: ListResponse<MessageAttemptOut> = client
+        .get(
+            &format!("api/v1/app/{app_id}/attempt/msg/{}/", msg.id),
+            StatusCode::OK,
+        )
+        .await
+        .unwrap();
+    assert_eq!(attempts.data.len(), 1);
+
+    // Invalid versions are rejected
+    let _: IgnoredAny = client
+        .post(
+            &format!("api/v1/app/{app_id}/msg/"),
+            serde_json::json!({
+                "eventType": "event.type",
+                "payload": {},
+                "payloadVersion": "1.2",
+            }),
+            StatusCode::UNPROCESSABLE_ENTITY,
+        )
+        .await
+        .unwrap();
+
+    receiver.jh.abort();
+    old_receiver.jh.abort();
+}
+
+#[tokio::test]
+async fn test_message_status_batch() {
+    let (client, _jh) = start_svix_server().await;
+
+    let app_id = create_test_app(&client, "app").await.unwrap().id;
+    let other_app_id = create_test_app(&client, "other app").await.unwrap().id;
+    let mut receiver = TestReceiver::start(StatusCode::OK);
+    create_test_endpoint(&client, &app_id, &receiver.endpoint)
+        .await
+        .unwrap();
+
+    let mut msg_ids = Vec::new();
+    for _ in 0..3 {
+        let msg = create_test_msg_with(
+            &client,
+            &app_id,
+            serde_json::json!({"test": "value"}),
+            "event.type",
+            [],
+        )
+        .await;
+        receiver.data_recv.recv().await.unwrap();
+        msg_ids.push(msg.id);
+    }
+    let other_msg_id = create_test_msg_with(
+        &client,
+        &other_app_id,
+        serde_json::json!({"test": "value"}),
+        "event.type",
+        [],
+    )
+    .await
+    .id;
+
+    let status_batch = |ids: serde_json::Value, expected: StatusCode| {
+        let client = &client;
+        let app_id = &app_id;
+        async move {
+            client
+                .post::<_, MessageStatusBatchOut>(
+                    &format!("api/v1/app/{app_id}/msg/status-batch/"),
+                    serde_json::json!({ "ids": ids }),
+                    expected,
+                )
+                .await
+        }
+    };
+
+    // All found, in the order they're asked for
+    let ids: Vec<_> = msg_ids.iter().rev().cloned().collect();
+    run_with_retries(|| async {
+        let out = status_batch(serde_json::json!(ids), StatusCode::OK).await?;
+        let results: Vec<_> = out.results.iter().map(|r| (&r.id, r.status)).collect();
+        let expected: Vec<_> = ids
+            .iter()
+            .map(|id| (id, MessageDeliveryStatus::Success))
+            .collect();
+        if results != expected {
+            anyhow::bail!("unexpected results: {results:?}");
+        }
+        Ok(())
+    })
+    .await
+    .unwrap();
+
+    // Unknown IDs, and those of other applications' messages, are left out
+    let out = status_batch(
+        serde_json::json!([
+            MessageId::new(None, None),
+            msg_ids[1],
+            other_msg_id,
+            msg_ids[0],
+        ]),
+        StatusCode::OK,
+    )
+    .await
+    .unwrap();
+    let results: Vec<_> = out.results.iter().map(|r| &r.id).collect();
+    assert_eq!(results, [&msg_ids[1], &msg_ids[0]]);
+
+    // Nothing to look up
+    let out = status_batch(serde_json::json!([]), StatusCode::OK)
+        .await
+        .unwrap();
+    assert!(out.results.is_empty());
+
+    // At most 50 IDs at once
+    let ids: Vec<_> = (0..50).map(|_| MessageId::new(None, None)).collect();
+    let out = status_batch(serde_json::json!(ids), StatusCode::OK)
+        .await
+        .unwrap();
+    assert!(out.results.is_empty());
+    let ids: Vec<_> = (0..51).map(|_| MessageId::new(None, None)).collect();
+    let _: IgnoredAny = client
+        .post(
+            &format!("api/v1/app/{app_id}/msg/status-batch/"),
+            serde_json::json!({ "ids": ids }),
+            StatusCode::UNPROCESSABLE_ENTITY,
+        )
+        .await
+        .unwrap();
+
+    receiver.jh.abort();
+}
@@ -18,15 +18,21 @@ use svix_server::{
             MessageAttemptId, MessageId, MessageUid, OrganizationId,
         },
     },
-    v1::endpoints::{
-        application::{ApplicationIn, ApplicationOut},
-        endpoint::{EndpointIn, EndpointOut, EndpointSecretRotateIn},
+    v1::{
+        endpoints::{
+            application::{ApplicationIn, ApplicationOut},
+            attempt::MessageAttemptOut,
+            endpoint::{EndpointIn, EndpointOut, EndpointSecretRotateIn},
+            message::{MessageIn, MessageOut},
+        },
+        utils::ListResponse,
     },
 };
 
 use crate::utils::{
     common_calls::{
         create_test_app, create_test_endpoint, create_test_message, default_test_endpoint,
+        message_in,
     },
     get_default_test_config, TestClient, TestReceiver,
 };
@@ -43,6 +49,20 @@ pub struct EndpointDisabledEvent {
     pub fail_since: DateTime<Utc>,
 }
 
+/// Sent when an endpoint has failed to deliver `op_webhooks_failure_count_threshold` messages in a
+/// row.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[allow(dead_code)]
+pub struct EndpointFailureCountEvent {
+    pub app_id: ApplicationId,
+    pub app_uid: Option<ApplicationUid>,
+    pub endpoint_id: EndpointId,
+    pub endpoint_uid: Option<EndpointUid>,
+    pub failure_count: u32,
+    pub fail_since: DateTime<Utc>,
+}
+
 /// Sent when an endpoint is created, updated, or deleted
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -63,8 +83,8 @@ pub struct MessageAttempetLast {
 }
 
 /// Sent when a message delivery has failed (all of the retry attempts have been exhausted) as a
-/// "message.attempt.exhausted" type or after it's failed four times as a "message.attempt.failing"
-/// event.
+/// "message.attempt.exhausted" type or after it's failed `op_webhooks_failing_event_after` times as
+/// a "message.attempt.failing" event.
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "camelCase")]
 #[allow(dead_code)]
@@ -77,12 +97,43 @@ pub struct MessageAttemptEvent {
     pub last_attempt: MessageAttempetLast,
 }
 
+/// Sent when a message wasn't delivered to an endpoint because its deadline had passed.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[allow(dead_code)]
+pub struct MessageExpiredEvent {
+    pub app_id: ApplicationId,
+    pub app_uid: Option<ApplicationUid>,
+    pub msg_id: MessageId,
+    pub msg_event_id: Option<MessageUid>,
+    pub endpoint_id: EndpointId,
+    pub deadline: DateTime<Utc>,
+}
+
+/// Sent when an endpoint's circuit breaker opens, or closes again.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[allow(dead_code)]
+pub struct EndpointCircuitEvent {
+    pub app_id: ApplicationId,
+    pub app_uid: Option<ApplicationUid>,
+    pub endpoint_id: EndpointId,
+    pub open_until: Option<DateTime<Utc>>,
+    pub consecutive_failures: u32,
+}
+
 #[derive(Debug, Deserialize)]
 #[serde(tag = "type", content = "data")]
 #[allow(dead_code)]
 pub enum OperationalWebhookTest {
     #[serde(rename = "endpoint.disabled")]
     EndpointDisabled(EndpointDisabledEvent),
+    #[serde(rename = "endpoint.failure_count_threshold")]
+    FailureCountThreshold(EndpointFailureCountEvent),
+    #[serde(rename = "endpoint.circuit_open")]
+    EndpointCircuitOpen(EndpointCircuitEvent),
+    #[serde(rename = "endpoint.circuit_closed")]
+    EndpointCircuitClosed(EndpointCircuitEvent),
     #[serde(rename = "endpoint.created")]
     EndpointCreated(EndpointEvent),
     #[serde(rename = "endpoint.updated")]
@@ -93,6 +144,8 @@ pub enum OperationalWebhookTest {
     MessageAttemptExhausted(MessageAttemptEvent),
     #[serde(rename = "message.attempt.failing")]
     MessageAttemptFailing(MessageAttemptEvent),
+    #[serde(rename = "message.expired")]
+    MessageExpired(MessageExpiredEvent),
 }
 
 /// Operational webhooks are dispatched by a special organization, so this function returns two
@@ -423,6 +476,437 @@ async fn test_message_attempt_operational_webhooks() {
     }
 }
 
+/// Sends a message to an endpoint that always fails, and returns the number of the attempt that
+/// the "message.attempt.failing" operational webhook was sent for.
+async fn failing_event_attempt_number(cfg: ConfigurationInner) -> usize {
+    let (client_regular, client_op, org_id, _jh) = start_svix_server_with_operational_webhooks(cfg);
+
+    let op_webhook_app: ApplicationOut = client_op
+        .post(
+            "api/v1/app/",
+            ApplicationIn {
+                name: "TestOperationalWebhookApplication".to_owned(),
+                rate_limit: None,
+                uid: Some(ApplicationUid(org_id.to_string())),
+                metadata: Metadata::default(),
+            },
+            StatusCode::CREATED,
+        )
+        .await
+        .unwrap();
+
+    let mut receiver = TestReceiver::start(StatusCode::OK);
+
+    let _op_webhook_endp: EndpointOut = client_op
+        .post(
+            &format!("api/v1/app/{}/endpoint/", op_webhook_app.id),
+            EndpointIn {
+                description: "TestOperationalWebhookEndpoint".to_owned(),
+                url: Url::parse(&receiver.endpoint).unwrap(),
+                ..default_test_endpoint()
+            },
+            StatusCode::CREATED,
+        )
+        .await
+        .unwrap();
+
+    let regular_app = create_test_app(&client_regular, "TestOperationalWebhookApplicationRegular")
+        .await
+        .unwrap();
+    create_test_endpoint(&client_regular, &regular_app.id, "http://junk.url")
+        .await
+        .unwrap();
+
+    let regular_msg = create_test_message(
+        &client_regular,
+        &regular_app.id,
+        serde_json::json!({"test": "data"}),
+    )
+    .await
+    .unwrap();
+
+    let mut failing_attempt = None;
+    loop {
+        let op_webhook_out: OperationalWebhookTest =
+            serde_json::from_value(receiver.data_recv.recv().await.unwrap()).unwrap();
+        match op_webhook_out {
+            OperationalWebhookTest::MessageAttemptFailing(event) => {
+                failing_attempt = Some(event.last_attempt.id);
+            }
+            OperationalWebhookTest::MessageAttemptExhausted(_) => break,
+            _ => {}
+        }
+    }
+    let failing_attempt = failing_attempt.expect("no message.attempt.failing event was sent");
+
+    let attempts: ListResponse<MessageAttemptOut> = client_regular
+        .get(
+            &format!(
+                "api/v1/app/{}/attempt/msg/{}/",
+                regular_app.id, regular_msg.id
+            ),
+            StatusCode::OK,
+        )
+        .await
+        .unwrap();
+
+    // Attempts are listed newest first
+    attempts.data.len()
+        - attempts
+            .data
+            .iter()
+            .position(|attempt| attempt.id == failing_attempt)
+            .unwrap()
+}
+
+#[tokio::test]
+async fn test_message_attempt_failing_event_after() {
+    let mut cfg = get_default_test_config();
+    cfg.retry_schedule = (0..5).map(|_| Duration::from_millis(1)).collect();
+
+    // The default
+    assert_eq!(failing_event_attempt_number(cfg.clone()).await, 4);
+
+    cfg.op_webhooks_failing_event_after = 1;
+    assert_eq!(failing_event_attempt_number(cfg.clone()).await, 1);
+
+    cfg.op_webhooks_failing_event_after = 5;
+    assert_eq!(failing_event_attempt_number(cfg).await, 5);
+}
+
+#[tokio::test]
+async fn test_failure_count_threshold_operational_webhook() {
+    let mut cfg = get_default_test_config();
+    cfg.retry_schedule = vec![Duration::from_millis(1)];
+    cfg.op_webhooks_failure_count_threshold = 2;
+
+    let (client_regular, client_op, org_id, _jh) = start_svix_server_with_operational_webhooks(cfg);
+
+    let op_webhook_app: ApplicationOut = client_op
+        .post(
+            "api/v1/app/",
+            ApplicationIn {
+                name: "TestOperationalWebhookApplication".to_owned(),
+                rate_limit: None,
+                uid: Some(ApplicationUid(org_id.to_string())),
+                metadata: Metadata::default(),
+            },
+            StatusCode::CREATED,
+        )
+        .await
+        .unwrap();
+
+    let mut receiver = TestReceiver::start(StatusCode::OK);
+
+    let _op_webhook_endp: EndpointOut = client_op
+        .post(
+            &format!("api/v1/app/{}/endpoint/", op_webhook_app.id),
+            EndpointIn {
+                description: "TestOperationalWebhookEndpoint".to_owned(),
+                url: Url::parse(&receiver.endpoint).unwrap(),
+                ..default_test_endpoint()
+            },
+            StatusCode::CREATED,
+        )
+        .await
+        .unwrap();
+
+    let regular_app = create_test_app(&client_regular, "TestOperationalWebhookApplicationRegular")
+        .await
+        .unwrap();
+    let regular_endp = create_test_endpoint(&client_regular, &regular_app.id, "http://junk.url")
+        .await
+        .unwrap();
+
+    // Exhaust the retries of two messages, one after the other
+    for _ in 0..2 {
+        create_test_message(
+            &client_regular,
+            &regular_app.id,
+            serde_json::json!({"test": "data"}),
+        )
+        .await
+        .unwrap();
+
+        loop {
+            let op_webhook_out: OperationalWebhookTest =
+                serde_json::from_value(receiver.data_recv.recv().await.unwrap()).unwrap();
+            match op_webhook_out {
+                OperationalWebhookTest::FailureCountThreshold(_) => {
+                    panic!("Threshold event sent before the threshold was reached")
+                }
+                OperationalWebhookTest::MessageAttemptExhausted(_) => break,
+                _ => {}
+            }
+        }
+    }
+
+    let event = loop {
+        let op_webhook_out: OperationalWebhookTest =
+            serde_json::from_value(receiver.data_recv.recv().await.unwrap()).unwrap();
+        if let OperationalWebhookTest::FailureCountThreshold(event) = op_webhook_out {
+            break event;
+        }
+    };
+
+    assert_eq!(event.app_id, regular_app.id);
+    assert_eq!(event.app_uid, regular_app.uid);
+    assert_eq!(event.endpoint_id, regular_endp.id);
+    assert_eq!(event.failure_count, 2);
+}
+
+#[tokio::test]
+async fn test_message_expired_operational_webhook() {
+    let cfg = get_default_test_config();
+    let (client_regular, client_op, org_id, _jh) = start_svix_server_with_operational_webhooks(cfg);
+
+    let op_webhook_app: ApplicationOut = client_op
+        .post(
+            "api/v1/app/",
+            ApplicationIn {
+                name: "TestOperationalWebhookApplication".to_owned(),
+                rate_limit: None,
+                uid: Some(ApplicationUid(org_id.to_string())),
+                metadata: Metadata::default(),
+            },
+            StatusCode::CREATED,
+        )
+        .await
+        .unwrap();
+
+    let mut receiver = TestReceiver::start(StatusCode::OK);
+
+    let _op_webhook_endp: EndpointOut = client_op
+        .post(
+            &format!("api/v1/app/{}/endpoint/", op_webhook_app.id),
+            EndpointIn {
+                description: "TestOperationalWebhookEndpoint".to_owned(),
+                url: Url::parse(&receiver.endpoint).unwrap(),
+                ..default_test_endpoint()
+            },
+            StatusCode::CREATED,
+        )
+        .await
+        .unwrap();
+
+    let regular_app = create_test_app(&client_regular, "TestOperationalWebhookApplicationRegular")
+        .await
+        .unwrap();
+    let regular_endp = create_test_endpoint(&client_regular, &regular_app.id, "http://junk.url")
+        .await
+        .unwrap();
+
+    let deadline = Utc::now() - chrono::Duration::seconds(1);
+    let regular_msg: MessageOut = client_regular
+        .post(
+            &format!("api/v1/app/{}/msg/", regular_app.id),
+            MessageIn {
+                deadline: Some(deadline),
+                ..message_in("sale.ending", serde_json::json!({"test": "data"})).unwrap()
+            },
+            StatusCode::ACCEPTED,
+        )
+        .await
+        .unwrap();
+
+    let event = loop {
+        let op_webhook_out: OperationalWebhookTest =
+            serde_json::from_value(receiver.data_recv.recv().await.unwrap()).unwrap();
+        match op_webhook_out {
+            OperationalWebhookTest::MessageExpired(event) => break event,
+            OperationalWebhookTest::MessageAttemptFailing(_)
+            | OperationalWebhookTest::MessageAttemptExhausted(_) => {
+                panic!("Delivery was attempted after the deadline")
+            }
+            _ => {}
+        }
+    };
+
+    assert_eq!(event.app_id, regular_app.id);
+    assert_eq!(event.app_uid, regular_app.uid);
+    assert_eq!(event.msg_id, regular_msg.id);
+    assert_eq!(event.endpoint_id, regular_endp.id);
+    assert_eq!(
+        event.deadline.timestamp_millis(),
+        deadline.timestamp_millis()
+    );
+}
+
+#[tokio::test]
+async fn test_endpoint_circuit_operational_webhooks() {
+    let mut cfg = get_default_test_config();
+    cfg.retry_schedule = (0..10).map(|_| Duration::from_millis(500)).collect();
+    cfg.endpoint_circuit_breaker_threshold = 2;
+    cfg.endpoint_circuit_breaker_cooldown = Duration::from_secs(1);
+    let (client_regular, client_op, org_id, _jh) = start_svix_server_with_operational_webhooks(cfg);
+
+    let op_webhook_app: ApplicationOut = client_op
+        .post(
+            "api/v1/app/",
+            ApplicationIn {
+                name: "TestOperationalWebhookApplication".to_owned(),
+                rate_limit: None,
+                uid: Some(ApplicationUid(org_id.to_string())),
+                metadata: Metadata::default(),
+            },
+            StatusCode::CREATED,
+        )
+        .await
+        .unwrap();
+
+    let mut receiver = TestReceiver::start(StatusCode::OK);
+
+    let _op_webhook_endp: EndpointOut = client_op
+        .post(
+            &format!("api/v1/app/{}/endpoint/", op_webhook_app.id),
+            EndpointIn {
+                description: "TestOperationalWebhookEndpoint".to_owned(),
+                url: Url::parse(&receiver.endpoint).unwrap(),
+                ..default_test_endpoint()
+            },
+            StatusCode::CREATED,
+        )
+        .await
+        .unwrap();
+
+    let regular_app = create_test_app(&client_regular, "TestOperationalWebhookApplicationRegular")
+        .await
+        .unwrap();
+    let regular_receiver = TestReceiver::start(StatusCode::INTERNAL_SERVER_ERROR);
+    let regular_endp =
+        create_test_endpoint(&client_regular, &regular_app.id, &regular_receiver.endpoint)
+            .await
+            .unwrap();
+
+    let msg = create_test_message(
+        &client_regular,
+        &regular_app.id,
+        serde_json::json!({"test": "data"}),
+    )
+    .await
+    .unwrap();
+
+    let opened = loop {
+        let op_webhook_out: OperationalWebhookTest =
+            serde_json::from_value(receiver.data_recv.recv().await.unwrap()).unwrap();
+        match op_webhook_out {
+            OperationalWebhookTest::EndpointCircuitOpen(event) => break event,
+            OperationalWebhookTest::EndpointCircuitClosed(_) => {
+                panic!("The circuit closed before it opened")
+            }
+            _ => {}
+        }
+    };
+    assert_eq!(opened.app_id, regular_app.id);
+    assert_eq!(opened.app_uid, regular_app.uid);
+    assert_eq!(opened.endpoint_id, regular_endp.id);
+    assert!(opened.open_until.unwrap() > Utc::now() - chrono::Duration::seconds(1));
+    assert_eq!(opened.consecutive_failures, 2);
+
+    // The first retry after the cooldown closes the circuit again
+    regular_receiver.set_response_status_code(StatusCode::OK);
+    let closed = loop {
+        let op_webhook_out: OperationalWebhookTest =
+            serde_json::from_value(receiver.data_recv.recv().await.unwrap()).unwrap();
+        if let OperationalWebhookTest::EndpointCircuitClosed(event) = op_webhook_out {
+            break event;
+        }
+    };
+    assert_eq!(closed.endpoint_id, regular_endp.id);
+    assert_eq!(closed.open_until, None);
+    assert!(closed.consecutive_failures >= 2);
+
+    // Deliveries put off while the circuit was open aren't recorded as attempts, so every attempt
+    // made was actually sent to the endpoint
+    let attempts: ListResponse<MessageAttemptOut> = client_regular
+        .get(
+            &format!("api/v1/app/{}/attempt/msg/{}/", regular_app.id, msg.id),
+            StatusCode::OK,
+        )
+        .await
+        .unwrap();
+    assert!(attempts
+        .data
+        .iter()
+        .all(|attempt| attempt.response_status_code != 0));
+}
+
+#[tokio::test]
+async fn test_batched_operational_webhooks() {
+    let mut cfg = get_default_test_config();
+    cfg.op_webhooks_batch_interval_ms = 1000;
+    let (client_regular, client_op, org_id, _jh) = start_svix_server_with_operational_webhooks(cfg);
+
+    let op_webhook_app: ApplicationOut = client_op
+        .post(
+            "api/v1/app/",
+            ApplicationIn {
+                name: "TestOperationalWebhookApplication".to_owned(),
+                rate_limit: None,
+                uid: Some(ApplicationUid(org_id.to_string())),
+                metadata: Metadata::default(),
+            },
+            StatusCode::CREATED,
+        )
+        .await
+        .unwrap();
+
+    let mut receiver = TestReceiver::start(StatusCode::OK);
+
+    let _op_webhook_endp: EndpointOut = client_op
+        .post(
+            &format!("api/v1/app/{}/endpoint/", op_webhook_app.id),
+            EndpointIn {
+                description: "TestOperationalWebhookEndpoint".to_owned(),
+                url: Url::parse(&receiver.endpoint).unwrap(),
+                ..default_test_endpoint()
+            },
+            StatusCode::CREATED,
+        )
+        .await
+        .unwrap();
+
+    let regular_app = create_test_app(&client_regular, "TestOperationalWebhookApplicationRegular")
+        .await
+        .unwrap();
+
+    // Receives a batch of `endpoint.created` events, returning their endpoint IDs
+    async fn recv_batch(receiver: &mut TestReceiver) -> Vec<EndpointId> {
+        let op_webhook_out = receiver.data_recv.recv().await.unwrap();
+        assert_eq!(op_webhook_out["type"], "endpoint.created");
+        let events: Vec<EndpointEvent> =
+            serde_json::from_value(op_webhook_out["data"].clone()).unwrap();
+        events.into_iter().map(|e| e.endpoint_id).collect()
+    }
+
+    // An event alone in its batch is still sent in an array
+    let endp = create_test_endpoint(&client_regular, &regular_app.id, "http://junk.url")
+        .await
+        .unwrap();
+    assert_eq!(recv_batch(&mut receiver).await, vec![endp.id]);
+
+    // Events within the same interval are sent together
+    let mut endp_ids = Vec::new();
+    for _ in 0..3 {
+        let endp = create_test_endpoint(&client_regular, &regular_app.id, "http://junk.url")
+            .await
+            .unwrap();
+        endp_ids.push(endp.id);
+    }
+    assert_eq!(recv_batch(&mut receiver).await, endp_ids);
+
+    // The end of the interval sends what was collected, and the next event starts a new batch
+    let endp = create_test_endpoint(&client_regular, &regular_app.id, "http://junk.url")
+        .await
+        .unwrap();
+    tokio::time::sleep(Duration::from_millis(1500)).await;
+    let next = create_test_endpoint(&client_regular, &regular_app.id, "http://junk.url")
+        .await
+        .unwrap();
+    assert_eq!(recv_batch(&mut receiver).await, vec![endp.id]);
+    assert_eq!(recv_batch(&mut receiver).await, vec![next.id]);
+}
+
 #[tokio::test]
 async fn test_operational_webhooks_event_types_exist() {
     let cfg = get_default_test_config();
@@ -431,9 +915,13 @@ async fn test_operational_webhooks_event_types_exist() {
     for et in &[
         "message.attempt.failing",
         "message.attempt.exhausted",
+        "message.expired",
         "endpoint.created",
         "endpoint.deleted",
         "endpoint.disabled",
+        "endpoint.failure_count_threshold",
+        "endpoint.circuit_open",
+        "endpoint.circuit_closed",
         "endpoint.created",
         "endpoint.updated",
     ] {
@@ -0,0 +1,194 @@
+// SPDX-FileCopyrightText: © 2022 Svix Authors
+// SPDX-License-Identifier: MIT
+
+use chrono::{Duration, Utc};
+use reqwest::StatusCode;
+use sea_orm::{ActiveModelTrait, ActiveValue::Set, EntityTrait};
+use serde::de::IgnoredAny;
+use svix_server::{
+    core::{
+        org_quota,
+        types::{BaseId, MessageUid, OrganizationId},
+    },
+    db::models::organizationquota,
+    v1::endpoints::message::{MessageIn, MessageOut},
+};
+
+use crate::utils::{
+    common_calls::{create_test_app, message_in},
+    get_default_test_config, start_svix_server_with_cfg_and_org_id,
+};
+
+#[tokio::test]
+async fn test_org_quota_exceeded() {
+    let cfg = get_default_test_config();
+    let org_id = OrganizationId::new(None, None);
+    let (client, _jh) = start_svix_server_with_cfg_and_org_id(&cfg, org_id.clone()).await;
+    let pool = svix_server::db::init_db(&cfg).await;
+
+    organizationquota::ActiveModel::new(
+        org_id.clone(),
+        2,
+        (Utc::now() + Duration::days(10)).into(),
+    )
+    .insert(&pool)
+    .await
+    .unwrap();
+
+    let app_id = create_test_app(&client, "v1OrgQuotaTestApp")
+        .await
+        .unwrap()
+        .id;
+
+    for _ in 0..2 {
+        let _: MessageOut = client
+            .post(
+                &format!("api/v1/app/{app_id}/msg/"),
+                message_in(&app_id, serde_json::json!({"test": "value"})).unwrap(),
+                StatusCode::ACCEPTED,
+            )
+            .await
+            .unwrap();
+    }
+
+    let (_, headers): (IgnoredAny, _) = client
+        .post_with_response_headers(
+            &format!("api/v1/app/{app_id}/msg/"),
+            message_in(&app_id, serde_json::json!({"test": "value"})).unwrap(),
+            StatusCode::TOO_MANY_REQUESTS,
+        )
+        .await
+        .unwrap();
+    let retry_after: i64 = headers["retry-after"].to_str().unwrap().parse().unwrap();
+    let ten_days = Duration::days(10).num_seconds();
+    assert!((ten_days - 60..=ten_days).contains(&retry_after));
+
+    let quota = organizationquota::Entity::find_by_id(org_id)
+        .one(&pool)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(quota.messages_this_month, 2);
+}
+
+#[tokio::test]
+async fn test_org_quota_given_back_on_failure() {
+    let cfg = get_default_test_config();
+    let org_id = OrganizationId::new(None, None);
+    let (client, _jh) = start_svix_server_with_cfg_and_org_id(&cfg, org_id.clone()).await;
+    let pool = svix_server::db::init_db(&cfg).await;
+
+    organizationquota::ActiveModel::new(
+        org_id.clone(),
+        2,
+        (Utc::now() + Duration::days(10)).into(),
+    )
+    .insert(&pool)
+    .await
+    .unwrap();
+
+    let app_id = create_test_app(&client, "v1OrgQuotaFailureTestApp")
+        .await
+        .unwrap()
+        .id;
+
+    let msg_in = MessageIn {
+        uid: Some(MessageUid("quota-test".to_owned())),
+        ..message_in(&app_id, serde_json::json!({"test": "value"})).unwrap()
+    };
+    let _: MessageOut = client
+        .post(
+            &format!("api/v1/app/{app_id}/msg/"),
+            msg_in.clone(),
+            StatusCode::ACCEPTED,
+        )
+        .await
+        .unwrap();
+
+    // The duplicate isn't created, so it doesn't use up the quota
+    let _: IgnoredAny = client
+        .post(
+            &format!("api/v1/app/{app_id}/msg/"),
+            msg_in,
+            StatusCode::CONFLICT,
+        )
+        .await
+        .unwrap();
+
+    let quota = organizationquota::Entity::find_by_id(org_id)
+        .one(&pool)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(quota.messages_this_month, 1);
+
+    let _: MessageOut = client
+        .post(
+            &format!("api/v1/app/{app_id}/msg/"),
+            message_in(&app_id, serde_json::json!({"test": "value"})).unwrap(),
+            StatusCode::ACCEPTED,
+        )
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn test_org_quota_reset() {
+    let cfg = get_default_test_config();
+    let org_id = OrganizationId::new(None, None);
+    let (client, _jh) = start_svix_server_with_cfg_and_org_id(&cfg, org_id.clone()).await;
+    let pool = svix_server::db::init_db(&cfg).await;
+
+    // Used up, but due to be reset
+    let reset_at = Utc::now() - Duration::hours(1);
+    let mut quota = organizationquota::ActiveModel::new(org_id.clone(), 1, reset_at.into());
+    quota.messages_this_month = Set(1);
+    quota.insert(&pool).await.unwrap();
+
+    let app_id = create_test_app(&client, "v1OrgQuotaResetTestApp")
+        .await
+        .unwrap()
+        .id;
+
+    // Reset when the next message is created, even if the background task hasn't got to it yet
+    let _: MessageOut = client
+        .post(
+            &format!("api/v1/app/{app_id}/msg/"),
+            message_in(&app_id, serde_json::json!({"test": "value"})).unwrap(),
+            StatusCode::ACCEPTED,
+        )
+        .await
+        .unwrap();
+    let _: IgnoredAny = client
+        .post(
+            &format!("api/v1/app/{app_id}/msg/"),
+            message_in(&app_id, serde_json::json!({"test": "value"})).unwrap(),
+            StatusCode::TOO_MANY_REQUESTS,
+        )
+        .await
+        .unwrap();
+
+    let quota = organizationquota::Entity::find_by_id(org_id.clone())
+        .one(&pool)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(quota.messages_this_month, 1);
+    assert!(quota.reset_at > Utc::now() + Duration::days(27));
+
+    // And by the background task otherwise
+    let other_org_id = OrganizationId::new(None, None);
+    let mut quota = organizationquota::ActiveModel::new(other_org_id.clone(), 1, reset_at.into());
+    quota.messages_this_month = Set(1);
+    quota.insert(&pool).await.unwrap();
+
+    org_quota::reset_expired_quotas(&pool).await.unwrap();
+
+    let quota = organizationquota::Entity::find_by_id(other_org_id)
+        .one(&pool)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(quota.messages_this_month, 0);
+    assert!(quota.reset_at > Utc::now());
+}
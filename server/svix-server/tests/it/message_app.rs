@@ -10,10 +10,10 @@ use svix_server::{
     cfg::CacheBackend,
     core::{
         cache::{self, CacheBehavior},
-        message_app::AppEndpointKey,
+        message_app::{AppEndpointKey, CreateMessageApp},
         types::{BaseId, OrganizationId},
     },
-    redis::RedisManager,
+    redis::{RedisManager, RedisTlsConfig},
 };
 
 use crate::utils::{
@@ -67,7 +67,11 @@ async fn test_app_deletion() {
     let cache = match cfg.cache_backend() {
         CacheBackend::None => cache::none::new(),
         CacheBackend::Redis(_) | CacheBackend::RedisCluster(_) => {
-            let mgr = RedisManager::from_cache_backend(&cfg.cache_backend()).await;
+            let mgr = RedisManager::from_cache_backend(
+                &cfg.cache_backend(),
+                &RedisTlsConfig::from_configuration(&cfg),
+            )
+            .await;
             cache::redis::new(mgr)
         }
 
@@ -98,6 +102,73 @@ async fn test_app_deletion() {
     );
 }
 
+/// Ensures that an unreadable cached [`CreateMessageApp`], such as one cached by a version with a
+/// different format, is replaced with the one from the DB instead of failing dispatch
+#[tokio::test]
+async fn test_unreadable_cached_app() {
+    dotenvy::dotenv().ok();
+    let cfg = svix_server::cfg::load().expect("Error loading Configuration");
+    let org_id = OrganizationId::new(None, None);
+    let (client, _jh) =
+        start_svix_server_with_cfg_and_org_id(&get_default_test_config(), org_id.clone()).await;
+
+    // Cannot run test using an in-memory cache as we can't write to the server's cache from
+    // within the test. See `test_app_deletion`. Without a cache there's nothing to test.
+    if matches!(
+        cfg.cache_backend(),
+        CacheBackend::Memory | CacheBackend::None
+    ) {
+        return;
+    }
+
+    let mut test_receiver = TestReceiver::start(axum::http::StatusCode::OK);
+
+    let app_id = create_test_app(&client, "TestUnreadableCachedApp")
+        .await
+        .unwrap()
+        .id;
+    let _ = create_test_endpoint(&client, &app_id, &test_receiver.endpoint)
+        .await
+        .unwrap();
+
+    let cache = match cfg.cache_backend() {
+        CacheBackend::Redis(_) | CacheBackend::RedisCluster(_) => {
+            let mgr = RedisManager::from_cache_backend(
+                &cfg.cache_backend(),
+                &RedisTlsConfig::from_configuration(&cfg),
+            )
+            .await;
+            cache::redis::new(mgr)
+        }
+
+        // See the above check.
+        CacheBackend::Memory | CacheBackend::None => unreachable!(),
+    };
+
+    let key = AppEndpointKey::new(&org_id, &app_id);
+    cache
+        .set_raw(
+            key.as_ref().as_bytes(),
+            br#"{"id": "not a CreateMessageApp""#,
+            Some(Duration::from_secs(30)),
+        )
+        .await
+        .unwrap();
+
+    let payload = serde_json::json!({"test": "value"});
+    create_test_message(&client, &app_id, payload.clone())
+        .await
+        .unwrap();
+
+    assert_eq!(
+        tokio::time::timeout(Duration::from_secs(5), test_receiver.data_recv.recv()).await,
+        Ok(Some(payload))
+    );
+
+    // The unreadable value was replaced with the one from the DB
+    assert!(cache.get::<CreateMessageApp>(&key).await.unwrap().is_some());
+}
+
 #[tokio::test]
 async fn test_endp_deletion() {
     dotenvy::dotenv().ok();
@@ -147,7 +218,11 @@ async fn test_endp_deletion() {
     let cache = match cfg.cache_backend() {
         CacheBackend::None => cache::none::new(),
         CacheBackend::Redis(_) | CacheBackend::RedisCluster(_) => {
-            let mgr = RedisManager::from_cache_backend(&cfg.cache_backend()).await;
+            let mgr = RedisManager::from_cache_backend(
+                &cfg.cache_backend(),
+                &RedisTlsConfig::from_configuration(&cfg),
+            )
+            .await;
             cache::redis::new(mgr)
         }
 
@@ -13,13 +13,18 @@ use svix_server::{
     queue::{
         new_pair, MessageTask, QueueTask, TaskQueueConsumer, TaskQueueDelivery, TaskQueueProducer,
     },
-    redis::RedisManager,
+    redis::{RedisManager, RedisTlsConfig},
 };
 use tokio::time::timeout;
 
 // TODO: Don't copy this from the Redis queue test directly, place the fn somewhere both can access
 async fn get_pool(cfg: &Configuration) -> RedisManager {
-    RedisManager::from_queue_backend(&cfg.queue_backend(), cfg.redis_pool_max_size).await
+    RedisManager::from_queue_backend(
+        &cfg.queue_backend(),
+        cfg.redis_pool_max_size,
+        &RedisTlsConfig::from_configuration(&cfg),
+    )
+    .await
 }
 
 fn task_queue_delivery_to_u16(tqd: &TaskQueueDelivery) -> u16 {
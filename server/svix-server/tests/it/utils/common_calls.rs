@@ -67,6 +67,13 @@ pub fn default_test_endpoint() -> EndpointIn {
         channels: Default::default(),
         key: Default::default(),
         metadata: Default::default(),
+        group_id: Default::default(),
+        response_webhook_event_type: Default::default(),
+        min_payload_version: Default::default(),
+        http_method: Default::default(),
+        content_type: Default::default(),
+        custom_data: Default::default(),
+        custom_signature_prefix: Default::default(),
     }
 }
 
@@ -123,6 +130,11 @@ pub fn message_in<T: Serialize>(event_type: &str, payload: T) -> Result<MessageI
         payload_retention_period: 5,
         channels: None,
         uid: None,
+        tags: None,
+        deadline: None,
+        signed_metadata: None,
+        payload_version: None,
+        endpoint_metadata_selector: None,
     })
 }
 
@@ -167,6 +179,11 @@ pub async fn create_test_msg_with(
                 payload_retention_period: 5,
                 channels,
                 uid: None,
+                tags: None,
+                deadline: None,
+                signed_metadata: None,
+                payload_version: None,
+                endpoint_metadata_selector: None,
             },
             StatusCode::ACCEPTED,
         )
@@ -169,11 +169,27 @@ impl TestClient {
         idempotency_key: &str,
         input: I,
         expected_code: StatusCode,
+    ) -> Result<O> {
+        self.post_with_header(
+            endpoint,
+            ("idempotency-key", idempotency_key),
+            input,
+            expected_code,
+        )
+        .await
+    }
+
+    pub async fn post_with_header<I: Serialize, O: DeserializeOwned>(
+        &self,
+        endpoint: &str,
+        (header_name, header_value): (&str, &str),
+        input: I,
+        expected_code: StatusCode,
     ) -> Result<O> {
         let mut req = self.client.post(self.build_uri(endpoint));
         req = self
             .add_headers(req)
-            .header("idempotency-key", idempotency_key)
+            .header(header_name, header_value)
             .json(&input);
 
         let resp = req.send().await.context("error sending request")?;
@@ -189,6 +205,34 @@ impl TestClient {
         resp.json().await.context("error receiving/paring response")
     }
 
+    /// Like [`Self::post`], but also returns the response's headers
+    pub async fn post_with_response_headers<I: Serialize, O: DeserializeOwned>(
+        &self,
+        endpoint: &str,
+        input: I,
+        expected_code: StatusCode,
+    ) -> Result<(O, reqwest::header::HeaderMap)> {
+        let mut req = self.client.post(self.build_uri(endpoint));
+        req = self.add_headers(req).json(&input);
+
+        let resp = req.send().await.context("error sending request")?;
+
+        if resp.status() != expected_code {
+            anyhow::bail!(
+                "assertion failed: expected status {}, actual status {}",
+                expected_code,
+                resp.status()
+            );
+        }
+
+        let headers = resp.headers().clone();
+        let body = resp
+            .json()
+            .await
+            .context("error receiving/parsing response")?;
+        Ok((body, headers))
+    }
+
     pub async fn put<I: Serialize, O: DeserializeOwned>(
         &self,
         endpoint: &str,
@@ -330,6 +374,21 @@ pub async fn start_svix_server_with_cfg(
 pub async fn start_svix_server_with_cfg_and_org_id(
     cfg: &ConfigurationInner,
     org_id: OrganizationId,
+) -> (TestClient, tokio::task::JoinHandle<()>) {
+    start_svix_server_with_cfg_and_prefix(
+        cfg,
+        org_id,
+        svix_ksuid::Ksuid::new(None, None).to_string(),
+    )
+    .await
+}
+
+/// Starts the server with the given queue prefix, such that tests can send tasks to its queue
+/// directly
+pub async fn start_svix_server_with_cfg_and_prefix(
+    cfg: &ConfigurationInner,
+    org_id: OrganizationId,
+    prefix: String,
 ) -> (TestClient, tokio::task::JoinHandle<()>) {
     let (tracing_subscriber, _guard) = setup_tracing(cfg, /* for_test = */ true);
 
@@ -340,12 +399,8 @@ pub async fn start_svix_server_with_cfg_and_org_id(
     let base_uri = format!("http://{}", listener.local_addr().unwrap());
 
     let jh = tokio::spawn(
-        svix_server::run_with_prefix(
-            Some(svix_ksuid::Ksuid::new(None, None).to_string()),
-            cfg,
-            Some(listener),
-        )
-        .with_subscriber(tracing_subscriber),
+        svix_server::run_with_prefix(Some(prefix), cfg, Some(listener))
+            .with_subscriber(tracing_subscriber),
     );
 
     (TestClient::new(base_uri, &token), jh)
@@ -4,15 +4,29 @@ use std::{net::TcpListener, sync::Arc, time::Duration};
 
 use axum::extract::State;
 use http::StatusCode;
-use svix_server::v1::{
-    endpoints::{attempt::MessageAttemptOut, endpoint::EndpointOut},
-    utils::ListResponse,
+use svix_server::{
+    cfg::QueueType,
+    core::{
+        cache::memory,
+        operational_webhooks::{OperationalWebhookMode, OperationalWebhookSenderInner},
+    },
+    v1::{
+        endpoints::{
+            attempt::MessageAttemptOut,
+            endpoint::{EndpointFailureStatusOut, EndpointIn, EndpointOut, EndpointStatsOut},
+        },
+        utils::ListResponse,
+    },
+    worker::queue_handler,
 };
-use tokio::sync::Mutex;
+use tokio::sync::{broadcast, Mutex};
 
 use crate::utils::{
-    common_calls::{create_test_app, create_test_endpoint, create_test_message},
+    common_calls::{
+        create_test_app, create_test_endpoint, create_test_message, endpoint_in, post_endpoint,
+    },
     get_default_test_config, run_with_retries, start_svix_server, start_svix_server_with_cfg,
+    TestReceiver,
 };
 
 /// Runs a full Axum server with two endpoints. The first endpoint redirects to the second endpoint
@@ -343,3 +357,233 @@ async fn test_endpoint_disable_on_sporadic_failure() {
         receiver.jh.abort();
     }
 }
+
+/// Endpoints are listed in their application's failure status once a message couldn't be delivered
+/// to them, until they successfully receive one again.
+#[tokio::test]
+async fn test_list_failure_status() {
+    let mut cfg = get_default_test_config();
+    cfg.retry_schedule = vec![];
+
+    let (client, _jh) = start_svix_server_with_cfg(&cfg).await;
+    let app_id = create_test_app(&client, "app").await.unwrap().id;
+
+    let failing = TestReceiver::start(StatusCode::INTERNAL_SERVER_ERROR);
+    let failing_id = create_test_endpoint(&client, &app_id, &failing.endpoint)
+        .await
+        .unwrap()
+        .id;
+    let healthy = TestReceiver::start(StatusCode::OK);
+    create_test_endpoint(&client, &app_id, &healthy.endpoint)
+        .await
+        .unwrap();
+
+    let before = chrono::Utc::now();
+    for _ in 0..2 {
+        create_test_message(&client, &app_id, serde_json::json!({}))
+            .await
+            .unwrap();
+    }
+
+    // Nothing is tracked without a cache
+    if matches!(cfg.cache_type, svix_server::cfg::CacheType::None) {
+        tokio::time::sleep(Duration::from_millis(500)).await;
+        let status: Vec<EndpointFailureStatusOut> = client
+            .get(
+                &format!("api/v1/app/{app_id}/failure-status"),
+                StatusCode::OK,
+            )
+            .await
+            .unwrap();
+        assert!(status.is_empty());
+        return;
+    }
+
+    run_with_retries(|| async {
+        let status: Vec<EndpointFailureStatusOut> = client
+            .get(
+                &format!("api/v1/app/{app_id}/failure-status"),
+                StatusCode::OK,
+            )
+            .await
+            .unwrap();
+        let [endp_status] = &status[..] else {
+            anyhow::bail!("Expected a single failing endpoint, got {}", status.len());
+        };
+        if endp_status.failure_count != 2 {
+            anyhow::bail!("Failure count is {}", endp_status.failure_count);
+        }
+        assert_eq!(endp_status.endpoint_id, failing_id);
+        assert!(endp_status.first_failure_at >= before);
+        Ok(())
+    })
+    .await
+    .unwrap();
+
+    // The endpoint is no longer failing once it receives a message
+    failing.set_response_status_code(StatusCode::OK);
+    create_test_message(&client, &app_id, serde_json::json!({}))
+        .await
+        .unwrap();
+
+    run_with_retries(|| async {
+        let status: Vec<EndpointFailureStatusOut> = client
+            .get(
+                &format!("api/v1/app/{app_id}/failure-status"),
+                StatusCode::OK,
+            )
+            .await
+            .unwrap();
+        if !status.is_empty() {
+            anyhow::bail!("Endpoint is still failing");
+        }
+        Ok(())
+    })
+    .await
+    .unwrap();
+
+    failing.jh.abort();
+    healthy.jh.abort();
+}
+
+#[tokio::test]
+async fn test_endpoint_last_delivery_success_at() {
+    let cfg = get_default_test_config();
+    let (client, _jh) = start_svix_server_with_cfg(&cfg).await;
+    let app_id = create_test_app(&client, "app").await.unwrap().id;
+
+    let receiver = TestReceiver::start(StatusCode::INTERNAL_SERVER_ERROR);
+    let endp_id = create_test_endpoint(&client, &app_id, &receiver.endpoint)
+        .await
+        .unwrap()
+        .id;
+    let stats_url = format!("api/v1/app/{app_id}/endpoint/{endp_id}/stats");
+
+    // Failed deliveries don't count
+    create_test_message(&client, &app_id, serde_json::json!({}))
+        .await
+        .unwrap();
+    tokio::time::sleep(Duration::from_millis(500)).await;
+    let stats: EndpointStatsOut = client.get(&stats_url, StatusCode::OK).await.unwrap();
+    assert_eq!(stats.last_delivery_success_at, None);
+
+    // Nothing is remembered without a cache
+    if matches!(cfg.cache_type, svix_server::cfg::CacheType::None) {
+        return;
+    }
+
+    receiver.set_response_status_code(StatusCode::OK);
+    let before = chrono::Utc::now();
+    create_test_message(&client, &app_id, serde_json::json!({}))
+        .await
+        .unwrap();
+
+    run_with_retries(|| async {
+        let stats: EndpointStatsOut = client.get(&stats_url, StatusCode::OK).await.unwrap();
+        let Some(last_delivery_success_at) = stats.last_delivery_success_at else {
+            anyhow::bail!("No successful delivery recorded yet");
+        };
+        assert!(last_delivery_success_at >= before);
+        Ok(())
+    })
+    .await
+    .unwrap();
+
+    receiver.jh.abort();
+}
+
+/// Messages over an endpoint's rate limit aren't sent, but wait until they can be
+#[tokio::test]
+async fn test_endpoint_rate_limit() {
+    let cfg = get_default_test_config();
+    if matches!(cfg.cache_type, svix_server::cfg::CacheType::None) {
+        return;
+    }
+
+    let (client, _jh) = start_svix_server_with_cfg(&cfg).await;
+    let app_id = create_test_app(&client, "app").await.unwrap().id;
+
+    let mut receiver = TestReceiver::start(StatusCode::OK);
+    let endp_id = post_endpoint(
+        &client,
+        &app_id,
+        EndpointIn {
+            rate_limit: Some(1),
+            ..endpoint_in(&receiver.endpoint)
+        },
+    )
+    .await
+    .unwrap()
+    .id;
+
+    for _ in 0..3 {
+        create_test_message(&client, &app_id, serde_json::json!({}))
+            .await
+            .unwrap();
+    }
+
+    run_with_retries(|| async {
+        let stats: EndpointStatsOut = client
+            .get(
+                &format!("api/v1/app/{app_id}/endpoint/{endp_id}/stats"),
+                StatusCode::OK,
+            )
+            .await
+            .unwrap();
+        if stats.success != 1 || stats.rate_limited != 2 {
+            anyhow::bail!(
+                "{} delivered and {} rate limited",
+                stats.success,
+                stats.rate_limited
+            );
+        }
+        Ok(())
+    })
+    .await
+    .unwrap();
+
+    receiver.data_recv.recv().await.unwrap();
+    assert!(receiver.data_recv.try_recv().is_err());
+
+    receiver.jh.abort();
+}
+
+#[tokio::test]
+async fn test_queue_handler_shutdown() {
+    let mut cfg = get_default_test_config();
+    cfg.queue_type = QueueType::Memory;
+    let cfg = Arc::new(cfg);
+
+    let db = svix_server::db::init_db_pool(&cfg).await;
+    let (queue_tx, queue_rx) = svix_server::queue::new_pair(&cfg, None).await;
+    let op_webhook_sender = OperationalWebhookSenderInner::new(
+        cfg.jwt_signing_config.clone(),
+        cfg.operational_webhook_address.clone(),
+        OperationalWebhookMode::from_batch_interval_ms(cfg.op_webhooks_batch_interval_ms),
+    );
+    let (shutdown_tx, shutdown_rx) = broadcast::channel(1);
+
+    let handler = tokio::spawn({
+        let cfg = cfg.clone();
+        async move {
+            queue_handler(
+                &cfg,
+                memory::new(),
+                db,
+                queue_tx,
+                queue_rx,
+                op_webhook_sender,
+                shutdown_rx,
+            )
+            .await
+        }
+    });
+
+    // The handler is waiting on an empty queue, which shouldn't hold up shutting down
+    shutdown_tx.send(()).unwrap();
+    tokio::time::timeout(Duration::from_secs(5), handler)
+        .await
+        .expect("queue handler didn't shut down")
+        .unwrap()
+        .unwrap();
+}